@@ -0,0 +1,46 @@
+/*
+ * C interface to the Graph Library serving path.  Build the library with
+ *
+ *     cargo build --release --no-default-features --features ffi
+ *
+ * and link against target/release/libgraph_library.so.  See src/ffi.rs for ownership and
+ * threading rules.
+ */
+#ifndef GRAPH_LIBRARY_H
+#define GRAPH_LIBRARY_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define GL_DISTANCE_COSINE 0
+#define GL_DISTANCE_DOT 1
+#define GL_DISTANCE_EUCLIDEAN 2
+
+typedef struct GlEmbeddings GlEmbeddings;
+typedef struct Ann GlAnn;
+
+/* Last error raised on the calling thread, or NULL */
+const char *gl_last_error(void);
+
+GlEmbeddings *gl_embeddings_load(const char *path, int distance);
+void gl_embeddings_free(GlEmbeddings *embeddings);
+size_t gl_embeddings_len(const GlEmbeddings *embeddings);
+size_t gl_embeddings_dims(const GlEmbeddings *embeddings);
+int64_t gl_embeddings_node_id(const GlEmbeddings *embeddings, const char *node_type, const char *name);
+int gl_embeddings_get(const GlEmbeddings *embeddings, uint64_t node_id, float *out, size_t out_len);
+
+GlAnn *gl_ann_load(const char *path);
+void gl_ann_free(GlAnn *ann);
+int64_t gl_ann_predict(const GlAnn *ann, const GlEmbeddings *embeddings,
+                       const float *query, size_t query_len, size_t k, size_t min_search_nodes,
+                       uint64_t *out_ids, float *out_distances);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
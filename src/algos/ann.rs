@@ -1,20 +1,187 @@
 use std::cmp::{Ordering,Eq};
 use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader,BufWriter,Read,Write,Error as IOError,ErrorKind,Result as IOResult};
+use std::sync::{Arc,RwLock};
 
 use rand::prelude::*;
-use rand_xorshift::XorShiftRng;
 use rayon::prelude::*;
 use float_ord::FloatOrd;
+use hashbrown::HashSet;
 
 use crate::graph::NodeID;
-use crate::embeddings::{EmbeddingStore,Entity};
+use crate::embeddings::{EmbeddingStore,StoreVersion};
+use crate::vocab::Vocab;
+use crate::distance::Distance;
 use crate::algos::graph_ann::{NodeDistance,TopK};
+use crate::algos::query_cache::QueryCache;
+use crate::resources::{ResourceTracker,ResourceReport};
+use crate::runtime::Runtime;
+use crate::error::{GraphLibError,check_dims};
+use crate::algos::utils::RngKind;
 
 #[inline(always)]
 fn dot(x: &[f32], y: &[f32]) -> f32 {
     x.iter().zip(y.iter()).map(|(xi, yi)| xi * yi).sum()
 }
 
+/// Number of lanes to accumulate in parallel, allowing the compiler to vectorize the dot product.
+const LANES: usize = 8;
+
+/// Identifies the binary Ann format
+const MAGIC: u64 = 0x414e_4e5f_5452_4545;
+
+/// Binary format version, bumped on incompatible changes
+const VERSION: u64 = 1;
+
+/// Leaves larger than this are split into chunks of this size and scored in parallel; smaller
+/// leaves aren't worth the dispatch overhead.
+const LEAF_CHUNK_SIZE: usize = 512;
+
+#[inline]
+fn dot_lanes(x: &[f32], y: &[f32]) -> f32 {
+    let mut acc = [0f32; LANES];
+    let xc = x.chunks_exact(LANES);
+    let yc = y.chunks_exact(LANES);
+    let tail = dot(xc.remainder(), yc.remainder());
+    xc.zip(yc).for_each(|(xi, yi)| {
+        for l in 0..LANES {
+            acc[l] += xi[l] * yi[l];
+        }
+    });
+    acc.iter().sum::<f32>() + tail
+}
+
+/// Int8 copy of an EmbeddingStore using symmetric, per embedding scales.  Leaf scanning is
+/// bound by memory bandwidth, so scanning a quarter of the bytes is roughly that much faster at
+/// the cost of some precision.
+struct QuantizedStore {
+    dims: usize,
+    values: Vec<i8>,
+    scales: Vec<f32>,
+
+    /// Version of the store the values were quantized from; other versions are ignored
+    version: StoreVersion
+}
+
+impl QuantizedStore {
+    fn new(es: &EmbeddingStore) -> Self {
+        let version = es.version();
+        let mut values = vec![0i8; es.len() * es.dims()];
+        let scales = values.par_chunks_mut(es.dims().max(1)).enumerate().map(|(node_id, out)| {
+            quantize(es.get_embedding(node_id), out)
+        }).collect();
+        QuantizedStore { dims: es.dims(), values, scales, version }
+    }
+
+    fn len(&self) -> usize {
+        self.scales.len()
+    }
+
+    fn memory_bytes(&self) -> usize {
+        self.values.len() + self.scales.len() * std::mem::size_of::<f32>()
+    }
+
+    #[inline]
+    fn dot(&self, query: &[i8], query_scale: f32, node_id: NodeID) -> f32 {
+        let emb = &self.values[node_id * self.dims..(node_id + 1) * self.dims];
+        let d: i32 = query.iter().zip(emb.iter()).map(|(qi, ei)| *qi as i32 * *ei as i32).sum();
+        d as f32 * query_scale * self.scales[node_id]
+    }
+}
+
+/// Quantizes the vector into out, returning the scale
+fn quantize(emb: &[f32], out: &mut [i8]) -> f32 {
+    let max = emb.iter().fold(0f32, |acc, ei| acc.max(ei.abs()));
+    let scale = if max > 0. { max / 127. } else { 1. };
+    emb.iter().zip(out.iter_mut()).for_each(|(ei, oi)| {
+        *oi = (ei / scale).round().clamp(-127., 127.) as i8;
+    });
+    scale
+}
+
+/// Scores leaf nodes against a query.  For cosine, dot, and euclidean distances we only compute
+/// the dot product per node, using the query norm and the squared norms precomputed during fit.
+/// Other distances fall back to the generic distance computation.  When a quantized store is
+/// provided, the dot products are approximated with int8 embeddings.
+struct LeafScorer<'a> {
+    es: &'a EmbeddingStore,
+    query: &'a [f32],
+    query_sq_norm: f32,
+    sq_norms: Option<&'a [f32]>,
+    quantized: Option<(&'a QuantizedStore, Vec<i8>, f32)>
+}
+
+impl <'a> LeafScorer<'a> {
+    fn new(es: &'a EmbeddingStore, query: &'a [f32], sq_norms: Option<&'a [f32]>) -> Self {
+        LeafScorer { es, query, query_sq_norm: dot_lanes(query, query), sq_norms, quantized: None }
+    }
+
+    fn with_quantized(mut self, qs: &'a QuantizedStore) -> Self {
+        let mut query = vec![0i8; self.query.len()];
+        let scale = quantize(self.query, &mut query);
+        self.quantized = Some((qs, query, scale));
+        self
+    }
+
+    #[inline]
+    fn dot(&self, node_id: NodeID) -> f32 {
+        match &self.quantized {
+            Some((qs, query, scale)) => qs.dot(query, *scale, node_id),
+            None => dot_lanes(self.query, self.es.get_embedding(node_id))
+        }
+    }
+
+    #[inline]
+    fn score(&self, node_id: NodeID) -> f32 {
+        match (self.es.distance(), self.sq_norms) {
+            (Distance::Dot, _) => -self.dot(node_id),
+            (Distance::Cosine, Some(norms)) => {
+                let score = self.dot(node_id) / (self.query_sq_norm * norms[node_id]).sqrt();
+                if score.is_nan() { std::f32::INFINITY } else { -score + 1. }
+            },
+            (Distance::Euclidean, Some(norms)) => {
+                let d = self.query_sq_norm + norms[node_id] - 2. * self.dot(node_id);
+                d.max(0.).sqrt()
+            },
+            (d, _) => d.compute(self.query, self.es.get_embedding(node_id))
+        }
+    }
+
+    fn score_leaf(&self, indices: &[NodeID], out: &mut [f32]) {
+        if indices.len() > LEAF_CHUNK_SIZE {
+            indices.par_chunks(LEAF_CHUNK_SIZE).zip(out.par_chunks_mut(LEAF_CHUNK_SIZE))
+                .for_each(|(idxs, o)| self.score_chunk(idxs, o));
+        } else {
+            self.score_chunk(indices, out);
+        }
+    }
+
+    #[inline]
+    fn score_chunk(&self, indices: &[NodeID], out: &mut [f32]) {
+        indices.iter().zip(out.iter_mut()).for_each(|(node_id, o)| {
+            *o = self.score(*node_id);
+        });
+    }
+}
+
+/// Squared norms of the embeddings along with the version of the store they were computed from,
+/// so norms from another store, or from before the embeddings were written, are never used.
+/// Queries take a reference to the values rather than holding the lock while searching.
+#[derive(Default)]
+struct SqNorms {
+    version: Option<StoreVersion>,
+    values: Arc<Vec<f32>>
+}
+
+/// Computes the squared norm of each embedding
+fn compute_sq_norms(es: &EmbeddingStore) -> Vec<f32> {
+    (0..es.len()).into_par_iter().map(|node_id| {
+        let emb = es.get_embedding(node_id);
+        dot_lanes(emb, emb)
+    }).collect()
+}
+
 struct Hyperplane {
     coef: Vec<f32>,
     bias: f32
@@ -82,11 +249,11 @@ impl Eq for HpDistance {}
 
 fn tree_predict(
     tree_table: &TreeTable,
-    es: &EmbeddingStore, 
-    emb: &[f32],
+    scorer: &LeafScorer,
     k: usize,
     mut min_search_nodes: usize
-) -> Vec<(NodeID, f32)> {
+) -> TopK {
+    let emb = scorer.query;
 
     // Must explore at least K
     min_search_nodes = min_search_nodes.max(k);
@@ -113,11 +280,7 @@ fn tree_predict(
                 }
 
                 // Score the nodes
-                let qemb = Entity::Embedding(emb);
-                
-                indices.par_iter().zip(buff.par_iter_mut()).for_each(|(&node_id, b)| {
-                    *b = es.compute_distance(&Entity::Node(node_id), &qemb);
-                });
+                scorer.score_leaf(indices, &mut buff[..n_nodes]);
 
                 indices.iter().zip(buff.iter()).for_each(|(node_id, dist)| {
                     return_set.push(*node_id, *dist);
@@ -136,9 +299,7 @@ fn tree_predict(
         if visited >= min_search_nodes { break }
     }
 
-    return_set.into_sorted().into_iter().map(|nd| {
-        nd.to_tup_cloned()
-    }).collect()
+    return_set
 }
 
 
@@ -196,23 +357,171 @@ fn tree_depth(
     }
 }
 
+/// Hyperparameters for building an Ann index
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AnnBuildConfig {
-    max_nodes_per_leaf: usize,
-    test_hp_per_split: usize,
-    num_sampled_nodes_split_test: usize
+    /// Number of trees to build.  More trees improve recall at the cost of memory and latency.
+    pub n_trees: usize,
+
+    /// Nodes are split until each leaf has fewer than this many nodes
+    pub max_nodes_per_leaf: usize,
+
+    /// Number of candidate hyperplanes to test at each split.  If 0, uses a single random
+    /// projection instead.
+    pub test_hp_per_split: usize,
+
+    /// Number of nodes sampled to evaluate each candidate hyperplane
+    pub num_sampled_nodes_split_test: usize,
+
+    /// If provided, each tree's splits are learned on a different random fraction of the nodes,
+    /// in (0, 1], which makes the trees more diverse.  The remaining nodes are routed into the
+    /// leaves afterwards so every tree still indexes every node; leaves can end up larger than
+    /// max_nodes_per_leaf as a result.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub bagging: Option<f32>,
+
+    /// Random seed
+    pub seed: u64,
+
+    /// Generator the splits draw from
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rng_kind: RngKind
 }
 
+impl AnnBuildConfig {
+    pub fn new(n_trees: usize, max_nodes_per_leaf: usize, seed: u64) -> Self {
+        AnnBuildConfig {
+            n_trees,
+            max_nodes_per_leaf,
+            test_hp_per_split: 5,
+            num_sampled_nodes_split_test: 30,
+            bagging: None,
+            seed,
+            rng_kind: RngKind::XorShift
+        }
+    }
+}
+
+/// Identifies a leaf as (tree index, leaf index), where leaves are numbered densely within each
+/// tree.  Ids are stable across save and load, so they can be used as terms in an inverted index.
+pub type LeafId = (usize, usize);
+
 /** Implements an ANN based on random hyperplanes.  It offers the advantage of also
  * producing leaf index transforms, which can be suitable for indexing in traditional 
  * inverted indexs
  */
 pub struct Ann {
-    trees: Vec<TreeTable>
+    trees: Vec<TreeTable>,
+
+    /// Tree table index of each leaf, in leaf id order, for each tree
+    leaves: Vec<Vec<TreeIndex>>,
+
+    /// Squared norms of the embeddings the index was fit on, used for fast leaf scoring.
+    /// Recomputed on the next query when stale, such as after loading the index.
+    sq_norms: RwLock<SqNorms>,
+
+    /// Optional int8 embeddings for leaf scoring, along with the number of candidates per result
+    /// to rerank with the full precision embeddings
+    quantized: Option<(QuantizedStore, usize)>,
+
+    /// Resources used during the last call to fit
+    report: Option<ResourceReport>
 }
 
 impl Ann {
     pub fn new() -> Self {
-        Ann { trees: Vec::new() }
+        Ann {
+            trees: Vec::new(),
+            leaves: Vec::new(),
+            sq_norms: RwLock::new(SqNorms::default()),
+            quantized: None,
+            report: None
+        }
+    }
+
+    /// Returns the time and memory used by the last fit, if the index has been fit.
+    pub fn resource_report(&self) -> Option<&ResourceReport> {
+        self.report.as_ref()
+    }
+
+    /// Estimated bytes allocated for the index
+    pub fn memory_bytes(&self) -> usize {
+        self.trees.iter().flat_map(|t| t.iter()).map(|node| {
+            std::mem::size_of::<Tree>() + match node {
+                Tree::Leaf { indices } => indices.len() * std::mem::size_of::<NodeID>(),
+                Tree::Split { hp, .. } => hp.coef.len() * std::mem::size_of::<f32>()
+            }
+        }).sum::<usize>()
+            + self.sq_norms.read().expect("RwLock poisoned!").values.len()
+                * std::mem::size_of::<f32>()
+            + self.quantized.as_ref().map(|(qs, _)| qs.memory_bytes()).unwrap_or(0)
+    }
+
+    /// Scans leaves with an int8 copy of the embeddings, then reranks the best `rerank_factor * k`
+    /// candidates with the full precision embeddings.  Only cosine, dot, and euclidean distances
+    /// benefit.  Quantization is dropped when the index is refit and isn't saved, so call this
+    /// again after fitting or loading.  Queries against other stores, or after the embeddings
+    /// were written without calling `update`, skip the quantized copy.
+    pub fn quantize(&mut self, es: &EmbeddingStore, rerank_factor: usize) -> Result<(), GraphLibError> {
+        if rerank_factor == 0 {
+            return Err("rerank_factor must be positive!".into())
+        }
+        self.quantized = Some((QuantizedStore::new(es), rerank_factor));
+        Ok(())
+    }
+
+    /// Moves nodes to the leaves matching their current embeddings, in every tree, after their
+    /// embeddings were rewritten in place.  Nodes the index hasn't seen yet are added.  Splits
+    /// aren't relearned, so refit once a large fraction of the nodes has moved.
+    pub fn update(&mut self, es: &EmbeddingStore, node_ids: &[NodeID]) -> Result<(), GraphLibError> {
+        if self.trees.is_empty() {
+            return Err(GraphLibError::EmptyIndex)
+        }
+        if node_ids.iter().any(|node_id| *node_id >= es.len()) {
+            return Err("node_ids contains nodes outside of the embedding store!".into())
+        }
+
+        let version = es.version();
+        let moved: HashSet<NodeID> = node_ids.iter().cloned().collect();
+        self.trees.par_iter_mut().for_each(|tree| {
+            tree.iter_mut().for_each(|node| {
+                if let Tree::Leaf { indices } = node {
+                    indices.retain(|node_id| !moved.contains(node_id));
+                }
+            });
+            for node_id in moved.iter() {
+                let leaf_idx = tree_leaf_index(tree, es.get_embedding(*node_id));
+                if let Tree::Leaf { indices } = &mut tree[leaf_idx] {
+                    indices.push(*node_id);
+                }
+            }
+        });
+
+        // Norms of another store are left stale, to be recomputed on the next query
+        let sq_norms = self.sq_norms.get_mut().expect("RwLock poisoned!");
+        if sq_norms.version.map(|v| v.id) == Some(version.id) {
+            let values = Arc::make_mut(&mut sq_norms.values);
+            values.resize(es.len().max(values.len()), 0.);
+            for node_id in moved.iter() {
+                let emb = es.get_embedding(*node_id);
+                values[*node_id] = dot_lanes(emb, emb);
+            }
+            sq_norms.version = Some(version);
+        }
+
+        // Quantized stores of other embeddings stay stale, and so ignored, until quantized again
+        if let Some((qs, _)) = self.quantized.as_mut() {
+            if qs.version.id == version.id && qs.len() == es.len() {
+                let dims = qs.dims;
+                for node_id in moved.iter() {
+                    let out = &mut qs.values[node_id * dims..(node_id + 1) * dims];
+                    qs.scales[*node_id] = quantize(es.get_embedding(*node_id), out);
+                }
+                qs.version = version;
+            }
+        }
+        Ok(())
     }
 
     pub fn fit(
@@ -224,38 +533,145 @@ impl Ann {
         num_sampled_nodes_split_test: Option<usize>,
         node_ids: Option<Vec<NodeID>>,
         seed: u64
-    ) {
-        let config = AnnBuildConfig {
-            max_nodes_per_leaf: max_nodes_per_leaf,
-            test_hp_per_split: test_hp_per_split.unwrap_or(5),
-            num_sampled_nodes_split_test: num_sampled_nodes_split_test.unwrap_or(30)
-        };
+    ) -> Result<(), GraphLibError> {
+        let mut config = AnnBuildConfig::new(n_trees, max_nodes_per_leaf, seed);
+        if let Some(test_hp_per_split) = test_hp_per_split {
+            config.test_hp_per_split = test_hp_per_split;
+        }
+        if let Some(num_sampled_nodes_split_test) = num_sampled_nodes_split_test {
+            config.num_sampled_nodes_split_test = num_sampled_nodes_split_test;
+        }
+        self.fit_with_config(es, &config, node_ids)
+    }
+
+    /// Fits the index using a build config, such as one loaded from an experiment file.
+    pub fn fit_with_config(
+        &mut self,
+        es: &EmbeddingStore,
+        config: &AnnBuildConfig,
+        node_ids: Option<Vec<NodeID>>
+    ) -> Result<(), GraphLibError> {
+        if config.n_trees == 0 {
+            return Err("Ann needs at least one tree!".into())
+        }
+        if config.max_nodes_per_leaf == 0 {
+            return Err("max_nodes_per_leaf must be positive!".into())
+        }
+        if let Some(frac) = config.bagging {
+            if frac <= 0f32 || frac > 1f32 || frac.is_nan() {
+                return Err("bagging must be in (0, 1]!".into())
+            }
+        }
+        let num_nodes = node_ids.as_ref().map(|nids| nids.len()).unwrap_or(es.len());
+        if num_nodes == 0 {
+            return Err(GraphLibError::EmptyGraph)
+        }
+        if let Some(nids) = node_ids.as_ref() {
+            if nids.iter().any(|node_id| *node_id >= es.len()) {
+                return Err("node_ids contains nodes outside of the embedding store!".into())
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("ann.fit", nodes = num_nodes, n_trees = config.n_trees).entered();
+
+        let tracker = ResourceTracker::new();
 
         // Setup the number of trees necessary to build
-        let mut trees = Vec::with_capacity(n_trees);
-        for _ in 0..n_trees {
+        let mut trees = Vec::with_capacity(config.n_trees);
+        for _ in 0..config.n_trees {
             trees.push(Vec::new());
         }
 
         // Learn each tree, using separate random seeds
-        trees.par_iter_mut().enumerate().for_each(|(idx, tree) | {
-            let mut indices: Vec<_> = if let Some(nids) = node_ids.as_ref() {
-                nids.iter().map(|idx| (*idx, false)).collect()
-            } else {
-                (0..es.len()).map(|idx| (idx, false)).collect()
-            };
-            let mut rng = XorShiftRng::seed_from_u64(seed + idx as u64);
-            self.fit_group_(&config, tree, 1, es, indices.as_mut_slice(), &mut rng);
+        tracker.phase("fit", || {
+            trees.par_iter_mut().enumerate().for_each(|(idx, tree) | {
+                let mut indices: Vec<_> = if let Some(nids) = node_ids.as_ref() {
+                    nids.iter().map(|idx| (*idx, false)).collect()
+                } else {
+                    (0..es.len()).map(|idx| (idx, false)).collect()
+                };
+                let mut rng = config.rng_kind.seed_from_u64(config.seed + idx as u64);
+                if let Some(frac) = config.bagging {
+                    // Learn the splits on a sample, then route everything else into the leaves
+                    indices.shuffle(&mut rng);
+                    let n_sample = ((indices.len() as f32 * frac).ceil() as usize).max(1);
+                    let rest = indices.split_off(n_sample.min(indices.len()));
+                    self.fit_group_(config, tree, 1, es, indices.as_mut_slice(), &mut rng);
+                    for (node_id, _) in rest {
+                        let leaf_idx = tree_leaf_index(tree, es.get_embedding(node_id));
+                        if let Tree::Leaf { indices } = &mut tree[leaf_idx] {
+                            indices.push(node_id);
+                        }
+                    }
+                } else {
+                    self.fit_group_(config, tree, 1, es, indices.as_mut_slice(), &mut rng);
+                }
+            });
         });
 
+        self.leaves = index_leaves(&trees);
         self.trees = trees;
+        self.quantized = None;
+        *self.sq_norms.get_mut().expect("RwLock poisoned!") = match es.distance() {
+            Distance::Cosine | Distance::Euclidean => {
+                SqNorms { version: Some(es.version()), values: Arc::new(compute_sq_norms(es)) }
+            },
+            _ => SqNorms::default()
+        };
+        tracker.record_bytes("index", self.memory_bytes());
+        tracker.record_bytes("embeddings", es.memory_bytes());
+        self.report = Some(tracker.report());
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            max_depth = self.depth().into_iter().max().unwrap_or(0),
+            leaf_sizes = ?self.leaf_size_histogram(),
+            memory_bytes = self.memory_bytes(),
+            "ann fit");
+
+        Ok(())
+    }
 
+    /// Same as fit, but builds the trees within the runtime's thread pool.
+    pub fn fit_with_runtime(
+        &mut self,
+        runtime: &Runtime,
+        es: &EmbeddingStore,
+        n_trees: usize,
+        max_nodes_per_leaf: usize,
+        test_hp_per_split: Option<usize>,
+        num_sampled_nodes_split_test: Option<usize>,
+        node_ids: Option<Vec<NodeID>>,
+        seed: u64
+    ) -> Result<(), GraphLibError> {
+        runtime.install(|| {
+            self.fit(es, n_trees, max_nodes_per_leaf, test_hp_per_split,
+                     num_sampled_nodes_split_test, node_ids, seed)
+        })
     }
 
     pub fn depth(&self) -> Vec<usize> {
         self.trees.par_iter().map(|t| tree_depth(t, t.len() - 1)).collect()
     }
 
+    /// Histogram of leaf sizes across all trees, as (upper bound, number of leaves) pairs with
+    /// power of two buckets.  Leaves of size 0 fall in the first bucket.  Skewed histograms
+    /// indicate poor splits, which hurt latency.
+    pub fn leaf_size_histogram(&self) -> Vec<(usize, usize)> {
+        let mut counts: Vec<usize> = Vec::new();
+        self.trees.iter().flat_map(|t| t.iter()).for_each(|node| {
+            if let Tree::Leaf { indices } = node {
+                let bucket = indices.len().next_power_of_two().trailing_zeros() as usize;
+                if counts.len() <= bucket {
+                    counts.resize(bucket + 1, 0);
+                }
+                counts[bucket] += 1;
+            }
+        });
+        counts.into_iter().enumerate().map(|(bucket, count)| (1 << bucket, count)).collect()
+    }
+
     fn fit_group_(
         &self, 
         config: &AnnBuildConfig,
@@ -312,50 +728,150 @@ impl Ann {
         tree_table.len() - 1
     }
 
+    /// Returns the approximate k nearest neighbors to the query.  Fails if the index hasn't been
+    /// fit or the query doesn't match the embedding dimensions.
     pub fn predict(
         &self, 
         es: &EmbeddingStore, 
         emb: &[f32],
         k: usize,
         min_search_nodes: Option<usize>
+    ) -> Result<Vec<NodeDistance>, GraphLibError> {
+        self.check_query(es, emb)?;
+        Ok(self.predict_unchecked(es, emb, k, min_search_nodes))
+    }
+
+    /// Same as predict, but the query is a node given by its qualified "node_type:name", such
+    /// as "gig:12345", and results are returned as qualified names.  The vocab must be the one
+    /// the embeddings are keyed by.  The query node is included in its own results.
+    pub fn predict_named(
+        &self,
+        es: &EmbeddingStore,
+        vocab: &Vocab,
+        qualified: &str,
+        k: usize,
+        min_search_nodes: Option<usize>
+    ) -> Result<Vec<(String, f32)>, GraphLibError> {
+        let node_id = vocab.lookup_qualified(qualified, es.len())?;
+
+        let results = self.predict(es, es.get_embedding(node_id), k, min_search_nodes)?;
+        Ok(results.into_iter()
+            .filter_map(|nd| vocab.qualified_name(nd.1).map(|name| (name, nd.0)))
+            .collect())
+    }
+
+    /// Returns the approximate neighbors within `max_distance` of the query, nearest first, up to
+    /// `limit` of them.  Useful for deduplication, where the number of matches isn't known ahead
+    /// of time.  The search budget is the same as predict with k = limit.
+    pub fn predict_within(
+        &self, 
+        es: &EmbeddingStore, 
+        emb: &[f32],
+        max_distance: f32,
+        limit: usize
+    ) -> Result<Vec<NodeDistance>, GraphLibError> {
+        self.check_query(es, emb)?;
+        let mut results = self.predict_unchecked(es, emb, limit, None);
+
+        // Results are sorted, so everything past the first miss is also too far
+        let n = results.partition_point(|nd| nd.0 <= max_distance);
+        results.truncate(n);
+        Ok(results)
+    }
+
+    fn check_query(&self, es: &EmbeddingStore, emb: &[f32]) -> Result<(), GraphLibError> {
+        if self.trees.is_empty() {
+            return Err(GraphLibError::EmptyIndex)
+        }
+        check_dims(es.dims(), emb.len())
+    }
+
+    fn predict_unchecked(
+        &self, 
+        es: &EmbeddingStore, 
+        emb: &[f32],
+        k: usize,
+        min_search_nodes: Option<usize>
     ) -> Vec<NodeDistance> {
         
         // Get the scores
         let min_search = min_search_nodes.unwrap_or(self.trees.len() * k);
-        let scores = self.trees.par_iter().map(|tree| {
-            tree_predict(tree, es, emb, k, min_search)
+        let version = es.version();
+        let quantized = self.quantized.as_ref().filter(|(qs, _)| qs.version == version);
+        let norms = self.current_sq_norms(es, version);
+        let sq_norms = norms.as_ref().map(|values| values.as_slice());
+        let (scorer, k_search) = match quantized {
+            Some((qs, rerank_factor)) => {
+                (LeafScorer::new(es, emb, sq_norms).with_quantized(qs), k * rerank_factor)
+            },
+            None => (LeafScorer::new(es, emb, sq_norms), k)
+        };
+        let top_ks = self.trees.par_iter().map(|tree| {
+            tree_predict(tree, &scorer, k_search, min_search)
         }).collect::<Vec<_>>();
 
-        // Fold them into a single vec
-        let n = scores.iter().map(|x| x.len()).sum::<usize>();
-        let mut all_scores = Vec::with_capacity(n);
-        scores.into_iter().for_each(|subset| {
-            subset.into_iter().for_each(|(node_id, s)| {
-                all_scores.push(NodeDistance::new(s, node_id));
-            });
-        });
+        // Merge the per-tree results, deduplicating nodes which show up in multiple trees
+        let mut all_scores = TopK::merge_sorted(top_ks);
 
-        // Sort by Score and Node ID
-        all_scores.par_sort();
-
-        // Deduplicate nodes which show up in multiple trees
-        let mut cur_pointer = 1;
-        let mut cur_node_id = all_scores[0].1;
-        for i in 1..n {
-            let next_id = all_scores[i].1;
-            if next_id != cur_node_id {
-                all_scores[cur_pointer] = all_scores[i];
-                cur_node_id = next_id;
-                cur_pointer += 1;
-            }
+        // Rerank the approximate candidates with exact distances
+        if quantized.is_some() {
+            let exact = LeafScorer::new(es, emb, sq_norms);
+            all_scores.par_iter_mut().for_each(|nd| *nd = NodeDistance::new(exact.score(nd.1), nd.1));
+            all_scores.par_sort();
+            all_scores.reverse();
         }
 
-        all_scores.truncate(cur_pointer);
-        all_scores.reverse();
         all_scores.truncate(k);
         all_scores
     }
 
+    /// Squared norms matching the current embeddings, for the distances which use them.  Stale
+    /// norms are recomputed first.
+    fn current_sq_norms(
+        &self,
+        es: &EmbeddingStore,
+        version: StoreVersion
+    ) -> Option<Arc<Vec<f32>>> {
+        if !matches!(es.distance(), Distance::Cosine | Distance::Euclidean) {
+            return None
+        }
+
+        {
+            let norms = self.sq_norms.read().expect("RwLock poisoned!");
+            if norms.version == Some(version) {
+                return Some(norms.values.clone())
+            }
+        }
+
+        // Computed outside the lock so concurrent queries aren't blocked
+        let values = Arc::new(compute_sq_norms(es));
+        *self.sq_norms.write().expect("RwLock poisoned!") = SqNorms {
+            version: Some(version),
+            values: values.clone()
+        };
+        Some(values)
+    }
+
+    /// Same as predict, but serves repeated queries from the cache.  Results are keyed by the
+    /// version of the embedding store, so queries against other stores, or after the embeddings
+    /// were written, miss.  The cache should be cleared whenever the index is refit.
+    pub fn predict_cached(
+        &self, 
+        cache: &QueryCache<Vec<NodeDistance>>,
+        es: &EmbeddingStore, 
+        emb: &[f32],
+        k: usize,
+        min_search_nodes: Option<usize>
+    ) -> Result<Vec<NodeDistance>, GraphLibError> {
+        self.check_query(es, emb)?;
+        let version = es.version();
+        let params = [k, min_search_nodes.unwrap_or(usize::MAX),
+                      version.id as usize, version.generation as usize];
+        Ok(cache.get_or_insert_with(emb, &params, || {
+            self.predict_unchecked(es, emb, k, min_search_nodes)
+        }))
+    }
+
     pub fn predict_leaf_indices(
         &self,
         emb: &[f32]
@@ -378,6 +894,161 @@ impl Ann {
         self.trees.len()
     }
 
+    /// Number of leaves in each tree
+    pub fn num_leaves(&self) -> Vec<usize> {
+        self.leaves.iter().map(|l| l.len()).collect()
+    }
+
+    /// Returns the leaf the embedding falls into within each tree.  Unlike predict_leaf_indices,
+    /// leaf ids are dense, making them suitable for postings.
+    pub fn predict_leaf_ids(&self, emb: &[f32]) -> Vec<LeafId> {
+        self.trees.par_iter().zip(self.leaves.par_iter()).enumerate().map(|(tree_idx, (tree, leaves))| {
+            let table_idx = tree_leaf_index(tree, emb);
+            let leaf_idx = leaves.binary_search(&table_idx)
+                .expect("Leaf should always be indexed!");
+            (tree_idx, leaf_idx)
+        }).collect()
+    }
+
+    /// Nodes within a leaf, or None if the leaf doesn't exist.  Iterating over every leaf of
+    /// every tree produces the postings for an inverted index.
+    pub fn leaf_members(&self, tree: usize, leaf: usize) -> Option<&[NodeID]> {
+        let table_idx = *self.leaves.get(tree)?.get(leaf)?;
+        match &self.trees[tree][table_idx] {
+            Tree::Leaf { indices } => Some(indices.as_slice()),
+            _ => None
+        }
+    }
+
+    /// Saves the index to a little endian binary file:
+    ///
+    /// ```text
+    /// [magic][version][dims][num_trees][num_norms][f32 norm * num_norms]
+    /// [num_nodes][node * num_nodes] * num_trees
+    /// ```
+    ///
+    /// where each node is either `[0][count][node id * count]` for a leaf or
+    /// `[1][f32 bias][f32 coef * dims][above][below]` for a split.  The embeddings aren't
+    /// included, so the same EmbeddingStore needs to be provided when predicting.
+    pub fn save(&self, path: &str) -> IOResult<()> {
+        let mut bw = BufWriter::new(File::create(path)?);
+        self.write_to(&mut bw)?;
+        bw.flush()
+    }
+
+    /// Writes the binary format to any writer.
+    pub fn write_to(&self, w: &mut impl Write) -> IOResult<()> {
+        let dims = self.trees.iter().flat_map(|t| t.iter()).find_map(|node| match node {
+            Tree::Split { hp, .. } => Some(hp.coef.len()),
+            _ => None
+        }).unwrap_or(0);
+
+        let sq_norms = self.sq_norms.read().expect("RwLock poisoned!");
+        for v in [MAGIC, VERSION, dims as u64, self.trees.len() as u64, sq_norms.values.len() as u64] {
+            write_u64(w, v)?;
+        }
+        for norm in sq_norms.values.iter() {
+            w.write_all(&norm.to_le_bytes())?;
+        }
+
+        for tree in self.trees.iter() {
+            write_u64(w, tree.len() as u64)?;
+            for node in tree.iter() {
+                match node {
+                    Tree::Leaf { indices } => {
+                        write_u64(w, 0)?;
+                        write_u64(w, indices.len() as u64)?;
+                        for node_id in indices.iter() {
+                            write_u64(w, *node_id as u64)?;
+                        }
+                    },
+                    Tree::Split { hp, above, below } => {
+                        write_u64(w, 1)?;
+                        w.write_all(&hp.bias.to_le_bytes())?;
+                        for c in hp.coef.iter() {
+                            w.write_all(&c.to_le_bytes())?;
+                        }
+                        write_u64(w, *above as u64)?;
+                        write_u64(w, *below as u64)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads an index previously written with `save`.
+    pub fn load(path: &str) -> IOResult<Self> {
+        Ann::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Reads the binary format from any reader.  Trees are validated so that a corrupt file
+    /// fails here rather than looping or panicking during predict.
+    pub fn read_from(r: &mut impl Read) -> IOResult<Self> {
+        if read_u64(r)? != MAGIC {
+            return Err(invalid_data("Not an Ann file!"))
+        }
+        if read_u64(r)? != VERSION {
+            return Err(invalid_data("Unsupported Ann version!"))
+        }
+
+        let dims = read_u64(r)? as usize;
+        let num_trees = read_u64(r)? as usize;
+        let num_norms = read_u64(r)? as usize;
+        let sq_norms = (0..num_norms).map(|_| read_f32(r)).collect::<IOResult<Vec<_>>>()?;
+
+        let mut trees = Vec::new();
+        for _ in 0..num_trees {
+            let num_nodes = read_u64(r)? as usize;
+            if num_nodes == 0 {
+                return Err(invalid_data("Empty tree!"))
+            }
+
+            let mut tree = Vec::new();
+            for idx in 0..num_nodes {
+                let node = match read_u64(r)? {
+                    0 => {
+                        let count = read_u64(r)? as usize;
+                        let indices = (0..count)
+                            .map(|_| read_u64(r).map(|node_id| node_id as NodeID))
+                            .collect::<IOResult<Vec<_>>>()?;
+                        Tree::Leaf { indices }
+                    },
+                    1 => {
+                        let bias = read_f32(r)?;
+                        let coef = (0..dims).map(|_| read_f32(r)).collect::<IOResult<Vec<_>>>()?;
+                        let above = read_u64(r)? as TreeIndex;
+                        let below = read_u64(r)? as TreeIndex;
+
+                        // Children are always written before their parents
+                        if above >= idx || below >= idx {
+                            return Err(invalid_data("Tree node index out of range!"))
+                        }
+                        Tree::Split { hp: Hyperplane::new(coef, bias), above, below }
+                    },
+                    _ => return Err(invalid_data("Unknown tree node!"))
+                };
+                tree.push(node);
+            }
+            trees.push(tree);
+        }
+
+        let leaves = index_leaves(&trees);
+        // Loaded norms aren't tied to a store, so the first query recomputes them
+        let sq_norms = RwLock::new(SqNorms { version: None, values: Arc::new(sq_norms) });
+        Ok(Ann { trees, leaves, sq_norms, quantized: None, report: None })
+    }
+
+}
+
+/// Finds the table index of every leaf, in table order
+fn index_leaves(trees: &[TreeTable]) -> Vec<Vec<TreeIndex>> {
+    trees.iter().map(|tree| {
+        tree.iter().enumerate()
+            .filter(|(_, node)| matches!(node, Tree::Leaf { .. }))
+            .map(|(idx, _)| idx)
+            .collect()
+    }).collect()
 }
 
 fn sort_binary(vec: &mut [(NodeID, bool)]) {
@@ -529,3 +1200,397 @@ fn compute_normal_rp(
     let bias = -median(rps.as_slice());
     Hyperplane::new(random_vec, bias)
 }
+
+fn invalid_data(msg: &str) -> IOError {
+    IOError::new(ErrorKind::InvalidData, msg)
+}
+
+fn write_u64(w: &mut impl Write, v: u64) -> IOResult<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn read_u64(r: &mut impl Read) -> IOResult<u64> {
+    let mut buff = [0u8; 8];
+    r.read_exact(&mut buff)?;
+    Ok(u64::from_le_bytes(buff))
+}
+
+fn read_f32(r: &mut impl Read) -> IOResult<f32> {
+    let mut buff = [0u8; 4];
+    r.read_exact(&mut buff)?;
+    Ok(f32::from_le_bytes(buff))
+}
+
+#[cfg(test)]
+mod ann_tests {
+    use super::*;
+    use rand_xorshift::XorShiftRng;
+
+    fn build_store(distance: Distance) -> EmbeddingStore {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut es = EmbeddingStore::new(1000, 11, distance);
+        for node_id in 0..es.len() {
+            let emb: Vec<f32> = (0..es.dims()).map(|_| rng.gen::<f32>() - 0.5).collect();
+            es.set_embedding(node_id, &emb);
+        }
+        es
+    }
+
+    #[test]
+    fn test_leaf_scorer() {
+        let query = [0.3, -0.1, 0.2, 0.5, -0.4, 0.1, 0.0, 0.2, -0.3, 0.1, 0.4];
+        for distance in [Distance::Cosine, Distance::Euclidean, Distance::Dot, Distance::ALT] {
+            let es = build_store(distance);
+            let sq_norms = compute_sq_norms(&es);
+            let scorer = LeafScorer::new(&es, &query, Some(&sq_norms));
+            let indices: Vec<_> = (0..es.len()).collect();
+            let mut out = vec![0f32; es.len()];
+            scorer.score_leaf(&indices, &mut out);
+            for node_id in indices {
+                let expected = distance.compute(&query, es.get_embedding(node_id));
+                assert!((out[node_id] - expected).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_predict() {
+        let es = build_store(Distance::Cosine);
+        let mut ann = Ann::new();
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+
+        let query = es.get_embedding(10).to_vec();
+        let results = ann.predict(&es, &query, 5, None).unwrap();
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].1, 10);
+        assert!(results[0].0.abs() < 1e-5);
+        assert!(ann.resource_report().is_some());
+    }
+
+    #[test]
+    fn test_stale_norms() {
+        let mut es = build_store(Distance::Euclidean);
+        let mut ann = Ann::new();
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+        let query = es.get_embedding(10).to_vec();
+
+        let check_exact = |ann: &Ann, es: &EmbeddingStore| {
+            for nd in ann.predict(es, &query, 10, Some(200)).unwrap() {
+                let d = Distance::Euclidean.compute(&query, es.get_embedding(nd.1));
+                assert!((nd.0 - d).abs() < 1e-4);
+            }
+        };
+
+        // Another store with the same number of embeddings
+        let mut doubled = build_store(Distance::Euclidean);
+        for node_id in 0..doubled.len() {
+            let emb: Vec<_> = doubled.get_embedding(node_id).iter().map(|ei| 2. * ei).collect();
+            doubled.set_embedding(node_id, &emb);
+        }
+        check_exact(&ann, &doubled);
+
+        // Embeddings rewritten in place without updating the index
+        for node_id in 0..es.len() {
+            let emb: Vec<_> = es.get_embedding(node_id).iter().map(|ei| -3. * ei).collect();
+            es.set_embedding(node_id, &emb);
+        }
+        check_exact(&ann, &es);
+    }
+
+    #[test]
+    fn test_predict_cached() {
+        let mut es = build_store(Distance::Cosine);
+        let mut ann = Ann::new();
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+        let cache = QueryCache::new(10, 1e-6).unwrap();
+
+        let query = es.get_embedding(10).to_vec();
+        let expected = ann.predict(&es, &query, 5, None).unwrap();
+        assert_eq!(ann.predict_cached(&cache, &es, &query, 5, None).unwrap(), expected);
+        assert_eq!(ann.predict_cached(&cache, &es, &query, 5, None).unwrap(), expected);
+        assert_eq!(cache.stats().hits, 1);
+
+        // Another store of the same shape misses
+        let other = build_store(Distance::Cosine);
+        ann.predict_cached(&cache, &other, &query, 5, None).unwrap();
+        assert_eq!(cache.stats().misses, 2);
+
+        // As do queries after the embeddings were written
+        es.set_embedding(11, &query);
+        let results = ann.predict_cached(&cache, &es, &query, 5, None).unwrap();
+        assert_eq!(cache.stats().misses, 3);
+        assert_eq!(results, ann.predict(&es, &query, 5, None).unwrap());
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let es = build_store(Distance::Cosine);
+        let mut ann = Ann::new();
+        let query = es.get_embedding(10).to_vec();
+        assert!(matches!(ann.predict(&es, &query, 5, None), Err(GraphLibError::EmptyIndex)));
+
+        assert!(ann.fit(&es, 0, 20, None, None, None, 2023).is_err());
+        assert!(ann.fit(&es, 5, 0, None, None, None, 2023).is_err());
+        assert!(matches!(ann.fit(&es, 5, 20, None, None, Some(vec![]), 2023),
+                         Err(GraphLibError::EmptyGraph)));
+        assert!(ann.fit(&es, 5, 20, None, None, Some(vec![1, 1000]), 2023).is_err());
+
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+        assert!(matches!(ann.predict(&es, &query[..5], 5, None),
+                         Err(GraphLibError::DimensionMismatch { expected: 11, found: 5 })));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let es = build_store(Distance::Cosine);
+        let mut ann = Ann::new();
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+
+        let mut buffer = Vec::new();
+        ann.write_to(&mut buffer).unwrap();
+        let loaded = Ann::read_from(&mut &buffer[..]).unwrap();
+        assert_eq!(loaded.num_trees(), 5);
+        assert_eq!(loaded.depth(), ann.depth());
+
+        let query = es.get_embedding(10).to_vec();
+        let expected = ann.predict(&es, &query, 5, None).unwrap();
+        let results = loaded.predict(&es, &query, 5, None).unwrap();
+        assert_eq!(results.iter().map(|nd| nd.1).collect::<Vec<_>>(),
+                   expected.iter().map(|nd| nd.1).collect::<Vec<_>>());
+
+        // Truncated and foreign files fail cleanly
+        assert!(Ann::read_from(&mut &buffer[..buffer.len() - 1]).is_err());
+        assert!(Ann::read_from(&mut &[0u8; 64][..]).is_err());
+    }
+
+    #[test]
+    fn test_leaf_ids() {
+        let es = build_store(Distance::Cosine);
+        let mut ann = Ann::new();
+        assert!(ann.num_leaves().is_empty());
+        assert!(ann.leaf_members(0, 0).is_none());
+
+        ann.fit(&es, 3, 20, None, None, None, 2023).unwrap();
+        let num_leaves = ann.num_leaves();
+        assert_eq!(num_leaves.len(), 3);
+
+        // Postings cover every node once per tree
+        for (tree, n) in num_leaves.iter().enumerate() {
+            let mut members: Vec<_> = (0..*n)
+                .flat_map(|leaf| ann.leaf_members(tree, leaf).unwrap().to_vec())
+                .collect();
+            members.sort();
+            assert_eq!(members, (0..es.len()).collect::<Vec<_>>());
+            assert!(ann.leaf_members(tree, *n).is_none());
+        }
+
+        let leaf_ids = ann.predict_leaf_ids(es.get_embedding(10));
+        assert_eq!(leaf_ids.len(), 3);
+        for (tree, leaf) in leaf_ids.iter() {
+            assert!(ann.leaf_members(*tree, *leaf).unwrap().contains(&10));
+        }
+
+        // Stable across save and load
+        let mut buffer = Vec::new();
+        ann.write_to(&mut buffer).unwrap();
+        let loaded = Ann::read_from(&mut &buffer[..]).unwrap();
+        assert_eq!(loaded.num_leaves(), num_leaves);
+        assert_eq!(loaded.predict_leaf_ids(es.get_embedding(10)), leaf_ids);
+    }
+
+    #[test]
+    fn test_predict_within() {
+        let es = build_store(Distance::Euclidean);
+        let mut ann = Ann::new();
+        let query = es.get_embedding(10).to_vec();
+        assert!(ann.predict_within(&es, &query, 0.5, 10).is_err());
+
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+        let results = ann.predict_within(&es, &query, 0.5, 100).unwrap();
+        assert!(results.len() > 0 && results.len() < 100);
+        assert_eq!(results[0].1, 10);
+        assert!(results.iter().all(|nd| nd.0 <= 0.5));
+        assert!(results.windows(2).all(|w| w[0].0 <= w[1].0));
+
+        // Duplicates only
+        let results = ann.predict_within(&es, &query, 0., 100).unwrap();
+        assert_eq!(results.iter().map(|nd| nd.1).collect::<Vec<_>>(), vec![10]);
+
+        let results = ann.predict_within(&es, &query, 10., 3).unwrap();
+        assert_eq!(results.len(), 3);
+    }
+
+    #[test]
+    fn test_quantize() {
+        let emb = [0.5, -0.25, 0.1, 0.];
+        let mut out = [0i8; 4];
+        let scale = quantize(&emb, &mut out);
+        assert_eq!(out, [127, -64, 25, 0]);
+        emb.iter().zip(out.iter()).for_each(|(ei, oi)| {
+            assert!((ei - *oi as f32 * scale).abs() <= scale / 2.);
+        });
+
+        let es = build_store(Distance::Cosine);
+        let qs = QuantizedStore::new(&es);
+        let query = es.get_embedding(10);
+        let mut q = vec![0i8; query.len()];
+        let q_scale = quantize(query, &mut q);
+        for node_id in 0..es.len() {
+            let exact = dot(query, es.get_embedding(node_id));
+            assert!((qs.dot(&q, q_scale, node_id) - exact).abs() < 1e-2);
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let mut es = build_store(Distance::Euclidean);
+        let mut ann = Ann::new();
+        assert!(ann.update(&es, &[0]).is_err());
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+
+        // Move node 10 on top of node 20
+        let target = es.get_embedding(20).to_vec();
+        es.set_embedding(10, &target);
+        ann.update(&es, &[10, 10]).unwrap();
+
+        let results = ann.predict(&es, &target, 2, None).unwrap();
+        let mut ids: Vec<_> = results.iter().map(|nd| nd.1).collect();
+        ids.sort();
+        assert_eq!(ids, vec![10, 20]);
+        assert!(results.iter().all(|nd| nd.0.abs() < 1e-3));
+
+        // Every tree still indexes node 10 exactly once
+        for tree in 0..ann.num_trees() {
+            let count: usize = (0..ann.num_leaves()[tree])
+                .map(|leaf| ann.leaf_members(tree, leaf).unwrap().iter().filter(|n| **n == 10).count())
+                .sum();
+            assert_eq!(count, 1);
+        }
+
+        assert!(ann.update(&es, &[1000]).is_err());
+    }
+
+    #[test]
+    fn test_predict_named() {
+        let es = build_store(Distance::Euclidean);
+        let mut vocab = Vocab::new();
+        for node_id in 0..es.len() {
+            vocab.get_or_insert("gig", &node_id.to_string());
+        }
+        let mut ann = Ann::new();
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+
+        let results = ann.predict_named(&es, &vocab, "gig:10", 5, Some(200)).unwrap();
+        let expected = ann.predict(&es, es.get_embedding(10), 5, Some(200)).unwrap();
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].0, "gig:10");
+        results.iter().zip(expected.iter()).for_each(|((name, d), nd)| {
+            assert_eq!(*name, format!("gig:{}", nd.1));
+            assert_eq!(*d, nd.0);
+        });
+
+        assert!(ann.predict_named(&es, &vocab, "gig:5000", 5, None).is_err());
+        assert!(ann.predict_named(&es, &vocab, "user:10", 5, None).is_err());
+    }
+
+    #[test]
+    fn test_quantized_predict() {
+        for distance in [Distance::Cosine, Distance::Euclidean, Distance::Dot] {
+            let es = build_store(distance);
+            let mut ann = Ann::new();
+            ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+            let query = es.get_embedding(10).to_vec();
+            let expected = ann.predict(&es, &query, 10, Some(200)).unwrap();
+
+            assert!(ann.quantize(&es, 0).is_err());
+            ann.quantize(&es, 4).unwrap();
+            let results = ann.predict(&es, &query, 10, Some(200)).unwrap();
+            assert_eq!(results.len(), 10);
+
+            // Reranked distances are exact
+            for nd in results.iter() {
+                let d = distance.compute(&query, es.get_embedding(nd.1));
+                assert!((nd.0 - d).abs() < 1e-4);
+            }
+            assert_eq!(results[0].1, expected[0].1);
+            let overlap = results.iter().filter(|nd| expected.iter().any(|e| e.1 == nd.1)).count();
+            assert!(overlap >= 8);
+
+            // Refitting drops the quantized store
+            ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+            assert!(ann.quantized.is_none());
+        }
+    }
+
+    #[test]
+    fn test_stale_quantized() {
+        let mut es = build_store(Distance::Cosine);
+        let mut ann = Ann::new();
+        ann.fit(&es, 5, 20, None, None, None, 2023).unwrap();
+        ann.quantize(&es, 1).unwrap();
+
+        // Shift every embedding over by one node without updating the index
+        let shifted: Vec<Vec<f32>> = (0..es.len())
+            .map(|node_id| es.get_embedding((node_id + 1) % es.len()).to_vec())
+            .collect();
+        shifted.iter().enumerate().for_each(|(node_id, emb)| es.set_embedding(node_id, emb));
+
+        let query = es.get_embedding(10).to_vec();
+        let results = ann.predict(&es, &query, 10, Some(200)).unwrap();
+        ann.quantized = None;
+        assert_eq!(results, ann.predict(&es, &query, 10, Some(200)).unwrap());
+    }
+
+    #[test]
+    fn test_bagging() {
+        let es = build_store(Distance::Cosine);
+        let mut config = AnnBuildConfig::new(5, 20, 2023);
+        config.bagging = Some(0.25);
+
+        let mut ann = Ann::new();
+        ann.fit_with_config(&es, &config, None).unwrap();
+
+        // Every tree still indexes every node exactly once
+        for tree in ann.trees.iter() {
+            let mut node_ids: Vec<_> = tree.iter().flat_map(|node| match node {
+                Tree::Leaf { indices } => indices.clone(),
+                _ => Vec::new()
+            }).collect();
+            node_ids.sort();
+            assert_eq!(node_ids, (0..es.len()).collect::<Vec<_>>());
+        }
+
+        let query = es.get_embedding(10).to_vec();
+        let results = ann.predict(&es, &query, 5, None).unwrap();
+        assert_eq!(results[0].1, 10);
+
+        config.bagging = Some(0.);
+        assert!(ann.fit_with_config(&es, &config, None).is_err());
+        config.bagging = Some(1.5);
+        assert!(ann.fit_with_config(&es, &config, None).is_err());
+
+        config.bagging = Some(0.25);
+        config.rng_kind = RngKind::Pcg;
+        ann.fit_with_config(&es, &config, None).unwrap();
+        assert_eq!(ann.predict(&es, &query, 5, None).unwrap()[0].1, 10);
+    }
+
+    #[test]
+    fn test_leaf_size_histogram() {
+        let es = build_store(Distance::Cosine);
+        let mut ann = Ann::new();
+        assert!(ann.leaf_size_histogram().is_empty());
+
+        ann.fit(&es, 3, 20, None, None, None, 2023).unwrap();
+        let histogram = ann.leaf_size_histogram();
+        let leaves: usize = histogram.iter().map(|(_, count)| count).sum();
+        let expected: usize = ann.trees.iter()
+            .map(|t| t.iter().filter(|n| matches!(n, Tree::Leaf { .. })).count())
+            .sum();
+        assert_eq!(leaves, expected);
+
+        for (i, (bound, _)) in histogram.iter().enumerate() {
+            assert_eq!(*bound, 1 << i);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Multi-level graph coarsening, HARP style.  Heavy edge matching repeatedly merges each node with
+//! the unmatched neighbor it's most strongly connected to, roughly halving the graph at every
+//! level.  EP then trains on the coarsest graph, where passes are cheap, and fine tunes on each
+//! finer level in turn.
+//!
+//! Since EP learns feature embeddings rather than node embeddings, prolongation is free: super
+//! nodes carry the union of their members' features, so every level shares the same feature ids
+//! and the embeddings learned on one level warm start the next.
+use float_ord::FloatOrd;
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+
+use crate::graph::{Graph,CDFGraph,CDFtoP,CumCSR,GraphBuilder,NodeID};
+use crate::embeddings::EmbeddingStore;
+use crate::feature_store::FeatureStore;
+use crate::error::GraphLibError;
+use crate::algos::ep::EmbeddingPropagation;
+use crate::algos::ep::model::Model;
+
+/// A single level of the hierarchy
+pub struct CoarseLevel {
+    /// Coarsened graph, with one node per super node
+    pub graph: CumCSR,
+
+    /// Super node of each node in the previous, finer, level
+    pub membership: Vec<NodeID>
+}
+
+/// Coarsening configuration
+#[derive(Clone,Copy,Debug)]
+pub struct Coarsening {
+    /// Stops coarsening once a level has at most this many nodes
+    pub min_nodes: usize,
+
+    /// Maximum number of levels to build
+    pub max_levels: usize,
+
+    /// Stops coarsening once a level shrinks the graph by less than this fraction, which happens
+    /// on star-like graphs where most nodes can't find a partner.
+    pub min_reduction: f32,
+
+    /// Random seed for the order nodes are matched in
+    pub seed: u64
+}
+
+impl Coarsening {
+
+    /// Builds the hierarchy, from the finest level to the coarsest.  Empty if the graph is already
+    /// small enough or can't be coarsened.
+    pub fn build(&self, graph: &impl CDFGraph) -> Vec<CoarseLevel> {
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        let mut levels: Vec<CoarseLevel> = Vec::new();
+        while levels.len() < self.max_levels {
+            let num_nodes = levels.last().map(|l| l.graph.len()).unwrap_or(graph.len());
+            if num_nodes <= self.min_nodes {
+                break
+            }
+
+            let level = match levels.last() {
+                Some(last) => coarsen(&last.graph, &mut rng),
+                None => coarsen(graph, &mut rng)
+            };
+
+            let reduction = 1. - level.graph.len() as f32 / num_nodes as f32;
+            if reduction < self.min_reduction {
+                break
+            }
+            levels.push(level);
+        }
+        levels
+    }
+}
+
+/// Merges each node with its unmatched neighbor with the highest transition probability, visiting
+/// nodes in random order.  Returns the super node of each node and the number of super nodes.
+pub fn heavy_edge_matching(graph: &impl CDFGraph, rng: &mut impl Rng) -> (Vec<NodeID>, usize) {
+    let mut order: Vec<_> = (0..graph.len()).collect();
+    order.shuffle(rng);
+
+    let mut membership = vec![None; graph.len()];
+    let mut num_groups = 0;
+    for node_id in order {
+        if membership[node_id].is_some() {
+            continue
+        }
+
+        let (edges, weights) = graph.get_edges(node_id);
+        let partner = edges.iter().zip(CDFtoP::new(weights))
+            .filter(|(t_n, _)| **t_n != node_id && membership[**t_n].is_none())
+            .max_by_key(|(t_n, p)| (FloatOrd(*p), **t_n))
+            .map(|(t_n, _)| *t_n);
+
+        membership[node_id] = Some(num_groups);
+        if let Some(partner) = partner {
+            membership[partner] = Some(num_groups);
+        }
+        num_groups += 1;
+    }
+
+    let membership = membership.into_iter()
+        .map(|g| g.expect("Every node is visited"))
+        .collect();
+    (membership, num_groups)
+}
+
+/// Coarsens the graph a single level.  Edges between super nodes sum the transition probabilities
+/// of their members' edges; edges within a super node are dropped.
+pub fn coarsen(graph: &impl CDFGraph, rng: &mut impl Rng) -> CoarseLevel {
+    let (membership, num_groups) = heavy_edge_matching(graph, rng);
+
+    let mut builder = GraphBuilder::new(true);
+    builder.ensure_nodes(num_groups);
+    for node_id in 0..graph.len() {
+        let (edges, weights) = graph.get_edges(node_id);
+        for (t_n, p) in edges.iter().zip(CDFtoP::new(weights)) {
+            let (f_g, t_g) = (membership[node_id], membership[*t_n]);
+            if f_g != t_g {
+                builder.add_edge(f_g, t_g, p);
+            }
+        }
+    }
+
+    CoarseLevel { graph: builder.build_cum_csr(), membership }
+}
+
+/// Trains EP across a coarsened hierarchy.  The coarsest level trains for the EP's passes, then
+/// each finer level, ending with the original graph, fine tunes for `fine_tune_passes`.
+#[derive(Clone,Copy,Debug)]
+pub struct MultiLevel {
+    /// How to build the hierarchy
+    pub coarsening: Coarsening,
+
+    /// Passes used on every level but the coarsest
+    pub fine_tune_passes: usize
+}
+
+impl MultiLevel {
+
+    /// Learns the feature embeddings.  Negative pools and node weights refer to nodes of the
+    /// original graph, so they're only used on the final level.  If provided, the feature embeddings warm start the
+    /// coarsest level.
+    pub fn learn<G: CDFGraph + Send + Sync, M: Model>(
+        &self,
+        ep: &EmbeddingPropagation,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: Option<EmbeddingStore>,
+        model: &M
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        let levels = self.coarsening.build(graph);
+
+        // Features for each level, built alongside the graphs
+        let mut level_features: Vec<FeatureStore> = Vec::with_capacity(levels.len());
+        for level in levels.iter() {
+            let finer = level_features.last().unwrap_or(features);
+            let merged = finer.merge_nodes(&level.membership, level.graph.len());
+            level_features.push(merged);
+        }
+
+        let coarse_ep = EmbeddingPropagation { negative_pools: None, node_weights: None, ..ep.clone() };
+        let fine_ep = EmbeddingPropagation { passes: self.fine_tune_passes, ..coarse_ep.clone() };
+
+        let mut embeddings = feature_embeddings;
+        for (idx, (level, fs)) in levels.iter().zip(level_features.iter()).enumerate().rev() {
+            let level_ep = if idx + 1 == levels.len() { &coarse_ep } else { &fine_ep };
+            embeddings = Some(level_ep.learn(&level.graph, fs, embeddings, model)?);
+        }
+
+        if levels.is_empty() {
+            ep.learn(graph, features, embeddings, model)
+        } else {
+            let final_ep = EmbeddingPropagation { passes: self.fine_tune_passes, ..ep.clone() };
+            final_ep.learn(graph, features, embeddings, model)
+        }
+    }
+}
+
+#[cfg(test)]
+mod coarsen_tests {
+    use super::*;
+    use crate::graph::CSR;
+    use crate::algos::ep::model::AveragedFeatureModel;
+    use crate::algos::utils::Sample;
+
+    fn build_graph(pairs: &[(NodeID, NodeID)]) -> CumCSR {
+        let mut edges = Vec::new();
+        for (f_n, t_n) in pairs.iter() {
+            edges.push((*f_n, *t_n, 1f32));
+            edges.push((*t_n, *f_n, 1f32));
+        }
+        CumCSR::convert(CSR::construct_from_edges(edges, false))
+    }
+
+    // Ring of 64 nodes
+    fn build_ring() -> CumCSR {
+        let pairs: Vec<_> = (0..64).map(|n| (n, (n + 1) % 64)).collect();
+        build_graph(&pairs)
+    }
+
+    #[test]
+    fn test_matching() {
+        // Disjoint pairs always match with each other
+        let graph = build_graph(&[(0, 1), (2, 3), (4, 5)]);
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let level = coarsen(&graph, &mut rng);
+        assert_eq!(level.graph.len(), 3);
+        assert_eq!(level.graph.edges(), 0);
+        for node_id in (0..6).step_by(2) {
+            assert_eq!(level.membership[node_id], level.membership[node_id + 1]);
+        }
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let graph = build_ring();
+        let coarsening = Coarsening { min_nodes: 8, max_levels: 10, min_reduction: 0.1, seed: 2023 };
+        let levels = coarsening.build(&graph);
+        assert!(levels.len() >= 2);
+
+        let mut num_nodes = graph.len();
+        for level in levels.iter() {
+            assert_eq!(level.membership.len(), num_nodes);
+            assert!(level.graph.len() < num_nodes);
+            assert!(level.membership.iter().all(|g| *g < level.graph.len()));
+            num_nodes = level.graph.len();
+        }
+
+        let coarsening = Coarsening { min_nodes: 64, ..coarsening };
+        assert!(coarsening.build(&graph).is_empty());
+    }
+
+    #[test]
+    fn test_multi_level() {
+        let graph = build_ring();
+        let mut features = FeatureStore::new(graph.len());
+        features.fill_missing_nodes();
+
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            d_model: 5,
+            valid_pct: 0.0,
+            passes: 2,
+            seed: 202220222,
+            ..EmbeddingPropagation::default()
+        };
+
+        let multi_level = MultiLevel {
+            coarsening: Coarsening { min_nodes: 8, max_levels: 3, min_reduction: 0.1, seed: 2023 },
+            fine_tune_passes: 1
+        };
+        let embeddings = multi_level.learn(&ep, &graph, &features, None, &model).unwrap();
+        assert_eq!(embeddings.len(), features.num_embeddings());
+        assert!((0..embeddings.len()).all(|idx| {
+            embeddings.get_embedding(idx).iter().all(|v| v.is_finite())
+        }));
+    }
+}
@@ -0,0 +1,126 @@
+//! Parallel weakly connected components.  We use min-label propagation with atomics: every node
+//! starts with its own id as its label and, for each edge, both endpoints adopt the smaller of the
+//! two labels.  This repeats until no labels change.  Since edges are traversed in both
+//! directions, this finds weakly connected components on directed graphs as well.
+//!
+//! This is the parallel counterpart to `algos::connected`, which is single threaded.
+use std::sync::atomic::{AtomicBool,AtomicUsize,Ordering};
+
+use hashbrown::HashMap;
+use rayon::prelude::*;
+
+use crate::graph::{Graph,NodeID,Subgraph};
+
+/// Computes the component id for each node.  Component ids are dense, starting from zero, and
+/// ordered by the smallest NodeID within the component.
+pub fn connected_components(graph: &(impl Graph + Sync)) -> Vec<usize> {
+    let labels: Vec<_> = (0..graph.len()).map(|node_id| AtomicUsize::new(node_id)).collect();
+
+    loop {
+        let changed = AtomicBool::new(false);
+        (0..graph.len()).into_par_iter().for_each(|node_id| {
+            let mut label = labels[node_id].load(Ordering::Relaxed);
+            for out_node in graph.get_edges(node_id).0.iter() {
+                label = label.min(labels[*out_node].load(Ordering::Relaxed));
+            }
+
+            // Push the smallest label to ourselves and all of our neighbors
+            if labels[node_id].fetch_min(label, Ordering::Relaxed) > label {
+                changed.store(true, Ordering::Relaxed);
+            }
+            for out_node in graph.get_edges(node_id).0.iter() {
+                if labels[*out_node].fetch_min(label, Ordering::Relaxed) > label {
+                    changed.store(true, Ordering::Relaxed);
+                }
+            }
+        });
+
+        if !changed.load(Ordering::Relaxed) {
+            break
+        }
+    }
+
+    // Compact the labels, which are the smallest node id in each component
+    let mut mapping = HashMap::new();
+    labels.into_iter().map(|label| {
+        let label = label.into_inner();
+        let next_id = mapping.len();
+        *mapping.entry(label).or_insert(next_id)
+    }).collect()
+}
+
+/// Counts the number of nodes in each component.
+pub fn component_sizes(components: &[usize]) -> Vec<usize> {
+    let n = components.iter().max().map(|c| c + 1).unwrap_or(0);
+    let mut sizes = vec![0; n];
+    components.iter().for_each(|c| sizes[*c] += 1);
+    sizes
+}
+
+/// Returns the nodes within the largest component, in NodeID order.  Ties are broken by the
+/// smallest component id.
+pub fn largest_component(graph: &(impl Graph + Sync)) -> Vec<NodeID> {
+    let components = connected_components(graph);
+    let sizes = component_sizes(&components);
+    let giant = sizes.iter().enumerate()
+        .max_by_key(|(c, size)| (**size, std::cmp::Reverse(*c)))
+        .map(|(c, _)| c);
+
+    match giant {
+        Some(giant) => components.iter().enumerate()
+            .filter(|(_, c)| **c == giant)
+            .map(|(node_id, _)| node_id)
+            .collect(),
+        None => Vec::new()
+    }
+}
+
+/// Extracts the giant component as a subgraph.  Returns the new graph along with the mapping from
+/// the new NodeIDs to the original NodeIDs.
+pub fn giant_component<G: Subgraph + Sync>(graph: &G) -> (G, Vec<NodeID>) {
+    let nodes = largest_component(graph);
+    (graph.subgraph(&nodes), nodes)
+}
+
+#[cfg(test)]
+mod components_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+
+    fn build_edges() -> Vec<(usize, usize, f32)> {
+        vec![
+            // Cluster 1, directed
+            (0, 1, 1.),
+            (2, 1, 1.),
+
+            // Cluster 2
+            (3, 4, 0.5),
+            (4, 3, 0.5),
+            (4, 5, 0.5),
+            (5, 6, 0.5),
+            (6, 5, 0.5),
+
+            // Cluster 3
+            (7, 8, 0.5),
+        ]
+    }
+
+    #[test]
+    fn test_components() {
+        let graph = CSR::construct_from_edges(build_edges(), false);
+        let components = connected_components(&graph);
+        assert_eq!(components, vec![0, 0, 0, 1, 1, 1, 1, 2, 2]);
+        assert_eq!(component_sizes(&components), vec![3, 4, 2]);
+        assert_eq!(largest_component(&graph), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_giant_component() {
+        let graph = CumCSR::convert(CSR::construct_from_edges(build_edges(), false));
+        let (subgraph, mapping) = giant_component(&graph);
+        assert_eq!(mapping, vec![3, 4, 5, 6]);
+        assert_eq!(subgraph.len(), 4);
+        assert_eq!(subgraph.edges(), 5);
+        assert_eq!(subgraph.get_edges(1).0, &[0, 2]);
+    }
+}
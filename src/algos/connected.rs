@@ -1,9 +1,8 @@
 //! Finds all connected components in the graph and returns a connected component list
 use std::collections::HashSet;
 
-use crate::NodeID;
 use crate::bitset::BitSet;
-use crate::graph::{Graph,CDFtoP};
+use crate::graph::{Graph,CDFtoP,NodeID};
 
 use crate::embeddings::EmbeddingStore;
 use crate::distance::Distance;
@@ -5,6 +5,7 @@ use std::collections::{VecDeque,BinaryHeap};
 use std::cmp::Reverse;
 use std::fmt::Write;
 
+use hashbrown::HashMap;
 use rayon::prelude::*;
 use rand::prelude::*;
 use rand_xorshift::XorShiftRng;
@@ -48,6 +49,97 @@ pub fn unweighted_walk_distance(
     distance
 }
 
+/// Computes the number of hops between nodes `a` and `b` using bidirectional BFS, assuming the
+/// graph is undirected (i.e. every edge has a reciprocal edge).  Returns None if the nodes are
+/// more than `max_hops` apart.  This only explores the local neighborhoods of each node, making it
+/// cheap enough to use for "2nd degree connection" style queries.
+pub fn hop_distance_upper_bound(
+    graph: &impl Graph,
+    a: NodeID,
+    b: NodeID,
+    max_hops: usize
+) -> Option<usize> {
+    bidirectional_hops(graph, graph, a, b, max_hops)
+}
+
+/// Directed version of hop_distance_upper_bound, which requires the transpose of the graph to
+/// search backward from `b`.
+pub fn directed_hop_distance_upper_bound(
+    graph: &impl Graph,
+    transpose: &impl Graph,
+    a: NodeID,
+    b: NodeID,
+    max_hops: usize
+) -> Option<usize> {
+    bidirectional_hops(graph, transpose, a, b, max_hops)
+}
+
+fn bidirectional_hops(
+    forward: &impl Graph,
+    backward: &impl Graph,
+    a: NodeID,
+    b: NodeID,
+    max_hops: usize
+) -> Option<usize> {
+    if a == b {
+        return Some(0)
+    }
+
+    let mut seen_f = HashMap::new();
+    let mut seen_b = HashMap::new();
+    seen_f.insert(a, 0usize);
+    seen_b.insert(b, 0usize);
+    let mut frontier_f = vec![a];
+    let mut frontier_b = vec![b];
+    let mut depth_f = 0;
+    let mut depth_b = 0;
+
+    while depth_f + depth_b < max_hops && frontier_f.len() > 0 && frontier_b.len() > 0 {
+        // Always expand the smaller frontier.  We expand the full level before checking for a
+        // meeting point to guarantee we return the shortest path.
+        let best = if frontier_f.len() <= frontier_b.len() {
+            depth_f += 1;
+            expand_frontier(forward, &mut frontier_f, &mut seen_f, &seen_b, depth_f)
+        } else {
+            depth_b += 1;
+            expand_frontier(backward, &mut frontier_b, &mut seen_b, &seen_f, depth_b)
+        };
+
+        if best.is_some() {
+            return best
+        }
+    }
+    None
+}
+
+/// Expands the frontier by one level, returning the shortest path length if we found a node seen
+/// by the other search.
+fn expand_frontier(
+    graph: &impl Graph,
+    frontier: &mut Vec<NodeID>,
+    seen: &mut HashMap<NodeID, usize>,
+    other: &HashMap<NodeID, usize>,
+    depth: usize
+) -> Option<usize> {
+    let mut best: Option<usize> = None;
+    let mut next = Vec::new();
+    for node in frontier.iter() {
+        for out_edge in graph.get_edges(*node).0.iter() {
+            if seen.contains_key(out_edge) {
+                continue
+            }
+            seen.insert(*out_edge, depth);
+            if let Some(o_depth) = other.get(out_edge) {
+                let d = depth + *o_depth;
+                best = Some(best.map(|b| b.min(d)).unwrap_or(d));
+            }
+            next.push(*out_edge);
+        }
+    }
+    *frontier = next;
+    best
+}
+
 /// Finds the top K nodes by degree.  Uses NodeID as a tie breaker.
 /// TODO: We should use the TopK struct from ANN and refactor this away.
 fn top_k_nodes(
@@ -133,6 +225,33 @@ mod dist_tests {
         assert_eq!(distances, vec![255, 255, 255, 0]);
     }
 
+    #[test]
+    fn test_hop_distance() {
+        // Undirected line graph 0 - 1 - 2 - 3 - 4
+        let mut edges = Vec::new();
+        for i in 0..4 {
+            edges.push((i, i + 1, 1.));
+            edges.push((i + 1, i, 1.));
+        }
+        let csr = CSR::construct_from_edges(edges, false);
+        assert_eq!(hop_distance_upper_bound(&csr, 2, 2, 3), Some(0));
+        assert_eq!(hop_distance_upper_bound(&csr, 0, 1, 3), Some(1));
+        assert_eq!(hop_distance_upper_bound(&csr, 0, 2, 3), Some(2));
+        assert_eq!(hop_distance_upper_bound(&csr, 0, 4, 4), Some(4));
+        assert_eq!(hop_distance_upper_bound(&csr, 0, 4, 3), None);
+    }
+
+    #[test]
+    fn test_directed_hop_distance() {
+        let edges = build_edges();
+        let transpose: Vec<_> = edges.iter().map(|(f, t, w)| (*t, *f, *w)).collect();
+        let csr = CSR::construct_from_edges(edges, false);
+        let t_csr = CSR::construct_from_edges(transpose, false);
+        assert_eq!(directed_hop_distance_upper_bound(&csr, &t_csr, 0, 3, 5), Some(3));
+        assert_eq!(directed_hop_distance_upper_bound(&csr, &t_csr, 3, 0, 5), None);
+        assert_eq!(directed_hop_distance_upper_bound(&csr, &t_csr, 2, 1, 5), Some(2));
+    }
+
     #[test]
     fn test_top_k() {
         let edges = build_edges();
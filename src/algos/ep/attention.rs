@@ -56,7 +56,7 @@ impl MultiHeadedAttention {
         emb.slice(start, self.d_k)
     }
 
-    fn get_value_vec(&self, emb: &ANode, head_num: usize) -> ANode {
+    pub(crate) fn get_value_vec(&self, emb: &ANode, head_num: usize) -> ANode {
         let query_key_size = self.num_heads * self.d_k * 2;
         let v = emb.value().len();
         let d_model = ((v - query_key_size) as f32 / self.num_heads as f32) as usize;
@@ -64,6 +64,24 @@ impl MultiHeadedAttention {
         emb.slice(start, d_model)
     }
 
+    // Plain f32 counterparts of the above, for scoring without the compute graph
+    fn query_values<'a>(&self, emb: &'a [f32], head_num: usize) -> &'a [f32] {
+        let start = self.d_k * head_num;
+        &emb[start..start + self.d_k]
+    }
+
+    fn key_values<'a>(&self, emb: &'a [f32], head_num: usize) -> &'a [f32] {
+        let start = (self.num_heads * self.d_k) + self.d_k * head_num;
+        &emb[start..start + self.d_k]
+    }
+
+    pub(crate) fn value_values<'a>(&self, emb: &'a [f32], head_num: usize) -> &'a [f32] {
+        let query_key_size = self.num_heads * self.d_k * 2;
+        let d_model = (emb.len() - query_key_size) / self.num_heads;
+        let start = query_key_size + d_model * head_num;
+        &emb[start..start + d_model]
+    }
+
 }
 
 /// Struct for easy access of the different pieces of the vector.
@@ -130,6 +148,122 @@ pub fn attention_mean<'a>(
     averages.sum_all() / (mha.num_heads as f32)
 }
 
+/// Share of the output each item receives, in item order, averaged over heads.  attention_mean
+/// averages the attention weighted values over every row, so an item's share is the mean of its
+/// column in the softmaxed attention matrix and the shares sum to 1.  Consumes the rng the same
+/// way as attention_mean, so random attention attends to the same features given the same seed.
+pub fn attention_weights<'a>(
+    it: impl Iterator<Item=&'a (ANode, f32)>,
+    mha: &MultiHeadedAttention,
+    rng: &mut impl Rng
+) -> Vec<f32> {
+    let features = it.collect::<Vec<_>>();
+    if features.len() <= 1 {
+        return vec![1f32; features.len()]
+    }
+
+    let mut weights = vec![0f32; features.len()];
+    for head in 0..mha.num_heads {
+        let items: Vec<_> = features.iter().map(|(node, count)| {
+            (Attention::new(node, mha, head), *count)
+        }).collect();
+
+        let attention_matrix = compute_attention_matrix(&items, &mha.attention_type, rng);
+        let sm_att_mat = compute_attention_softmax(attention_matrix, mha.d_k);
+        sm_att_mat.iter().for_each(|row| {
+            row.iter().zip(weights.iter_mut()).for_each(|(v, w)| {
+                if let Some(v) = v {
+                    *w += v.value()[0];
+                }
+            });
+        });
+    }
+
+    let denom = (features.len() * mha.num_heads) as f32;
+    weights.iter_mut().for_each(|w| *w /= denom);
+    weights
+}
+
+/// Plain f32 counterpart of attention_mean, which never builds the compute graph.  Items are
+/// feature embeddings and their counts.  Consumes the rng the same way as attention_mean, but
+/// uses the exact softmax rather than the approximation used in training.
+pub fn attention_mean_values(
+    items: &[(&[f32], f32)],
+    mha: &MultiHeadedAttention,
+    rng: &mut impl Rng
+) -> Vec<f32> {
+    if items.len() == 1 {
+        return mha.value_values(items[0].0, 0).to_vec()
+    }
+
+    let d_model = mha.value_values(items[0].0, 0).len();
+    let mut output = vec![0f32; d_model];
+    let d_k = (mha.d_k as f32).sqrt();
+    for head in 0..mha.num_heads {
+        let (rows, scale) = attention_rows(items, mha, head, rng);
+        for mut row in rows.into_iter() {
+            let mut weights: Vec<_> = row.iter().map(|(_, d)| d / d_k).collect();
+            softmax_values(&mut weights);
+            row.iter_mut().zip(weights.into_iter()).for_each(|((_, d), w)| *d = w);
+            for (j, w) in row {
+                let value = mha.value_values(items[j].0, head);
+                output.iter_mut().zip(value.iter()).for_each(|(oi, vi)| *oi += w * scale * vi);
+            }
+        }
+    }
+
+    let denom = (items.len() * mha.num_heads) as f32;
+    output.iter_mut().for_each(|oi| *oi /= denom);
+    output
+}
+
+// Unnormalized attention scores of each row as (column, score) pairs, following the attention
+// type as in compute_attention_matrix, along with the scale applied to the values.
+fn attention_rows(
+    items: &[(&[f32], f32)],
+    mha: &MultiHeadedAttention,
+    head: usize,
+    rng: &mut impl Rng
+) -> (Vec<Vec<(usize, f32)>>, f32) {
+    let n = items.len();
+    let dot = |i: usize, j: usize| {
+        mha.query_values(items[i].0, head).iter()
+            .zip(mha.key_values(items[j].0, head).iter())
+            .map(|(q, k)| q * k)
+            .sum::<f32>()
+    };
+
+    match &mha.attention_type {
+        AttentionType::Full => {
+            let rows = (0..n).map(|i| {
+                (0..n).map(|j| {
+                    let num = items[i].1 * items[j].1;
+                    (j, if num >= 1f32 { dot(i, j) * num } else { dot(i, j) })
+                }).collect::<Vec<_>>()
+            }).collect();
+            (rows, 1f32)
+        },
+        AttentionType::Sliding { window_size } => {
+            let rows = (0..n).map(|i| {
+                let (start, stop) = (i.saturating_sub(*window_size), (i + window_size + 1).min(n));
+                (start..stop).map(|j| (j, dot(i, j))).collect::<Vec<_>>()
+            }).collect();
+            (rows, 1f32)
+        },
+        AttentionType::Random { num_features } => {
+            // Queries and keys are both scaled in training
+            let (k, scale) = num_features.sample(n, true, rng)
+                .expect("num_features should be validated by Model::check!");
+            let mut buff = vec![0; k];
+            let rows = (0..n).map(|i| {
+                (0..n).choose_multiple_fill(rng, buff.as_mut_slice());
+                buff.iter().map(|j| (*j, scale * scale * dot(i, *j))).collect::<Vec<_>>()
+            }).collect();
+            (rows, scale)
+        }
+    }
+}
+
 /// Computes value level attention scaling.
 fn scale_vecs<'a>(
     items: Vec<(Attention, f32)>, 
@@ -225,7 +359,8 @@ fn compute_random_attention_matrix(
 ) -> AttentionMatrix {
     
     // Sample K features and scale
-    let (k, scale) = sample.sample(items.len(), true, rng);
+    let (k, scale) = sample.sample(items.len(), true, rng)
+        .expect("num_features should be validated by Model::check!");
     let items = items.iter()
         .map(|(at, w)| (at.scale(scale), w))
         .collect::<Vec<_>>();
@@ -295,6 +430,16 @@ pub fn softmax(numers: ANode, exact: bool) -> ANode {
     &n / n.sum()
 }
 
+/// Exact softmax, in place, of plain values
+pub fn softmax_values(xs: &mut [f32]) {
+    let max_value = xs.iter().cloned()
+        .max_by_key(|v| FloatOrd(*v))
+        .unwrap_or(0f32);
+    xs.iter_mut().for_each(|x| *x = (*x - max_value).exp());
+    let total: f32 = xs.iter().sum();
+    xs.iter_mut().for_each(|x| *x /= total);
+}
+
 #[cfg(test)]
 mod attention_tests {
     use super::*;
@@ -398,6 +543,54 @@ mod attention_tests {
 
     }
 
+    #[test]
+    fn test_attention_weights() {
+        let mha = MultiHeadedAttention::new(1, 1, AttentionType::Full);
+        let feats = vec![
+            (Variable::new(vec![-1., -1., 1., 1.]), 1f32),
+            (Variable::new(vec![0., 0., 2., 2.]), 1f32),
+            (Variable::new(vec![1., 1., -1., -1.]), 1f32)
+        ];
+
+        // Column means of the softmax matrix in test_att_softmax
+        let mut rng = XorShiftRng::seed_from_u64(0);
+        let weights = attention_weights(feats.iter(), &mha, &mut rng);
+        let exp_weights = [
+            (0.66524096 + 1./3. + 0.09003057) / 3.,
+            (0.24472847 + 1./3. + 0.24472847) / 3.,
+            (0.09003057 + 1./3. + 0.66524096) / 3.
+        ];
+        for (w, ew) in weights.iter().zip(exp_weights.iter()) {
+            assert!((w - ew).abs() < 1e-5);
+        }
+        assert!((weights.iter().sum::<f32>() - 1.).abs() < 1e-5);
+
+        assert_eq!(attention_weights(feats[..1].iter(), &mha, &mut rng), vec![1.]);
+    }
+
+    #[test]
+    fn test_attention_mean_values() {
+        let mha = MultiHeadedAttention::new(1, 1, AttentionType::Full);
+        let embs = [vec![-1., -1., 1., 1.], vec![0., 0., 2., 2.], vec![1., 1., -1., -1.]];
+        let items: Vec<_> = embs.iter().map(|e| (e.as_slice(), 1f32)).collect();
+
+        // Mean of the reweighted rows in test_att_reweighted
+        let mut rng = XorShiftRng::seed_from_u64(0);
+        let mean = attention_mean_values(&items, &mha, &mut rng);
+        let expected = (1.0647 + 0.6667 - 0.0858) / 3.;
+        for mi in mean.iter() {
+            assert!((mi - expected).abs() < 1e-3);
+        }
+
+        let feats: Vec<_> = embs.iter().map(|e| (Variable::new(e.clone()), 1f32)).collect();
+        let graph_mean = attention_mean(feats.iter(), &mha, &mut rng);
+        for (mi, gi) in mean.iter().zip(graph_mean.value().iter()) {
+            assert!((mi - gi).abs() < 1e-3);
+        }
+
+        assert_eq!(attention_mean_values(&items[..1], &mha, &mut rng), vec![1., 1.]);
+    }
+
     #[test]
     fn test_att_reweighted() {
         let feats = create_att_vecs();
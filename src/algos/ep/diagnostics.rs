@@ -0,0 +1,136 @@
+//! Per pass health checks of the feature embeddings.  A single bad gradient can write NaNs into a
+//! row, which then spread to every node referencing the feature without the loss necessarily
+//! showing it.  After each pass we summarize the row norms, count all-zero and non-finite rows,
+//! and measure how many features were updated at all, optionally repairing the non-finite rows.
+use std::sync::atomic::{AtomicBool,Ordering};
+
+use float_ord::FloatOrd;
+use rayon::prelude::*;
+
+use crate::embeddings::EmbeddingStore;
+
+/// Percentiles of the row norms reported in `EmbeddingHealth::norm_percentiles`
+pub const NORM_PERCENTILES: [f32; 5] = [0.01, 0.25, 0.5, 0.75, 0.99];
+
+/// Diagnostics configuration
+#[derive(Clone,Copy,Debug,Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Diagnostics {
+    /// If true, rows with NaN or infinite values are re-randomized and their optimizer moments
+    /// reset, rather than only reported
+    pub repair: bool
+}
+
+/// Health of the feature embeddings after a pass
+#[derive(Clone,Debug,PartialEq)]
+pub struct EmbeddingHealth {
+    /// Pass the embeddings were checked after
+    pub pass: usize,
+
+    /// L2 norms of the finite rows at each of `NORM_PERCENTILES`, or zeros if there are none
+    pub norm_percentiles: [f32; 5],
+
+    /// Rows which are entirely zero
+    pub zero_rows: usize,
+
+    /// Rows with at least one NaN or infinite value, before any repair
+    pub nonfinite_rows: usize,
+
+    /// Non-finite rows which were re-randomized
+    pub repaired_rows: usize,
+
+    /// Fraction of the features updated during the pass, if tracked
+    pub coverage: Option<f32>
+}
+
+impl EmbeddingHealth {
+
+    /// Checks the embeddings.  `touched` flags the features updated since the last check.
+    pub fn compute(
+        pass: usize,
+        embeddings: &EmbeddingStore,
+        touched: Option<&[AtomicBool]>
+    ) -> Self {
+        let rows: Vec<_> = (0..embeddings.len()).into_par_iter().map(|idx| {
+            let e = embeddings.get_embedding(idx);
+            if e.iter().any(|ei| !ei.is_finite()) {
+                None
+            } else {
+                let norm = e.iter().map(|ei| ei * ei).sum::<f32>().sqrt();
+                Some((e.iter().all(|ei| *ei == 0f32), norm))
+            }
+        }).collect();
+
+        let nonfinite_rows = rows.iter().filter(|r| r.is_none()).count();
+        let zero_rows = rows.iter().filter(|r| matches!(r, Some((true, _)))).count();
+        let mut norms: Vec<_> = rows.into_iter().flatten().map(|(_, norm)| norm).collect();
+        norms.par_sort_unstable_by_key(|n| FloatOrd(*n));
+
+        let mut norm_percentiles = [0f32; 5];
+        if norms.len() > 0 {
+            norm_percentiles.iter_mut().zip(NORM_PERCENTILES.iter()).for_each(|(np, p)| {
+                let idx = ((norms.len() - 1) as f32 * p).round() as usize;
+                *np = norms[idx];
+            });
+        }
+
+        let coverage = touched.map(|touched| {
+            let count = touched.par_iter().filter(|t| t.load(Ordering::Relaxed)).count();
+            count as f32 / touched.len().max(1) as f32
+        });
+
+        EmbeddingHealth {
+            pass,
+            norm_percentiles,
+            zero_rows,
+            nonfinite_rows,
+            repaired_rows: 0,
+            coverage
+        }
+    }
+
+    /// Whether every row is finite
+    pub fn is_healthy(&self) -> bool {
+        self.nonfinite_rows == self.repaired_rows
+    }
+}
+
+/// Rows with at least one NaN or infinite value
+pub fn nonfinite_rows(embeddings: &EmbeddingStore) -> Vec<usize> {
+    (0..embeddings.len()).into_par_iter()
+        .filter(|idx| embeddings.get_embedding(*idx).iter().any(|ei| !ei.is_finite()))
+        .collect()
+}
+
+// Marks features as updated
+pub(crate) fn mark_touched<'a>(touched: &[AtomicBool], feat_ids: impl Iterator<Item=&'a usize>) {
+    feat_ids.for_each(|feat_id| touched[*feat_id].store(true, Ordering::Relaxed));
+}
+
+#[cfg(test)]
+mod diagnostics_tests {
+    use super::*;
+    use crate::distance::Distance;
+
+    #[test]
+    fn test_health() {
+        let mut es = EmbeddingStore::new(5, 2, Distance::Cosine);
+        es.set_embedding(0, &[3., 4.]);
+        es.set_embedding(1, &[1., 0.]);
+        es.set_embedding(2, &[f32::NAN, 1.]);
+        es.set_embedding(3, &[0., f32::INFINITY]);
+
+        let touched: Vec<_> = (0..5).map(|idx| AtomicBool::new(idx < 2)).collect();
+        let health = EmbeddingHealth::compute(1, &es, Some(&touched));
+        assert_eq!(health.nonfinite_rows, 2);
+        assert_eq!(health.zero_rows, 1);
+        assert_eq!(health.norm_percentiles, [0., 1., 1., 5., 5.]);
+        assert_eq!(health.coverage, Some(0.4));
+        assert!(!health.is_healthy());
+        assert_eq!(nonfinite_rows(&es), vec![2, 3]);
+
+        mark_touched(&touched, [4].iter());
+        assert_eq!(EmbeddingHealth::compute(1, &es, Some(&touched)).coverage, Some(0.6));
+        assert_eq!(EmbeddingHealth::compute(1, &es, None).coverage, None);
+    }
+}
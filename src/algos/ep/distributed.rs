@@ -0,0 +1,581 @@
+//! Distributed EP training through a parameter server.  A single machine caps out well short of
+//! our largest graphs, but the feature embeddings, unlike node embeddings, are small enough to
+//! live in one place.  The server owns the feature embeddings and the optimizer state; each worker
+//! trains on its own partition of the graph, see `algos::partition`, with a local copy of the
+//! feature embeddings.  Workers send each batch's gradients to the server, which applies them and
+//! replies with the updated embeddings of the touched features, and they pull the full table at
+//! the start of every pass to pick up everyone else's updates.
+//!
+//! The protocol is a minimal binary one over TCP, with little endian numbers:
+//!
+//! * Pull: `[1]`, answered with the number of embeddings and dims as u64s, then every embedding.
+//! * Push: `[2]`, alpha and t as f32s, the number of gradients as a u64, then each feature id as a
+//!   u64 followed by its gradient.  Answered with the updated embedding of each feature, in order.
+//! * Done: `[3]`, answered with `[1]`.  The server exits once every worker is done.
+//! * Hello: `[4]` and the worker's config hash as a u64, answered with `[1]` if it matches the
+//!   server's and `[0]` otherwise.  Sent once when connecting, so a misconfigured worker can't
+//!   train against the shared embeddings with different hyperparameters; the server drops
+//!   connections which send any other op first.
+//!
+//! The server is unauthenticated and unencrypted: anyone who can reach its port can read the
+//! embeddings and push arbitrary updates.  Only bind it to interfaces on a trusted network.
+use std::collections::{HashMap as CHashMap};
+use std::io::{BufReader,BufWriter,Error,ErrorKind,Read,Result as IOResult,Write};
+use std::net::{TcpListener,TcpStream,ToSocketAddrs};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize,Ordering};
+
+use crate::embeddings::EmbeddingStore;
+use crate::distance::Distance;
+use crate::feature_store::FeatureStore;
+use crate::graph::Graph as CGraph;
+use crate::resources::ResourceTracker;
+use crate::runtime::Runtime;
+use crate::error::{GraphLibError,check_dims};
+use crate::algos::grad_utils::optimizer::{Optimizer,AdamOptimizer};
+use crate::algos::utils::SplitRng;
+
+use super::{EmbeddingPropagation,LRScheduler,RemoteUpdates,randomize_embedding_store};
+use super::model::Model;
+use super::state::TrainingState;
+
+const OP_PULL: u8 = 1;
+const OP_PUSH: u8 = 2;
+const OP_DONE: u8 = 3;
+const OP_HELLO: u8 = 4;
+
+/// Largest push the server will read, in bytes of feature ids and gradients
+const MAX_PUSH_BYTES: usize = 1 << 30;
+
+/// Owns the shared feature embeddings and applies the workers' gradients with Adam.  Updates from
+/// different workers are applied concurrently, hogwild style.
+pub struct ParameterServer {
+    ep: EmbeddingPropagation,
+    feature_embeddings: EmbeddingStore,
+    optimizer: AdamOptimizer,
+
+    // Furthest pass any worker has reached, and the number of pushes applied
+    pass: AtomicUsize,
+    step: AtomicUsize,
+
+    // Schedule and random stream restored from a checkpoint, carried into the next one
+    lr_scheduler: Option<LRScheduler>,
+    resumed_rng: Option<(usize, u64)>
+}
+
+impl ParameterServer {
+
+    /// Creates a server for the feature embeddings of `features` under `model`.  If no feature
+    /// embeddings are provided, they're randomly initialized as `EmbeddingPropagation::learn`
+    /// would.
+    pub fn new<M: Model>(
+        ep: &EmbeddingPropagation,
+        features: &FeatureStore,
+        feature_embeddings: Option<EmbeddingStore>,
+        model: &M
+    ) -> Result<Self, GraphLibError> {
+        let dims = model.feature_dims(ep.d_model);
+        let feature_embeddings = match feature_embeddings {
+            Some(fe) => {
+                check_dims(dims, fe.dims())?;
+                if fe.len() < features.num_embeddings() {
+                    return Err(GraphLibError::DimensionMismatch {
+                        expected: features.num_embeddings(),
+                        found: fe.len()
+                    })
+                }
+                fe
+            },
+            None => {
+                let mut fe = EmbeddingStore::new(features.num_embeddings(), dims, Distance::Cosine);
+                randomize_embedding_store(&mut fe, &mut SplitRng::new(ep.seed, ep.rng_kind).rng());
+                fe
+            }
+        };
+
+        let optimizer = AdamOptimizer::new(0.9, 0.999, feature_embeddings.dims(),
+                                           feature_embeddings.len());
+        Ok(ParameterServer::with_optimizer(ep, feature_embeddings, optimizer, 0, 0))
+    }
+
+    /// Resumes serving from a checkpoint: the feature embeddings and training state returned by
+    /// a previous `serve`.  The state's learning rate schedule and random stream are carried
+    /// into the checkpoints this server returns.  Fails if the state was trained with a
+    /// different config or doesn't match the feature embeddings.
+    pub fn resume(
+        ep: &EmbeddingPropagation,
+        feature_embeddings: EmbeddingStore,
+        state: &TrainingState
+    ) -> Result<Self, GraphLibError> {
+        state.check_config(ep)?;
+        state.check_embeddings(&feature_embeddings)?;
+        let optimizer = AdamOptimizer::from_state(&state.optimizer)?;
+        let mut server = ParameterServer::with_optimizer(ep, feature_embeddings, optimizer,
+                                                         state.pass, state.step);
+        server.lr_scheduler = state.lr_scheduler.clone();
+        server.resumed_rng = Some((state.pass, state.rng_seed));
+        Ok(server)
+    }
+
+    fn with_optimizer(
+        ep: &EmbeddingPropagation,
+        feature_embeddings: EmbeddingStore,
+        optimizer: AdamOptimizer,
+        pass: usize,
+        step: usize
+    ) -> Self {
+        // Only the hyperparameters are needed, not any per node data
+        let ep = EmbeddingPropagation {
+            negative_pools: None,
+            frozen_features: None,
+            node_weights: None,
+            ..ep.clone()
+        };
+        ParameterServer {
+            ep,
+            feature_embeddings,
+            optimizer,
+            pass: AtomicUsize::new(pass),
+            step: AtomicUsize::new(step),
+            lr_scheduler: None,
+            resumed_rng: None
+        }
+    }
+
+    /// Serves `num_workers` workers, one thread each, returning the feature embeddings once all
+    /// of them are done, along with the training state to resume from.  Fails if a worker sends a
+    /// malformed request, has a different config, or drops its connection.  There's no
+    /// authentication, so the listener should only be reachable from the workers.
+    pub fn serve(
+        self,
+        listener: &TcpListener,
+        num_workers: usize
+    ) -> Result<(EmbeddingStore, TrainingState), GraphLibError> {
+        let results: Vec<IOResult<()>> = std::thread::scope(|s| {
+            let mut handles = Vec::with_capacity(num_workers);
+            for _ in 0..num_workers {
+                match listener.accept() {
+                    Ok((stream, _)) => handles.push(s.spawn(|| self.handle(stream))),
+                    Err(e) => return vec![Err(e)]
+                }
+            }
+            handles.into_iter()
+                .map(|h| h.join().unwrap_or_else(|_| {
+                    Err(Error::new(ErrorKind::Other, "Worker handler panicked!"))
+                }))
+                .collect()
+        });
+
+        results.into_iter().collect::<IOResult<Vec<_>>>()?;
+        let state = self.checkpoint();
+        Ok((self.feature_embeddings, state))
+    }
+
+    /// Snapshot of the training state.  Workers compute their own learning rate schedules, so
+    /// none is recorded unless one was restored by `resume`.
+    pub fn checkpoint(&self) -> TrainingState {
+        let pass = self.pass.load(Ordering::Relaxed);
+        let mut state = TrainingState::new(&self.ep, pass, self.step.load(Ordering::Relaxed),
+                                           self.optimizer.state(), self.lr_scheduler.clone());
+
+        // Until a worker starts a new pass, the restored stream still applies
+        if let Some((resumed_pass, rng_seed)) = self.resumed_rng {
+            if resumed_pass == pass {
+                state.rng_seed = rng_seed;
+            }
+        }
+        state
+    }
+
+    // Answers a single worker's requests until it's done
+    fn handle(&self, stream: TcpStream) -> IOResult<()> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+        let fe = &self.feature_embeddings;
+        // Workers must prove they share the server's config before anything else
+        let mut greeted = false;
+        loop {
+            let op = read_u8(&mut reader)?;
+            if !greeted && op != OP_HELLO {
+                return Err(protocol_error(&format!("Op {} sent before hello", op)))
+            }
+            match op {
+                OP_PULL => {
+                    write_u64(&mut writer, fe.len() as u64)?;
+                    write_u64(&mut writer, fe.dims() as u64)?;
+                    for feat_id in 0..fe.len() {
+                        write_f32s(&mut writer, fe.get_embedding(feat_id))?;
+                    }
+                },
+                OP_PUSH => {
+                    let alpha = read_f32(&mut reader)?;
+                    let t = read_f32(&mut reader)?;
+                    // Each feature is pushed at most once, so larger counts are malformed and
+                    // mustn't size the allocations below
+                    let n = read_u64(&mut reader)? as usize;
+                    let frame_bytes = n.saturating_mul(8 + 4 * fe.dims());
+                    if n > fe.len() || frame_bytes > MAX_PUSH_BYTES {
+                        return Err(protocol_error(&format!("Push of {} gradients is too large", n)))
+                    }
+                    let mut feat_ids = Vec::with_capacity(n);
+                    let mut grads = CHashMap::with_capacity(n);
+                    for _ in 0..n {
+                        let feat_id = read_u64(&mut reader)? as usize;
+                        if feat_id >= fe.len() {
+                            return Err(protocol_error(&format!("Unknown feature id {}", feat_id)))
+                        }
+                        let mut grad = vec![0f32; fe.dims()];
+                        read_f32s(&mut reader, &mut grad)?;
+                        feat_ids.push(feat_id);
+                        grads.insert(feat_id, grad);
+                    }
+
+                    self.optimizer.update(fe, grads, alpha, t);
+                    self.pass.fetch_max(t as usize, Ordering::Relaxed);
+                    self.step.fetch_add(1, Ordering::Relaxed);
+                    for feat_id in feat_ids {
+                        write_f32s(&mut writer, fe.get_embedding(feat_id))?;
+                    }
+                },
+                OP_DONE => {
+                    writer.write_all(&[1])?;
+                    writer.flush()?;
+                    return Ok(())
+                },
+                OP_HELLO => {
+                    let matches = read_u64(&mut reader)? == self.ep.config_hash();
+                    writer.write_all(&[matches as u8])?;
+                    writer.flush()?;
+                    if !matches {
+                        return Err(protocol_error("Worker config doesn't match the server's"))
+                    }
+                    greeted = true;
+                },
+                op => return Err(protocol_error(&format!("Unknown op {}", op)))
+            }
+            writer.flush()?;
+        }
+    }
+}
+
+/// A worker's connection to the parameter server.  Requests from the training threads are
+/// serialized over the single connection.
+pub struct ParameterClient {
+    conn: Mutex<(BufReader<TcpStream>, BufWriter<TcpStream>)>
+}
+
+impl ParameterClient {
+
+    /// Connects to the server, failing if the server was started with a different config
+    pub fn connect(
+        addr: impl ToSocketAddrs,
+        ep: &EmbeddingPropagation
+    ) -> Result<Self, GraphLibError> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::new(stream);
+
+        writer.write_all(&[OP_HELLO])?;
+        write_u64(&mut writer, ep.config_hash())?;
+        writer.flush()?;
+        if read_u8(&mut reader)? != 1 {
+            return Err("Worker config doesn't match the parameter server's!".into())
+        }
+        Ok(ParameterClient { conn: Mutex::new((reader, writer)) })
+    }
+
+    /// Fetches the current feature embeddings
+    pub fn pull(&self) -> Result<EmbeddingStore, GraphLibError> {
+        let mut conn = self.conn.lock().expect("Mutex poisoned!");
+        let (reader, writer) = &mut *conn;
+        writer.write_all(&[OP_PULL])?;
+        writer.flush()?;
+
+        let len = read_u64(reader)? as usize;
+        let dims = read_u64(reader)? as usize;
+        let mut values = vec![0f32; len * dims];
+        read_f32s(reader, &mut values)?;
+        EmbeddingStore::new_with_vec(len, dims, Distance::Cosine, values)
+            .ok_or_else(|| protocol_error("Bad feature embeddings").into())
+    }
+
+    /// Tells the server this worker has finished training
+    pub fn done(&self) -> Result<(), GraphLibError> {
+        let mut conn = self.conn.lock().expect("Mutex poisoned!");
+        let (reader, writer) = &mut *conn;
+        writer.write_all(&[OP_DONE])?;
+        writer.flush()?;
+        read_u8(reader)?;
+        Ok(())
+    }
+}
+
+impl RemoteUpdates for ParameterClient {
+    fn refresh(&self, feature_embeddings: &EmbeddingStore) -> Result<(), GraphLibError> {
+        let shared = self.pull()?;
+        check_dims(feature_embeddings.dims(), shared.dims())?;
+        if shared.len() != feature_embeddings.len() {
+            return Err(GraphLibError::DimensionMismatch {
+                expected: feature_embeddings.len(),
+                found: shared.len()
+            })
+        }
+        for feat_id in 0..shared.len() {
+            feature_embeddings.get_embedding_mut_hogwild(feat_id)
+                .copy_from_slice(shared.get_embedding(feat_id));
+        }
+        Ok(())
+    }
+
+    fn push(
+        &self,
+        feature_embeddings: &EmbeddingStore,
+        grads: CHashMap<usize, Vec<f32>>,
+        alpha: f32,
+        t: f32
+    ) -> Result<(), GraphLibError> {
+        let mut conn = self.conn.lock().expect("Mutex poisoned!");
+        let (reader, writer) = &mut *conn;
+        writer.write_all(&[OP_PUSH])?;
+        write_f32s(writer, &[alpha, t])?;
+        write_u64(writer, grads.len() as u64)?;
+        let mut feat_ids = Vec::with_capacity(grads.len());
+        for (feat_id, grad) in grads.iter() {
+            check_dims(feature_embeddings.dims(), grad.len())?;
+            write_u64(writer, *feat_id as u64)?;
+            write_f32s(writer, grad)?;
+            feat_ids.push(*feat_id);
+        }
+        writer.flush()?;
+
+        for feat_id in feat_ids {
+            read_f32s(reader, feature_embeddings.get_embedding_mut_hogwild(feat_id))?;
+        }
+        Ok(())
+    }
+}
+
+impl EmbeddingPropagation {
+
+    /// Trains as one worker of a distributed run, against the parameter server at `addr`.  The
+    /// graph and features are this worker's shard, such as from `Partition::subgraphs` and
+    /// `FeatureStore::subset`, and must share the server's feature vocabulary.  Each worker
+    /// should use a different seed.  Returns the server's feature embeddings as of when this
+    /// worker finished; the server's return value reflects every worker.
+    pub fn learn_worker<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        addr: impl ToSocketAddrs,
+        graph: &G,
+        features: &FeatureStore,
+        model: &M
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        if self.asynchronous {
+            return Err("Distributed training doesn't support asynchronous updates!".into())
+        }
+
+        let client = ParameterClient::connect(addr, self)?;
+        let feature_embeddings = client.pull()?;
+        let tracker = ResourceTracker::new();
+        self.learn_feature_embeddings(graph, features, Some(feature_embeddings), model, None,
+                                      &Runtime::global(), &tracker, Some(&client), None)?;
+
+        let feature_embeddings = client.pull()?;
+        client.done()?;
+        Ok(feature_embeddings)
+    }
+}
+
+fn protocol_error(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Parameter server protocol error: {}", msg))
+}
+
+fn read_u8(r: &mut impl Read) -> IOResult<u8> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u64(r: &mut impl Read) -> IOResult<u64> {
+    let mut buf = [0u8; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_f32(r: &mut impl Read) -> IOResult<f32> {
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf)?;
+    Ok(f32::from_le_bytes(buf))
+}
+
+fn read_f32s(r: &mut impl Read, out: &mut [f32]) -> IOResult<()> {
+    for v in out.iter_mut() {
+        *v = read_f32(r)?;
+    }
+    Ok(())
+}
+
+fn write_u64(w: &mut impl Write, v: u64) -> IOResult<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_f32s(w: &mut impl Write, values: &[f32]) -> IOResult<()> {
+    values.iter().try_for_each(|v| w.write_all(&v.to_le_bytes()))
+}
+
+#[cfg(test)]
+mod distributed_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+    use crate::algos::ep::model::AveragedFeatureModel;
+    use crate::algos::partition::{Partitioning,PartitionMethod,StreamOrder};
+    use crate::algos::utils::Sample;
+
+    fn build_ep(seed: u64) -> EmbeddingPropagation {
+        EmbeddingPropagation {
+            batch_size: 2,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            seed,
+            ..EmbeddingPropagation::default()
+        }
+    }
+
+    #[test]
+    fn test_distributed() {
+        // Two 4-cliques joined by a single edge
+        let mut edges = Vec::new();
+        for clique in [0, 4] {
+            for u in clique..clique + 4 {
+                (clique..clique + 4).filter(|v| *v != u).for_each(|v| edges.push((u, v, 1f32)));
+            }
+        }
+        edges.push((3, 4, 1.));
+        edges.push((4, 3, 1.));
+        let graph = CumCSR::convert(CSR::construct_from_edges(edges, false));
+
+        let mut features = FeatureStore::new(graph.len());
+        for node_id in 0..graph.len() {
+            features.set_features(node_id, [("node", node_id.to_string())].into_iter());
+        }
+
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+        let partition = Partitioning {
+            num_parts: 2,
+            method: PartitionMethod::Ldg,
+            slack: 0.1,
+            passes: 2,
+            order: StreamOrder::Natural
+        }.partition(&graph).unwrap();
+
+        let server = ParameterServer::new(&build_ep(2023), &features, None, &model).unwrap();
+        // Clones share the underlying buffer, so snapshot the values
+        let initial = server.feature_embeddings.as_slice().to_vec();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (learned, state) = std::thread::scope(|s| {
+            let server = s.spawn(|| server.serve(&listener, 2));
+            for (part, (subgraph, nodes)) in partition.subgraphs(&graph).into_iter().enumerate() {
+                let shard = features.subset(&nodes);
+                let ep = build_ep(part as u64);
+                let model = &model;
+                s.spawn(move || {
+                    let fe = ep.learn_worker(addr, &subgraph, &shard, model).unwrap();
+                    assert_eq!(fe.len(), shard.num_embeddings());
+                });
+            }
+            server.join().unwrap().unwrap()
+        });
+
+        assert_eq!(learned.len(), features.num_embeddings());
+        let dims = learned.dims();
+        let changed = learned.as_slice().chunks(dims).zip(initial.chunks(dims))
+            .filter(|(new, old)| new != old)
+            .count();
+        assert!(changed > 0);
+
+        // The checkpoint resumes with the same config only
+        assert_eq!(state.pass, 2);
+        assert!(state.step > 0);
+        let resumed = ParameterServer::resume(&build_ep(2023), learned.clone(), &state).unwrap();
+        let restored = TrainingState {
+            rng_seed: 17, lr_scheduler: Some(LRScheduler::noop()), ..state.clone()
+        };
+        let checkpoint = ParameterServer::resume(&build_ep(2023), learned.clone(), &restored)
+            .unwrap()
+            .checkpoint();
+        assert_eq!((checkpoint.pass, checkpoint.step), (state.pass, state.step));
+        assert_eq!(checkpoint.rng_seed, 17);
+        assert!(matches!(checkpoint.lr_scheduler, Some(LRScheduler::Noop)));
+        assert_eq!(resumed.checkpoint().optimizer, state.optimizer);
+        let other = EmbeddingPropagation { alpha: 1e-3, ..build_ep(2023) };
+        assert!(ParameterServer::resume(&other, learned, &state).is_err());
+
+        let asynchronous = EmbeddingPropagation { asynchronous: true, ..build_ep(0) };
+        assert!(asynchronous.learn_worker(addr, &graph, &features, &model).is_err());
+    }
+
+    #[test]
+    fn test_config_mismatch() {
+        let mut features = FeatureStore::new(2);
+        features.fill_missing_nodes();
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+        let server = ParameterServer::new(&build_ep(2023), &features, None, &model).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::scope(|s| {
+            let server = s.spawn(|| server.serve(&listener, 1));
+            let other = EmbeddingPropagation { d_model: 8, ..build_ep(2023) };
+            assert!(ParameterClient::connect(addr, &other).is_err());
+            assert!(server.join().unwrap().is_err());
+        });
+    }
+
+    #[test]
+    fn test_oversized_push() {
+        let mut features = FeatureStore::new(2);
+        features.fill_missing_nodes();
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = build_ep(2023);
+        let server = ParameterServer::new(&ep, &features, None, &model).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::scope(|s| {
+            let server = s.spawn(|| server.serve(&listener, 1));
+            let client = ParameterClient::connect(addr, &ep).unwrap();
+            {
+                let mut conn = client.conn.lock().unwrap();
+                let writer = &mut conn.1;
+                writer.write_all(&[OP_PUSH]).unwrap();
+                write_f32s(writer, &[1e-2, 1.]).unwrap();
+                write_u64(writer, u64::MAX).unwrap();
+                writer.flush().unwrap();
+            }
+            assert!(server.join().unwrap().is_err());
+        });
+    }
+
+    #[test]
+    fn test_requires_hello() {
+        let mut features = FeatureStore::new(2);
+        features.fill_missing_nodes();
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = build_ep(2023);
+        let server = ParameterServer::new(&ep, &features, None, &model).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::scope(|s| {
+            let server = s.spawn(|| server.serve(&listener, 1));
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.write_all(&[OP_PULL]).unwrap();
+            stream.flush().unwrap();
+            assert!(server.join().unwrap().is_err());
+        });
+    }
+}
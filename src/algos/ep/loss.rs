@@ -4,14 +4,17 @@
 use simple_grad::*;
 use rand::prelude::*;
 use rand_distr::{Distribution,Uniform};
+use float_ord::FloatOrd;
 
-use crate::EmbeddingStore;
-use crate::FeatureStore;
+use crate::embeddings::EmbeddingStore;
+use crate::feature_store::FeatureStore;
 use crate::graph::{Graph as CGraph,NodeID};
+use crate::algos::utils::EdgeAliasTable;
 use super::model::*;
-use super::attention::softmax;
+use super::attention::{softmax,softmax_values};
 
 #[derive(Copy,Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Loss {
     /// This is the max margin loss with threshold that's common in embedding work.  FaceNet was
     /// one of the first to define it and a good starting point
@@ -34,7 +37,41 @@ pub enum Loss {
 
     /// This uses PPR to generate a set of candidates for optimize toward.  Should be broken out as
     /// it's fairly unique.
-    PPR(f32, usize, f32)
+    PPR(f32, usize, f32),
+
+    /// Bayesian Personalized Ranking, which maximizes the log likelihood that the reconstruction
+    /// scores higher than each negative.  Uses dot products for similarity.
+    BPR(usize),
+
+    /// Triplet loss which only optimizes the hardest negative, the one closest to the
+    /// reconstruction, rather than averaging the margin over all the negatives.  With enough
+    /// negatives this fits considerably tighter than MarginLoss.
+    HardTriplet(f32, usize)
+}
+
+/// How the losses against each positive are combined when contrasting with several
+#[derive(Copy,Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PositiveAggregation {
+    /// Averages the losses
+    Mean,
+
+    /// Smooth maximum of the losses, log(mean(exp(loss))), which focuses on the positives the
+    /// node is furthest from while still learning from the rest
+    LogSumExp
+}
+
+/// Contrasts each node against several independently constructed positives rather than one.  On
+/// graphs with noisy or diverse neighborhoods a single sampled reconstruction is a high variance
+/// target; several give a more stable one.
+#[derive(Copy,Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultiPositive {
+    /// Number of positives constructed per node
+    pub positives: usize,
+
+    /// How the per positive losses are combined
+    pub aggregation: PositiveAggregation
 }
 
 impl Loss {
@@ -45,7 +82,9 @@ impl Loss {
             Loss::StarSpace(_, negs) => *negs,
             Loss::RankLoss(_, negs) => *negs,
             Loss::RankSpace(_, negs) => *negs,
-            Loss::PPR(_, negs, _) => *negs
+            Loss::PPR(_, negs, _) => *negs,
+            Loss::BPR(negs) => *negs,
+            Loss::HardTriplet(_, negs) => *negs
         }
     }
 
@@ -152,11 +191,201 @@ impl Loss {
                 } else {
                     Constant::scalar(0f32)
                 }
+            },
+
+            Loss::BPR(_) => {
+                let pos = hv.dot(&thv);
+                let losses = hus.iter().map(|hu| {
+                    // -ln(sigmoid(x)) = ln(1 + e^-x), rearranged for negative x so the exponent
+                    // never overflows
+                    let x = &pos - hu.dot(&hv);
+                    if x.value()[0] >= 0f32 {
+                        ((-x).exp() + 1f32).ln()
+                    } else {
+                        (x.clone().exp() + 1f32).ln() - &x
+                    }
+                }).collect::<Vec<_>>();
+
+                if losses.len() > 0 {
+                    let n_losses = losses.len() as f32;
+                    losses.sum_all() / n_losses
+                } else {
+                    Constant::scalar(0f32)
+                }
+            },
+
+            Loss::HardTriplet(gamma, _) => {
+                let d1 = gamma + euclidean_distance(&thv, &hv);
+
+                // Only the closest negative contributes to the loss
+                let hardest = hus.iter()
+                    .map(|hu| euclidean_distance(&thv, hu))
+                    .min_by_key(|d| FloatOrd(d.value()[0]));
+
+                match hardest {
+                    Some(d2) => {
+                        let loss = &d1 - d2;
+                        if loss.value()[0] > 0f32 { loss } else { Constant::scalar(0f32) }
+                    },
+                    None => Constant::scalar(0f32)
+                }
+            }
+
+        }
+    }
+
+    /// Computes the loss against each positive reconstruction, with the same negatives, and
+    /// combines them
+    pub fn compute_multiple(
+        &self,
+        thvs: Vec<ANode>,
+        hv: ANode,
+        hus: &[ANode],
+        aggregation: PositiveAggregation
+    ) -> ANode {
+        let k = thvs.len();
+        let mut losses: Vec<_> = thvs.into_iter()
+            .map(|thv| self.compute(thv, hv.clone(), hus))
+            .collect();
+
+        if k == 1 {
+            return losses.pop().expect("Exactly one loss")
+        }
+
+        match aggregation {
+            PositiveAggregation::Mean => losses.sum_all() / k as f32,
+            PositiveAggregation::LogSumExp => {
+                // Shifted by the max so the exponents never overflow
+                let max = losses.iter()
+                    .map(|l| l.value()[0])
+                    .max_by_key(|l| FloatOrd(*l))
+                    .unwrap_or(0f32);
+                let exps = losses.into_iter().map(|l| (l - max).exp()).collect::<Vec<_>>();
+                (exps.sum_all() / k as f32).ln() + max
+            }
+        }
+    }
+
+    /// Plain f32 counterpart of compute, for scoring without building the compute graph.  The
+    /// rank losses use the exact softmax rather than training's approximation.
+    pub fn score(&self, thv: &[f32], hv: &[f32], hus: &[Vec<f32>]) -> f32 {
+        match self {
+
+            Loss::MarginLoss(gamma, _) | Loss::PPR(gamma, _, _) => {
+                let d1 = gamma + euclidean_values(thv, hv);
+                mean_positive(hus.iter().map(|hu| d1 - euclidean_values(thv, hu)))
+            },
+
+            Loss::RankSpace(gamma, n) => {
+                Loss::StarSpace(*gamma, *n).score(thv, hv, hus)
+                    + Loss::RankLoss(*gamma, *n).score(thv, hv, hus)
+            },
+
+            Loss::StarSpace(gamma, _) => {
+                let thv_norm = normalize_values(thv);
+                let hv_norm = normalize_values(hv);
+                let reconstruction_dist = dot_values(&thv_norm, &hv_norm);
+                mean_positive(hus.iter().map(|hu| {
+                    gamma - (reconstruction_dist - dot_values(&hv_norm, &normalize_values(hu)))
+                }))
+            },
+
+            Loss::Contrastive(pos_margin, neg_margin, _) => {
+                let thv_norm = normalize_values(thv);
+                let hv_norm = normalize_values(hv);
+                let pos_reconstruction = pos_margin - dot_values(&thv_norm, &hv_norm);
+                let margins = hus.iter()
+                    .map(|hu| dot_values(&hv_norm, &normalize_values(hu)) - neg_margin)
+                    .chain(std::iter::once(pos_reconstruction));
+                mean_positive(margins)
+            },
+
+            Loss::RankLoss(tau, _) => {
+                let mut ds: Vec<_> = hus.iter().map(|hu| dot_values(hu, hv)).collect();
+                ds.push(dot_values(hv, thv));
+                softmax_values(&mut ds);
+                let p = ds[ds.len() - 1];
+                if p < *tau { -p.ln() } else { 0f32 }
+            },
+
+            Loss::BPR(_) => {
+                let pos = dot_values(hv, thv);
+                if hus.is_empty() {
+                    return 0f32
+                }
+                let total: f32 = hus.iter().map(|hu| {
+                    let x = pos - dot_values(hu, hv);
+                    if x >= 0f32 {
+                        ((-x).exp() + 1f32).ln()
+                    } else {
+                        (x.exp() + 1f32).ln() - x
+                    }
+                }).sum();
+                total / hus.len() as f32
+            },
+
+            Loss::HardTriplet(gamma, _) => {
+                let d1 = gamma + euclidean_values(thv, hv);
+                hus.iter()
+                    .map(|hu| euclidean_values(thv, hu))
+                    .min_by_key(|d| FloatOrd(*d))
+                    .map(|d2| (d1 - d2).max(0f32))
+                    .unwrap_or(0f32)
             }
 
         }
     }
 
+    /// Plain f32 counterpart of compute_multiple
+    pub fn score_multiple(
+        &self,
+        thvs: &[Vec<f32>],
+        hv: &[f32],
+        hus: &[Vec<f32>],
+        aggregation: PositiveAggregation
+    ) -> f32 {
+        let k = thvs.len();
+        let losses: Vec<_> = thvs.iter().map(|thv| self.score(thv, hv, hus)).collect();
+        if k == 1 {
+            return losses[0]
+        }
+
+        match aggregation {
+            PositiveAggregation::Mean => losses.iter().sum::<f32>() / k as f32,
+            PositiveAggregation::LogSumExp => {
+                let max = losses.iter().cloned()
+                    .max_by_key(|l| FloatOrd(*l))
+                    .unwrap_or(0f32);
+                let exps: f32 = losses.iter().map(|l| (l - max).exp()).sum();
+                (exps / k as f32).ln() + max
+            }
+        }
+    }
+
+    /// Plain f32 counterpart of construct_positive
+    pub fn construct_positive_values<G: CGraph, R: Rng, M: Model>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        weighted: Option<&EdgeAliasTable>,
+        rng: &mut R
+    ) -> Vec<f32> {
+        match self {
+            Loss::PPR(_, num, restart_p) => {
+                let nodes = ppr_positives(graph, node, *num, *restart_p, weighted, rng);
+                model.construct_from_multiple_nodes_values(nodes.into_iter(),
+                        feature_store, feature_embeddings, rng)
+            },
+            _ => {
+                model.reconstruct_node_values(
+                    graph, node, feature_store, feature_embeddings, rng)
+            }
+        }
+    }
+
     pub fn construct_positive<G: CGraph, R: Rng, M: Model>(
         &self,
         graph: &G,
@@ -164,19 +393,12 @@ impl Loss {
         feature_store: &FeatureStore,
         feature_embeddings: &EmbeddingStore,
         model: &M,
+        weighted: Option<&EdgeAliasTable>,
         rng: &mut R
     ) -> (NodeCounts,ANode) {
         match self {
             Loss::PPR(_, num, restart_p) => {
-                let mut nodes = Vec::with_capacity(*num);
-                for _ in 0..(*num) {
-                    if let Some(node) = random_walk(node, graph, rng, *restart_p, 10) {
-                        nodes.push((node, 1f32));
-                    }
-                }
-                if nodes.len() == 0 {
-                    nodes.push((node, 1f32));
-                }
+                let nodes = ppr_positives(graph, node, *num, *restart_p, weighted, rng);
                 model.construct_from_multiple_nodes(nodes.into_iter(),
                         feature_store, feature_embeddings, rng)
             },
@@ -190,12 +412,34 @@ impl Loss {
 
 }
 
+// Endpoints of num random walks from the node, falling back to the node itself
+fn ppr_positives<R: Rng, G: CGraph>(
+    graph: &G,
+    node: NodeID,
+    num: usize,
+    restart_p: f32,
+    weighted: Option<&EdgeAliasTable>,
+    rng: &mut R
+) -> Vec<(NodeID, f32)> {
+    let mut nodes = Vec::with_capacity(num);
+    for _ in 0..num {
+        if let Some(node) = random_walk(node, graph, rng, restart_p, 10, weighted) {
+            nodes.push((node, 1f32));
+        }
+    }
+    if nodes.len() == 0 {
+        nodes.push((node, 1f32));
+    }
+    nodes
+}
+
 fn random_walk<R: Rng, G: CGraph>(
     anchor: NodeID, 
     graph: &G,
     rng: &mut R,
     restart_p: f32,
-    max_steps: usize
+    max_steps: usize,
+    weighted: Option<&EdgeAliasTable>
 ) -> Option<NodeID> {
     let anchor_edges = graph.get_edges(anchor).0;
     let mut node = anchor;
@@ -208,8 +452,7 @@ fn random_walk<R: Rng, G: CGraph>(
         if edges.len() == 0 || i > max_steps {
             break
         }
-        let dist = Uniform::new(0, edges.len());
-        node = edges[dist.sample(rng)];
+        node = edges[sample_edge(graph, node, weighted, rng)];
         // We want at least one step in our walk
         // before exiting since zero-steps guarantees an anchor
         // edge
@@ -219,12 +462,27 @@ fn random_walk<R: Rng, G: CGraph>(
     if node != anchor {
         Some(node)
     } else if anchor_edges.len() > 0 {
-        Some(anchor_edges[Uniform::new(0, anchor_edges.len()).sample(rng)])
+        Some(anchor_edges[sample_edge(graph, anchor, weighted, rng)])
     } else {
         None
     }
 }
 
+/// Selects the next edge offset for a node with edges.  When weighted, the alias tables are built
+/// from the graph's CDF weights, which is how we construct them for EP.
+fn sample_edge<R: Rng, G: CGraph>(
+    graph: &G,
+    node: NodeID,
+    weighted: Option<&EdgeAliasTable>,
+    rng: &mut R
+) -> usize {
+    match weighted {
+        Some(tables) => tables.sample_offset(graph, node, rng)
+            .expect("Only sampled from nodes with edges"),
+        None => Uniform::new(0, graph.degree(node)).sample(rng)
+    }
+}
+
 
 fn l2norm(v: ANode) -> ANode {
     v.pow(2f32).sum().pow(0.5)
@@ -242,9 +500,33 @@ fn euclidean_distance(e1: &ANode, e2: &ANode) -> ANode {
     (e1 - e2).pow(2f32).sum().pow(0.5)
 }
 
+// Plain f32 helpers for scoring
+
+fn dot_values(x1: &[f32], x2: &[f32]) -> f32 {
+    x1.iter().zip(x2.iter()).map(|(a, b)| a * b).sum()
+}
+
+fn normalize_values(v: &[f32]) -> Vec<f32> {
+    let norm = dot_values(v, v).sqrt();
+    v.iter().map(|vi| vi / norm).collect()
+}
+
+fn euclidean_values(e1: &[f32], e2: &[f32]) -> f32 {
+    e1.iter().zip(e2.iter()).map(|(a, b)| (a - b).powf(2f32)).sum::<f32>().sqrt()
+}
+
+// Mean of the positive losses, or zero if there are none
+fn mean_positive(losses: impl Iterator<Item=f32>) -> f32 {
+    let (total, n) = losses.filter(|l| *l > 0f32)
+        .fold((0f32, 0usize), |(total, n), l| (total + l, n + 1));
+    if n > 0 { total / n as f32 } else { 0f32 }
+}
+
 #[cfg(test)]
 mod ep_loss_tests {
     use super::*;
+    use rand_xorshift::XorShiftRng;
+    use crate::graph::{CSR,CumCSR};
 
     #[test]
     fn test_euclidean_dist() {
@@ -254,6 +536,115 @@ mod ep_loss_tests {
         assert_eq!(dist.value(), &[(8f32).powf(0.5)]);
     }
 
+    #[test]
+    fn test_weighted_random_walk() {
+        let edges = vec![(0, 1, 1f32), (0, 2, 99f32), (1, 0, 1f32), (2, 0, 1f32)];
+        let csr = CSR::construct_from_edges(edges, false);
+        let ccsr = CumCSR::convert(csr);
+        let tables = EdgeAliasTable::new(&ccsr);
+        let mut rng = XorShiftRng::seed_from_u64(20222022);
+        let mut counts = [0usize; 3];
+        for _ in 0..1000 {
+            // Single step walks only
+            if let Some(node) = random_walk(0, &ccsr, &mut rng, 1f32, 1, Some(&tables)) {
+                counts[node] += 1;
+            }
+        }
+        assert!(counts[2] > counts[1] * 10);
+    }
+
+    #[test]
+    fn test_bpr() {
+        let hv = Variable::new(vec![1f32, 0f32]);
+        let thv = Variable::new(vec![1f32, 0f32]);
+        let hus = vec![Variable::new(vec![0f32, 1f32]), Variable::new(vec![2f32, 0f32])];
+        let loss = Loss::BPR(2).compute(thv, hv, &hus);
+
+        // Scores of 1 - 0 and 1 - 2
+        let expected = ((1f32 + (-1f32).exp()).ln() + (1f32 + 1f32.exp()).ln()) / 2f32;
+        assert!((loss.value()[0] - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_hard_triplet() {
+        let hv = Variable::new(vec![1f32, 0f32]);
+        let thv = Variable::new(vec![0f32, 0f32]);
+        let hus = vec![Variable::new(vec![0f32, 1.5f32]), Variable::new(vec![0f32, 1.2f32])];
+
+        let loss = Loss::HardTriplet(1f32, 2).compute(thv.clone(), hv.clone(), &hus);
+        assert!((loss.value()[0] - 0.8).abs() < 1e-5);
+
+        // Margin loss averages the violations instead
+        let loss = Loss::MarginLoss(1f32, 2).compute(thv.clone(), hv.clone(), &hus);
+        assert!((loss.value()[0] - 0.65).abs() < 1e-5);
+
+        let hus = vec![Variable::new(vec![0f32, 3f32])];
+        let loss = Loss::HardTriplet(1f32, 1).compute(thv, hv, &hus);
+        assert_eq!(loss.value()[0], 0f32);
+    }
+
+    #[test]
+    fn test_multiple_positives() {
+        let hv = Variable::new(vec![1f32, 0f32]);
+        let thvs = vec![Variable::new(vec![0f32, 0f32]), Variable::new(vec![1f32, 0f32])];
+        let hus = vec![Variable::new(vec![0f32, 1.5f32])];
+        let loss = Loss::HardTriplet(1f32, 1);
+
+        // Losses of 1 + 1 - 1.5 and 1 + 0 - 1.8, clipped to 0
+        let single: Vec<_> = thvs.iter()
+            .map(|thv| loss.compute(thv.clone(), hv.clone(), &hus).value()[0])
+            .collect();
+        assert!((single[0] - 0.5).abs() < 1e-5);
+        assert_eq!(single[1], 0f32);
+
+        let mean = loss.compute_multiple(thvs.clone(), hv.clone(), &hus, PositiveAggregation::Mean);
+        assert!((mean.value()[0] - 0.25).abs() < 1e-5);
+
+        let lse = loss.compute_multiple(thvs.clone(), hv.clone(), &hus, 
+                                        PositiveAggregation::LogSumExp);
+        let expected = ((0.5f32.exp() + 1f32) / 2f32).ln();
+        assert!((lse.value()[0] - expected).abs() < 1e-5);
+        assert!(lse.value()[0] > mean.value()[0]);
+
+        let one = loss.compute_multiple(vec![thvs[0].clone()], hv, &hus, 
+                                        PositiveAggregation::LogSumExp);
+        assert_eq!(one.value(), &[single[0]]);
+    }
+
+    #[test]
+    fn test_score_matches_compute() {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut rand_vec = || (0..4).map(|_| rng.gen::<f32>() * 2f32 - 1f32).collect::<Vec<_>>();
+        let hv = rand_vec();
+        let thvs = vec![rand_vec(), rand_vec()];
+        let hus: Vec<_> = (0..5).map(|_| rand_vec()).collect();
+        let losses = [
+            Loss::MarginLoss(1f32, 5),
+            Loss::Contrastive(0.5, 0.1, 5),
+            Loss::StarSpace(0.5, 5),
+            Loss::RankLoss(0.9, 5),
+            Loss::RankSpace(0.9, 5),
+            Loss::PPR(1f32, 5, 0.5),
+            Loss::BPR(5),
+            Loss::HardTriplet(1f32, 5)
+        ];
+
+        let graph_hus: Vec<_> = hus.iter().map(|hu| Variable::new(hu.clone())).collect();
+        let graph_thvs: Vec<_> = thvs.iter().map(|thv| Variable::new(thv.clone())).collect();
+        for loss in losses.iter() {
+            for aggregation in [PositiveAggregation::Mean, PositiveAggregation::LogSumExp] {
+                let expected = loss.compute_multiple(graph_thvs.clone(), 
+                                                     Variable::new(hv.clone()), 
+                                                     &graph_hus, aggregation).value()[0];
+                let score = loss.score_multiple(&thvs, &hv, &hus, aggregation);
+                // The rank losses use an approximate softmax in training
+                assert!((score - expected).abs() < 1e-3, "{:?}: {} vs {}", loss, score, expected);
+            }
+            let score = loss.score(&thvs[0], &hv, &hus[..0]);
+            assert!(score.is_finite());
+        }
+    }
+
     #[test]
     fn test_l2norm() {
         let x = Variable::new(vec![1f32, 3f32]);
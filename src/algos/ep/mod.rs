@@ -4,39 +4,112 @@
 pub mod loss;
 pub mod model;
 pub mod attention;
+pub mod state;
+pub mod diagnostics;
+pub mod two_tower;
+pub mod tuning;
+#[cfg(feature = "distributed")]
+pub mod distributed;
 
 use std::fmt::Write;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
 
 use rayon::prelude::*;
-use hashbrown::HashMap;
+use hashbrown::{HashMap,HashSet};
 use std::collections::{HashMap as CHashMap};
 use rand::prelude::*;
 use rand_distr::StandardNormal;
-use rand_xorshift::XorShiftRng;
 use simple_grad::*;
 
 use crate::graph::{Graph as CGraph,NodeID};
 use crate::embeddings::EmbeddingStore;
 use crate::distance::Distance;
 use crate::progress::CLProgressBar;
+use crate::resources::{ResourceTracker,ResourceReport};
+use crate::runtime::Runtime;
+use crate::error::{GraphLibError,check_dims};
 use crate::feature_store::FeatureStore;
-use crate::algos::grad_utils::scheduler::LRScheduler;
 use crate::algos::grad_utils::optimizer::{Optimizer,AdamOptimizer};
 use crate::algos::grad_utils::node_sampler::*;
+use crate::algos::grad_utils::batch_size::{NoiseScaleEstimator,squared_norm};
+use crate::algos::grad_utils::precision::ExampleGrads;
+use crate::algos::utils::{SplitRng,RngKind,EdgeAliasTable};
+
+pub use crate::algos::grad_utils::batch_size::AdaptiveBatchSize;
+pub use crate::algos::grad_utils::scheduler::LRScheduler;
+pub use crate::algos::grad_utils::precision::Precision;
+pub use self::diagnostics::{Diagnostics,EmbeddingHealth};
+pub use self::loss::{MultiPositive,PositiveAggregation};
+pub use self::two_tower::TwoTowerEmbeddings;
+pub use crate::algos::grad_utils::node_sampler::{CandidatePools,DegreeBalancing};
 
 use self::loss::*;
 use self::model::{Model,NodeCounts};
+use self::state::TrainingState;
+
+// Independent random streams split from the seed, so training, validation, and noise never share
+// random numbers.
+const TRAIN_STREAM: u64 = 0;
+const VALID_STREAM: u64 = 1;
+const RANKING_STREAM: u64 = 2;
+const NOISE_STREAM: u64 = 3;
+const DELTA_STREAM: u64 = 4;
+const DIAGNOSTICS_STREAM: u64 = 5;
+const SHUFFLE_STREAM: u64 = 6;
+const HOLDOUT_STREAM: u64 = 7;
 
 #[derive(Clone,Copy,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LossWeighting {
     DegreeLog,
     DegreeExponential(f32),
     None
 }
 
+/// Ranks held out (node, neighbor) pairs from the validation nodes against sampled negatives after
+/// each pass.  Loss values aren't comparable across losses and don't track retrieval quality
+/// particularly well; mean reciprocal rank does.
+#[derive(Clone,Copy,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RankingValidation {
+    /// Number of (node, neighbor) pairs to sample from the validation nodes
+    pub num_pairs: usize,
+
+    /// Number of random negatives each neighbor is ranked against
+    pub num_negatives: usize
+}
+
+/// Feature embeddings shared with other trainers, such as through a parameter server.  Rather
+/// than applying batch gradients locally, they're sent off and the local copy is refreshed from
+/// the shared one.
+pub(crate) trait RemoteUpdates: Sync {
+    /// Overwrites the local feature embeddings with the shared ones
+    fn refresh(&self, feature_embeddings: &EmbeddingStore) -> Result<(), GraphLibError>;
+
+    /// Applies gradients to the shared feature embeddings, updating the local copies of the
+    /// touched features
+    fn push(
+        &self,
+        feature_embeddings: &EmbeddingStore,
+        grads: CHashMap<usize, Vec<f32>>,
+        alpha: f32,
+        t: f32
+    ) -> Result<(), GraphLibError>;
+}
+
+// Where a run left off, from which a TrainingState can be taken
+struct RunState {
+    pass: usize,
+    step: usize,
+    optimizer: AdamOptimizer,
+    lr_scheduler: LRScheduler
+}
+
 /// Defines the propagator
-#[derive(Debug)]
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EmbeddingPropagation {
     /// Learning rate for updating feature embeddings
     pub alpha: f32,
@@ -63,6 +136,10 @@ pub struct EmbeddingPropagation {
     /// Random seed
     pub seed: u64,
 
+    /// Generator behind every random stream drawn from the seed
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rng_kind: RngKind,
+
     /// We split out valid_pct of nodes to use for validation.
     pub valid_pct: f32,
 
@@ -70,40 +147,435 @@ pub struct EmbeddingPropagation {
     /// useful when the model overfits and validation start to diverge.
     pub noise: f32,
 
+    /// If true, positives are sampled proportionally to their edge weights rather than
+    /// uniformly from the neighborhood.  Only PPR losses sample positives, via their walks; the
+    /// other losses reconstruct the node from its neighborhood, so they reject this option.
+    pub weighted_positives: bool,
+
+    /// If provided, estimates the gradient noise scale during the first passes and adjusts the
+    /// batch size, and learning rate, accordingly.
+    pub adaptive_batch: Option<AdaptiveBatchSize>,
+
+    /// If true, each example's gradients are applied to the shared feature embeddings as soon as
+    /// they're computed, via hogwild updates, rather than aggregated and applied once per batch.
+    /// This keeps every thread busy on high core count machines at the cost of determinism.  The
+    /// learning rate is scaled down by the batch size to account for the extra updates.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub asynchronous: bool,
+
+    /// If true, sampled negatives which are actually neighbors of the anchor are replaced.  This
+    /// matters most on dense graphs, where neighbors are frequently sampled as negatives.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exclude_neighbors: bool,
+
+    /// If provided, high degree nodes are downsampled as anchors each pass, improving the
+    /// embeddings of tail nodes.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub degree_balancing: Option<DegreeBalancing>,
+
+    /// If provided, reports the mean reciprocal rank of held out edges after each pass.  Requires
+    /// a non-zero valid_pct.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ranking_validation: Option<RankingValidation>,
+
+    /// If provided, negatives are drawn from the anchor's candidate pool rather than from all
+    /// nodes.  Hard negatives are not used when pools are provided.  Pools are data rather than
+    /// configuration, so they're never read from or written to config files.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub negative_pools: Option<CandidatePools>,
+
+    /// If provided, a mask over feature ids; features marked true are never updated.  This allows
+    /// warm starting from pretrained feature embeddings, such as text tokens, and only learning
+    /// the remaining features.  See `FeatureStore::namespace_mask`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub frozen_features: Option<Vec<bool>>,
+
+    /// If provided, an importance weight per node, such as revenue or recency, which scales the
+    /// node's loss and gradients when it's the anchor.  Nodes with zero weight are never used as
+    /// anchors but can still be positives and negatives.  Validation losses are unweighted.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub node_weights: Option<Vec<f32>>,
+
+    /// Precision of the per example gradients buffered until a batch is aggregated.  BF16 halves
+    /// their memory while the feature embeddings and optimizer stay f32.  The compute graph itself
+    /// is always f32, and asynchronous training doesn't buffer gradients so it's unaffected.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub gradient_precision: Precision,
+
+    /// If provided, a feature's batch gradient is only applied once its L2 norm reaches the
+    /// threshold, sparing the optimizer's moment reads and writes for the many features with
+    /// negligible gradients on wide feature spaces.  Skipped gradients are accumulated and added
+    /// to the feature's next gradient, so small but consistent signals are still applied.  Only
+    /// synchronous batches are thresholded; residuals left over when training ends are dropped.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub gradient_threshold: Option<f32>,
+
+    /// If provided, checks the health of the feature embeddings after each pass: row norm
+    /// percentiles, all-zero and non-finite rows, and the fraction of features updated.  Non-finite
+    /// rows are re-randomized when requested.  See `EmbeddingHealth`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub diagnostics: Option<Diagnostics>,
+
+    /// If provided, each node is contrasted against several independently constructed positives,
+    /// sharing the same negatives, and the losses combined.  Costs a reconstruction per extra
+    /// positive.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub multi_positive: Option<MultiPositive>,
+
     /// Whether to show a pretty indicator
     pub indicator: bool
 }
 
+/// Small, single pass friendly defaults with every optional behavior disabled.  Set fields with
+/// struct update syntax, `EmbeddingPropagation { d_model: 64, ..Default::default() }`, so new
+/// options don't break existing code.
+impl Default for EmbeddingPropagation {
+    fn default() -> Self {
+        EmbeddingPropagation {
+            alpha: 1e-2,
+            loss: Loss::MarginLoss(1f32, 1usize),
+            batch_size: 32,
+            d_model: 32,
+            passes: 10,
+            hard_negs: 0,
+            loss_weighting: LossWeighting::None,
+            seed: 2023,
+            rng_kind: RngKind::XorShift,
+            valid_pct: 0.1,
+            noise: 0.0,
+            weighted_positives: false,
+            adaptive_batch: None,
+            asynchronous: false,
+            exclude_neighbors: false,
+            degree_balancing: None,
+            ranking_validation: None,
+            negative_pools: None,
+            frozen_features: None,
+            node_weights: None,
+            gradient_precision: Precision::F32,
+            gradient_threshold: None,
+            diagnostics: None,
+            multi_positive: None,
+            indicator: false
+        }
+    }
+}
+
 impl EmbeddingPropagation {
 
-    /// Learns the feature embeddings.
+    /// Learns the feature embeddings.  Fails if the graph is empty, the feature store doesn't
+    /// cover the graph, or the provided feature embeddings don't match the model.
     pub fn learn<G: CGraph + Send + Sync, M: Model>(
         &self, 
         graph: &G, 
         features: &FeatureStore,
         feature_embeddings: Option<EmbeddingStore>,
         model: &M
-    ) -> EmbeddingStore {
-        let feat_embeds = self.learn_feature_embeddings(graph, features, feature_embeddings, model);
-        feat_embeds
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        let tracker = ResourceTracker::new();
+        self.learn_feature_embeddings(graph, features, feature_embeddings, model, None,
+                                      &Runtime::global(), &tracker, None, None)
+            .map(|(fe, _)| fe)
+    }
+
+    /// Learns the feature embeddings, resuming from a checkpoint when given one: the feature
+    /// embeddings and training state returned by a previous call.  Training picks up at the pass
+    /// after the state's with its optimizer moments, learning rate schedule, and random stream,
+    /// so raising `passes` extends a finished run.  Returns the state to resume from next.
+    /// Error feedback residuals from gradient thresholding start over from zero.  Fails if the
+    /// state was trained with a different config or doesn't match the feature embeddings, or if
+    /// the batch size is adaptive, since its adjustments aren't recorded.
+    pub fn learn_resumable<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: Option<EmbeddingStore>,
+        state: Option<&TrainingState>,
+        model: &M
+    ) -> Result<(EmbeddingStore, TrainingState), GraphLibError> {
+        if let Some(state) = state {
+            state.check_config(self)?;
+            if self.adaptive_batch.is_some() {
+                return Err("Training with adaptive batch sizes can't be resumed!".into())
+            }
+            let fe = feature_embeddings.as_ref()
+                .ok_or("Resuming requires the checkpoint's feature embeddings!")?;
+            state.check_embeddings(fe)?;
+        }
+
+        let tracker = ResourceTracker::new();
+        let (fe, run) = self.learn_feature_embeddings(graph, features, feature_embeddings, model,
+                                                      None, &Runtime::global(), &tracker, None,
+                                                      state)?;
+        let state = TrainingState::new(self, run.pass, run.step, run.optimizer.state(),
+                                       Some(run.lr_scheduler));
+        Ok((fe, state))
+    }
+
+    /// Learns the feature embeddings, additionally returning the time spent in each phase and
+    /// the memory used by the key structures.
+    pub fn learn_with_report<G: CGraph + Send + Sync, M: Model>(
+        &self, 
+        graph: &G, 
+        features: &FeatureStore,
+        feature_embeddings: Option<EmbeddingStore>,
+        model: &M
+    ) -> Result<(EmbeddingStore, ResourceReport), GraphLibError> {
+        self.learn_with_runtime(&Runtime::global(), graph, features, feature_embeddings, model)
+    }
+
+    /// Learns the feature embeddings within the runtime's thread pool.  If the runtime has a
+    /// memory budget, the batch size is capped to fit the estimated per batch gradient state.
+    pub fn learn_with_runtime<G: CGraph + Send + Sync, M: Model>(
+        &self, 
+        runtime: &Runtime,
+        graph: &G, 
+        features: &FeatureStore,
+        feature_embeddings: Option<EmbeddingStore>,
+        model: &M
+    ) -> Result<(EmbeddingStore, ResourceReport), GraphLibError> {
+        let tracker = ResourceTracker::new();
+        let (feat_embeds, _) = runtime.install(|| {
+            self.learn_feature_embeddings(
+                graph, features, feature_embeddings, model, None, runtime, &tracker, None, None)
+        })?;
+        Ok((feat_embeds, tracker.report()))
     }
     
+    /// Learns free embeddings directly for each node, with no feature composition: the
+    /// shallow, LINE/DeepWalk style baseline trained with the same losses, samplers, and
+    /// optimizer.  Every node is treated as having a single feature of its own, so feature ids
+    /// are NodeIDs throughout: `node_embeddings`, `frozen_features`, and the returned store are
+    /// all indexed by node.  With `AveragedFeatureModel` the returned embeddings are the node
+    /// embeddings; other models can materialize them with `embed_nodes` over
+    /// `FeatureStore::identity`.
+    pub fn learn_node_embeddings<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        node_embeddings: Option<EmbeddingStore>,
+        model: &M
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        let features = FeatureStore::identity(graph.len());
+        let tracker = ResourceTracker::new();
+        self.learn_feature_embeddings(graph, &features, node_embeddings, model, None,
+                                      &Runtime::global(), &tracker, None, None)
+            .map(|(fe, _)| fe)
+    }
+
+    /// Fine tunes existing feature embeddings on a delta: the nodes which were added, or whose
+    /// edges or features changed, since the embeddings were learned.  Only delta nodes are used as
+    /// anchors and only the features they reference are updated, so a small delta costs a small
+    /// fraction of a full retrain.  The graph and features are the full, updated, ones, so
+    /// positives and negatives still come from everywhere.  Features appended to the vocabulary
+    /// since are randomly initialized.  Runs for the EP's passes, which should usually be few.
+    pub fn fine_tune<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: EmbeddingStore,
+        delta_nodes: &[NodeID],
+        model: &M
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        if delta_nodes.is_empty() {
+            return Err("Delta must contain at least one node!".into())
+        }
+        let num_nodes = graph.len().min(features.num_nodes());
+        if let Some(node_id) = delta_nodes.iter().find(|node_id| **node_id >= num_nodes) {
+            return Err(GraphLibError::InvalidInput(
+                format!("Delta node {} is not in the graph", node_id)))
+        }
+
+        let feature_embeddings = self.grow_feature_embeddings(features, feature_embeddings)?;
+
+        // Freeze everything the delta doesn't reference, on top of anything already frozen
+        let mut frozen = vec![true; features.num_embeddings()];
+        for node_id in delta_nodes.iter() {
+            features.get_features(*node_id).iter().for_each(|feat_id| frozen[*feat_id] = false);
+            features.get_dense(*node_id).iter().enumerate()
+                .filter(|(_, v)| **v != 0f32)
+                .for_each(|(col, _)| frozen[features.dense_feature_id(col)] = false);
+        }
+        if let Some(already) = &self.frozen_features {
+            frozen.iter_mut().zip(already.iter()).for_each(|(f, a)| *f |= *a);
+        }
+
+        let ep = EmbeddingPropagation { frozen_features: Some(frozen), ..self.clone() };
+        let tracker = ResourceTracker::new();
+        ep.learn_feature_embeddings(graph, features, Some(feature_embeddings), model,
+                                    Some(delta_nodes), &Runtime::global(), &tracker, None, None)
+            .map(|(fe, _)| fe)
+    }
+
+    // Appends randomly initialized embeddings for features added to the vocabulary since the
+    // embeddings were learned
+    fn grow_feature_embeddings(
+        &self,
+        features: &FeatureStore,
+        feature_embeddings: EmbeddingStore
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        let num_embeddings = features.num_embeddings();
+        if feature_embeddings.len() >= num_embeddings {
+            return Ok(feature_embeddings)
+        }
+
+        // Dense projections sit after the discrete features, so new features shift them and we
+        // can't tell which old embedding belongs where.
+        if features.dense_dims() > 0 {
+            return Err(GraphLibError::DimensionMismatch { 
+                expected: num_embeddings, 
+                found: feature_embeddings.len() 
+            })
+        }
+
+        let mut rng = self.stream(DELTA_STREAM).rng();
+        let mut grown = EmbeddingStore::new(num_embeddings, feature_embeddings.dims(), 
+                                            feature_embeddings.distance());
+        randomize_embedding_store(&mut grown, &mut rng);
+        for feat_id in 0..feature_embeddings.len() {
+            grown.set_embedding(feat_id, feature_embeddings.get_embedding(feat_id));
+        }
+        Ok(grown)
+    }
+
+    /// Skips training entirely and only scores the provided validation nodes against an existing
+    /// set of feature embeddings.  This allows us to evaluate a trained model against a new
+    /// validation set, or a different loss, without touching the weights.  Returns the average
+    /// validation loss.
+    pub fn validate<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        valid_idxs: &[NodeID]
+    ) -> f32 {
+        self.evaluate(graph, valid_idxs, features, feature_embeddings, model)
+    }
+
+    /// Average loss of the given nodes under the EP's loss and samplers, with negatives drawn
+    /// from the nodes themselves.  Embeddings and losses are computed on plain f32 values rather
+    /// than through the compute graph, so nothing is allocated for backpropagation.  Cheap
+    /// enough for held out monitoring, or for scoring embeddings trained under other
+    /// hyperparameters.  Each node uses a fixed random stream, so repeated calls agree.
+    pub fn evaluate<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        nodes: &[NodeID],
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M
+    ) -> f32 {
+        if nodes.len() == 0 {
+            return 0f32
+        }
+
+        let random_sampler = RandomWalkHardStrategy::new(self.hard_negs, nodes);
+        let pool_sampler = self.negative_pools.as_ref()
+            .map(|pools| CandidatePoolStrategy::new(pools, nodes));
+        let positive_tables = self.positive_tables(graph);
+        self.compute_validation_error(graph, features, feature_embeddings, model, 
+                                      nodes, &random_sampler, pool_sampler.as_ref(),
+                                      positive_tables.as_ref())
+    }
+
+    /// Materializes node embeddings for every node in the graph from the learned feature
+    /// embeddings, using the same aggregation as training.  This is the store to hand to the ANN.
+    pub fn embed_nodes<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M
+    ) -> EmbeddingStore {
+        let dims = model.node_dims(feature_embeddings.dims());
+        let es = EmbeddingStore::new(graph.len(), dims, Distance::Cosine);
+        let pb = CLProgressBar::new(graph.len() as u64, self.indicator);
+        pb.update_message(|msg| { write!(msg, "Embedding nodes").expect("Should never hit"); });
+
+        (0..graph.len()).into_par_iter().for_each(|node_id| {
+            // Nodes without any features are left as zeros
+            if features.get_features(node_id).len() > 0 {
+                self.embed_node(node_id, features, feature_embeddings, model, &es);
+            }
+            pb.inc(1);
+        });
+        pb.finish();
+        es
+    }
+
+    /// Recomputes the embeddings of only the given nodes, writing them into a store produced by
+    /// `embed_nodes`.  This refreshes nodes whose features or neighborhoods changed without
+    /// re-embedding the whole graph.  Nodes which no longer have any features are zeroed.
+    pub fn embed_subset<M: Model>(
+        &self,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        nodes: &[NodeID],
+        es: &mut EmbeddingStore
+    ) -> Result<(), GraphLibError> {
+        check_dims(model.node_dims(feature_embeddings.dims()), es.dims())?;
+        let num_nodes = es.len().min(features.num_nodes());
+        if let Some(node_id) = nodes.iter().find(|node_id| **node_id >= num_nodes) {
+            return Err(GraphLibError::InvalidInput(
+                format!("Node {} is not in the embedding or feature store", node_id)))
+        }
+
+        let es = &*es;
+        nodes.par_iter().for_each(|node_id| {
+            if features.get_features(*node_id).len() > 0 {
+                self.embed_node(*node_id, features, feature_embeddings, model, es);
+            } else {
+                es.get_embedding_mut_hogwild(*node_id).iter_mut().for_each(|ei| *ei = 0f32);
+            }
+        });
+        Ok(())
+    }
+
+    // Writes a single node's embedding.  Each node only writes its own row, so this is safe to
+    // call in parallel across distinct nodes.
+    fn embed_node<M: Model>(
+        &self,
+        node_id: NodeID,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        es: &EmbeddingStore
+    ) {
+        let mut rng = self.rng_kind.seed_from_u64(self.seed + node_id as u64);
+        let (_, emb) = model.construct_node_embedding(
+            node_id, 1f32, features, feature_embeddings, &mut rng);
+        es.get_embedding_mut_hogwild(node_id).copy_from_slice(emb.value());
+    }
+
     // The uber expensive function
     fn learn_feature_embeddings<G: CGraph + Send + Sync, M: Model>(
         &self,
         graph: &G,
         features: &FeatureStore,
         feature_embeddings: Option<EmbeddingStore>,
-        model: &M
-    ) -> EmbeddingStore {
+        model: &M,
+        anchors: Option<&[NodeID]>,
+        runtime: &Runtime,
+        tracker: &ResourceTracker,
+        remote: Option<&dyn RemoteUpdates>,
+        resume: Option<&TrainingState>
+    ) -> Result<(EmbeddingStore, RunState), GraphLibError> {
 
-        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        self.check_inputs(graph, features, feature_embeddings.as_ref(), model)?;
+
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("ep.learn", nodes = graph.len(), passes = self.passes, 
+                                        d_model = self.d_model).entered();
+
+        let init_start = Instant::now();
+        let mut rng = SplitRng::new(self.seed, self.rng_kind).rng();
 
         let dims = model.feature_dims(self.d_model);
         let feature_embeddings = if let Some(embs) = feature_embeddings {
             embs
         } else {
-            let mut fe = EmbeddingStore::new(features.num_features(), dims, Distance::Cosine);
+            let mut fe = EmbeddingStore::new(features.num_embeddings(), dims, Distance::Cosine);
             // Initialize embeddings as random
             randomize_embedding_store(&mut fe, &mut rng);
             fe
@@ -111,30 +583,56 @@ impl EmbeddingPropagation {
 
         // Initializer SGD optimizer.  Right now we hard code the parameters for the optimizer but
         // in the future we could allow for this to be parameterized.
-        let optimizer = AdamOptimizer::new(0.9, 0.999,
-            feature_embeddings.dims(), 
-            feature_embeddings.len()); 
+        let optimizer = match resume {
+            Some(state) => AdamOptimizer::from_state(&state.optimizer)?,
+            None => AdamOptimizer::new(0.9, 0.999,
+                feature_embeddings.dims(), 
+                feature_embeddings.len())
+        };
 
-        // Pull out validation idxs;
+        tracker.add_time("initialize", init_start.elapsed());
+        tracker.record_bytes("feature_embeddings", feature_embeddings.memory_bytes());
+        tracker.record_bytes("optimizer", 2 * feature_embeddings.memory_bytes());
+
+        // Pull out validation idxs.  They come from their own stream so a resumed run holds out
+        // the same nodes as the one it resumes.
         let mut node_idxs: Vec<_> = (0..graph.len()).into_iter().collect();
-        node_idxs.shuffle(&mut rng);
+        node_idxs.shuffle(&mut self.stream(HOLDOUT_STREAM).rng());
         let valid_idx = (graph.len() as f32 * self.valid_pct) as usize;
         let valid_idxs = node_idxs.split_off(graph.len() - valid_idx);
 
-        // Number of update stpes
-        let steps_per_pass = (node_idxs.len() as f32 / self.batch_size as f32).ceil() as usize;
+        // When fine tuning, only the delta nodes are anchors but negatives are still drawn from
+        // every training node
+        let all_idxs = anchors.map(|delta| {
+            let all = node_idxs.clone();
+            let delta: HashSet<_> = delta.iter().collect();
+            node_idxs.retain(|node_id| delta.contains(node_id));
+            all
+        });
 
-        let pb = CLProgressBar::new((self.passes * steps_per_pass) as u64, self.indicator);
+        // Number of update stpes
+        let item_bytes = self.batch_item_bytes(features, feature_embeddings.dims());
+        let mut batch_size = runtime.cap_batch_size(self.batch_size, item_bytes);
+        let pass_size = self.degree_balancing
+            .map(|db| db.expected_size(graph, &node_idxs))
+            .unwrap_or(node_idxs.len());
+        let mut steps_per_pass = (pass_size as f32 / batch_size as f32).ceil() as usize;
+
+        let first_pass = resume.map(|state| state.pass + 1).unwrap_or(1);
+        let remaining_passes = (self.passes + 1).saturating_sub(first_pass);
+        let pb = CLProgressBar::new((remaining_passes * steps_per_pass) as u64, self.indicator);
         
         // Enable/disable shared memory pool
         use_shared_pool(false);
 
         let total_updates = steps_per_pass * self.passes;
-        let lr_scheduler = {
-            let warm_up_steps = (total_updates as f32 / 5f32) as usize;
-            let max_steps = self.passes * steps_per_pass;
-            LRScheduler::cos_decay(self.alpha / 100f32, self.alpha, warm_up_steps, max_steps)
-        };
+        let mut lr_scale = 1f32;
+        let mut lr_scheduler = resume.and_then(|state| state.lr_scheduler.clone())
+            .unwrap_or_else(|| {
+                let warm_up_steps = (total_updates as f32 / 5f32) as usize;
+                let max_steps = self.passes * steps_per_pass;
+                LRScheduler::cos_decay(self.alpha / 100f32, self.alpha, warm_up_steps, max_steps)
+            });
 
         // Noise 
         let noise_scheduler = if self.noise > 1e-9 {
@@ -146,75 +644,142 @@ impl EmbeddingPropagation {
         };
 
         // Initialize samplers for negatives.
-        let random_sampler = RandomWalkHardStrategy::new(self.hard_negs, &node_idxs);
+        let negative_idxs = all_idxs.as_deref().unwrap_or(&node_idxs);
+        let random_sampler = RandomWalkHardStrategy::new(self.hard_negs, negative_idxs);
         let valid_random_sampler = RandomWalkHardStrategy::new(self.hard_negs, &valid_idxs);
+        let pool_sampler = self.negative_pools.as_ref()
+            .map(|pools| CandidatePoolStrategy::new(pools, negative_idxs));
+        let valid_pool_sampler = self.negative_pools.as_ref()
+            .map(|pools| CandidatePoolStrategy::new(pools, &valid_idxs));
+        let positive_tables = self.positive_tables(graph);
+
+        // Held out pairs are fixed across passes so the MRRs are comparable
+        let ranking_pairs = self.ranking_validation
+            .map(|rv| self.sample_ranking_pairs(graph, features, &valid_idxs, rv.num_pairs))
+            .unwrap_or_default();
 
         let mut last_error = std::f32::INFINITY;
-        let step = AtomicUsize::new(1);
+        let step = AtomicUsize::new(resume.map(|state| state.step).unwrap_or(0) + 1);
         let mut valid_error = std::f32::INFINITY;
+        let mut valid_mrr: Option<f32> = None;
+        let noise_estimator = Mutex::new(NoiseScaleEstimator::new());
+        let remote_error: Mutex<Option<GraphLibError>> = Mutex::new(None);
+        let residuals = Mutex::new(CHashMap::new());
+        let touched: Option<Vec<AtomicBool>> = self.diagnostics.map(|_| {
+            (0..feature_embeddings.len()).map(|_| AtomicBool::new(false)).collect()
+        });
         
-        for pass in 1..(self.passes + 1) {
+        for pass in first_pass..(self.passes + 1) {
+
+            // Pick up the updates from every other trainer since the last pass
+            if let (Some(remote), true) = (remote, pass > first_pass) {
+                remote.refresh(&feature_embeddings)?;
+            }
 
             pb.update_message(|msg| {
                 msg.clear();
                 let cur_step = step.load(Ordering::Relaxed);
-                let alpha = lr_scheduler.compute(cur_step);
+                let alpha = lr_scheduler.compute(cur_step) * lr_scale;
                 let noise = noise_scheduler.compute(cur_step);
                 write!(msg, "Pass {}/{}, Train: {:.5}, Valid: {:.5}, LR: {:.5}, Noise: {:.5}", pass, self.passes, 
                        last_error, valid_error, alpha, noise)
                     .expect("Error writing out indicator message!");
+                if let Some(mrr) = valid_mrr {
+                    write!(msg, ", Valid MRR: {:.5}", mrr)
+                        .expect("Error writing out indicator message!");
+                }
             });
 
             if pass % 10 == 0 {
                 println!();
             }
 
-            // Shuffle for SGD
-            node_idxs.shuffle(&mut rng);
-            let err_cnt: (f32, usize) = node_idxs.par_iter().chunks(self.batch_size).enumerate().map(|(i, nodes)| {
-
-                let sampler = (&random_sampler).initialize_batch(
-                    &nodes,
-                    graph,
-                    features);
+            // Only estimate the noise scale while warming up
+            let estimate_noise = self.adaptive_batch
+                .map(|ab| pass <= ab.warmup_passes)
+                .unwrap_or(false);
+
+            // Shuffle for SGD.  Each pass shuffles the same order with its own stream, which a
+            // resumed run restores from the training state.
+            let train_start = Instant::now();
+            let shuffle_seeds = match resume {
+                Some(state) if pass == first_pass => SplitRng::new(state.rng_seed, self.rng_kind),
+                _ => self.stream(SHUFFLE_STREAM).split(pass as u64)
+            };
+            let mut pass_rng = shuffle_seeds.rng();
+            let mut pass_idxs = node_idxs.clone();
+            pass_idxs.shuffle(&mut pass_rng);
+            let pass_idxs = match &self.degree_balancing {
+                Some(db) => db.sample(graph, &pass_idxs, &mut pass_rng),
+                None => pass_idxs
+            };
+
+            let err_cnt: (f32, f32, usize) = pass_idxs.par_iter().chunks(batch_size).enumerate().map(|(i, nodes)| {
+
+                let sampler = if let Some(ps) = &pool_sampler {
+                    EitherSampler::Right(ps.initialize_batch(&nodes, graph, features))
+                } else {
+                    EitherSampler::Left((&random_sampler).initialize_batch(&nodes, graph, features))
+                };
+                let sampler = self.wrap_sampler(sampler, &nodes, graph);
                 
                 let n_nodes = nodes.len();
+
+                if self.asynchronous {
+                    // Apply each example's gradients immediately, without waiting on the batch
+                    let cur_step = step.fetch_add(1, Ordering::Relaxed);
+                    let alpha = lr_scheduler.compute(cur_step) * lr_scale / n_nodes as f32;
+                    let noise = noise_scheduler.compute(cur_step);
+                    let (error, sq_norm) = nodes.par_iter().map(|node_id| {
+                        let grads = self.compute_node_gradients(
+                            graph, **node_id, pass, i, features, &feature_embeddings, model, &sampler,
+                            positive_tables.as_ref());
+
+                        match grads {
+                            Some((err, grad_set)) => {
+                                let sq_norm = if cfg!(feature = "tracing") {
+                                    squared_norm(grad_set.values())
+                                } else {
+                                    0f32
+                                };
+                                let mut grads: CHashMap<_, _> = grad_set.into_iter().collect();
+                                self.remove_frozen(&mut grads);
+                                if let Some(touched) = &touched {
+                                    diagnostics::mark_touched(touched, grads.keys());
+                                }
+                                let seeds = self.stream(NOISE_STREAM).split(pass as u64)
+                                    .split(i as u64).split(**node_id as u64);
+                                self.add_gradient_noise(&mut grads, noise, seeds);
+                                optimizer.update(&feature_embeddings, grads, alpha, pass as f32);
+                                (err, sq_norm)
+                            },
+                            None => (0f32, 0f32)
+                        }
+                    }).reduce(|| (0f32, 0f32), |a, b| (a.0 + b.0, a.1 + b.1));
+
+                    pb.inc(1);
+                    return (error / n_nodes as f32, sq_norm.sqrt())
+                }
+
                 // Compute grads for batch
                 let grads: Vec<_> = nodes.par_iter().filter_map(|node_id| {
-                    let n_id = **node_id;
-                    let mut rng = XorShiftRng::seed_from_u64(self.seed + (i + n_id) as u64);
-                    let (mut loss, hv_vars, thv_vars, hu_vars) = self.run_forward_pass(
-                        graph, n_id, &features, &feature_embeddings, 
-                        model, &sampler, &mut rng);
-
-                    loss = match self.loss_weighting {
-                        LossWeighting::DegreeLog => {
-                            let decrease = (1f32 + graph.degree(n_id) as f32).ln();
-                            loss / decrease
-                        },
-                        LossWeighting::DegreeExponential(weight) => {
-                            let decrease = (graph.degree(n_id) as f32).powf(weight);
-                            loss / decrease
-                        },
-                        LossWeighting::None => { loss }
-                    };
-
-                    let loss_value = loss.value()[0];
-
-                    // Sometimes there are weird errors due to underflows in softmax
-                    // In this case, just print the graph and don't return
-                    if loss_value.is_nan() {
-                        Graph::print_graph(&loss);
-                        None
-                    } else if loss_value > 0f32 {
-                        let grads = self.extract_gradients(&loss, hv_vars, thv_vars, hu_vars);
-                        Some((loss_value, grads))
-                    } else {
-                        None
-                    }
+                    self.compute_node_gradients(
+                        graph, **node_id, pass, i, features, &feature_embeddings, model, &sampler,
+                        positive_tables.as_ref())
+                        .map(|(err, grad_set)| {
+                            (err, ExampleGrads::new(grad_set, self.gradient_precision))
+                        })
                 }).collect();
 
                 let cnt = grads.len();
+
+                // Average squared norm of the per-example gradients
+                let small_sq_norm = if estimate_noise && cnt > 1 {
+                    grads.iter().map(|(_, grad_set)| grad_set.squared_norm())
+                        .sum::<f32>() / cnt as f32
+                } else {
+                    0f32
+                };
                 
                 // We are using std Hashmap instead of hashbrown due to a weird bug
                 // where the optimizer, for whatever reason, has trouble draining it
@@ -225,65 +790,525 @@ impl EmbeddingPropagation {
                 // Since we're dealing with multiple reconstructions with likely shared features,
                 // we aggregate all the gradients
                 let error = grads.into_iter().map(|(err, grad_set)| {
-                    for (feat, grad) in grad_set.into_iter() {
-                        let e = all_grads.entry(feat).or_insert_with(|| vec![0.; grad.len()]);
-                        e.iter_mut().zip(grad.iter()).for_each(|(ei, gi)| *ei += *gi);
-                    }
+                    grad_set.accumulate(&mut all_grads);
                     err
                 }).sum::<f32>();
 
+                if estimate_noise && cnt > 1 {
+                    let big_sq_norm = squared_norm(all_grads.values()) / (cnt * cnt) as f32;
+                    noise_estimator.lock()
+                        .expect("Mutex poisoned!")
+                        .update(small_sq_norm, big_sq_norm, cnt);
+                }
+
                 let cur_step = step.fetch_add(1, Ordering::Relaxed);
 
+                // Only pay for the gradient norm when it's being traced
+                let grad_norm = if cfg!(feature = "tracing") && cnt > 0 {
+                    squared_norm(all_grads.values()).sqrt()
+                } else {
+                    0f32
+                };
+
                 if cnt > 0 {
-                    // Add gaussian noise to help regulate embeddings
-                    if self.noise > 0.0 {
-                        let noise = noise_scheduler.compute(cur_step);
-                        all_grads.par_iter_mut().for_each(|(feat, emb)| {
-                            let mut rng = XorShiftRng::seed_from_u64(self.seed + (i + feat) as u64);
-                            emb.iter_mut().for_each(|ei| {
-                                *ei += noise * rng.sample::<f32,StandardNormal>(StandardNormal);
-                            });
-                        });
+                    self.remove_frozen(&mut all_grads);
+                    self.threshold_gradients(&mut all_grads, &residuals);
+                    if let Some(touched) = &touched {
+                        diagnostics::mark_touched(touched, all_grads.keys());
                     }
 
+                    // Add gaussian noise to help regulate embeddings
+                    let seeds = self.stream(NOISE_STREAM).split(pass as u64).split(i as u64);
+                    self.add_gradient_noise(&mut all_grads, noise_scheduler.compute(cur_step), seeds);
+
                     // Backpropagate embeddings
-                    let alpha = lr_scheduler.compute(cur_step);
-                    optimizer.update(&feature_embeddings, all_grads, alpha, pass as f32);
+                    let alpha = lr_scheduler.compute(cur_step) * lr_scale;
+                    match remote {
+                        Some(remote) => {
+                            let pushed = remote.push(&feature_embeddings, all_grads, alpha, pass as f32);
+                            if let Err(e) = pushed {
+                                remote_error.lock().expect("Mutex poisoned!").get_or_insert(e);
+                            }
+                        },
+                        None => optimizer.update(&feature_embeddings, all_grads, alpha, pass as f32)
+                    }
                 }
 
                 // Update progress bar
                 pb.inc(1);
                 if cnt > 0 {
-                    error / n_nodes as f32
+                    (error / n_nodes as f32, grad_norm)
                 } else {
-                    0f32
+                    (0f32, grad_norm)
                 }
             })
-            .map(|x| { if x.is_infinite() { (0f32, 1usize) } else { (x, 1usize) } })
-            .reduce(|| (0f32, 0usize), |a, b| (a.0 + b.0, a.1 + b.1));
+            .map(|(x, g)| { if x.is_infinite() { (0f32, g, 1usize) } else { (x, g, 1usize) } })
+            .reduce(|| (0f32, 0f32, 0usize), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+
+            last_error = err_cnt.0 / if err_cnt.2 > 0 { err_cnt.2 as f32} else { 1f32 };
+            tracker.add_time("train", train_start.elapsed());
+            if let Some(e) = remote_error.lock().expect("Mutex poisoned!").take() {
+                return Err(e)
+            }
+
+            if let Some(config) = &self.diagnostics {
+                let health = tracker.phase("diagnostics", || {
+                    self.check_health(pass, &feature_embeddings, &optimizer, touched.as_deref(), config)
+                });
+                self.report_health(&health, &pb);
+            }
 
-            last_error = err_cnt.0 / if err_cnt.1 > 0 { err_cnt.1 as f32} else { 1f32 };
+            // Once we've finished warming up, update the batch size from the noise scale
+            if let Some(ab) = &self.adaptive_batch {
+                if pass == ab.warmup_passes {
+                    let noise_scale = noise_estimator.lock()
+                        .expect("Mutex poisoned!")
+                        .noise_scale();
+
+                    let (new_batch_size, scale) = ab.adjust(batch_size, noise_scale);
+                    let new_batch_size = runtime.cap_batch_size(new_batch_size, item_bytes);
+
+                    #[cfg(feature = "tracing")]
+                    tracing::info!(noise_scale = ?noise_scale, batch_size, new_batch_size, "adaptive batch size");
+
+                    if new_batch_size != batch_size {
+                        batch_size = new_batch_size;
+                        lr_scale *= scale;
+                        steps_per_pass = (pass_size as f32 / batch_size as f32).ceil() as usize;
+
+                        // Rebuild the schedule for the remaining steps
+                        let cur_step = step.load(Ordering::Relaxed);
+                        let max_steps = cur_step + (self.passes - pass) * steps_per_pass;
+                        let warm_up_steps = (total_updates as f32 / 5f32) as usize;
+                        lr_scheduler = LRScheduler::cos_decay(self.alpha / 100f32, self.alpha, 
+                                                              warm_up_steps, max_steps);
+                        pb.set_length((cur_step - 1 + (self.passes - pass) * steps_per_pass) as u64);
+                    }
+                }
+            }
             
             if valid_idxs.len() > 0 {
-                // Validate.  We use the same random seed for consistency across iterations.
-                let valid_errors = valid_idxs.par_iter().chunks(self.batch_size).map(|nodes| {
-                    let sampler = (&valid_random_sampler).initialize_batch(&nodes, graph, features);
-
-                    nodes.par_iter().map(|node_id| {
-                        let mut rng = XorShiftRng::seed_from_u64(self.seed - 1);
-                        let loss = self.run_forward_pass(
-                            graph, **node_id, &features, &feature_embeddings, 
-                            model, &sampler, &mut rng).0;
-
-                        loss.value()[0]
-                    }).sum::<f32>()
-                }).sum::<f32>();
-                
-                valid_error = valid_errors / valid_idxs.len() as f32;
+                valid_error = tracker.phase("validate", || {
+                    self.compute_validation_error(
+                        graph, features, &feature_embeddings, model, 
+                        &valid_idxs, &valid_random_sampler, valid_pool_sampler.as_ref(),
+                        positive_tables.as_ref())
+                });
             }
+
+            if let Some(rv) = &self.ranking_validation {
+                if ranking_pairs.len() > 0 {
+                    valid_mrr = Some(tracker.phase("validate", || {
+                        self.compute_ranking_mrr(graph, features, &feature_embeddings, model, 
+                                                 &ranking_pairs, rv.num_negatives)
+                    }));
+                }
+            }
+
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                pass,
+                train_loss = last_error,
+                valid_loss = valid_error,
+                valid_mrr = ?valid_mrr,
+                grad_norm = err_cnt.1 / err_cnt.2.max(1) as f32,
+                lr = lr_scheduler.compute(step.load(Ordering::Relaxed)) * lr_scale,
+                batch_size,
+                "ep pass");
         }
         pb.finish();
-        feature_embeddings
+        let run = RunState {
+            pass: self.passes.max(first_pass - 1),
+            step: step.load(Ordering::Relaxed) - 1,
+            optimizer,
+            lr_scheduler
+        };
+        Ok((feature_embeddings, run))
+    }
+
+    // Validates everything we'd otherwise panic on deep within the training loop
+    fn check_inputs<G: CGraph, M: Model>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: Option<&EmbeddingStore>,
+        model: &M
+    ) -> Result<(), GraphLibError> {
+        if graph.len() == 0 {
+            return Err(GraphLibError::EmptyGraph)
+        }
+        if self.batch_size == 0 {
+            return Err("batch_size must be positive!".into())
+        }
+        model.check()?;
+        if !(0f32..1f32).contains(&self.valid_pct) {
+            return Err("valid_pct must be in [0, 1)!".into())
+        }
+        match self.degree_balancing {
+            Some(DegreeBalancing::Capped(0)) => {
+                return Err("Degree balancing cap must be positive!".into())
+            },
+            Some(DegreeBalancing::InverseDegree(exponent)) if exponent < 0f32 || exponent.is_nan() => {
+                return Err("Degree balancing exponent must be non-negative!".into())
+            },
+            _ => {}
+        }
+        if let Some(rv) = &self.ranking_validation {
+            if rv.num_pairs == 0 || rv.num_negatives == 0 {
+                return Err("Ranking validation needs pairs and negatives!".into())
+            }
+        }
+        if let Some(threshold) = self.gradient_threshold {
+            if !threshold.is_finite() || threshold < 0f32 {
+                return Err("Gradient threshold must be finite and non-negative!".into())
+            }
+        }
+        if self.weighted_positives && !matches!(self.loss, Loss::PPR(..)) {
+            return Err("Weighted positives are only supported by PPR losses!".into())
+        }
+        if matches!(self.multi_positive, Some(mp) if mp.positives == 0) {
+            return Err("Multiple positives needs at least one positive!".into())
+        }
+        if self.asynchronous && self.adaptive_batch.is_some() {
+            return Err("Adaptive batch sizes need synchronous updates!".into())
+        }
+        if features.num_nodes() < graph.len() {
+            return Err(GraphLibError::DimensionMismatch { 
+                expected: graph.len(), 
+                found: features.num_nodes() 
+            })
+        }
+        if let Some(frozen) = &self.frozen_features {
+            if feature_embeddings.is_none() {
+                return Err("Frozen features need pretrained feature embeddings!".into())
+            }
+            if frozen.len() > features.num_embeddings() {
+                return Err(GraphLibError::DimensionMismatch { 
+                    expected: features.num_embeddings(), 
+                    found: frozen.len() 
+                })
+            }
+        }
+        if let Some(weights) = &self.node_weights {
+            if weights.len() < graph.len() {
+                return Err(GraphLibError::DimensionMismatch { 
+                    expected: graph.len(), 
+                    found: weights.len() 
+                })
+            }
+            if weights.iter().any(|w| !w.is_finite() || *w < 0f32) {
+                return Err("Node weights must be finite and non-negative!".into())
+            }
+        }
+        if let Some(fe) = feature_embeddings {
+            check_dims(model.feature_dims(self.d_model), fe.dims())?;
+            if fe.len() < features.num_embeddings() {
+                return Err(GraphLibError::DimensionMismatch { 
+                    expected: features.num_embeddings(), 
+                    found: fe.len() 
+                })
+            }
+        }
+        Ok(())
+    }
+
+    // Rough estimate of the transient memory for a single batch item: every feature of the
+    // anchor, its reconstruction, and each negative carries a value, a buffered gradient, and a
+    // copy in the aggregated gradients.
+    fn batch_item_bytes(&self, features: &FeatureStore, dims: usize) -> usize {
+        let num_nodes = features.num_nodes().max(1);
+        let total_feats: usize = features.iter().map(|f| f.len()).sum();
+        let avg_feats = (total_feats / num_nodes).max(1) + features.dense_dims();
+        let nodes_per_item = 1 + self.num_positives() + self.loss.negatives() + self.hard_negs;
+        let value_bytes = 2 * std::mem::size_of::<f32>() + self.gradient_precision.bytes();
+        avg_feats * nodes_per_item * dims * value_bytes
+    }
+
+    fn compute_validation_error<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        valid_idxs: &[NodeID],
+        valid_random_sampler: &RandomWalkHardStrategy,
+        valid_pool_sampler: Option<&CandidatePoolStrategy>,
+        positive_tables: Option<&EdgeAliasTable>
+    ) -> f32 {
+        // Validate.  Each node uses the same random stream every pass for consistency across
+        // iterations.
+        let valid_errors = valid_idxs.par_iter().chunks(self.batch_size).map(|nodes| {
+            let sampler = if let Some(ps) = &valid_pool_sampler {
+                EitherSampler::Right(ps.initialize_batch(&nodes, graph, features))
+            } else {
+                EitherSampler::Left(valid_random_sampler.initialize_batch(&nodes, graph, features))
+            };
+            let sampler = self.wrap_sampler(sampler, &nodes, graph);
+
+            nodes.par_iter().map(|node_id| {
+                let mut rng = self.stream(VALID_STREAM).split(**node_id as u64).rng();
+                self.score_node(graph, **node_id, &features, &feature_embeddings, 
+                                model, &sampler, positive_tables, &mut rng)
+            }).sum::<f32>()
+        }).sum::<f32>();
+        
+        valid_errors / valid_idxs.len() as f32
+    }
+
+    // Samples up to num_pairs (node, neighbor) pairs from the validation nodes.  Nodes without
+    // features can't be embedded, so they're skipped.
+    fn sample_ranking_pairs<G: CGraph>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        valid_idxs: &[NodeID],
+        num_pairs: usize
+    ) -> Vec<(NodeID, NodeID)> {
+        let mut rng = self.stream(RANKING_STREAM).rng();
+        let mut candidates = valid_idxs.to_vec();
+        candidates.shuffle(&mut rng);
+        candidates.into_iter()
+            .filter(|u| graph.degree(*u) > 0 && features.get_features(*u).len() > 0)
+            .filter_map(|u| {
+                let edges = graph.get_edges(u).0;
+                let v = edges[rng.gen_range(0, edges.len())];
+                if v != u && features.get_features(v).len() > 0 {
+                    Some((u, v))
+                } else {
+                    None
+                }
+            })
+            .take(num_pairs)
+            .collect()
+    }
+
+    // Mean reciprocal rank of each neighbor against random negatives, by cosine distance to the
+    // node.  Negatives which are neighbors of the node are skipped.
+    fn compute_ranking_mrr<G: CGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        pairs: &[(NodeID, NodeID)],
+        num_negatives: usize
+    ) -> f32 {
+        let rrs = pairs.par_iter().enumerate().map(|(idx, (u, v))| {
+            // Same seed each pass for consistency
+            let mut rng = self.stream(RANKING_STREAM).split(idx as u64).rng();
+            let hu = model.construct_node_embedding(
+                *u, 1f32, features, feature_embeddings, &mut rng).1;
+            let hv = model.construct_node_embedding(
+                *v, 1f32, features, feature_embeddings, &mut rng).1;
+            let pos_d = Distance::Cosine.compute(hu.value(), hv.value());
+
+            let neighbors = graph.get_edges(*u).0;
+            let mut closer = 0usize;
+            for _ in 0..num_negatives {
+                let neg = rng.gen_range(0, graph.len());
+                if neg == *u || neg == *v || neighbors.contains(&neg) 
+                    || features.get_features(neg).len() == 0 {
+                    continue
+                }
+
+                let hn = model.construct_node_embedding(
+                    neg, 1f32, features, feature_embeddings, &mut rng).1;
+                if Distance::Cosine.compute(hu.value(), hn.value()) < pos_d {
+                    closer += 1;
+                }
+            }
+            1f32 / (closer + 1) as f32
+        }).sum::<f32>();
+
+        rrs / pairs.len().max(1) as f32
+    }
+
+    // Wraps the batch sampler to filter out neighbors, if enabled
+    fn wrap_sampler<S: NodeSampler, G: CGraph, T: std::borrow::Borrow<NodeID>>(
+        &self,
+        sampler: S,
+        nodes: &[T],
+        graph: &G
+    ) -> EitherSampler<S, NeighborExclusionSampler<S>> {
+        if self.exclude_neighbors {
+            EitherSampler::Right(NeighborExclusionSampler::new(sampler, nodes, graph))
+        } else {
+            EitherSampler::Left(sampler)
+        }
+    }
+
+    // Runs the forward pass for a node and extracts the gradients, returning None if the node
+    // has no loss.  Losses are weighted according to the loss weighting and the node weights.
+    fn compute_node_gradients<G: CGraph + Send + Sync, S: NodeSampler, M: Model>(
+        &self,
+        graph: &G,
+        n_id: NodeID,
+        pass: usize,
+        batch_idx: usize,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        sampler: &S,
+        positive_tables: Option<&EdgeAliasTable>
+    ) -> Option<(f32, HashMap<usize, Vec<f32>>)> {
+        let weight = self.node_weights.as_ref().map(|w| w[n_id]).unwrap_or(1f32);
+        if weight == 0f32 {
+            return None
+        }
+
+        let mut rng = self.stream(TRAIN_STREAM).split(pass as u64)
+            .split(batch_idx as u64).split(n_id as u64).rng();
+        let (mut loss, hv_vars, thv_vars, hu_vars) = self.run_forward_pass(
+            graph, n_id, &features, &feature_embeddings, 
+            model, sampler, positive_tables, &mut rng);
+
+        loss = match self.loss_weighting {
+            LossWeighting::DegreeLog => {
+                let decrease = (1f32 + graph.degree(n_id) as f32).ln();
+                loss / decrease
+            },
+            LossWeighting::DegreeExponential(weight) => {
+                let decrease = (graph.degree(n_id) as f32).powf(weight);
+                loss / decrease
+            },
+            LossWeighting::None => { loss }
+        };
+
+        let loss_value = loss.value()[0];
+
+        // Sometimes there are weird errors due to underflows in softmax
+        // In this case, just print the graph and don't return
+        if loss_value.is_nan() {
+            Graph::print_graph(&loss);
+            None
+        } else if loss_value > 0f32 {
+            let mut grads = self.extract_gradients(&loss, hv_vars, thv_vars, hu_vars);
+
+            // Scaling the loss by a constant scales its gradients by the same constant, so we
+            // skip the extra node in the compute graph.
+            if self.node_weights.is_some() {
+                grads.values_mut().for_each(|g| g.iter_mut().for_each(|gi| *gi *= weight));
+            }
+            Some((loss_value * weight, grads))
+        } else {
+            None
+        }
+    }
+
+    // Drops gradients for frozen features so the optimizer never touches them
+    fn remove_frozen(&self, grads: &mut CHashMap<usize, Vec<f32>>) {
+        if let Some(frozen) = &self.frozen_features {
+            grads.retain(|feat_id, _| !frozen.get(*feat_id).cloned().unwrap_or(false));
+        }
+    }
+
+    // Holds back gradients whose norm, including previously held back gradients for the feature,
+    // is under the gradient threshold.  Held back gradients are added to the feature's next one.
+    fn threshold_gradients(
+        &self,
+        grads: &mut CHashMap<usize, Vec<f32>>,
+        residuals: &Mutex<CHashMap<usize, Vec<f32>>>
+    ) {
+        let threshold = match self.gradient_threshold {
+            Some(threshold) => threshold,
+            None => return
+        };
+
+        let mut residuals = residuals.lock().expect("Mutex poisoned!");
+        grads.retain(|feat_id, grad| {
+            if let Some(residual) = residuals.remove(feat_id) {
+                grad.iter_mut().zip(residual.iter()).for_each(|(gi, ri)| *gi += *ri);
+            }
+            let norm = grad.iter().map(|gi| gi * gi).sum::<f32>().sqrt();
+            if norm < threshold {
+                residuals.insert(*feat_id, std::mem::take(grad));
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    // Number of positives constructed per node
+    fn num_positives(&self) -> usize {
+        self.multi_positive.map(|mp| mp.positives).unwrap_or(1)
+    }
+
+    // Checks the feature embeddings after a pass, repairing non-finite rows if requested, and
+    // resets the updated feature flags for the next pass
+    fn check_health(
+        &self,
+        pass: usize,
+        feature_embeddings: &EmbeddingStore,
+        optimizer: &AdamOptimizer,
+        touched: Option<&[AtomicBool]>,
+        config: &Diagnostics
+    ) -> EmbeddingHealth {
+        let mut health = EmbeddingHealth::compute(pass, feature_embeddings, touched);
+        if config.repair && health.nonfinite_rows > 0 {
+            let mut rng = self.stream(DIAGNOSTICS_STREAM).split(pass as u64).rng();
+            let rows = diagnostics::nonfinite_rows(feature_embeddings);
+            for feat_id in rows.iter() {
+                randomize_embedding(feature_embeddings.get_embedding_mut_hogwild(*feat_id), &mut rng);
+                optimizer.reset(*feat_id);
+            }
+            health.repaired_rows = rows.len();
+        }
+        if let Some(touched) = touched {
+            touched.iter().for_each(|t| t.store(false, Ordering::Relaxed));
+        }
+        health
+    }
+
+    fn report_health(&self, health: &EmbeddingHealth, pb: &CLProgressBar) {
+        let coverage = health.coverage.unwrap_or(0f32) * 100f32;
+        pb.println(format!(
+            "Pass {} health - Norms p1/p50/p99: {:.4}/{:.4}/{:.4}, Zero rows: {}, \
+             Non-finite rows: {} ({} repaired), Coverage: {:.2}%",
+            health.pass, health.norm_percentiles[0], health.norm_percentiles[2],
+            health.norm_percentiles[4], health.zero_rows, health.nonfinite_rows,
+            health.repaired_rows, coverage));
+
+        #[cfg(feature = "tracing")]
+        {
+            if health.nonfinite_rows > 0 {
+                tracing::warn!(pass = health.pass, nonfinite_rows = health.nonfinite_rows,
+                               repaired_rows = health.repaired_rows, "non-finite feature embeddings");
+            }
+            tracing::info!(
+                pass = health.pass,
+                norm_percentiles = ?health.norm_percentiles,
+                zero_rows = health.zero_rows,
+                nonfinite_rows = health.nonfinite_rows,
+                coverage,
+                "ep health");
+        }
+    }
+
+    // Alias tables for weighted positive walks, only built when a loss will walk them
+    fn positive_tables<G: CGraph>(&self, graph: &G) -> Option<EdgeAliasTable> {
+        match self.loss {
+            Loss::PPR(..) if self.weighted_positives => Some(EdgeAliasTable::new(graph)),
+            _ => None
+        }
+    }
+
+    // Independent random stream for the given purpose
+    fn stream(&self, purpose: u64) -> SplitRng {
+        SplitRng::new(self.seed, self.rng_kind).split(purpose)
+    }
+
+    // Adds gaussian noise to the gradients, if enabled.  Each feature's noise comes from its own
+    // split of the provided stream.
+    fn add_gradient_noise(&self, grads: &mut CHashMap<usize, Vec<f32>>, noise: f32, seeds: SplitRng) {
+        if self.noise > 0.0 {
+            grads.par_iter_mut().for_each(|(feat, emb)| {
+                let mut rng = seeds.split(*feat as u64).rng();
+                emb.iter_mut().for_each(|ei| {
+                    *ei += noise * rng.sample::<f32,StandardNormal>(StandardNormal);
+                });
+            });
+        }
     }
 
     fn run_forward_pass<G: CGraph + Send + Sync, R: Rng, S: NodeSampler, M: Model>(
@@ -294,15 +1319,19 @@ impl EmbeddingPropagation {
         feature_embeddings: &EmbeddingStore,
         model: &M,
         sampler: &S,
+        positive_tables: Option<&EdgeAliasTable>,
         rng: &mut R
-    ) -> (ANode, NodeCounts, NodeCounts, Vec<NodeCounts>) {
+    ) -> (ANode, NodeCounts, Vec<NodeCounts>, Vec<NodeCounts>) {
         // h(v)
         let (hv_vars, hv) = model.construct_node_embedding(
             node, 1f32, features, &feature_embeddings, rng);
         
-        // ~h(v)
-        let (thv_vars, thv) = self.loss.construct_positive(
-            graph, node, features, &feature_embeddings, model, rng);
+        // ~h(v), once per positive
+        let (thv_vars, thvs): (Vec<_>, Vec<_>) = (0..self.num_positives()).map(|_| {
+            self.loss.construct_positive(
+                graph, node, features, &feature_embeddings, model, 
+                positive_tables, rng)
+        }).unzip();
         
         // h(u)
         let num_negs = self.loss.negatives();
@@ -320,17 +1349,52 @@ impl EmbeddingPropagation {
         });
 
         // Compute error
-        let loss = self.loss.compute(thv, hv.clone(), &hus);
+        let aggregation = self.multi_positive
+            .map(|mp| mp.aggregation)
+            .unwrap_or(PositiveAggregation::Mean);
+        let loss = self.loss.compute_multiple(thvs, hv.clone(), &hus, aggregation);
 
         (loss, hv_vars, thv_vars, hu_vars)
 
     }
 
+    // Plain f32 counterpart of run_forward_pass, which only scores the node and never builds the
+    // compute graph
+    fn score_node<G: CGraph + Send + Sync, R: Rng, S: NodeSampler, M: Model>(
+        &self, 
+        graph: &G,
+        node: NodeID,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        sampler: &S,
+        positive_tables: Option<&EdgeAliasTable>,
+        rng: &mut R
+    ) -> f32 {
+        let hv = model.construct_node_values(node, 1f32, features, feature_embeddings, rng);
+        let thvs: Vec<_> = (0..self.num_positives()).map(|_| {
+            self.loss.construct_positive_values(
+                graph, node, features, feature_embeddings, model, positive_tables, rng)
+        }).collect();
+
+        let num_negs = self.loss.negatives();
+        let mut negatives = Vec::with_capacity(num_negs);
+        sampler.sample_negatives(graph, node, &mut negatives, num_negs, rng);
+        let hus: Vec<_> = negatives.into_iter().map(|neg_node| {
+            model.construct_node_values(neg_node, 1f32, features, feature_embeddings, rng)
+        }).collect();
+
+        let aggregation = self.multi_positive
+            .map(|mp| mp.aggregation)
+            .unwrap_or(PositiveAggregation::Mean);
+        self.loss.score_multiple(&thvs, &hv, &hus, aggregation)
+    }
+
     fn extract_gradients(
         &self, 
         loss: &ANode,
         hv_vars: NodeCounts,
-        thv_vars: NodeCounts,
+        thv_vars: Vec<NodeCounts>,
         hu_vars: Vec<NodeCounts>
     ) -> HashMap<usize, Vec<f32>> {
 
@@ -346,7 +1410,9 @@ impl EmbeddingPropagation {
 
         let mut grads = HashMap::new();
         extract_grads(&agraph, &mut grads, hv_vars.into_iter());
-        extract_grads(&agraph, &mut grads, thv_vars.into_iter());
+        thv_vars.into_iter().for_each(|thv_var| {
+            extract_grads(&agraph, &mut grads, thv_var.into_iter());
+        });
         hu_vars.into_iter().for_each(|hu_var| {
             extract_grads(&agraph, &mut grads, hu_var.into_iter());
         });
@@ -380,22 +1446,28 @@ pub fn extract_grads(
 // Randomize embeddings.  Might make more sense to have in the embedding file.
 fn randomize_embedding_store(es: &mut EmbeddingStore, rng: &mut impl Rng) {
     for idx in 0..es.len() {
-        let e = es.get_embedding_mut(idx);
-        let mut norm = 0f32;
-        e.iter_mut().for_each(|ei| {
-            *ei = 2f32 * rng.gen::<f32>() - 1f32;
-            norm += ei.powf(2f32);
-        });
-        norm = norm.sqrt();
-        e.iter_mut().for_each(|ei| *ei /= norm);
+        randomize_embedding(es.get_embedding_mut(idx), rng);
     }
 }
 
+// Randomizes a single embedding to a unit vector
+fn randomize_embedding(e: &mut [f32], rng: &mut impl Rng) {
+    let mut norm = 0f32;
+    e.iter_mut().for_each(|ei| {
+        *ei = 2f32 * rng.gen::<f32>() - 1f32;
+        norm += ei.powf(2f32);
+    });
+    norm = norm.sqrt();
+    e.iter_mut().for_each(|ei| *ei /= norm);
+}
+
 
 #[cfg(test)]
 mod ep_tests {
     use super::*;
+    use rand_xorshift::XorShiftRng;
     use crate::graph::{CumCSR,CSR};
+    use crate::algos::utils::Sample;
 
     fn build_star_edges() -> Vec<(usize, usize, f32)> {
         let mut edges = Vec::new();
@@ -412,31 +1484,795 @@ mod ep_tests {
     #[test]
     fn test_simple_learn_dist() {
         let edges = build_star_edges();
-        let csr = CSR::construct_from_edges(edges);
+        let csr = CSR::construct_from_edges(edges, false);
         let ccsr = CumCSR::convert(csr);
         
-        let mut feature_store = FeatureStore::new(ccsr.len(), "feat".to_string());
+        let mut feature_store = FeatureStore::new(ccsr.len());
         feature_store.fill_missing_nodes();
 
-        let model = super::model::AveragedFeatureModel::new(None, None);
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
         let ep = EmbeddingPropagation {
-            alpha: 1e-2,
-            loss: Loss::MarginLoss(1f32, 1usize),
-            batch_size: 32,
-            hard_negs: 0,
             d_model: 5,
             valid_pct: 0.0,
             passes: 50,
-            noise: 0.0,
             seed: 202220222,
-            indicator: false
+            ..EmbeddingPropagation::default()
         };
 
-        let embeddings = ep.learn_feature_embeddings(&ccsr, &feature_store, None, &model);
+        let embeddings = ep.learn(&ccsr, &feature_store, None, &model).unwrap();
         for idx in 0..embeddings.len() {
             let e = embeddings.get_embedding(idx);
             println!("{:?} -> {:?}", idx, e);
         }
     }
 
+    #[test]
+    fn test_validate_only() {
+        let edges = build_star_edges();
+        let csr = CSR::construct_from_edges(edges, false);
+        let ccsr = CumCSR::convert(csr);
+
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            d_model: 5,
+            valid_pct: 0.0,
+            passes: 1,
+            seed: 202220222,
+            ..EmbeddingPropagation::default()
+        };
+
+        let mut rng = XorShiftRng::seed_from_u64(ep.seed);
+        let mut fe = EmbeddingStore::new(feature_store.num_features(), 5, Distance::Cosine);
+        randomize_embedding_store(&mut fe, &mut rng);
+        let orig = fe.get_embedding(0).to_vec();
+
+        let valid_idxs: Vec<_> = (0..10).collect();
+        let error = ep.validate(&ccsr, &feature_store, &fe, &model, &valid_idxs);
+        assert!(error.is_finite());
+        assert!(error >= 0f32);
+
+        // Weights shouldn't change
+        assert_eq!(fe.get_embedding(0), orig.as_slice());
+
+        // Empty validation sets are a noop
+        assert_eq!(ep.validate(&ccsr, &feature_store, &fe, &model, &[]), 0f32);
+
+        // Evaluation is repeatable and can score the embeddings under another loss
+        let eval_error = ep.evaluate(&ccsr, &valid_idxs, &feature_store, &fe, &model);
+        assert!((eval_error - error).abs() < 1e-5);
+        let bpr_ep = EmbeddingPropagation { loss: Loss::BPR(3), ..ep.clone() };
+        let bpr_error = bpr_ep.evaluate(&ccsr, &valid_idxs, &feature_store, &fe, &model);
+        assert!(bpr_error.is_finite());
+        let again = bpr_ep.evaluate(&ccsr, &valid_idxs, &feature_store, &fe, &model);
+        assert!((again - bpr_error).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_evaluate_score() {
+        // Node 0 points at nodes 1 and 2; node 3 is the only candidate negative
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(vec![(0, 1, 1.), (0, 2, 1.)], false));
+        let mut feature_store = FeatureStore::new(4);
+        for (node_id, name) in ["a", "b", "c", "d"].iter().enumerate() {
+            feature_store.set_features(node_id, [("node", *name)].into_iter());
+        }
+
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 2, Distance::Cosine);
+        for (node_id, emb) in [[0., 0.], [2., 0.], [0., 2.], [3., 3.]].iter().enumerate() {
+            fe.set_embedding(feature_store.get_features(node_id)[0], emb);
+        }
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            loss: Loss::MarginLoss(3., 1),
+            d_model: 2,
+            negative_pools: Some(CandidatePools::Global(vec![3])),
+            ..EmbeddingPropagation::default()
+        };
+
+        // h(0) = [0, 0], its reconstruction is the mean of b and c, [1, 1], and the negative is
+        // [3, 3], so the loss is 3 + |[1, 1]| - |[1, 1] - [3, 3]| = 3 - sqrt(2)
+        let score = ep.evaluate(&ccsr, &[0], &feature_store, &fe, &model);
+        assert!((score - (3. - 2f32.sqrt())).abs() < 1e-5);
+    }
+
+    // The plain f32 forward path used for scoring should match the compute graph's values
+    fn check_plain_values<M: Model>(model: &M, tolerance: f32) {
+        let mut edges = Vec::new();
+        for u in 0..6 {
+            edges.push((u, (u + 1) % 6, 1f32));
+            edges.push(((u + 1) % 6, u, 1f32));
+            edges.push((u, (u + 2) % 6, 2f32));
+        }
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(edges, false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        for node_id in 0..ccsr.len() {
+            let group = (node_id % 3).to_string();
+            feature_store.set_features(node_id, 
+                [("node", node_id.to_string()), ("group", group)].into_iter());
+        }
+        feature_store.add_dense_columns(&["price"]);
+        feature_store.set_dense(1, &[2.]).unwrap();
+
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), model.feature_dims(4), 
+                                         Distance::Cosine);
+        randomize_embedding_store(&mut fe, &mut rng);
+
+        let assert_close = |graph: &ANode, values: &[f32]| {
+            assert_eq!(graph.value().len(), values.len());
+            for (gi, vi) in graph.value().iter().zip(values.iter()) {
+                assert!((gi - vi).abs() < tolerance, "{:?} vs {:?}", graph.value(), values);
+            }
+        };
+        for node_id in 0..ccsr.len() {
+            let seeded = || XorShiftRng::seed_from_u64(node_id as u64);
+            let (_, emb) = model.construct_node_embedding(
+                node_id, 1., &feature_store, &fe, &mut seeded());
+            let values = model.construct_node_values(
+                node_id, 1., &feature_store, &fe, &mut seeded());
+            assert_close(&emb, &values);
+
+            let (_, emb) = model.reconstruct_node_embedding(
+                &ccsr, node_id, &feature_store, &fe, &mut seeded());
+            let values = model.reconstruct_node_values(
+                &ccsr, node_id, &feature_store, &fe, &mut seeded());
+            assert_close(&emb, &values);
+
+            let nodes = [(node_id, 1f32), ((node_id + 1) % 6, 2f32)];
+            let (_, emb) = model.construct_from_multiple_nodes(
+                nodes.iter().cloned(), &feature_store, &fe, &mut seeded());
+            let values = model.construct_from_multiple_nodes_values(
+                nodes.iter().cloned(), &feature_store, &fe, &mut seeded());
+            assert_close(&emb, &values);
+        }
+    }
+
+    #[test]
+    fn test_plain_forward_path() {
+        use super::model::{MaxPoolFeatureModel,WeightedSumFeatureModel,AttentionFeatureModel};
+        use super::attention::{MultiHeadedAttention,AttentionType};
+
+        check_plain_values(&super::model::AveragedFeatureModel::new(Sample::All, None, false, true), 
+                           1e-5);
+        check_plain_values(&MaxPoolFeatureModel::new(Sample::All, None, false), 1e-5);
+        check_plain_values(&WeightedSumFeatureModel::new(Sample::All, None, false), 1e-5);
+
+        // Training's attention uses an approximate softmax
+        for at in [AttentionType::Full, AttentionType::Sliding { window_size: 1 }] {
+            let mha = MultiHeadedAttention::new(2, 2, at);
+            check_plain_values(&AttentionFeatureModel::new(mha, Sample::All, None, false), 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_dense_projection() {
+        let mut feature_store = FeatureStore::new(2);
+        feature_store.set_features(0, [("feat", "a"), ("feat", "b")].into_iter());
+        feature_store.set_features(1, [("feat", "a")].into_iter());
+        feature_store.add_dense_columns(&["price"]);
+        feature_store.set_dense(0, &[2.]).unwrap();
+
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 2, Distance::Cosine);
+        fe.set_embedding(0, &[1., 0.]);
+        fe.set_embedding(1, &[0., 1.]);
+        fe.set_embedding(feature_store.dense_feature_id(0), &[0.5, 0.25]);
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let (counts, emb) = model.construct_node_embedding(0, 1., &feature_store, &fe, &mut rng);
+        assert_eq!(emb.value(), &[1.5, 1.]);
+        assert!(counts.contains_key(&2));
+
+        // Nodes without dense values only use their discrete features
+        let (counts, emb) = model.construct_node_embedding(1, 1., &feature_store, &fe, &mut rng);
+        assert_eq!(emb.value(), &[1., 0.]);
+        assert!(!counts.contains_key(&2));
+
+        // Reconstructions project the weighted average
+        let nodes = vec![(0, 1.), (1, 1.)].into_iter();
+        let (_, emb) = model.construct_from_multiple_nodes(nodes, &feature_store, &fe, &mut rng);
+        assert!((emb.value()[0] - (2. / 3. + 0.5)).abs() < 1e-6);
+
+        let mut out = vec![1., 0.];
+        super::model::project_dense(0, &feature_store, &fe, &mut out);
+        assert_eq!(out, vec![2., 0.5]);
+    }
+
+    #[test]
+    fn test_embed_features() {
+        let mut feature_store = FeatureStore::new(1);
+        feature_store.set_features(0, [("feat", "a"), ("feat", "b"), ("feat", "a")].into_iter());
+
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 2, Distance::Cosine);
+        fe.set_embedding(0, &[1., 0.]);
+        fe.set_embedding(1, &[0., 1.]);
+
+        // Cold start embeddings match the training aggregation
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let (_, emb) = model.construct_node_embedding(0, 1., &feature_store, &fe, &mut rng);
+        let cold = super::model::embed_features(&[0, 1, 0], &fe, &model);
+        assert_eq!(emb.value(), cold.as_slice());
+
+        // Unknown features are skipped
+        let named = [("feat", "b"), ("feat", "missing")];
+        let cold = super::model::embed_named_features(&named, &feature_store, &fe, &model);
+        assert_eq!(cold, vec![0., 1.]);
+        assert_eq!(super::model::embed_features(&[10], &fe, &model), vec![0., 0.]);
+    }
+
+    #[test]
+    fn test_embed_nodes() {
+        let edges = vec![(0, 1, 1.), (1, 0, 1.), (1, 2, 1.), (2, 1, 1.)];
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(edges, false));
+
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.set_features(0, [("feat", "a"), ("feat", "b")].into_iter());
+        feature_store.set_features(1, [("feat", "b")].into_iter());
+
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 2, Distance::Cosine);
+        fe.set_embedding(0, &[1., 0.]);
+        fe.set_embedding(1, &[0., 1.]);
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            d_model: 2,
+            valid_pct: 0.0,
+            passes: 1,
+            ..EmbeddingPropagation::default()
+        };
+
+        let embeddings = ep.embed_nodes(&ccsr, &feature_store, &fe, &model);
+        assert_eq!(embeddings.len(), 3);
+        assert_eq!(embeddings.get_embedding(0), &[0.5, 0.5]);
+        assert_eq!(embeddings.get_embedding(1), &[0., 1.]);
+        assert_eq!(embeddings.get_embedding(2), &[0., 0.]);
+    }
+
+    #[test]
+    fn test_rng_kind() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            d_model: 4,
+            passes: 0,
+            ..EmbeddingPropagation::default()
+        };
+        let pcg = EmbeddingPropagation { rng_kind: RngKind::Pcg, ..ep.clone() };
+
+        // Initialization follows the generator, and each generator is reproducible
+        let xorshift_fe = ep.learn(&ccsr, &feature_store, None, &model).unwrap();
+        let pcg_fe = pcg.learn(&ccsr, &feature_store, None, &model).unwrap();
+        let pcg_fe_2 = pcg.learn(&ccsr, &feature_store, None, &model).unwrap();
+        assert_ne!(xorshift_fe.get_embedding(0), pcg_fe.get_embedding(0));
+        assert_eq!(pcg_fe.get_embedding(0), pcg_fe_2.get_embedding(0));
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let edges = vec![(0, 1, 1.), (1, 0, 1.)];
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(edges, false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let mut ep = EmbeddingPropagation {
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 1,
+            ..EmbeddingPropagation::default()
+        };
+
+        // Feature embeddings with the wrong dimensions
+        let fe = EmbeddingStore::new(feature_store.num_embeddings(), 3, Distance::Cosine);
+        assert!(matches!(ep.learn(&ccsr, &feature_store, Some(fe), &model),
+                         Err(GraphLibError::DimensionMismatch { expected: 4, found: 3 })));
+
+        // Feature store which doesn't cover the graph
+        let small_store = FeatureStore::new(1);
+        assert!(ep.learn(&ccsr, &small_store, None, &model).is_err());
+
+        ep.batch_size = 0;
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+
+        ep.batch_size = 32;
+        ep.asynchronous = true;
+        ep.adaptive_batch = Some(AdaptiveBatchSize::new(1, 8, 64));
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+
+        ep.asynchronous = false;
+        ep.adaptive_batch = None;
+        ep.ranking_validation = Some(RankingValidation { num_pairs: 10, num_negatives: 0 });
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+
+        ep.ranking_validation = None;
+        ep.degree_balancing = Some(DegreeBalancing::Capped(0));
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+        ep.degree_balancing = Some(DegreeBalancing::InverseDegree(-1f32));
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+        ep.degree_balancing = Some(DegreeBalancing::InverseDegree(0.5));
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_ok());
+
+        ep.weighted_positives = true;
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+        ep.loss = Loss::PPR(1f32, 1, 0.5);
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_ok());
+
+        let bad_model = super::model::AveragedFeatureModel::new(
+            Sample::Probability(1.5), None, false, false);
+        assert!(ep.learn(&ccsr, &feature_store, None, &bad_model).is_err());
+    }
+
+    #[test]
+    fn test_frozen_features() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        for node_id in 0..ccsr.len() {
+            let token = (node_id % 10).to_string();
+            feature_store.set_features(node_id, 
+                [("pretrained", token), ("node", node_id.to_string())].into_iter());
+        }
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let mut ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            frozen_features: Some(feature_store.namespace_mask(&["pretrained"])),
+            ..EmbeddingPropagation::default()
+        };
+
+        // Frozen features need something to start from
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+
+        let mut rng = XorShiftRng::seed_from_u64(ep.seed);
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 4, Distance::Cosine);
+        randomize_embedding_store(&mut fe, &mut rng);
+        // Clones share the underlying buffer, so snapshot the values
+        let orig = fe.as_slice().to_vec();
+        let orig_row = |feat_id: usize| &orig[feat_id * 4..(feat_id + 1) * 4];
+
+        let fe = ep.learn(&ccsr, &feature_store, Some(fe), &model).unwrap();
+        let frozen = ep.frozen_features.as_ref().unwrap();
+        assert_eq!(frozen.iter().filter(|f| **f).count(), 10);
+        for feat_id in 0..fe.len() {
+            if frozen[feat_id] {
+                assert_eq!(fe.get_embedding(feat_id), orig_row(feat_id));
+            }
+        }
+        assert!((0..fe.len()).any(|feat_id| {
+            !frozen[feat_id] && fe.get_embedding(feat_id) != orig_row(feat_id)
+        }));
+
+        ep.frozen_features = Some(vec![false; feature_store.num_embeddings() + 1]);
+        assert!(ep.learn(&ccsr, &feature_store, Some(fe), &model).is_err());
+    }
+
+    #[test]
+    fn test_fine_tune() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        for node_id in 0..ccsr.len() {
+            feature_store.set_features(node_id, [("node", node_id.to_string())].into_iter());
+        }
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            ..EmbeddingPropagation::default()
+        };
+        let orig = ep.learn(&ccsr, &feature_store, None, &model).unwrap();
+
+        // The delta brings a brand new feature with it
+        feature_store.add_features(0, [("tag", "new")].into_iter());
+        let delta = [0, 1];
+        let touched: Vec<_> = delta.iter()
+            .flat_map(|node_id| feature_store.get_features(*node_id).to_vec())
+            .collect();
+
+        let fe = ep.fine_tune(&ccsr, &feature_store, orig.clone(), &delta, &model).unwrap();
+        assert_eq!(fe.len(), orig.len() + 1);
+        for feat_id in 0..orig.len() {
+            if !touched.contains(&feat_id) {
+                assert_eq!(fe.get_embedding(feat_id), orig.get_embedding(feat_id));
+            }
+        }
+        assert!(touched.iter().any(|feat_id| {
+            *feat_id >= orig.len() || fe.get_embedding(*feat_id) != orig.get_embedding(*feat_id)
+        }));
+
+        assert!(ep.fine_tune(&ccsr, &feature_store, orig.clone(), &[], &model).is_err());
+        assert!(ep.fine_tune(&ccsr, &feature_store, orig, &[100], &model).is_err());
+    }
+
+    #[test]
+    fn test_resume() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 32,
+            d_model: 4,
+            valid_pct: 0.1,
+            passes: 2,
+            ..EmbeddingPropagation::default()
+        };
+
+        // Clones share their embeddings, so each resume trains its own copy.  A single thread
+        // keeps the updates in a fixed order.
+        let copy = |es: &EmbeddingStore| {
+            EmbeddingStore::new_with_vec(es.len(), es.dims(), es.distance(), es.as_slice().to_vec())
+                .unwrap()
+        };
+        let runtime = Runtime::with_threads(1).unwrap();
+        let resume = |ep: &EmbeddingPropagation, fe: &EmbeddingStore, state: &TrainingState| {
+            runtime.install(|| {
+                ep.learn_resumable(&ccsr, &feature_store, Some(copy(fe)), Some(state), &model)
+            })
+        };
+
+        let (fe, state) = runtime.install(|| {
+            ep.learn_resumable(&ccsr, &feature_store, None, None, &model)
+        }).unwrap();
+        assert_eq!((state.pass, state.step), (2, 6));
+        assert!(state.lr_scheduler.is_some());
+
+        // Extending the run picks up where it left off, the same way every time
+        let extended = EmbeddingPropagation { passes: 4, ..ep.clone() };
+        let (fe_1, state_1) = resume(&extended, &fe, &state).unwrap();
+        let (fe_2, _) = resume(&extended, &fe, &state).unwrap();
+        assert_eq!((state_1.pass, state_1.step), (4, 12));
+        assert_ne!(fe_1.as_slice(), fe.as_slice());
+        assert_eq!(fe_1.as_slice(), fe_2.as_slice());
+
+        // The random stream comes from the state
+        let reseeded = TrainingState { rng_seed: state.rng_seed + 1, ..state.clone() };
+        let (fe_3, _) = resume(&extended, &fe, &reseeded).unwrap();
+        assert_ne!(fe_3.as_slice(), fe_1.as_slice());
+
+        // A finished run has nothing left to train
+        let (fe_4, state_4) = resume(&ep, &fe, &state).unwrap();
+        assert_eq!(fe_4.as_slice(), fe.as_slice());
+        assert_eq!((state_4.pass, state_4.step), (2, 6));
+
+        assert!(resume(&EmbeddingPropagation { alpha: 1e-3, ..extended.clone() }, &fe, &state)
+            .is_err());
+        let adaptive = EmbeddingPropagation {
+            adaptive_batch: Some(AdaptiveBatchSize::new(1, 8, 64)), ..extended.clone()
+        };
+        assert!(resume(&adaptive, &fe, &state).is_err());
+        assert!(extended.learn_resumable(&ccsr, &feature_store, None, Some(&state), &model)
+            .is_err());
+    }
+
+    #[test]
+    fn test_ranking_validation() {
+        // Two disjoint cliques of ten nodes
+        let mut edges = Vec::new();
+        for offset in [0, 10] {
+            for ni in offset..(offset + 10) {
+                for no in offset..(offset + 10) {
+                    if ni != no { edges.push((ni, no, 1f32)); }
+                }
+            }
+        }
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(edges, false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 2,
+            valid_pct: 0.5,
+            passes: 1,
+            ranking_validation: Some(RankingValidation { num_pairs: 5, num_negatives: 10 }),
+            ..EmbeddingPropagation::default()
+        };
+
+        let valid_idxs: Vec<_> = (0..ccsr.len()).collect();
+        let pairs = ep.sample_ranking_pairs(&ccsr, &feature_store, &valid_idxs, 20);
+        assert_eq!(pairs.len(), 20);
+        assert!(pairs.iter().all(|(u, v)| ccsr.get_edges(*u).0.contains(v)));
+
+        // Each clique embedded in its own direction ranks every neighbor first
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 2, Distance::Cosine);
+        for node_id in 0..ccsr.len() {
+            let feat = feature_store.get_features(node_id)[0];
+            let emb = if node_id < 10 { [1f32, 0f32] } else { [0f32, 1f32] };
+            fe.get_embedding_mut(feat).copy_from_slice(&emb);
+        }
+        let mrr = ep.compute_ranking_mrr(&ccsr, &feature_store, &fe, &model, &pairs, 50);
+        assert_eq!(mrr, 1f32);
+
+        // Mixing up the cliques ranks neighbors behind negatives
+        for node_id in 0..ccsr.len() {
+            let feat = feature_store.get_features(node_id)[0];
+            let emb = if node_id % 2 == 0 { [1f32, 0f32] } else { [0f32, 1f32] };
+            fe.get_embedding_mut(feat).copy_from_slice(&emb);
+        }
+        let mrr = ep.compute_ranking_mrr(&ccsr, &feature_store, &fe, &model, &pairs, 50);
+        assert!(mrr < 1f32);
+
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_ok());
+    }
+
+    #[test]
+    fn test_asynchronous() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            asynchronous: true,
+            ..EmbeddingPropagation::default()
+        };
+
+        let mut rng = XorShiftRng::seed_from_u64(ep.seed);
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 4, Distance::Cosine);
+        randomize_embedding_store(&mut fe, &mut rng);
+        // Clones share the underlying buffer, so snapshot the values
+        let orig = fe.as_slice().to_vec();
+
+        let fe = ep.learn(&ccsr, &feature_store, Some(fe), &model).unwrap();
+        let changed = fe.as_slice().chunks(4).zip(orig.chunks(4))
+            .filter(|(new, old)| new != old)
+            .count();
+        assert!(changed > 0);
+        assert!(fe.as_slice().iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn test_node_weights() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let mut ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            node_weights: Some(vec![0f32; ccsr.len()]),
+            ..EmbeddingPropagation::default()
+        };
+
+        let mut rng = XorShiftRng::seed_from_u64(ep.seed);
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 4, Distance::Cosine);
+        randomize_embedding_store(&mut fe, &mut rng);
+        // Clones share the underlying buffer, so snapshot the values
+        let orig = fe.as_slice().to_vec();
+
+        // Zero weights mean no anchors, so nothing is ever updated
+        let fe = ep.learn(&ccsr, &feature_store, Some(fe), &model).unwrap();
+        assert_eq!(fe.as_slice(), &orig[..]);
+
+        let mut weights = vec![0f32; ccsr.len()];
+        weights[0] = 2f32;
+        ep.node_weights = Some(weights);
+        let fe = ep.learn(&ccsr, &feature_store, Some(fe), &model).unwrap();
+        assert!(fe.as_slice() != &orig[..]);
+        assert!(fe.as_slice().iter().all(|v| v.is_finite()));
+
+        ep.node_weights = Some(vec![1f32; ccsr.len() - 1]);
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+        ep.node_weights = Some(vec![-1f32; ccsr.len()]);
+        assert!(ep.learn(&ccsr, &feature_store, None, &model).is_err());
+    }
+
+    #[test]
+    fn test_gradient_precision() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            gradient_precision: Precision::BF16,
+            ..EmbeddingPropagation::default()
+        };
+
+        let mut rng = XorShiftRng::seed_from_u64(ep.seed);
+        let mut fe = EmbeddingStore::new(feature_store.num_embeddings(), 4, Distance::Cosine);
+        randomize_embedding_store(&mut fe, &mut rng);
+        let orig = fe.clone();
+
+        let fe = ep.learn(&ccsr, &feature_store, Some(fe), &model).unwrap();
+        assert!(fe.as_slice() != orig.as_slice());
+        assert!(fe.as_slice().iter().all(|v| v.is_finite()));
+
+        let full = EmbeddingPropagation { gradient_precision: Precision::F32, ..ep.clone() };
+        assert!(ep.batch_item_bytes(&feature_store, 4) < full.batch_item_bytes(&feature_store, 4));
+    }
+
+    #[test]
+    fn test_gradient_threshold() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            gradient_threshold: Some(1f32),
+            ..EmbeddingPropagation::default()
+        };
+
+        // Feature 0 is held back until its accumulated gradient crosses the threshold
+        let residuals = Mutex::new(CHashMap::new());
+        let mut grads: CHashMap<_, _> = vec![(0, vec![0.6, 0.]), (1, vec![3., 4.])]
+            .into_iter()
+            .collect();
+        ep.threshold_gradients(&mut grads, &residuals);
+        assert_eq!(grads.keys().collect::<Vec<_>>(), vec![&1]);
+        assert_eq!(residuals.lock().unwrap()[&0], vec![0.6, 0.]);
+
+        let mut grads: CHashMap<_, _> = vec![(0, vec![0.6, 0.])].into_iter().collect();
+        ep.threshold_gradients(&mut grads, &residuals);
+        assert_eq!(grads[&0], vec![1.2, 0.]);
+        assert!(residuals.lock().unwrap().is_empty());
+
+        let fe = ep.learn(&ccsr, &feature_store, None, &model).unwrap();
+        assert!(fe.as_slice().iter().all(|v| v.is_finite()));
+
+        let negative = EmbeddingPropagation { gradient_threshold: Some(-1f32), ..ep.clone() };
+        assert!(negative.learn(&ccsr, &feature_store, None, &model).is_err());
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            diagnostics: Some(Diagnostics { repair: true }),
+            ..EmbeddingPropagation::default()
+        };
+
+        let mut fe = ep.learn(&ccsr, &feature_store, None, &model).unwrap();
+        assert!(fe.as_slice().iter().all(|v| v.is_finite()));
+
+        // Corrupted rows are reported and re-randomized
+        fe.set_embedding(3, &[f32::NAN, 0., 0., 0.]);
+        let optimizer = AdamOptimizer::new(0.9, 0.999, fe.dims(), fe.len());
+        let touched: Vec<_> = (0..fe.len()).map(|idx| AtomicBool::new(idx == 0)).collect();
+        let config = Diagnostics { repair: true };
+        let health = ep.check_health(1, &fe, &optimizer, Some(&touched), &config);
+        assert_eq!((health.nonfinite_rows, health.repaired_rows), (1, 1));
+        assert!(health.is_healthy());
+        assert_eq!(health.coverage, Some(1f32 / fe.len() as f32));
+        assert!(fe.get_embedding(3).iter().all(|v| v.is_finite()));
+        assert!(touched.iter().all(|t| !t.load(Ordering::Relaxed)));
+
+        let health = ep.check_health(2, &fe, &optimizer, None, &Diagnostics { repair: false });
+        assert_eq!((health.nonfinite_rows, health.coverage), (0, None));
+    }
+
+    #[test]
+    fn test_multi_positive() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let model = super::model::AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            passes: 2,
+            multi_positive: Some(MultiPositive { 
+                positives: 3, 
+                aggregation: PositiveAggregation::LogSumExp 
+            }),
+            ..EmbeddingPropagation::default()
+        };
+
+        let fe = ep.learn(&ccsr, &feature_store, None, &model).unwrap();
+        assert!(fe.as_slice().iter().all(|v| v.is_finite()));
+
+        // Extra positives are accounted for when sizing batches
+        let single = EmbeddingPropagation { multi_positive: None, ..ep.clone() };
+        assert!(ep.batch_item_bytes(&feature_store, 4) > single.batch_item_bytes(&feature_store, 4));
+
+        let none = EmbeddingPropagation { 
+            multi_positive: Some(MultiPositive { positives: 0, aggregation: PositiveAggregation::Mean }),
+            ..ep
+        };
+        assert!(none.learn(&ccsr, &feature_store, None, &model).is_err());
+    }
+
+    #[test]
+    fn test_pooling_models() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        feature_store.fill_missing_nodes();
+
+        let ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            passes: 2,
+            ..EmbeddingPropagation::default()
+        };
+
+        let model = super::model::MaxPoolFeatureModel::new(Sample::All, Some(10), false);
+        let fe = ep.learn(&ccsr, &feature_store, None, &model).unwrap();
+        assert_eq!(fe.dims(), 4);
+        assert_eq!(ep.embed_nodes(&ccsr, &feature_store, &fe, &model).dims(), 4);
+
+        // The learned scalar is an extra dimension of each feature embedding
+        let model = super::model::WeightedSumFeatureModel::new(Sample::All, Some(10), false);
+        let fe = ep.learn(&ccsr, &feature_store, None, &model).unwrap();
+        assert_eq!(fe.dims(), 5);
+        assert!(fe.as_slice().iter().all(|v| v.is_finite()));
+        assert_eq!(ep.embed_nodes(&ccsr, &feature_store, &fe, &model).dims(), 4);
+    }
+
+    #[test]
+    fn test_node_embeddings() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_star_edges(), false));
+        let model = super::model::AveragedFeatureModel::new(Sample::All, Some(10), false, false);
+        let mut ep = EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            passes: 2,
+            ..EmbeddingPropagation::default()
+        };
+
+        let ne = ep.learn_node_embeddings(&ccsr, None, &model).unwrap();
+        assert_eq!((ne.len(), ne.dims()), (ccsr.len(), 4));
+
+        // Rows are the node embeddings themselves
+        let identity = FeatureStore::identity(ccsr.len());
+        assert_eq!(identity.get_features(7), &[7]);
+        let es = ep.embed_nodes(&ccsr, &identity, &ne, &model);
+        assert_eq!(es.get_embedding(7), ne.get_embedding(7));
+
+        // Frozen features are frozen nodes
+        let mut frozen = vec![false; ccsr.len()];
+        frozen[3] = true;
+        ep.frozen_features = Some(frozen);
+        let before = ne.get_embedding(3).to_vec();
+        let ne = ep.learn_node_embeddings(&ccsr, Some(ne), &model).unwrap();
+        assert_eq!(ne.get_embedding(3), before.as_slice());
+
+        // Hybrid: shared features plus a free embedding per node
+        let mut feature_store = FeatureStore::new(ccsr.len());
+        (0..ccsr.len()).for_each(|node| {
+            feature_store.set_features(node, [("parity", (node % 2).to_string())].into_iter());
+        });
+        feature_store.add_node_features();
+        feature_store.add_node_features();
+        assert_eq!(feature_store.get_features(5).len(), 2);
+        assert_eq!(feature_store.num_embeddings(), 2 + ccsr.len());
+    }
+
 }
@@ -1,20 +1,45 @@
 //! The Embedding Propagation framework parameterizes over the feature aggregator - that is, given
 //! a node with a set of features, how do we combine them to product a node embedding?
 //! This module defines them
+//!
+//! # Custom models
+//!
+//! `Model` is the extension point for new aggregators, and is stable: downstream crates can
+//! implement it without forking.  The stable surface is the `Model` trait, `NodeCounts`, the
+//! `simple_grad` re-export, and the public helpers below: `collect_embeddings_from_node`,
+//! `collect_embeddings_from_features`, `sample_neighbors`, `add_dense_projection_with`, and the
+//! `mean_embeddings`, `max_pool_embeddings`, and `weighted_sum_embeddings` pooling functions.
+//! These only change in breaking releases; new trait methods always come with defaults.
+//!
+//! The one rule implementations must follow is that every feature embedding variable used to
+//! build an embedding is in the returned `NodeCounts`, keyed by feature id.  EP only extracts
+//! gradients for the variables it finds there, so anything missing silently never learns.  The
+//! `collect_*` helpers take care of this.
+use std::cmp::Reverse;
+
+/// The autograd library embeddings are built with, re-exported so custom models don't need to
+/// depend on a matching version themselves
+pub use simple_grad;
+
 use simple_grad::*;
+use float_ord::FloatOrd;
 use hashbrown::HashMap;
 use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
 
-use crate::FeatureStore;
-use crate::EmbeddingStore;
+use crate::feature_store::FeatureStore;
+use crate::embeddings::EmbeddingStore;
 use crate::graph::{Graph as CGraph,NodeID, CDFtoP};
 use crate::algos::utils::{Sample,weighted_reservoir_sample,reservoir_sample};
-use super::attention::{attention_mean,MultiHeadedAttention};
+use crate::error::GraphLibError;
+use super::attention::{attention_mean,attention_weights,softmax,MultiHeadedAttention,AttentionType};
+use super::attention::{attention_mean_values,softmax_values};
 
-/// Main interface for model.  Needs to be threadsafe
+/// Main interface for model.  Needs to be threadsafe, as nodes are embedded in parallel.
 pub trait Model: Send + Sync {
 
-    /// Given a node, construct a node embedding from its features.
+    /// Given a node, construct a node embedding from its features.  `weight` is added to the count
+    /// of each of the node's features.
     fn construct_node_embedding<R: Rng>(
         &self,
         node: NodeID,
@@ -24,7 +49,8 @@ pub trait Model: Send + Sync {
         rng: &mut R
     ) -> (NodeCounts, ANode);
 
-    /// Given a node, reconstruct a node from its neighborhood
+    /// Given a node, reconstruct a node from its neighborhood.  The reconstruction is the positive
+    /// the node's own embedding is pulled toward.
     fn reconstruct_node_embedding<G: CGraph, R: Rng>(
         &self,
         graph: &G,
@@ -43,15 +69,82 @@ pub trait Model: Send + Sync {
         rng: &mut R
     ) -> (NodeCounts, ANode); 
 
+    /// Construct an embedding from a raw list of feature ids rather than a node in the feature
+    /// store.  All features are used, so this is the inference counterpart to
+    /// construct_node_embedding.
+    fn construct_from_features<R: Rng>(
+        &self,
+        features: &[usize],
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> ANode;
+
+    /// Plain f32 counterpart of construct_node_embedding, for scoring nodes when no gradients are
+    /// needed.  The built in models compute it without the compute graph; the default builds the
+    /// graph and reads off the value.
+    fn construct_node_values<R: Rng>(
+        &self,
+        node: NodeID,
+        weight: f32,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        self.construct_node_embedding(node, weight, feature_store, feature_embeddings, rng)
+            .1.value().to_vec()
+    }
+
+    /// Plain f32 counterpart of reconstruct_node_embedding
+    fn reconstruct_node_values<G: CGraph, R: Rng>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        self.reconstruct_node_embedding(graph, node, feature_store, feature_embeddings, rng)
+            .1.value().to_vec()
+    }
+
+    /// Plain f32 counterpart of construct_from_multiple_nodes
+    fn construct_from_multiple_nodes_values<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+        &self,
+        nodes: I,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        self.construct_from_multiple_nodes(nodes, feature_store, feature_embeddings, rng)
+            .1.value().to_vec()
+    }
+
     /// Indicates whether it uses attention
-    fn uses_attention(&self) -> bool;
+    fn uses_attention(&self) -> bool {
+        false
+    }
+
+    /// Validates the model's settings, such as its sample rates, before training starts.
+    fn check(&self) -> Result<(), GraphLibError> {
+        Ok(())
+    }
+
+    /// Size of the feature embeddings needed for node embeddings of size d_model.  Models which
+    /// store extra values alongside each feature, such as attention's queries and keys, need more.
+    fn feature_dims(&self, d_model: usize) -> usize {
+        d_model
+    }
 
-    /// Size of the node embedding.  
-    fn feature_dims(&self, d_model: usize) -> usize;
+    /// Inverse of feature_dims: size of the node embedding given the feature embedding size.
+    fn node_dims(&self, feature_dims: usize) -> usize {
+        feature_dims
+    }
 
     /// Currently unused and should be axed (YAGNI).  If models have parmeters they can learn, we
     /// can expose them here.  Not wired up currently
-    fn parameters(&self) -> Vec<ANode>;
+    fn parameters(&self) -> Vec<ANode> {
+        Vec::with_capacity(0)
+    }
 }
 
 /// Creates node embeddings by averaging features together
@@ -139,14 +232,70 @@ impl Model for AveragedFeatureModel {
             None, rng)
     }
 
+    fn construct_from_features<R: Rng>(
+        &self,
+        features: &[usize],
+        feature_embeddings: &EmbeddingStore,
+        _rng: &mut R
+    ) -> ANode {
+        let feature_map = collect_embeddings_from_features(features, feature_embeddings);
+        mean_embeddings(feature_map.values())
+    }
+
+    fn construct_node_values<R: Rng>(
+        &self,
+        node: NodeID,
+        weight: f32,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        node_values(node, weight, feature_store, feature_embeddings, self.max_features,
+                    ValuePooling::Mean, rng)
+    }
+
+    fn reconstruct_node_values<G: CGraph, R: Rng>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        let it = sample_neighbors(graph, node, self.max_neighbor_nodes, 
+                                  self.weighted_neighbor_sampling, 
+                                  self.weighted_neighbor_averaging, rng);
+        multiple_nodes_values(it, feature_store, feature_embeddings, self.max_features, 
+                              ValuePooling::Mean, rng)
+    }
+
+    fn construct_from_multiple_nodes_values<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+        &self,
+        nodes: I,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        multiple_nodes_values(nodes, feature_store, feature_embeddings, self.max_features, 
+                              ValuePooling::Mean, rng)
+    }
+
     fn feature_dims(&self, d_model: usize) -> usize {
         d_model
     }
 
+    fn node_dims(&self, feature_dims: usize) -> usize {
+        feature_dims
+    }
+
     fn uses_attention(&self) -> bool {
         false
     }
 
+    fn check(&self) -> Result<(), GraphLibError> {
+        self.max_features.check()
+    }
+
     fn parameters(&self) -> Vec<ANode> {
         Vec::with_capacity(0)
     }
@@ -179,6 +328,47 @@ impl AttentionFeatureModel {
     ) -> Self {
         AttentionFeatureModel { mha, max_features, max_neighbor_nodes, weighted_neighbor_sampling }
     }
+
+    /// Share of the embedding each feature contributes through attention, for auditing why nodes
+    /// embed near each other.  Uses every feature, as in construct_from_features, and returns
+    /// (feature id, weight) pairs summing to 1, from most to least attended.  Dense columns and
+    /// features without embeddings are excluded.
+    pub fn attention_weights(
+        &self,
+        features: &[usize],
+        feature_embeddings: &EmbeddingStore
+    ) -> Vec<(usize, f32)> {
+        let feature_map = collect_embeddings_from_features(features, feature_embeddings);
+        let ids: Vec<_> = if self.mha.preserve_feature_order() {
+            features.iter().filter(|f| feature_map.contains_key(*f)).cloned().collect()
+        } else {
+            feature_map.keys().cloned().collect()
+        };
+
+        // Same fixed rng as embed_features, so random attention matches the embedding
+        let mut rng = XorShiftRng::seed_from_u64(0);
+        let it = ids.iter().map(|f| feature_map.get(f).expect("Collected above"));
+        let weights = attention_weights(it, &self.mha, &mut rng);
+
+        // Repeated features are attended to separately when order is preserved
+        let mut totals = HashMap::new();
+        ids.iter().zip(weights.into_iter()).for_each(|(f, w)| {
+            *totals.entry(*f).or_insert(0f32) += w;
+        });
+        let mut totals: Vec<_> = totals.into_iter().collect();
+        totals.sort_by_key(|(f, w)| (Reverse(FloatOrd(*w)), *f));
+        totals
+    }
+
+    /// Attention weights over a node's features
+    pub fn node_attention_weights(
+        &self,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore
+    ) -> Vec<(usize, f32)> {
+        self.attention_weights(feature_store.get_features(node), feature_embeddings)
+    }
 }
 
 impl Model for AttentionFeatureModel {
@@ -234,6 +424,60 @@ impl Model for AttentionFeatureModel {
             Some(self.mha.clone()), rng)
     }
 
+    fn construct_from_features<R: Rng>(
+        &self,
+        features: &[usize],
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> ANode {
+        let feature_map = collect_embeddings_from_features(features, feature_embeddings);
+        if self.mha.preserve_feature_order() {
+            let it = features.iter()
+                .filter(|f| feature_map.contains_key(*f))
+                .map(|f| feature_map.get(f).expect("Some type of error!"));
+            attention_mean(it, &self.mha, rng)
+        } else {
+            attention_mean(feature_map.values(), &self.mha, rng)
+        }
+    }
+
+    fn construct_node_values<R: Rng>(
+        &self,
+        node: NodeID,
+        _weight: f32,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        node_values(node, 1f32, feature_store, feature_embeddings, self.max_features,
+                    ValuePooling::Attention(&self.mha), rng)
+    }
+
+    fn reconstruct_node_values<G: CGraph, R: Rng>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        let it = sample_neighbors(graph, node, self.max_neighbor_nodes, 
+                                  self.weighted_neighbor_sampling, false, rng);
+        multiple_nodes_values(it, feature_store, feature_embeddings, self.max_features, 
+                              ValuePooling::Attention(&self.mha), rng)
+    }
+
+    fn construct_from_multiple_nodes_values<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+        &self,
+        nodes: I,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        multiple_nodes_values(nodes, feature_store, feature_embeddings, self.max_features, 
+                              ValuePooling::Attention(&self.mha), rng)
+    }
+
     fn uses_attention(&self) -> bool {
         true
     }
@@ -242,18 +486,314 @@ impl Model for AttentionFeatureModel {
         self.mha.num_heads * (self.mha.d_k * 2 + d_model)
     }
 
+    fn node_dims(&self, feature_dims: usize) -> usize {
+        feature_dims / self.mha.num_heads - self.mha.d_k * 2
+    }
+
+    fn check(&self) -> Result<(), GraphLibError> {
+        self.max_features.check()?;
+        if let AttentionType::Random { num_features } = &self.mha.attention_type {
+            num_features.check()?;
+        }
+        Ok(())
+    }
+
     fn parameters(&self) -> Vec<ANode> {
         Vec::with_capacity(0)
     }
  
 }
 
+/// Creates node embeddings from the element-wise max of the feature embeddings.  Nearly as cheap
+/// as averaging but lets a single strong feature dominate a dimension rather than being washed out
+/// by many weak ones.  Gradients only flow to the feature holding each dimension's max.
+pub struct MaxPoolFeatureModel {
+    /// Randomly sample max_features if provided
+    max_features: Sample,
+
+    /// Max neighbors to consider for reconstruction
+    max_neighbor_nodes: Option<usize>,
+
+    /// If true, samples neighborhoods proportionally to their edge weights
+    weighted_neighbor_sampling: bool
+}
+
+impl MaxPoolFeatureModel {
+    pub fn new(
+        max_features: Sample,
+        max_neighbor_nodes: Option<usize>,
+        weighted_neighbor_sampling: bool
+    ) -> Self {
+        MaxPoolFeatureModel { max_features, max_neighbor_nodes, weighted_neighbor_sampling }
+    }
+}
+
+impl Model for MaxPoolFeatureModel {
+    fn construct_node_embedding<R: Rng>(
+        &self,
+        node: NodeID,
+        weight: f32,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode) {
+        pooled_node_embedding(
+            node, weight, feature_store, feature_embeddings, self.max_features, Pooling::Max, rng)
+    }
+
+    fn reconstruct_node_embedding<G: CGraph, R: Rng>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode){
+        let it = sample_neighbors(graph, node, self.max_neighbor_nodes, 
+                                  self.weighted_neighbor_sampling, false, rng);
+        pooled_from_multiple_nodes(
+            it, feature_store, feature_embeddings, self.max_features, Pooling::Max, rng)
+    }
+
+    fn construct_from_multiple_nodes<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+        &self,
+        nodes: I,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode) { 
+        pooled_from_multiple_nodes(
+            nodes, feature_store, feature_embeddings, self.max_features, Pooling::Max, rng)
+    }
+
+    fn construct_from_features<R: Rng>(
+        &self,
+        features: &[usize],
+        feature_embeddings: &EmbeddingStore,
+        _rng: &mut R
+    ) -> ANode {
+        let feature_map = collect_embeddings_from_features(features, feature_embeddings);
+        max_pool_embeddings(feature_map.values())
+    }
+
+    fn construct_node_values<R: Rng>(
+        &self,
+        node: NodeID,
+        weight: f32,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        node_values(node, weight, feature_store, feature_embeddings, self.max_features,
+                    ValuePooling::Max, rng)
+    }
+
+    fn reconstruct_node_values<G: CGraph, R: Rng>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        let it = sample_neighbors(graph, node, self.max_neighbor_nodes, 
+                                  self.weighted_neighbor_sampling, false, rng);
+        multiple_nodes_values(it, feature_store, feature_embeddings, self.max_features, 
+                              ValuePooling::Max, rng)
+    }
+
+    fn construct_from_multiple_nodes_values<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+        &self,
+        nodes: I,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        multiple_nodes_values(nodes, feature_store, feature_embeddings, self.max_features, 
+                              ValuePooling::Max, rng)
+    }
+
+    fn feature_dims(&self, d_model: usize) -> usize {
+        d_model
+    }
+
+    fn node_dims(&self, feature_dims: usize) -> usize {
+        feature_dims
+    }
+
+    fn uses_attention(&self) -> bool {
+        false
+    }
+
+    fn check(&self) -> Result<(), GraphLibError> {
+        self.max_features.check()
+    }
+
+    fn parameters(&self) -> Vec<ANode> {
+        Vec::with_capacity(0)
+    }
+}
+
+/// Creates node embeddings from a weighted sum of the feature embeddings, where each feature
+/// learns a scalar importance alongside its embedding.  The scalar is stored as an extra, final,
+/// dimension of the feature embedding and the weights are the softmax of the scalars, so this is
+/// attention with a query shared by every node: far cheaper, as nothing is pairwise.
+pub struct WeightedSumFeatureModel {
+    /// Randomly sample max_features if provided
+    max_features: Sample,
+
+    /// Max neighbors to consider for reconstruction
+    max_neighbor_nodes: Option<usize>,
+
+    /// If true, samples neighborhoods proportionally to their edge weights
+    weighted_neighbor_sampling: bool
+}
+
+impl WeightedSumFeatureModel {
+    pub fn new(
+        max_features: Sample,
+        max_neighbor_nodes: Option<usize>,
+        weighted_neighbor_sampling: bool
+    ) -> Self {
+        WeightedSumFeatureModel { max_features, max_neighbor_nodes, weighted_neighbor_sampling }
+    }
+
+    /// Learned importance of each feature, the softmax of their scalars, as (feature id, weight)
+    /// pairs from most to least important.  Features without embeddings are excluded.
+    pub fn feature_weights(
+        &self,
+        features: &[usize],
+        feature_embeddings: &EmbeddingStore
+    ) -> Vec<(usize, f32)> {
+        let feature_map = collect_embeddings_from_features(features, feature_embeddings);
+        let d = feature_embeddings.dims() - 1;
+        let max = feature_map.values()
+            .map(|(emb, count)| emb.value()[d] + count.ln())
+            .max_by_key(|l| FloatOrd(*l))
+            .unwrap_or(0f32);
+        let mut weights: Vec<_> = feature_map.iter()
+            .map(|(f, (emb, count))| (*f, (emb.value()[d] + count.ln() - max).exp()))
+            .collect();
+        let total: f32 = weights.iter().map(|(_, w)| w).sum();
+        weights.iter_mut().for_each(|(_, w)| *w /= total);
+        weights.sort_by_key(|(f, w)| (Reverse(FloatOrd(*w)), *f));
+        weights
+    }
+}
+
+impl Model for WeightedSumFeatureModel {
+    fn construct_node_embedding<R: Rng>(
+        &self,
+        node: NodeID,
+        weight: f32,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode) {
+        pooled_node_embedding(node, weight, feature_store, feature_embeddings, self.max_features, 
+                              Pooling::WeightedSum, rng)
+    }
+
+    fn reconstruct_node_embedding<G: CGraph, R: Rng>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode){
+        let it = sample_neighbors(graph, node, self.max_neighbor_nodes, 
+                                  self.weighted_neighbor_sampling, false, rng);
+        pooled_from_multiple_nodes(it, feature_store, feature_embeddings, self.max_features, 
+                                   Pooling::WeightedSum, rng)
+    }
+
+    fn construct_from_multiple_nodes<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+        &self,
+        nodes: I,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode) { 
+        pooled_from_multiple_nodes(nodes, feature_store, feature_embeddings, self.max_features, 
+                                   Pooling::WeightedSum, rng)
+    }
+
+    fn construct_from_features<R: Rng>(
+        &self,
+        features: &[usize],
+        feature_embeddings: &EmbeddingStore,
+        _rng: &mut R
+    ) -> ANode {
+        let feature_map = collect_embeddings_from_features(features, feature_embeddings);
+        weighted_sum_embeddings(feature_map.values())
+    }
+
+    fn construct_node_values<R: Rng>(
+        &self,
+        node: NodeID,
+        weight: f32,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        node_values(node, weight, feature_store, feature_embeddings, self.max_features,
+                    ValuePooling::WeightedSum, rng)
+    }
+
+    fn reconstruct_node_values<G: CGraph, R: Rng>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        let it = sample_neighbors(graph, node, self.max_neighbor_nodes, 
+                                  self.weighted_neighbor_sampling, false, rng);
+        multiple_nodes_values(it, feature_store, feature_embeddings, self.max_features, 
+                              ValuePooling::WeightedSum, rng)
+    }
+
+    fn construct_from_multiple_nodes_values<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+        &self,
+        nodes: I,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        multiple_nodes_values(nodes, feature_store, feature_embeddings, self.max_features, 
+                              ValuePooling::WeightedSum, rng)
+    }
+
+    fn feature_dims(&self, d_model: usize) -> usize {
+        d_model + 1
+    }
+
+    fn node_dims(&self, feature_dims: usize) -> usize {
+        feature_dims - 1
+    }
+
+    fn uses_attention(&self) -> bool {
+        false
+    }
+
+    fn check(&self) -> Result<(), GraphLibError> {
+        self.max_features.check()
+    }
+
+    fn parameters(&self) -> Vec<ANode> {
+        Vec::with_capacity(0)
+    }
+}
+
 /// We track the number of times a features has been seen to help reduce the gradient graph we need
 /// to compute.  It's a bit of a headache for the book keeping but the speed up is worth it.  Could
 /// probably be abstracted better.
 pub type NodeCounts = HashMap<usize, (ANode, f32)>;
 
-/// Gets the feature embeddings for a node, adding or updating the counts
+/// Gets the feature embeddings for a node, adding or updating the counts.  Panics if max_features
+/// has a probability outside of (0, 1], which Model::check rejects before training.
 pub fn collect_embeddings_from_node<R: Rng>(
     node: NodeID,
     mut weight: f32,
@@ -264,7 +804,8 @@ pub fn collect_embeddings_from_node<R: Rng>(
     rng: &mut R
 ) {
     let feats = feature_store.get_features(node);
-    let (max_features, scalar) = max_features.sample(feats.len(), true, rng);
+    let (max_features, scalar) = max_features.sample(feats.len(), true, rng)
+        .expect("max_features should be validated by Model::check!");
     weight += scalar;
     for feat in feats.choose_multiple(rng, max_features) {
         if let Some((_emb, count)) = feat_map.get_mut(feat) {
@@ -277,6 +818,57 @@ pub fn collect_embeddings_from_node<R: Rng>(
     }
 }
 
+/// Gets the feature embeddings for a raw list of features.  Unlike collect_embeddings_from_node,
+/// no sampling is done and ids outside of the feature embeddings are ignored.
+pub fn collect_embeddings_from_features(
+    features: &[usize],
+    feature_embeddings: &EmbeddingStore
+) -> NodeCounts {
+    let mut feat_map = HashMap::new();
+    for feat in features.iter().filter(|f| **f < feature_embeddings.len()) {
+        if let Some((_emb, count)) = feat_map.get_mut(feat) {
+            *count += 1f32;
+        } else {
+            let v = Variable::pooled(feature_embeddings.get_embedding(*feat));
+            feat_map.insert(*feat, (v, 1f32));
+        }
+    }
+    feat_map
+}
+
+/// Embeds a set of features which don't belong to a node in the training graph, ie. cold start
+/// nodes, using the same aggregation as training.  Returns a zero vector when none of the
+/// features have embeddings.
+pub fn embed_features<M: Model>(
+    features: &[usize],
+    feature_embeddings: &EmbeddingStore,
+    model: &M
+) -> Vec<f32> {
+    let no_embeddings = features.iter().all(|f| *f >= feature_embeddings.len());
+    if no_embeddings {
+        return vec![0f32; model.node_dims(feature_embeddings.dims())]
+    }
+
+    // Only random attention uses the rng; fix it so inference is repeatable
+    let mut rng = XorShiftRng::seed_from_u64(0);
+    model.construct_from_features(features, feature_embeddings, &mut rng).value().to_vec()
+}
+
+/// Same as embed_features, but with (namespace, name) features looked up in the feature store's
+/// vocab.  Features which weren't seen during training are skipped.
+pub fn embed_named_features<M: Model>(
+    features: &[(&str, &str)],
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    model: &M
+) -> Vec<f32> {
+    let vocab = feature_store.get_vocab();
+    let ids: Vec<_> = features.iter()
+        .filter_map(|(ns, name)| vocab.get_node_id(*ns, name))
+        .collect();
+    embed_features(&ids, feature_embeddings, model)
+}
+
 // H(n)
 // Average the features associated with a node
 // to create the node embedding
@@ -297,6 +889,8 @@ pub fn construct_node_embedding<R: Rng>(
                                  rng);
 
     let mean = mean_embeddings(feature_map.values());
+    let mean = add_dense_projection(&[(node, 1f32)], feature_store, feature_embeddings,
+                                    &mut feature_map, None, mean);
     (feature_map, mean)
 }
 
@@ -331,6 +925,8 @@ pub fn attention_construct_node_embedding<R: Rng>(
     } else {
         attention_mean(feature_map.values(), &mha, rng)
     };
+    let mean = add_dense_projection(&[(node, 1f32)], feature_store, feature_embeddings,
+                                    &mut feature_map, Some(&mha), mean);
     (feature_map, mean)
 }
 
@@ -351,35 +947,42 @@ fn reconstruct_node_embedding<G: CGraph, R: Rng>(
     weighted_neighbor_averaging: bool,
     rng: &mut R
 ) -> (NodeCounts, ANode) {
-    let (edges, weights) = &graph.get_edges(node);
+    let it = sample_neighbors(graph, node, max_nodes, weighted_neighbor_sampling, 
+                              weighted_neighbor_averaging, rng);
+    construct_from_multiple_nodes(it,
+        feature_store,
+        feature_embeddings,
+        max_features,
+        mha,
+        rng)
+}
+
+/// Neighbors used to reconstruct a node along with their weights, uniformly or proportionally to
+/// edge weights sampled down to max_nodes.  Weights are 1 unless weighted_neighbor_averaging,
+/// in which case they're the edge transition probabilities scaled by the degree.
+pub fn sample_neighbors<'a, G: CGraph, R: Rng>(
+    graph: &'a G,
+    node: NodeID,
+    max_nodes: Option<usize>,
+    weighted_neighbor_sampling: bool,
+    weighted_neighbor_averaging: bool,
+    rng: &mut R
+) -> Box<dyn Iterator<Item=(NodeID, f32)> + 'a> {
+    let (edges, weights) = graph.get_edges(node);
     
     let mn = max_nodes.unwrap_or(edges.len());
-    let weights: Box<dyn Iterator<Item=f32>> = if weighted_neighbor_averaging {
-        Box::new(CDFtoP::new(weights).map(|p| p * edges.len() as f32))
+    let weights: Box<dyn Iterator<Item=f32> + 'a> = if weighted_neighbor_averaging {
+        Box::new(CDFtoP::new(weights).map(move |p| p * edges.len() as f32))
     } else {
         Box::new(std::iter::repeat(1f32))
     };
     let it = edges.iter().cloned().zip(weights);
     if edges.len() <= mn {
-        construct_from_multiple_nodes(it,
-            feature_store,
-            feature_embeddings,
-            max_features,
-            mha,
-            rng)
+        Box::new(it)
+    } else if weighted_neighbor_sampling {
+        Box::new(weighted_reservoir_sample(it, mn, rng).into_iter())
     } else {
-        let it:Box<dyn Iterator<Item=(NodeID, f32)>> = if weighted_neighbor_sampling {
-            Box::new(weighted_reservoir_sample(it, mn, rng).into_iter())
-        } else {
-            Box::new(reservoir_sample(it, mn, rng).into_iter())
-        };
-
-        construct_from_multiple_nodes(it,
-            feature_store,
-            feature_embeddings,
-            max_features,
-            mha,
-            rng)
+        Box::new(reservoir_sample(it, mn, rng).into_iter())
     }
 }
 
@@ -393,11 +996,16 @@ fn construct_from_multiple_nodes<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
 ) -> (NodeCounts, ANode) {
     let mut feature_map = HashMap::new();
     let mut new_nodes = Vec::with_capacity(0);
+    let mut dense_nodes = Vec::with_capacity(0);
     for (node, weight) in nodes {
         if mha.is_some() {
             new_nodes.push(node.clone());
         }
 
+        if feature_store.dense_dims() > 0 {
+            dense_nodes.push((node, weight));
+        }
+
         collect_embeddings_from_node(node, weight, feature_store, 
                                      feature_embeddings, 
                                      &mut feature_map,
@@ -405,14 +1013,367 @@ fn construct_from_multiple_nodes<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
                                      rng);
     }
 
-    let mean = if let Some(attention) = mha {
-        attention_multiple(new_nodes, feature_store, &feature_map, attention, rng)
+    let mean = if let Some(attention) = &mha {
+        attention_multiple(new_nodes, feature_store, &feature_map, attention.clone(), rng)
     } else {
         mean_embeddings(feature_map.values())
     };
+    let mean = add_dense_projection(&dense_nodes, feature_store, feature_embeddings,
+                                    &mut feature_map, mha.as_ref(), mean);
     (feature_map, mean)
 }
 
+// Pooling operators for the cheap aggregation models
+#[derive(Clone,Copy)]
+enum Pooling {
+    Max,
+    WeightedSum
+}
+
+impl Pooling {
+    fn pool<'a>(&self, items: impl Iterator<Item=&'a (ANode, f32)>) -> ANode {
+        match self {
+            Pooling::Max => max_pool_embeddings(items),
+            Pooling::WeightedSum => weighted_sum_embeddings(items)
+        }
+    }
+
+    // Maps a feature embedding into the node embedding space, for dense columns
+    fn project(&self, emb: &ANode) -> ANode {
+        match self {
+            Pooling::Max => emb.clone(),
+            Pooling::WeightedSum => emb.slice(0, emb.value().len() - 1)
+        }
+    }
+}
+
+fn pooled_node_embedding<R: Rng>(
+    node: NodeID,
+    weight: f32,
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    max_features: Sample,
+    pooling: Pooling,
+    rng: &mut R
+) -> (NodeCounts, ANode) {
+    let mut feature_map = HashMap::new();
+    collect_embeddings_from_node(node, weight, feature_store, feature_embeddings, 
+                                 &mut feature_map, max_features, rng);
+
+    let emb = pooling.pool(feature_map.values());
+    let emb = add_dense_projection_with(&[(node, 1f32)], feature_store, feature_embeddings,
+                                        &mut feature_map, &|v| pooling.project(v), emb);
+    (feature_map, emb)
+}
+
+fn pooled_from_multiple_nodes<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+    nodes: I,
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    max_features: Sample,
+    pooling: Pooling,
+    rng: &mut R
+) -> (NodeCounts, ANode) {
+    let mut feature_map = HashMap::new();
+    let mut dense_nodes = Vec::with_capacity(0);
+    for (node, weight) in nodes {
+        if feature_store.dense_dims() > 0 {
+            dense_nodes.push((node, weight));
+        }
+        collect_embeddings_from_node(node, weight, feature_store, feature_embeddings, 
+                                     &mut feature_map, max_features, rng);
+    }
+
+    let emb = pooling.pool(feature_map.values());
+    let emb = add_dense_projection_with(&dense_nodes, feature_store, feature_embeddings,
+                                        &mut feature_map, &|v| pooling.project(v), emb);
+    (feature_map, emb)
+}
+
+// Adds the learned linear projection of the dense columns to the embedding.  Each dense column
+// has its own feature embedding, so the projection is the sum of the column embeddings scaled by
+// the node's values, which also lets gradients flow through the usual NodeCounts book keeping.
+// With multiple nodes we project the weighted average of their dense vectors.  Attention models
+// project into the first head's value space.
+fn add_dense_projection(
+    nodes: &[(NodeID, f32)],
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    feat_map: &mut NodeCounts,
+    mha: Option<&MultiHeadedAttention>,
+    emb: ANode
+) -> ANode {
+    let project = |var: &ANode| match mha {
+        Some(mha) => mha.get_value_vec(var, 0),
+        None => var.clone()
+    };
+    add_dense_projection_with(nodes, feature_store, feature_embeddings, feat_map, &project, emb)
+}
+
+/// Adds the learned projection of the nodes' dense columns, if the feature store has any, to an
+/// embedding.  `project` maps a column's feature embedding into the node embedding space, which
+/// is the identity when they're the same size.  Column embeddings are added to feat_map.
+pub fn add_dense_projection_with(
+    nodes: &[(NodeID, f32)],
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    feat_map: &mut NodeCounts,
+    project: &dyn Fn(&ANode) -> ANode,
+    emb: ANode
+) -> ANode {
+    let dims = feature_store.dense_dims();
+    if dims == 0 || nodes.is_empty() {
+        return emb
+    }
+
+    let mut avg = vec![0f32; dims];
+    let mut total = 0f32;
+    for (node, weight) in nodes.iter() {
+        avg.iter_mut().zip(feature_store.get_dense(*node).iter())
+            .for_each(|(ai, xi)| *ai += weight * xi);
+        total += weight;
+    }
+
+    if total <= 0f32 {
+        return emb
+    }
+
+    let mut terms = Vec::with_capacity(dims);
+    for (column, x) in avg.iter().enumerate() {
+        let x = x / total;
+        if x == 0f32 { continue }
+
+        let feat_id = feature_store.dense_feature_id(column);
+        let var = feat_map.entry(feat_id).or_insert_with(|| {
+            (Variable::pooled(feature_embeddings.get_embedding(feat_id)), 1f32)
+        }).0.clone();
+
+        terms.push(&project(&var) * x);
+    }
+
+    if terms.is_empty() {
+        emb
+    } else {
+        emb + terms.sum_all()
+    }
+}
+
+/// Non-differentiable version of the dense projection for inference: adds the projection of the
+/// node's dense columns to an already constructed embedding.  Matches the averaged models.
+pub fn project_dense(
+    node: NodeID,
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    out: &mut [f32]
+) {
+    for (column, x) in feature_store.get_dense(node).iter().enumerate() {
+        if *x == 0f32 { continue }
+        let e = feature_embeddings.get_embedding(feature_store.dense_feature_id(column));
+        out.iter_mut().zip(e.iter()).for_each(|(oi, ei)| *oi += x * ei);
+    }
+}
+
+// Feature ids and their counts: the plain counterpart of NodeCounts, for scoring without the
+// compute graph
+type FeatureCounts = HashMap<usize, f32>;
+
+// Plain f32 versions of the models' aggregations, matching their compute graphs
+#[derive(Clone,Copy)]
+enum ValuePooling<'a> {
+    Mean,
+    Max,
+    WeightedSum,
+    Attention(&'a MultiHeadedAttention)
+}
+
+impl ValuePooling<'_> {
+    // Orders a node's features for pooling; context windows attend in the node's feature order
+    fn node_items(
+        &self,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        counts: &FeatureCounts
+    ) -> Vec<(usize, f32)> {
+        match self {
+            ValuePooling::Attention(mha) if mha.preserve_feature_order() => {
+                feature_store.get_features(node).iter()
+                    .filter_map(|f| counts.get(f).map(|c| (*f, *c)))
+                    .collect()
+            },
+            _ => counts.iter().map(|(f, c)| (*f, *c)).collect()
+        }
+    }
+
+    fn pool<R: Rng>(
+        &self,
+        items: &[(usize, f32)],
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> Vec<f32> {
+        let embs: Vec<_> = items.iter()
+            .map(|(f, count)| (feature_embeddings.get_embedding(*f), *count))
+            .collect();
+        match *self {
+            ValuePooling::Mean => {
+                let mut out = vec![0f32; feature_embeddings.dims()];
+                let mut n = 0f32;
+                embs.iter().for_each(|(emb, count)| {
+                    out.iter_mut().zip(emb.iter()).for_each(|(oi, ei)| *oi += count * ei);
+                    n += count;
+                });
+                out.iter_mut().for_each(|oi| *oi /= n);
+                out
+            },
+            ValuePooling::Max => {
+                let mut out = embs[0].0.to_vec();
+                embs[1..].iter().for_each(|(emb, _)| {
+                    out.iter_mut().zip(emb.iter()).for_each(|(oi, ei)| *oi = (*oi).max(*ei));
+                });
+                out
+            },
+            ValuePooling::WeightedSum => {
+                let d = feature_embeddings.dims() - 1;
+                if embs.len() == 1 {
+                    return embs[0].0[..d].to_vec()
+                }
+                let mut weights: Vec<_> = embs.iter()
+                    .map(|(emb, count)| emb[d] + count.max(f32::MIN_POSITIVE).ln())
+                    .collect();
+                softmax_values(&mut weights);
+                let mut out = vec![0f32; d];
+                embs.iter().zip(weights.iter()).for_each(|((emb, _), w)| {
+                    out.iter_mut().zip(emb.iter()).for_each(|(oi, ei)| *oi += w * ei);
+                });
+                out
+            },
+            ValuePooling::Attention(mha) => attention_mean_values(&embs, mha, rng)
+        }
+    }
+
+    // Maps a dense column's embedding into the node embedding space
+    fn project<'b>(&self, emb: &'b [f32]) -> &'b [f32] {
+        match self {
+            ValuePooling::Mean | ValuePooling::Max => emb,
+            ValuePooling::WeightedSum => &emb[..emb.len() - 1],
+            ValuePooling::Attention(mha) => mha.value_values(emb, 0)
+        }
+    }
+}
+
+// Plain counterpart of collect_embeddings_from_node, sampling features the same way
+fn collect_feature_counts<R: Rng>(
+    node: NodeID,
+    mut weight: f32,
+    feature_store: &FeatureStore,
+    counts: &mut FeatureCounts,
+    max_features: Sample,
+    rng: &mut R
+) {
+    let feats = feature_store.get_features(node);
+    let (max_features, scalar) = max_features.sample(feats.len(), true, rng)
+        .expect("max_features should be validated by Model::check!");
+    weight += scalar;
+    for feat in feats.choose_multiple(rng, max_features) {
+        *counts.entry(*feat).or_insert(0f32) += weight;
+    }
+}
+
+// Plain counterpart of add_dense_projection_with
+fn add_dense_values(
+    nodes: &[(NodeID, f32)],
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    pooling: ValuePooling,
+    emb: &mut [f32]
+) {
+    let dims = feature_store.dense_dims();
+    if dims == 0 || nodes.is_empty() {
+        return
+    }
+
+    let mut avg = vec![0f32; dims];
+    let mut total = 0f32;
+    for (node, weight) in nodes.iter() {
+        avg.iter_mut().zip(feature_store.get_dense(*node).iter())
+            .for_each(|(ai, xi)| *ai += weight * xi);
+        total += weight;
+    }
+
+    if total <= 0f32 {
+        return
+    }
+
+    for (column, x) in avg.iter().enumerate() {
+        let x = x / total;
+        if x == 0f32 { continue }
+        let e = feature_embeddings.get_embedding(feature_store.dense_feature_id(column));
+        emb.iter_mut().zip(pooling.project(e).iter()).for_each(|(oi, ei)| *oi += x * ei);
+    }
+}
+
+// Plain counterpart of a model's node embedding
+fn node_values<R: Rng>(
+    node: NodeID,
+    weight: f32,
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    max_features: Sample,
+    pooling: ValuePooling,
+    rng: &mut R
+) -> Vec<f32> {
+    let mut counts = FeatureCounts::new();
+    collect_feature_counts(node, weight, feature_store, &mut counts, max_features, rng);
+    let items = pooling.node_items(node, feature_store, &counts);
+    let mut emb = pooling.pool(&items, feature_embeddings, rng);
+    add_dense_values(&[(node, 1f32)], feature_store, feature_embeddings, pooling, &mut emb);
+    emb
+}
+
+// Plain counterpart of a model's embedding of multiple nodes.  Attention attends within each
+// node and averages the nodes, as in attention_multiple; the other poolings pool all of the
+// nodes' features together.
+fn multiple_nodes_values<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+    nodes: I,
+    feature_store: &FeatureStore,
+    feature_embeddings: &EmbeddingStore,
+    max_features: Sample,
+    pooling: ValuePooling,
+    rng: &mut R
+) -> Vec<f32> {
+    let nodes: Vec<_> = nodes.collect();
+    let mut counts = FeatureCounts::new();
+    for (node, weight) in nodes.iter() {
+        collect_feature_counts(*node, *weight, feature_store, &mut counts, max_features, rng);
+    }
+
+    let mut emb = if let ValuePooling::Attention(_) = pooling {
+        let mut per_node = FeatureCounts::new();
+        let node_embs: Vec<_> = nodes.iter().map(|(node, _)| {
+            per_node.clear();
+            let feats = feature_store.get_features(*node);
+            feats.iter().filter(|f| counts.contains_key(*f)).for_each(|f| {
+                *per_node.entry(*f).or_insert(0f32) += 1f32;
+            });
+            let items: Vec<_> = feats.iter()
+                .filter_map(|f| per_node.get(f).map(|c| (*f, *c)))
+                .collect();
+            pooling.pool(&items, feature_embeddings, rng)
+        }).collect();
+
+        let mut mean = vec![0f32; node_embs[0].len()];
+        node_embs.iter().for_each(|e| {
+            mean.iter_mut().zip(e.iter()).for_each(|(mi, ei)| *mi += ei);
+        });
+        mean.iter_mut().for_each(|mi| *mi /= node_embs.len() as f32);
+        mean
+    } else {
+        let items: Vec<_> = counts.iter().map(|(f, c)| (*f, *c)).collect();
+        pooling.pool(&items, feature_embeddings, rng)
+    };
+    add_dense_values(&nodes, feature_store, feature_embeddings, pooling, &mut emb);
+    emb
+}
+
 
 fn attention_multiple(
     new_nodes: Vec<NodeID>,
@@ -443,6 +1404,7 @@ fn attention_multiple(
     mean_embeddings(output.iter())
 }
 
+/// Count weighted mean of the feature embeddings
 pub fn mean_embeddings<'a>(
     items: impl Iterator<Item=&'a (ANode, f32)>
 ) -> ANode {
@@ -460,3 +1422,109 @@ pub fn mean_embeddings<'a>(
     vs.sum_all() / n as f32
 }
 
+
+/// Element-wise max of the feature embeddings.  Counts are ignored.
+pub fn max_pool_embeddings<'a>(
+    items: impl Iterator<Item=&'a (ANode, f32)>
+) -> ANode {
+    let embs: Vec<_> = items.map(|(emb, _)| emb).collect();
+    if embs.len() == 1 {
+        return embs[0].clone()
+    }
+
+    // Feature holding the max of each dimension, ties going to the first
+    let dims = embs[0].value().len();
+    let mut argmax = vec![0usize; dims];
+    for d in 0..dims {
+        for idx in 1..embs.len() {
+            if embs[idx].value()[d] > embs[argmax[d]].value()[d] {
+                argmax[d] = idx;
+            }
+        }
+    }
+
+    // Slices out runs of dimensions held by the same feature
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for d in 1..(dims + 1) {
+        if d == dims || argmax[d] != argmax[start] {
+            parts.push(embs[argmax[start]].slice(start, d - start));
+            start = d;
+        }
+    }
+    parts.concat()
+}
+
+/// Weighted sum of the feature embeddings, whose final dimension is a learned scalar.  Features
+/// are weighted by the softmax of their scalars plus log counts, so repeated features count more,
+/// and the scalar is dropped from the output.
+pub fn weighted_sum_embeddings<'a>(
+    items: impl Iterator<Item=&'a (ANode, f32)>
+) -> ANode {
+    let items: Vec<_> = items.collect();
+    let d = items[0].0.value().len() - 1;
+    if items.len() == 1 {
+        return items[0].0.slice(0, d)
+    }
+
+    let logits: Vec<_> = items.iter()
+        .map(|(emb, count)| emb.slice(d, 1) + count.max(f32::MIN_POSITIVE).ln())
+        .collect();
+    let weights = softmax(logits.concat(), true);
+    items.iter().enumerate()
+        .map(|(idx, (emb, _))| &emb.slice(0, d) * &weights.slice(idx, 1))
+        .collect::<Vec<_>>()
+        .sum_all()
+}
+
+#[cfg(test)]
+mod model_tests {
+    use super::*;
+
+    #[test]
+    fn test_max_pool() {
+        let items = vec![
+            (Variable::new(vec![1f32, 5f32, 0f32, -1f32]), 1f32),
+            (Variable::new(vec![2f32, 3f32, 0f32, -2f32]), 2f32)
+        ];
+        let pooled = max_pool_embeddings(items.iter());
+        assert_eq!(pooled.value(), &[2f32, 5f32, 0f32, -1f32]);
+
+        // Gradients only reach the max of each dimension
+        let mut graph = Graph::new();
+        graph.backward(&pooled.sum());
+        assert_eq!(graph.get_grad(&items[0].0).unwrap(), &[0f32, 1f32, 1f32, 1f32]);
+        assert_eq!(graph.get_grad(&items[1].0).unwrap(), &[1f32, 0f32, 0f32, 0f32]);
+    }
+
+    #[test]
+    fn test_weighted_sum() {
+        // Equal scalars and counts average the embeddings
+        let items = vec![
+            (Variable::new(vec![1f32, 3f32, 0.5f32]), 1f32),
+            (Variable::new(vec![3f32, 1f32, 0.5f32]), 1f32)
+        ];
+        let summed = weighted_sum_embeddings(items.iter());
+        assert_eq!(summed.value(), &[2f32, 2f32]);
+
+        // The larger scalar dominates
+        let items = vec![
+            (Variable::new(vec![1f32, 0f32, 5f32]), 1f32),
+            (Variable::new(vec![0f32, 1f32, 0f32]), 1f32)
+        ];
+        let summed = weighted_sum_embeddings(items.iter());
+        assert!(summed.value()[0] > 0.99);
+
+        let single = weighted_sum_embeddings(items[..1].iter());
+        assert_eq!(single.value(), &[1f32, 0f32]);
+
+        let mut es = EmbeddingStore::new(2, 3, crate::distance::Distance::Cosine);
+        es.set_embedding(0, &[1f32, 0f32, 5f32]);
+        es.set_embedding(1, &[0f32, 1f32, 0f32]);
+        let model = WeightedSumFeatureModel::new(Sample::All, None, false);
+        let weights = model.feature_weights(&[1, 0], &es);
+        assert_eq!(weights[0].0, 0);
+        assert!((weights[0].1 + weights[1].1 - 1f32).abs() < 1e-5);
+        assert_eq!(model.node_dims(model.feature_dims(4)), 4);
+    }
+}
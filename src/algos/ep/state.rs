@@ -0,0 +1,337 @@
+//! Training state, for checkpointing and resuming EP training.  Feature embeddings alone aren't
+//! enough to resume a run: Adam's moments, the position within the learning rate schedule, and the
+//! random stream all matter too.  The state also records a hash of the hyperparameters it was
+//! trained with, so resuming with a different config fails loudly instead of silently continuing.
+//!
+//! Serialized states carry a format version; states from other versions are rejected rather than
+//! misread.
+use crate::embeddings::EmbeddingStore;
+use crate::error::{GraphLibError,check_dims};
+use crate::algos::utils::RngKind;
+
+use super::{EmbeddingPropagation,LRScheduler,LossWeighting,DegreeBalancing,Precision};
+use super::SHUFFLE_STREAM;
+use super::loss::{Loss,PositiveAggregation};
+
+/// Version of the serialized TrainingState format.  Version 2 hashes the config from explicit
+/// field encodings and leaves out the number of passes.
+pub const TRAINING_STATE_VERSION: u32 = 2;
+
+/// Adam's first and second moments, row major by feature id
+#[derive(Clone,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OptimizerState {
+    pub beta_1: f32,
+    pub beta_2: f32,
+    pub dims: usize,
+    pub mom: Vec<f32>,
+    pub var: Vec<f32>
+}
+
+/// Everything needed to resume training besides the feature embeddings themselves
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrainingState {
+    /// Serialization format version
+    pub version: u32,
+
+    /// `EmbeddingPropagation::config_hash` of the run
+    pub config_hash: u64,
+
+    /// Last pass completed
+    pub pass: usize,
+
+    /// Number of updates applied
+    pub step: usize,
+
+    /// Optimizer moments
+    pub optimizer: OptimizerState,
+
+    /// Learning rate schedule in effect, when known.  Distributed workers each compute their own.
+    pub lr_scheduler: Option<LRScheduler>,
+
+    /// Seed of the random stream shuffling the next pass
+    pub rng_seed: u64
+}
+
+impl TrainingState {
+    pub fn new(
+        ep: &EmbeddingPropagation,
+        pass: usize,
+        step: usize,
+        optimizer: OptimizerState,
+        lr_scheduler: Option<LRScheduler>
+    ) -> Self {
+        TrainingState {
+            version: TRAINING_STATE_VERSION,
+            config_hash: ep.config_hash(),
+            pass,
+            step,
+            optimizer,
+            lr_scheduler,
+            rng_seed: ep.stream(SHUFFLE_STREAM).split(pass as u64 + 1).seed()
+        }
+    }
+
+    /// Fails if the optimizer moments don't cover the feature embeddings
+    pub fn check_embeddings(
+        &self,
+        feature_embeddings: &EmbeddingStore
+    ) -> Result<(), GraphLibError> {
+        check_dims(feature_embeddings.dims(), self.optimizer.dims)?;
+        let expected = feature_embeddings.len() * feature_embeddings.dims();
+        if self.optimizer.mom.len() != expected {
+            let found = self.optimizer.mom.len();
+            return Err(GraphLibError::DimensionMismatch { expected, found })
+        }
+        Ok(())
+    }
+
+    /// Fails if the state was trained with different hyperparameters than `ep`
+    pub fn check_config(&self, ep: &EmbeddingPropagation) -> Result<(), GraphLibError> {
+        let expected = ep.config_hash();
+        if self.config_hash != expected {
+            return Err(GraphLibError::InvalidInput(format!(
+                "Training state was created with a different config: hash {:x}, expected {:x}",
+                self.config_hash, expected)))
+        }
+        Ok(())
+    }
+
+    /// Writes the state as JSON
+    #[cfg(feature = "serde")]
+    pub fn write_to(&self, w: &mut impl std::io::Write) -> Result<(), GraphLibError> {
+        serde_json::to_writer(w, self).map_err(|e| {
+            GraphLibError::InvalidInput(format!("Unable to serialize training state: {}", e))
+        })
+    }
+
+    /// Reads a state written by `write_to`, failing if it's from another format version
+    #[cfg(feature = "serde")]
+    pub fn read_from(r: &mut impl std::io::Read) -> Result<Self, GraphLibError> {
+        let malformed = |e: serde_json::Error| {
+            GraphLibError::InvalidInput(format!("Malformed training state: {}", e))
+        };
+        let value: serde_json::Value = serde_json::from_reader(r).map_err(malformed)?;
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(v) if v == TRAINING_STATE_VERSION as u64 => (),
+            v => return Err(GraphLibError::InvalidInput(format!(
+                "Unsupported training state version {:?}, expected {}", v, TRAINING_STATE_VERSION)))
+        }
+        serde_json::from_value(value).map_err(malformed)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn save(&self, path: &str) -> Result<(), GraphLibError> {
+        let mut bw = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut bw)
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn load(path: &str) -> Result<Self, GraphLibError> {
+        let mut br = std::io::BufReader::new(std::fs::File::open(path)?);
+        TrainingState::read_from(&mut br)
+    }
+}
+
+impl EmbeddingPropagation {
+
+    /// Hash of the hyperparameters which change what's learned, stable across processes and
+    /// builds.  The seed, passes, and indicator are left out, so a finished run can be extended
+    /// with more passes, as are data such as negative pools, frozen features, and node weights.
+    /// Each field is hashed from an explicit encoding rather than its Debug output, so renaming
+    /// a type or variant doesn't invalidate checkpoints.
+    pub fn config_hash(&self) -> u64 {
+        let mut h = ConfigHasher::new();
+        h.f32(self.alpha);
+        h.loss(&self.loss);
+        h.usize(self.batch_size);
+        h.usize(self.d_model);
+        h.usize(self.hard_negs);
+        match self.loss_weighting {
+            LossWeighting::DegreeLog => h.tag(0),
+            LossWeighting::DegreeExponential(exp) => { h.tag(1); h.f32(exp) },
+            LossWeighting::None => h.tag(2)
+        }
+        h.tag(match self.rng_kind {
+            RngKind::XorShift => 0,
+            RngKind::Pcg => 1
+        });
+        h.f32(self.valid_pct);
+        h.f32(self.noise);
+        h.bool(self.weighted_positives);
+        h.option(self.adaptive_batch, |h, ab| {
+            h.usize(ab.warmup_passes);
+            h.usize(ab.min_batch_size);
+            h.usize(ab.max_batch_size);
+        });
+        h.bool(self.asynchronous);
+        h.bool(self.exclude_neighbors);
+        h.option(self.degree_balancing, |h, db| match db {
+            DegreeBalancing::Capped(cap) => { h.tag(0); h.usize(cap) },
+            DegreeBalancing::InverseDegree(exp) => { h.tag(1); h.f32(exp) }
+        });
+        h.option(self.ranking_validation, |h, rv| {
+            h.usize(rv.num_pairs);
+            h.usize(rv.num_negatives);
+        });
+        h.tag(match self.gradient_precision {
+            Precision::F32 => 0,
+            Precision::BF16 => 1
+        });
+        h.option(self.gradient_threshold, |h, threshold| h.f32(threshold));
+        h.option(self.multi_positive, |h, mp| {
+            h.usize(mp.positives);
+            h.tag(match mp.aggregation {
+                PositiveAggregation::Mean => 0,
+                PositiveAggregation::LogSumExp => 1
+            });
+        });
+        h.finish()
+    }
+}
+
+// FNV-1a over the little endian encodings of the fields, since std's hasher isn't stable across
+// releases.  Enums are hashed as a fixed tag followed by their fields.
+struct ConfigHasher(u64);
+
+impl ConfigHasher {
+    fn new() -> Self {
+        ConfigHasher(0xcbf29ce484222325)
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn tag(&mut self, tag: u8) {
+        self.bytes(&[tag]);
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.tag(v as u8);
+    }
+
+    fn usize(&mut self, v: usize) {
+        self.bytes(&(v as u64).to_le_bytes());
+    }
+
+    fn f32(&mut self, v: f32) {
+        self.bytes(&v.to_bits().to_le_bytes());
+    }
+
+    fn option<T>(&mut self, v: Option<T>, f: impl FnOnce(&mut Self, T)) {
+        match v {
+            Some(v) => { self.tag(1); f(self, v) },
+            None => self.tag(0)
+        }
+    }
+
+    fn loss(&mut self, loss: &Loss) {
+        match *loss {
+            Loss::MarginLoss(gamma, negs) => { self.tag(0); self.f32(gamma); self.usize(negs) },
+            Loss::Contrastive(pos, neg, negs) => {
+                self.tag(1); self.f32(pos); self.f32(neg); self.usize(negs)
+            },
+            Loss::StarSpace(gamma, negs) => { self.tag(2); self.f32(gamma); self.usize(negs) },
+            Loss::RankLoss(tau, negs) => { self.tag(3); self.f32(tau); self.usize(negs) },
+            Loss::RankSpace(tau, negs) => { self.tag(4); self.f32(tau); self.usize(negs) },
+            Loss::PPR(gamma, negs, restart) => {
+                self.tag(5); self.f32(gamma); self.usize(negs); self.f32(restart)
+            },
+            Loss::BPR(negs) => { self.tag(6); self.usize(negs) },
+            Loss::HardTriplet(gamma, negs) => { self.tag(7); self.f32(gamma); self.usize(negs) }
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod state_tests {
+    use super::*;
+    use crate::algos::grad_utils::optimizer::AdamOptimizer;
+    use crate::distance::Distance;
+
+    fn build_ep() -> EmbeddingPropagation {
+        EmbeddingPropagation {
+            d_model: 4,
+            passes: 3,
+            ..EmbeddingPropagation::default()
+        }
+    }
+
+    fn build_state(ep: &EmbeddingPropagation) -> TrainingState {
+        let optimizer = OptimizerState {
+            beta_1: 0.9, beta_2: 0.999, dims: 2, mom: vec![0.1, 0.2], var: vec![0.3, 0.4]
+        };
+        TrainingState::new(ep, 2, 10, optimizer, Some(LRScheduler::cos_decay(1e-4, 1e-2, 2, 30)))
+    }
+
+    #[test]
+    fn test_check_config() {
+        let ep = build_ep();
+        let state = build_state(&ep);
+        assert!(state.check_config(&ep).is_ok());
+        let reseeded = EmbeddingPropagation { seed: 1, indicator: true, passes: 6, ..ep.clone() };
+        assert!(state.check_config(&reseeded).is_ok());
+        assert!(state.check_config(&EmbeddingPropagation { alpha: 1e-3, ..ep.clone() }).is_err());
+        assert!(state.check_config(&EmbeddingPropagation { d_model: 8, ..ep.clone() }).is_err());
+        let pcg = EmbeddingPropagation { rng_kind: RngKind::Pcg, ..ep.clone() };
+        assert!(state.check_config(&pcg).is_err());
+
+        // Variants with the same fields hash differently
+        let star = EmbeddingPropagation { loss: Loss::StarSpace(1., 5), ..ep.clone() };
+        let rank = EmbeddingPropagation { loss: Loss::RankLoss(1., 5), ..ep.clone() };
+        assert_ne!(star.config_hash(), rank.config_hash());
+        let capped = EmbeddingPropagation {
+            degree_balancing: Some(DegreeBalancing::Capped(2)), ..ep.clone()
+        };
+        assert_ne!(capped.config_hash(), ep.config_hash());
+    }
+
+    #[test]
+    fn test_check_embeddings() {
+        let ep = build_ep();
+        let state = build_state(&ep);
+        assert!(state.check_embeddings(&EmbeddingStore::new(1, 2, Distance::Cosine)).is_ok());
+        assert!(state.check_embeddings(&EmbeddingStore::new(2, 2, Distance::Cosine)).is_err());
+        assert!(state.check_embeddings(&EmbeddingStore::new(2, 1, Distance::Cosine)).is_err());
+    }
+
+    #[test]
+    fn test_optimizer_state() {
+        let optimizer = AdamOptimizer::new(0.9, 0.999, 2, 3);
+        let state = optimizer.state();
+        assert_eq!(state.mom.len(), 6);
+        assert_eq!(AdamOptimizer::from_state(&state).unwrap().state(), state);
+
+        let truncated = OptimizerState { var: vec![0.; 4], ..state.clone() };
+        assert!(AdamOptimizer::from_state(&truncated).is_err());
+        let ragged = OptimizerState { mom: vec![0.; 5], var: vec![0.; 5], ..state };
+        assert!(AdamOptimizer::from_state(&ragged).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialization() {
+        let ep = build_ep();
+        let state = build_state(&ep);
+        let mut buffer = Vec::new();
+        state.write_to(&mut buffer).unwrap();
+
+        let read = TrainingState::read_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!((read.pass, read.step, read.rng_seed), (2, 10, state.rng_seed));
+        assert_eq!(read.optimizer, state.optimizer);
+        assert!(read.check_config(&ep).is_ok());
+
+        let mut value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+        value["version"] = serde_json::Value::from(TRAINING_STATE_VERSION + 1);
+        let buffer = serde_json::to_vec(&value).unwrap();
+        assert!(TrainingState::read_from(&mut buffer.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,454 @@
+//! Hyperparameter sweeps for EP.  Full training runs are far too expensive to compare many
+//! configurations, so trials train on a subgraph sampled from the full graph, and successive
+//! halving spends most of the budget on the promising ones: every configuration trains for a few
+//! passes, the best 1/eta continue with eta times as many, and so on until the survivors train
+//! for the full passes.  Hyperband runs several such brackets, trading off the number of
+//! configurations against how early they're judged.
+//!
+//! Trials retrain from scratch at each rung rather than resuming, so a rung's results don't
+//! depend on the learning rate schedule of a shorter run.  Every trial is scored on the same held
+//! out nodes with the base config's loss, as losses of different configs aren't comparable.
+//! Held out nodes get a node weight of zero while training, so they're never anchors.
+use std::collections::VecDeque;
+
+use float_ord::FloatOrd;
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+
+use crate::graph::{Graph as CGraph,Subgraph,NodeID};
+use crate::feature_store::FeatureStore;
+use crate::error::GraphLibError;
+
+use super::EmbeddingPropagation;
+use super::loss::Loss;
+use super::model::Model;
+
+/// Values to search over.  Empty lists keep the base config's value.
+#[derive(Clone,Debug,Default)]
+pub struct SearchSpace {
+    pub alpha: Vec<f32>,
+    pub loss: Vec<Loss>,
+    pub batch_size: Vec<usize>,
+    pub d_model: Vec<usize>
+}
+
+impl SearchSpace {
+
+    /// Every combination of the values, applied to the base config
+    pub fn grid(&self, base: &EmbeddingPropagation) -> Vec<EmbeddingPropagation> {
+        let mut configs = Vec::new();
+        for alpha in or_base(&self.alpha, base.alpha) {
+            for loss in or_base(&self.loss, base.loss) {
+                for batch_size in or_base(&self.batch_size, base.batch_size) {
+                    for d_model in or_base(&self.d_model, base.d_model) {
+                        configs.push(EmbeddingPropagation {
+                            alpha, loss, batch_size, d_model, ..base.clone()
+                        });
+                    }
+                }
+            }
+        }
+        configs
+    }
+
+    /// Draws `n` configs, each value picked uniformly at random
+    pub fn sample(
+        &self,
+        base: &EmbeddingPropagation,
+        n: usize,
+        rng: &mut impl Rng
+    ) -> Vec<EmbeddingPropagation> {
+        (0..n).map(|_| EmbeddingPropagation {
+            alpha: *or_base(&self.alpha, base.alpha).choose(rng).unwrap(),
+            loss: *or_base(&self.loss, base.loss).choose(rng).unwrap(),
+            batch_size: *or_base(&self.batch_size, base.batch_size).choose(rng).unwrap(),
+            d_model: *or_base(&self.d_model, base.d_model).choose(rng).unwrap(),
+            ..base.clone()
+        }).collect()
+    }
+}
+
+// The values to search, or the base value alone if there are none
+fn or_base<T: Copy>(values: &[T], base: T) -> Vec<T> {
+    if values.is_empty() { vec![base] } else { values.to_vec() }
+}
+
+/// Score of a single trial at a single rung
+#[derive(Clone,Debug)]
+pub struct TrialResult {
+    /// Index of the config, unique across brackets
+    pub trial: usize,
+
+    /// Config trained
+    pub config: EmbeddingPropagation,
+
+    /// Hyperband bracket, always 0 for plain successive halving
+    pub bracket: usize,
+
+    /// Rung within the bracket, starting from 0
+    pub rung: usize,
+
+    /// Passes trained for
+    pub passes: usize,
+
+    /// Average loss of the held out nodes under the base config's loss
+    pub valid_loss: f32
+}
+
+/// Every trial run by a sweep
+#[derive(Clone,Debug,Default)]
+pub struct TuningReport {
+    pub trials: Vec<TrialResult>
+}
+
+impl TuningReport {
+
+    /// Lowest validation loss among the trials trained for the most passes
+    pub fn best(&self) -> Option<&TrialResult> {
+        let max_passes = self.trials.iter().map(|t| t.passes).max()?;
+        self.trials.iter()
+            .filter(|t| t.passes == max_passes)
+            .min_by_key(|t| score_key(t.valid_loss))
+    }
+}
+
+// Orders losses with NaNs last
+fn score_key(loss: f32) -> FloatOrd<f32> {
+    FloatOrd(if loss.is_nan() { f32::INFINITY } else { loss })
+}
+
+/// Sweep configuration
+#[derive(Clone,Copy,Debug)]
+pub struct Tuner {
+    /// Passes the surviving configs train for in the final rung
+    pub max_passes: usize,
+
+    /// Fraction of the configs dropped at each rung is 1 - 1/eta.  Must be at least 2.
+    pub eta: usize,
+
+    /// Number of nodes in the sampled subgraph.  If None, trials use the full graph.
+    pub num_nodes: Option<usize>,
+
+    /// Fraction of the subgraph's nodes held out for scoring
+    pub holdout_pct: f32,
+
+    /// Seed for sampling the subgraph, the held out nodes, and the configs
+    pub seed: u64
+}
+
+impl Tuner {
+
+    /// Runs successive halving over the configs.  The number of rungs is set so the final rung
+    /// holds a single config.
+    pub fn successive_halving<G: Subgraph + Send + Sync, M: Model>(
+        &self,
+        base: &EmbeddingPropagation,
+        configs: Vec<EmbeddingPropagation>,
+        graph: &G,
+        features: &FeatureStore,
+        model: &M
+    ) -> Result<TuningReport, GraphLibError> {
+        self.check()?;
+        if configs.is_empty() {
+            return Err("At least one config is required!".into())
+        }
+
+        let mut rungs = 1;
+        while self.eta.pow(rungs as u32 - 1) < configs.len() {
+            rungs += 1;
+        }
+
+        let trial = self.prepare(base, graph, features)?;
+        let mut report = TuningReport::default();
+        let trials = configs.into_iter().enumerate().collect();
+        trial.run_bracket(self, 0, trials, rungs, model, &mut report)?;
+        Ok(report)
+    }
+
+    /// Runs Hyperband, sampling the configs of each bracket from the search space.  Bracket `s`
+    /// starts enough configs to halve `s` times, at `max_passes / eta^s` passes each.
+    pub fn hyperband<G: Subgraph + Send + Sync, M: Model>(
+        &self,
+        base: &EmbeddingPropagation,
+        space: &SearchSpace,
+        graph: &G,
+        features: &FeatureStore,
+        model: &M
+    ) -> Result<TuningReport, GraphLibError> {
+        self.check()?;
+        let mut s_max = 0;
+        while self.eta.pow(s_max as u32 + 1) <= self.max_passes {
+            s_max += 1;
+        }
+
+        let trial = self.prepare(base, graph, features)?;
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        let mut report = TuningReport::default();
+        let mut next_trial = 0;
+        for s in (0..=s_max).rev() {
+            let scale = (s_max + 1) as f32 / (s + 1) as f32;
+            let n = (scale * self.eta.pow(s as u32) as f32).ceil() as usize;
+            let trials: Vec<_> = space.sample(base, n, &mut rng).into_iter()
+                .enumerate()
+                .map(|(idx, config)| (next_trial + idx, config))
+                .collect();
+            next_trial += n;
+            trial.run_bracket(self, s_max - s, trials, s + 1, model, &mut report)?;
+        }
+        Ok(report)
+    }
+
+    fn check(&self) -> Result<(), GraphLibError> {
+        if self.eta < 2 {
+            return Err("eta must be at least 2!".into())
+        }
+        if self.max_passes == 0 {
+            return Err("max_passes must be at least 1!".into())
+        }
+        if self.holdout_pct.is_nan() || self.holdout_pct <= 0. || self.holdout_pct >= 1. {
+            return Err("holdout_pct must be between 0 and 1!".into())
+        }
+        Ok(())
+    }
+
+    // Samples the subgraph and the held out nodes shared by every trial
+    fn prepare<G: Subgraph + Send + Sync>(
+        &self,
+        base: &EmbeddingPropagation,
+        graph: &G,
+        features: &FeatureStore
+    ) -> Result<TrialData<G>, GraphLibError> {
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        let num_nodes = self.num_nodes.unwrap_or(graph.len());
+        let (graph, features, _) = sample_subgraph(graph, features, num_nodes, &mut rng);
+
+        let mut candidates: Vec<_> = (0..graph.len())
+            .filter(|node_id| graph.degree(*node_id) > 0)
+            .collect();
+        candidates.shuffle(&mut rng);
+        let num_holdout = (candidates.len() as f32 * self.holdout_pct) as usize;
+        let holdout = candidates[..num_holdout].to_vec();
+        if holdout.is_empty() {
+            return Err("Subgraph is too small to hold out any nodes!".into())
+        }
+
+        let mut node_weights = vec![1f32; graph.len()];
+        holdout.iter().for_each(|node_id| node_weights[*node_id] = 0f32);
+
+        // Pools and weights refer to nodes of the full graph
+        let scorer = EmbeddingPropagation {
+            negative_pools: None, node_weights: None, ..base.clone()
+        };
+        Ok(TrialData { graph, features, holdout, node_weights, scorer })
+    }
+}
+
+// Data shared by every trial of a sweep
+struct TrialData<G> {
+    graph: G,
+    features: FeatureStore,
+    holdout: Vec<NodeID>,
+    node_weights: Vec<f32>,
+    scorer: EmbeddingPropagation
+}
+
+impl <G: Subgraph + Send + Sync> TrialData<G> {
+
+    // Trains and scores a config for the given passes
+    fn score<M: Model>(
+        &self,
+        config: &EmbeddingPropagation,
+        passes: usize,
+        model: &M
+    ) -> Result<f32, GraphLibError> {
+        let ep = EmbeddingPropagation {
+            passes,
+            valid_pct: 0.,
+            negative_pools: None,
+            node_weights: Some(self.node_weights.clone()),
+            ranking_validation: None,
+            indicator: false,
+            ..config.clone()
+        };
+        let fe = ep.learn(&self.graph, &self.features, None, model)?;
+        Ok(self.scorer.evaluate(&self.graph, &self.holdout, &self.features, &fe, model))
+    }
+
+    // Successive halving over the trials, with the final rung training for max_passes
+    fn run_bracket<M: Model>(
+        &self,
+        tuner: &Tuner,
+        bracket: usize,
+        mut trials: Vec<(usize, EmbeddingPropagation)>,
+        rungs: usize,
+        model: &M,
+        report: &mut TuningReport
+    ) -> Result<(), GraphLibError> {
+        for rung in 0..rungs {
+            let passes = (tuner.max_passes / tuner.eta.pow((rungs - 1 - rung) as u32)).max(1);
+            let mut scored = Vec::with_capacity(trials.len());
+            for (trial, config) in trials.into_iter() {
+                let valid_loss = self.score(&config, passes, model)?;
+
+                #[cfg(feature = "tracing")]
+                tracing::info!(trial, bracket, rung, passes, valid_loss, "ep tuning trial");
+
+                report.trials.push(TrialResult {
+                    trial, config: config.clone(), bracket, rung, passes, valid_loss
+                });
+                scored.push((valid_loss, trial, config));
+            }
+
+            scored.sort_by_key(|(valid_loss, trial, _)| (score_key(*valid_loss), *trial));
+            let keep = (scored.len() / tuner.eta).max(1);
+            trials = scored.into_iter()
+                .take(keep)
+                .map(|(_, trial, config)| (trial, config))
+                .collect();
+        }
+        Ok(())
+    }
+}
+
+/// Samples a connected-ish subgraph of about `num_nodes` nodes by breadth first search from
+/// random seed nodes, restarting from a new seed whenever the search runs dry.  Unlike sampling
+/// nodes uniformly, this keeps most of the sampled nodes' edges.  Returns the induced subgraph,
+/// its features, and the original NodeID of each subgraph node.
+pub fn sample_subgraph<G: Subgraph>(
+    graph: &G,
+    features: &FeatureStore,
+    num_nodes: usize,
+    rng: &mut impl Rng
+) -> (G, FeatureStore, Vec<NodeID>) {
+    let num_nodes = num_nodes.min(graph.len());
+    let mut order: Vec<NodeID> = (0..graph.len()).collect();
+    order.shuffle(rng);
+
+    let mut seen = vec![false; graph.len()];
+    let mut nodes = Vec::with_capacity(num_nodes);
+    let mut queue = VecDeque::new();
+    let mut seeds = order.into_iter();
+    while nodes.len() < num_nodes {
+        let node_id = match queue.pop_front() {
+            Some(node_id) => node_id,
+            None => match seeds.find(|node_id| !seen[*node_id]) {
+                Some(node_id) => { seen[node_id] = true; node_id },
+                None => break
+            }
+        };
+
+        nodes.push(node_id);
+        for t_n in graph.get_edges(node_id).0.iter() {
+            if !seen[*t_n] {
+                seen[*t_n] = true;
+                queue.push_back(*t_n);
+            }
+        }
+    }
+
+    nodes.sort_unstable();
+    (graph.subgraph(&nodes), features.subset(&nodes), nodes)
+}
+
+#[cfg(test)]
+mod tuning_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+    use crate::algos::ep::model::AveragedFeatureModel;
+    use crate::algos::utils::Sample;
+
+    // Ring of 40 nodes with chords to the node 2 ahead
+    fn build_graph() -> CumCSR {
+        let mut edges = Vec::new();
+        for u in 0..40 {
+            for v in [(u + 1) % 40, (u + 2) % 40] {
+                edges.push((u, v, 1f32));
+                edges.push((v, u, 1f32));
+            }
+        }
+        CumCSR::convert(CSR::construct_from_edges(edges, true))
+    }
+
+    fn build_ep() -> EmbeddingPropagation {
+        EmbeddingPropagation {
+            batch_size: 8,
+            d_model: 4,
+            passes: 1,
+            ..EmbeddingPropagation::default()
+        }
+    }
+
+    #[test]
+    fn test_search_space() {
+        let base = build_ep();
+        let space = SearchSpace {
+            alpha: vec![1e-2, 1e-3],
+            d_model: vec![4, 8, 16],
+            ..SearchSpace::default()
+        };
+        let grid = space.grid(&base);
+        assert_eq!(grid.len(), 6);
+        assert!(grid.iter().all(|ep| ep.batch_size == 8));
+        assert_eq!((grid[5].alpha, grid[5].d_model), (1e-3, 16));
+
+        let sampled = space.sample(&base, 5, &mut XorShiftRng::seed_from_u64(1));
+        assert_eq!(sampled.len(), 5);
+        assert!(sampled.iter().all(|ep| [4, 8, 16].contains(&ep.d_model)));
+    }
+
+    #[test]
+    fn test_sample_subgraph() {
+        let graph = build_graph();
+        let mut features = FeatureStore::new(graph.len());
+        features.fill_missing_nodes();
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let (sub, sub_features, nodes) = sample_subgraph(&graph, &features, 10, &mut rng);
+        assert_eq!((sub.len(), sub_features.num_nodes(), nodes.len()), (10, 10, 10));
+        assert_eq!(sub_features.get_features(3), features.get_features(nodes[3]));
+
+        // A breadth first sample of a ring keeps most of its edges
+        assert!(sub.edges() >= 24);
+    }
+
+    #[test]
+    fn test_successive_halving() {
+        let graph = build_graph();
+        let mut features = FeatureStore::new(graph.len());
+        features.fill_missing_nodes();
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+
+        let base = build_ep();
+        let space = SearchSpace { alpha: vec![1e-1, 1e-2, 1e-3, 1e-4], ..SearchSpace::default() };
+        let tuner = Tuner { max_passes: 4, eta: 2, num_nodes: Some(30), holdout_pct: 0.2, seed: 7 };
+        let report = tuner.successive_halving(&base, space.grid(&base), &graph, &features, &model)
+            .unwrap();
+
+        // 4 configs at 1 pass, 2 at 2, then 1 at 4
+        let passes: Vec<_> = report.trials.iter().map(|t| t.passes).collect();
+        assert_eq!(passes, vec![1, 1, 1, 1, 2, 2, 4]);
+        assert!(report.trials.iter().all(|t| t.valid_loss.is_finite()));
+        assert_eq!(report.best().unwrap().trial, report.trials[6].trial);
+
+        assert!(Tuner { eta: 1, ..tuner }
+            .successive_halving(&base, space.grid(&base), &graph, &features, &model).is_err());
+        assert!(tuner.successive_halving(&base, Vec::new(), &graph, &features, &model).is_err());
+    }
+
+    #[test]
+    fn test_hyperband() {
+        let graph = build_graph();
+        let mut features = FeatureStore::new(graph.len());
+        features.fill_missing_nodes();
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+
+        let base = build_ep();
+        let space = SearchSpace { d_model: vec![2, 4], ..SearchSpace::default() };
+        let tuner = Tuner { max_passes: 2, eta: 2, num_nodes: None, holdout_pct: 0.2, seed: 7 };
+        let report = tuner.hyperband(&base, &space, &graph, &features, &model).unwrap();
+
+        // Bracket 0 halves 2 configs from 1 pass to 2, bracket 1 trains 2 configs for 2 passes
+        let rungs: Vec<_> = report.trials.iter().map(|t| (t.bracket, t.passes)).collect();
+        assert_eq!(rungs, vec![(0, 1), (0, 1), (0, 2), (1, 2), (1, 2)]);
+        assert_eq!(report.best().unwrap().passes, 2);
+    }
+}
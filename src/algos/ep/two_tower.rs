@@ -0,0 +1,212 @@
+//! Two-tower training for directed interaction graphs, such as user -> gig clicks, where the
+//! query side and the item side need distinct embeddings.  Each tower has its own copy of every
+//! feature embedding.
+//!
+//! Rather than teaching the training loop about towers, we train on a stacked problem: nodes
+//! [0, n) are the source copies of the nodes and [n, 2n) the destination copies, each with their
+//! own copy of the features.  Every directed edge u -> v becomes the edges u -> v' and v' -> u,
+//! so both towers take turns as anchors while their positives always come from the other tower.
+//! Negatives are drawn from the opposite tower through per anchor candidate pools.
+use hashbrown::HashMap;
+
+use crate::graph::{Graph,CDFGraph,CDFtoP,CumCSR,GraphBuilder};
+use crate::embeddings::EmbeddingStore;
+use crate::feature_store::FeatureStore;
+use crate::error::{GraphLibError,check_dims};
+
+use super::{EmbeddingPropagation,CandidatePools};
+use super::loss::Loss;
+use super::model::Model;
+
+/// Prefixes the namespaces of the destination tower's features
+const DESTINATION_PREFIX: &str = "__dst__:";
+
+/// Feature embeddings of each tower.  Both are indexed by the feature ids of the original
+/// FeatureStore, so either can be passed to `embed_nodes` with it.
+pub struct TwoTowerEmbeddings {
+    /// Query side embeddings, for the sources of edges
+    pub source: EmbeddingStore,
+
+    /// Item side embeddings, for the destinations of edges
+    pub destination: EmbeddingStore
+}
+
+impl EmbeddingPropagation {
+
+    /// Learns separate source and destination feature embeddings from the directed edges of the
+    /// graph: sources are pulled towards the destinations they point to and away from other
+    /// destinations, and vice versa.  Frozen features and node weights apply to both towers.
+    /// Fails if the features have dense columns, negative pools are set, as the towers provide
+    /// their own, or the loss is PPR, whose walks would cross back into the anchor's tower.
+    pub fn learn_two_tower<G: CDFGraph + Send + Sync, M: Model>(
+        &self,
+        graph: &G,
+        features: &FeatureStore,
+        feature_embeddings: Option<TwoTowerEmbeddings>,
+        model: &M
+    ) -> Result<TwoTowerEmbeddings, GraphLibError> {
+        if self.negative_pools.is_some() {
+            return Err("Two-tower training provides its own negative pools!".into())
+        }
+        if let Loss::PPR(..) = self.loss {
+            return Err("Two-tower training doesn't support PPR losses!".into())
+        }
+
+        let num_nodes = graph.len();
+        let stacked = features.stack_towers(DESTINATION_PREFIX)?;
+        let num_features = features.num_features();
+
+        let feature_embeddings = feature_embeddings
+            .map(|towers| stack_embeddings(towers, num_features))
+            .transpose()?;
+
+        let pools = CandidatePools::PerAnchor {
+            pools: vec![(num_nodes..2 * num_nodes).collect(), (0..num_nodes).collect()],
+            assignments: (0..2 * num_nodes).map(|node_id| (node_id, node_id / num_nodes.max(1)))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let ep = EmbeddingPropagation {
+            negative_pools: Some(pools),
+            frozen_features: self.frozen_features.as_ref().map(|frozen| {
+                let mut frozen = frozen.clone();
+                frozen.resize(num_features, false);
+                double(&frozen)
+            }),
+            node_weights: self.node_weights.as_ref().map(|weights| double(weights)),
+            ..self.clone()
+        };
+
+        let embeddings = ep.learn(&tower_graph(graph), &stacked, feature_embeddings, model)?;
+        Ok(split_embeddings(&embeddings, num_features))
+    }
+}
+
+/// Builds the stacked graph: every edge u -> v of the original graph becomes u -> n + v and
+/// n + v -> u, both weighted by the transition probability of u -> v.
+pub fn tower_graph(graph: &impl CDFGraph) -> CumCSR {
+    let num_nodes = graph.len();
+    let mut builder = GraphBuilder::new(true);
+    builder.ensure_nodes(2 * num_nodes);
+    for node_id in 0..num_nodes {
+        let (edges, weights) = graph.get_edges(node_id);
+        for (t_n, p) in edges.iter().zip(CDFtoP::new(weights)) {
+            builder.add_edge(node_id, num_nodes + *t_n, p);
+            builder.add_edge(num_nodes + *t_n, node_id, p);
+        }
+    }
+    builder.build_cum_csr()
+}
+
+// Repeats the values once for each tower
+fn double<T: Clone>(values: &[T]) -> Vec<T> {
+    values.iter().chain(values.iter()).cloned().collect()
+}
+
+// Concatenates the towers into a single store, destination after source
+fn stack_embeddings(
+    towers: TwoTowerEmbeddings,
+    num_features: usize
+) -> Result<EmbeddingStore, GraphLibError> {
+    let TwoTowerEmbeddings { source, destination } = towers;
+    check_dims(source.dims(), destination.dims())?;
+    for tower in [&source, &destination] {
+        if tower.len() != num_features {
+            return Err(GraphLibError::DimensionMismatch {
+                expected: num_features,
+                found: tower.len()
+            })
+        }
+    }
+
+    let mut stacked = EmbeddingStore::new(2 * num_features, source.dims(), source.distance());
+    for feat_id in 0..num_features {
+        stacked.set_embedding(feat_id, source.get_embedding(feat_id));
+        stacked.set_embedding(num_features + feat_id, destination.get_embedding(feat_id));
+    }
+    Ok(stacked)
+}
+
+// Splits a stacked store back into its towers
+fn split_embeddings(embeddings: &EmbeddingStore, num_features: usize) -> TwoTowerEmbeddings {
+    let tower = |offset: usize| {
+        let mut es = EmbeddingStore::new(num_features, embeddings.dims(), embeddings.distance());
+        for feat_id in 0..num_features {
+            es.set_embedding(feat_id, embeddings.get_embedding(offset + feat_id));
+        }
+        es
+    };
+    TwoTowerEmbeddings { source: tower(0), destination: tower(num_features) }
+}
+
+#[cfg(test)]
+mod two_tower_tests {
+    use super::*;
+    use crate::graph::CSR;
+    use crate::algos::ep::model::AveragedFeatureModel;
+    use crate::algos::utils::Sample;
+
+    // Users 0-3 click gigs 4-7: user u clicks gigs 4 + u and 4 + (u + 1) % 4
+    fn build_graph() -> CumCSR {
+        let mut edges = Vec::new();
+        for u in 0..4 {
+            edges.push((u, 4 + u, 1f32));
+            edges.push((u, 4 + (u + 1) % 4, 1f32));
+        }
+        CumCSR::convert(CSR::construct_from_edges(edges, false))
+    }
+
+    fn build_ep() -> EmbeddingPropagation {
+        EmbeddingPropagation {
+            batch_size: 4,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 2,
+            ..EmbeddingPropagation::default()
+        }
+    }
+
+    #[test]
+    fn test_tower_graph() {
+        let graph = build_graph();
+        let towers = tower_graph(&graph);
+        assert_eq!(towers.len(), 16);
+        assert_eq!(towers.edges(), 16);
+        assert_eq!(towers.get_edges(0).0, &[12, 13]);
+        assert_eq!(towers.get_edges(12).0, &[0, 3]);
+        assert_eq!(towers.get_edges(12).1, &[0.5, 1.]);
+        assert_eq!(towers.degree(4), 0);
+    }
+
+    #[test]
+    fn test_learn_two_tower() {
+        let graph = build_graph();
+        let mut features = FeatureStore::new(graph.len());
+        features.fill_missing_nodes();
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+
+        let ep = build_ep();
+        let towers = ep.learn_two_tower(&graph, &features, None, &model).unwrap();
+        assert_eq!((towers.source.len(), towers.destination.len()), (8, 8));
+        assert_ne!(towers.source.get_embedding(0), towers.destination.get_embedding(0));
+
+        // Frozen features are frozen in both towers
+        let mut frozen = vec![false; 8];
+        frozen[4] = true;
+        let frozen_ep = EmbeddingPropagation { frozen_features: Some(frozen), ..ep.clone() };
+        let (source, destination) = (towers.source.get_embedding(4).to_vec(),
+                                     towers.destination.get_embedding(4).to_vec());
+        let towers = frozen_ep.learn_two_tower(&graph, &features, Some(towers), &model).unwrap();
+        assert_eq!(towers.source.get_embedding(4), source.as_slice());
+        assert_eq!(towers.destination.get_embedding(4), destination.as_slice());
+
+        let es = ep.embed_nodes(&graph, &features, &towers.destination, &model);
+        assert_eq!(es.len(), graph.len());
+
+        let pools = CandidatePools::Global(vec![0]);
+        let pooled_ep = EmbeddingPropagation { negative_pools: Some(pools), ..ep.clone() };
+        assert!(pooled_ep.learn_two_tower(&graph, &features, None, &model).is_err());
+        let ppr_ep = EmbeddingPropagation { loss: Loss::PPR(1f32, 1, 0.5), ..ep };
+        assert!(ppr_ep.learn_two_tower(&graph, &features, None, &model).is_err());
+    }
+}
@@ -0,0 +1,608 @@
+//! Offline evaluation utilities for comparing embedding spaces.  These are intended to help decide
+//! which encoder to promote, such as PPREmbed versus Embedding Propagation, without having to
+//! export everything out to Python.
+//!
+//! Link prediction metrics (AUC, hits@k, and MRR over held out edges) give a standard way to
+//! compare losses and models on the same graph.
+//!
+//! `drift` compares two versions of the same space, such as consecutive retrains, to gate
+//! deployments on how much neighborhoods and embeddings moved.
+//!
+//! Finally, `ann_recall` measures an ANN index against brute force ground truth so tree counts,
+//! leaf sizes, and search budgets can be tuned from data.
+use std::time::{Duration,Instant};
+
+use rayon::prelude::*;
+use float_ord::FloatOrd;
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+
+use crate::graph::{Graph,NodeID};
+use crate::bitset::BitSet;
+use crate::embeddings::EmbeddingStore;
+use crate::vocab::TranslationTable;
+use crate::algos::graph_ann::{TopK,NodeDistance};
+use crate::algos::ann::Ann;
+use crate::algos::utils::AliasTable;
+use crate::error::{GraphLibError,check_dims};
+
+/// Summary of how well one embedding space's neighborhoods agree with another's.
+#[derive(Clone,Copy,Debug)]
+pub struct NeighborhoodAgreement {
+    /// Average fraction of the top K neighbors shared between the two spaces
+    pub overlap: f32,
+
+    /// Average Spearman rank correlation of the source neighbors when ranked in the target space
+    pub rank_correlation: f32,
+
+    /// Number of anchor nodes which contributed to the metrics
+    pub nodes: usize
+}
+
+/// Measures how well the neighborhoods in the `source` embedding space predict those in the
+/// `target` space.  The translation table maps source NodeIDs to target NodeIDs; only nodes shared
+/// between both spaces are considered as neighbors.  For each anchor, we compute the top K
+/// neighbors in both spaces, the overlap between them, and the rank correlation of the source
+/// neighbors as ordered by the target space.
+pub fn cross_space_agreement(
+    source: &EmbeddingStore,
+    target: &EmbeddingStore,
+    translation: &TranslationTable,
+    anchors: &[NodeID],
+    k: usize
+) -> NeighborhoodAgreement {
+
+    // Map target nodes back to the source so we only search over shared nodes
+    let mut reverse = vec![None; target.len()];
+    let mut shared = BitSet::new(target.len());
+    translation.iter().enumerate().for_each(|(source_id, target_id)| {
+        if let Some(t_id) = target_id {
+            reverse[*t_id] = Some(source_id);
+            shared.set_bit(*t_id);
+        }
+    });
+
+    let (overlap, rank_corr, n) = anchors.par_iter().filter_map(|s_anchor| {
+        let t_anchor = translation[*s_anchor]?;
+
+        let source_nn = top_k(source, *s_anchor, k,
+                              |node_id| translation[node_id].is_some());
+        let target_nn = top_k(target, t_anchor, k, |node_id| shared.is_set(node_id));
+        if source_nn.len() == 0 {
+            return None
+        }
+
+        // Overlap between the neighborhoods, in source NodeID space
+        let target_set: Vec<_> = target_nn.iter()
+            .filter_map(|nd| reverse[nd.1])
+            .collect();
+        let shared_count = source_nn.iter()
+            .filter(|nd| target_set.contains(&nd.1))
+            .count();
+        let overlap = shared_count as f32 / source_nn.len() as f32;
+
+        // Rank the source neighbors by their target distances
+        let t_emb = target.get_embedding(t_anchor);
+        let target_dists: Vec<_> = source_nn.iter().map(|nd| {
+            let t_id = translation[nd.1].expect("Filtered to shared nodes!");
+            target.distance().compute(t_emb, target.get_embedding(t_id))
+        }).collect();
+
+        Some((overlap, spearman(&target_dists)))
+    }).map(|(overlap, rank_corr)| (overlap, rank_corr, 1usize))
+    .reduce(|| (0f32, 0f32, 0usize), |a, b| (a.0 + b.0, a.1 + b.1, a.2 + b.2));
+
+    let denom = n.max(1) as f32;
+    NeighborhoodAgreement {
+        overlap: overlap / denom,
+        rank_correlation: rank_corr / denom,
+        nodes: n
+    }
+}
+
+/// How negatives are drawn when scoring held out edges
+#[derive(Clone,Copy,Debug)]
+pub enum NegativeSampling {
+    /// Uniformly from all nodes
+    Uniform,
+
+    /// Proportionally to node degree.  Popular nodes make for harder negatives and penalize
+    /// embeddings which simply learn popularity.
+    Degree
+}
+
+/// Link prediction evaluation configuration
+#[derive(Clone,Debug)]
+pub struct LinkPrediction {
+    /// Number of negatives to rank each held out edge against
+    pub num_negatives: usize,
+
+    /// Cutoffs to compute hits@k for
+    pub hits_at: Vec<usize>,
+
+    /// How negatives are sampled
+    pub sampling: NegativeSampling,
+
+    /// Random seed
+    pub seed: u64
+}
+
+/// Link prediction metrics, averaged over the held out edges
+#[derive(Clone,Debug)]
+pub struct LinkPredictionMetrics {
+    /// Probability a positive is closer than a random negative, with ties counting half
+    pub auc: f32,
+
+    /// Fraction of positives ranked within the top k, for each k in `hits_at`
+    pub hits: Vec<(usize, f32)>,
+
+    /// Mean reciprocal rank of the positive among the negatives
+    pub mrr: f32,
+
+    /// Number of held out edges which were scored
+    pub edges: usize
+}
+
+impl LinkPrediction {
+
+    /// Scores each held out edge (u, v) by ranking v against sampled negatives by their distance
+    /// to u.  Negatives are filtered to exclude u, v, and u's neighbors in the training graph, so
+    /// known edges are never counted against the embedding.  Edges with nodes outside of the
+    /// embedding store are skipped.
+    pub fn evaluate<G: Graph + Sync>(
+        &self,
+        graph: &G,
+        held_out: &[(NodeID, NodeID)],
+        es: &EmbeddingStore
+    ) -> LinkPredictionMetrics {
+        let num_nodes = graph.len().min(es.len());
+        let degree_table = match self.sampling {
+            NegativeSampling::Uniform => None,
+            NegativeSampling::Degree => {
+                let degrees: Vec<_> = (0..num_nodes).map(|node_id| graph.degree(node_id) as f32).collect();
+                AliasTable::new(&degrees)
+            }
+        };
+
+        let (auc, hits, mrr, n) = held_out.par_iter().enumerate().filter_map(|(idx, (u, v))| {
+            if *u >= num_nodes || *v >= num_nodes {
+                return None
+            }
+
+            let mut rng = XorShiftRng::seed_from_u64(self.seed + idx as u64);
+            let negatives = self.sample_negatives(graph, *u, *v, num_nodes, degree_table.as_ref(), &mut rng);
+            if negatives.is_empty() {
+                return None
+            }
+
+            let u_emb = es.get_embedding(*u);
+            let pos_d = es.distance().compute(u_emb, es.get_embedding(*v));
+            let (mut closer, mut ties) = (0usize, 0usize);
+            negatives.iter().for_each(|neg| {
+                let d = es.distance().compute(u_emb, es.get_embedding(*neg));
+                if d < pos_d {
+                    closer += 1;
+                } else if d == pos_d {
+                    ties += 1;
+                }
+            });
+
+            let auc = 1f32 - (closer as f32 + 0.5 * ties as f32) / negatives.len() as f32;
+            let rank = closer + 1;
+            let hits: Vec<_> = self.hits_at.iter()
+                .map(|k| if rank <= *k { 1f32 } else { 0f32 })
+                .collect();
+            Some((auc, hits, 1f32 / rank as f32, 1usize))
+        }).reduce(|| (0f32, vec![0f32; self.hits_at.len()], 0f32, 0usize), |a, b| {
+            let hits = a.1.iter().zip(b.1.iter()).map(|(x, y)| x + y).collect();
+            (a.0 + b.0, hits, a.2 + b.2, a.3 + b.3)
+        });
+
+        let denom = n.max(1) as f32;
+        LinkPredictionMetrics {
+            auc: auc / denom,
+            hits: self.hits_at.iter().zip(hits.into_iter())
+                .map(|(k, h)| (*k, h / denom))
+                .collect(),
+            mrr: mrr / denom,
+            edges: n
+        }
+    }
+
+    // Samples up to num_negatives distinct negatives.  Small or dense graphs might not have
+    // enough valid negatives so we bound the number of attempts.
+    fn sample_negatives<G: Graph>(
+        &self,
+        graph: &G,
+        u: NodeID,
+        v: NodeID,
+        num_nodes: usize,
+        degree_table: Option<&AliasTable>,
+        rng: &mut impl Rng
+    ) -> Vec<NodeID> {
+        let neighbors = graph.get_edges(u).0;
+        let mut negatives = Vec::with_capacity(self.num_negatives);
+        for _ in 0..(self.num_negatives * 10) {
+            if negatives.len() == self.num_negatives { break }
+
+            // Falls back to uniform when no node has edges
+            let neg = match degree_table {
+                Some(table) => table.sample(rng),
+                None => rng.gen_range(0, num_nodes)
+            };
+
+            if neg != u && neg != v && !neighbors.contains(&neg) && !negatives.contains(&neg) {
+                negatives.push(neg);
+            }
+        }
+        negatives
+    }
+}
+
+/// Recall and latency of an ANN index for a single search budget
+#[derive(Clone,Copy,Debug)]
+pub struct AnnRecall {
+    /// Minimum number of nodes searched per tree
+    pub min_search_nodes: usize,
+
+    /// Average fraction of the exact top K returned by the index
+    pub recall: f32,
+
+    /// Average time per query
+    pub mean_latency: Duration,
+
+    /// 99th percentile time per query
+    pub p99_latency: Duration
+}
+
+/// Samples `num_queries` nodes from the embedding store and compares the index's top K against
+/// the exact top K, computed by brute force, for each `min_search_nodes` in the sweep.  Queries
+/// are run one at a time so latencies reflect serving a single request.  Fails if the index
+/// hasn't been fit on the embedding store.
+pub fn ann_recall(
+    ann: &Ann,
+    es: &EmbeddingStore,
+    num_queries: usize,
+    k: usize,
+    min_search_nodes: &[usize],
+    seed: u64
+) -> Result<Vec<AnnRecall>, GraphLibError> {
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    let queries = rand::seq::index::sample(&mut rng, es.len(), num_queries.min(es.len())).into_vec();
+    let truth: Vec<Vec<NodeID>> = queries.iter().map(|q| {
+        es.top_k(es.get_embedding(*q), k, |_| true)
+            .into_iter()
+            .map(|nd| nd.1)
+            .collect()
+    }).collect();
+
+    let mut sweep = Vec::with_capacity(min_search_nodes.len());
+    for msn in min_search_nodes.iter() {
+        let mut latencies = Vec::with_capacity(queries.len());
+        let mut recall = 0f32;
+        for (q, exact) in queries.iter().zip(truth.iter()) {
+            let start = Instant::now();
+            let results = ann.predict(es, es.get_embedding(*q), k, Some(*msn))?;
+            latencies.push(start.elapsed());
+
+            let found = results.iter().filter(|nd| exact.contains(&nd.1)).count();
+            recall += found as f32 / exact.len().max(1) as f32;
+        }
+
+        latencies.sort();
+        let n = latencies.len().max(1);
+        sweep.push(AnnRecall {
+            min_search_nodes: *msn,
+            recall: recall / n as f32,
+            mean_latency: latencies.iter().sum::<Duration>() / n as u32,
+            p99_latency: latencies.get((n * 99 / 100).min(n - 1)).cloned().unwrap_or_default()
+        });
+    }
+    Ok(sweep)
+}
+
+/// How much an embedding space moved between two versions, such as consecutive retrains, over a
+/// sample of nodes.  Used to gate deployments on embedding stability.
+#[derive(Clone,Copy,Debug)]
+pub struct Drift {
+    /// Average fraction of each node's top K neighbors shared between the old and new spaces
+    pub overlap: f32,
+
+    /// Average cosine distance between each node's old, possibly aligned, and new embedding
+    pub cosine_shift: f32,
+
+    /// Number of sampled nodes which contributed to the metrics
+    pub nodes: usize
+}
+
+/// Measures drift between two versions of the same embedding space, where node ids match.  Only
+/// nodes in both stores are considered as neighbors, so nodes added since the old version don't
+/// count against the overlap.  Independently trained spaces are arbitrarily rotated relative to
+/// each other, so with `align` the old space is first rotated onto the new with orthogonal
+/// Procrustes, fit on the sample; rotations don't change neighborhoods, only the cosine shift.
+pub fn drift(
+    old: &EmbeddingStore,
+    new: &EmbeddingStore,
+    sample: &[NodeID],
+    k: usize,
+    align: bool
+) -> Result<Drift, GraphLibError> {
+    check_dims(old.dims(), new.dims())?;
+    let shared = old.len().min(new.len());
+    if let Some(node_id) = sample.iter().find(|node_id| **node_id >= shared) {
+        return Err(GraphLibError::InvalidInput(format!("Node {} isn't in both stores", node_id)))
+    }
+
+    let rotation = if align && !sample.is_empty() {
+        let old_rows: Vec<_> = sample.iter().map(|n| old.get_embedding(*n)).collect();
+        let new_rows: Vec<_> = sample.iter().map(|n| new.get_embedding(*n)).collect();
+        Some(procrustes(&old_rows, &new_rows, old.dims()))
+    } else {
+        None
+    };
+
+    let (overlap, shift) = sample.par_iter().map(|node_id| {
+        let old_nn = top_k(old, *node_id, k, |n| n < shared);
+        let new_nn = top_k(new, *node_id, k, |n| n < shared);
+        let found = old_nn.iter().filter(|nd| new_nn.iter().any(|nd2| nd2.1 == nd.1)).count();
+        let overlap = found as f32 / old_nn.len().max(1) as f32;
+
+        let old_emb = old.get_embedding(*node_id);
+        let new_emb = new.get_embedding(*node_id);
+        let shift = match rotation.as_ref() {
+            Some(r) => cosine_distance(&rotate(old_emb, r), new_emb),
+            None => cosine_distance(old_emb, new_emb)
+        };
+        (overlap, shift)
+    }).reduce(|| (0f32, 0f32), |a, b| (a.0 + b.0, a.1 + b.1));
+
+    let denom = sample.len().max(1) as f32;
+    Ok(Drift { overlap: overlap / denom, cosine_shift: shift / denom, nodes: sample.len() })
+}
+
+fn cosine_distance(x: &[f32], y: &[f32]) -> f32 {
+    let (mut xy, mut xx, mut yy) = (0f32, 0f32, 0f32);
+    x.iter().zip(y.iter()).for_each(|(xi, yi)| {
+        xy += xi * yi;
+        xx += xi * xi;
+        yy += yi * yi;
+    });
+    if xx == 0f32 || yy == 0f32 {
+        return 1f32
+    }
+    1f32 - xy / (xx.sqrt() * yy.sqrt())
+}
+
+/// Row vector times a row major dims x dims matrix
+fn rotate(x: &[f32], r: &[f64]) -> Vec<f32> {
+    let dims = x.len();
+    let mut out = vec![0f64; dims];
+    x.iter().enumerate().for_each(|(i, xi)| {
+        let row = &r[i * dims..(i + 1) * dims];
+        out.iter_mut().zip(row.iter()).for_each(|(o, rij)| *o += *xi as f64 * rij);
+    });
+    out.into_iter().map(|v| v as f32).collect()
+}
+
+/// Row major product of two dims x dims matrices, optionally transposing the left one
+fn matmul(a: &[f64], b: &[f64], dims: usize, transpose_a: bool) -> Vec<f64> {
+    let mut out = vec![0f64; dims * dims];
+    out.par_chunks_mut(dims).enumerate().for_each(|(i, row)| {
+        for l in 0..dims {
+            let a_il = if transpose_a { a[l * dims + i] } else { a[i * dims + l] };
+            if a_il != 0f64 {
+                row.iter_mut().zip(b[l * dims..(l + 1) * dims].iter())
+                    .for_each(|(o, b_lj)| *o += a_il * b_lj);
+            }
+        }
+    });
+    out
+}
+
+/// Orthogonal Procrustes: the rotation R minimizing ||XR - Y||, which is the orthogonal polar
+/// factor of X^T Y.  We avoid needing an SVD by computing the polar factor with Newton-Schulz
+/// iterations, X <- X (3I - X^T X) / 2, which converge once the starting singular values are
+/// under sqrt(3); scaling by the Frobenius norm guarantees that.  Singular values which are
+/// exactly zero stay zero, so rank deficient samples yield a partial rotation.
+fn procrustes(xs: &[&[f32]], ys: &[&[f32]], dims: usize) -> Vec<f64> {
+    const MAX_ITERS: usize = 100;
+    const TOL: f64 = 1e-9;
+
+    let mut m = vec![0f64; dims * dims];
+    xs.iter().zip(ys.iter()).for_each(|(x, y)| {
+        for i in 0..dims {
+            let xi = x[i] as f64;
+            m[i * dims..(i + 1) * dims].iter_mut().zip(y.iter())
+                .for_each(|(mij, yj)| *mij += xi * *yj as f64);
+        }
+    });
+
+    let norm = m.iter().map(|v| v * v).sum::<f64>().sqrt();
+    if norm == 0f64 {
+        // Nothing to align with; fall back to the identity
+        return (0..dims * dims).map(|idx| if idx / dims == idx % dims { 1. } else { 0. }).collect()
+    }
+    m.iter_mut().for_each(|v| *v /= norm);
+
+    for _ in 0..MAX_ITERS {
+        let mut inner = matmul(&m, &m, dims, true);
+        inner.iter_mut().enumerate().for_each(|(idx, v)| {
+            *v = if idx / dims == idx % dims { 3f64 - *v } else { -*v };
+        });
+        let next: Vec<_> = matmul(&m, &inner, dims, false).into_iter().map(|v| v / 2f64).collect();
+        let delta = next.iter().zip(m.iter()).map(|(a, b)| (a - b) * (a - b)).sum::<f64>();
+        m = next;
+        if delta < TOL {
+            break
+        }
+    }
+    m
+}
+
+/// Single threaded top-k scan, excluding the anchor itself.  We're already parallelized over
+/// anchors so there's no need to parallelize each scan.
+fn top_k<F: Fn(NodeID) -> bool>(
+    es: &EmbeddingStore,
+    anchor: NodeID,
+    k: usize,
+    filter: F
+) -> Vec<NodeDistance> {
+    let emb = es.get_embedding(anchor);
+    let mut tk = TopK::new(k);
+    for node_id in 0..es.len() {
+        if node_id != anchor && filter(node_id) {
+            tk.push(node_id, es.distance().compute(emb, es.get_embedding(node_id)));
+        }
+    }
+    tk.into_sorted()
+}
+
+/// Computes the Spearman rank correlation between the given ordering (0..n) and the ordering
+/// implied by the provided scores.
+fn spearman(scores: &[f32]) -> f32 {
+    let n = scores.len();
+    if n < 2 {
+        return 1f32
+    }
+
+    let mut order: Vec<_> = (0..n).collect();
+    order.sort_by_key(|idx| FloatOrd(scores[*idx]));
+    let d2 = order.iter().enumerate().map(|(rank, idx)| {
+        let d = rank as f32 - *idx as f32;
+        d * d
+    }).sum::<f32>();
+
+    let n = n as f32;
+    1f32 - (6f32 * d2) / (n * (n * n - 1f32))
+}
+
+#[cfg(test)]
+mod evaluation_tests {
+    use super::*;
+    use crate::distance::Distance;
+
+    fn build_store(offset: f32) -> EmbeddingStore {
+        let mut es = EmbeddingStore::new(5, 1, Distance::Euclidean);
+        for node_id in 0..5 {
+            es.set_embedding(node_id, &[(node_id as f32).powf(2f32) + offset]);
+        }
+        es
+    }
+
+    #[test]
+    fn test_link_prediction() {
+        use crate::graph::CSR;
+
+        // Nodes sit on a line, so the closest node is always the best prediction
+        let es = build_store(0.);
+        let graph = CSR::construct_from_edges(vec![(0, 1, 1.), (4, 4, 1.)], false);
+        let lp = LinkPrediction {
+            num_negatives: 10,
+            hits_at: vec![1, 2],
+            sampling: NegativeSampling::Uniform,
+            seed: 2023
+        };
+
+        // Training edges are filtered out of the negatives, leaving 2 and 3 for node 0
+        let metrics = lp.evaluate(&graph, &[(0, 4)], &es);
+        assert_eq!(metrics.edges, 1);
+        assert_eq!(metrics.auc, 0.);
+        assert_eq!(metrics.mrr, 1. / 3.);
+        assert_eq!(metrics.hits, vec![(1, 0.), (2, 0.)]);
+
+        let metrics = lp.evaluate(&graph, &[(1, 0), (3, 2), (7, 0)], &es);
+        assert_eq!(metrics.edges, 2);
+        assert_eq!(metrics.auc, 1.);
+        assert_eq!(metrics.mrr, 1.);
+        assert_eq!(metrics.hits, vec![(1, 1.), (2, 1.)]);
+
+        // Only nodes with edges are sampled with degree weighting
+        let lp = LinkPrediction { sampling: NegativeSampling::Degree, ..lp };
+        let metrics = lp.evaluate(&graph, &[(2, 1)], &es);
+        assert_eq!(metrics.edges, 1);
+        assert_eq!(metrics.mrr, 1.);
+    }
+
+    #[test]
+    fn test_ann_recall() {
+        use rand::distributions::Standard;
+
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut es = EmbeddingStore::new(500, 8, Distance::Cosine);
+        for node_id in 0..es.len() {
+            let emb: Vec<f32> = (&mut rng).sample_iter(Standard).take(8).collect();
+            es.set_embedding(node_id, &emb);
+        }
+
+        let mut ann = Ann::new();
+        ann.fit(&es, 5, 10, None, None, None, 2023).unwrap();
+        let sweep = ann_recall(&ann, &es, 20, 10, &[10, 100, 500], 2023).unwrap();
+        assert_eq!(sweep.len(), 3);
+        assert_eq!(sweep[1].min_search_nodes, 100);
+        assert!(sweep.iter().all(|r| r.recall >= 0. && r.recall <= 1.));
+
+        // Searching every node is exact, barring floating point differences at the cutoff
+        assert!(sweep[2].recall > 0.99);
+        assert!(sweep[0].recall <= sweep[2].recall);
+    }
+
+    #[test]
+    fn test_drift() {
+        use rand::distributions::Standard;
+
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut old = EmbeddingStore::new(50, 4, Distance::Cosine);
+        let mut new = EmbeddingStore::new(60, 4, Distance::Cosine);
+        for node_id in 0..new.len() {
+            let x: Vec<f32> = (&mut rng).sample_iter(Standard).map(|v: f32| v - 0.5).take(4).collect();
+            if node_id < old.len() {
+                old.set_embedding(node_id, &x);
+            }
+            // Rotated, which leaves every neighborhood intact
+            new.set_embedding(node_id, &[x[1], -x[0], x[3], x[2]]);
+        }
+
+        let sample: Vec<_> = (0..20).collect();
+        let unaligned = drift(&old, &new, &sample, 5, false).unwrap();
+        assert_eq!(unaligned.nodes, 20);
+        assert!((unaligned.overlap - 1.).abs() < 1e-5);
+        assert!(unaligned.cosine_shift > 0.1);
+
+        let aligned = drift(&old, &new, &sample, 5, true).unwrap();
+        assert!((aligned.overlap - 1.).abs() < 1e-5);
+        assert!(aligned.cosine_shift.abs() < 1e-4);
+
+        assert!(drift(&old, &new, &[55], 5, false).is_err());
+        let other = EmbeddingStore::new(50, 3, Distance::Cosine);
+        assert!(drift(&old, &other, &sample, 5, true).is_err());
+    }
+
+    #[test]
+    fn test_spearman() {
+        assert_eq!(spearman(&[1., 2., 3., 4.]), 1.);
+        assert_eq!(spearman(&[4., 3., 2., 1.]), -1.);
+    }
+
+    #[test]
+    fn test_identical_spaces() {
+        let source = build_store(0.);
+        let target = build_store(10.);
+        let translation: TranslationTable = (0..5).map(|idx| Some(idx)).collect();
+        let anchors: Vec<_> = (0..5).collect();
+        let agreement = cross_space_agreement(&source, &target, &translation, &anchors, 2);
+        assert_eq!(agreement.nodes, 5);
+        assert_eq!(agreement.overlap, 1.);
+        assert_eq!(agreement.rank_correlation, 1.);
+    }
+
+    #[test]
+    fn test_partial_translation() {
+        let source = build_store(0.);
+        let target = build_store(0.);
+        let translation: TranslationTable = vec![Some(0), None, Some(2), Some(3), Some(4)];
+        let anchors: Vec<_> = (0..5).collect();
+        let agreement = cross_space_agreement(&source, &target, &translation, &anchors, 2);
+        assert_eq!(agreement.nodes, 4);
+        assert_eq!(agreement.overlap, 1.);
+    }
+}
@@ -4,10 +4,12 @@
 /// learning anonymous node embeddings.
 use hashbrown::HashMap;
 use float_ord::FloatOrd;
+use rayon::prelude::*;
 
-use crate::graph::Graph;
+use crate::graph::{Graph,CDFGraph,CDFtoP};
 use crate::feature_store::FeatureStore;
 use crate::bitset::BitSet;
+use crate::error::GraphLibError;
 
 /// Propagates features into a feature store.
 pub fn propagate_features(
@@ -94,9 +96,141 @@ pub fn propagate_features(
     }
 }
 
+/// Diffuses weighted features from nodes with features into nodes without them.  Each node with
+/// features starts with a distribution over its features summing to 1.  Each iteration, every
+/// featureless node takes the edge weighted average of its neighbors' distributions, scaled by
+/// decay, so features arriving from further away carry less weight.  Nodes which already have
+/// features are never modified.
+#[derive(Clone,Copy,Debug)]
+pub struct FeatureDiffusion {
+    /// Number of iterations, which is also the max number of hops a feature can travel
+    pub max_iters: usize,
+
+    /// Multiplier applied to feature weights at each hop, in (0, 1]
+    pub decay: f32,
+
+    /// Max number of features kept for each node
+    pub k: usize,
+
+    /// Features with a diffused weight below this are dropped
+    pub threshold: f32
+}
+
+impl FeatureDiffusion {
+
+    /// Adds the diffused features to each node which had none.  Nodes which are too far from
+    /// any features stay empty, so call `fill_missing_nodes` afterwards if needed.
+    pub fn diffuse(
+        &self, 
+        graph: &(impl CDFGraph + Send + Sync), 
+        features: &mut FeatureStore
+    ) -> Result<(), GraphLibError> {
+        if self.decay.is_nan() || self.decay <= 0. || self.decay > 1. {
+            return Err("decay must be in (0, 1]!".into())
+        }
+        if self.k == 0 {
+            return Err("k must be positive!".into())
+        }
+        if graph.len() > features.num_nodes() {
+            return Err(GraphLibError::DimensionMismatch { 
+                expected: graph.len(), 
+                found: features.num_nodes() 
+            })
+        }
+
+        let seeded: Vec<_> = (0..graph.len())
+            .map(|node_id| features.get_features(node_id).len() > 0)
+            .collect();
+
+        let mut state: Vec<Vec<(usize, f32)>> = (0..graph.len()).map(|node_id| {
+            let feats = features.get_features(node_id);
+            let w = 1. / feats.len() as f32;
+            feats.iter().map(|f| (*f, w)).collect()
+        }).collect();
+
+        for _iter in 0..self.max_iters {
+            let next: Vec<_> = (0..graph.len()).into_par_iter().map(|node_id| {
+                if seeded[node_id] {
+                    return state[node_id].clone()
+                }
+
+                let (edges, weights) = graph.get_edges(node_id);
+                let mut working_map = HashMap::new();
+                for (edge, p) in edges.iter().zip(CDFtoP::new(weights)) {
+                    for (feat, w) in state[*edge].iter() {
+                        *working_map.entry(*feat).or_insert(0.) += p * w * self.decay;
+                    }
+                }
+
+                let mut working_vec: Vec<_> = working_map.into_iter()
+                    .filter(|(_, w)| *w >= self.threshold)
+                    .collect();
+                working_vec.sort_by_key(|(f, w)| (FloatOrd(-*w), *f));
+                working_vec.truncate(self.k);
+                working_vec
+            }).collect();
+            state = next;
+        }
+
+        for (node_id, feats) in state.into_iter().enumerate() {
+            if !seeded[node_id] {
+                features.set_features_raw(node_id, feats.into_iter().map(|(f, _)| f));
+            }
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod pf_tests {
     use super::*;
-    use crate::graph::CSR;
+    use crate::graph::{CSR,CumCSR};
+
+    // Line graph 0 - 1 - 2 - 3 - 4, with features only on the ends
+    fn build_line() -> (CumCSR, FeatureStore) {
+        let mut edges = Vec::new();
+        for ni in 0..4 {
+            edges.push((ni, ni + 1, 1f32));
+            edges.push((ni + 1, ni, 1f32));
+        }
+        let graph = CumCSR::convert(CSR::construct_from_edges(edges, false));
+        let mut features = FeatureStore::new(5);
+        features.set_features(0, [("color", "red")].into_iter());
+        features.set_features(4, [("color", "blue"), ("size", "large")].into_iter());
+        (graph, features)
+    }
+
+    #[test]
+    fn test_diffuse() {
+        let (graph, mut features) = build_line();
+        let diffusion = FeatureDiffusion { max_iters: 4, decay: 1., k: 10, threshold: 0. };
+        diffusion.diffuse(&graph, &mut features).unwrap();
+
+        // Node 1 is closest to red, node 3 is closest to blue and large, which tie
+        assert_eq!(features.get_features(0), &[0]);
+        assert_eq!(features.get_features(1), &[0, 1, 2]);
+        assert_eq!(features.get_features(2), &[0, 1, 2]);
+        assert_eq!(features.get_features(3), &[1, 2, 0]);
+        assert_eq!(features.get_features(4), &[1, 2]);
+    }
+
+    #[test]
+    fn test_decay() {
+        // Each hop halves the weight, so only the nearest features survive the threshold
+        let (graph, mut features) = build_line();
+        let diffusion = FeatureDiffusion { max_iters: 4, decay: 0.5, k: 10, threshold: 0.2 };
+        diffusion.diffuse(&graph, &mut features).unwrap();
+        assert_eq!(features.get_features(1), &[0]);
+        assert!(features.get_features(2).is_empty());
+        assert!(features.get_features(3).is_empty());
 
+        let (graph, mut features) = build_line();
+        let diffusion = FeatureDiffusion { max_iters: 4, decay: 1., k: 1, threshold: 0. };
+        diffusion.diffuse(&graph, &mut features).unwrap();
+        assert_eq!(features.get_features(1), &[0]);
+        assert_eq!(features.get_features(3).len(), 1);
+
+        let diffusion = FeatureDiffusion { decay: 0., ..diffusion };
+        assert!(diffusion.diffuse(&graph, &mut features).is_err());
+    }
 }
@@ -0,0 +1,114 @@
+//! Nearest neighbor introspection over learned feature embeddings.  Looking up which features
+//! embed closest to a query feature, such as the tokens nearest "logo", is the quickest sanity
+//! check of a training run.  This builds a small Ann over the discrete features and answers
+//! queries by name, so it can be done without exporting the embeddings.
+use crate::embeddings::EmbeddingStore;
+use crate::feature_store::FeatureStore;
+use crate::error::GraphLibError;
+use crate::algos::ann::{Ann,AnnBuildConfig};
+
+/// A named neighbor: (namespace, feature, distance)
+pub type NamedNeighbor = (String, String, f32);
+
+/// Ann over the discrete feature embeddings of a FeatureStore.  Dense column projections aren't
+/// indexed.
+pub struct FeatureNeighbors<'a> {
+    features: &'a FeatureStore,
+    feature_embeddings: &'a EmbeddingStore,
+    ann: Ann
+}
+
+impl <'a> FeatureNeighbors<'a> {
+
+    /// Indexes the feature embeddings.  Fails if there are fewer embeddings than features.
+    pub fn new(
+        features: &'a FeatureStore,
+        feature_embeddings: &'a EmbeddingStore,
+        config: &AnnBuildConfig
+    ) -> Result<Self, GraphLibError> {
+        if feature_embeddings.len() < features.num_features() {
+            return Err(GraphLibError::DimensionMismatch {
+                expected: features.num_features(),
+                found: feature_embeddings.len()
+            })
+        }
+
+        let mut ann = Ann::new();
+        let feat_ids = (0..features.num_features()).collect();
+        ann.fit_with_config(feature_embeddings, config, Some(feat_ids))?;
+        Ok(FeatureNeighbors { features, feature_embeddings, ann })
+    }
+
+    /// Approximate k nearest features to a feature id, excluding itself, nearest first
+    pub fn nearest(&self, feat_id: usize, k: usize) -> Result<Vec<(usize, f32)>, GraphLibError> {
+        if feat_id >= self.features.num_features() {
+            return Err(GraphLibError::InvalidInput(format!("Unknown feature id: {}", feat_id)))
+        }
+
+        let emb = self.feature_embeddings.get_embedding(feat_id);
+        let results = self.ann.predict(self.feature_embeddings, emb, k + 1, None)?;
+        Ok(results.into_iter()
+            .filter(|nd| nd.1 != feat_id)
+            .take(k)
+            .map(|nd| (nd.1, nd.0))
+            .collect())
+    }
+
+    /// Same as `nearest`, but the query and results are (namespace, feature) names
+    pub fn nearest_named(
+        &self,
+        namespace: &str,
+        name: &str,
+        k: usize
+    ) -> Result<Vec<NamedNeighbor>, GraphLibError> {
+        let vocab = self.features.get_vocab();
+        let feat_id = vocab.get_node_id(namespace, name).ok_or_else(|| {
+            GraphLibError::InvalidInput(format!("Unknown feature: {} {}", namespace, name))
+        })?;
+
+        Ok(self.nearest(feat_id, k)?.into_iter()
+            .map(|(f_id, d)| {
+                let (ns, name) = vocab.get_name(f_id).expect("Indexed features are in the vocab");
+                (ns.to_string(), name.to_string(), d)
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod feature_nn_tests {
+    use super::*;
+    use crate::distance::Distance;
+
+    #[test]
+    fn test_nearest_named() {
+        let mut features = FeatureStore::new(1);
+        features.set_features(0, [("color", "red"), ("color", "crimson"), ("color", "blue"),
+                                  ("color", "navy")].into_iter());
+
+        // Reds point one way, blues the other
+        let values = vec![
+            1., 0.1,
+            0.9, 0.2,
+            -1., 0.1,
+            -0.9, 0.2
+        ];
+        let fe = EmbeddingStore::new_with_vec(4, 2, Distance::Cosine, values).unwrap();
+        let config = AnnBuildConfig::new(2, 4, 2023);
+        let fnn = FeatureNeighbors::new(&features, &fe, &config).unwrap();
+
+        let nearest = fnn.nearest_named("color", "red", 1).unwrap();
+        assert_eq!(nearest.len(), 1);
+        assert_eq!((nearest[0].0.as_str(), nearest[0].1.as_str()), ("color", "crimson"));
+
+        let nearest = fnn.nearest_named("color", "navy", 3).unwrap();
+        assert_eq!(nearest[0].1, "blue");
+        assert!(nearest.iter().all(|(_, name, _)| name != "navy"));
+
+        assert!(fnn.nearest_named("color", "green", 1).is_err());
+        assert!(fnn.nearest(4, 1).is_err());
+
+        let small = EmbeddingStore::new(2, 2, Distance::Cosine);
+        assert!(FeatureNeighbors::new(&features, &small, &config).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Adaptive batch sizing.  We estimate the gradient noise scale (McCandlish et al., "An Empirical
+//! Model of Large-Batch Training") during the early passes and use it to pick a batch size, rather
+//! than having to hand tune it for every graph.
+
+/// Controls how the batch size is adapted during optimization.
+#[derive(Clone,Copy,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AdaptiveBatchSize {
+    /// Number of passes used to estimate the gradient noise scale before adjusting the batch size
+    pub warmup_passes: usize,
+
+    /// Smallest batch size we'll adjust down to
+    pub min_batch_size: usize,
+
+    /// Largest batch size we'll adjust up to
+    pub max_batch_size: usize
+}
+
+impl AdaptiveBatchSize {
+    pub fn new(warmup_passes: usize, min_batch_size: usize, max_batch_size: usize) -> Self {
+        AdaptiveBatchSize { warmup_passes, min_batch_size, max_batch_size }
+    }
+
+    /// Given the current batch size and the estimated noise scale, returns the new batch size and
+    /// the amount to scale the learning rate by.  We use square root scaling of the learning
+    /// rate, which works better than linear scaling with Adam.
+    pub fn adjust(&self, batch_size: usize, noise_scale: Option<f32>) -> (usize, f32) {
+        match noise_scale {
+            Some(ns) if ns.is_finite() && ns > 0f32 => {
+                let new_batch_size = (ns.round() as usize)
+                    .max(self.min_batch_size)
+                    .min(self.max_batch_size)
+                    .max(1);
+
+                let scale = (new_batch_size as f32 / batch_size as f32).sqrt();
+                (new_batch_size, scale)
+            },
+            _ => (batch_size, 1f32)
+        }
+    }
+}
+
+/// Tracks estimates of the true gradient norm and the trace of the gradient covariance across
+/// batches.  Each batch provides two gradient estimates: the per-example gradients and the batch
+/// mean gradient, from which we can produce unbiased estimates of both.
+#[derive(Debug,Default)]
+pub struct NoiseScaleEstimator {
+    /// Sum of the squared gradient norm estimates
+    g_sq: f32,
+
+    /// Sum of the covariance trace estimates
+    s: f32,
+
+    /// Number of batches seen
+    n: usize
+}
+
+impl NoiseScaleEstimator {
+    pub fn new() -> Self {
+        NoiseScaleEstimator::default()
+    }
+
+    /// Updates the estimator.  `small_sq_norm` is the average squared norm of the per-example
+    /// gradients, `big_sq_norm` is the squared norm of the batch mean gradient.
+    pub fn update(&mut self, small_sq_norm: f32, big_sq_norm: f32, batch_size: usize) {
+        if batch_size < 2 {
+            return
+        }
+        let b = batch_size as f32;
+        let g_sq = (b * big_sq_norm - small_sq_norm) / (b - 1f32);
+        let s = (small_sq_norm - big_sq_norm) / (1f32 - 1f32 / b);
+        if g_sq.is_finite() && s.is_finite() {
+            self.g_sq += g_sq;
+            self.s += s;
+            self.n += 1;
+        }
+    }
+
+    /// Returns the simple noise scale, B_simple = tr(Σ) / |G|^2
+    pub fn noise_scale(&self) -> Option<f32> {
+        if self.n == 0 || self.g_sq <= 0f32 {
+            None
+        } else {
+            Some(self.s / self.g_sq)
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = NoiseScaleEstimator::default();
+    }
+}
+
+/// Computes the squared L2 norm of a set of sparse gradients
+pub fn squared_norm<'a>(grads: impl Iterator<Item=&'a Vec<f32>>) -> f32 {
+    grads.map(|g| g.iter().map(|gi| gi * gi).sum::<f32>()).sum()
+}
+
+#[cfg(test)]
+mod batch_size_tests {
+    use super::*;
+
+    #[test]
+    fn test_noise_scale() {
+        let mut est = NoiseScaleEstimator::new();
+        assert_eq!(est.noise_scale(), None);
+
+        // No noise: per example gradients match the batch gradient
+        est.update(4f32, 4f32, 10);
+        assert_eq!(est.noise_scale(), Some(0f32));
+
+        // Pure noise, no signal in the gradient
+        est.reset();
+        est.update(10f32, 1f32, 10);
+        assert_eq!(est.noise_scale(), None);
+
+        est.reset();
+        est.update(20f32, 3f32, 10);
+        let ns = est.noise_scale().unwrap();
+        let expected = ((20f32 - 3f32) / 0.9f32) / ((30f32 - 20f32) / 9f32);
+        assert!((ns - expected).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_adjust() {
+        let ab = AdaptiveBatchSize::new(1, 16, 256);
+        assert_eq!(ab.adjust(64, None), (64, 1f32));
+        assert_eq!(ab.adjust(64, Some(1024f32)), (256, 2f32));
+        assert_eq!(ab.adjust(64, Some(1f32)), (16, 0.5f32));
+    }
+}
@@ -1,3 +1,5 @@
 pub mod scheduler;
 pub mod optimizer;
 pub mod node_sampler;
+pub mod batch_size;
+pub mod precision;
@@ -1,6 +1,7 @@
 //! Defines Samplers for selecting negatives from the graph.  This is a big over-engineered right
 //! now as the intent was to have richer samplers which ended up not being the limiting step.
 use std::borrow::Borrow;
+use hashbrown::HashMap;
 use rand::prelude::*;
 use rand_distr::{Distribution,Uniform};
 
@@ -106,6 +107,284 @@ impl <'a> NodeSampler for RandomWalkHardSampler<'a> {
     }
 }
 
+/// Pools of candidate negatives, such as all items within the same category or marketplace as the
+/// anchor.  Restricting negatives to a pool calibrates distances within each segment.
+#[derive(Clone,Debug)]
+pub enum CandidatePools {
+    /// Every anchor samples from the same pool
+    Global(Vec<NodeID>),
+
+    /// Each anchor samples from its assigned pool.  Anchors share pools by index so segments only
+    /// need to be stored once.  Anchors without an assignment sample from the fallback nodes.
+    PerAnchor {
+        pools: Vec<Vec<NodeID>>,
+        assignments: HashMap<NodeID, usize>
+    }
+}
+
+impl CandidatePools {
+    fn get_pool(&self, anchor: NodeID) -> Option<&[NodeID]> {
+        let pool = match self {
+            CandidatePools::Global(pool) => Some(pool.as_slice()),
+            CandidatePools::PerAnchor { pools, assignments } => {
+                assignments.get(&anchor).and_then(|idx| pools.get(*idx)).map(|p| p.as_slice())
+            }
+        };
+        pool.filter(|p| p.len() > 0)
+    }
+}
+
+/// Draws negatives uniformly from the anchor's candidate pool, falling back to the provided
+/// nodes when the anchor has no pool.
+pub struct CandidatePoolStrategy<'a> {
+    pools: &'a CandidatePools,
+    fallback: Vec<NodeID>
+}
+
+impl <'a> CandidatePoolStrategy<'a> {
+    pub fn new(pools: &'a CandidatePools, fallback: &[NodeID]) -> Self {
+        CandidatePoolStrategy { pools, fallback: fallback.to_vec() }
+    }
+}
+
+impl <'a, 'b> BatchSamplerStrategy for &'b CandidatePoolStrategy<'a> {
+    type Sampler = CandidatePoolSampler<'b>;
+
+    fn initialize_batch<
+        G: CGraph + Send + Sync,
+        T: Borrow<NodeID>>
+    (
+        &self,
+        _nodes: &[T],
+        _graph: &G,
+        _features: &FeatureStore
+    ) -> Self::Sampler {
+        CandidatePoolSampler { pools: self.pools, fallback: self.fallback.as_slice() }
+    }
+}
+
+pub struct CandidatePoolSampler<'a> {
+    pools: &'a CandidatePools,
+    fallback: &'a [NodeID]
+}
+
+impl <'a> NodeSampler for CandidatePoolSampler<'a> {
+    fn sample_negatives<R: Rng>(
+        &self, 
+        graph: &impl CGraph,
+        anchor: NodeID, 
+        negatives: &mut Vec<NodeID>,
+        num_negs: usize,
+        rng: &mut R
+    ) {
+        if let Some(pool) = self.pools.get_pool(anchor) {
+            // Skip the anchor and its direct neighbors, which are positives.  We bound the number
+            // of attempts in case the pool is mostly neighbors.
+            let anchor_edges = graph.get_edges(anchor).0;
+            let dist = Uniform::new(0, pool.len());
+            for _ in 0..(num_negs * 4) {
+                if negatives.len() >= num_negs { break }
+                let node = pool[dist.sample(rng)];
+                if node != anchor && !anchor_edges.contains(&node) {
+                    negatives.push(node);
+                }
+            }
+        }
+
+        if self.fallback.len() > 0 {
+            let dist = Uniform::new(0, self.fallback.len());
+            while negatives.len() < num_negs {
+                negatives.push(self.fallback[dist.sample(rng)]);
+            }
+        }
+    }
+}
+
+/// Downsamples high degree anchors when building each pass.  Otherwise hubs, which show up as
+/// anchors as often as any other node but also dominate everyone's reconstructions, drown out the
+/// gradient signal for tail nodes.
+#[derive(Clone,Copy,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DegreeBalancing {
+    /// Nodes with more edges than the cap are kept with probability cap / degree
+    Capped(usize),
+
+    /// Nodes are kept with probability 1 / degree^exponent
+    InverseDegree(f32)
+}
+
+impl DegreeBalancing {
+    /// Probability a node with the given degree is used as an anchor within a pass
+    pub fn keep_probability(&self, degree: usize) -> f32 {
+        match self {
+            DegreeBalancing::Capped(cap) => {
+                if degree > *cap { *cap as f32 / degree as f32 } else { 1f32 }
+            },
+            DegreeBalancing::InverseDegree(exponent) => {
+                1f32 / (degree.max(1) as f32).powf(*exponent)
+            }
+        }
+    }
+
+    /// Expected number of nodes kept each pass
+    pub fn expected_size(&self, graph: &impl CGraph, nodes: &[NodeID]) -> usize {
+        let total: f32 = nodes.iter()
+            .map(|node| self.keep_probability(graph.degree(*node)))
+            .sum();
+        total.ceil() as usize
+    }
+
+    /// Samples the nodes to use for a pass, preserving their order
+    pub fn sample<R: Rng>(
+        &self, 
+        graph: &impl CGraph, 
+        nodes: &[NodeID], 
+        rng: &mut R
+    ) -> Vec<NodeID> {
+        nodes.iter()
+            .filter(|node| rng.gen::<f32>() < self.keep_probability(graph.degree(**node)))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Allows selecting between two samplers at runtime.
+pub enum EitherSampler<A, B> {
+    Left(A),
+    Right(B)
+}
+
+impl <A: NodeSampler, B: NodeSampler> NodeSampler for EitherSampler<A, B> {
+    fn sample_negatives<R: Rng>(
+        &self, 
+        graph: &impl CGraph,
+        anchor: NodeID, 
+        negatives: &mut Vec<NodeID>,
+        num_negs: usize,
+        rng: &mut R
+    ) {
+        match self {
+            EitherSampler::Left(s) => s.sample_negatives(graph, anchor, negatives, num_negs, rng),
+            EitherSampler::Right(s) => s.sample_negatives(graph, anchor, negatives, num_negs, rng)
+        }
+    }
+}
+
+/// Anchors with at least this many edges get a bloom filter over their neighbors, rather than
+/// scanning the adjacency list for every sampled negative.
+const BLOOM_MIN_DEGREE: usize = 64;
+
+/// Number of times we'll ask the wrapped sampler for replacements before giving up
+const MAX_EXCLUSION_ROUNDS: usize = 4;
+
+/// Filters the negatives of a wrapped sampler against the anchor's adjacency list, since
+/// neighbors sampled as negatives produce gradients contradicting the positive.  Filters for the
+/// high degree anchors within the batch are built up front.  If the wrapped sampler can't find
+/// enough non-neighbors, such as in very dense graphs, the remaining slots are filled with the
+/// rejected candidates so the number of negatives is unchanged.
+pub struct NeighborExclusionSampler<S> {
+    sampler: S,
+    filters: HashMap<NodeID, BloomFilter>
+}
+
+impl <S: NodeSampler> NeighborExclusionSampler<S> {
+    pub fn new<G: CGraph, T: Borrow<NodeID>>(sampler: S, nodes: &[T], graph: &G) -> Self {
+        let filters = nodes.iter()
+            .map(|node| *node.borrow())
+            .filter(|node| graph.degree(*node) >= BLOOM_MIN_DEGREE)
+            .map(|node| (node, BloomFilter::new(graph.get_edges(node).0)))
+            .collect();
+
+        NeighborExclusionSampler { sampler, filters }
+    }
+
+    fn is_neighbor(&self, graph: &impl CGraph, anchor: NodeID, node: NodeID) -> bool {
+        if node == anchor { return true }
+        if let Some(filter) = self.filters.get(&anchor) {
+            // Bloom filters don't have false negatives
+            if !filter.contains(node) { return false }
+        }
+        graph.get_edges(anchor).0.contains(&node)
+    }
+}
+
+impl <S: NodeSampler> NodeSampler for NeighborExclusionSampler<S> {
+    fn sample_negatives<R: Rng>(
+        &self, 
+        graph: &impl CGraph,
+        anchor: NodeID, 
+        negatives: &mut Vec<NodeID>,
+        num_negs: usize,
+        rng: &mut R
+    ) {
+        let mut rejected = Vec::new();
+        let mut candidates = Vec::with_capacity(num_negs);
+        for _ in 0..MAX_EXCLUSION_ROUNDS {
+            if negatives.len() >= num_negs { break }
+
+            candidates.clear();
+            self.sampler.sample_negatives(graph, anchor, &mut candidates, 
+                                          num_negs - negatives.len(), rng);
+            if candidates.is_empty() { break }
+
+            for node in candidates.drain(..) {
+                if self.is_neighbor(graph, anchor, node) {
+                    rejected.push(node);
+                } else {
+                    negatives.push(node);
+                }
+            }
+        }
+
+        let missing = num_negs.saturating_sub(negatives.len());
+        negatives.extend(rejected.into_iter().take(missing));
+    }
+}
+
+/// Simple bloom filter over NodeIDs, sized for roughly a 1% false positive rate.
+struct BloomFilter {
+    bits: Vec<u64>,
+    num_hashes: usize
+}
+
+impl BloomFilter {
+    fn new(nodes: &[NodeID]) -> Self {
+        // ~10 bits per item with 7 hashes gets us to about 1%
+        let num_words = ((nodes.len() * 10) / 64).max(1);
+        let mut filter = BloomFilter { bits: vec![0; num_words], num_hashes: 7 };
+        for node in nodes.iter() {
+            let (h1, h2) = BloomFilter::hash(*node);
+            for i in 0..filter.num_hashes {
+                let bit = filter.bit(h1, h2, i);
+                filter.bits[bit / 64] |= 1u64 << (bit % 64);
+            }
+        }
+        filter
+    }
+
+    fn contains(&self, node: NodeID) -> bool {
+        let (h1, h2) = BloomFilter::hash(node);
+        (0..self.num_hashes).all(|i| {
+            let bit = self.bit(h1, h2, i);
+            self.bits[bit / 64] & (1u64 << (bit % 64)) != 0
+        })
+    }
+
+    // Double hashing: the ith hash is h1 + i * h2
+    fn bit(&self, h1: u64, h2: u64, i: usize) -> usize {
+        (h1.wrapping_add((i as u64).wrapping_mul(h2)) % (self.bits.len() as u64 * 64)) as usize
+    }
+
+    // Splitmix64 finalizer, split into two halves
+    fn hash(node: NodeID) -> (u64, u64) {
+        let mut z = (node as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z & 0xffff_ffff, (z >> 32) | 1)
+    }
+}
+
 fn random_walk<R: Rng, G: CGraph>(
     anchor: NodeID, 
     graph: &G,
@@ -139,3 +418,124 @@ fn random_walk<R: Rng, G: CGraph>(
     }
 }
 
+#[cfg(test)]
+mod node_sampler_tests {
+    use super::*;
+    use rand_xorshift::XorShiftRng;
+    use crate::graph::CSR;
+
+    #[test]
+    fn test_candidate_pools() {
+        let graph = CSR::construct_from_edges(vec![(0, 1, 1.), (1, 0, 1.), (4, 5, 1.)], false);
+        let mut assignments = HashMap::new();
+        assignments.insert(0, 0);
+        assignments.insert(4, 1);
+        let pools = CandidatePools::PerAnchor {
+            pools: vec![vec![0, 1, 2, 3], vec![4, 5]],
+            assignments
+        };
+        let fallback = [5];
+        let strategy = CandidatePoolStrategy::new(&pools, &fallback);
+        let sampler = (&strategy).initialize_batch(&[0usize], &graph, &FeatureStore::new(6));
+
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut negatives = Vec::new();
+        sampler.sample_negatives(&graph, 0, &mut negatives, 10, &mut rng);
+        assert_eq!(negatives.len(), 10);
+        assert!(negatives.iter().all(|n| *n == 2 || *n == 3));
+
+        // Every candidate is the anchor or a neighbor, so fall back
+        negatives.clear();
+        sampler.sample_negatives(&graph, 4, &mut negatives, 3, &mut rng);
+        assert_eq!(negatives, vec![5, 5, 5]);
+
+        // No assignment
+        negatives.clear();
+        sampler.sample_negatives(&graph, 2, &mut negatives, 2, &mut rng);
+        assert_eq!(negatives, vec![5, 5]);
+    }
+
+    #[test]
+    fn test_degree_balancing() {
+        let capped = DegreeBalancing::Capped(10);
+        assert_eq!(capped.keep_probability(0), 1f32);
+        assert_eq!(capped.keep_probability(10), 1f32);
+        assert_eq!(capped.keep_probability(40), 0.25);
+
+        let inverse = DegreeBalancing::InverseDegree(0.5);
+        assert_eq!(inverse.keep_probability(0), 1f32);
+        assert_eq!(inverse.keep_probability(1), 1f32);
+        assert_eq!(inverse.keep_probability(16), 0.25);
+
+        // Node 0 is a hub connected to everyone else
+        let mut edges = Vec::new();
+        for n in 1..101 {
+            edges.push((0, n, 1.));
+            edges.push((n, 0, 1.));
+        }
+        let graph = CSR::construct_from_edges(edges, false);
+        let nodes: Vec<_> = (0..graph.len()).collect();
+        let balancing = DegreeBalancing::Capped(1);
+        assert_eq!(balancing.expected_size(&graph, &nodes), 101);
+
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let hub_count = (0..1000)
+            .filter(|_| balancing.sample(&graph, &nodes, &mut rng).contains(&0))
+            .count();
+        assert!(hub_count > 0 && hub_count < 30);
+
+        let sampled = balancing.sample(&graph, &nodes, &mut rng);
+        assert!(sampled.len() >= 100);
+        assert!(sampled.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_bloom_filter() {
+        let nodes: Vec<NodeID> = (0..1000).map(|n| n * 3).collect();
+        let filter = BloomFilter::new(&nodes);
+        assert!(nodes.iter().all(|n| filter.contains(*n)));
+
+        let false_positives = (0..1000).filter(|n| filter.contains(n * 3 + 1)).count();
+        assert!(false_positives < 50);
+    }
+
+    #[test]
+    fn test_neighbor_exclusion() {
+        // Node 0 is connected to the first hundred odd nodes, enough edges to use the bloom filter
+        let mut edges = Vec::new();
+        for n in (1..200).step_by(2) {
+            edges.push((0, n, 1.));
+            edges.push((n, 0, 1.));
+        }
+        edges.push((2, 4, 1.));
+        edges.push((1000, 999, 1.));
+        let graph = CSR::construct_from_edges(edges, false);
+        let train_idxs: Vec<_> = (0..graph.len()).collect();
+
+        let strategy = RandomWalkHardStrategy::new(0, &train_idxs);
+        let nodes = [0usize, 2];
+        let sampler = NeighborExclusionSampler::new(
+            (&strategy).initialize_batch(&nodes, &graph, &FeatureStore::new(graph.len())),
+            &nodes, &graph);
+        assert!(sampler.filters.contains_key(&0));
+        assert!(!sampler.filters.contains_key(&2));
+
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        for anchor in nodes {
+            let mut negatives = Vec::new();
+            sampler.sample_negatives(&graph, anchor, &mut negatives, 20, &mut rng);
+            assert_eq!(negatives.len(), 20);
+            let anchor_edges = graph.get_edges(anchor).0;
+            assert!(negatives.iter().all(|n| *n != anchor && !anchor_edges.contains(n)));
+        }
+
+        // Every candidate is a neighbor, so we keep the count by reusing rejected nodes
+        let strategy = RandomWalkHardStrategy::new(0, &[1, 3]);
+        let sampler = NeighborExclusionSampler::new(
+            (&strategy).initialize_batch(&nodes, &graph, &FeatureStore::new(graph.len())),
+            &nodes, &graph);
+        let mut negatives = Vec::new();
+        sampler.sample_negatives(&graph, 0, &mut negatives, 5, &mut rng);
+        assert_eq!(negatives.len(), 5);
+    }
+}
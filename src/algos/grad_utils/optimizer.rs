@@ -3,6 +3,8 @@
 use rayon::prelude::*;
 use crate::embeddings::EmbeddingStore;
 use crate::distance::Distance;
+use crate::error::GraphLibError;
+use crate::algos::ep::state::OptimizerState;
 use std::collections::{HashMap as CHashMap};
 
 /// Optimizer trait.  We provide it the feature set, the gradient maps, and a few other details
@@ -36,6 +38,54 @@ impl AdamOptimizer {
         let var = EmbeddingStore::new(length, dims, Distance::Cosine);
         AdamOptimizer { beta_1, beta_2, mom, var, eps: 1e-8 }
     }
+
+    /// Snapshot of the moments, for checkpointing
+    pub fn state(&self) -> OptimizerState {
+        let flatten = |es: &EmbeddingStore| {
+            (0..es.len()).flat_map(|idx| es.get_embedding(idx).iter().cloned()).collect()
+        };
+        OptimizerState {
+            beta_1: self.beta_1,
+            beta_2: self.beta_2,
+            dims: self.mom.dims(),
+            mom: flatten(&self.mom),
+            var: flatten(&self.var)
+        }
+    }
+
+    /// Zeroes the moments of a feature, such as after its embedding is re-initialized.  Like
+    /// updates, this is hogwild.
+    pub fn reset(&self, feat_id: usize) {
+        self.mom.get_embedding_mut_hogwild(feat_id).iter_mut().for_each(|m_i| *m_i = 0f32);
+        self.var.get_embedding_mut_hogwild(feat_id).iter_mut().for_each(|v_i| *v_i = 0f32);
+    }
+
+    /// Restores an optimizer from a snapshot.  Fails if the moments don't have the same size or
+    /// aren't a multiple of dims.
+    pub fn from_state(state: &OptimizerState) -> Result<Self, GraphLibError> {
+        if state.dims == 0 || state.mom.len() % state.dims != 0 {
+            return Err("Optimizer moments aren't a multiple of dims!".into())
+        }
+        if state.mom.len() != state.var.len() {
+            return Err(GraphLibError::DimensionMismatch {
+                expected: state.mom.len(),
+                found: state.var.len()
+            })
+        }
+
+        let length = state.mom.len() / state.dims;
+        let restore = |values: &[f32]| {
+            EmbeddingStore::new_with_vec(length, state.dims, Distance::Cosine, values.to_vec())
+                .expect("Sizes were checked")
+        };
+        Ok(AdamOptimizer {
+            beta_1: state.beta_1,
+            beta_2: state.beta_2,
+            mom: restore(&state.mom),
+            var: restore(&state.var),
+            eps: 1e-8
+        })
+    }
 }
 
 impl Optimizer for AdamOptimizer {
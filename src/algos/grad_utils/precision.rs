@@ -0,0 +1,134 @@
+//! Reduced precision storage for per example gradients.  Synchronous batches hold every example's
+//! gradients until the batch is aggregated, which dominates the memory traffic of the batch hot
+//! path.  Storing them as bfloat16 halves that while the feature embeddings and the optimizer,
+//! the master weights, stay f32.  bfloat16 keeps f32's exponent range, so unlike IEEE f16 small
+//! gradients don't underflow and no loss scaling is needed; only the mantissa is truncated to 8
+//! bits, which averages out across the batch.
+use std::collections::{HashMap as CHashMap};
+
+use hashbrown::HashMap;
+
+/// Precision of the buffered per example gradients
+#[derive(Clone,Copy,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Precision {
+    /// Full f32 gradients
+    F32,
+
+    /// bfloat16 gradients, accumulated into f32
+    BF16
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision::F32
+    }
+}
+
+impl Precision {
+    /// Bytes per buffered gradient value
+    pub fn bytes(&self) -> usize {
+        match self {
+            Precision::F32 => 4,
+            Precision::BF16 => 2
+        }
+    }
+}
+
+/// Rounds an f32 to bfloat16, to nearest even.  NaNs stay NaNs.
+pub fn to_bf16(x: f32) -> u16 {
+    let bits = x.to_bits();
+    if x.is_nan() {
+        return ((bits >> 16) | 0x40) as u16
+    }
+    let rounding = 0x7fff + ((bits >> 16) & 1);
+    (bits.wrapping_add(rounding) >> 16) as u16
+}
+
+/// Widens a bfloat16 back to f32, which is exact
+pub fn from_bf16(x: u16) -> f32 {
+    f32::from_bits((x as u32) << 16)
+}
+
+/// A single example's gradients, as buffered until the batch is aggregated
+pub enum ExampleGrads {
+    Full(HashMap<usize, Vec<f32>>),
+    Half(HashMap<usize, Vec<u16>>)
+}
+
+impl ExampleGrads {
+    pub fn new(grads: HashMap<usize, Vec<f32>>, precision: Precision) -> Self {
+        match precision {
+            Precision::F32 => ExampleGrads::Full(grads),
+            Precision::BF16 => ExampleGrads::Half(grads.into_iter()
+                .map(|(feat_id, grad)| (feat_id, grad.into_iter().map(to_bf16).collect()))
+                .collect())
+        }
+    }
+
+    /// Squared L2 norm of the gradients
+    pub fn squared_norm(&self) -> f32 {
+        match self {
+            ExampleGrads::Full(grads) => grads.values()
+                .map(|g| g.iter().map(|gi| gi * gi).sum::<f32>())
+                .sum(),
+            ExampleGrads::Half(grads) => grads.values()
+                .map(|g| g.iter().map(|gi| from_bf16(*gi).powi(2)).sum::<f32>())
+                .sum()
+        }
+    }
+
+    /// Adds the gradients into the f32 batch gradients
+    pub fn accumulate(self, all_grads: &mut CHashMap<usize, Vec<f32>>) {
+        match self {
+            ExampleGrads::Full(grads) => grads.into_iter().for_each(|(feat, grad)| {
+                let e = all_grads.entry(feat).or_insert_with(|| vec![0.; grad.len()]);
+                e.iter_mut().zip(grad.iter()).for_each(|(ei, gi)| *ei += *gi);
+            }),
+            ExampleGrads::Half(grads) => grads.into_iter().for_each(|(feat, grad)| {
+                let e = all_grads.entry(feat).or_insert_with(|| vec![0.; grad.len()]);
+                e.iter_mut().zip(grad.iter()).for_each(|(ei, gi)| *ei += from_bf16(*gi));
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod precision_tests {
+    use super::*;
+
+    #[test]
+    fn test_bf16() {
+        for x in [0f32, 1., -2.5, 2f32.powi(-100), 2f32.powi(100), f32::INFINITY] {
+            assert_eq!(from_bf16(to_bf16(x)), x);
+        }
+        assert!(from_bf16(to_bf16(f32::NAN)).is_nan());
+
+        // 8 bits of precision, rounding to nearest even
+        let x = 1.00390625f32;
+        assert_eq!(from_bf16(to_bf16(x)), 1.);
+        assert_eq!(from_bf16(to_bf16(1.01)), 1.0078125);
+        assert!((from_bf16(to_bf16(0.1)) - 0.1).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_accumulate() {
+        let grads: HashMap<_, _> = vec![(0, vec![0.5, -1.]), (3, vec![2., 0.25])]
+            .into_iter()
+            .collect();
+        let mut full = CHashMap::new();
+        let mut half = CHashMap::new();
+        let example = ExampleGrads::new(grads.clone(), Precision::F32);
+        assert_eq!(example.squared_norm(), 5.3125);
+        example.accumulate(&mut full);
+        ExampleGrads::new(grads.clone(), Precision::F32).accumulate(&mut full);
+
+        let example = ExampleGrads::new(grads.clone(), Precision::BF16);
+        assert_eq!(example.squared_norm(), 5.3125);
+        example.accumulate(&mut half);
+        ExampleGrads::new(grads, Precision::BF16).accumulate(&mut half);
+
+        assert_eq!(full, half);
+        assert_eq!(full[&3], vec![4., 0.5]);
+    }
+}
@@ -1,7 +1,8 @@
 //! Learning rate scheduler.  We use a couple of different variants depending on the method needed.
 
 /// Tracks the different schedulers which have different benefits
-#[derive(Debug)]
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LRScheduler {
     /// Generally useful: uses a warmup period to get good starting gradients in Adam, then slowly
     /// decays over the course of the rest of the passes.
@@ -4,6 +4,7 @@
 //! connectedness of the graph.  Meh.
 use std::cmp::{Eq,PartialEq,Ordering,Reverse};
 use std::collections::BinaryHeap;
+use std::hash::Hash;
 
 use hashbrown::HashSet;
 use rand::prelude::*;
@@ -13,6 +14,8 @@ use float_ord::FloatOrd;
 
 use crate::graph::{Graph as CGraph,NodeID};
 use crate::embeddings::{EmbeddingStore,Entity};
+use crate::error::{GraphLibError,check_dims};
+use crate::algos::ann::Ann as HyperplaneAnn;
 
 /// Defines a distance metric which we can use with heaps.  Lower == better
 
@@ -59,39 +62,56 @@ impl <A: Eq> Eq for DistanceFromEntity<A> {}
 
 pub type NodeDistance = DistanceFromEntity<NodeID>;
 
-/// Struct which tracks the top K nodes according to some distance.  Useful outside of ANN as well.
-pub struct TopK {
-    heap: BinaryHeap<Reverse<NodeDistance>>,
-    k: usize
+/// Struct which tracks the top K items according to some distance.  Useful outside of ANN as well.
+/// Items default to node ids, but any ordered payload works.
+pub struct TopK<A = NodeID> {
+    heap: BinaryHeap<Reverse<DistanceFromEntity<A>>>,
+    k: usize,
+
+    /// Items further than this are never kept
+    threshold: Option<f32>
 }
 
-impl TopK {
+impl <A: Ord> TopK<A> {
     pub fn new(k: usize) -> Self {
         TopK {
             k: k,
-            heap: BinaryHeap::with_capacity(k+1)
+            heap: BinaryHeap::with_capacity(k+1),
+            threshold: None
         }
     }
 
-    pub fn push(&mut self, node_id: NodeID, score: f32) {
-        self.push_nd(Reverse(NodeDistance::new(score, node_id)));
+    /// Drops any item with a distance above the threshold, so range limited queries don't need
+    /// to filter afterwards.
+    pub fn with_threshold(mut self, threshold: f32) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    pub fn push(&mut self, item: A, score: f32) {
+        self.push_nd(Reverse(DistanceFromEntity::new(score, item)));
     }
 
-    fn push_nd(&mut self, nd: Reverse<NodeDistance>) {
+    fn push_nd(&mut self, nd: Reverse<DistanceFromEntity<A>>) {
+        if let Some(threshold) = self.threshold {
+            if nd.0.0.is_nan() || nd.0.0 > threshold {
+                return
+            }
+        }
         self.heap.push(nd);
         if self.heap.len() > self.k {
             self.heap.pop();
         }
     }
 
-    pub fn into_sorted(self) -> Vec<NodeDistance> {
-        let mut results: Vec<NodeDistance> = self.heap.into_iter()
+    pub fn into_sorted(self) -> Vec<DistanceFromEntity<A>> {
+        let mut results: Vec<DistanceFromEntity<A>> = self.heap.into_iter()
             .map(|n| n.0).collect();
         results.sort_by_key(|n| FloatOrd(n.0));
         results
     }
 
-    pub fn extend(&mut self, other: TopK) {
+    pub fn extend(&mut self, other: TopK<A>) {
         other.heap.into_iter().for_each(|nd| {
             self.push_nd(nd);
         });
@@ -100,21 +120,82 @@ impl TopK {
     pub fn len(&self) -> usize {
         self.heap.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+impl <A: Ord + Hash + Clone> TopK<A> {
+
+    /// Merges several top k sets, such as one per shard or tree, into a single sorted list of the
+    /// best unique items, sized to the largest k among them.  Since each set is already sorted,
+    /// this walks their heads and stops as soon as it's full rather than sorting everything.  An
+    /// item in several sets keeps its best distance.
+    pub fn merge_sorted(top_ks: Vec<TopK<A>>) -> Vec<DistanceFromEntity<A>> {
+        let k = top_ks.iter().map(|tk| tk.k).max().unwrap_or(0);
+        let mut lists: Vec<_> = top_ks.into_iter()
+            .map(|tk| tk.into_sorted().into_iter())
+            .collect();
+
+        // DistanceFromEntity is already reversed, so the heap pops the closest head first
+        let mut heads = BinaryHeap::with_capacity(lists.len());
+        lists.iter_mut().enumerate().for_each(|(idx, list)| {
+            if let Some(nd) = list.next() {
+                heads.push((nd, Reverse(idx)));
+            }
+        });
+
+        let mut seen = HashSet::new();
+        let mut results = Vec::with_capacity(k);
+        while results.len() < k {
+            let (nd, Reverse(idx)) = match heads.pop() {
+                Some(head) => head,
+                None => break
+            };
+            if let Some(next) = lists[idx].next() {
+                heads.push((next, Reverse(idx)));
+            }
+            if seen.insert(nd.1.clone()) {
+                results.push(nd);
+            }
+        }
+        results
+    }
 }
 
 /// This Ann hill climbs from random starting nodes within the graph.  if the graph isn't fully
 /// connected, good luck.  Depending on the smoothness of the embeddings amongst neighbors, has a
-/// habit of running into local minimas.  It's fine, just not anything special.
+/// habit of running into local minimas.  It's fine, just not anything special.  Widening the beam
+/// helps with the local minimas.
 #[derive(Debug)]
 pub struct Ann {
     k: usize,
     max_steps: usize,
-    seed: u64
+    seed: u64,
+
+    /// Number of frontier nodes explored each step.  1 is a greedy climb.
+    beam_width: usize,
+
+    /// Probability of abandoning the current climb for a new starting node at each step
+    restart_prob: f32
 }
 
 impl Ann {
     pub fn new(k: usize, max_steps: usize, seed: u64) -> Self {
-        Ann {k, max_steps, seed}
+        Ann {k, max_steps, seed, beam_width: 1, restart_prob: 0.05}
+    }
+
+    /// Sets the number of frontier nodes to expand each step
+    pub fn with_beam_width(mut self, beam_width: usize) -> Self {
+        self.beam_width = beam_width;
+        self
+    }
+
+    /// Sets the probability of restarting the climb at each step
+    pub fn with_restart_prob(mut self, restart_prob: f32) -> Self {
+        self.restart_prob = restart_prob;
+        self
     }
 
     pub fn find<G: CGraph + Send + Sync>(
@@ -122,76 +203,145 @@ impl Ann {
         query: &[f32],
         graph: &G, 
         embeddings: &EmbeddingStore,
-    ) -> Vec<NodeDistance> {
+    ) -> Result<Vec<NodeDistance>, GraphLibError> {
+        self.find_from_seeds(query, graph, embeddings, &[])
+    }
+
+    /// Two stage search: a coarse query against the hyperplane Ann provides the starting nodes
+    /// for the hill climbs, which then refine the results through the graph.  The Ann needs to
+    /// be built on the same embeddings.
+    pub fn find_with_ann<G: CGraph + Send + Sync>(
+        &self, 
+        query: &[f32],
+        graph: &G, 
+        embeddings: &EmbeddingStore,
+        coarse: &HyperplaneAnn,
+        num_seeds: usize
+    ) -> Result<Vec<NodeDistance>, GraphLibError> {
+        let seeds: Vec<_> = coarse.predict(embeddings, query, num_seeds, None)?
+            .into_iter().map(|nd| nd.1).collect();
+        self.find_from_seeds(query, graph, embeddings, &seeds)
+    }
+
+    /// Starts each hill climb from the next provided seed, in order, before falling back to 
+    /// uniformly random starting nodes once they're exhausted.
+    pub fn find_from_seeds<G: CGraph + Send + Sync>(
+        &self, 
+        query: &[f32],
+        graph: &G, 
+        embeddings: &EmbeddingStore,
+        seeds: &[NodeID]
+    ) -> Result<Vec<NodeDistance>, GraphLibError> {
+        if graph.len() == 0 {
+            return Err(GraphLibError::EmptyGraph)
+        }
+        if embeddings.len() < graph.len() {
+            return Err(GraphLibError::DimensionMismatch { 
+                expected: graph.len(), 
+                found: embeddings.len() 
+            })
+        }
+        check_dims(embeddings.dims(), query.len())?;
+        if self.beam_width == 0 {
+            return Err(GraphLibError::InvalidInput("beam_width must be at least 1".into()))
+        }
+        if self.restart_prob.is_nan() || self.restart_prob < 0. || self.restart_prob >= 1. {
+            return Err(GraphLibError::InvalidInput("restart_prob must be in [0, 1)".into()))
+        }
+        if let Some(node_id) = seeds.iter().find(|n| **n >= graph.len()) {
+            return Err(GraphLibError::InvalidInput(
+                format!("Seed node {} is not in the graph", node_id)))
+        }
+
         let mut rng = XorShiftRng::seed_from_u64(self.seed);
-        hill_climb(
+        Ok(self.hill_climb(
             Entity::Embedding(query), 
             graph,
             embeddings,
-            self.k,
-            self.max_steps,
-            &mut rng)
+            seeds,
+            &mut rng))
     }
-    
-}
 
-// This hill climbs.  We start with a node and compute the embeddings for each node.  We greedily
-// explore the edges where the distance is minmized.  We return the best nodes after performing the
-// search `max_steps` times.
-fn hill_climb<'a, G: CGraph, R: Rng>(
-    needle: Entity<'a>, 
-    graph: &G, 
-    es: &EmbeddingStore,
-    k: usize,
-    mut max_steps: usize,
-    rng: &mut R
-) -> Vec<NodeDistance> {
-    let distribution = Uniform::new(0, graph.len());
-
-    let mut heap = BinaryHeap::new();
-    let mut best = TopK::new(k);
-    let mut seen = HashSet::new();
-
-    while max_steps > 0 {
-
-        // Find a starting node, randomly selected
-        heap.clear();
-        let start_node = distribution.sample(rng);
-        seen.insert(start_node.clone());
-        let start_d = es.compute_distance(&needle, &Entity::Node(start_node.clone()));
-        let start = NodeDistance::new(start_d, start_node);
-        heap.push(start.clone());
-
-        loop {
-            // Hardcoded restart rate for the time being
-            if rng.gen::<f32>() < 0.05f32 {
-                break
-            }
+    // This hill climbs.  We start with a node and compute the embeddings for each node.  We
+    // explore the edges of the best `beam_width` frontier nodes, where the distance is minimized.
+    // We return the best nodes after performing the search `max_steps` times.  Climbs start from
+    // the seeds first, then from random nodes.
+    fn hill_climb<'a, G: CGraph, R: Rng>(
+        &self,
+        needle: Entity<'a>, 
+        graph: &G, 
+        es: &EmbeddingStore,
+        seeds: &[NodeID],
+        rng: &mut R
+    ) -> Vec<NodeDistance> {
+        let distribution = Uniform::new(0, graph.len());
+
+        let mut heap = BinaryHeap::new();
+        let mut beam = Vec::with_capacity(self.beam_width);
+        let mut best = TopK::new(self.k);
+        let mut seen = HashSet::new();
+        let mut seeds = seeds.iter();
+        let mut max_steps = self.max_steps;
+
+        while max_steps > 0 {
+
+            // Find a starting node, from the seeds if we have any left, otherwise randomly selected
+            heap.clear();
+            let start_node = match seeds.next() {
+                Some(node_id) => *node_id,
+                None => distribution.sample(rng)
+            };
+            seen.insert(start_node);
+            let start_d = es.compute_distance(&needle, &Entity::Node(start_node));
+            heap.push(NodeDistance::new(start_d, start_node));
+
+            loop {
+                if rng.gen::<f32>() < self.restart_prob {
+                    break
+                }
 
-            let cur_node = heap.pop().expect("Shouldn't be empty!");
-            best.push(cur_node.1, cur_node.0);
-            // Get edges, compute distances between them and needle, add to the heap
-            for edge in graph.get_edges(cur_node.1).0.iter() {
-                if !seen.contains(edge) {
-                    seen.insert(*edge);
-                    let dist = es.compute_distance(&needle, &Entity::Node(*edge));
-                    heap.push(NodeDistance::new(dist, *edge));
+                // Take the best frontier nodes
+                beam.clear();
+                while beam.len() < self.beam_width {
+                    match heap.pop() {
+                        Some(cur_node) => beam.push(cur_node),
+                        None => break
+                    }
+                }
+
+                // Get edges, compute distances between them and needle, add to the heap
+                for cur_node in beam.iter() {
+                    if max_steps == 0 {
+                        break
+                    }
+
+                    best.push(cur_node.1, cur_node.0);
+                    for edge in graph.get_edges(cur_node.1).0.iter() {
+                        if !seen.contains(edge) {
+                            seen.insert(*edge);
+                            let dist = es.compute_distance(&needle, &Entity::Node(*edge));
+                            heap.push(NodeDistance::new(dist, *edge));
+                        }
+                    }
+                    max_steps -= 1;
                 }
-            }
 
-            max_steps -= 1;
-            if max_steps == 0 || heap.len() == 0 {
-                break
+                if max_steps == 0 || heap.len() == 0 {
+                    break
+                }
             }
-        }
 
+        }
+        best.into_sorted()
     }
-    best.into_sorted()
+    
 }
 
 #[cfg(test)]
 mod ann_tests {
     use super::*;
+    use crate::graph::CSR;
+    use crate::distance::Distance;
 
     fn build_star_edges() -> Vec<(usize, usize, f32)> {
         let mut edges = Vec::new();
@@ -221,4 +371,88 @@ mod ann_tests {
         assert_eq!(results[1], NodeDistance(0.1, 1));
         assert_eq!(results[2], NodeDistance(0.15, 4));
     }
+
+    #[test]
+    fn test_top_k_threshold() {
+        let mut top_k = TopK::new(3).with_threshold(0.12);
+        top_k.push("a", 0.1);
+        top_k.push("b", 0.2);
+        top_k.push("c", f32::NAN);
+        top_k.push("d", 0.01);
+        assert_eq!(top_k.len(), 2);
+
+        let results = top_k.into_sorted();
+        assert_eq!(results, vec![DistanceFromEntity(0.01, "d"), DistanceFromEntity(0.1, "a")]);
+    }
+
+    #[test]
+    fn test_merge_sorted() {
+        let mut tk1 = TopK::new(3);
+        [(1, 0.1), (2, 0.2), (3, 0.3)].iter().for_each(|(n, s)| tk1.push(*n, *s));
+        let mut tk2 = TopK::new(3);
+        [(2, 0.2), (4, 0.05), (5, 0.25)].iter().for_each(|(n, s)| tk2.push(*n, *s));
+
+        let results = TopK::merge_sorted(vec![tk1, tk2, TopK::new(1)]);
+        let expected = vec![NodeDistance(0.05, 4), NodeDistance(0.1, 1), NodeDistance(0.2, 2)];
+        assert_eq!(results, expected);
+
+        assert!(TopK::<NodeID>::merge_sorted(Vec::new()).is_empty());
+    }
+
+    // Line graph where each node's embedding is its position, so the only way to reach the end
+    // of the line within a small budget is to start near it.
+    fn build_line() -> (CSR, EmbeddingStore) {
+        let max = 200;
+        let mut edges = Vec::new();
+        for ni in 0..(max - 1) {
+            edges.push((ni, ni + 1, 1f32));
+            edges.push((ni + 1, ni, 1f32));
+        }
+        let graph = CSR::construct_from_edges(edges, false);
+        let mut es = EmbeddingStore::new(max, 1, Distance::Euclidean);
+        for node_id in 0..max {
+            es.set_embedding(node_id, &[node_id as f32]);
+        }
+        (graph, es)
+    }
+
+    #[test]
+    fn test_seeds() {
+        let (graph, es) = build_line();
+        let ann = Ann::new(3, 6, 2023);
+        let results = ann.find_from_seeds(&[195.], &graph, &es, &[194, 196, 194, 196]).unwrap();
+        assert_eq!(results[0], NodeDistance(0., 195));
+
+        let err = ann.find_from_seeds(&[195.], &graph, &es, &[200]);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_beam_search() {
+        let (graph, es) = build_line();
+
+        // Without restarts, the beam expands both ends of the line each step
+        let ann = Ann::new(1, 40, 2023)
+            .with_beam_width(3)
+            .with_restart_prob(0.);
+        let results = ann.find_from_seeds(&[195.], &graph, &es, &[180]).unwrap();
+        assert_eq!(results, vec![NodeDistance(0., 195)]);
+
+        let ann = Ann::new(1, 40, 2023).with_beam_width(0);
+        assert!(ann.find(&[195.], &graph, &es).is_err());
+
+        let ann = Ann::new(1, 40, 2023).with_restart_prob(1.);
+        assert!(ann.find(&[195.], &graph, &es).is_err());
+    }
+
+    #[test]
+    fn test_find_with_ann() {
+        let (graph, es) = build_line();
+        let mut coarse = HyperplaneAnn::new();
+        coarse.fit(&es, 5, 10, None, None, None, 2023).unwrap();
+
+        let ann = Ann::new(3, 10, 2023);
+        let results = ann.find_with_ann(&[150.], &graph, &es, &coarse, 3).unwrap();
+        assert_eq!(results[0], NodeDistance(0., 150));
+    }
 }
@@ -2,7 +2,7 @@
 use rayon::prelude::*;
 
 use crate::algos::rwr::{RWR,ppr_estimate};
-use crate::algos::utils::{Sample,FeatureHasher};
+use crate::algos::utils::{Sample,FeatureHasher,RngKind,EdgeAliasTable};
 use crate::graph::{Graph as CGraph, CDFGraph};
 use crate::embeddings::EmbeddingStore;
 use crate::distance::Distance;
@@ -39,6 +39,10 @@ impl InstantEmbeddings {
         let es = EmbeddingStore::new(n, self.dims, Distance::Cosine);
         let fh = FeatureHasher::new(self.dims);
         let pb = CLProgressBar::new(n as u64, true);
+        let tables = match self.estimator {
+            Estimator::RandomWalk { .. } => Some(EdgeAliasTable::new(graph)),
+            Estimator::SparsePPR { .. } => None
+        };
         (0..graph.len()).into_par_iter().for_each(|node_id| {
             let ppr = match self.estimator {
                 Estimator::RandomWalk {steps, walks, beta, seed} => {
@@ -47,10 +51,12 @@ impl InstantEmbeddings {
                         walks: walks,
                         beta: beta,
                         single_threaded: false,
-                        seed: seed + node_id as u64
+                        seed: seed + node_id as u64,
+                        rng_kind: RngKind::XorShift,
+                        deterministic: false
                     };
 
-                    rwr.sample_bfs(graph, node_id)
+                    rwr.sample_bfs_with(graph, tables.as_ref().expect("Built for walks!"), node_id)
                 },
                 Estimator::SparsePPR { p, eps } => ppr_estimate(graph, node_id, p, eps)
             };
@@ -0,0 +1,149 @@
+//! Inverted file index.  A coarse quantizer, learned with k-means, assigns every embedding to its
+//! nearest centroid, and each centroid keeps a posting list of its embeddings.  Queries only scan
+//! the posting lists of the `nprobe` nearest centroids, trading recall for speed, which scales
+//! better than the hyperplane forest on very large embedding sets.
+use crate::graph::NodeID;
+use crate::embeddings::EmbeddingStore;
+use crate::algos::graph_ann::NodeDistance;
+use crate::algos::kmeans::{self,KMeans};
+use crate::algos::retrieval::ClusterRetrieval;
+use crate::error::{GraphLibError,check_dims};
+
+/// Inverted file index over an EmbeddingStore.  The posting lists and two stage search are
+/// provided by ClusterRetrieval; the index adds learning the coarse quantizer and validating
+/// queries.  It only stores ids, so queries need the same EmbeddingStore it was built on.
+pub struct Ivf {
+    /// Coarse quantizer and the embeddings assigned to each centroid
+    index: ClusterRetrieval,
+
+    /// Number of embeddings indexed
+    num_nodes: usize
+}
+
+impl Ivf {
+
+    /// Learns the coarse quantizer with k-means and builds the posting lists.  The balanced
+    /// variant keeps posting lists, and therefore query latency, even.
+    pub fn fit(es: &EmbeddingStore, kmeans: &KMeans) -> Result<Self, GraphLibError> {
+        let (assignments, centroids) = kmeans.fit(es)?;
+        Ivf::from_assignments(es, centroids, &assignments)
+    }
+
+    /// Builds the posting lists from an existing coarse quantizer, such as one shared with other
+    /// indexes.
+    pub fn from_centroids(es: &EmbeddingStore, centroids: EmbeddingStore) -> Result<Self, GraphLibError> {
+        if centroids.len() == 0 {
+            return Err(GraphLibError::EmptyIndex)
+        }
+        check_dims(es.dims(), centroids.dims())?;
+        let assignments = kmeans::assign(es, &centroids);
+        Ivf::from_assignments(es, centroids, &assignments)
+    }
+
+    fn from_assignments(
+        es: &EmbeddingStore,
+        centroids: EmbeddingStore,
+        assignments: &[usize]
+    ) -> Result<Self, GraphLibError> {
+        let index = ClusterRetrieval::new(centroids, assignments)?;
+        Ok(Ivf { index, num_nodes: es.len() })
+    }
+
+    /// Number of posting lists
+    pub fn num_lists(&self) -> usize {
+        self.index.num_clusters()
+    }
+
+    /// Embeddings within a posting list, or None if it doesn't exist
+    pub fn posting_list(&self, list: usize) -> Option<&[NodeID]> {
+        if list < self.num_lists() {
+            Some(self.index.cluster_members(list))
+        } else {
+            None
+        }
+    }
+
+    /// Centroids of the coarse quantizer
+    pub fn centroids(&self) -> &EmbeddingStore {
+        self.index.centroids()
+    }
+
+    /// Returns the approximate k nearest neighbors to the query by scanning the posting lists of
+    /// the `nprobe` nearest centroids.  Distances are exact.
+    pub fn predict(
+        &self,
+        es: &EmbeddingStore,
+        emb: &[f32],
+        k: usize,
+        nprobe: usize
+    ) -> Result<Vec<NodeDistance>, GraphLibError> {
+        if self.num_lists() == 0 {
+            return Err(GraphLibError::EmptyIndex)
+        }
+        if es.len() != self.num_nodes {
+            return Err(GraphLibError::DimensionMismatch { expected: self.num_nodes, found: es.len() })
+        }
+        check_dims(es.dims(), emb.len())?;
+        Ok(self.index.search(es, emb, k, nprobe, |_| true))
+    }
+}
+
+#[cfg(test)]
+mod ivf_tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_xorshift::XorShiftRng;
+    use crate::distance::Distance;
+    use crate::embeddings::Entity;
+
+    fn build_store() -> EmbeddingStore {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut es = EmbeddingStore::new(1000, 8, Distance::Euclidean);
+        for node_id in 0..es.len() {
+            let emb: Vec<f32> = (0..8).map(|_| rng.gen::<f32>()).collect();
+            es.set_embedding(node_id, &emb);
+        }
+        es
+    }
+
+    #[test]
+    fn test_ivf() {
+        let es = build_store();
+        let kmeans = KMeans { k: 10, batch_size: 100, iterations: 20, max_cluster_size: Some(100), seed: 2023 };
+        let ivf = Ivf::fit(&es, &kmeans).unwrap();
+        assert_eq!(ivf.num_lists(), 10);
+        assert!(ivf.posting_list(10).is_none());
+
+        let mut members: Vec<_> = (0..10).flat_map(|l| ivf.posting_list(l).unwrap().to_vec()).collect();
+        members.sort();
+        assert_eq!(members, (0..es.len()).collect::<Vec<_>>());
+
+        // Probing every list is exact
+        let query = es.get_embedding(10);
+        let results = ivf.predict(&es, query, 5, 10).unwrap();
+        let exact = es.nearest_neighbor(&Entity::Node(10), 5, |_| true);
+        assert_eq!(results, exact);
+
+        assert!(ivf.predict(&es, &[0.; 3], 5, 2).is_err());
+    }
+
+    #[test]
+    fn test_from_centroids() {
+        let es = build_store();
+        let kmeans = KMeans { k: 5, batch_size: 100, iterations: 10, max_cluster_size: None, seed: 2023 };
+        let ivf = Ivf::fit(&es, &kmeans).unwrap();
+
+        let mut centroids = EmbeddingStore::new(5, 8, Distance::Euclidean);
+        for c_id in 0..5 {
+            centroids.set_embedding(c_id, ivf.centroids().get_embedding(c_id));
+        }
+        let shared = Ivf::from_centroids(&es, centroids).unwrap();
+        for list in 0..5 {
+            assert_eq!(shared.posting_list(list), ivf.posting_list(list));
+        }
+
+        // Without balancing, every embedding is in its nearest list, so one probe finds itself
+        let results = shared.predict(&es, es.get_embedding(10), 5, 1).unwrap();
+        assert_eq!(results[0], NodeDistance(0., 10));
+    }
+}
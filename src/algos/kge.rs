@@ -0,0 +1,401 @@
+//! Knowledge graph embeddings over typed edges.  Every edge is a (head, relation, tail) triple,
+//! with the edge type as the relation, and we learn an embedding per entity and per relation such
+//! that true triples score better than corrupted ones, where the head or tail is swapped for a
+//! random node of the same type.  Unlike EP's homogeneous losses, this lets relations such as
+//! seller_of and in_category pull nodes in different directions.
+//!
+//! Entity and relation embeddings are stacked into a single EmbeddingStore, as in `skipgram`, so
+//! they share the Adam optimizer and learning rate schedule used by EmbeddingPropagation.  Relation
+//! ids are offset by the number of entities.
+use std::collections::{HashMap as CHashMap};
+use std::fmt::Write;
+
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+
+use crate::graph::{Graph,NodeID};
+use crate::graph::typed::{TypedGraph,NodeType,EdgeType};
+use crate::embeddings::{EmbeddingStore,randomize_embedding_store};
+use crate::distance::Distance;
+use crate::progress::CLProgressBar;
+use crate::error::GraphLibError;
+use crate::algos::grad_utils::optimizer::{Optimizer,AdamOptimizer};
+use crate::algos::grad_utils::scheduler::LRScheduler;
+
+/// Scoring function for a triple
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum KGEModel {
+    /// Relations translate heads onto tails: the distance is ||h + r - t||.  Entities are kept
+    /// within the unit ball.  Handles one-to-one and asymmetric relations.
+    TransE,
+
+    /// Relations are diagonal bilinear maps: the distance is -sum(h * r * t).  Only models
+    /// symmetric relations, but tends to do better on many-to-many ones.
+    DistMult
+}
+
+impl KGEModel {
+
+    /// Distance of a triple; lower is more plausible.
+    pub fn distance(&self, head: &[f32], relation: &[f32], tail: &[f32]) -> f32 {
+        let triple = head.iter().zip(relation.iter()).zip(tail.iter());
+        match self {
+            KGEModel::TransE => triple
+                .map(|((hi, ri), ti)| (hi + ri - ti).powf(2.))
+                .sum::<f32>()
+                .sqrt(),
+            KGEModel::DistMult => -triple
+                .map(|((hi, ri), ti)| hi * ri * ti)
+                .sum::<f32>()
+        }
+    }
+
+    /// Distance used by the returned entity embeddings, so nearest neighbor queries match how the
+    /// model compares entities.
+    fn entity_distance(&self) -> Distance {
+        match self {
+            KGEModel::TransE => Distance::Euclidean,
+            KGEModel::DistMult => Distance::Dot
+        }
+    }
+}
+
+/// Knowledge graph embedding trainer configuration
+#[derive(Clone,Copy,Debug)]
+pub struct KGE {
+    /// Scoring function
+    pub model: KGEModel,
+
+    /// Dimensions of the embeddings
+    pub dims: usize,
+
+    /// Number of corrupted triples per edge
+    pub negatives: usize,
+
+    /// Margin between the distances of true and corrupted triples
+    pub margin: f32,
+
+    /// Learning rate
+    pub alpha: f32,
+
+    /// Number of edges per update
+    pub batch_size: usize,
+
+    /// Number of passes over the edges
+    pub passes: usize,
+
+    /// Random seed
+    pub seed: u64,
+
+    /// Whether to show a pretty indicator
+    pub indicator: bool
+}
+
+impl KGE {
+
+    /// Learns entity and relation embeddings from every edge in the graph; edge weights are
+    /// ignored.  Returns the entity embeddings, one per node, and the relation embeddings, one per
+    /// edge type up to the largest seen.
+    pub fn learn<G: Graph + Send + Sync>(
+        &self,
+        graph: &TypedGraph<G>
+    ) -> Result<(EmbeddingStore, EmbeddingStore), GraphLibError> {
+        if self.dims == 0 || self.negatives == 0 {
+            return Err("dims and negatives must be positive!".into())
+        }
+        if self.margin.is_nan() || self.margin <= 0. {
+            return Err("margin must be positive!".into())
+        }
+
+        let triples = collect_triples(graph);
+        if triples.is_empty() {
+            return Err(GraphLibError::EmptyGraph)
+        }
+
+        let num_entities = graph.len();
+        let num_relations = triples.iter()
+            .map(|(_, r, _)| *r as usize)
+            .max()
+            .expect("Triples aren't empty") + 1;
+        let nodes_by_type = group_by_type(graph.node_types());
+
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        let mut embeddings = EmbeddingStore::new(num_entities + num_relations, self.dims, Distance::Euclidean);
+        randomize_embedding_store(&mut embeddings, &mut rng);
+
+        let optimizer = AdamOptimizer::new(0.9, 0.999, self.dims, embeddings.len());
+
+        let batch_size = self.batch_size.max(1);
+        let steps_per_pass = (triples.len() as f32 / batch_size as f32).ceil() as usize;
+        let total_steps = steps_per_pass * self.passes;
+        let lr_scheduler = LRScheduler::cos_decay(self.alpha / 100f32, self.alpha,
+                                                  total_steps / 5, total_steps);
+
+        let pb = CLProgressBar::new(total_steps as u64, self.indicator);
+        let mut triple_idxs: Vec<_> = (0..triples.len()).collect();
+        let mut step = 1;
+        for pass in 1..(self.passes + 1) {
+            triple_idxs.shuffle(&mut rng);
+            let mut error = 0f32;
+            for (i, batch) in triple_idxs.chunks(batch_size).enumerate() {
+                let grads: Vec<_> = batch.par_iter().map(|triple_idx| {
+                    let seed = self.seed + (pass * triples.len() + triple_idx) as u64;
+                    let mut rng = XorShiftRng::seed_from_u64(seed);
+                    self.compute_triple_gradients(&embeddings, graph, num_entities, triples[*triple_idx],
+                                                  &nodes_by_type, &mut rng)
+                }).collect();
+
+                // Aggregate gradients for shared entities and relations
+                let mut all_grads = CHashMap::new();
+                let mut cnt = 0usize;
+                for (err, n, grad_set) in grads.into_iter() {
+                    error += err;
+                    cnt += n;
+                    for (id, grad) in grad_set.into_iter() {
+                        add_grad(&mut all_grads, id, &grad);
+                    }
+                }
+
+                if cnt > 0 {
+                    all_grads.values_mut().for_each(|g| g.iter_mut().for_each(|gi| *gi /= cnt as f32));
+                    let updated: Vec<_> = all_grads.keys().cloned().collect();
+                    let alpha = lr_scheduler.compute(step);
+                    optimizer.update(&embeddings, all_grads, alpha, pass as f32);
+
+                    // TransE can trivially satisfy the margin by pushing entities apart, so we
+                    // project them back onto the unit ball.
+                    if self.model == KGEModel::TransE {
+                        updated.into_iter().filter(|id| *id < num_entities).for_each(|id| {
+                            let e = embeddings.get_embedding_mut(id);
+                            let norm = e.iter().map(|ei| ei.powf(2.)).sum::<f32>().sqrt();
+                            if norm > 1. {
+                                e.iter_mut().for_each(|ei| *ei /= norm);
+                            }
+                        });
+                    }
+                }
+
+                step += 1;
+                pb.inc(1);
+                if i % 100 == 0 {
+                    pb.update_message(|msg| {
+                        msg.clear();
+                        write!(msg, "Pass {}/{}, Loss: {:.5}", pass, self.passes, error / (i + 1) as f32)
+                            .expect("Error writing out indicator message!");
+                    });
+                }
+            }
+        }
+        pb.finish();
+
+        let mut entities = EmbeddingStore::new(num_entities, self.dims, self.model.entity_distance());
+        for node_id in 0..num_entities {
+            entities.set_embedding(node_id, embeddings.get_embedding(node_id));
+        }
+        let mut relations = EmbeddingStore::new(num_relations, self.dims, Distance::Euclidean);
+        for rel_id in 0..num_relations {
+            relations.set_embedding(rel_id, embeddings.get_embedding(num_entities + rel_id));
+        }
+        Ok((entities, relations))
+    }
+
+    /// Computes the margin loss, number of (true, corrupted) pairs, and gradients for a single
+    /// triple.  Corruptions replace either the head or the tail with a node of the same type.
+    fn compute_triple_gradients<G: Graph>(
+        &self,
+        embeddings: &EmbeddingStore,
+        graph: &TypedGraph<G>,
+        num_entities: usize,
+        (head, relation, tail): (NodeID, EdgeType, NodeID),
+        nodes_by_type: &[Vec<NodeID>],
+        rng: &mut impl Rng
+    ) -> (f32, usize, CHashMap<usize, Vec<f32>>) {
+        let mut grads: CHashMap<usize, Vec<f32>> = CHashMap::new();
+        let mut error = 0f32;
+        let mut pairs = 0usize;
+
+        let rel_id = num_entities + relation as usize;
+        let positive = (head, rel_id, tail);
+        let d_pos = self.triple_distance(embeddings, positive);
+        for _ in 0..self.negatives {
+            let corrupt_head = rng.gen::<bool>();
+            let original = if corrupt_head { head } else { tail };
+            let candidates = &nodes_by_type[graph.node_type(original) as usize];
+            let replacement = candidates[rng.gen_range(0, candidates.len())];
+            if replacement == original { continue }
+
+            let negative = if corrupt_head {
+                (replacement, rel_id, tail)
+            } else {
+                (head, rel_id, replacement)
+            };
+
+            pairs += 1;
+            let loss = self.margin + d_pos - self.triple_distance(embeddings, negative);
+            if loss > 0. {
+                error += loss;
+                self.add_triple_grads(embeddings, positive, 1., &mut grads);
+                self.add_triple_grads(embeddings, negative, -1., &mut grads);
+            }
+        }
+
+        (error, pairs, grads)
+    }
+
+    fn triple_distance(&self, embeddings: &EmbeddingStore, (h, r, t): (usize, usize, usize)) -> f32 {
+        self.model.distance(embeddings.get_embedding(h), embeddings.get_embedding(r),
+                            embeddings.get_embedding(t))
+    }
+
+    /// Adds the gradient of the triple's distance, scaled by sign, to the gradient map.
+    fn add_triple_grads(
+        &self,
+        embeddings: &EmbeddingStore,
+        (h, r, t): (usize, usize, usize),
+        sign: f32,
+        grads: &mut CHashMap<usize, Vec<f32>>
+    ) {
+        let (v_h, v_r, v_t) = (embeddings.get_embedding(h), embeddings.get_embedding(r),
+                               embeddings.get_embedding(t));
+        match self.model {
+            KGEModel::TransE => {
+                let diff: Vec<_> = v_h.iter().zip(v_r.iter()).zip(v_t.iter())
+                    .map(|((hi, ri), ti)| hi + ri - ti)
+                    .collect();
+                let norm = diff.iter().map(|di| di.powf(2.)).sum::<f32>().sqrt();
+
+                // Norm isn't differentiable at zero, where it's already minimal anyways
+                if norm == 0. { return }
+
+                let g: Vec<_> = diff.iter().map(|di| sign * di / norm).collect();
+                add_grad(grads, h, &g);
+                add_grad(grads, r, &g);
+                let g: Vec<_> = g.iter().map(|gi| -gi).collect();
+                add_grad(grads, t, &g);
+            },
+            KGEModel::DistMult => {
+                let grad = |a: &[f32], b: &[f32]| -> Vec<f32> {
+                    a.iter().zip(b.iter()).map(|(ai, bi)| -sign * ai * bi).collect()
+                };
+                add_grad(grads, h, &grad(v_r, v_t));
+                add_grad(grads, r, &grad(v_h, v_t));
+                add_grad(grads, t, &grad(v_h, v_r));
+            }
+        }
+    }
+}
+
+fn add_grad(grads: &mut CHashMap<usize, Vec<f32>>, id: usize, grad: &[f32]) {
+    let e = grads.entry(id).or_insert_with(|| vec![0.; grad.len()]);
+    e.iter_mut().zip(grad.iter()).for_each(|(ei, gi)| *ei += *gi);
+}
+
+/// Every edge as a (head, relation, tail) triple
+fn collect_triples<G: Graph>(graph: &TypedGraph<G>) -> Vec<(NodeID, EdgeType, NodeID)> {
+    (0..graph.len()).flat_map(|node_id| {
+        let (edges, _) = graph.get_edges(node_id);
+        edges.iter().zip(graph.get_edge_types(node_id).iter())
+            .map(move |(t_n, et)| (node_id, *et, *t_n))
+    }).collect()
+}
+
+/// Nodes of each type, indexed by type
+fn group_by_type(node_types: &[NodeType]) -> Vec<Vec<NodeID>> {
+    let num_types = node_types.iter().max().map(|nt| *nt as usize + 1).unwrap_or(0);
+    let mut groups = vec![Vec::new(); num_types];
+    node_types.iter().enumerate().for_each(|(node_id, nt)| groups[*nt as usize].push(node_id));
+    groups
+}
+
+#[cfg(test)]
+mod kge_tests {
+    use super::*;
+    use crate::graph::CSR;
+
+    // Four sellers (type 0) with two gigs (type 1) each, and every gig in one of two categories
+    // (type 2).  Sellers point to their gigs with relation 0 and gigs point to their category with
+    // relation 1.
+    fn build_graph() -> TypedGraph<CSR> {
+        let mut edges = Vec::new();
+        for seller in 0..4 {
+            for gig in [4 + 2 * seller, 5 + 2 * seller] {
+                edges.push((seller, gig, 1f32));
+                edges.push((gig, 12 + seller % 2, 1f32));
+            }
+        }
+        let graph = CSR::construct_from_edges(edges, false);
+
+        let node_types: Vec<NodeType> = (0..14)
+            .map(|node_id| if node_id < 4 { 0 } else if node_id < 12 { 1 } else { 2 })
+            .collect();
+        let edge_types = (0..graph.len())
+            .flat_map(|node_id| vec![node_types[node_id]; graph.degree(node_id)])
+            .collect();
+        TypedGraph::new(graph, node_types, edge_types).unwrap()
+    }
+
+    fn build_kge(model: KGEModel) -> KGE {
+        KGE {
+            model,
+            dims: 8,
+            negatives: 4,
+            margin: 1.,
+            alpha: 5e-2,
+            batch_size: 4,
+            passes: 100,
+            seed: 2023,
+            indicator: false
+        }
+    }
+
+    // True triples should, on average, be closer than every corrupted tail of the right type
+    fn check_ranking(kge: &KGE) {
+        let graph = build_graph();
+        let (entities, relations) = kge.learn(&graph).unwrap();
+        assert_eq!(entities.len(), 14);
+        assert_eq!(relations.len(), 2);
+
+        let triples = collect_triples(&graph);
+        let nodes_by_type = group_by_type(graph.node_types());
+        let d = |h: NodeID, r: EdgeType, t: NodeID| {
+            kge.model.distance(entities.get_embedding(h), relations.get_embedding(r as usize),
+                               entities.get_embedding(t))
+        };
+
+        let (mut pos, mut neg, mut num_neg) = (0f32, 0f32, 0usize);
+        for (h, r, t) in triples.iter() {
+            pos += d(*h, *r, *t);
+            for c in nodes_by_type[graph.node_type(*t) as usize].iter() {
+                if !triples.contains(&(*h, *r, *c)) {
+                    neg += d(*h, *r, *c);
+                    num_neg += 1;
+                }
+            }
+        }
+        let (pos, neg) = (pos / triples.len() as f32, neg / num_neg as f32);
+        assert!(pos < neg, "{} >= {}", pos, neg);
+    }
+
+    #[test]
+    fn test_transe() {
+        check_ranking(&build_kge(KGEModel::TransE));
+    }
+
+    #[test]
+    fn test_distmult() {
+        check_ranking(&build_kge(KGEModel::DistMult));
+    }
+
+    #[test]
+    fn test_invalid() {
+        let graph = build_graph();
+        let kge = build_kge(KGEModel::TransE);
+        assert!(KGE { negatives: 0, ..kge }.learn(&graph).is_err());
+        assert!(KGE { margin: 0., ..kge }.learn(&graph).is_err());
+
+        let empty = CSR::construct_from_edges(Vec::new(), false);
+        let empty = TypedGraph::from_node_types(empty, vec![0]).unwrap();
+        assert!(matches!(kge.learn(&empty), Err(GraphLibError::EmptyGraph)));
+    }
+}
@@ -0,0 +1,218 @@
+//! Mini-batch k-means over an EmbeddingStore.  Centroids are initialized with k-means++ over a
+//! sample of the embeddings.  Each iteration then assigns a random batch of embeddings to their
+//! nearest centroids and nudges those centroids toward them with a per-centroid learning rate,
+//! which decays as the centroid absorbs more embeddings.  Useful for coarse quantization, sharding
+//! embeddings, and analysis.
+//!
+//! The balanced variant caps cluster sizes during the final assignment: embeddings with the most
+//! to lose from not getting their nearest centroid are assigned first, and the rest fall back to
+//! their nearest centroid with room left.
+use float_ord::FloatOrd;
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+
+use crate::graph::NodeID;
+use crate::embeddings::EmbeddingStore;
+use crate::error::GraphLibError;
+
+/// k-means++ initialization looks at this many sampled embeddings per cluster
+const INIT_SAMPLES_PER_CLUSTER: usize = 64;
+
+/// Mini-batch k-means configuration
+#[derive(Clone,Copy,Debug)]
+pub struct KMeans {
+    /// Number of clusters
+    pub k: usize,
+
+    /// Number of embeddings sampled per iteration
+    pub batch_size: usize,
+
+    /// Number of mini-batch iterations
+    pub iterations: usize,
+
+    /// If provided, no cluster is assigned more than this many embeddings
+    pub max_cluster_size: Option<usize>,
+
+    /// Random seed
+    pub seed: u64
+}
+
+impl KMeans {
+
+    /// Clusters the embeddings, returning the cluster of each embedding and the centroids.  The
+    /// centroids use the same distance as the embeddings.
+    pub fn fit(&self, es: &EmbeddingStore) -> Result<(Vec<usize>, EmbeddingStore), GraphLibError> {
+        if self.k == 0 || self.batch_size == 0 {
+            return Err("k and batch_size must be positive!".into())
+        }
+        if es.len() < self.k {
+            return Err(GraphLibError::DimensionMismatch { expected: self.k, found: es.len() })
+        }
+        if let Some(cap) = self.max_cluster_size {
+            if cap * self.k < es.len() {
+                return Err("max_cluster_size is too small to fit every embedding!".into())
+            }
+        }
+
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        let mut centroids = self.init_centroids(es, &mut rng);
+
+        let mut counts = vec![0usize; self.k];
+        for _ in 0..self.iterations {
+            let batch: Vec<_> = (0..self.batch_size)
+                .map(|_| rng.gen_range(0, es.len()))
+                .collect();
+
+            let assignments: Vec<_> = batch.par_iter()
+                .map(|node_id| nearest_centroid(&centroids, es.get_embedding(*node_id)))
+                .collect();
+
+            for (node_id, c_id) in batch.iter().zip(assignments.into_iter()) {
+                counts[c_id] += 1;
+                let lr = 1. / counts[c_id] as f32;
+                let emb = es.get_embedding(*node_id);
+                centroids.get_embedding_mut(c_id).iter_mut().zip(emb.iter()).for_each(|(ci, ei)| {
+                    *ci += lr * (*ei - *ci);
+                });
+            }
+        }
+
+        let assignments = match self.max_cluster_size {
+            Some(cap) => balanced_assign(es, &centroids, cap),
+            None => assign(es, &centroids)
+        };
+        Ok((assignments, centroids))
+    }
+
+    // k-means++: each centroid is picked with probability proportional to its squared distance
+    // from the nearest centroid picked so far.
+    fn init_centroids(&self, es: &EmbeddingStore, rng: &mut impl Rng) -> EmbeddingStore {
+        let mut node_ids: Vec<NodeID> = (0..es.len()).collect();
+        node_ids.shuffle(rng);
+        node_ids.truncate((self.k * INIT_SAMPLES_PER_CLUSTER).max(self.k));
+
+        let distance = es.distance();
+        let mut centroids = EmbeddingStore::new(self.k, es.dims(), distance);
+        centroids.set_embedding(0, es.get_embedding(node_ids[0]));
+        let mut min_d = vec![std::f32::INFINITY; node_ids.len()];
+        for c_id in 1..self.k {
+            let last = centroids.get_embedding(c_id - 1);
+            min_d.par_iter_mut().zip(node_ids.par_iter()).for_each(|(d, node_id)| {
+                let nd = distance.compute(es.get_embedding(*node_id), last).max(0.);
+                *d = d.min(nd * nd);
+            });
+
+            let total: f32 = min_d.iter().filter(|d| d.is_finite()).sum();
+            let idx = if total > 0. {
+                let mut p = rng.gen::<f32>() * total;
+                min_d.iter().position(|d| {
+                    if d.is_finite() { p -= *d; }
+                    p <= 0.
+                }).unwrap_or(node_ids.len() - 1)
+            } else {
+                // Everything sits on top of a centroid already
+                c_id
+            };
+            centroids.set_embedding(c_id, es.get_embedding(node_ids[idx]));
+        }
+        centroids
+    }
+}
+
+/// Assigns each embedding to its nearest centroid
+pub fn assign(es: &EmbeddingStore, centroids: &EmbeddingStore) -> Vec<usize> {
+    (0..es.len()).into_par_iter()
+        .map(|node_id| nearest_centroid(centroids, es.get_embedding(node_id)))
+        .collect()
+}
+
+fn nearest_centroid(centroids: &EmbeddingStore, emb: &[f32]) -> usize {
+    let distance = centroids.distance();
+    (0..centroids.len())
+        .min_by_key(|c_id| FloatOrd(distance.compute(emb, centroids.get_embedding(*c_id))))
+        .expect("Should always have centroids!")
+}
+
+fn balanced_assign(es: &EmbeddingStore, centroids: &EmbeddingStore, cap: usize) -> Vec<usize> {
+    let distance = centroids.distance();
+    let scores = |node_id: NodeID| -> Vec<(usize, f32)> {
+        let emb = es.get_embedding(node_id);
+        let mut scores: Vec<_> = (0..centroids.len())
+            .map(|c_id| (c_id, distance.compute(emb, centroids.get_embedding(c_id))))
+            .collect();
+        scores.sort_by_key(|(_, d)| FloatOrd(*d));
+        scores
+    };
+
+    // Regret is how much worse off an embedding is with its second choice
+    let mut order: Vec<_> = (0..es.len()).into_par_iter().map(|node_id| {
+        let scores = scores(node_id);
+        let regret = scores.get(1).map(|s| s.1 - scores[0].1).unwrap_or(0.);
+        (node_id, scores[0].0, regret)
+    }).collect();
+    order.par_sort_by_key(|(node_id, _, regret)| (FloatOrd(-*regret), *node_id));
+
+    let mut sizes = vec![0usize; centroids.len()];
+    let mut assignments = vec![0; es.len()];
+    for (node_id, best, _) in order {
+        let c_id = if sizes[best] < cap {
+            best
+        } else {
+            scores(node_id).into_iter()
+                .map(|(c_id, _)| c_id)
+                .find(|c_id| sizes[*c_id] < cap)
+                .expect("Capacity was validated to fit every embedding")
+        };
+        sizes[c_id] += 1;
+        assignments[node_id] = c_id;
+    }
+    assignments
+}
+
+#[cfg(test)]
+mod kmeans_tests {
+    use super::*;
+    use crate::distance::Distance;
+
+    // Three tight blobs of 100 points each
+    fn build_blobs() -> EmbeddingStore {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let centers = [[0., 0.], [10., 0.], [0., 10.]];
+        let mut es = EmbeddingStore::new(300, 2, Distance::Euclidean);
+        for node_id in 0..es.len() {
+            let center = centers[node_id % 3];
+            let emb = [center[0] + rng.gen::<f32>(), center[1] + rng.gen::<f32>()];
+            es.set_embedding(node_id, &emb);
+        }
+        es
+    }
+
+    #[test]
+    fn test_kmeans() {
+        let es = build_blobs();
+        let kmeans = KMeans { k: 3, batch_size: 50, iterations: 50, max_cluster_size: None, seed: 2023 };
+        let (assignments, centroids) = kmeans.fit(&es).unwrap();
+        assert_eq!(centroids.len(), 3);
+
+        // Every blob ends up in its own cluster
+        for node_id in 0..es.len() {
+            assert_eq!(assignments[node_id], assignments[node_id % 3]);
+        }
+        let mut clusters = assignments[..3].to_vec();
+        clusters.sort();
+        assert_eq!(clusters, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_balanced() {
+        let es = build_blobs();
+        let kmeans = KMeans { k: 2, batch_size: 50, iterations: 50, max_cluster_size: Some(150), seed: 2023 };
+        let (assignments, _) = kmeans.fit(&es).unwrap();
+        let size = assignments.iter().filter(|c| **c == 0).count();
+        assert_eq!(size, 150);
+
+        let kmeans = KMeans { max_cluster_size: Some(100), ..kmeans };
+        assert!(kmeans.fit(&es).is_err());
+    }
+}
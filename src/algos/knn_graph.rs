@@ -0,0 +1,143 @@
+//! Builds a k-nearest-neighbor graph over an EmbeddingStore.  Candidates come from the Ann index,
+//! optionally refined by checking the neighbors of neighbors with exact distances.  The resulting
+//! graph can be used anywhere a CSR can, such as RWR, clustering, or as the navigable graph for
+//! graph_ann.
+use hashbrown::HashSet;
+use rayon::prelude::*;
+
+use crate::graph::{CSR,NodeID};
+use crate::embeddings::{EmbeddingStore,Entity};
+use crate::algos::ann::{Ann,AnnBuildConfig};
+use crate::algos::graph_ann::{NodeDistance,TopK};
+use crate::error::GraphLibError;
+
+/// Builds a graph connecting each node to its k approximate nearest neighbors.
+///
+/// Each refinement pass rescores the neighbors of each node's neighbors with exact distances,
+/// which usually recovers most of the neighbors the Ann missed.  Edge weights are
+/// exp(nearest distance - distance), so the nearest neighbor of each node has weight 1 and the
+/// weights are valid regardless of the distance metric.  If symmetric, every edge is added in
+/// both directions and mutual neighbors get the sum of both weights.
+pub fn build(
+    es: &EmbeddingStore,
+    k: usize,
+    ann_config: &AnnBuildConfig,
+    refinement_passes: usize,
+    symmetric: bool
+) -> Result<CSR, GraphLibError> {
+    if k == 0 {
+        return Err("k must be positive!".into())
+    }
+    if es.len() < 2 {
+        return Err("Need at least two embeddings to build a kNN graph!".into())
+    }
+
+    let mut ann = Ann::new();
+    ann.fit_with_config(es, ann_config, None)?;
+
+    // Ask for one extra since each node is usually its own nearest neighbor
+    let mut neighbors = (0..es.len()).into_par_iter().map(|node_id| {
+        let mut nds = ann.predict(es, es.get_embedding(node_id), k + 1, None)?;
+        nds.retain(|nd| nd.1 != node_id);
+        nds.truncate(k);
+        Ok(nds)
+    }).collect::<Result<Vec<_>, GraphLibError>>()?;
+
+    for _ in 0..refinement_passes {
+        neighbors = refine(es, &neighbors, k);
+    }
+
+    let mut edges: Vec<_> = neighbors.iter().enumerate().flat_map(|(node_id, nds)| {
+        let nearest = nds.first().map(|nd| nd.0).unwrap_or(0.);
+        nds.iter().map(move |nd| {
+            let weight = (nearest - nd.0).exp();
+            (node_id, nd.1, if weight.is_finite() { weight } else { 0. })
+        })
+    }).collect();
+
+    if symmetric {
+        let reversed: Vec<_> = edges.iter().map(|(f_n, t_n, w)| (*t_n, *f_n, *w)).collect();
+        edges.extend(reversed);
+    }
+
+    CSR::try_construct_from_edges(edges, symmetric)
+}
+
+// Rescores each node's neighbors along with their neighbors, keeping the exact top k.
+fn refine(
+    es: &EmbeddingStore,
+    neighbors: &[Vec<NodeDistance>],
+    k: usize
+) -> Vec<Vec<NodeDistance>> {
+    (0..neighbors.len()).into_par_iter().map(|node_id| {
+        let mut candidates: HashSet<NodeID> = HashSet::new();
+        for nd in neighbors[node_id].iter() {
+            candidates.insert(nd.1);
+            candidates.extend(neighbors[nd.1].iter().map(|nd2| nd2.1));
+        }
+        candidates.remove(&node_id);
+
+        let node = Entity::Node(node_id);
+        let mut top_k = TopK::new(k);
+        candidates.into_iter().for_each(|c_id| {
+            top_k.push(c_id, es.compute_distance(&node, &Entity::Node(c_id)));
+        });
+        top_k.into_sorted()
+    }).collect()
+}
+
+#[cfg(test)]
+mod knn_graph_tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_xorshift::XorShiftRng;
+    use crate::distance::Distance;
+    use crate::graph::Graph;
+
+    fn build_store() -> EmbeddingStore {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut es = EmbeddingStore::new(300, 5, Distance::Euclidean);
+        for node_id in 0..es.len() {
+            let emb: Vec<f32> = (0..5).map(|_| rng.gen::<f32>()).collect();
+            es.set_embedding(node_id, &emb);
+        }
+        es
+    }
+
+    #[test]
+    fn test_build() {
+        let es = build_store();
+        let config = AnnBuildConfig::new(5, 20, 2023);
+        let graph = build(&es, 5, &config, 2, false).unwrap();
+        assert_eq!(graph.len(), es.len());
+
+        let mut found = 0;
+        for node_id in 0..es.len() {
+            let (edges, weights) = graph.get_edges(node_id);
+            assert_eq!(edges.len(), 5);
+            assert!(!edges.contains(&node_id));
+            assert!(weights.iter().all(|w| *w > 0. && *w <= 1.));
+
+            let exact = es.nearest_neighbor(&Entity::Node(node_id), 5, |n| n != node_id);
+            found += exact.iter().filter(|nd| edges.contains(&nd.1)).count();
+        }
+
+        // Refinement should recover nearly all of the true neighbors
+        let recall = found as f32 / (5 * es.len()) as f32;
+        assert!(recall > 0.9, "recall: {}", recall);
+    }
+
+    #[test]
+    fn test_symmetric() {
+        let es = build_store();
+        let config = AnnBuildConfig::new(5, 20, 2023);
+        let graph = build(&es, 3, &config, 0, true).unwrap();
+        for node_id in 0..es.len() {
+            for to_node in graph.get_edges(node_id).0.iter() {
+                assert!(graph.get_edges(*to_node).0.contains(&node_id));
+            }
+        }
+
+        assert!(build(&es, 0, &config, 0, true).is_err());
+    }
+}
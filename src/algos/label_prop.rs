@@ -0,0 +1,150 @@
+//! Semi-supervised label propagation.  A labeled subset of nodes provides label distributions,
+//! which are repeatedly averaged over each node's weighted neighborhood until the scores stop
+//! changing.  Labeled nodes are clamped back toward their original distributions every iteration,
+//! so the known labels anchor the propagation.  Unlike `lpa`, which discovers clusters, this
+//! classifies nodes into a fixed set of labels.
+use rayon::prelude::*;
+
+use crate::graph::{CDFGraph,CDFtoP,NodeID};
+use crate::embeddings::EmbeddingStore;
+use crate::distance::Distance;
+use crate::error::GraphLibError;
+
+/// Label propagation configuration
+#[derive(Clone,Copy,Debug)]
+pub struct LabelPropagation {
+    /// Maximum number of iterations
+    pub max_iters: usize,
+
+    /// How strongly labeled nodes are pulled back to their original distribution each iteration,
+    /// in [0, 1].  1 fully clamps them, 0 lets them drift like every other node.
+    pub clamp: f32,
+
+    /// Stops once no label score changes by more than this between iterations
+    pub tolerance: f32
+}
+
+impl LabelPropagation {
+
+    /// Propagates the labels, returning the label scores for every node as an EmbeddingStore with
+    /// one dimension per label.  Label distributions are normalized to sum to 1.  Nodes which
+    /// can't be reached from any labeled node score zero for every label.
+    pub fn propagate(
+        &self,
+        graph: &(impl CDFGraph + Send + Sync),
+        num_labels: usize,
+        labels: &[(NodeID, Vec<f32>)]
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        if self.clamp.is_nan() || self.clamp < 0. || self.clamp > 1. {
+            return Err("clamp must be in [0, 1]!".into())
+        }
+        if num_labels == 0 {
+            return Err("num_labels must be positive!".into())
+        }
+
+        let mut seeds: Vec<Option<Vec<f32>>> = vec![None; graph.len()];
+        for (node_id, dist) in labels.iter() {
+            if *node_id >= graph.len() {
+                return Err(GraphLibError::InvalidInput(
+                    format!("Labeled node {} is not in the graph", node_id)))
+            }
+            if dist.len() != num_labels {
+                return Err(GraphLibError::DimensionMismatch { expected: num_labels, found: dist.len() })
+            }
+            let total: f32 = dist.iter().sum();
+            if !total.is_finite() || total <= 0. || dist.iter().any(|p| *p < 0.) {
+                return Err(GraphLibError::InvalidInput(
+                    format!("Labels for node {} must be non-negative with a positive sum", node_id)))
+            }
+            seeds[*node_id] = Some(dist.iter().map(|p| p / total).collect());
+        }
+
+        let mut scores: Vec<f32> = seeds.iter().flat_map(|seed| match seed {
+            Some(dist) => dist.clone(),
+            None => vec![0.; num_labels]
+        }).collect();
+        let mut next = scores.clone();
+
+        for _iter in 0..self.max_iters {
+            next.par_chunks_mut(num_labels).enumerate().for_each(|(node_id, row)| {
+                let (edges, weights) = graph.get_edges(node_id);
+                if edges.is_empty() {
+                    row.copy_from_slice(&scores[node_id * num_labels..(node_id + 1) * num_labels]);
+                } else {
+                    row.iter_mut().for_each(|r| *r = 0.);
+                    for (edge, p) in edges.iter().zip(CDFtoP::new(weights)) {
+                        let neighbor = &scores[edge * num_labels..(edge + 1) * num_labels];
+                        row.iter_mut().zip(neighbor.iter()).for_each(|(r, n)| *r += p * n);
+                    }
+                }
+
+                if let Some(dist) = &seeds[node_id] {
+                    row.iter_mut().zip(dist.iter()).for_each(|(r, d)| {
+                        *r = self.clamp * d + (1. - self.clamp) * *r;
+                    });
+                }
+            });
+
+            let delta = scores.par_iter().zip(next.par_iter())
+                .map(|(s, n)| (s - n).abs())
+                .reduce(|| 0., f32::max);
+
+            std::mem::swap(&mut scores, &mut next);
+            if delta <= self.tolerance {
+                break
+            }
+        }
+
+        Ok(EmbeddingStore::new_with_vec(graph.len(), num_labels, Distance::Cosine, scores)
+            .expect("Scores are sized to the graph"))
+    }
+}
+
+#[cfg(test)]
+mod label_prop_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+
+    // Two triangles, 0-1-2 and 3-4-5, joined by a single edge between 2 and 3
+    fn build_graph() -> CumCSR {
+        let pairs = [(0, 1), (1, 2), (0, 2), (3, 4), (4, 5), (3, 5), (2, 3)];
+        let mut edges = Vec::new();
+        for (f_n, t_n) in pairs.iter() {
+            edges.push((*f_n, *t_n, 1f32));
+            edges.push((*t_n, *f_n, 1f32));
+        }
+        CumCSR::convert(CSR::construct_from_edges(edges, false))
+    }
+
+    #[test]
+    fn test_propagate() {
+        let graph = build_graph();
+        let lp = LabelPropagation { max_iters: 100, clamp: 1., tolerance: 1e-6 };
+        let labels = vec![(0, vec![1., 0.]), (5, vec![0., 2.])];
+        let scores = lp.propagate(&graph, 2, &labels).unwrap();
+
+        assert_eq!(scores.dims(), 2);
+        assert_eq!(scores.get_embedding(0), &[1., 0.]);
+        assert_eq!(scores.get_embedding(5), &[0., 1.]);
+        for node_id in 1..3 {
+            let s = scores.get_embedding(node_id);
+            assert!(s[0] > s[1]);
+        }
+        for node_id in 3..5 {
+            let s = scores.get_embedding(node_id);
+            assert!(s[1] > s[0]);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let graph = build_graph();
+        let lp = LabelPropagation { max_iters: 100, clamp: 1., tolerance: 1e-6 };
+        assert!(lp.propagate(&graph, 2, &[(0, vec![1.])]).is_err());
+        assert!(lp.propagate(&graph, 2, &[(10, vec![1., 0.])]).is_err());
+        assert!(lp.propagate(&graph, 2, &[(0, vec![0., 0.])]).is_err());
+
+        let lp = LabelPropagation { clamp: 1.5, ..lp };
+        assert!(lp.propagate(&graph, 2, &[(0, vec![1., 0.])]).is_err());
+    }
+}
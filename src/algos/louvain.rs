@@ -0,0 +1,253 @@
+//! Louvain modularity optimization for community detection.  Each level greedily moves nodes
+//! between communities while modularity improves, then collapses each community into a single node
+//! and repeats on the aggregated graph.
+//!
+//! Plain Louvain can produce communities which are internally disconnected.  As in Leiden, we
+//! refine each level by splitting such communities into their connected components before
+//! aggregating, guaranteeing every community is connected.  We don't implement Leiden's full
+//! randomized merge refinement.
+//!
+//! The graph is treated as undirected with raw edge weights: every edge should show up in both
+//! directions (see `CSR::to_undirected`), and CDF graphs, which store cumulative weights, should
+//! be converted back first.
+use hashbrown::HashMap;
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+
+use crate::graph::{Graph,NodeID};
+
+/// Louvain configuration
+#[derive(Clone,Copy,Debug)]
+pub struct Louvain {
+    /// Resolution parameter.  Values above 1 produce smaller communities, below 1 larger ones.
+    pub resolution: f32,
+
+    /// Maximum number of aggregation levels
+    pub max_levels: usize,
+
+    /// Maximum number of sweeps over the nodes within each level
+    pub max_sweeps: usize,
+
+    /// Random seed for the order in which nodes are visited
+    pub seed: u64
+}
+
+/// Weighted adjacency list, including self loops for aggregated communities
+type Adjacency = Vec<Vec<(usize, f32)>>;
+
+impl Louvain {
+
+    /// Detects communities, returning the assignments of each node at every level, from the finest
+    /// to the coarsest.  Community ids are dense within each level.
+    pub fn fit(&self, graph: &impl Graph) -> Vec<Vec<usize>> {
+        let mut adj: Adjacency = (0..graph.len()).map(|node_id| {
+            let (edges, weights) = graph.get_edges(node_id);
+            edges.iter().cloned().zip(weights.iter().cloned()).collect()
+        }).collect();
+
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        let mut membership: Vec<_> = (0..graph.len()).collect();
+        let mut levels = Vec::new();
+        for _ in 0..self.max_levels {
+            let communities = self.local_moving(&adj, &mut rng);
+            let communities = split_disconnected(&adj, &communities);
+            let n_communities = communities.iter().max().map(|c| c + 1).unwrap_or(0);
+
+            membership.iter_mut().for_each(|m| *m = communities[*m]);
+            levels.push(membership.clone());
+
+            // Converged: nothing merged
+            if n_communities == adj.len() {
+                break
+            }
+            adj = aggregate(&adj, &communities, n_communities);
+        }
+        levels
+    }
+
+    fn local_moving(&self, adj: &Adjacency, rng: &mut impl Rng) -> Vec<usize> {
+        let n = adj.len();
+        let degrees: Vec<f32> = adj.iter().map(|edges| edges.iter().map(|(_, w)| w).sum()).collect();
+        let m2: f32 = degrees.iter().sum();
+        let mut communities: Vec<_> = (0..n).collect();
+        if m2 <= 0. {
+            return communities
+        }
+
+        let mut totals = degrees.clone();
+        let mut order: Vec<_> = (0..n).collect();
+        order.shuffle(rng);
+
+        // Scratch space for the weight from the node to each neighboring community
+        let mut neighbor_weights = vec![0f32; n];
+        let mut touched = Vec::new();
+        for _ in 0..self.max_sweeps {
+            let mut moved = 0;
+            for &node in order.iter() {
+                let cur = communities[node];
+                let k_i = degrees[node];
+                for (out_node, w) in adj[node].iter() {
+                    if *out_node == node { continue }
+                    let c = communities[*out_node];
+                    if neighbor_weights[c] == 0. {
+                        touched.push(c);
+                    }
+                    neighbor_weights[c] += w;
+                }
+
+                // Remove the node from its community and find the best community to join
+                totals[cur] -= k_i;
+                let gain = |c: usize, nw: f32| nw - self.resolution * totals[c] * k_i / m2;
+                let mut best = (cur, gain(cur, neighbor_weights[cur]));
+                for &c in touched.iter() {
+                    let g = gain(c, neighbor_weights[c]);
+                    if g > best.1 + 1e-7 {
+                        best = (c, g);
+                    }
+                }
+
+                totals[best.0] += k_i;
+                if best.0 != cur {
+                    communities[node] = best.0;
+                    moved += 1;
+                }
+
+                touched.drain(..).for_each(|c| neighbor_weights[c] = 0.);
+            }
+
+            if moved == 0 { break }
+        }
+
+        compact(&communities)
+    }
+}
+
+/// Relabels communities to dense ids in order of first appearance
+fn compact(communities: &[usize]) -> Vec<usize> {
+    let mut mapping = HashMap::new();
+    communities.iter().map(|c| {
+        let next_id = mapping.len();
+        *mapping.entry(*c).or_insert(next_id)
+    }).collect()
+}
+
+/// Splits communities which aren't internally connected into their connected components.
+fn split_disconnected(adj: &Adjacency, communities: &[usize]) -> Vec<usize> {
+    let mut refined = vec![usize::MAX; adj.len()];
+    let mut next_id = 0;
+    let mut stack = Vec::new();
+    for start in 0..adj.len() {
+        if refined[start] != usize::MAX { continue }
+        refined[start] = next_id;
+        stack.push(start);
+        while let Some(node) = stack.pop() {
+            for (out_node, _) in adj[node].iter() {
+                if refined[*out_node] == usize::MAX && communities[*out_node] == communities[node] {
+                    refined[*out_node] = next_id;
+                    stack.push(*out_node);
+                }
+            }
+        }
+        next_id += 1;
+    }
+    refined
+}
+
+/// Collapses each community into a single node, summing edge weights.  Edges within a community
+/// become self loops.
+fn aggregate(adj: &Adjacency, communities: &[usize], n_communities: usize) -> Adjacency {
+    let mut new_adj: Vec<HashMap<usize, f32>> = vec![HashMap::new(); n_communities];
+    for (node, edges) in adj.iter().enumerate() {
+        let c = communities[node];
+        for (out_node, w) in edges.iter() {
+            *new_adj[c].entry(communities[*out_node]).or_insert(0.) += w;
+        }
+    }
+
+    new_adj.into_iter().map(|edges| {
+        let mut edges: Vec<_> = edges.into_iter().collect();
+        edges.sort_by_key(|(c, _)| *c);
+        edges
+    }).collect()
+}
+
+/// Computes the modularity of a set of community assignments with the given resolution.
+pub fn modularity(graph: &(impl Graph + Sync), communities: &[usize], resolution: f32) -> f32 {
+    let n_communities = communities.iter().max().map(|c| c + 1).unwrap_or(0);
+    let mut totals = vec![0f32; n_communities];
+    let mut m2 = 0f32;
+    let internal: f32 = (0..graph.len()).into_par_iter().map(|node_id: NodeID| {
+        let (edges, weights) = graph.get_edges(node_id);
+        edges.iter().zip(weights.iter())
+            .filter(|(out_node, _)| communities[**out_node] == communities[node_id])
+            .map(|(_, w)| *w)
+            .sum::<f32>()
+    }).sum();
+
+    for node_id in 0..graph.len() {
+        let k_i: f32 = graph.get_edges(node_id).1.iter().sum();
+        totals[communities[node_id]] += k_i;
+        m2 += k_i;
+    }
+
+    if m2 <= 0. {
+        return 0.
+    }
+
+    internal / m2 - resolution * totals.iter().map(|t| (t / m2).powi(2)).sum::<f32>()
+}
+
+#[cfg(test)]
+mod louvain_tests {
+    use super::*;
+    use crate::graph::CSR;
+
+    fn build_graph() -> CSR {
+        // Two cliques joined by a single edge
+        let mut edges = Vec::new();
+        for offset in [0, 4] {
+            for u in 0..4 {
+                for v in 0..4 {
+                    if u != v { edges.push((u + offset, v + offset, 1.)); }
+                }
+            }
+        }
+        edges.push((3, 4, 1.));
+        edges.push((4, 3, 1.));
+        CSR::construct_from_edges(edges, false)
+    }
+
+    #[test]
+    fn test_louvain() {
+        let graph = build_graph();
+        let louvain = Louvain { resolution: 1., max_levels: 10, max_sweeps: 20, seed: 2023 };
+        let levels = louvain.fit(&graph);
+        assert!(levels.len() >= 1);
+
+        let communities = &levels[0];
+        assert!(communities[0..4].iter().all(|c| *c == communities[0]));
+        assert!(communities[4..8].iter().all(|c| *c == communities[4]));
+        assert_ne!(communities[0], communities[4]);
+
+        // Coarser levels never split communities
+        let last = levels.last().unwrap();
+        assert_eq!(last, communities);
+        assert!(modularity(&graph, communities, 1.) > 0.3);
+    }
+
+    #[test]
+    fn test_split_disconnected() {
+        let adj = vec![vec![(1, 1.)], vec![(0, 1.)], vec![], vec![]];
+        assert_eq!(split_disconnected(&adj, &[0, 0, 0, 1]), vec![0, 0, 1, 2]);
+    }
+
+    #[test]
+    fn test_modularity() {
+        let graph = build_graph();
+        let singletons: Vec<_> = (0..8).collect();
+        let all = vec![0; 8];
+        assert!(modularity(&graph, &singletons, 1.) < 0.);
+        assert!(modularity(&graph, &all, 1.).abs() < 1e-6);
+    }
+}
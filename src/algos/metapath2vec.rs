@@ -0,0 +1,216 @@
+//! Metapath constrained random walks over heterogeneous graphs (metapath2vec).  A metapath is a
+//! sequence of node types, ie. user -> gig -> seller -> gig -> user, and each step of a walk may
+//! only move to a neighbor of the next type in the sequence.  Metapaths must start and end with the
+//! same type so walks can repeat them until they reach the desired length.
+//!
+//! The walks can be fed straight into `algos::skipgram`, or collapsed into a co-occurrence graph
+//! with `context_graph` for training with EmbeddingPropagation.
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+
+use crate::graph::{Graph,CDFGraph,CDFtoP,NodeID,CSR,GraphBuilder};
+use crate::graph::typed::{TypedGraph,NodeType,EdgeType};
+
+/// Metapath walk generator configuration
+#[derive(Clone,Debug)]
+pub struct MetaPath2Vec {
+    /// Sequence of node types to follow.  First and last types must match.
+    pub metapath: Vec<NodeType>,
+
+    /// Optional edge type for each hop in the metapath
+    pub edge_types: Option<Vec<EdgeType>>,
+
+    /// Maximum number of nodes in each walk, including the start node
+    pub walk_len: usize,
+
+    /// Number of walks to start from each node of the metapath's first type
+    pub walks_per_node: usize,
+
+    /// If true, neighbors are chosen proportionally to their transition probability, otherwise
+    /// uniformly among those matching the metapath.
+    pub weighted: bool,
+
+    /// Random seed
+    pub seed: u64
+}
+
+impl MetaPath2Vec {
+
+    pub fn new(
+        metapath: Vec<NodeType>,
+        edge_types: Option<Vec<EdgeType>>,
+        walk_len: usize,
+        walks_per_node: usize,
+        weighted: bool,
+        seed: u64
+    ) -> Result<Self, &'static str> {
+        if metapath.len() < 2 {
+            return Err("Metapath needs at least two node types!")
+        }
+        if metapath[0] != metapath[metapath.len() - 1] {
+            return Err("Metapath must start and end with the same node type!")
+        }
+        if let Some(et) = edge_types.as_ref() {
+            if et.len() != metapath.len() - 1 {
+                return Err("Metapath needs exactly one edge type per hop!")
+            }
+        }
+        Ok(MetaPath2Vec { metapath, edge_types, walk_len, walks_per_node, weighted, seed })
+    }
+
+    /// Generates the walk corpus, starting `walks_per_node` walks from every node matching the
+    /// first type of the metapath.  Walks are ordered by pass, then by start node, and end early
+    /// when no neighbor matches the metapath.
+    pub fn generate<G: CDFGraph + Send + Sync>(&self, graph: &TypedGraph<G>) -> Vec<Vec<NodeID>> {
+        let nodes = graph.nodes_of_type(self.metapath[0]);
+        let n = nodes.len();
+        (0..(n * self.walks_per_node)).into_par_iter().map(|idx| {
+            let mut rng = XorShiftRng::seed_from_u64(self.seed + idx as u64);
+            self.walk(graph, nodes[idx % n], &mut rng)
+        }).collect()
+    }
+
+    /// Runs a single metapath walk from the start node, which should match the metapath's first
+    /// type.
+    pub fn walk<G: CDFGraph>(
+        &self,
+        graph: &TypedGraph<G>,
+        start_node: NodeID,
+        rng: &mut impl Rng
+    ) -> Vec<NodeID> {
+        let mut walk = Vec::with_capacity(self.walk_len);
+        if self.walk_len == 0 || graph.node_type(start_node) != self.metapath[0] {
+            return walk
+        }
+
+        // Last type is the same as the first, so we cycle through the rest
+        let cycle = self.metapath.len() - 1;
+        let mut candidates = Vec::new();
+        let mut cur_node = start_node;
+        walk.push(cur_node);
+        while walk.len() < self.walk_len {
+            let hop = (walk.len() - 1) % cycle;
+            let next_type = self.metapath[hop + 1];
+            let edge_type = self.edge_types.as_ref().map(|et| et[hop]);
+
+            let (edges, weights) = graph.get_edges(cur_node);
+            let types = graph.get_edge_types(cur_node);
+            candidates.clear();
+            candidates.extend((0..edges.len()).filter(|idx| {
+                graph.node_type(edges[*idx]) == next_type
+                    && edge_type.map(|et| et == types[*idx]).unwrap_or(true)
+            }));
+
+            if candidates.is_empty() {
+                break
+            }
+
+            let idx = if self.weighted {
+                let probs = CDFtoP::new(weights);
+                let total: f32 = candidates.iter().map(|idx| probs.prob(*idx)).sum();
+                let mut p = rng.gen::<f32>() * total;
+                let mut choice = candidates[candidates.len() - 1];
+                for idx in candidates.iter() {
+                    let w = probs.prob(*idx);
+                    if p < w {
+                        choice = *idx;
+                        break
+                    }
+                    p -= w;
+                }
+                choice
+            } else {
+                candidates[rng.gen_range(0, candidates.len())]
+            };
+
+            cur_node = edges[idx];
+            walk.push(cur_node);
+        }
+
+        walk
+    }
+}
+
+/// Collapses walks into a graph connecting each node to the nodes within `window` steps of it,
+/// weighted by the number of co-occurrences.  This lets walk corpora be trained with
+/// EmbeddingPropagation, after converting the result to a CumCSR.
+pub fn context_graph(num_nodes: usize, walks: &[Vec<NodeID>], window: usize) -> CSR {
+    let mut builder = GraphBuilder::new(true);
+    for walk in walks.iter() {
+        for (i, u) in walk.iter().enumerate() {
+            for v in walk.iter().skip(i + 1).take(window) {
+                if u != v {
+                    builder.add_edge(*u, *v, 1.);
+                    builder.add_edge(*v, *u, 1.);
+                }
+            }
+        }
+    }
+
+    // Make sure every node has a row, even if it never shows up in a walk
+    builder.ensure_nodes(num_nodes);
+    builder.build_csr()
+}
+
+#[cfg(test)]
+mod metapath2vec_tests {
+    use super::*;
+    use crate::graph::CumCSR;
+
+    const USER: NodeType = 0;
+    const GIG: NodeType = 1;
+    const SELLER: NodeType = 2;
+
+    fn build_graph() -> TypedGraph<CumCSR> {
+        // Users 0, 1 view gigs 2, 3 which are sold by seller 4.  User 0 also follows user 1.
+        let edges = vec![
+            (0, 1, 1.),
+            (0, 2, 1.), (2, 0, 1.),
+            (1, 3, 1.), (3, 1, 1.),
+            (2, 4, 1.), (4, 2, 1.),
+            (3, 4, 1.), (4, 3, 1.),
+        ];
+        let graph = CumCSR::convert(CSR::construct_from_edges(edges, false));
+        TypedGraph::from_node_types(graph, vec![USER, USER, GIG, GIG, SELLER]).unwrap()
+    }
+
+    #[test]
+    fn test_new() {
+        assert!(MetaPath2Vec::new(vec![USER], None, 5, 1, false, 0).is_err());
+        assert!(MetaPath2Vec::new(vec![USER, GIG], None, 5, 1, false, 0).is_err());
+        assert!(MetaPath2Vec::new(vec![USER, GIG, USER], Some(vec![0]), 5, 1, false, 0).is_err());
+        assert!(MetaPath2Vec::new(vec![USER, GIG, USER], Some(vec![0, 0]), 5, 1, false, 0).is_ok());
+    }
+
+    #[test]
+    fn test_walk_follows_metapath() {
+        let graph = build_graph();
+        let metapath = vec![USER, GIG, SELLER, GIG, USER];
+        let mp = MetaPath2Vec::new(metapath.clone(), None, 13, 10, true, 2023).unwrap();
+        let walks = mp.generate(&graph);
+        assert_eq!(walks.len(), 20);
+        for walk in walks.iter() {
+            assert_eq!(walk.len(), 13);
+            for (i, node) in walk.iter().enumerate() {
+                assert_eq!(graph.node_type(*node), metapath[i % 4]);
+            }
+        }
+
+        // Sellers can't start walks and edge types which don't exist stop them immediately
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        assert!(mp.walk(&graph, 4, &mut rng).is_empty());
+        let mp = MetaPath2Vec::new(metapath, Some(vec![1, 1, 1, 1]), 13, 1, false, 2023).unwrap();
+        assert_eq!(mp.walk(&graph, 0, &mut rng), vec![0]);
+    }
+
+    #[test]
+    fn test_context_graph() {
+        let walks = vec![vec![0, 2, 4], vec![1]];
+        let graph = context_graph(6, &walks, 1);
+        assert_eq!(graph.len(), 6);
+        assert_eq!(graph.get_edges(0), (&[2][..], &[1.][..]));
+        assert_eq!(graph.get_edges(2), (&[0, 4][..], &[1., 1.][..]));
+        assert_eq!(graph.degree(1), 0);
+    }
+}
@@ -3,6 +3,7 @@ pub mod grwr;
 pub mod reweighter;
 pub mod dist;
 pub mod lpa;
+pub mod label_prop;
 pub mod slpa;
 pub mod ep;
 pub mod graph_ann;
@@ -13,11 +14,33 @@ pub mod feat_propagation;
 pub mod alignment;
 pub mod pprrank;
 pub mod ann;
+pub mod knn_graph;
+pub mod kmeans;
+pub mod ivf;
 pub mod emb_aligner;
 pub mod pagerank;
+pub mod simrank;
+pub mod ppr_push;
 pub mod vpcg;
 pub mod pprembed;
 pub mod instantembedding;
 pub mod lsr;
 pub mod connected;
+pub mod retrieval;
+pub mod evaluation;
+pub mod components;
+pub mod query_cache;
+pub mod node2vec;
+pub mod temporal_walk;
+pub mod metapath2vec;
+pub mod skipgram;
+pub mod kge;
+pub mod louvain;
+pub mod coarsen;
+pub mod triangles;
+pub mod shortest_path;
+pub mod refresh;
+pub mod feature_nn;
+pub mod smoothing;
+pub mod partition;
 mod grad_utils;
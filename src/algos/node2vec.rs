@@ -0,0 +1,198 @@
+//! Node2vec style biased second order random walks.  The return parameter `p` controls how likely
+//! a walk is to immediately revisit the previous node, while the in-out parameter `q` controls
+//! whether walks stay local (BFS like, q > 1) or move outward (DFS like, q < 1).
+//!
+//! Rather than precomputing alias tables for every edge pair, which is quadratic in the degree, we
+//! use rejection sampling: the next node is proposed from the first order sampler and accepted
+//! proportionally to its second order bias.
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+
+use crate::graph::{Graph,NodeID};
+use crate::sampler::Sampler;
+
+/// Walk generator configuration
+#[derive(Clone,Copy,Debug)]
+pub struct Node2Vec {
+    /// Return parameter
+    pub p: f32,
+
+    /// In-out parameter
+    pub q: f32,
+
+    /// Maximum number of nodes in each walk, including the start node
+    pub walk_len: usize,
+
+    /// Number of walks to start from each node
+    pub walks_per_node: usize,
+
+    /// Random seed
+    pub seed: u64
+}
+
+impl Node2Vec {
+
+    pub fn new(
+        p: f32,
+        q: f32,
+        walk_len: usize,
+        walks_per_node: usize,
+        seed: u64
+    ) -> Result<Self, &'static str> {
+        if !(p > 0. && q > 0.) {
+            return Err("p and q must be greater than zero!")
+        }
+        Ok(Node2Vec { p, q, walk_len, walks_per_node, seed })
+    }
+
+    /// Generates the walk corpus, starting `walks_per_node` walks from each node.  Walks are
+    /// ordered by pass, then by start node, and end early at dead ends.
+    pub fn generate<G: Graph + Send + Sync>(
+        &self,
+        graph: &G,
+        sampler: &impl Sampler<G>
+    ) -> Vec<Vec<NodeID>> {
+        let nodes: Vec<_> = (0..graph.len()).collect();
+        self.generate_from(graph, sampler, &nodes)
+    }
+
+    /// Generates walks starting only from the provided nodes.
+    pub fn generate_from<G: Graph + Send + Sync>(
+        &self,
+        graph: &G,
+        sampler: &impl Sampler<G>,
+        nodes: &[NodeID]
+    ) -> Vec<Vec<NodeID>> {
+        let n = nodes.len();
+        (0..(n * self.walks_per_node)).into_par_iter().map(|idx| {
+            let mut rng = XorShiftRng::seed_from_u64(self.seed + idx as u64);
+            self.walk(graph, sampler, nodes[idx % n], &mut rng)
+        }).collect()
+    }
+
+    /// Runs a single biased walk from the start node.
+    pub fn walk<G: Graph>(
+        &self,
+        graph: &G,
+        sampler: &impl Sampler<G>,
+        start_node: NodeID,
+        rng: &mut impl Rng
+    ) -> Vec<NodeID> {
+        let mut walk = Vec::with_capacity(self.walk_len);
+        if self.walk_len == 0 {
+            return walk
+        }
+
+        walk.push(start_node);
+
+        // First step has no previous node, so it's unbiased
+        let mut cur_node = match sampler.sample(graph, start_node, rng) {
+            Some(node) if self.walk_len > 1 => node,
+            _ => return walk
+        };
+        walk.push(cur_node);
+
+        let max_bias = (1. / self.p).max(1.).max(1. / self.q);
+        while walk.len() < self.walk_len {
+            let prev_node = walk[walk.len() - 2];
+            let prev_edges = graph.get_edges(prev_node).0;
+            let next_node = loop {
+                match sampler.sample(graph, cur_node, rng) {
+                    Some(candidate) => {
+                        let bias = if candidate == prev_node {
+                            1. / self.p
+                        } else if prev_edges.contains(&candidate) {
+                            1.
+                        } else {
+                            1. / self.q
+                        };
+
+                        if rng.gen::<f32>() * max_bias < bias {
+                            break Some(candidate)
+                        }
+                    },
+                    None => break None
+                }
+            };
+
+            match next_node {
+                Some(node) => {
+                    walk.push(node);
+                    cur_node = node;
+                },
+                None => break
+            }
+        }
+
+        walk
+    }
+}
+
+#[cfg(test)]
+mod node2vec_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+    use crate::sampler::Unweighted;
+
+    fn build_graph() -> CumCSR {
+        // Star with a tail: 0 is the hub, 4 -> 5 -> 6 is a dead end
+        let edges = vec![
+            (0, 1, 1.), (1, 0, 1.),
+            (0, 2, 1.), (2, 0, 1.),
+            (0, 3, 1.), (3, 0, 1.),
+            (0, 4, 1.), (4, 0, 1.),
+            (4, 5, 1.),
+            (5, 6, 1.),
+        ];
+        CumCSR::convert(CSR::construct_from_edges(edges, false))
+    }
+
+    #[test]
+    fn test_walks() {
+        let graph = build_graph();
+        let n2v = Node2Vec::new(1., 1., 5, 3, 2023).unwrap();
+        let walks = n2v.generate(&graph, &Unweighted);
+        assert_eq!(walks.len(), 3 * graph.len());
+
+        for (i, walk) in walks.iter().enumerate() {
+            assert_eq!(walk[0], i % graph.len());
+            assert!(walk.len() <= 5);
+            for w in walk.windows(2) {
+                assert!(graph.get_edges(w[0]).0.contains(&w[1]));
+            }
+        }
+
+        // Dead end
+        assert_eq!(walks[6], vec![6]);
+        assert_eq!(walks, n2v.generate(&graph, &Unweighted));
+    }
+
+    #[test]
+    fn test_return_bias() {
+        let graph = build_graph();
+        let mut rng = XorShiftRng::seed_from_u64(1);
+
+        // Low p always returns
+        let n2v = Node2Vec::new(1e-4, 1., 6, 1, 0).unwrap();
+        let returns = (0..100).filter(|_| {
+            let walk = n2v.walk(&graph, &Unweighted, 1, &mut rng);
+            walk[2] == 1
+        }).count();
+        assert!(returns > 95);
+
+        // High p avoids returning to the previous node
+        let n2v = Node2Vec::new(1e4, 1., 6, 1, 0).unwrap();
+        let returns = (0..100).filter(|_| {
+            let walk = n2v.walk(&graph, &Unweighted, 1, &mut rng);
+            walk[2] == 1
+        }).count();
+        assert!(returns < 5);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Node2Vec::new(0., 1., 5, 1, 0).is_err());
+        assert!(Node2Vec::new(1., -1., 5, 1, 0).is_err());
+    }
+}
@@ -1,3 +1,5 @@
+//! Global PageRank via deterministic power iteration.  For single source scores, see
+//! `algos::ppr_push`.
 use rayon::prelude::*;
 
 use std::fmt::Write;
@@ -75,3 +77,30 @@ impl PageRank {
     }
 
 }
+
+#[cfg(test)]
+mod pagerank_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+
+    #[test]
+    fn test_pagerank() {
+        let edges = vec![
+            (0, 1, 1.),
+            (2, 1, 1.),
+            (3, 1, 1.),
+            (1, 0, 1.),
+        ];
+        let graph = CumCSR::convert(CSR::construct_from_edges(edges, false));
+        let pr = PageRank::new(100, 0.85, 1e-6);
+        let scores = pr.compute(&graph, false);
+
+        assert!((scores.iter().sum::<f32>() - 1.).abs() < 1e-4);
+        assert!(scores[1] > scores[0]);
+        assert!(scores[0] > scores[2]);
+        assert_eq!(scores[2], scores[3]);
+
+        // Deterministic
+        assert_eq!(scores, pr.compute(&graph, false));
+    }
+}
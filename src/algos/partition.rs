@@ -0,0 +1,249 @@
+//! Streaming graph partitioning, the first step to sharding training across machines.  Nodes are
+//! assigned to K balanced parts one at a time, each going to the part holding most of its already
+//! placed neighbors, discounted by how full that part is.  This is far cheaper than multilevel
+//! partitioners like METIS while still cutting a fraction of the edges a hash partition would.
+//! Restreaming, running extra passes which reassign nodes knowing where every neighbor ended up,
+//! closes much of the remaining gap.
+//!
+//! Only out edges are considered, and edge weights are ignored, so graphs should be symmetric.
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+
+use crate::graph::{Graph,Subgraph,NodeID};
+use crate::error::GraphLibError;
+
+/// Scoring function used to place each node
+#[derive(Clone,Copy,Debug)]
+pub enum PartitionMethod {
+    /// Linear Deterministic Greedy: neighbors in the part scaled by the part's remaining capacity
+    Ldg,
+
+    /// Fennel: neighbors in the part minus a superlinear penalty on the part's size.  Gamma of 1.5
+    /// is the usual choice; higher values favor balance over edge cut.
+    Fennel { gamma: f32 }
+}
+
+/// Order in which nodes are streamed
+#[derive(Clone,Copy,Debug)]
+pub enum StreamOrder {
+    /// NodeID order, which works well when ids follow the graph's locality, such as BFS order
+    Natural,
+
+    /// Random order, given a seed
+    Random(u64)
+}
+
+/// Partitioning configuration
+#[derive(Clone,Copy,Debug)]
+pub struct Partitioning {
+    /// Number of parts
+    pub num_parts: usize,
+
+    /// How nodes are scored against each part
+    pub method: PartitionMethod,
+
+    /// Allowed imbalance: no part holds more than (1 + slack) * n / num_parts nodes
+    pub slack: f32,
+
+    /// Number of passes over the nodes.  Passes after the first restream, moving each node to its
+    /// best part given the current placement of every neighbor.
+    pub passes: usize,
+
+    /// Order in which nodes are streamed
+    pub order: StreamOrder
+}
+
+/// Assignment of nodes to parts
+#[derive(Clone,Debug)]
+pub struct Partition {
+    /// Part of each node
+    pub assignments: Vec<usize>,
+
+    /// Number of parts
+    pub num_parts: usize
+}
+
+// Marks nodes which have yet to be placed
+const UNASSIGNED: usize = usize::MAX;
+
+impl Partitioning {
+
+    /// Partitions the graph.  Fails if there are no parts or the slack is negative.
+    pub fn partition(&self, graph: &impl Graph) -> Result<Partition, GraphLibError> {
+        if self.num_parts == 0 {
+            return Err("num_parts must be at least 1!".into())
+        }
+        if self.slack.is_nan() || self.slack < 0. {
+            return Err("slack must be non-negative!".into())
+        }
+
+        let n = graph.len();
+        let k = self.num_parts;
+        let capacity = ((1. + self.slack as f64) * n as f64 / k as f64).ceil().max(1.) as usize;
+
+        // Fennel's alpha, with m the number of undirected edges
+        let fennel_alpha = match self.method {
+            PartitionMethod::Fennel { gamma } => {
+                let m = graph.edges() as f64 / 2.;
+                m * (k as f64).powf(gamma as f64 - 1.) / (n.max(1) as f64).powf(gamma as f64)
+            },
+            PartitionMethod::Ldg => 0.
+        };
+
+        let score = |neighbors: usize, size: usize| -> f64 {
+            match self.method {
+                PartitionMethod::Ldg => neighbors as f64 * (1. - size as f64 / capacity as f64),
+                PartitionMethod::Fennel { gamma } => {
+                    let gamma = gamma as f64;
+                    neighbors as f64 - fennel_alpha * gamma * (size as f64).powf(gamma - 1.)
+                }
+            }
+        };
+
+        let mut order: Vec<NodeID> = (0..n).collect();
+        if let StreamOrder::Random(seed) = self.order {
+            order.shuffle(&mut XorShiftRng::seed_from_u64(seed));
+        }
+
+        let mut assignments = vec![UNASSIGNED; n];
+        let mut sizes = vec![0usize; k];
+        let mut counts = vec![0usize; k];
+        for _pass in 0..self.passes.max(1) {
+            for node_id in order.iter().cloned() {
+                let current = assignments[node_id];
+                if current != UNASSIGNED {
+                    sizes[current] -= 1;
+                }
+
+                counts.iter_mut().for_each(|c| *c = 0);
+                for t_n in graph.get_edges(node_id).0.iter() {
+                    let part = assignments[*t_n];
+                    if *t_n != node_id && part != UNASSIGNED {
+                        counts[part] += 1;
+                    }
+                }
+
+                // Ties go to the smaller part, then the lower part id
+                let mut best: Option<(usize, f64)> = None;
+                for part in (0..k).filter(|part| sizes[*part] < capacity) {
+                    let s = score(counts[part], sizes[part]);
+                    let better = match best {
+                        None => true,
+                        Some((b_part, b_s)) => s > b_s || (s == b_s && sizes[part] < sizes[b_part])
+                    };
+                    if better {
+                        best = Some((part, s));
+                    }
+                }
+
+                let (part, _) = best.expect("Capacity covers every node");
+                assignments[node_id] = part;
+                sizes[part] += 1;
+            }
+        }
+
+        Ok(Partition { assignments, num_parts: k })
+    }
+}
+
+impl Partition {
+
+    /// Number of nodes in each part
+    pub fn part_sizes(&self) -> Vec<usize> {
+        let mut sizes = vec![0; self.num_parts];
+        self.assignments.iter().for_each(|part| sizes[*part] += 1);
+        sizes
+    }
+
+    /// Nodes within a part, in NodeID order
+    pub fn members(&self, part: usize) -> Vec<NodeID> {
+        self.assignments.iter().enumerate()
+            .filter(|(_, p)| **p == part)
+            .map(|(node_id, _)| node_id)
+            .collect()
+    }
+
+    /// Number of edges whose endpoints are in different parts.  Symmetric graphs count each
+    /// undirected edge twice.
+    pub fn edge_cut(&self, graph: &impl Graph) -> usize {
+        (0..graph.len()).map(|node_id| {
+            let part = self.assignments[node_id];
+            graph.get_edges(node_id).0.iter()
+                .filter(|t_n| self.assignments[**t_n] != part)
+                .count()
+        }).sum()
+    }
+
+    /// Subgraph induced by each part, along with the mapping from the subgraph's NodeIDs to the
+    /// original NodeIDs.  Cut edges are dropped.
+    pub fn subgraphs<G: Subgraph>(&self, graph: &G) -> Vec<(G, Vec<NodeID>)> {
+        (0..self.num_parts).map(|part| {
+            let nodes = self.members(part);
+            (graph.subgraph(&nodes), nodes)
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod partition_tests {
+    use super::*;
+    use crate::graph::CSR;
+
+    // Two 4-cliques, 0-3 and 4-7, joined by the edge 3-4
+    fn build_graph() -> CSR {
+        let mut edges = Vec::new();
+        for clique in [0, 4] {
+            for u in clique..clique + 4 {
+                for v in clique..clique + 4 {
+                    if u != v {
+                        edges.push((u, v, 1.));
+                    }
+                }
+            }
+        }
+        edges.push((3, 4, 1.));
+        edges.push((4, 3, 1.));
+        CSR::construct_from_edges(edges, false)
+    }
+
+    #[test]
+    fn test_partition() {
+        let graph = build_graph();
+        for method in [PartitionMethod::Ldg, PartitionMethod::Fennel { gamma: 1.5 }] {
+            let config = Partitioning {
+                num_parts: 2,
+                method,
+                slack: 0.1,
+                passes: 2,
+                order: StreamOrder::Natural
+            };
+            let partition = config.partition(&graph).unwrap();
+            assert_eq!(partition.assignments, vec![0, 0, 0, 0, 1, 1, 1, 1]);
+            assert_eq!(partition.part_sizes(), vec![4, 4]);
+            assert_eq!(partition.edge_cut(&graph), 2);
+
+            let subgraphs = partition.subgraphs(&graph);
+            assert_eq!(subgraphs[1].1, vec![4, 5, 6, 7]);
+            assert_eq!(subgraphs[1].0.len(), 4);
+            assert_eq!(subgraphs[1].0.edges(), 12);
+        }
+    }
+
+    #[test]
+    fn test_balance() {
+        let graph = build_graph();
+        let config = Partitioning {
+            num_parts: 3,
+            method: PartitionMethod::Ldg,
+            slack: 0.,
+            passes: 3,
+            order: StreamOrder::Random(2023)
+        };
+        let partition = config.partition(&graph).unwrap();
+        assert!(partition.part_sizes().iter().all(|size| *size <= 3));
+        assert_eq!(partition.part_sizes().iter().sum::<usize>(), 8);
+
+        assert!(Partitioning { num_parts: 0, ..config }.partition(&graph).is_err());
+        assert!(Partitioning { slack: -1., ..config }.partition(&graph).is_err());
+    }
+}
@@ -5,13 +5,19 @@ use rayon::prelude::*;
 use hashbrown::HashMap;
 
 use crate::algos::rwr::RWR;
-use crate::algos::utils::{Sample,FeatureHasher};
+use crate::algos::ppr_push::ForwardPush;
+use crate::algos::utils::{Sample,FeatureHasher,RngKind,EdgeAliasTable};
 use crate::embeddings::EmbeddingStore;
 use crate::distance::Distance;
 use crate::feature_store::FeatureStore;
-use crate::graph::{CDFGraph,Graph};
+use crate::graph::{CDFGraph,Graph,NodeID};
 use crate::progress::CLProgressBar;
+use crate::resources::{ResourceTracker,ResourceReport};
+use crate::runtime::Runtime;
+use crate::error::GraphLibError;
 
+#[derive(Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PPREmbed {
 
     /// Number of random walks per node to estimate neighborhood
@@ -31,30 +37,272 @@ pub struct PPREmbed {
 
     /// Random seed
     pub seed: u64,
+
+    /// Generator the random walks draw from
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub rng_kind: RngKind,
+
+    /// If provided, estimates the neighborhood with Forward Push using this residual threshold
+    /// rather than sampling random walks.  Deterministic, bit for bit, and less noisy.
+    pub push_eps: Option<f32>,
+
+    /// Number of signed hashes each feature is projected with.  More hashes reduce the impact of
+    /// collisions at the cost of denser embeddings.
+    #[cfg_attr(feature = "serde", serde(default = "default_hash_count"))]
+    pub hash_count: usize,
+
+    /// How aggregated feature weights are scaled before hashing
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub weight_transform: WeightTransform,
+
+    /// If true, embeddings are scaled to unit length
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub l2_normalize: bool,
+
+    /// If true, results are reproducible across runs and machines, regardless of core count.
+    /// Neighborhoods and features are aggregated in a fixed order and each embedding is written
+    /// to its own buffer rather than through hogwild updates, at a small cost in speed.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub deterministic: bool
+}
+
+#[cfg(feature = "serde")]
+fn default_hash_count() -> usize { 3 }
+
+/// Transform applied to a feature's aggregated page rank weight
+#[derive(Clone,Copy,Debug,PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WeightTransform {
+    /// ln(weight * |V|), clipped at zero, so only features more common in the neighborhood than
+    /// at random contribute.  Suits sparse graphs.
+    Log,
+
+    /// Square root of the weight, which dampens heavy features without discarding light ones
+    Sqrt,
+
+    /// The raw weight
+    Identity
+}
+
+impl Default for WeightTransform {
+    fn default() -> Self {
+        WeightTransform::Log
+    }
+}
+
+impl WeightTransform {
+    #[inline]
+    pub fn apply(&self, weight: f32, num_nodes: usize) -> f32 {
+        match self {
+            WeightTransform::Log => (weight * num_nodes as f32).ln().max(0f32),
+            WeightTransform::Sqrt => weight.sqrt(),
+            WeightTransform::Identity => weight
+        }
+    }
+}
+
+/// Page rank weighted features of each node's neighborhood, as (feature id, weight) pairs sorted
+/// by feature id.  Feature ids are those of the FeatureStore the neighborhoods were built from.
+#[derive(Clone,Debug)]
+pub struct SparseNeighborhoods {
+    rows: Vec<Vec<(usize, f32)>>
+}
+
+impl SparseNeighborhoods {
+    pub fn get_features(&self, node_id: NodeID) -> &[(usize, f32)] {
+        &self.rows[node_id]
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Total number of (feature, weight) pairs
+    pub fn nnz(&self) -> usize {
+        self.rows.iter().map(|row| row.len()).sum()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&[(usize, f32)]> {
+        self.rows.iter().map(|row| row.as_slice())
+    }
+
+    /// Writes one line per node in libsvm style, `feat_id:weight` pairs separated by spaces, which
+    /// most GBDT libraries read directly.
+    pub fn write_to(&self, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        for row in self.rows.iter() {
+            let line: Vec<_> = row.iter()
+                .map(|(feat_id, weight)| format!("{}:{}", feat_id, weight))
+                .collect();
+            writeln!(w, "{}", line.join(" "))?;
+        }
+        Ok(())
+    }
 }
 
 impl PPREmbed {
-    /// Learns the feature embeddings.
+    /// Learns the embeddings, additionally returning the time taken and the memory used by the
+    /// key structures.
+    pub fn learn_with_report<G: Graph + CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        features: &FeatureStore
+    ) -> Result<(EmbeddingStore, ResourceReport), GraphLibError> {
+        let tracker = ResourceTracker::new();
+        let embs = tracker.phase("embed", || self.learn(graph, features))?;
+        tracker.record_bytes("embeddings", embs.memory_bytes());
+        Ok((embs, tracker.report()))
+    }
+
+    /// Learns the embeddings within the runtime's thread pool.
+    pub fn learn_with_runtime<G: Graph + CDFGraph + Send + Sync>(
+        &self, 
+        runtime: &Runtime,
+        graph: &G, 
+        features: &FeatureStore
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        runtime.install(|| self.learn(graph, features))
+    }
+
+    /// Learns the feature embeddings.  Fails if the restart criteria is Sample::All or a
+    /// probability outside of (0, 1], or if push_eps isn't positive.
     pub fn learn<G: Graph + CDFGraph + Send + Sync>(
         &self, 
         graph: &G, 
         features: &FeatureStore
-    ) -> EmbeddingStore {
-        let embs = EmbeddingStore::new(graph.len(), self.dims, Distance::Cosine);
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("pprembed.learn", nodes = graph.len(), dims = self.dims,
+                                        push = self.push_eps.is_some()).entered();
+        #[cfg(feature = "tracing")]
+        let start = std::time::Instant::now();
+
+        let embs = self.embed_with(graph, features, graph.len(), |node_id| vec![(node_id, 1f32)])?;
+
+        #[cfg(feature = "tracing")]
+        tracing::info!(elapsed_ms = start.elapsed().as_secs_f64() * 1e3, "pprembed embedded nodes");
+
+        Ok(embs)
+    }
+
+    /// Embeds the neighborhoods of weighted seed sets, one embedding per set, such as a user's
+    /// recent interactions.  Walks, or pushes, restart at the seeds in proportion to their
+    /// weights, so the embedding is personalized to the set rather than to a single node.  Sets
+    /// with invalid weights are left as zeros.
+    pub fn embed_seeds<G: Graph + CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        features: &FeatureStore,
+        seed_sets: &[Vec<(NodeID, f32)>]
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        self.embed_with(graph, features, seed_sets.len(), |idx| seed_sets[idx].clone())
+    }
+
+    /// Aggregates the page rank weighted features of each node's neighborhood without hashing
+    /// them, such as for use as GBDT inputs.  Weights are the raw aggregated weights, before the
+    /// weight transform, and only those above eps are kept.
+    pub fn learn_sparse<G: Graph + CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        features: &FeatureStore
+    ) -> Result<SparseNeighborhoods, GraphLibError> {
+        let seeds_of = |node_id| vec![(node_id, 1f32)];
+        let (_, sparse) = self.run(graph, features, graph.len(), seeds_of, false, true)?;
+        Ok(sparse.expect("Sparse output requested"))
+    }
+
+    /// Learns both the hashed embeddings and the sparse neighborhoods in a single pass.
+    pub fn learn_dense_and_sparse<G: Graph + CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        features: &FeatureStore
+    ) -> Result<(EmbeddingStore, SparseNeighborhoods), GraphLibError> {
+        let seeds_of = |node_id| vec![(node_id, 1f32)];
+        let (embs, sparse) = self.run(graph, features, graph.len(), seeds_of, true, true)?;
+        Ok((embs.expect("Dense output requested"), sparse.expect("Sparse output requested")))
+    }
+
+    fn embed_with<G: Graph + CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        features: &FeatureStore,
+        n: usize,
+        seeds_of: impl Fn(usize) -> Vec<(NodeID, f32)> + Sync
+    ) -> Result<EmbeddingStore, GraphLibError> {
+        let (embs, _) = self.run(graph, features, n, seeds_of, true, false)?;
+        Ok(embs.expect("Dense output requested"))
+    }
+
+    // Validates the restart criteria, returning the Forward Push estimator if enabled
+    fn forward_push(&self) -> Result<Option<ForwardPush>, GraphLibError> {
+        // Match the expected walk length of the restart criteria
+        let alpha = match self.steps {
+            Sample::Probability(p) if p > 0. && p <= 1. => p,
+            Sample::Fixed(steps) => 1. / (steps as f32 + 1.),
+            _ => return Err("steps must be fixed or a probability in (0, 1]!".into())
+        };
+        match self.push_eps {
+            Some(eps) => Ok(Some(ForwardPush::new(alpha, eps)?)),
+            None => Ok(None)
+        }
+    }
+
+    // Aggregates `n` neighborhoods, where the seeds of each are produced on demand so we never
+    // hold them all at once, into hashed embeddings, sparse feature weights, or both.
+    fn run<G: Graph + CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        features: &FeatureStore,
+        n: usize,
+        seeds_of: impl Fn(usize) -> Vec<(NodeID, f32)> + Sync,
+        dense: bool,
+        sparse: bool
+    ) -> Result<(Option<EmbeddingStore>, Option<SparseNeighborhoods>), GraphLibError> {
+        let push = self.forward_push()?;
         let hasher = FeatureHasher::new(self.dims);
-        let pb = CLProgressBar::new(graph.len() as u64, true);
+
+        // Walks share alias tables over the whole graph rather than rebuilding them per node
+        let tables = if push.is_none() { Some(EdgeAliasTable::new(graph)) } else { None };
+
+        // Forward push itself is deterministic, so fixing the summation order is all it takes for
+        // reproducible results
+        let ordered = self.deterministic || push.is_some();
+
+        let num_nodes = graph.len();
+        let pb = CLProgressBar::new(n as u64, true);
         pb.update_message(|msg| write!(msg, "Embedding...").expect("Shouldn't fail"));
-        (0..graph.len()).into_par_iter().for_each(|node_id| {
-            let rwr = RWR {
-                steps: self.steps,
-                walks: self.num_walks,
-                beta: self.beta,
-                single_threaded: false,
-                seed: self.seed + node_id as u64
+        let process = |idx: usize, emb: Option<&mut [f32]>| {
+            let seeds = seeds_of(idx);
+            let neighborhood: HashMap<NodeID, f32> = if let Some(push) = push.as_ref() {
+                push.compute_seeds(graph, &seeds).into_iter()
+                    .map(|(k, v)| (k, v / (graph.degree(k) as f32).powf(self.beta)))
+                    .collect()
+            } else {
+                // Single nodes keep their historical seeds
+                let seed = match seeds.as_slice() {
+                    [(node_id, _)] => *node_id,
+                    _ => idx
+                };
+                let rwr = RWR {
+                    steps: self.steps,
+                    walks: self.num_walks,
+                    beta: self.beta,
+                    single_threaded: false,
+                    seed: self.seed + seed as u64,
+                    rng_kind: self.rng_kind,
+                    deterministic: self.deterministic
+                };
+                let tables = tables.as_ref().expect("Built when walking!");
+                rwr.sample_bfs_seeds_with(graph, tables, &seeds)
             };
 
+            // Floating point sums depend on the order of the terms, which hash maps don't fix
+            let mut neighborhood: Vec<_> = neighborhood.into_iter().collect();
+            if ordered {
+                neighborhood.sort_unstable_by_key(|(node_id, _)| *node_id);
+            }
+
             let mut feat_maps = HashMap::new();
-            rwr.sample_bfs(graph, node_id)
+            neighborhood
                 .into_iter()
                 .for_each(|(node_id, weight)| {
                     features.get_features(node_id).iter().for_each(|feat_id| {
@@ -63,22 +311,51 @@ impl PPREmbed {
                     });
                 });
 
-            let emb = embs.get_embedding_mut_hogwild(node_id);
-            let n = graph.len();
-            feat_maps.into_iter()
+            let mut row: Vec<_> = feat_maps.into_iter()
                 .filter(|(_,w)| *w > self.eps)
-                .for_each(|(feat_id, weight)| {
-                for hash_num in 0..3 {
-                    let (sign, dim) = hasher.hash(feat_id, hash_num);
-                    emb[dim] += sign as f32 * (weight * n as f32).ln().max(0f32);
+                .collect();
+            if sparse || ordered {
+                row.sort_unstable_by_key(|(feat_id, _)| *feat_id);
+            }
+
+            if let Some(emb) = emb {
+                row.iter().for_each(|(feat_id, weight)| {
+                    let weight = self.weight_transform.apply(*weight, num_nodes);
+                    for hash_num in 0..self.hash_count {
+                        let (sign, dim) = hasher.hash(*feat_id, hash_num);
+                        emb[dim] += sign as f32 * weight;
+                    }
+                });
+
+                if self.l2_normalize {
+                    let norm = emb.iter().map(|v| v * v).sum::<f32>().sqrt();
+                    if norm > 0f32 {
+                        emb.iter_mut().for_each(|v| *v /= norm);
+                    }
                 }
-            });
+            }
 
             pb.inc(1);
-        });
-        pb.finish();
+            if sparse { row } else { Vec::with_capacity(0) }
+        };
 
-        embs
+        let (embs, rows) = if dense && self.deterministic {
+            // Each task owns its slice of the output, so nothing is shared between threads
+            let mut values = vec![0f32; n * self.dims];
+            let rows: Vec<_> = values.par_chunks_mut(self.dims).enumerate()
+                .map(|(idx, emb)| process(idx, Some(emb)))
+                .collect();
+            (EmbeddingStore::new_with_vec(n, self.dims, Distance::Cosine, values), rows)
+        } else {
+            let embs = if dense { Some(EmbeddingStore::new(n, self.dims, Distance::Cosine)) } else { None };
+            let rows: Vec<_> = (0..n).into_par_iter()
+                .map(|idx| process(idx, embs.as_ref().map(|es| es.get_embedding_mut_hogwild(idx))))
+                .collect();
+            (embs, rows)
+        };
+        pb.finish();
+        let sparse = if sparse { Some(SparseNeighborhoods { rows }) } else { None };
+        Ok((embs, sparse))
     }
 }
 
@@ -11,8 +11,8 @@ use rand_xorshift::XorShiftRng;
 use simple_grad::*;
 
 use crate::algos::rwr::RWR;
-use crate::algos::utils::Sample;
-use crate::sampler::Weighted;
+use crate::algos::utils::{Sample,RngKind,EdgeAliasTable};
+use crate::sampler::Alias;
 use crate::graph::{Graph as CGraph, CDFGraph, NodeID};
 use crate::embeddings::{EmbeddingStore,randomize_embedding_store};
 use crate::distance::Distance;
@@ -110,7 +110,7 @@ impl PprRank {
         let feature_embeddings = if let Some(embs) = feature_embeddings {
             embs
         } else {
-            let mut fe = EmbeddingStore::new(features.num_features(), self.dims, Distance::Cosine);
+            let mut fe = EmbeddingStore::new(features.num_embeddings(), self.dims, Distance::Cosine);
             // Initialize embeddings as random
             randomize_embedding_store(&mut fe, &mut rng);
             fe
@@ -282,9 +282,14 @@ impl PprRank {
             walks: self.num_walks,
             beta: self.beta,
             single_threaded: false,
-            seed: seed + 13
+            seed: seed + 13,
+            rng_kind: RngKind::XorShift,
+            deterministic: false
         };
 
+        // Every node's walks share the same alias tables
+        let tables = EdgeAliasTable::new(graph);
+
         let pb = CLProgressBar::new(graph.len() as u64, self.indicator);
         pb.update_message(|msg| write!(msg, "Computing random walks...").unwrap());
         let idxs = (0..graph.len()).collect::<Vec<_>>();
@@ -293,7 +298,7 @@ impl PprRank {
                 let mut nodes = Vec::with_capacity(self.k);
                 let mut weights = Vec::with_capacity(self.k);
                 
-                let scores = rwr.sample(graph, &Weighted, *node_id).into_iter();
+                let scores = rwr.sample(graph, &Alias(&tables), *node_id).into_iter();
                 let mut scores: Vec<_> = scores.collect();
                 scores.sort_by_key(|(_k, v)| FloatOrd(-*v));
                 scores.into_iter()
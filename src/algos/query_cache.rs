@@ -0,0 +1,197 @@
+//! LRU cache for nearest neighbor queries.  Query traffic tends to be heavily repeated, so caching
+//! results avoids a full index search for each hit.  Queries are keyed by a hash of the query
+//! vector, quantized to a fixed resolution so tiny floating point differences still hit, along
+//! with the search parameters.
+use std::collections::BTreeMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash,Hasher};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64,Ordering};
+
+use hashbrown::HashMap;
+
+use crate::error::GraphLibError;
+
+/// Hit and miss counts for a cache
+#[derive(Clone,Copy,Debug,Default,PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64
+}
+
+impl CacheStats {
+    /// Fraction of lookups which were served from the cache
+    pub fn hit_rate(&self) -> f32 {
+        let total = self.hits + self.misses;
+        if total == 0 { 0. } else { self.hits as f32 / total as f32 }
+    }
+}
+
+struct Lru<V> {
+    /// Maps the query key to the value and the tick it was last accessed
+    entries: HashMap<u64, (V, u64)>,
+
+    /// Orders keys by last access, oldest first
+    recency: BTreeMap<u64, u64>,
+
+    tick: u64
+}
+
+impl <V> Lru<V> {
+    fn touch(&mut self, key: u64) {
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            self.recency.remove(&entry.1);
+            entry.1 = self.tick;
+            self.recency.insert(self.tick, key);
+        }
+    }
+}
+
+/// Threadsafe LRU cache keyed by quantized query vectors.
+pub struct QueryCache<V> {
+    capacity: usize,
+    resolution: f32,
+    lru: Mutex<Lru<V>>,
+    hits: AtomicU64,
+    misses: AtomicU64
+}
+
+impl <V: Clone> QueryCache<V> {
+
+    /// Creates a new cache holding up to `capacity` results.  Query vectors are quantized to
+    /// multiples of `resolution` before hashing.
+    pub fn new(capacity: usize, resolution: f32) -> Result<Self, GraphLibError> {
+        if capacity == 0 {
+            return Err("Capacity must be greater than zero!".into())
+        }
+        if !(resolution > 0.) {
+            return Err("Resolution must be greater than zero!".into())
+        }
+
+        Ok(QueryCache {
+            capacity,
+            resolution,
+            lru: Mutex::new(Lru { entries: HashMap::new(), recency: BTreeMap::new(), tick: 0 }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0)
+        })
+    }
+
+    /// Hashes the quantized query along with the search parameters.
+    pub fn key(&self, query: &[f32], params: &[usize]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        query.iter().for_each(|qi| ((qi / self.resolution).round() as i64).hash(&mut hasher));
+        params.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns the cached value for the query, computing and caching it with `f` on a miss.  The
+    /// lock isn't held while computing, so concurrent misses on the same query may each compute
+    /// the value.
+    pub fn get_or_insert_with(
+        &self,
+        query: &[f32],
+        params: &[usize],
+        f: impl FnOnce() -> V
+    ) -> V {
+        let key = self.key(query, params);
+        {
+            let mut lru = self.lru.lock().expect("Mutex poisoned!");
+            if lru.entries.contains_key(&key) {
+                lru.touch(key);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return lru.entries[&key].0.clone()
+            }
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let value = f();
+        self.insert(key, value.clone());
+        value
+    }
+
+    fn insert(&self, key: u64, value: V) {
+        let mut lru = self.lru.lock().expect("Mutex poisoned!");
+        lru.tick += 1;
+        let tick = lru.tick;
+        if let Some((_, old_tick)) = lru.entries.insert(key, (value, tick)) {
+            lru.recency.remove(&old_tick);
+        }
+        lru.recency.insert(tick, key);
+
+        // Evict the least recently used
+        while lru.entries.len() > self.capacity {
+            let (&oldest, &old_key) = lru.recency.iter().next()
+                .expect("Recency and entries out of sync!");
+            lru.recency.remove(&oldest);
+            lru.entries.remove(&old_key);
+        }
+    }
+
+    /// Number of cached results
+    pub fn len(&self) -> usize {
+        self.lru.lock().expect("Mutex poisoned!").entries.len()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Drops all cached results and resets the stats.  Should be called whenever the underlying
+    /// index or embeddings change.
+    pub fn clear(&self) {
+        let mut lru = self.lru.lock().expect("Mutex poisoned!");
+        lru.entries.clear();
+        lru.recency.clear();
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod query_cache_tests {
+    use super::*;
+
+    #[test]
+    fn test_hits_and_misses() {
+        let cache = QueryCache::new(2, 1e-3).unwrap();
+        assert_eq!(cache.get_or_insert_with(&[1., 2.], &[10], || 1), 1);
+        // Within the quantization resolution, so should hit
+        assert_eq!(cache.get_or_insert_with(&[1.0001, 2.], &[10], || 2), 1);
+        // Different parameters miss
+        assert_eq!(cache.get_or_insert_with(&[1., 2.], &[5], || 3), 3);
+
+        let stats = cache.stats();
+        assert_eq!(stats, CacheStats { hits: 1, misses: 2 });
+        assert_eq!(stats.hit_rate(), 1. / 3.);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let cache = QueryCache::new(2, 1e-3).unwrap();
+        cache.get_or_insert_with(&[1.], &[], || 1);
+        cache.get_or_insert_with(&[2.], &[], || 2);
+
+        // Touch 1 so 2 is the least recently used
+        cache.get_or_insert_with(&[1.], &[], || 0);
+        cache.get_or_insert_with(&[3.], &[], || 3);
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.get_or_insert_with(&[1.], &[], || 0), 1);
+        assert_eq!(cache.get_or_insert_with(&[2.], &[], || 4), 4);
+
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.stats().hit_rate(), 0.);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(QueryCache::<usize>::new(0, 1.).is_err());
+        assert!(QueryCache::<usize>::new(1, 0.).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! Selective refresh of node embeddings as the graph changes.  Rather than re-embedding every node
+//! and refitting the index after each batch of edge updates, we track which nodes' neighborhoods
+//! were touched and only recompute those, moving them within the existing Ann index.  Feature
+//! embeddings are left alone; see `EmbeddingPropagation::fine_tune` for updating those.
+use hashbrown::HashSet;
+
+use crate::graph::{Graph,NodeID};
+use crate::graph::dynamic::EdgeUpdate;
+use crate::embeddings::EmbeddingStore;
+use crate::feature_store::FeatureStore;
+use crate::error::GraphLibError;
+use crate::algos::ann::Ann;
+use crate::algos::ep::EmbeddingPropagation;
+use crate::algos::ep::model::Model;
+
+/// Records the nodes whose embeddings are out of date
+#[derive(Clone,Debug)]
+pub struct StalenessTracker {
+    /// How far a change spreads: 0 only marks the endpoints of an updated edge, 1 also marks
+    /// their neighbors, and so on.
+    hops: usize,
+
+    stale: HashSet<NodeID>
+}
+
+impl StalenessTracker {
+    pub fn new(hops: usize) -> Self {
+        StalenessTracker { hops, stale: HashSet::new() }
+    }
+
+    /// Marks a single node as stale, such as one whose features changed
+    pub fn mark(&mut self, node_id: NodeID) {
+        self.stale.insert(node_id);
+    }
+
+    /// Marks the endpoints of an edge update, and nodes within `hops` of them, as stale.  The
+    /// graph should already reflect the update so new neighbors are reached.
+    pub fn record(&mut self, graph: &impl Graph, update: &EdgeUpdate) {
+        let (u, v) = match update {
+            EdgeUpdate::Insert(u, v, _) => (*u, *v),
+            EdgeUpdate::Delete(u, v) => (*u, *v)
+        };
+
+        let mut frontier = vec![u, v];
+        self.stale.extend(frontier.iter().cloned());
+        for _ in 0..self.hops {
+            let mut next = Vec::new();
+            for node_id in frontier.iter().filter(|node_id| **node_id < graph.len()) {
+                for t_n in graph.get_edges(*node_id).0.iter() {
+                    if self.stale.insert(*t_n) {
+                        next.push(*t_n);
+                    }
+                }
+            }
+            frontier = next;
+        }
+    }
+
+    /// Records every update in a delta stream
+    pub fn record_all<'a>(&mut self, graph: &impl Graph, updates: impl IntoIterator<Item=&'a EdgeUpdate>) {
+        updates.into_iter().for_each(|update| self.record(graph, update));
+    }
+
+    /// Stale nodes, in ascending order
+    pub fn stale_nodes(&self) -> Vec<NodeID> {
+        let mut nodes: Vec<_> = self.stale.iter().cloned().collect();
+        nodes.sort_unstable();
+        nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.stale.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stale.is_empty()
+    }
+
+    /// Recomputes the embeddings of every stale node with the model's aggregation, writes them
+    /// into the store, and moves them within the index, if provided.  The store must already
+    /// cover any new nodes.  Returns the refreshed nodes and clears them from the tracker.
+    pub fn refresh<M: Model>(
+        &mut self,
+        ep: &EmbeddingPropagation,
+        features: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        model: &M,
+        es: &mut EmbeddingStore,
+        ann: Option<&mut Ann>
+    ) -> Result<Vec<NodeID>, GraphLibError> {
+        let nodes = self.stale_nodes();
+        if nodes.is_empty() {
+            return Ok(nodes)
+        }
+
+        ep.embed_subset(features, feature_embeddings, model, &nodes, es)?;
+        if let Some(ann) = ann {
+            ann.update(es, &nodes)?;
+        }
+        self.stale.clear();
+        Ok(nodes)
+    }
+}
+
+#[cfg(test)]
+mod refresh_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+    use crate::graph::dynamic::DynamicGraph;
+    use crate::algos::ep::model::AveragedFeatureModel;
+    use crate::algos::utils::Sample;
+    use crate::distance::Distance;
+
+    // Path 0-1-2-3-4
+    fn build_graph() -> DynamicGraph {
+        let mut edges = Vec::new();
+        for n in 0..4 {
+            edges.push((n, n + 1, 1f32));
+            edges.push((n + 1, n, 1f32));
+        }
+        DynamicGraph::new(CumCSR::convert(CSR::construct_from_edges(edges, false)))
+    }
+
+    #[test]
+    fn test_record() {
+        let mut graph = build_graph();
+        let update = EdgeUpdate::Insert(0, 5, 1.);
+        graph.apply(vec![update]).unwrap();
+
+        let mut tracker = StalenessTracker::new(0);
+        tracker.record(&graph, &update);
+        assert_eq!(tracker.stale_nodes(), vec![0, 5]);
+
+        let mut tracker = StalenessTracker::new(1);
+        tracker.record_all(&graph, graph.log());
+        tracker.mark(4);
+        assert_eq!(tracker.stale_nodes(), vec![0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn test_refresh() {
+        let graph = build_graph();
+        let mut features = FeatureStore::new(graph.len());
+        for node_id in 0..graph.len() {
+            features.set_features(node_id, [("node", node_id.to_string())].into_iter());
+        }
+
+        let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+        let ep = EmbeddingPropagation {
+            batch_size: 4,
+            d_model: 4,
+            valid_pct: 0.0,
+            passes: 1,
+            ..EmbeddingPropagation::default()
+        };
+        let fe = ep.learn(&graph, &features, None, &model).unwrap();
+        let mut es = ep.embed_nodes(&graph, &features, &fe, &model);
+        let mut ann = Ann::new();
+        ann.fit(&es, 2, 2, None, None, None, 2023).unwrap();
+
+        // Node 2 takes on node 4's features
+        features.set_features(2, [("node", "4")].into_iter());
+        let mut tracker = StalenessTracker::new(0);
+        tracker.mark(2);
+        let refreshed = tracker.refresh(&ep, &features, &fe, &model, &mut es, Some(&mut ann)).unwrap();
+        assert_eq!(refreshed, vec![2]);
+        assert!(tracker.is_empty());
+        assert_eq!(es.get_embedding(2), es.get_embedding(4));
+
+        let query = es.get_embedding(4).to_vec();
+        let results = ann.predict(&es, &query, 2, Some(5)).unwrap();
+        assert!(results.iter().all(|nd| nd.1 == 2 || nd.1 == 4));
+
+        let mut small = EmbeddingStore::new(2, es.dims(), Distance::Cosine);
+        tracker.mark(2);
+        assert!(tracker.refresh(&ep, &features, &fe, &model, &mut small, None).is_err());
+    }
+}
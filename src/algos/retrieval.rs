@@ -0,0 +1,134 @@
+//! Two stage retrieval over an EmbeddingStore.  Rather than scanning every embedding, we first
+//! score a small set of cluster centroids against the query, select the closest clusters, and then
+//! exactly score only the members of those clusters.  This is the classic IVF-flat approach: it's
+//! much simpler to operate than the tree indexes and works well for mid-sized stores where the
+//! clusters are reasonably balanced.
+use rayon::prelude::*;
+
+use crate::graph::NodeID;
+use crate::embeddings::{EmbeddingStore,Entity};
+use crate::algos::graph_ann::{TopK,NodeDistance};
+use crate::error::GraphLibError;
+
+/// Stores the cluster centroids along with the members of each cluster.
+pub struct ClusterRetrieval {
+    /// Centroid for each cluster, where the NodeID is the cluster id
+    centroids: EmbeddingStore,
+
+    /// Members of each cluster
+    clusters: Vec<Vec<NodeID>>
+}
+
+impl ClusterRetrieval {
+
+    /// Builds the cluster lists from a set of centroids and the cluster assignment for each node in
+    /// the embedding store.
+    pub fn new(
+        centroids: EmbeddingStore,
+        assignments: &[usize]
+    ) -> Result<Self, GraphLibError> {
+        let mut clusters = vec![Vec::new(); centroids.len()];
+        for (node_id, cluster_id) in assignments.iter().enumerate() {
+            if *cluster_id >= clusters.len() {
+                return Err(GraphLibError::InvalidInput(format!(
+                    "Cluster assignment {} exceeds the number of centroids!", cluster_id)))
+            }
+            clusters[*cluster_id].push(node_id);
+        }
+        Ok(ClusterRetrieval { centroids, clusters })
+    }
+
+    pub fn num_clusters(&self) -> usize {
+        self.clusters.len()
+    }
+
+    pub fn centroids(&self) -> &EmbeddingStore {
+        &self.centroids
+    }
+
+    pub fn cluster_members(&self, cluster_id: usize) -> &[NodeID] {
+        &self.clusters[cluster_id]
+    }
+
+    /// Returns the closest `n_clusters` clusters to the query embedding.
+    pub fn select_clusters(&self, query: &[f32], n_clusters: usize) -> Vec<NodeDistance> {
+        self.centroids.nearest_neighbor(&Entity::Embedding(query), n_clusters, |_| true)
+    }
+
+    /// Finds the top K nearest nodes to the query, exhaustively scoring only the members of the
+    /// closest `n_clusters` clusters.
+    pub fn search<F>(
+        &self,
+        es: &EmbeddingStore,
+        query: &[f32],
+        k: usize,
+        n_clusters: usize,
+        filter: F
+    ) -> Vec<NodeDistance>
+        where F: Sync + Fn(NodeID) -> bool
+    {
+        // Stage 1: Select the candidate clusters
+        let selected = self.select_clusters(query, n_clusters);
+
+        // Stage 2: Exactly score members within the selected clusters
+        let distance = es.distance();
+        selected.par_iter().flat_map(|nd| {
+            self.clusters[nd.1].par_iter()
+        }).filter(|node_id| filter(**node_id))
+        .fold(|| TopK::new(k), |mut acc, node_id| {
+            let d = distance.compute(query, es.get_embedding(*node_id));
+            acc.push(*node_id, d);
+            acc
+        }).reduce(|| TopK::new(k), |mut tk1, tk2| {
+            tk1.extend(tk2);
+            tk1
+        }).into_sorted()
+    }
+
+}
+
+#[cfg(test)]
+mod retrieval_tests {
+    use super::*;
+    use crate::distance::Distance;
+
+    fn build_store() -> EmbeddingStore {
+        let mut es = EmbeddingStore::new(6, 2, Distance::Euclidean);
+        es.set_embedding(0, &[0., 0.]);
+        es.set_embedding(1, &[0., 1.]);
+        es.set_embedding(2, &[1., 0.]);
+        es.set_embedding(3, &[10., 10.]);
+        es.set_embedding(4, &[10., 11.]);
+        es.set_embedding(5, &[11., 10.]);
+        es
+    }
+
+    #[test]
+    fn test_two_stage_search() {
+        let es = build_store();
+        let mut centroids = EmbeddingStore::new(2, 2, Distance::Euclidean);
+        centroids.set_embedding(0, &[0.5, 0.5]);
+        centroids.set_embedding(1, &[10.5, 10.5]);
+
+        let cr = ClusterRetrieval::new(centroids, &[0, 0, 0, 1, 1, 1]).unwrap();
+        assert_eq!(cr.num_clusters(), 2);
+
+        let results = cr.search(&es, &[9.5, 10.], 2, 1, |_| true);
+        let ids: Vec<_> = results.iter().map(|nd| nd.1).collect();
+        assert_eq!(ids, vec![3, 4]);
+
+        // Filtering should remove nodes from consideration
+        let results = cr.search(&es, &[0., 0.], 5, 1, |node_id| node_id != 0);
+        let ids: Vec<_> = results.iter().map(|nd| nd.1).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(!ids.contains(&0));
+    }
+
+    #[test]
+    fn test_bad_assignments() {
+        let centroids = EmbeddingStore::new(2, 2, Distance::Euclidean);
+        assert!(matches!(ClusterRetrieval::new(centroids, &[0, 2]),
+                         Err(GraphLibError::InvalidInput(_))));
+    }
+
+}
@@ -1,21 +1,58 @@
 //! Classic Random walk with Restarts.  This uses the Rp3b algorithm to allow biasing toward/away
 //! from popular nodes to rarer nodes.  
+use std::fmt::Write;
+
 use hashbrown::{HashMap,HashSet};
+use float_ord::FloatOrd;
 use rand::prelude::*;
-use rand_xorshift::XorShiftRng;
-use rand_distr::{Distribution,Uniform};
 use rayon::prelude::*;
 
 use crate::graph::{Graph,NodeID,CDFtoP,CDFGraph};
-use crate::sampler::{Sampler, weighted_sample_cdf};
-use crate::algos::utils::Sample;
+use crate::sampler::Sampler;
+use crate::algos::utils::{Sample,AliasTable,EdgeAliasTable,RngKind};
+use crate::progress::CLProgressBar;
 
 pub struct RWR {
     pub steps: Sample,
     pub walks: usize,
     pub beta: f32,
     pub single_threaded: bool,
-    pub seed: u64
+    pub seed: u64,
+
+    /// Generator the walks draw from
+    pub rng_kind: RngKind,
+
+    /// If true, `sample_bfs` visits each level in node order, so the results are reproducible
+    /// for a seed at the cost of a sort per level
+    pub deterministic: bool
+}
+
+// Where walks start and restart.  A single seed never consumes randomness, so single source
+// walks are the same whether or not they go through a seed set.
+enum Seeds {
+    Single(NodeID),
+    Weighted(Vec<NodeID>, AliasTable)
+}
+
+impl Seeds {
+    // None if the weights are empty, negative, or sum to zero
+    fn new(seeds: &[(NodeID, f32)]) -> Option<Self> {
+        let weights: Vec<_> = seeds.iter().map(|(_, w)| *w).collect();
+        let table = AliasTable::new(&weights)?;
+        if seeds.len() == 1 {
+            Some(Seeds::Single(seeds[0].0))
+        } else {
+            Some(Seeds::Weighted(seeds.iter().map(|(node_id, _)| *node_id).collect(), table))
+        }
+    }
+
+    #[inline]
+    fn sample(&self, rng: &mut impl Rng) -> NodeID {
+        match self {
+            Seeds::Single(node_id) => *node_id,
+            Seeds::Weighted(nodes, table) => nodes[table.sample(rng)]
+        }
+    }
 }
 
 impl RWR {
@@ -25,11 +62,36 @@ impl RWR {
         graph: &G, 
         sampler: &impl Sampler<G>,
         start_node: NodeID
+    ) -> HashMap<NodeID, f32> {
+        self.sample_from(graph, sampler, &Seeds::Single(start_node))
+    }
+
+    /// Samples personalized PageRank over a weighted seed set, such as a user's recent
+    /// interactions.  Each walk starts at a seed drawn proportionally to its weight, and walks
+    /// reaching a dead end restart at a freshly drawn seed, matching `ForwardPush::compute_seeds`.
+    /// Empty if the weights are negative or don't sum to a positive value.
+    pub fn sample_seeds<G: Graph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        sampler: &impl Sampler<G>,
+        seeds: &[(NodeID, f32)]
+    ) -> HashMap<NodeID, f32> {
+        match Seeds::new(seeds) {
+            Some(seeds) => self.sample_from(graph, sampler, &seeds),
+            None => HashMap::new()
+        }
+    }
+
+    fn sample_from<G: Graph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        sampler: &impl Sampler<G>,
+        seeds: &Seeds
     ) -> HashMap<NodeID, f32> {
         if self.single_threaded {
-            self.sample_st(graph, sampler, start_node)
+            self.sample_st(graph, sampler, seeds)
         } else {
-            self.sample_mt(graph, sampler, start_node)
+            self.sample_mt(graph, sampler, seeds)
         }
     }
 
@@ -37,12 +99,12 @@ impl RWR {
         &self, 
         graph: &G, 
         sampler: &impl Sampler<G>,
-        start_node: NodeID
+        seeds: &Seeds
     ) -> HashMap<NodeID, f32> {
         let mut ret = (0..self.walks).into_par_iter()
             .map(|idx| {
-                let mut rng = XorShiftRng::seed_from_u64(self.seed + idx as u64);
-                self.walk(graph, sampler, start_node, &mut rng) 
+                let mut rng = self.rng_kind.seed_from_u64(self.seed + idx as u64);
+                self.walk_seeds(graph, sampler, seeds, &mut rng) 
             }).fold(|| HashMap::new(), |mut acc, node_id| {
                 *acc.entry(node_id).or_insert(0f32) += 1.; 
                 acc
@@ -66,13 +128,13 @@ impl RWR {
         &self, 
         graph: &G, 
         sampler: &impl Sampler<G>,
-        start_node: NodeID
+        seeds: &Seeds
     ) -> HashMap<NodeID, f32> {
         let mut counts = HashMap::new();
-        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+        let mut rng = self.rng_kind.seed_from_u64(self.seed);
 
         (0..self.walks).for_each(|_| {
-            let node = self.walk(graph, sampler, start_node, &mut rng);
+            let node = self.walk_seeds(graph, sampler, seeds, &mut rng);
             *counts.entry(node).or_insert(0f32) += 1f32; 
         });
 
@@ -84,9 +146,49 @@ impl RWR {
         counts 
     }
 
+    /// Samples the neighborhoods of many source nodes at once, parallelizing across sources
+    /// rather than walks.  Each source runs its walks on a single thread, seeded by the RWR's seed
+    /// plus the source id, so results don't depend on the number of threads.  Neighborhoods are
+    /// returned in the same order as the sources, sorted by descending score; if `k` is provided,
+    /// only the top k neighbors of each are kept to bound memory.
+    pub fn sample_batch<G: Graph + Send + Sync>(
+        &self,
+        graph: &G,
+        sampler: &impl Sampler<G>,
+        sources: &[NodeID],
+        k: Option<usize>,
+        indicator: bool
+    ) -> Vec<Vec<(NodeID, f32)>> {
+        let pb = CLProgressBar::new(sources.len() as u64, indicator);
+        pb.update_message(|msg| write!(msg, "Sampling neighborhoods...").expect("Shouldn't fail"));
+        let neighborhoods = sources.par_iter().map(|source| {
+            let rwr = RWR {
+                steps: self.steps,
+                walks: self.walks,
+                beta: self.beta,
+                single_threaded: true,
+                seed: self.seed + *source as u64,
+                rng_kind: self.rng_kind,
+                deterministic: self.deterministic
+            };
+            let mut scores: Vec<_> = rwr.sample_st(graph, sampler, &Seeds::Single(*source)).into_iter().collect();
+            scores.sort_by_key(|(node_id, score)| (FloatOrd(-*score), *node_id));
+            if let Some(k) = k {
+                scores.truncate(k);
+            }
+            pb.inc(1);
+            scores
+        }).collect();
+        pb.finish();
+        neighborhoods
+    }
+
+    // Takes a step for each walk at the node, drawing the steps from the graph's alias tables if
+    // provided or from a table over just this node's edges otherwise
     fn sample_level<G: CDFGraph + Send + Sync>(
         &self, 
         graph: &G, 
+        tables: Option<&EdgeAliasTable>,
         start_node: NodeID,
         walks: usize,
         rng: &mut impl Rng,
@@ -94,40 +196,97 @@ impl RWR {
     ) {
 
         let (edges, weights) = graph.get_edges(start_node);
-        let probs = CDFtoP::new(weights);
-        let mut cur_idx = weighted_sample_cdf(weights, rng);
         if edges.len() > 0 {
-            let dist = Uniform::new(0, edges.len());
+            // Matches EdgeAliasTable, which walks degenerate weights, such as all zeros, uniformly
+            let local = match tables {
+                Some(_) => None,
+                None => AliasTable::from_cdf(weights)
+                    .or_else(|| AliasTable::new(&vec![1f32; edges.len()]))
+            };
             for _ in 0..walks {
-                let candidate = dist.sample(rng);
-                let cur_p = probs.prob(cur_idx);
-                let new_p = probs.prob(candidate);
-                if rng.gen::<f32>() < new_p / cur_p {
-                    cur_idx = candidate;
-                }
-                *entries.entry(edges[cur_idx]).or_insert(0) += 1;
+                let offset = match (tables, local.as_ref()) {
+                    (Some(tables), _) => tables.sample_offset(graph, start_node, rng),
+                    (None, table) => table.map(|table| table.sample(rng))
+                }.expect("Node has edges!");
+                *entries.entry(edges[offset]).or_insert(0) += 1;
             }
         } else {
             *entries.entry(start_node).or_insert(0) += 1;
         }
     }
 
+    /// Samples the neighborhood of a node by advancing all walks a level at a time.  Each visited
+    /// node gets its own alias table; when sampling many nodes of the same graph, build an
+    /// EdgeAliasTable once and use `sample_bfs_with` instead.
     pub fn sample_bfs<G: CDFGraph + Send + Sync>(
         &self, 
         graph: &G, 
         start_node: NodeID
     ) -> HashMap<NodeID, f32> {
-        let mut rng = XorShiftRng::seed_from_u64(self.seed as u64);
+        self.sample_bfs_from(graph, None, &Seeds::Single(start_node))
+    }
+
+    /// Same as `sample_bfs`, but steps are drawn from alias tables built from the graph.
+    pub fn sample_bfs_with<G: CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        tables: &EdgeAliasTable,
+        start_node: NodeID
+    ) -> HashMap<NodeID, f32> {
+        self.sample_bfs_from(graph, Some(tables), &Seeds::Single(start_node))
+    }
+
+    /// Same as `sample_bfs`, but walks start at a weighted seed set.  Empty if the weights are
+    /// negative or don't sum to a positive value.
+    pub fn sample_bfs_seeds<G: CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        seeds: &[(NodeID, f32)]
+    ) -> HashMap<NodeID, f32> {
+        match Seeds::new(seeds) {
+            Some(seeds) => self.sample_bfs_from(graph, None, &seeds),
+            None => HashMap::new()
+        }
+    }
+
+    /// Same as `sample_bfs_seeds`, but steps are drawn from alias tables built from the graph.
+    pub fn sample_bfs_seeds_with<G: CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        tables: &EdgeAliasTable,
+        seeds: &[(NodeID, f32)]
+    ) -> HashMap<NodeID, f32> {
+        match Seeds::new(seeds) {
+            Some(seeds) => self.sample_bfs_from(graph, Some(tables), &seeds),
+            None => HashMap::new()
+        }
+    }
+
+    fn sample_bfs_from<G: CDFGraph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        tables: Option<&EdgeAliasTable>,
+        seeds: &Seeds
+    ) -> HashMap<NodeID, f32> {
+        let mut rng = self.rng_kind.seed_from_u64(self.seed);
         let mut ret = HashMap::new();
 
         let mut counts = HashMap::new();
         let mut next_counts = HashMap::new();
-        counts.insert(start_node, self.walks);
+        for _ in 0..self.walks {
+            *counts.entry(seeds.sample(&mut rng)).or_insert(0usize) += 1;
+        }
         let mut pass = 1;
         loop {
             if counts.len() == 0 { break }
-            counts.drain().for_each(|(node_id, num_walks)| {
-                self.sample_level(graph, node_id, num_walks, &mut rng, &mut next_counts);
+
+            // Visit nodes in a fixed order so the rng stream, and the result, is reproducible
+            let mut level: Vec<_> = counts.drain().collect();
+            if self.deterministic {
+                level.sort_unstable_by_key(|(node_id, _)| *node_id);
+            }
+            level.into_iter().for_each(|(node_id, num_walks)| {
+                self.sample_level(graph, tables, node_id, num_walks, &mut rng, &mut next_counts);
             });
 
             match self.steps {
@@ -169,13 +328,26 @@ impl RWR {
         start_node: NodeID,
         rng: &mut impl Rng
     ) -> NodeID {
-       let mut cur_node = start_node;
+        self.walk_seeds(graph, sampler, &Seeds::Single(start_node), rng)
+    }
+
+    // Runs a random walk from a sampled seed, restarting at a new seed on dead ends
+    fn walk_seeds<G: Graph + Send + Sync>(
+        &self, 
+        graph: &G, 
+        sampler: &impl Sampler<G>,
+        seeds: &Seeds,
+        rng: &mut impl Rng
+    ) -> NodeID {
+       let mut cur_node = seeds.sample(rng);
        match self.steps {
            Sample::Probability(alpha) => loop {
 
                // Sample the next edge
-               cur_node = sampler.sample(graph, cur_node, rng)
-                   .unwrap_or(start_node);
+               cur_node = match sampler.sample(graph, cur_node, rng) {
+                   Some(next_node) => next_node,
+                   None => seeds.sample(rng)
+               };
 
                if rng.gen::<f32>() < alpha {
                    break
@@ -184,8 +356,10 @@ impl RWR {
            },
            Sample::Fixed(steps) => for _ in 0..steps {
                // Sample the next edge
-               cur_node = sampler.sample(graph, cur_node, rng)
-                   .unwrap_or(start_node);
+               cur_node = match sampler.sample(graph, cur_node, rng) {
+                   Some(next_node) => next_node,
+                   None => seeds.sample(rng)
+               };
            },
            Sample::All => { panic!("Sample::All is illegal!") }
        }
@@ -305,4 +479,72 @@ mod rwr_tests {
         assert_eq!(v[2].0, 1);
     }
 
+    #[test]
+    fn test_sample_batch() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_edges(), false));
+        let rwr = RWR {
+            steps: Sample::Probability(0.1),
+            walks: 1_000,
+            beta: 0.5,
+            single_threaded: false,
+            seed: 2023,
+            rng_kind: RngKind::XorShift,
+            deterministic: false
+        };
+
+        let batch = rwr.sample_batch(&ccsr, &Unweighted, &[0, 2, 0], None, false);
+        assert_eq!(batch.len(), 3);
+        assert_eq!(batch[0], batch[2]);
+        for scores in batch.iter() {
+            assert!(scores.windows(2).all(|w| w[0].1 >= w[1].1));
+        }
+
+        // Matches sampling each source on its own
+        let single = RWR { single_threaded: true, seed: rwr.seed + 2, ..rwr };
+        let mut expected: Vec<_> = single.sample(&ccsr, &Unweighted, 2).into_iter().collect();
+        expected.sort_by_key(|(node_id, score)| (FloatOrd(-*score), *node_id));
+        assert_eq!(batch[1], expected);
+
+        let truncated = rwr.sample_batch(&ccsr, &Unweighted, &[0], Some(1), false);
+        assert_eq!(truncated[0], batch[0][..1].to_vec());
+    }
+
+    #[test]
+    fn test_sample_seeds() {
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(build_edges(), false));
+        let rwr = RWR {
+            steps: Sample::Probability(0.1),
+            walks: 1_000,
+            beta: 0.5,
+            single_threaded: true,
+            seed: 2023,
+            rng_kind: RngKind::XorShift,
+            deterministic: true
+        };
+
+        // Reproducible for a seed, with either generator
+        assert_eq!(rwr.sample_bfs(&ccsr, 0), rwr.sample_bfs(&ccsr, 0));
+        let pcg = RWR { rng_kind: RngKind::Pcg, ..rwr };
+        assert_eq!(pcg.sample_bfs(&ccsr, 0), pcg.sample_bfs(&ccsr, 0));
+
+        // A single seed is the same as sampling from the node
+        assert_eq!(rwr.sample_seeds(&ccsr, &Unweighted, &[(2, 3.)]), rwr.sample(&ccsr, &Unweighted, 2));
+        assert_eq!(rwr.sample_bfs_seeds(&ccsr, &[(2, 3.)]), rwr.sample_bfs(&ccsr, 2));
+
+        // Prebuilt alias tables take the same steps as per node tables
+        let tables = EdgeAliasTable::new(&ccsr);
+        assert_eq!(rwr.sample_bfs_with(&ccsr, &tables, 0), rwr.sample_bfs(&ccsr, 0));
+        assert_eq!(rwr.sample_bfs_seeds_with(&ccsr, &tables, &[(0, 1.), (2, 1.)]),
+                   rwr.sample_bfs_seeds(&ccsr, &[(0, 1.), (2, 1.)]));
+
+        let map = rwr.sample_seeds(&ccsr, &Unweighted, &[(0, 1.), (2, 1.)]);
+        assert!(!map.is_empty());
+        assert!(map.values().all(|w| w.is_finite() && *w > 0.));
+        assert!(!rwr.sample_bfs_seeds(&ccsr, &[(0, 1.), (2, 1.)]).is_empty());
+
+        assert!(rwr.sample_seeds(&ccsr, &Unweighted, &[]).is_empty());
+        assert!(rwr.sample_seeds(&ccsr, &Unweighted, &[(0, 0.), (2, 0.)]).is_empty());
+        assert!(rwr.sample_bfs_seeds(&ccsr, &[(0, -1.)]).is_empty());
+    }
+
 }
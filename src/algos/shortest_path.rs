@@ -0,0 +1,111 @@
+//! Single and multi-source shortest paths.  BFS ignores edge weights and counts hops while
+//! Dijkstra treats edge weights as non-negative distances.  Note that CDF graphs store cumulative
+//! weights, so weighted distances should be computed on the original CSR.
+//!
+//! The multi-source variants compute the distance from each node to its nearest seed in a single
+//! traversal, which is useful both as a feature and for evaluating embeddings against seed sets.
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap,VecDeque};
+
+use float_ord::FloatOrd;
+
+use crate::graph::{Graph,NodeID};
+
+/// Computes the number of hops from the source to every node.  Unreachable nodes are None.
+pub fn bfs(graph: &impl Graph, source: NodeID) -> Vec<Option<usize>> {
+    multi_source_bfs(graph, &[source])
+}
+
+/// Computes the number of hops from each node to the nearest seed, following edges outward from
+/// the seeds.  Unreachable nodes are None.
+pub fn multi_source_bfs(graph: &impl Graph, seeds: &[NodeID]) -> Vec<Option<usize>> {
+    let mut distances = vec![None; graph.len()];
+    let mut queue = VecDeque::new();
+    for seed in seeds.iter() {
+        if distances[*seed].is_none() {
+            distances[*seed] = Some(0);
+            queue.push_back(*seed);
+        }
+    }
+
+    while let Some(node_id) = queue.pop_front() {
+        let next_dist = distances[node_id].map(|d| d + 1);
+        for out_node in graph.get_edges(node_id).0.iter() {
+            if distances[*out_node].is_none() {
+                distances[*out_node] = next_dist;
+                queue.push_back(*out_node);
+            }
+        }
+    }
+
+    distances
+}
+
+/// Computes the weighted distance from the source to every node.  Unreachable nodes are None.
+pub fn dijkstra(graph: &impl Graph, source: NodeID) -> Vec<Option<f32>> {
+    multi_source_dijkstra(graph, &[source])
+}
+
+/// Computes the weighted distance from each node to the nearest seed.  Unreachable nodes are None.
+/// Edge weights must be non-negative.
+pub fn multi_source_dijkstra(graph: &impl Graph, seeds: &[NodeID]) -> Vec<Option<f32>> {
+    let mut distances: Vec<Option<f32>> = vec![None; graph.len()];
+    let mut heap = BinaryHeap::new();
+    for seed in seeds.iter() {
+        distances[*seed] = Some(0.);
+        heap.push(Reverse((FloatOrd(0f32), *seed)));
+    }
+
+    while let Some(Reverse((FloatOrd(dist), node_id))) = heap.pop() {
+        // Skip stale entries
+        if distances[node_id].map(|d| dist > d).unwrap_or(false) {
+            continue
+        }
+
+        let (edges, weights) = graph.get_edges(node_id);
+        for (out_node, w) in edges.iter().zip(weights.iter()) {
+            debug_assert!(*w >= 0., "Dijkstra requires non-negative edge weights!");
+            let new_dist = dist + w;
+            let better = distances[*out_node].map(|d| new_dist < d).unwrap_or(true);
+            if better {
+                distances[*out_node] = Some(new_dist);
+                heap.push(Reverse((FloatOrd(new_dist), *out_node)));
+            }
+        }
+    }
+
+    distances
+}
+
+#[cfg(test)]
+mod shortest_path_tests {
+    use super::*;
+    use crate::graph::CSR;
+
+    fn build_graph() -> CSR {
+        let edges = vec![
+            (0, 1, 1.),
+            (1, 2, 1.),
+            (0, 2, 5.),
+            (2, 3, 0.5),
+            (4, 3, 1.),
+        ];
+        CSR::construct_from_edges(edges, false)
+    }
+
+    #[test]
+    fn test_bfs() {
+        let graph = build_graph();
+        assert_eq!(bfs(&graph, 0), vec![Some(0), Some(1), Some(1), Some(2), None]);
+        assert_eq!(multi_source_bfs(&graph, &[1, 4]), vec![None, Some(0), Some(1), Some(1), Some(0)]);
+        assert_eq!(multi_source_bfs(&graph, &[]), vec![None; 5]);
+    }
+
+    #[test]
+    fn test_dijkstra() {
+        let graph = build_graph();
+        assert_eq!(dijkstra(&graph, 0), vec![Some(0.), Some(1.), Some(2.), Some(2.5), None]);
+        assert_eq!(multi_source_dijkstra(&graph, &[0, 4]),
+                   vec![Some(0.), Some(1.), Some(2.), Some(1.), Some(0.)]);
+    }
+}
@@ -0,0 +1,327 @@
+//! Skip-gram with negative sampling (word2vec) over walk corpora.  Combined with the walks from
+//! `algos::node2vec`, this gives a complete DeepWalk/node2vec pipeline.
+//!
+//! We keep separate center and context embeddings, stacked into a single EmbeddingStore so they can
+//! share the Adam optimizer and learning rate schedule used by EmbeddingPropagation.  Center ids are
+//! the NodeIDs while context ids are offset by the number of nodes.
+use std::collections::{HashMap as CHashMap};
+use std::fmt::Write;
+
+use rand::prelude::*;
+use rand_distr::{Distribution,Uniform};
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+
+use crate::graph::NodeID;
+use crate::embeddings::{EmbeddingStore,randomize_embedding_store};
+use crate::distance::Distance;
+use crate::progress::CLProgressBar;
+use crate::algos::utils::AliasTable;
+use crate::algos::grad_utils::optimizer::{Optimizer,AdamOptimizer};
+use crate::algos::grad_utils::scheduler::LRScheduler;
+
+/// Skip-gram trainer configuration
+#[derive(Clone,Copy,Debug)]
+pub struct SkipGram {
+    /// Dimensions of the embeddings
+    pub dims: usize,
+
+    /// Max distance between the center and a context node within a walk.  Each center node samples
+    /// an effective window uniformly from [1, window], weighting closer contexts higher.
+    pub window: usize,
+
+    /// Number of negatives to sample per context node, drawn from the unigram distribution raised
+    /// to the 3/4 power.
+    pub negatives: usize,
+
+    /// Frequent nodes are randomly discarded with a probability based on this threshold.  Zero
+    /// disables subsampling.  Typical values are between 1e-5 and 1e-3.
+    pub subsample: f32,
+
+    /// Learning rate
+    pub alpha: f32,
+
+    /// Number of walks per update
+    pub batch_size: usize,
+
+    /// Number of passes over the corpus
+    pub passes: usize,
+
+    /// Random seed
+    pub seed: u64,
+
+    /// Whether to show a pretty indicator
+    pub indicator: bool
+}
+
+impl SkipGram {
+
+    /// Learns embeddings for `num_nodes` nodes from the walks.  Nodes which never show up in a
+    /// walk keep their random initialization.
+    pub fn learn(&self, num_nodes: usize, walks: &[Vec<NodeID>]) -> EmbeddingStore {
+        let mut rng = XorShiftRng::seed_from_u64(self.seed);
+
+        // Centers are randomized, contexts start at zero
+        let mut embeddings = EmbeddingStore::new(2 * num_nodes, self.dims, Distance::Cosine);
+        randomize_embedding_store(&mut embeddings, &mut rng);
+        (num_nodes..2 * num_nodes).for_each(|ctx_id| {
+            embeddings.get_embedding_mut(ctx_id).iter_mut().for_each(|ei| *ei = 0.);
+        });
+
+        let optimizer = AdamOptimizer::new(0.9, 0.999, self.dims, embeddings.len());
+
+        let counts = count_nodes(num_nodes, walks);
+        let keep_probs = keep_probabilities(&counts, self.subsample);
+        let negative_table = unigram_table(&counts);
+
+        let batch_size = self.batch_size.max(1);
+        let steps_per_pass = (walks.len() as f32 / batch_size as f32).ceil() as usize;
+        let total_steps = steps_per_pass * self.passes;
+        let lr_scheduler = LRScheduler::cos_decay(self.alpha / 100f32, self.alpha,
+                                                  total_steps / 5, total_steps);
+
+        let pb = CLProgressBar::new(total_steps as u64, self.indicator);
+        let mut walk_idxs: Vec<_> = (0..walks.len()).collect();
+        let mut step = 1;
+        for pass in 1..(self.passes + 1) {
+            walk_idxs.shuffle(&mut rng);
+            let mut error = 0f32;
+            for (i, batch) in walk_idxs.chunks(batch_size).enumerate() {
+                let grads: Vec<_> = batch.par_iter().map(|walk_idx| {
+                    let offset = (pass as u64).wrapping_mul(walks.len() as u64)
+                        .wrapping_add(*walk_idx as u64);
+                    let seed = self.seed.wrapping_add(offset);
+                    let mut rng = XorShiftRng::seed_from_u64(seed);
+                    self.compute_walk_gradients(&embeddings, num_nodes, &walks[*walk_idx],
+                                                &keep_probs, negative_table.as_ref(), &mut rng)
+                }).collect();
+
+                // Aggregate gradients for shared nodes
+                let mut all_grads = CHashMap::new();
+                let mut cnt = 0usize;
+                for (err, n, grad_set) in grads.into_iter() {
+                    error += err;
+                    cnt += n;
+                    for (id, grad) in grad_set.into_iter() {
+                        let e = all_grads.entry(id).or_insert_with(|| vec![0.; grad.len()]);
+                        e.iter_mut().zip(grad.iter()).for_each(|(ei, gi)| *ei += *gi);
+                    }
+                }
+
+                if cnt > 0 {
+                    all_grads.values_mut().for_each(|g| g.iter_mut().for_each(|gi| *gi /= cnt as f32));
+                    let alpha = lr_scheduler.compute(step);
+                    optimizer.update(&embeddings, all_grads, alpha, pass as f32);
+                }
+
+                step += 1;
+                pb.inc(1);
+                if i % 100 == 0 {
+                    pb.update_message(|msg| {
+                        msg.clear();
+                        write!(msg, "Pass {}/{}, Loss: {:.5}", pass, self.passes, error / (i + 1) as f32)
+                            .expect("Error writing out indicator message!");
+                    });
+                }
+            }
+        }
+        pb.finish();
+
+        // Only the centers are returned
+        let mut node_embeddings = EmbeddingStore::new(num_nodes, self.dims, Distance::Cosine);
+        for node_id in 0..num_nodes {
+            node_embeddings.set_embedding(node_id, embeddings.get_embedding(node_id));
+        }
+        node_embeddings
+    }
+
+    /// Computes the loss, number of (center, context) pairs, and gradients for a single walk.
+    fn compute_walk_gradients(
+        &self,
+        embeddings: &EmbeddingStore,
+        num_nodes: usize,
+        walk: &[NodeID],
+        keep_probs: &[f32],
+        negative_table: Option<&AliasTable>,
+        rng: &mut impl Rng
+    ) -> (f32, usize, CHashMap<usize, Vec<f32>>) {
+        let mut grads: CHashMap<usize, Vec<f32>> = CHashMap::new();
+        let mut error = 0f32;
+        let mut pairs = 0usize;
+
+        // Subsample frequent nodes
+        let walk: Vec<_> = walk.iter()
+            .filter(|node_id| rng.gen::<f32>() < keep_probs[**node_id])
+            .cloned()
+            .collect();
+
+        let negative_table = match negative_table {
+            Some(table) if walk.len() >= 2 && self.window > 0 => table,
+            _ => return (0., 0, grads)
+        };
+
+        let window_dist = Uniform::new_inclusive(1, self.window);
+        let mut center_grad = vec![0f32; self.dims];
+        for (pos, center) in walk.iter().enumerate() {
+            let w = window_dist.sample(rng);
+            let start = pos.saturating_sub(w);
+            let stop = (pos + w + 1).min(walk.len());
+            let v_c = embeddings.get_embedding(*center);
+            for ctx_pos in start..stop {
+                if ctx_pos == pos { continue }
+
+                center_grad.iter_mut().for_each(|gi| *gi = 0.);
+                pairs += 1;
+
+                // Positive
+                let context = walk[ctx_pos];
+                error += self.update_pair(embeddings, num_nodes, v_c, context, 1.,
+                                          &mut center_grad, &mut grads);
+
+                // Negatives
+                for _ in 0..self.negatives {
+                    let neg = negative_table.sample(rng);
+                    if neg == context { continue }
+                    error += self.update_pair(embeddings, num_nodes, v_c, neg, 0.,
+                                              &mut center_grad, &mut grads);
+                }
+
+                add_grad(&mut grads, *center, &center_grad);
+            }
+        }
+
+        (error, pairs, grads)
+    }
+
+    /// Logistic loss for a single (center, context) pair, accumulating the center gradient and
+    /// adding the context gradient.  Returns the loss.
+    fn update_pair(
+        &self,
+        embeddings: &EmbeddingStore,
+        num_nodes: usize,
+        v_c: &[f32],
+        context: NodeID,
+        label: f32,
+        center_grad: &mut [f32],
+        grads: &mut CHashMap<usize, Vec<f32>>
+    ) -> f32 {
+        let ctx_id = num_nodes + context;
+        let u_o = embeddings.get_embedding(ctx_id);
+        let score = sigmoid(v_c.iter().zip(u_o.iter()).map(|(vi, ui)| vi * ui).sum());
+        let g = score - label;
+
+        center_grad.iter_mut().zip(u_o.iter()).for_each(|(ci, ui)| *ci += g * ui);
+        let ctx_grad: Vec<_> = v_c.iter().map(|vi| g * vi).collect();
+        add_grad(grads, ctx_id, &ctx_grad);
+
+        let p = if label > 0. { score } else { 1. - score };
+        -(p.max(1e-7)).ln()
+    }
+}
+
+#[inline]
+fn sigmoid(x: f32) -> f32 {
+    1. / (1. + (-x).exp())
+}
+
+fn add_grad(grads: &mut CHashMap<usize, Vec<f32>>, id: usize, grad: &[f32]) {
+    let e = grads.entry(id).or_insert_with(|| vec![0.; grad.len()]);
+    e.iter_mut().zip(grad.iter()).for_each(|(ei, gi)| *ei += *gi);
+}
+
+/// Counts the number of times each node shows up in the corpus
+fn count_nodes(num_nodes: usize, walks: &[Vec<NodeID>]) -> Vec<usize> {
+    let mut counts = vec![0usize; num_nodes];
+    walks.iter().flat_map(|w| w.iter()).for_each(|node_id| counts[*node_id] += 1);
+    counts
+}
+
+/// Probability of keeping each node, using the word2vec subsampling formula
+fn keep_probabilities(counts: &[usize], threshold: f32) -> Vec<f32> {
+    let total = counts.iter().sum::<usize>() as f32;
+    counts.iter().map(|c| {
+        if threshold <= 0. || *c == 0 {
+            1.
+        } else {
+            let f = *c as f32 / total;
+            (((f / threshold).sqrt() + 1.) * threshold / f).min(1.)
+        }
+    }).collect()
+}
+
+/// Alias table over the unigram distribution raised to the 3/4 power.  None if there are no
+/// counts.
+fn unigram_table(counts: &[usize]) -> Option<AliasTable> {
+    let weights: Vec<_> = counts.iter().map(|c| (*c as f32).powf(0.75)).collect();
+    AliasTable::new(&weights)
+}
+
+#[cfg(test)]
+mod skipgram_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+    use crate::embeddings::Entity;
+    use crate::algos::node2vec::Node2Vec;
+    use crate::sampler::Unweighted;
+
+    #[test]
+    fn test_keep_probabilities() {
+        let probs = keep_probabilities(&[100, 1, 0], 1e-2);
+        assert!(probs[0] < 0.5);
+        assert_eq!(probs[1], 1.);
+        assert_eq!(probs[2], 1.);
+        assert_eq!(keep_probabilities(&[100, 1], 0.), vec![1., 1.]);
+    }
+
+    #[test]
+    fn test_unigram_table() {
+        let table = unigram_table(&[1, 0, 1]).unwrap();
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let firsts = (0..1000).filter(|_| {
+            let neg = table.sample(&mut rng);
+            assert_ne!(neg, 1);
+            neg == 0
+        }).count();
+        assert!(firsts > 400 && firsts < 600, "{}", firsts);
+        assert!(unigram_table(&[0, 0]).is_none());
+    }
+
+    #[test]
+    fn test_skipgram() {
+        // Two cliques joined by a single edge
+        let mut edges = Vec::new();
+        for offset in [0, 5] {
+            for u in 0..5 {
+                for v in 0..5 {
+                    if u != v { edges.push((u + offset, v + offset, 1.)); }
+                }
+            }
+        }
+        edges.push((4, 5, 1.));
+        edges.push((5, 4, 1.));
+        let graph = CumCSR::convert(CSR::construct_from_edges(edges, false));
+
+        let walks = Node2Vec::new(1., 1., 20, 20, 2023).unwrap().generate(&graph, &Unweighted);
+        let sg = SkipGram {
+            dims: 8,
+            window: 3,
+            negatives: 3,
+            subsample: 0.,
+            alpha: 5e-2,
+            batch_size: 8,
+            passes: 10,
+            seed: 2023,
+            indicator: false
+        };
+        let es = sg.learn(10, &walks);
+        assert_eq!(es.len(), 10);
+
+        let d = |u, v| es.compute_distance(&Entity::Node(u), &Entity::Node(v));
+        assert!(d(0, 1) < d(0, 9));
+        assert!(d(6, 7) < d(6, 2));
+
+        // Seeds near the top of the range wrap rather than overflow
+        let sg = SkipGram { seed: u64::MAX, passes: 1, ..sg };
+        assert_eq!(sg.learn(10, &walks).len(), 10);
+    }
+}
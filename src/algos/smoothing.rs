@@ -0,0 +1,94 @@
+//! Post-processing which smooths node embeddings over the graph.  Each iteration blends every
+//! node's embedding with the edge weighted mean of its neighbors' embeddings, a low pass filter
+//! which tends to help retrieval when neighbors should be near each other.  A single iteration is
+//! usually enough; many iterations oversmooth, collapsing connected components to a point.
+use rayon::prelude::*;
+
+use crate::graph::{CDFGraph,CDFtoP};
+use crate::embeddings::EmbeddingStore;
+use crate::error::GraphLibError;
+
+/// Smoothing configuration
+#[derive(Clone,Copy,Debug)]
+pub struct Smoothing {
+    /// Weight of the neighbor mean, in [0, 1].  0 leaves embeddings unchanged while 1 replaces
+    /// each with its neighbors' mean.
+    pub alpha: f32,
+
+    /// Number of smoothing passes
+    pub iterations: usize
+}
+
+impl Smoothing {
+
+    /// Smooths the embeddings in place.  Every node is updated from the previous iteration's
+    /// embeddings, so the result doesn't depend on node order.  Nodes without edges, and nodes
+    /// beyond the end of the graph, are left as is.
+    pub fn smooth(
+        &self,
+        graph: &(impl CDFGraph + Send + Sync),
+        es: &mut EmbeddingStore
+    ) -> Result<(), GraphLibError> {
+        if self.alpha.is_nan() || self.alpha < 0. || self.alpha > 1. {
+            return Err("alpha must be in [0, 1]!".into())
+        }
+        if graph.len() > es.len() {
+            return Err(GraphLibError::DimensionMismatch { expected: graph.len(), found: es.len() })
+        }
+
+        let dims = es.dims();
+        for _iter in 0..self.iterations {
+            let mut next = vec![0f32; graph.len() * dims];
+            next.par_chunks_mut(dims).enumerate().for_each(|(node_id, out)| {
+                let emb = es.get_embedding(node_id);
+                let (edges, weights) = graph.get_edges(node_id);
+                if edges.is_empty() {
+                    out.copy_from_slice(emb);
+                    return
+                }
+
+                for (t_n, p) in edges.iter().zip(CDFtoP::new(weights)) {
+                    let t_emb = es.get_embedding(*t_n);
+                    out.iter_mut().zip(t_emb.iter()).for_each(|(o, ti)| *o += p * ti);
+                }
+                out.iter_mut().zip(emb.iter()).for_each(|(o, ei)| {
+                    *o = (1. - self.alpha) * ei + self.alpha * *o;
+                });
+            });
+
+            next.chunks(dims).enumerate().for_each(|(node_id, emb)| {
+                es.get_embedding_mut(node_id).copy_from_slice(emb);
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod smoothing_tests {
+    use super::*;
+    use crate::graph::{CSR,CumCSR};
+    use crate::distance::Distance;
+
+    #[test]
+    fn test_smooth() {
+        // Path 0 - 1 - 2; node 3 is outside of the graph
+        let edges = vec![(0, 1, 1.), (1, 0, 1.), (1, 2, 1.), (2, 1, 1.)];
+        let graph = CumCSR::convert(CSR::construct_from_edges(edges, false));
+        let values = vec![0., 1., 2., 5.];
+        let mut es = EmbeddingStore::new_with_vec(4, 1, Distance::Euclidean, values.clone()).unwrap();
+
+        let smoothing = Smoothing { alpha: 0.5, iterations: 1 };
+        smoothing.smooth(&graph, &mut es).unwrap();
+        let smoothed: Vec<_> = (0..4).map(|n| es.get_embedding(n)[0]).collect();
+        assert_eq!(smoothed, vec![0.5, 1., 1.5, 5.]);
+
+        let mut es = EmbeddingStore::new_with_vec(4, 1, Distance::Euclidean, values).unwrap();
+        Smoothing { alpha: 0., iterations: 3 }.smooth(&graph, &mut es).unwrap();
+        assert_eq!(es.get_embedding(2), &[2.]);
+
+        assert!(Smoothing { alpha: 1.5, iterations: 1 }.smooth(&graph, &mut es).is_err());
+        let mut small = EmbeddingStore::new(2, 1, Distance::Euclidean);
+        assert!(smoothing.smooth(&graph, &mut small).is_err());
+    }
+}
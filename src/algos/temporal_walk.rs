@@ -0,0 +1,148 @@
+//! Time respecting random walks over temporal graphs.  After the first edge, a walk may only
+//! traverse edges strictly newer than the edge it arrived on, so every walk is a plausible causal
+//! sequence of interactions.  Since edges are sorted by time within each node, the valid edges are
+//! always a suffix of the node's edges within the window.
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+
+use crate::graph::{Graph,NodeID};
+use crate::graph::temporal::{TemporalWindow,Timestamp};
+
+/// Temporal walk generator configuration
+#[derive(Clone,Copy,Debug)]
+pub struct TemporalWalk {
+    /// Maximum number of nodes in each walk, including the start node
+    pub walk_len: usize,
+
+    /// Number of walks to start from each node
+    pub walks_per_node: usize,
+
+    /// If true, edges are chosen proportionally to their weight, otherwise uniformly
+    pub weighted: bool,
+
+    /// Random seed
+    pub seed: u64
+}
+
+impl TemporalWalk {
+
+    /// Generates the walk corpus over the window, starting `walks_per_node` walks from each node.
+    /// Walks are ordered by pass, then by start node, and end early once no newer edges exist.
+    pub fn generate(&self, graph: &TemporalWindow) -> Vec<Vec<NodeID>> {
+        let n = graph.len();
+        (0..(n * self.walks_per_node)).into_par_iter().map(|idx| {
+            let mut rng = XorShiftRng::seed_from_u64(self.seed + idx as u64);
+            self.walk(graph, idx % n, &mut rng)
+        }).collect()
+    }
+
+    /// Runs a single time respecting walk from the start node.
+    pub fn walk(
+        &self,
+        graph: &TemporalWindow,
+        start_node: NodeID,
+        rng: &mut impl Rng
+    ) -> Vec<NodeID> {
+        let mut walk = Vec::with_capacity(self.walk_len);
+        if self.walk_len == 0 {
+            return walk
+        }
+
+        walk.push(start_node);
+        let mut cur_node = start_node;
+        let mut cur_time: Option<Timestamp> = None;
+        while walk.len() < self.walk_len {
+            let (edges, weights, timestamps) = graph.get_temporal_edges(cur_node);
+
+            // The first step can take any edge in the window
+            let offset = match cur_time {
+                Some(t) => timestamps.partition_point(|ts| *ts <= t),
+                None    => 0
+            };
+
+            match self.choose(&weights[offset..], rng) {
+                Some(idx) => {
+                    cur_node = edges[offset + idx];
+                    cur_time = Some(timestamps[offset + idx]);
+                    walk.push(cur_node);
+                },
+                None => break
+            }
+        }
+
+        walk
+    }
+
+    /// Picks an index from the candidate edges, or None if there aren't any.  The candidates
+    /// change with the arrival time, so weighted sampling is a linear scan rather than a CDF.
+    fn choose(&self, weights: &[f32], rng: &mut impl Rng) -> Option<usize> {
+        if weights.is_empty() {
+            return None
+        }
+
+        if !self.weighted {
+            return Some(rng.gen_range(0, weights.len()))
+        }
+
+        let total: f32 = weights.iter().sum();
+        if total <= 0. {
+            return Some(rng.gen_range(0, weights.len()))
+        }
+
+        let mut p = rng.gen::<f32>() * total;
+        for (idx, w) in weights.iter().enumerate() {
+            if p < *w {
+                return Some(idx)
+            }
+            p -= w;
+        }
+        Some(weights.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod temporal_walk_tests {
+    use super::*;
+    use crate::graph::temporal::TemporalCSR;
+
+    fn build_graph() -> TemporalCSR {
+        // A chain 0 -> 1 -> 2 -> 3 with increasing times, plus a stale 1 -> 4 edge which is older
+        // than the 0 -> 1 edge and can never be followed after it.
+        let edges = vec![
+            (0, 1, 1., 10),
+            (1, 4, 1., 5),
+            (1, 2, 1., 20),
+            (2, 3, 1., 30),
+            (3, 0, 1., 25),
+        ];
+        TemporalCSR::construct_from_edges(edges)
+    }
+
+    #[test]
+    fn test_walk() {
+        let graph = build_graph();
+        let window = graph.window(0, Timestamp::MAX);
+        let walker = TemporalWalk { walk_len: 10, walks_per_node: 1, weighted: true, seed: 2023 };
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        for _ in 0..20 {
+            // 3 -> 0 is older than 2 -> 3, so the walk has to stop
+            assert_eq!(walker.walk(&window, 0, &mut rng), vec![0, 1, 2, 3]);
+        }
+
+        // Starting from 3, the first edge can be any age, but 0 -> 1 is older than 3 -> 0
+        assert_eq!(walker.walk(&window, 3, &mut rng), vec![3, 0]);
+    }
+
+    #[test]
+    fn test_generate_window() {
+        let graph = build_graph();
+        let window = graph.window(0, 25);
+        let walker = TemporalWalk { walk_len: 10, walks_per_node: 2, weighted: false, seed: 2023 };
+        let walks = walker.generate(&window);
+        assert_eq!(walks.len(), 10);
+        assert_eq!(walks[0], vec![0, 1, 2]);
+        assert_eq!(walks[2], vec![2]);
+        assert_eq!(walks, walker.generate(&window));
+    }
+}
@@ -0,0 +1,159 @@
+//! Exact triangle counting and local clustering coefficients.  Edges are treated as undirected:
+//! u and v are neighbors if either u -> v or v -> u exists, and self loops are ignored.
+//!
+//! Each triangle is counted exactly once from its lowest NodeID by intersecting sorted neighbor
+//! lists, in parallel across nodes.
+use std::sync::atomic::{AtomicUsize,Ordering};
+
+use rayon::prelude::*;
+
+use crate::graph::{Graph,NodeID};
+use crate::feature_store::FeatureStore;
+
+/// Builds sorted, deduplicated, undirected neighbor lists without self loops.
+fn undirected_neighbors(graph: &(impl Graph + Sync)) -> Vec<Vec<NodeID>> {
+    let mut neighbors: Vec<Vec<NodeID>> = (0..graph.len()).into_par_iter().map(|node_id| {
+        graph.get_edges(node_id).0.iter()
+            .filter(|out_node| **out_node != node_id)
+            .cloned()
+            .collect()
+    }).collect();
+
+    // Add the reverse edges
+    for node_id in 0..graph.len() {
+        for out_node in graph.get_edges(node_id).0.iter() {
+            if *out_node != node_id {
+                neighbors[*out_node].push(node_id);
+            }
+        }
+    }
+
+    neighbors.par_iter_mut().for_each(|n| {
+        n.sort_unstable();
+        n.dedup();
+    });
+    neighbors
+}
+
+/// Counts the number of triangles each node participates in.
+pub fn count_triangles(graph: &(impl Graph + Sync)) -> Vec<usize> {
+    let neighbors = undirected_neighbors(graph);
+    count_triangles_from_neighbors(&neighbors)
+}
+
+fn count_triangles_from_neighbors(neighbors: &[Vec<NodeID>]) -> Vec<usize> {
+    let counts: Vec<_> = (0..neighbors.len()).map(|_| AtomicUsize::new(0)).collect();
+    (0..neighbors.len()).into_par_iter().for_each(|u| {
+        let n_u = &neighbors[u];
+        // Only look at higher neighbors so each triangle u < v < w is found once
+        let start = n_u.partition_point(|v| *v <= u);
+        for &v in n_u[start..].iter() {
+            let n_v = &neighbors[v];
+            let mut i = n_u.partition_point(|w| *w <= v);
+            let mut j = n_v.partition_point(|w| *w <= v);
+            while i < n_u.len() && j < n_v.len() {
+                if n_u[i] < n_v[j] {
+                    i += 1;
+                } else if n_u[i] > n_v[j] {
+                    j += 1;
+                } else {
+                    counts[u].fetch_add(1, Ordering::Relaxed);
+                    counts[v].fetch_add(1, Ordering::Relaxed);
+                    counts[n_u[i]].fetch_add(1, Ordering::Relaxed);
+                    i += 1;
+                    j += 1;
+                }
+            }
+        }
+    });
+    counts.into_iter().map(|c| c.into_inner()).collect()
+}
+
+/// Total number of triangles in the graph
+pub fn total_triangles(triangles: &[usize]) -> usize {
+    triangles.iter().sum::<usize>() / 3
+}
+
+/// Computes the number of triangles per node along with the local clustering coefficient: the
+/// fraction of pairs of neighbors which are themselves connected.  Nodes with fewer than two
+/// neighbors have a coefficient of zero.
+pub fn clustering_coefficients(graph: &(impl Graph + Sync)) -> (Vec<usize>, Vec<f32>) {
+    let neighbors = undirected_neighbors(graph);
+    let triangles = count_triangles_from_neighbors(&neighbors);
+    let coefs = triangles.par_iter().zip(neighbors.par_iter()).map(|(t, n)| {
+        let d = n.len() as f32;
+        if n.len() < 2 { 0. } else { 2. * *t as f32 / (d * (d - 1.)) }
+    }).collect();
+    (triangles, coefs)
+}
+
+/// Adds bucketed triangle counts, log2 scaled, and clustering coefficients, in tenths, to each
+/// node as features.  Useful as cheap structural features before EmbeddingPropagation.
+pub fn add_structural_features(
+    features: &mut FeatureStore,
+    triangles: &[usize],
+    coefficients: &[f32]
+) {
+    for node_id in 0..features.num_nodes().min(triangles.len()) {
+        let t_bucket = ((triangles[node_id] + 1) as f32).log2().floor() as usize;
+        let c_bucket = (coefficients[node_id] * 10.).floor().min(9.) as usize;
+        features.add_features(node_id, [
+            ("triangles", t_bucket.to_string()),
+            ("clustering", c_bucket.to_string())
+        ].into_iter());
+    }
+}
+
+#[cfg(test)]
+mod triangles_tests {
+    use super::*;
+    use crate::graph::CSR;
+
+    fn build_graph() -> CSR {
+        // Two triangles sharing the 1-2 edge, plus a tail.  Directed edges and duplicates
+        // should all be treated as a single undirected edge.
+        let edges = vec![
+            (0, 1, 1.),
+            (1, 0, 1.),
+            (0, 2, 1.),
+            (1, 2, 1.),
+            (3, 1, 1.),
+            (2, 3, 1.),
+            (3, 4, 1.),
+            (4, 4, 1.),
+        ];
+        CSR::construct_from_edges(edges, false)
+    }
+
+    #[test]
+    fn test_triangles() {
+        let graph = build_graph();
+        let triangles = count_triangles(&graph);
+        assert_eq!(triangles, vec![1, 2, 2, 1, 0]);
+        assert_eq!(total_triangles(&triangles), 2);
+    }
+
+    #[test]
+    fn test_clustering() {
+        let graph = build_graph();
+        let (_, coefs) = clustering_coefficients(&graph);
+        assert_eq!(coefs[0], 1.);
+        assert_eq!(coefs[1], 2. / 3.);
+        assert_eq!(coefs[3], 1. / 3.);
+        assert_eq!(coefs[4], 0.);
+    }
+
+    #[test]
+    fn test_structural_features() {
+        let graph = build_graph();
+        let (triangles, coefs) = clustering_coefficients(&graph);
+        let mut features = FeatureStore::new(graph.len());
+        features.set_features(0, [("id", "zero")].into_iter());
+        add_structural_features(&mut features, &triangles, &coefs);
+
+        let pretty = features.get_pretty_features(0);
+        assert_eq!(pretty.len(), 3);
+        assert_eq!(pretty[1], ("triangles".to_string(), "1".to_string()));
+        assert_eq!(pretty[2], ("clustering".to_string(), "9".to_string()));
+    }
+}
@@ -6,9 +6,12 @@ use std::collections::BinaryHeap;
 use float_ord::FloatOrd;
 use rand::prelude::*;
 use rand_distr::{Uniform,Binomial};
+use rand_xorshift::XorShiftRng;
+use rand_pcg::Pcg64Mcg;
 use ahash::AHasher;
 
-use crate::NodeID;
+use crate::graph::{Graph,CDFtoP,NodeID};
+use crate::error::GraphLibError;
 
 /// Counts a set of items by id.  See the test for examples.
 pub struct Counter<'a> {
@@ -125,6 +128,7 @@ impl <A:Ord,B> Ord for OrdFirst<A,B> {
     }
 }
 
+/// Uniform reservoir sampling (Algorithm R).  Returns up to `size` items.
 pub fn reservoir_sample(
     it: impl Iterator<Item=(NodeID, f32)>,
     size: usize,
@@ -135,7 +139,8 @@ pub fn reservoir_sample(
         if i < size {
             sample.push(n);
         } else {
-            let idx = Uniform::new(0, i).sample(rng);
+            // The i-th item, counting from zero, replaces an entry with probability size / (i + 1)
+            let idx = Uniform::new_inclusive(0, i).sample(rng);
             if idx < size {
                 sample[idx] = n;
             }
@@ -144,25 +149,104 @@ pub fn reservoir_sample(
     sample
 }
 
+/// Weighted reservoir sampling without replacement, using Efraimidis and Spirakis' A-ES with
+/// exponential jumps.  Rather than drawing a key for every item, we draw how much weight to skip
+/// before the next item enters the reservoir, so long streams only spend random numbers on the
+/// items which make it in.  Keys are kept in log space so small weights don't underflow to zero.
+/// Items with non-positive or non-finite weights are never sampled.  The reservoir is returned in
+/// no particular order.
 pub fn weighted_reservoir_sample<A>(
     items: impl Iterator<Item=(A, f32)>,
     n: usize,
     rng: &mut impl Rng
 ) -> Vec<(A, f32)> {
-    let mut bh = BinaryHeap::with_capacity(n+1);
-    for (i, (item, weight)) in items.enumerate() {
-        let nw = rng.gen::<f32>().powf(1f32 / weight);
-        bh.push(Reverse(OrdFirst(FloatOrd(nw), (item, weight))));
-        if i >= n {
+    a_expj(items, n, rng).into_iter()
+        .map(|(_, item, weight)| (item, weight))
+        .collect()
+}
+
+/// Same as `weighted_reservoir_sample`, but each item is keyed by its position in the stream and
+/// the reservoir is returned in stream order.  Given the same rng, the output is stable regardless
+/// of how the reservoir heap happened to be laid out.
+pub fn weighted_reservoir_sample_keyed<A>(
+    items: impl Iterator<Item=(A, f32)>,
+    n: usize,
+    rng: &mut impl Rng
+) -> Vec<(usize, A, f32)> {
+    let mut sample = a_expj(items, n, rng);
+    sample.sort_by_key(|(pos, _, _)| *pos);
+    sample
+}
+
+fn a_expj<A>(
+    items: impl Iterator<Item=(A, f32)>,
+    n: usize,
+    rng: &mut impl Rng
+) -> Vec<(usize, A, f32)> {
+    if n == 0 {
+        return Vec::new()
+    }
+
+    // Min heap on the log keys, so the root is the threshold to beat
+    let mut bh = BinaryHeap::with_capacity(n);
+    let mut skip = 0f32;
+    for (pos, (item, weight)) in items.enumerate() {
+        if !weight.is_finite() || weight <= 0f32 {
+            continue
+        }
+
+        if bh.len() < n {
+            let key = log_uniform(rng) / weight;
+            bh.push(Reverse(OrdFirst(FloatOrd(key), (pos, item, weight))));
+            if bh.len() == n {
+                skip = next_jump(reservoir_threshold(&bh), rng);
+            }
+            continue
+        }
+
+        skip -= weight;
+        if skip <= 0f32 {
+            // The item's key, conditioned on beating the threshold, is r^(1/w) for r drawn from
+            // (threshold^w, 1).
+            let t_w = (weight * reservoir_threshold(&bh)).exp();
+            let r = (t_w + (1f32 - t_w) * rng.gen::<f32>()).max(std::f32::MIN_POSITIVE);
             bh.pop();
+            bh.push(Reverse(OrdFirst(FloatOrd(r.ln() / weight), (pos, item, weight))));
+            skip = next_jump(reservoir_threshold(&bh), rng);
         }
     }
     bh.into_iter().map(|of| of.0.1).collect()
 }
 
+type Reservoir<A> = BinaryHeap<Reverse<OrdFirst<FloatOrd<f32>, (usize, A, f32)>>>;
+
+// Smallest log key within a full reservoir
+#[inline]
+fn reservoir_threshold<A>(bh: &Reservoir<A>) -> f32 {
+    bh.peek().map(|of| (of.0).0.0).unwrap_or(std::f32::NEG_INFINITY)
+}
+
+// Log of a uniform draw from (0, 1]
+#[inline]
+fn log_uniform(rng: &mut impl Rng) -> f32 {
+    (1f32 - rng.gen::<f32>()).ln()
+}
+
+// Weight to skip before the next item enters the reservoir
+#[inline]
+fn next_jump(threshold: f32, rng: &mut impl Rng) -> f32 {
+    if threshold < 0f32 {
+        log_uniform(rng) / threshold
+    } else {
+        // Nothing can beat a key of one
+        std::f32::INFINITY
+    }
+}
+
 pub struct IllegalSample;
 
 #[derive(Clone,Copy,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Sample {
     All,
     Fixed(usize),
@@ -171,7 +255,7 @@ pub enum Sample {
 
 impl Sample {
     pub fn new(n: f32) -> Result<Sample,IllegalSample> {
-        if n < 0f32 {
+        if n < 0f32 || !n.is_finite() {
             Err(IllegalSample)
         } else {
             let s = if n > 0f32 && n < 1f32 {
@@ -187,22 +271,34 @@ impl Sample {
         Sample::All
     }
 
+    /// Fails if a probability is outside of (0, 1], which Sample::new never produces.
+    pub fn check(&self) -> Result<(), GraphLibError> {
+        match self {
+            Sample::Probability(p) if !(*p > 0f32 && *p <= 1f32) => {
+                Err(GraphLibError::InvalidInput(format!("Sample probability {} must be in (0, 1]!", p)))
+            },
+            _ => Ok(())
+        }
+    }
+
     /// Samples from a Sample, returning both the number of entries
     /// to sample as well as the scalar for drop out.  Probability is
-    /// interpretted as the expected number of success.
+    /// interpretted as the expected number of success.  Fails on probabilities outside of (0, 1].
     pub fn sample(
         &self,
         n: usize,
         at_least_one: bool,
         rng: &mut impl Rng
-    ) -> (usize, f32) {
-        match self {
+    ) -> Result<(usize, f32), GraphLibError> {
+        self.check()?;
+        let s = match self {
             Sample::Fixed(k) => { 
                 let r = (*k).min(n);
                 (r, n as f32 / r as f32) 
             },
             Sample::Probability(p) => { 
-                let dist = Binomial::new(n as u64, *p as f64).unwrap();
+                let dist = Binomial::new(n as u64, *p as f64)
+                    .map_err(|e| GraphLibError::InvalidInput(format!("{:?}", e)))?;
                 let k = dist.sample(rng);
                 if k == 0 && at_least_one {
                     (1, (1f32 - p.powf(n as f32)) / p)
@@ -212,10 +308,305 @@ impl Sample {
             },
             Sample::All => { (n, 1f32) }
 
+        };
+        Ok(s)
+    }
+}
+
+/// Deterministically splits anchor nodes into disjoint shards for each pass.  Every process which
+/// shares the seed and number of shards will compute the same shards, allowing separate
+/// processes, or machines, to train on disjoint anchors without coordination.
+#[derive(Clone,Copy,Debug)]
+pub struct AnchorSharder {
+    num_shards: usize,
+    seed: u64
+}
+
+impl AnchorSharder {
+    pub fn new(num_shards: usize, seed: u64) -> Result<Self, GraphLibError> {
+        if num_shards == 0 {
+            Err("Number of shards must be greater than zero!".into())
+        } else {
+            Ok(AnchorSharder { num_shards, seed })
+        }
+    }
+
+    pub fn num_shards(&self) -> usize {
+        self.num_shards
+    }
+
+    /// Computes all shards for a given pass.  Nodes are sorted prior to shuffling so the ordering
+    /// of the input doesn't matter.  Shard sizes differ by at most one.
+    pub fn shards(&self, nodes: &[NodeID], pass: usize) -> Vec<Vec<NodeID>> {
+        let mut nodes = nodes.to_vec();
+        nodes.sort_unstable();
+        let mut rng = XorShiftRng::seed_from_u64(self.seed.wrapping_add(pass as u64));
+        nodes.shuffle(&mut rng);
+
+        let base = nodes.len() / self.num_shards;
+        let extra = nodes.len() % self.num_shards;
+        let mut shards = Vec::with_capacity(self.num_shards);
+        let mut start = 0;
+        for shard_id in 0..self.num_shards {
+            let size = base + if shard_id < extra { 1 } else { 0 };
+            shards.push(nodes[start..start+size].to_vec());
+            start += size;
+        }
+        shards
+    }
+
+    /// Computes a single shard for a pass.
+    pub fn shard(
+        &self, 
+        nodes: &[NodeID], 
+        pass: usize, 
+        shard_id: usize
+    ) -> Result<Vec<NodeID>, GraphLibError> {
+        if shard_id >= self.num_shards {
+            return Err("Shard id exceeds number of shards!".into())
+        }
+        Ok(self.shards(nodes, pass).swap_remove(shard_id))
+    }
+}
+
+/// Walker's alias method: O(1) weighted sampling after an O(n) build.  Each slot holds its own
+/// item with some probability and an alias otherwise, so a sample only needs a uniform slot and a
+/// coin flip rather than a binary search over the CDF.
+#[derive(Clone,Debug)]
+pub struct AliasTable {
+    prob: Vec<f32>,
+    alias: Vec<usize>
+}
+
+impl AliasTable {
+    /// Builds the table from unnormalized weights.  Returns None if there are no weights, if any
+    /// are negative or not finite, or if they sum to zero.
+    pub fn new(weights: &[f32]) -> Option<Self> {
+        if weights.iter().any(|w| !w.is_finite() || *w < 0f32) {
+            return None
+        }
+        let total: f32 = weights.iter().sum();
+        if !total.is_finite() || total <= 0f32 {
+            return None
+        }
+
+        let mut prob = vec![0f32; weights.len()];
+        let mut alias = vec![0; weights.len()];
+        fill_alias(weights, total, &mut prob, &mut alias);
+        Some(AliasTable { prob, alias })
+    }
+
+    /// Builds the table from a CDF, such as the edge weights of a CDFGraph
+    pub fn from_cdf(cdf: &[f32]) -> Option<Self> {
+        // Clamps away rounding errors from the subtraction
+        let weights: Vec<_> = CDFtoP::new(cdf).map(|p| p.max(0f32)).collect();
+        AliasTable::new(&weights)
+    }
+
+    /// Number of items
+    pub fn len(&self) -> usize {
+        self.prob.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.prob.is_empty()
+    }
+
+    /// Samples an item index proportionally to its weight
+    #[inline]
+    pub fn sample(&self, rng: &mut impl Rng) -> usize {
+        alias_sample(&self.prob, &self.alias, rng)
+    }
+}
+
+/// Alias tables for every node's outbound edges, aligned with the graph's edges.  Weighted walks
+/// which would otherwise binary search each node's CDF on every step take O(1) per step instead.
+/// The tables are only valid for the graph they were built from.
+#[derive(Clone,Debug)]
+pub struct EdgeAliasTable {
+    prob: Vec<f32>,
+    alias: Vec<usize>
+}
+
+impl EdgeAliasTable {
+    /// Builds the tables in O(edges).  Edge weights are read as CDFs, as with a CDFGraph or the
+    /// graphs EP trains on.  Nodes whose weights are degenerate, such as all zeros, are sampled
+    /// uniformly.
+    pub fn new(graph: &impl Graph) -> Self {
+        let mut prob = vec![1f32; graph.edges()];
+        let mut alias = vec![0; graph.edges()];
+        let mut weights = Vec::new();
+        for node_id in 0..graph.len() {
+            let (start, stop) = graph.get_edge_range(node_id);
+            weights.clear();
+            weights.extend(CDFtoP::new(graph.get_edges(node_id).1).map(|p| p.max(0f32)));
+            let total: f32 = weights.iter().sum();
+            if total.is_finite() && total > 0f32 {
+                fill_alias(&weights, total, &mut prob[start..stop], &mut alias[start..stop]);
+            } else {
+                alias[start..stop].iter_mut().enumerate().for_each(|(i, a)| *a = i);
+            }
+        }
+        EdgeAliasTable { prob, alias }
+    }
+
+    /// Samples the offset of an outbound edge, within the node's edges, proportionally to its
+    /// weight.  None if the node has no edges.
+    #[inline]
+    pub fn sample_offset(&self, graph: &impl Graph, node: NodeID, rng: &mut impl Rng) -> Option<usize> {
+        let (start, stop) = graph.get_edge_range(node);
+        if start == stop {
+            None
+        } else {
+            Some(alias_sample(&self.prob[start..stop], &self.alias[start..stop], rng))
+        }
+    }
+
+    /// Samples a neighbor proportionally to its edge weight.  None if the node has no edges.
+    #[inline]
+    pub fn sample(&self, graph: &impl Graph, node: NodeID, rng: &mut impl Rng) -> Option<NodeID> {
+        self.sample_offset(graph, node, rng).map(|offset| graph.get_edges(node).0[offset])
+    }
+}
+
+#[inline]
+fn alias_sample(prob: &[f32], alias: &[usize], rng: &mut impl Rng) -> usize {
+    let idx = rng.gen_range(0, prob.len());
+    if rng.gen::<f32>() < prob[idx] { idx } else { alias[idx] }
+}
+
+// Vose's construction.  Slots with less than their fair share are topped up by slots with more,
+// which then become their alias.  Aliases are offsets within the slice.
+fn fill_alias(weights: &[f32], total: f32, prob: &mut [f32], alias: &mut [usize]) {
+    let n = weights.len() as f32;
+    let mut small = Vec::new();
+    let mut large = Vec::new();
+    for (i, w) in weights.iter().enumerate() {
+        prob[i] = w * n / total;
+        alias[i] = i;
+        if prob[i] < 1f32 { small.push(i) } else { large.push(i) }
+    }
+
+    while !small.is_empty() && !large.is_empty() {
+        let s = small.pop().expect("Checked above");
+        let l = *large.last().expect("Checked above");
+        alias[s] = l;
+        prob[l] -= 1f32 - prob[s];
+        if prob[l] < 1f32 {
+            large.pop();
+            small.push(l);
         }
     }
+
+    // Whatever's left is a full slot, up to rounding errors
+    small.into_iter().chain(large.into_iter()).for_each(|i| prob[i] = 1f32);
 }
 
+/// Generator backing the random streams handed out by SplitRng.  XorShift is the fastest and
+/// the default; PCG has better statistical quality for a small cost.  Algorithms take it as a
+/// setting, like their seed, so different runs in the same process can use different generators.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RngKind {
+    XorShift,
+    Pcg
+}
+
+impl Default for RngKind {
+    fn default() -> Self {
+        RngKind::XorShift
+    }
+}
+
+impl RngKind {
+    /// Creates a generator of this kind from a seed
+    pub fn seed_from_u64(self, seed: u64) -> CrateRng {
+        match self {
+            RngKind::XorShift => CrateRng::XorShift(XorShiftRng::seed_from_u64(seed)),
+            RngKind::Pcg => CrateRng::Pcg(Pcg64Mcg::seed_from_u64(seed))
+        }
+    }
+}
+
+/// Random number generator of either kind
+pub enum CrateRng {
+    XorShift(XorShiftRng),
+    Pcg(Pcg64Mcg)
+}
+
+impl RngCore for CrateRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            CrateRng::XorShift(rng) => rng.next_u32(),
+            CrateRng::Pcg(rng) => rng.next_u32()
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            CrateRng::XorShift(rng) => rng.next_u64(),
+            CrateRng::Pcg(rng) => rng.next_u64()
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            CrateRng::XorShift(rng) => rng.fill_bytes(dest),
+            CrateRng::Pcg(rng) => rng.fill_bytes(dest)
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        match self {
+            CrateRng::XorShift(rng) => rng.try_fill_bytes(dest),
+            CrateRng::Pcg(rng) => rng.try_fill_bytes(dest)
+        }
+    }
+}
+
+/// Splittable source of random streams.  Each split derives a child seed by hashing the key, such
+/// as a pass, batch, or node id, into the parent seed, so nested splits give every
+/// (pass, batch, node) its own stream.  Adding ids to the seed instead collides: batch 2 of node 3
+/// gets the same stream as batch 3 of node 2, in every pass.
+#[derive(Clone,Copy,Debug)]
+pub struct SplitRng {
+    seed: u64,
+    kind: RngKind
+}
+
+impl SplitRng {
+    /// Creates a root stream backed by the given generator
+    pub fn new(seed: u64, kind: RngKind) -> Self {
+        SplitRng { seed, kind }
+    }
+
+    /// Derives an independent child stream
+    pub fn split(&self, key: u64) -> Self {
+        let seed = splitmix64(self.seed ^ splitmix64(key.wrapping_add(SPLITMIX_GAMMA)));
+        SplitRng { seed, kind: self.kind }
+    }
+
+    /// Seed of this stream
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Creates a generator for this stream.  Calling it twice yields the same numbers.
+    pub fn rng(&self) -> CrateRng {
+        self.kind.seed_from_u64(self.seed)
+    }
+}
+
+const SPLITMIX_GAMMA: u64 = 0x9e3779b97f4a7c15;
+
+// SplitMix64 finalizer, which scrambles nearby inputs into unrelated outputs
+#[inline]
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(SPLITMIX_GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
 
 #[cfg(test)]
 mod utils_tests {
@@ -252,6 +643,18 @@ mod utils_tests {
         assert_eq!(best_count, 0);
     }
 
+    #[test]
+    fn test_illegal_sample() {
+        assert!(Sample::new(-1.).is_err());
+        assert!(Sample::new(f32::NAN).is_err());
+
+        let mut rng = XorShiftRng::seed_from_u64(1);
+        assert!(Sample::Probability(2.).sample(10, true, &mut rng).is_err());
+        assert!(Sample::Probability(0.).sample(10, true, &mut rng).is_err());
+        assert!(Sample::Probability(f32::NAN).check().is_err());
+        assert!(Sample::Probability(1.).check().is_ok());
+    }
+
     #[test]
     fn test_choose_last() {
         let counts = vec![0, 1, 1];
@@ -268,6 +671,35 @@ mod utils_tests {
         assert_eq!(best_count, 0);
     }
 
+    #[test]
+    fn test_anchor_sharding() {
+        let nodes: Vec<_> = (0..103).collect();
+        let sharder = AnchorSharder::new(4, 2022).unwrap();
+        let shards = sharder.shards(&nodes, 1);
+        assert_eq!(shards.len(), 4);
+        assert!(shards.iter().all(|s| s.len() == 25 || s.len() == 26));
+
+        // Disjoint and complete
+        let mut all: Vec<_> = shards.iter().flatten().cloned().collect();
+        all.sort();
+        assert_eq!(all, nodes);
+
+        // Deterministic regardless of input order
+        let mut rev = nodes.clone();
+        rev.reverse();
+        assert_eq!(sharder.shard(&rev, 1, 2).unwrap(), shards[2]);
+
+        // Changes across passes
+        assert_ne!(sharder.shards(&nodes, 2)[0], shards[0]);
+
+        assert!(sharder.shard(&nodes, 1, 4).is_err());
+        assert!(AnchorSharder::new(0, 2022).is_err());
+
+        // Seeds near the top of the range wrap rather than overflow
+        let sharder = AnchorSharder::new(4, u64::MAX).unwrap();
+        assert_eq!(sharder.shards(&nodes, 2).iter().map(|s| s.len()).sum::<usize>(), 103);
+    }
+
     #[test]
     fn test_counter() {
         let counts = [0, 0, 0, 1, 2, 2, 3];
@@ -289,5 +721,120 @@ mod utils_tests {
 
     }
 
+    #[test]
+    fn test_reservoir_sample() {
+        // Every item is equally likely to survive, including the first
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut counts = [0usize; 3];
+        for _ in 0..6000 {
+            let items = (0..3).map(|node_id| (node_id, 1f32));
+            counts[reservoir_sample(items, 1, &mut rng)[0].0] += 1;
+        }
+        assert!(counts.iter().all(|c| *c > 1800 && *c < 2200), "{:?}", counts);
+    }
+
+    #[test]
+    fn test_weighted_reservoir_sample() {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+
+        // Item 0 has ten times the weight of the other nine
+        let mut hits = 0usize;
+        for _ in 0..5000 {
+            let items = (0..10).map(|i| (i, if i == 0 { 10f32 } else { 1f32 }));
+            let sample = weighted_reservoir_sample(items, 1, &mut rng);
+            assert_eq!(sample.len(), 1);
+            if sample[0].0 == 0 { hits += 1; }
+        }
+        let p = hits as f32 / 5000.;
+        assert!((p - 10. / 19.).abs() < 0.03, "{}", p);
+
+        // Long streams with skips still return a full reservoir of distinct items
+        let items = (0..10000).map(|i| (i, 1f32 + (i % 7) as f32));
+        let mut sample: Vec<_> = weighted_reservoir_sample(items, 50, &mut rng).into_iter()
+            .map(|(i, _)| i)
+            .collect();
+        sample.sort();
+        sample.dedup();
+        assert_eq!(sample.len(), 50);
+
+        // Zero weights are never sampled
+        let items = vec![(0, 0f32), (1, 1f32), (2, 0f32)];
+        assert_eq!(weighted_reservoir_sample(items.into_iter(), 2, &mut rng), vec![(1, 1f32)]);
+        assert!(weighted_reservoir_sample((0..5).map(|i| (i, 1f32)), 0, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn test_weighted_reservoir_sample_keyed() {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let items = (0..1000usize).map(|i| (i * 2, 1f32));
+        let sample = weighted_reservoir_sample_keyed(items, 20, &mut rng);
+        assert_eq!(sample.len(), 20);
+        assert!(sample.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(sample.iter().all(|(pos, item, _)| *item == pos * 2));
+    }
+
+    #[test]
+    fn test_alias_table() {
+        assert!(AliasTable::new(&[]).is_none());
+        assert!(AliasTable::new(&[0., 0.]).is_none());
+        assert!(AliasTable::new(&[1., -1.]).is_none());
+
+        let table = AliasTable::new(&[1., 0., 3.]).unwrap();
+        assert_eq!(table.len(), 3);
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut counts = [0usize; 3];
+        for _ in 0..10000 {
+            counts[table.sample(&mut rng)] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        let p = counts[2] as f32 / 10000.;
+        assert!((p - 0.75).abs() < 0.02, "{}", p);
+
+        let from_cdf = AliasTable::from_cdf(&[0.25, 0.25, 1.]).unwrap();
+        let mut counts = [0usize; 3];
+        for _ in 0..10000 {
+            counts[from_cdf.sample(&mut rng)] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        let p = counts[0] as f32 / 10000.;
+        assert!((p - 0.25).abs() < 0.02, "{}", p);
+    }
+
+    #[test]
+    fn test_edge_alias_table() {
+        use crate::graph::{CSR,CumCSR};
+        let edges = vec![(0, 1, 1.), (0, 2, 3.), (1, 0, 1.)];
+        let graph = CumCSR::convert(CSR::construct_from_edges(edges, false));
+        let table = EdgeAliasTable::new(&graph);
+
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let twos = (0..10000).filter(|_| table.sample(&graph, 0, &mut rng) == Some(2)).count();
+        let p = twos as f32 / 10000.;
+        assert!((p - 0.75).abs() < 0.02, "{}", p);
+        assert_eq!(table.sample(&graph, 1, &mut rng), Some(0));
+        assert_eq!(table.sample(&graph, 2, &mut rng), None);
+    }
+
+    #[test]
+    fn test_split_rng() {
+        let root = SplitRng::new(2023, RngKind::XorShift);
+        let stream = |pass: u64, batch: u64, node: u64| root.split(pass).split(batch).split(node);
+
+        // Reproducible
+        assert_eq!(stream(1, 2, 3).seed(), stream(1, 2, 3).seed());
+        assert_eq!(stream(1, 2, 3).rng().next_u64(), stream(1, 2, 3).rng().next_u64());
+
+        // Additive seeding would collide on all of these
+        assert_ne!(stream(1, 2, 3).seed(), stream(1, 3, 2).seed());
+        assert_ne!(stream(1, 2, 3).seed(), stream(2, 1, 3).seed());
+        assert_ne!(stream(0, 0, 1).seed(), stream(0, 1, 0).seed());
+        assert_ne!(root.split(0).seed(), root.seed());
+
+        // Same seed, different generators
+        let pcg = SplitRng::new(2023, RngKind::Pcg);
+        assert_eq!(pcg.split(5).seed(), root.split(5).seed());
+        assert_ne!(pcg.split(5).rng().next_u64(), root.split(5).rng().next_u64());
+    }
+
 }
 
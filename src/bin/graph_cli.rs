@@ -0,0 +1,18 @@
+//! Runs the standard pipeline from a TOML config.  See the cli module for the config format.
+//!
+//! ```text
+//! cargo run --release --no-default-features --features cli --bin graph_cli -- stats config.toml
+//! ```
+use std::io::{stdout,BufWriter};
+use std::process::exit;
+
+use graph_library::cli::run;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut out = BufWriter::new(stdout().lock());
+    if let Err(e) = run(&args, &mut out) {
+        eprintln!("{}", e);
+        exit(1);
+    }
+}
@@ -0,0 +1,415 @@
+//! Driver for the `graph_cli` binary, which runs the standard pipeline over an edge list and an
+//! optional feature file without needing a bespoke driver crate.  Every subcommand reads the same
+//! TOML config, using the sections it needs:
+//!
+//! ```text
+//! [graph]
+//! path = "edges.tsv"          # src<TAB>dst[<TAB>weight]
+//! weighted = false
+//! undirected = true
+//! output = "graph.tsv"        # build-graph only
+//!
+//! [features]
+//! path = "features.jsonl"     # .jsonl or .tsv, using the "node" node type
+//! min_count = 2
+//!
+//! [train-ep]
+//! output = "node_embeddings.tsv"
+//! [train-ep.params]           # EmbeddingPropagation
+//! alpha = 0.01
+//! ...
+//!
+//! [pprembed]
+//! output = "ppr_embeddings.tsv"
+//! [pprembed.params]           # PPREmbed
+//! ...
+//!
+//! [build-ann]
+//! embeddings = "node_embeddings.tsv"
+//! output = "index.ann"
+//! [build-ann.params]          # AnnBuildConfig
+//! ...
+//!
+//! [query]
+//! embeddings = "node_embeddings.tsv"
+//! index = "index.ann"
+//! k = 10
+//! ```
+//!
+//! Embeddings are written in the same format as `NodeEmbeddings.save`, so they can be loaded from
+//! python or through the ffi module.
+use std::io::Write;
+
+use serde::Deserialize;
+
+use crate::algos::ann::{Ann,AnnBuildConfig};
+use crate::algos::components::{connected_components,component_sizes};
+use crate::algos::ep::EmbeddingPropagation;
+use crate::algos::ep::model::AveragedFeatureModel;
+use crate::algos::pprembed::PPREmbed;
+use crate::algos::utils::Sample;
+use crate::config;
+use crate::distance::Distance;
+use crate::embeddings::EmbeddingStore;
+use crate::error::GraphLibError;
+use crate::feature_store::FeatureStore;
+use crate::graph::{CSR,CumCSR,Graph,SymmetrizePolicy};
+use crate::graph::io::{Compression,EDGE_LIST_NODE_TYPE,load_edge_list};
+use crate::io::{EmbeddingReader,EmbeddingWriter,open_file_for_writing};
+use crate::vocab::Vocab;
+
+const USAGE: &str = "Usage: graph_cli <build-graph|train-ep|pprembed|build-ann|query|stats> <config.toml> [nodes...]";
+
+/// Config for the whole pipeline.  Sections are only required by the subcommands which use them.
+#[derive(Deserialize)]
+pub struct CliConfig {
+    pub graph: Option<GraphConfig>,
+    pub features: Option<FeaturesConfig>,
+
+    #[serde(rename = "train-ep")]
+    pub train_ep: Option<TrainEpConfig>,
+
+    pub pprembed: Option<PPREmbedConfig>,
+
+    #[serde(rename = "build-ann")]
+    pub build_ann: Option<BuildAnnConfig>,
+
+    pub query: Option<QueryConfig>
+}
+
+#[derive(Deserialize)]
+pub struct GraphConfig {
+    /// Edge list to load
+    pub path: String,
+
+    /// Field delimiter, defaults to tab
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+
+    /// If true, reads the weight from the third field
+    #[serde(default)]
+    pub weighted: bool,
+
+    /// If true, adds the reverse of every edge, summing weights of duplicates
+    #[serde(default)]
+    pub undirected: bool,
+
+    /// Where build-graph writes the cleaned edge list
+    pub output: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct FeaturesConfig {
+    /// Feature file, either JSONL or TSV as read by FeatureStore
+    pub path: String,
+
+    /// Features occurring fewer times are dropped
+    pub min_count: Option<usize>
+}
+
+#[derive(Deserialize)]
+pub struct TrainEpConfig {
+    /// Where to write the node embeddings
+    pub output: String,
+
+    /// If provided, also writes the feature embeddings
+    pub feature_output: Option<String>,
+
+    /// Number of features to sample per node, defaulting to all of them
+    pub max_features: Option<Sample>,
+
+    /// Number of neighbors to sample when reconstructing a node
+    pub max_neighbor_nodes: Option<usize>,
+
+    pub params: EmbeddingPropagation
+}
+
+#[derive(Deserialize)]
+pub struct PPREmbedConfig {
+    /// Where to write the node embeddings
+    pub output: String,
+
+    pub params: PPREmbed
+}
+
+#[derive(Deserialize)]
+pub struct BuildAnnConfig {
+    /// Embeddings to index
+    pub embeddings: String,
+
+    #[serde(default = "default_distance")]
+    pub distance: Distance,
+
+    /// Where to write the index
+    pub output: String,
+
+    pub params: AnnBuildConfig
+}
+
+#[derive(Deserialize)]
+pub struct QueryConfig {
+    /// Embeddings the index was built over
+    pub embeddings: String,
+
+    #[serde(default = "default_distance")]
+    pub distance: Distance,
+
+    /// Index written by build-ann
+    pub index: String,
+
+    /// Number of neighbors to return
+    pub k: usize,
+
+    pub min_search_nodes: Option<usize>,
+
+    /// Node type of the queried nodes, defaulting to the edge list node type
+    pub node_type: Option<String>
+}
+
+fn default_delimiter() -> char {
+    '\t'
+}
+
+fn default_distance() -> Distance {
+    Distance::Cosine
+}
+
+/// Runs the subcommand given by the arguments, excluding the program name, writing results to
+/// `out`.
+pub fn run(args: &[String], out: &mut impl Write) -> Result<(), GraphLibError> {
+    if args.len() < 2 {
+        return Err(GraphLibError::InvalidInput(USAGE.to_string()))
+    }
+
+    let config: CliConfig = config::from_file(&args[1])?;
+    match args[0].as_str() {
+        "build-graph" => build_graph(&config, out),
+        "train-ep"    => train_ep(&config, out),
+        "pprembed"    => pprembed(&config, out),
+        "build-ann"   => build_ann(&config, out),
+        "query"       => query(&config, &args[2..], out),
+        "stats"       => stats(&config, out),
+        cmd => Err(GraphLibError::InvalidInput(format!("Unknown command {}!\n{}", cmd, USAGE)))
+    }
+}
+
+fn missing(section: &str) -> GraphLibError {
+    GraphLibError::InvalidInput(format!("Config is missing the [{}] section!", section))
+}
+
+fn load_graph(config: &CliConfig) -> Result<(Vocab, CSR), GraphLibError> {
+    let gc = config.graph.as_ref().ok_or_else(|| missing("graph"))?;
+    let (vocab, csr) = load_edge_list(&gc.path, gc.delimiter, gc.weighted, Compression::Infer)?;
+    let csr = if gc.undirected { csr.to_undirected(SymmetrizePolicy::Sum) } else { csr };
+    Ok((vocab, csr))
+}
+
+fn load_features(config: &CliConfig, vocab: &Vocab) -> Result<FeatureStore, GraphLibError> {
+    let mut features = match config.features.as_ref() {
+        Some(fc) if fc.path.ends_with(".tsv") => FeatureStore::from_tsv(&fc.path, vocab, fc.min_count)?,
+        Some(fc) => FeatureStore::from_jsonl(&fc.path, vocab, fc.min_count)?,
+        None => FeatureStore::new(vocab.len())
+    };
+    features.fill_missing_nodes();
+    Ok(features)
+}
+
+fn write_embeddings(
+    path: &str,
+    vocab: &Vocab,
+    es: &EmbeddingStore,
+    num_embeddings: usize
+) -> Result<(), GraphLibError> {
+    let mut writer = EmbeddingWriter::new(path, vocab, None)?;
+    writer.stream((0..num_embeddings).map(|node_id| (node_id, es.get_embedding(node_id))))?;
+    Ok(())
+}
+
+fn build_graph(config: &CliConfig, out: &mut impl Write) -> Result<(), GraphLibError> {
+    let output = config.graph.as_ref().and_then(|gc| gc.output.as_ref())
+        .ok_or_else(|| GraphLibError::InvalidInput("build-graph needs graph.output!".to_string()))?;
+
+    let (vocab, csr) = load_graph(config)?;
+    let mut writer = open_file_for_writing(output, None)?;
+    for (from_node, to_node, w) in csr.iter_edges() {
+        let (_, f_name) = vocab.get_name(from_node).expect("Node should be in vocab");
+        let (_, t_name) = vocab.get_name(to_node).expect("Node should be in vocab");
+        writeln!(writer, "{}\t{}\t{}", f_name, t_name, w)?;
+    }
+    writer.flush()?;
+    write_stats(&csr, out)
+}
+
+fn train_ep(config: &CliConfig, out: &mut impl Write) -> Result<(), GraphLibError> {
+    let tc = config.train_ep.as_ref().ok_or_else(|| missing("train-ep"))?;
+    let (vocab, csr) = load_graph(config)?;
+    let features = load_features(config, &vocab)?;
+    let graph = CumCSR::convert(csr);
+
+    let model = AveragedFeatureModel::new(
+        tc.max_features.unwrap_or(Sample::All), tc.max_neighbor_nodes, false, false);
+    let feature_embeddings = tc.params.learn(&graph, &features, None, &model)?;
+    let node_embeddings = tc.params.embed_nodes(&graph, &features, &feature_embeddings, &model);
+
+    write_embeddings(&tc.output, &vocab, &node_embeddings, graph.len())?;
+    if let Some(path) = tc.feature_output.as_ref() {
+        write_embeddings(path, features.get_vocab(), &feature_embeddings, features.num_features())?;
+    }
+    writeln!(out, "Wrote {} node embeddings to {}", graph.len(), tc.output)?;
+    Ok(())
+}
+
+fn pprembed(config: &CliConfig, out: &mut impl Write) -> Result<(), GraphLibError> {
+    let pc = config.pprembed.as_ref().ok_or_else(|| missing("pprembed"))?;
+    let (vocab, csr) = load_graph(config)?;
+    let features = load_features(config, &vocab)?;
+    let graph = CumCSR::convert(csr);
+
+    let embeddings = pc.params.learn(&graph, &features)?;
+    write_embeddings(&pc.output, &vocab, &embeddings, graph.len())?;
+    writeln!(out, "Wrote {} node embeddings to {}", graph.len(), pc.output)?;
+    Ok(())
+}
+
+fn build_ann(config: &CliConfig, out: &mut impl Write) -> Result<(), GraphLibError> {
+    let bc = config.build_ann.as_ref().ok_or_else(|| missing("build-ann"))?;
+    let (_vocab, es) = EmbeddingReader::load(&bc.embeddings, bc.distance, &None, None, None)?;
+
+    let mut ann = Ann::new();
+    ann.fit_with_config(&es, &bc.params, None)?;
+    ann.save(&bc.output)?;
+    writeln!(out, "Indexed {} embeddings with {} trees, max depth {}", es.len(), ann.num_trees(),
+             ann.depth().iter().max().unwrap_or(&0))?;
+    Ok(())
+}
+
+fn query(config: &CliConfig, nodes: &[String], out: &mut impl Write) -> Result<(), GraphLibError> {
+    let qc = config.query.as_ref().ok_or_else(|| missing("query"))?;
+    let (vocab, es) = EmbeddingReader::load(&qc.embeddings, qc.distance, &None, None, None)?;
+    let ann = Ann::load(&qc.index)?;
+    let node_type = qc.node_type.as_deref().unwrap_or(EDGE_LIST_NODE_TYPE);
+
+    for name in nodes.iter() {
+        let node_id = vocab.get_node_id(node_type, name)
+            .ok_or_else(|| GraphLibError::InvalidInput(format!("Unknown node {}!", name)))?;
+        let results = ann.predict(&es, es.get_embedding(node_id), qc.k, qc.min_search_nodes)?;
+        for nd in results.iter() {
+            let (_, neighbor) = vocab.get_name(nd.1).expect("Node should be in vocab");
+            writeln!(out, "{}\t{}\t{}", name, neighbor, nd.0)?;
+        }
+    }
+    Ok(())
+}
+
+fn stats(config: &CliConfig, out: &mut impl Write) -> Result<(), GraphLibError> {
+    let (_vocab, csr) = load_graph(config)?;
+    write_stats(&csr, out)
+}
+
+fn write_stats(graph: &CSR, out: &mut impl Write) -> Result<(), GraphLibError> {
+    let degrees: Vec<_> = (0..graph.len()).map(|node_id| graph.degree(node_id)).collect();
+    let isolated = degrees.iter().filter(|d| **d == 0).count();
+    let max_degree = degrees.iter().max().cloned().unwrap_or(0);
+    let mean_degree = graph.edges() as f32 / graph.len().max(1) as f32;
+    let self_loops = graph.iter_edges().filter(|(f, t, _)| f == t).count();
+    let components = component_sizes(&connected_components(graph)).iter()
+        .filter(|size| **size > 0)
+        .count();
+
+    writeln!(out, "nodes\t{}", graph.len())?;
+    writeln!(out, "edges\t{}", graph.edges())?;
+    writeln!(out, "mean_degree\t{:.3}", mean_degree)?;
+    writeln!(out, "max_degree\t{}", max_degree)?;
+    writeln!(out, "isolated_nodes\t{}", isolated)?;
+    writeln!(out, "self_loops\t{}", self_loops)?;
+    writeln!(out, "components\t{}", components)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    fn write_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    fn run_cli(args: &[&str]) -> Result<String, GraphLibError> {
+        let args: Vec<_> = args.iter().map(|a| a.to_string()).collect();
+        let mut out = Vec::new();
+        run(&args, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let edges = write_file("cli_test_edges.tsv", "a\tb\nb\tc\nc\ta\nd\te\n");
+        let features = write_file("cli_test_features.jsonl", concat!(
+            "{\"node_type\": \"node\", \"node\": \"a\", \"features\": [\"red\"]}\n",
+            "{\"node_type\": \"node\", \"node\": \"b\", \"features\": [\"red\"]}\n"));
+        let tmp = |name: &str| std::env::temp_dir().join(name).to_str().unwrap().to_string();
+        let (graph_out, emb_out, ann_out) = (tmp("cli_test_graph.tsv"), tmp("cli_test_emb.tsv"), tmp("cli_test.ann"));
+
+        let config = write_file("cli_test.toml", &format!(r#"
+            [graph]
+            path = "{}"
+            undirected = true
+            output = "{}"
+
+            [features]
+            path = "{}"
+
+            [pprembed]
+            output = "{}"
+            [pprembed.params]
+            num_walks = 100
+            steps = {{ Probability = 0.2 }}
+            beta = 0.8
+            dims = 16
+            eps = 1e-5
+            seed = 2023
+
+            [build-ann]
+            embeddings = "{}"
+            output = "{}"
+            [build-ann.params]
+            n_trees = 2
+            max_nodes_per_leaf = 10
+            test_hp_per_split = 5
+            num_sampled_nodes_split_test = 30
+            seed = 2023
+
+            [query]
+            embeddings = "{}"
+            index = "{}"
+            k = 2
+        "#, edges, graph_out, features, emb_out, emb_out, ann_out, emb_out, ann_out));
+
+        let stats = run_cli(&["stats", &config]).unwrap();
+        assert!(stats.contains("nodes\t5\n"));
+        assert!(stats.contains("edges\t8\n"));
+        assert!(stats.contains("components\t2\n"));
+
+        run_cli(&["build-graph", &config]).unwrap();
+        assert_eq!(std::fs::read_to_string(&graph_out).unwrap().lines().count(), 8);
+
+        run_cli(&["pprembed", &config]).unwrap();
+        run_cli(&["build-ann", &config]).unwrap();
+        let results = run_cli(&["query", &config, "a"]).unwrap();
+        let lines: Vec<_> = results.lines().collect();
+        assert_eq!(lines.len(), 2);
+        // a and b share a feature and are symmetric in the triangle, so are each other's neighbors
+        assert!(lines.iter().all(|l| l.starts_with("a\ta\t") || l.starts_with("a\tb\t")));
+
+        assert!(run_cli(&["query", &config, "missing"]).is_err());
+        assert!(run_cli(&["train-ep", &config]).is_err());
+        assert!(run_cli(&["unknown", &config]).is_err());
+        assert!(run_cli(&["stats"]).is_err());
+
+        for path in [edges, features, config, graph_out, emb_out, ann_out] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Reads and writes configuration structs, such as EmbeddingPropagation, PPREmbed, or
+//! AnnBuildConfig, from TOML or JSON files.  This allows experiment configs to be versioned
+//! alongside their results rather than hardcoded as struct literals.
+//!
+//! ```text
+//! alpha = 0.01
+//! loss = { MarginLoss = [1.0, 10] }
+//! loss_weighting = "DegreeLog"
+//! ...
+//! ```
+//!
+//! Enums use serde's default representation: unit variants are strings and variants with values
+//! are single key tables.  Optional fields can be omitted.
+use std::fs;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::error::GraphLibError;
+
+/// Parses a config from a JSON string
+pub fn from_json<T: DeserializeOwned>(s: &str) -> Result<T, GraphLibError> {
+    serde_json::from_str(s)
+        .map_err(|e| GraphLibError::InvalidInput(format!("Malformed JSON config: {}", e)))
+}
+
+/// Parses a config from a TOML string
+pub fn from_toml<T: DeserializeOwned>(s: &str) -> Result<T, GraphLibError> {
+    toml::from_str(s)
+        .map_err(|e| GraphLibError::InvalidInput(format!("Malformed TOML config: {}", e)))
+}
+
+/// Loads a config file, using the extension to choose between TOML and JSON
+pub fn from_file<T: DeserializeOwned>(path: &str) -> Result<T, GraphLibError> {
+    let contents = fs::read_to_string(path)?;
+    if path.ends_with(".toml") {
+        from_toml(&contents)
+    } else if path.ends_with(".json") {
+        from_json(&contents)
+    } else {
+        Err(GraphLibError::InvalidInput(format!("Unknown config format for {}!", path)))
+    }
+}
+
+/// Writes a config as pretty printed JSON, suitable for storing next to experiment results
+pub fn to_json<T: Serialize>(config: &T) -> Result<String, GraphLibError> {
+    serde_json::to_string_pretty(config)
+        .map_err(|e| GraphLibError::InvalidInput(format!("Unable to serialize config: {}", e)))
+}
+
+#[cfg(test)]
+mod config_tests {
+    use super::*;
+    use crate::algos::ann::AnnBuildConfig;
+    use crate::algos::ep::{EmbeddingPropagation,LossWeighting};
+    use crate::algos::ep::loss::Loss;
+    use crate::algos::pprembed::{PPREmbed,WeightTransform};
+    use crate::algos::utils::Sample;
+
+    #[test]
+    fn test_ep_toml() {
+        let ep: EmbeddingPropagation = from_toml(r#"
+            alpha = 0.01
+            loss = { MarginLoss = [1.0, 10] }
+            batch_size = 128
+            d_model = 32
+            passes = 5
+            hard_negs = 0
+            loss_weighting = { DegreeExponential = 0.5 }
+            seed = 2023
+            valid_pct = 0.1
+            noise = 0.0
+            weighted_positives = false
+            indicator = false
+
+            [adaptive_batch]
+            warmup_passes = 1
+            min_batch_size = 32
+            max_batch_size = 1024
+        "#).unwrap();
+
+        assert!(matches!(ep.loss, Loss::MarginLoss(m, 10) if m == 1.));
+        assert!(matches!(ep.loss_weighting, LossWeighting::DegreeExponential(w) if w == 0.5));
+        assert_eq!(ep.adaptive_batch.map(|ab| ab.max_batch_size), Some(1024));
+        assert!(ep.negative_pools.is_none());
+
+        // Round trips through JSON
+        let ep: EmbeddingPropagation = from_json(&to_json(&ep).unwrap()).unwrap();
+        assert_eq!(ep.batch_size, 128);
+        assert_eq!(ep.seed, 2023);
+    }
+
+    #[test]
+    fn test_json() {
+        let ppr: PPREmbed = from_json(r#"{
+            "num_walks": 100, "steps": {"Probability": 0.2}, "beta": 0.8,
+            "dims": 256, "eps": 1e-3, "seed": 1
+        }"#).unwrap();
+        assert!(matches!(ppr.steps, Sample::Probability(p) if p == 0.2));
+        assert!(ppr.push_eps.is_none());
+        assert_eq!(ppr.hash_count, 3);
+        assert_eq!(ppr.weight_transform, WeightTransform::Log);
+        assert!(!ppr.l2_normalize);
+
+        let config: AnnBuildConfig = from_json(&to_json(&AnnBuildConfig::new(10, 50, 2023)).unwrap()).unwrap();
+        assert_eq!(config.n_trees, 10);
+        assert_eq!(config.test_hp_per_split, 5);
+
+        let missing: Result<PPREmbed, _> = from_json("{\"num_walks\": 100}");
+        assert!(matches!(missing, Err(GraphLibError::InvalidInput(_))));
+        assert!(from_file::<PPREmbed>("config.yaml").is_err());
+    }
+}
@@ -3,6 +3,7 @@ use std::arch::x86_64::*;
 use float_ord::FloatOrd;
 
 #[derive(Copy,Clone,Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Distance {
     /// A* using Landmark Triangulation
     ALT,
@@ -1,9 +1,16 @@
 //! The main Embedding class.  This defines both distance metrics as well as access to the
 //! embeddings.
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool,AtomicU64,Ordering};
+use std::time::{SystemTime,UNIX_EPOCH};
+
 use rayon::prelude::*;
 use rand::prelude::*;
 
 use crate::graph::NodeID;
+use crate::vocab::Vocab;
+use crate::io::EmbeddingWriter;
+use crate::error::GraphLibError;
 use crate::bitset::BitSet;
 use crate::hogwild::Hogwild;
 use crate::algos::graph_ann::{TopK,NodeDistance};
@@ -36,7 +43,95 @@ pub struct EmbeddingStore {
     distance: Distance,
 
     /// Number of nodes in the Embedding Store
-    nodes: usize
+    nodes: usize,
+
+    /// Optional per node timestamps, updated whenever an embedding is written through this store.
+    /// Copied rather than shared by clones
+    timestamps: Option<Timestamps>,
+
+    /// Tracks writes to the embeddings.  Shared by clones, as they share the embeddings
+    version: Arc<VersionClock>
+}
+
+static STORE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Identifies the contents of an EmbeddingStore: the id is unique to a set of embeddings and the
+/// generation changes whenever they have been written since the last check.  Structures derived
+/// from the embeddings, such as cached results or precomputed norms, record the version they
+/// were built from to detect when they're stale.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct StoreVersion {
+    pub id: u64,
+    pub generation: u64
+}
+
+/// Writes only flag the store as dirty, which is cheap as the flag is rarely contended once set;
+/// the generation is bumped when the version is next read.
+struct VersionClock {
+    id: u64,
+    generation: AtomicU64,
+    dirty: AtomicBool
+}
+
+impl VersionClock {
+    fn new() -> Self {
+        VersionClock {
+            id: STORE_ID.fetch_add(1, Ordering::Relaxed),
+            generation: AtomicU64::new(0),
+            dirty: AtomicBool::new(false)
+        }
+    }
+
+    #[inline]
+    fn mark_dirty(&self) {
+        if !self.dirty.load(Ordering::Relaxed) {
+            self.dirty.store(true, Ordering::Release);
+        }
+    }
+
+    fn current(&self) -> StoreVersion {
+        if self.dirty.swap(false, Ordering::AcqRel) {
+            self.generation.fetch_add(1, Ordering::AcqRel);
+        }
+        StoreVersion { id: self.id, generation: self.generation.load(Ordering::Acquire) }
+    }
+}
+
+/// Tracks when each embedding was last written.  Timestamps are taken from a clock which defaults
+/// to the wall time, in seconds, when tracking was enabled but can be set by the caller, allowing
+/// pipelines to use their own notion of time (e.g. the run date or an iteration counter).
+struct Timestamps {
+    clock: AtomicU64,
+    rows: Hogwild<Vec<u64>>
+}
+
+// Each clone gets its own clock and a copy of the rows, starting from the original's, so a clone
+// only records the writes made through it: setting the clock or writing through one store doesn't
+// change the timestamps of another, even though they share the embeddings.
+impl Clone for Timestamps {
+    fn clone(&self) -> Self {
+        Timestamps {
+            clock: AtomicU64::new(self.clock.load(Ordering::Relaxed)),
+            rows: Hogwild::new(self.rows.as_slice().to_vec())
+        }
+    }
+}
+
+impl Timestamps {
+    fn new(nodes: usize) -> Self {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Timestamps {
+            clock: AtomicU64::new(now),
+            rows: Hogwild::new(vec![0; nodes])
+        }
+    }
+
+    #[inline]
+    fn touch(&self, node_id: NodeID) {
+        self.rows.get()[node_id] = self.clock.load(Ordering::Relaxed);
+    }
 }
 
 impl EmbeddingStore {
@@ -46,7 +141,9 @@ impl EmbeddingStore {
             distance,
             bitfield: BitSet::new(nodes),
             embeddings: Hogwild::new(vec![0.; nodes * dims]),
-            nodes
+            nodes,
+            timestamps: None,
+            version: Arc::new(VersionClock::new())
         }
     }
 
@@ -68,7 +165,9 @@ impl EmbeddingStore {
                 distance,
                 bitfield: bitfield,
                 embeddings: Hogwild::new(vec),
-                nodes
+                nodes,
+                timestamps: None,
+                version: Arc::new(VersionClock::new())
             };
             Some(es)
         }
@@ -91,6 +190,22 @@ impl EmbeddingStore {
         self.distance
     }
 
+    /// Current version of the embeddings.  Writes made concurrently with this call may not be
+    /// reflected until the next call.
+    pub fn version(&self) -> StoreVersion {
+        self.version.current()
+    }
+
+    /// Estimated bytes allocated for the embeddings and their metadata
+    pub fn memory_bytes(&self) -> usize {
+        let ts_bytes = if self.timestamps.is_some() {
+            self.nodes * std::mem::size_of::<u64>()
+        } else {
+            0
+        };
+        self.embeddings.len() * std::mem::size_of::<f32>() + (self.nodes / 8) + ts_bytes
+    }
+
     pub fn set_embedding(&mut self, node_id: NodeID, embedding: &[f32]) {
         self.get_embedding_mut(node_id).iter_mut().zip(embedding.iter()).for_each(|(ei, wi)| {
             *ei = *wi;
@@ -98,19 +213,103 @@ impl EmbeddingStore {
         self.bitfield.set_bit(node_id);
     }
 
+    /// Enables tracking of when each embedding was last written.  Embeddings which have never been
+    /// written since enabling have a timestamp of zero.
+    pub fn track_timestamps(&mut self) {
+        if self.timestamps.is_none() {
+            self.timestamps = Some(Timestamps::new(self.nodes));
+        }
+    }
+
+    pub fn tracks_timestamps(&self) -> bool {
+        self.timestamps.is_some()
+    }
+
+    /// Sets the clock used to timestamp subsequent writes.
+    pub fn set_clock(&self, now: u64) {
+        if let Some(ts) = &self.timestamps {
+            ts.clock.store(now, Ordering::Relaxed);
+        }
+    }
+
+    /// Gets the time an embedding was last written, if tracking is enabled.
+    pub fn last_updated(&self, node_id: NodeID) -> Option<u64> {
+        self.timestamps.as_ref().map(|ts| ts.rows[node_id])
+    }
+
+    /// Returns the last updated time for all embeddings, if tracking is enabled.
+    pub fn timestamps(&self) -> Option<&[u64]> {
+        self.timestamps.as_ref().map(|ts| ts.rows.as_slice())
+    }
+
+    /// Finds all nodes which were last written before `threshold`, ordered from most stale to
+    /// least stale.  Returns an empty set if tracking isn't enabled.
+    pub fn stale_nodes(&self, threshold: u64) -> Vec<NodeID> {
+        if let Some(ts) = &self.timestamps {
+            let mut stale: Vec<_> = ts.rows.par_iter().enumerate()
+                .filter(|(_, t)| **t < threshold)
+                .map(|(node_id, _)| node_id)
+                .collect();
+            stale.par_sort_by_key(|node_id| (ts.rows[*node_id], *node_id));
+            stale
+        } else {
+            Vec::new()
+        }
+    }
+
     pub fn get_embedding(&self, node_id: NodeID) -> &[f32] {
         let start = node_id * self.dims;
         &self.embeddings[start..start+self.dims]
     }
 
+    /// Embedding of the node named by a qualified "node_type:name" string in the vocab
+    pub fn get_named_embedding(
+        &self,
+        vocab: &Vocab,
+        qualified: &str
+    ) -> Result<&[f32], GraphLibError> {
+        let node_id = vocab.lookup_qualified(qualified, self.len())?;
+        Ok(self.get_embedding(node_id))
+    }
+
+    /// Writes the embeddings as `node_type<TAB>name<TAB>[vector]` records, keyed by the vocab,
+    /// which can be read back with EmbeddingReader
+    pub fn export(
+        &self,
+        path: &str,
+        vocab: &Vocab,
+        comp_level: Option<u32>
+    ) -> Result<(), GraphLibError> {
+        if vocab.len() < self.len() {
+            return Err(GraphLibError::DimensionMismatch { expected: self.len(), found: vocab.len() })
+        }
+
+        let mut writer = EmbeddingWriter::new(path, vocab, comp_level)?;
+        writer.stream((0..self.len()).map(|node_id| (node_id, self.get_embedding(node_id))))?;
+        Ok(())
+    }
+
+    /// All embeddings as a single row major slice
+    pub fn as_slice(&self) -> &[f32] {
+        self.embeddings.get().as_slice()
+    }
+
     pub fn get_embedding_mut(&mut self, node_id: NodeID) -> &mut [f32] {
         let start = node_id * self.dims;
         self.bitfield.set_bit(node_id);
+        self.version.mark_dirty();
+        if let Some(ts) = &self.timestamps {
+            ts.touch(node_id);
+        }
         &mut self.embeddings[start..start+self.dims]
     }
 
     pub fn get_embedding_mut_hogwild(&self, node_id: NodeID) -> &mut [f32] {
         let start = node_id * self.dims;
+        self.version.mark_dirty();
+        if let Some(ts) = &self.timestamps {
+            ts.touch(node_id);
+        }
         &mut self.embeddings.get()[start..start+self.dims]
     }
 
@@ -154,22 +353,30 @@ impl EmbeddingStore {
     ) -> Vec<NodeDistance>  
         where F: Sync + Fn(NodeID) -> bool 
     {
-        let query_emb = self.extract_vec(q);
-        (0..self.len()).into_par_iter().map(|node_id| {
-            let dist = if filter(node_id) {
+        self.top_k(self.extract_vec(q), k, filter)
+    }
+
+    /// Exact top k search against an adhoc query, scanning the full store in parallel.  Nodes
+    /// for which the filter returns false are skipped entirely.  For small to medium stores this
+    /// is often fast enough to serve directly, and it's the ground truth for ANN recall.
+    pub fn top_k<F>(
+        &self,
+        query: &[f32],
+        k: usize,
+        filter: F
+    ) -> Vec<NodeDistance>
+        where F: Sync + Fn(NodeID) -> bool
+    {
+        (0..self.len()).into_par_iter()
+            .filter(|node_id| filter(*node_id))
+            .fold(|| TopK::new(k), |mut acc, node_id| {
                 let node_emb = self.get_embedding(node_id);
-                self.distance.compute(query_emb, node_emb)
-            } else {
-                std::f32::MAX
-            };
-            (node_id, dist)
-        }).fold(|| TopK::new(k), |mut acc, (node_id, dist)| {
-            acc.push(node_id, dist);
-            acc
-        }).reduce(|| TopK::new(k),|mut tk1, tk2| {
-            tk1.extend(tk2);
-            tk1
-        }).into_sorted()
+                acc.push(node_id, self.distance.compute(query, node_emb));
+                acc
+            }).reduce(|| TopK::new(k),|mut tk1, tk2| {
+                tk1.extend(tk2);
+                tk1
+            }).into_sorted()
     }
 
 }
@@ -214,6 +421,87 @@ mod embedding_tests {
         assert_eq!(es.compute_distance(&Entity::Node(0), &Entity::Node(35)), 8f32.sqrt());
     }
 
+    #[test]
+    fn test_named_export() {
+        let mut vocab = Vocab::new();
+        vocab.get_or_insert("gig", "a");
+        vocab.get_or_insert("user", "b:c");
+        let mut es = EmbeddingStore::new(2, 2, Distance::Cosine);
+        es.set_embedding(0, &[1., 0.]);
+        es.set_embedding(1, &[0.5, 2.]);
+        assert_eq!(es.get_named_embedding(&vocab, "user:b:c").unwrap(), &[0.5, 2.]);
+        assert!(es.get_named_embedding(&vocab, "gig:b").is_err());
+
+        let path = std::env::temp_dir().join("embeddings_test_export.txt");
+        let path = path.to_str().unwrap();
+        es.export(path, &vocab, None).unwrap();
+        let (loaded_vocab, loaded) = crate::io::EmbeddingReader::load(
+            path, Distance::Cosine, &None, None, None).unwrap();
+        for qualified in ["gig:a", "user:b:c"] {
+            assert_eq!(loaded.get_named_embedding(&loaded_vocab, qualified).unwrap(),
+                       es.get_named_embedding(&vocab, qualified).unwrap());
+        }
+
+        let short = EmbeddingStore::new(3, 2, Distance::Cosine);
+        assert!(short.export(path, &vocab, None).is_err());
+    }
+
+    #[test]
+    fn test_versions() {
+        let mut es = EmbeddingStore::new(4, 2, Distance::Euclidean);
+        let version = es.version();
+        assert_eq!(es.version(), version);
+
+        es.set_embedding(1, &[1., 1.]);
+        let written = es.version();
+        assert_ne!(written, version);
+        assert_eq!(es.version(), written);
+
+        es.get_embedding_mut_hogwild(2)[0] = 1.;
+        assert_ne!(es.version(), written);
+
+        // Clones share their embeddings, and so their versions, but new stores don't
+        let clone = es.clone();
+        assert_eq!(clone.version(), es.version());
+        clone.get_embedding_mut_hogwild(0)[0] = 1.;
+        assert_eq!(clone.version(), es.version());
+        assert_ne!(EmbeddingStore::new(4, 2, Distance::Euclidean).version().id, es.version().id);
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let mut es = EmbeddingStore::new(4, 2, Distance::Euclidean);
+        assert_eq!(es.last_updated(0), None);
+        assert_eq!(es.stale_nodes(10), Vec::<NodeID>::new());
+
+        es.track_timestamps();
+        es.set_clock(5);
+        es.set_embedding(1, &[1., 1.]);
+        es.set_clock(10);
+        es.set_embedding(2, &[1., 1.]);
+        es.get_embedding_mut_hogwild(3)[0] = 1.;
+
+        assert_eq!(es.last_updated(0), Some(0));
+        assert_eq!(es.last_updated(1), Some(5));
+        assert_eq!(es.last_updated(2), Some(10));
+        assert_eq!(es.last_updated(3), Some(10));
+        assert_eq!(es.stale_nodes(10), vec![0, 1]);
+        assert_eq!(es.timestamps(), Some(&[0, 5, 10, 10][..]));
+
+        // Clones keep their own clocks and timestamps
+        let mut clone = es.clone();
+        clone.set_clock(20);
+        es.set_embedding(0, &[1., 1.]);
+        assert_eq!(es.last_updated(0), Some(10));
+        assert_eq!(clone.last_updated(0), Some(0));
+        clone.set_embedding(0, &[1., 1.]);
+        assert_eq!(clone.last_updated(0), Some(20));
+        assert_eq!(es.last_updated(0), Some(10));
+        clone.set_embedding(1, &[2., 2.]);
+        assert_eq!(clone.timestamps(), Some(&[20, 20, 10, 10][..]));
+        assert_eq!(es.timestamps(), Some(&[10, 5, 10, 10][..]));
+    }
+
     #[test]
     fn test_distances() {
         let alt_d = Distance::ALT.compute(&[1., 2., 1.], &[3., 2., 4.]);
@@ -236,4 +524,19 @@ mod embedding_tests {
         assert_eq!(overlap_d, 1. - 1. / 4.);
     }
 
+    #[test]
+    fn test_top_k() {
+        let mut es = EmbeddingStore::new(5, 1, Distance::Euclidean);
+        for node_id in 0..5 {
+            es.set_embedding(node_id, &[node_id as f32]);
+        }
+
+        let results = es.top_k(&[2.2], 2, |_| true);
+        assert_eq!(results.iter().map(|nd| nd.1).collect::<Vec<_>>(), vec![2, 3]);
+
+        // Filtered nodes are never returned, even when fewer than k remain
+        let results = es.top_k(&[2.2], 3, |node_id| node_id < 2);
+        assert_eq!(results.iter().map(|nd| nd.1).collect::<Vec<_>>(), vec![1, 0]);
+    }
+
 }
@@ -0,0 +1,88 @@
+//! Crate wide error type.  Historically bad inputs would panic deep within a parallel loop, taking
+//! the host process down with them; the public entry points now validate their inputs up front and
+//! report failures through GraphLibError instead.
+use std::fmt;
+use std::io;
+
+/// Errors surfaced by the public APIs
+#[derive(Debug)]
+pub enum GraphLibError {
+    /// An argument or configuration value was illegal
+    InvalidInput(String),
+
+    /// Embeddings, queries, or stores didn't have the expected size
+    DimensionMismatch { expected: usize, found: usize },
+
+    /// The graph, or the set of nodes to operate on, was empty
+    EmptyGraph,
+
+    /// The index was queried before being fit
+    EmptyIndex,
+
+    /// Reading or writing failed
+    Io(io::Error)
+}
+
+impl fmt::Display for GraphLibError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphLibError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
+            GraphLibError::DimensionMismatch { expected, found } => {
+                write!(f, "Dimension mismatch: expected {}, found {}", expected, found)
+            },
+            GraphLibError::EmptyGraph => write!(f, "Graph is empty!"),
+            GraphLibError::EmptyIndex => write!(f, "Index has not been fit!"),
+            GraphLibError::Io(e) => write!(f, "IO error: {}", e)
+        }
+    }
+}
+
+impl std::error::Error for GraphLibError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GraphLibError::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for GraphLibError {
+    fn from(e: io::Error) -> Self {
+        GraphLibError::Io(e)
+    }
+}
+
+/// Most constructors return static string errors, which we treat as invalid input
+impl From<&'static str> for GraphLibError {
+    fn from(msg: &'static str) -> Self {
+        GraphLibError::InvalidInput(msg.to_string())
+    }
+}
+
+/// Checks that an embedding has the expected number of dimensions
+pub(crate) fn check_dims(expected: usize, found: usize) -> Result<(), GraphLibError> {
+    if expected != found {
+        Err(GraphLibError::DimensionMismatch { expected, found })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod error_tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        let e: GraphLibError = "bad config".into();
+        assert_eq!(e.to_string(), "Invalid input: bad config");
+
+        let e: GraphLibError = io::Error::new(io::ErrorKind::NotFound, "missing").into();
+        assert!(matches!(e, GraphLibError::Io(_)));
+        assert!(std::error::Error::source(&e).is_some());
+
+        assert!(check_dims(3, 3).is_ok());
+        assert!(matches!(check_dims(3, 2),
+                         Err(GraphLibError::DimensionMismatch { expected: 3, found: 2 })));
+    }
+}
@@ -1,7 +1,43 @@
 //! Defines the FeatureStore class which is used to define discrete features for each node
+//!
+//! Every feature lives in a namespace, or field, such as "title_tokens", "category", or
+//! "seller_country".  Namespaces are the feature types in the vocabulary and are given dense ids,
+//! which lets models treat fields differently and lets experiments mask out entire fields.
+//!
+//! Nodes can also have dense numeric columns, such as price or rating, which can't be expressed as
+//! discrete features.  EP models learn a linear projection of the dense vector, stored as one
+//! extra feature embedding per column after the discrete features.
 use std::sync::Arc;
-use crate::NodeID;
+use std::fs::File;
+use std::io::{BufRead,BufReader,BufWriter,Read,Write,Error as IOError,ErrorKind,Result as IOResult};
+
+use hashbrown::HashMap;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+use serde_json::Value;
+
+use crate::graph::NodeID;
 use crate::vocab::Vocab;
+use crate::embeddings::{EmbeddingStore,randomize_embedding_store};
+use crate::distance::Distance;
+use crate::error::GraphLibError;
+use crate::io::{RecordReader,EmbeddingReader,EmbeddingWriter,open_file_for_reading};
+
+/// Identifies the binary FeatureStore format
+const MAGIC: u64 = 0x4645_4154_5354_4f52;
+
+/// Binary format version, bumped on incompatible changes.  Version 2 added dense columns.
+const VERSION: u64 = 2;
+
+/// Namespace used for features which don't specify one
+const DEFAULT_NAMESPACE: &str = "feat";
+
+/// Namespace dense column embeddings are exported under
+const DENSE_NAMESPACE: &str = "__dense__";
+
+/// Parsed features for a node, as (namespace, feature) pairs
+type RawFeatures = (NodeID, Vec<(String, String)>);
 
 /// Makes it compatible for with feature setting
 struct ArcWrap(Arc<String>);
@@ -22,6 +58,16 @@ pub struct FeatureStore {
 
     /// Maps a raw feature to a feature_id
     feature_vocab: Vocab,
+
+    /// Dense numeric columns
+    dense: DenseFeatures
+}
+
+/// Dense columns, stored row major by node
+#[derive(Clone,Debug,Default)]
+struct DenseFeatures {
+    names: Vec<String>,
+    values: Vec<f32>
 }
 
 impl FeatureStore {
@@ -30,6 +76,27 @@ impl FeatureStore {
         FeatureStore {
             features: vec![Vec::with_capacity(0); size],
             feature_vocab: Vocab::new(),
+            dense: DenseFeatures::default()
+        }
+    }
+
+    /// Gives every node a single feature of its own, ("node", node_id), so feature ids are
+    /// NodeIDs.  Learning over this store learns free embeddings per node.
+    pub fn identity(size: usize) -> Self {
+        let mut fs = FeatureStore::new(size);
+        fs.fill_missing_nodes();
+        fs
+    }
+
+    /// Appends the node's own feature, ("node", node_id), to every node.  Each node then learns
+    /// a free embedding alongside its shared features, which carries nodes whose features are
+    /// uninformative.
+    pub fn add_node_features(&mut self) {
+        for i in 0..self.features.len() {
+            let feat_id = self.feature_vocab.get_or_insert("node", i.to_string());
+            if !self.features[i].contains(&feat_id) {
+                self.features[i].push(feat_id);
+            }
         }
     }
 
@@ -47,6 +114,22 @@ impl FeatureStore {
             .collect()
     }
 
+    /// Appends features to a node, keeping its existing features.
+    pub fn add_features<A,B>(
+        &mut self, 
+        node: NodeID, 
+        node_features: impl Iterator<Item=(A, B)>
+    )
+        where
+            A: AsRef<str>,
+            B: AsRef<str>
+    {
+        for (ft, fname) in node_features {
+            let feat_id = self.feature_vocab.get_or_insert(ft.as_ref(), fname.as_ref());
+            self.features[node].push(feat_id);
+        }
+    }
+
     pub fn set_features_raw(
         &mut self, 
         node: NodeID, 
@@ -70,6 +153,17 @@ impl FeatureStore {
         }).collect()
     }
 
+    /// Pretty features of the node named by a qualified "node_type:name" string in the graph's
+    /// vocab
+    pub fn get_named_features(
+        &self,
+        vocab: &Vocab,
+        qualified: &str
+    ) -> Result<Vec<(String, String)>, GraphLibError> {
+        let node_id = vocab.lookup_qualified(qualified, self.num_nodes())?;
+        Ok(self.get_pretty_features(node_id))
+    }
+
     pub fn num_features(&self) -> usize {
         self.feature_vocab.len()
     }
@@ -88,6 +182,210 @@ impl FeatureStore {
         }
     }
 
+    /// Names of all feature namespaces, indexed by namespace id
+    pub fn namespaces(&self) -> &[Arc<String>] {
+        self.feature_vocab.node_types()
+    }
+
+    /// Looks up the id of a namespace
+    pub fn namespace_id(&self, namespace: &str) -> Option<usize> {
+        self.feature_vocab.get_node_type_id_by_name(namespace)
+    }
+
+    /// Namespace id of a feature
+    pub fn feature_namespace(&self, feat_id: usize) -> usize {
+        self.feature_vocab.get_node_type_id(feat_id)
+            .expect("Feature id not in vocabulary!")
+    }
+
+    /// Namespace id of every feature, indexed by feature id.  Models use this to apply per
+    /// namespace treatment without going through the vocabulary.
+    pub fn feature_namespaces(&self) -> Vec<usize> {
+        (0..self.feature_vocab.len()).map(|feat_id| self.feature_namespace(feat_id)).collect()
+    }
+
+    /// Features for a node belonging to the given namespace
+    pub fn get_features_in_namespace(
+        &self,
+        node: NodeID,
+        namespace_id: usize
+    ) -> impl Iterator<Item=usize> + '_ {
+        self.features[node].iter()
+            .filter(move |f_i| self.feature_namespace(**f_i) == namespace_id)
+            .cloned()
+    }
+
+    /// Returns a copy of the FeatureStore with all features in the provided namespaces removed
+    /// from every node.  Feature ids are unchanged, so embeddings learned on the original store can
+    /// be reused for masking experiments.
+    pub fn mask_namespaces(&self, namespaces: &[&str]) -> FeatureStore {
+        let masked: Vec<_> = namespaces.iter()
+            .filter_map(|ns| self.namespace_id(ns))
+            .collect();
+
+        let features = self.features.iter().map(|feats| {
+            feats.iter()
+                .filter(|f_i| !masked.contains(&self.feature_namespace(**f_i)))
+                .cloned()
+                .collect()
+        }).collect();
+
+        FeatureStore { features, feature_vocab: self.clone_vocab(), dense: self.dense.clone() }
+    }
+
+    /// Merges nodes into groups, such as the super nodes of a coarsened graph.  Each group gets the
+    /// union of its members' features and the mean of their dense values.  Feature ids are
+    /// unchanged, so feature embeddings transfer between the two stores.
+    pub fn merge_nodes(&self, membership: &[usize], num_groups: usize) -> FeatureStore {
+        let mut features = vec![Vec::new(); num_groups];
+        membership.iter().enumerate().for_each(|(node_id, group)| {
+            features[*group].extend_from_slice(&self.features[node_id]);
+        });
+        features.iter_mut().for_each(|feats: &mut Vec<usize>| {
+            feats.sort_unstable();
+            feats.dedup();
+        });
+
+        let dims = self.dense_dims();
+        let mut values = vec![0f32; num_groups * dims];
+        if dims > 0 {
+            let mut counts = vec![0usize; num_groups];
+            membership.iter().enumerate().for_each(|(node_id, group)| {
+                counts[*group] += 1;
+                values[group * dims..(group + 1) * dims].iter_mut()
+                    .zip(self.get_dense(node_id).iter())
+                    .for_each(|(v, d)| *v += d);
+            });
+            values.chunks_mut(dims).zip(counts.iter())
+                .filter(|(_, c)| **c > 0)
+                .for_each(|(row, c)| row.iter_mut().for_each(|v| *v /= *c as f32));
+        }
+
+        let dense = DenseFeatures { names: self.dense.names.clone(), values };
+        FeatureStore { features, feature_vocab: self.clone_vocab(), dense }
+    }
+
+    /// Restricts the store to a subset of nodes, such as a graph partition, where node i of the new
+    /// store is `nodes[i]`.  Feature ids are unchanged, so feature embeddings are shared between
+    /// the two stores.
+    pub fn subset(&self, nodes: &[NodeID]) -> FeatureStore {
+        let features = nodes.iter().map(|node_id| self.features[*node_id].clone()).collect();
+        let values = nodes.iter()
+            .flat_map(|node_id| self.get_dense(*node_id).iter().cloned())
+            .collect();
+        let dense = DenseFeatures { names: self.dense.names.clone(), values };
+        FeatureStore { features, feature_vocab: self.clone_vocab(), dense }
+    }
+
+    /// Stacks a second copy of the store after the first, for two-tower training.  Nodes
+    /// [n, 2n) carry the features of nodes [0, n) from a copy of the vocabulary whose namespaces
+    /// are prefixed with `prefix`, so feature `f` of the copy has id `num_features() + f`.  Fails
+    /// if the store has dense columns or the prefixed namespaces already exist.
+    pub fn stack_towers(&self, prefix: &str) -> Result<FeatureStore, GraphLibError> {
+        if self.dense_dims() > 0 {
+            return Err("Stacking towers doesn't support dense features!".into())
+        }
+
+        let offset = self.num_features();
+        let mut feature_vocab = self.clone_vocab();
+        for feat_id in 0..offset {
+            let (ns, name) = self.feature_vocab.get_name(feat_id)
+                .expect("Feature id not in vocabulary!");
+            let copy_id = feature_vocab.get_or_insert(format!("{}{}", prefix, ns), name);
+            if copy_id != offset + feat_id {
+                return Err(GraphLibError::InvalidInput(
+                    format!("Namespace prefix {} collides with existing namespaces", prefix)))
+            }
+        }
+
+        let mut features = self.features.clone();
+        features.extend(self.features.iter()
+            .map(|feats| feats.iter().map(|f_i| offset + f_i).collect::<Vec<_>>()));
+        Ok(FeatureStore { features, feature_vocab, dense: DenseFeatures::default() })
+    }
+
+    /// Mask over every feature embedding, including dense columns, which is true for features in
+    /// the provided namespaces.  Useful for freezing pretrained features during training.
+    pub fn namespace_mask(&self, namespaces: &[&str]) -> Vec<bool> {
+        let ns_ids: Vec<_> = namespaces.iter()
+            .filter_map(|ns| self.namespace_id(ns))
+            .collect();
+
+        let mut mask: Vec<_> = (0..self.num_features())
+            .map(|feat_id| ns_ids.contains(&self.feature_namespace(feat_id)))
+            .collect();
+        mask.resize(self.num_embeddings(), false);
+        mask
+    }
+
+    /// Exports the vocabulary grouped by namespace as (namespace, [(feature id, name)]), in
+    /// namespace id order.
+    pub fn vocab_by_namespace(&self) -> Vec<(Arc<String>, Vec<(usize, String)>)> {
+        let mut groups: Vec<_> = self.namespaces().iter()
+            .map(|ns| (ns.clone(), Vec::new()))
+            .collect();
+
+        for feat_id in 0..self.feature_vocab.len() {
+            let ns_id = self.feature_namespace(feat_id);
+            let (_ns, name) = self.feature_vocab.get_name(feat_id)
+                .expect("Should never be unavailable!");
+            groups[ns_id].1.push((feat_id, name.to_string()));
+        }
+        groups
+    }
+
+    /// Adds dense numeric columns, initialized to zero for every node.
+    pub fn add_dense_columns<S: AsRef<str>>(&mut self, names: &[S]) {
+        let old_dims = self.dense_dims();
+        let new_dims = old_dims + names.len();
+        let mut values = vec![0f32; self.num_nodes() * new_dims];
+        if old_dims > 0 {
+            values.chunks_mut(new_dims).zip(self.dense.values.chunks(old_dims)).for_each(|(new, old)| {
+                new[..old_dims].copy_from_slice(old);
+            });
+        }
+        self.dense.values = values;
+        self.dense.names.extend(names.iter().map(|n| n.as_ref().to_string()));
+    }
+
+    /// Sets the dense values for a node, which must have one value per dense column.
+    pub fn set_dense(&mut self, node: NodeID, values: &[f32]) -> Result<(), &'static str> {
+        let dims = self.dense_dims();
+        if values.len() != dims {
+            return Err("Number of values doesn't match the number of dense columns!")
+        }
+        self.dense.values[node * dims..(node + 1) * dims].copy_from_slice(values);
+        Ok(())
+    }
+
+    /// Dense values for a node, empty if there are no dense columns
+    pub fn get_dense(&self, node: NodeID) -> &[f32] {
+        let dims = self.dense_dims();
+        &self.dense.values[node * dims..(node + 1) * dims]
+    }
+
+    /// Number of dense columns
+    pub fn dense_dims(&self) -> usize {
+        self.dense.names.len()
+    }
+
+    /// Names of the dense columns
+    pub fn dense_names(&self) -> &[String] {
+        &self.dense.names
+    }
+
+    /// Feature embedding id holding the projection for a dense column.  Dense projections are
+    /// placed after the discrete features, so adding features shifts them.
+    pub fn dense_feature_id(&self, column: usize) -> usize {
+        self.num_features() + column
+    }
+
+    /// Number of feature embeddings needed to train on the store: one per discrete feature plus
+    /// one per dense column.
+    pub fn num_embeddings(&self) -> usize {
+        self.num_features() + self.dense_dims()
+    }
+
     pub fn get_vocab(&self) -> &Vocab {
         &self.feature_vocab
     }
@@ -132,8 +430,656 @@ impl FeatureStore {
 
             new_fs.set_features(node_id, new_feats);
         });
+        new_fs.dense = self.dense.clone();
         new_fs
     }
 
+    /// Number of nodes each feature appears in, counting repeats within a node once.
+    pub fn document_frequencies(&self) -> Vec<usize> {
+        let mut counts = vec![0usize; self.feature_vocab.len()];
+        let mut last_seen = vec![usize::MAX; self.feature_vocab.len()];
+        for (node_id, feats) in self.features.iter().enumerate() {
+            for f_i in feats.iter() {
+                if last_seen[*f_i] != node_id {
+                    last_seen[*f_i] = node_id;
+                    counts[*f_i] += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Drops features which appear in fewer than `min_count` nodes or in more than `max_df`
+    /// fraction of nodes, compacting the feature id space.  Surviving features keep their relative
+    /// order and namespace.  Returns the pruned store along with the mapping from old feature ids
+    /// to new ones, None for pruned features.
+    ///
+    /// Nodes can lose all of their features; call `fill_missing_nodes` before training if needed.
+    pub fn prune(&self, min_count: usize, max_df: f32) -> (FeatureStore, Vec<Option<usize>>) {
+        let dfs = self.document_frequencies();
+        let max_count = (max_df * self.features.len() as f32).floor() as usize;
+
+        let mut new_vocab = Vocab::new();
+        let remapping: Vec<_> = dfs.iter().enumerate().map(|(f_i, df)| {
+            if *df >= min_count && *df <= max_count {
+                let (ns, name) = self.feature_vocab.get_name(f_i)
+                    .expect("Should never be unavailable!");
+                Some(new_vocab.get_or_insert((*ns).clone(), name))
+            } else {
+                None
+            }
+        }).collect();
+
+        let features = self.features.par_iter().map(|feats| {
+            feats.iter().filter_map(|f_i| remapping[*f_i]).collect()
+        }).collect();
+
+        let fs = FeatureStore { features, feature_vocab: new_vocab, dense: self.dense.clone() };
+        (fs, remapping)
+    }
+
+    /// Saves the FeatureStore, including the feature vocabulary, in a compact binary format.  All
+    /// integers are little endian u64s and strings are length prefixed UTF-8:
+    ///
+    /// ```text
+    /// [magic][version][num_nodes][num_namespaces][num_features]
+    /// [namespace] * num_namespaces
+    /// [namespace id][feature name] * num_features
+    /// [count][feature id * count] * num_nodes
+    /// [num_dense][dense name] * num_dense
+    /// [f32 value * num_dense] * num_nodes
+    /// ```
+    ///
+    /// Loading the file reproduces identical feature and namespace ids.
+    pub fn save(&self, path: &str) -> IOResult<()> {
+        let mut bw = BufWriter::new(File::create(path)?);
+        self.write_to(&mut bw)?;
+        bw.flush()
+    }
+
+    /// Writes the binary format to any writer.
+    pub fn write_to(&self, w: &mut impl Write) -> IOResult<()> {
+        let namespaces = self.namespaces();
+        for v in [MAGIC, VERSION, self.num_nodes() as u64, namespaces.len() as u64, self.num_features() as u64] {
+            write_u64(w, v)?;
+        }
+
+        for ns in namespaces.iter() {
+            write_str(w, ns)?;
+        }
+
+        for feat_id in 0..self.num_features() {
+            let (_ns, name) = self.feature_vocab.get_name(feat_id)
+                .expect("Should never be unavailable!");
+            write_u64(w, self.feature_namespace(feat_id) as u64)?;
+            write_str(w, name)?;
+        }
+
+        for feats in self.features.iter() {
+            write_u64(w, feats.len() as u64)?;
+            for f_i in feats.iter() {
+                write_u64(w, *f_i as u64)?;
+            }
+        }
+
+        write_u64(w, self.dense_dims() as u64)?;
+        for name in self.dense.names.iter() {
+            write_str(w, name)?;
+        }
+        for v in self.dense.values.iter() {
+            w.write_all(&v.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Loads a FeatureStore previously written with `save`.
+    pub fn load(path: &str) -> IOResult<Self> {
+        FeatureStore::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Reads the binary format from any reader.
+    pub fn read_from(r: &mut impl Read) -> IOResult<Self> {
+        if read_u64(r)? != MAGIC {
+            return Err(invalid_data("Not a FeatureStore file!"))
+        }
+        let version = read_u64(r)?;
+        if version == 0 || version > VERSION {
+            return Err(invalid_data("Unsupported FeatureStore version!"))
+        }
+
+        let num_nodes = read_u64(r)? as usize;
+        let num_namespaces = read_u64(r)? as usize;
+        let num_features = read_u64(r)? as usize;
+
+        let namespaces = (0..num_namespaces)
+            .map(|_| read_str(r))
+            .collect::<IOResult<Vec<_>>>()?;
+
+        // Features were originally inserted in id order, so reinserting them in the same order
+        // recreates both the feature and namespace ids.
+        let mut feature_vocab = Vocab::new();
+        for feat_id in 0..num_features {
+            let ns_id = read_u64(r)? as usize;
+            let name = read_str(r)?;
+            let ns = namespaces.get(ns_id)
+                .ok_or_else(|| invalid_data("Namespace id out of range!"))?;
+            if feature_vocab.get_or_insert(ns.as_str(), name) != feat_id {
+                return Err(invalid_data("Duplicate feature in vocabulary!"))
+            }
+        }
+
+        let mut features = Vec::with_capacity(num_nodes);
+        for _ in 0..num_nodes {
+            let count = read_u64(r)? as usize;
+            let feats = (0..count).map(|_| {
+                let f_i = read_u64(r)? as usize;
+                if f_i < num_features { Ok(f_i) } else { Err(invalid_data("Feature id out of range!")) }
+            }).collect::<IOResult<Vec<_>>>()?;
+            features.push(feats);
+        }
+
+        // Version 1 files predate dense columns
+        let mut dense = DenseFeatures::default();
+        if version >= 2 {
+            let num_dense = read_u64(r)? as usize;
+            dense.names = (0..num_dense)
+                .map(|_| read_str(r))
+                .collect::<IOResult<Vec<_>>>()?;
+            dense.values = (0..(num_nodes * num_dense)).map(|_| {
+                let mut buff = [0u8; 4];
+                r.read_exact(&mut buff)?;
+                Ok(f32::from_le_bytes(buff))
+            }).collect::<IOResult<Vec<_>>>()?;
+        }
+
+        Ok(FeatureStore { features, feature_vocab, dense })
+    }
+
+    /// Writes feature embeddings, such as those learned by EP, keyed by name rather than feature
+    /// id.  Each line is a `namespace<TAB>feature<TAB>[vector]` record, the same format as node
+    /// embeddings, so they can be loaded as NodeEmbeddings.  Dense column embeddings are written
+    /// under the "__dense__" namespace.
+    pub fn write_embeddings(
+        &self, 
+        path: &str, 
+        feature_embeddings: &EmbeddingStore,
+        comp_level: Option<u32>
+    ) -> Result<(), GraphLibError> {
+        if feature_embeddings.len() < self.num_embeddings() {
+            return Err(GraphLibError::DimensionMismatch { 
+                expected: self.num_embeddings(), 
+                found: feature_embeddings.len()
+            })
+        }
+
+        let vocab = self.embedding_vocab();
+        let mut writer = EmbeddingWriter::new(path, &vocab, comp_level)?;
+        writer.stream((0..self.num_embeddings()).map(|feat_id| {
+            (feat_id, feature_embeddings.get_embedding(feat_id))
+        }))?;
+        Ok(())
+    }
+
+    /// Reads feature embeddings written by `write_embeddings`, possibly from a different
+    /// FeatureStore, and aligns them to this store's feature ids by name.  Features missing from
+    /// the file are randomly initialized and records for unknown features are ignored.  Returns
+    /// the embeddings along with a mask of which features were loaded, which can be used to
+    /// freeze them while training the rest.
+    pub fn read_embeddings(
+        &self, 
+        path: &str, 
+        seed: u64
+    ) -> Result<(EmbeddingStore, Vec<bool>), GraphLibError> {
+        let (file_vocab, file_es) = EmbeddingReader::load(path, Distance::Cosine, &None, None, None)?;
+        let mut es = EmbeddingStore::new(self.num_embeddings(), file_es.dims(), Distance::Cosine);
+        let mut rng = XorShiftRng::seed_from_u64(seed);
+        randomize_embedding_store(&mut es, &mut rng);
+
+        let mut loaded = vec![false; self.num_embeddings()];
+        let table = file_vocab.create_translation_table(&self.embedding_vocab());
+        for (file_id, feat_id) in table.into_iter().enumerate() {
+            if let Some(feat_id) = feat_id {
+                es.get_embedding_mut(feat_id).copy_from_slice(file_es.get_embedding(file_id));
+                loaded[feat_id] = true;
+            }
+        }
+        Ok((es, loaded))
+    }
+
+    // Feature vocabulary with dense columns appended, so ids line up with feature embeddings
+    fn embedding_vocab(&self) -> Vocab {
+        let mut vocab = self.clone_vocab();
+        for name in self.dense.names.iter() {
+            vocab.get_or_insert(DENSE_NAMESPACE, name);
+        }
+        vocab
+    }
+
+    /// Loads features from a JSONL file, one node per line:
+    ///
+    /// ```text
+    /// {"node_type": "gig", "node": "123", "features": ["logo", "design"]}
+    /// {"node_type": "gig", "node": "456", "features": {"category": ["design"], "title_tokens": ["logo"]}}
+    /// ```
+    ///
+    /// Features given as a list go in the "feat" namespace, while an object maps namespaces to
+    /// features.  Nodes missing from `vocab` are skipped, and features occurring fewer than
+    /// `min_count` times are dropped.  Lines are parsed in parallel.
+    pub fn from_jsonl(path: &str, vocab: &Vocab, min_count: Option<usize>) -> IOResult<Self> {
+        FeatureStore::load_text(path, vocab, min_count, parse_jsonl_line)
+    }
+
+    /// Loads features from a TSV file in the same format as `FeatureSet.load_into`:
+    ///
+    /// ```text
+    /// node_type<TAB>name<TAB>f1 f2 f3 ...
+    /// ```
+    ///
+    /// All features go in the "feat" namespace.  Otherwise behaves like `from_jsonl`.
+    pub fn from_tsv(path: &str, vocab: &Vocab, min_count: Option<usize>) -> IOResult<Self> {
+        FeatureStore::load_text(path, vocab, min_count, parse_tsv_line)
+    }
+
+    fn load_text(
+        path: &str,
+        vocab: &Vocab,
+        min_count: Option<usize>,
+        parser: fn(&Vocab, &str) -> Result<Option<RawFeatures>, String>
+    ) -> IOResult<Self> {
+        let reader = open_file_for_reading(path)?;
+        let mut records = Vec::new();
+        RecordReader::new(10_000, 0).read(reader.lines().map(|l| l.unwrap()),
+            |i, line| {
+                if line.trim().is_empty() {
+                    return None
+                }
+                Some(parser(vocab, &line).map_err(|e| {
+                    IOError::new(ErrorKind::InvalidData, format!("Line {}: {}", i + 1, e))
+                }))
+            },
+            |_, record| {
+                if let Some(r) = record? {
+                    records.push(r);
+                }
+                Ok(())
+            })?;
+
+        Ok(FeatureStore::from_records(vocab.len(), records, min_count.unwrap_or(0)))
+    }
+
+    /// Builds the feature vocabulary from parsed records, counting features in parallel when
+    /// pruning.
+    fn from_records(num_nodes: usize, records: Vec<RawFeatures>, min_count: usize) -> Self {
+        let counts = if min_count > 1 {
+            records.par_iter()
+                .fold(|| HashMap::new(), |mut acc, (_, feats)| {
+                    feats.iter().for_each(|(ns, f)| *acc.entry((ns.as_str(), f.as_str())).or_insert(0usize) += 1);
+                    acc
+                })
+                .reduce(|| HashMap::new(), |mut hm1, hm2| {
+                    hm2.into_iter().for_each(|(k, v)| *hm1.entry(k).or_insert(0) += v);
+                    hm1
+                })
+        } else {
+            HashMap::new()
+        };
+
+        let mut fs = FeatureStore::new(num_nodes);
+        for (node_id, feats) in records.iter() {
+            let kept = feats.iter().filter(|(ns, f)| {
+                min_count <= 1 || counts[&(ns.as_str(), f.as_str())] >= min_count
+            });
+            fs.add_features(*node_id, kept.map(|(ns, f)| (ns, f)));
+        }
+        fs
+    }
+
 }
 
+fn invalid_data(msg: &str) -> IOError {
+    IOError::new(ErrorKind::InvalidData, msg)
+}
+
+fn write_u64(w: &mut impl Write, v: u64) -> IOResult<()> {
+    w.write_all(&v.to_le_bytes())
+}
+
+fn write_str(w: &mut impl Write, s: &str) -> IOResult<()> {
+    write_u64(w, s.len() as u64)?;
+    w.write_all(s.as_bytes())
+}
+
+fn read_u64(r: &mut impl Read) -> IOResult<u64> {
+    let mut buff = [0u8; 8];
+    r.read_exact(&mut buff)?;
+    Ok(u64::from_le_bytes(buff))
+}
+
+fn read_str(r: &mut impl Read) -> IOResult<String> {
+    let len = read_u64(r)? as usize;
+    let mut buff = vec![0u8; len];
+    r.read_exact(&mut buff)?;
+    String::from_utf8(buff).map_err(|_| invalid_data("Invalid UTF-8 string!"))
+}
+
+fn parse_jsonl_line(vocab: &Vocab, line: &str) -> Result<Option<RawFeatures>, String> {
+    let record: Value = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let get_str = |key: &str| record.get(key)
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| format!("Missing string field '{}'", key));
+
+    let node_type = get_str("node_type")?;
+    let name = get_str("node")?;
+    let node_id = match vocab.get_node_id(node_type, name) {
+        Some(node_id) => node_id,
+        None => return Ok(None)
+    };
+
+    let to_strings = |ns: &str, v: &Value| -> Result<Vec<(String, String)>, String> {
+        v.as_array()
+            .ok_or_else(|| format!("Features for namespace '{}' must be a list", ns))?
+            .iter()
+            .map(|f| f.as_str()
+                .map(|f| (ns.to_string(), f.to_string()))
+                .ok_or_else(|| "Features must be strings".to_string()))
+            .collect()
+    };
+
+    let features = match record.get("features") {
+        Some(Value::Object(namespaces)) => {
+            let mut feats = Vec::new();
+            for (ns, v) in namespaces.iter() {
+                feats.extend(to_strings(ns, v)?);
+            }
+            feats
+        },
+        Some(v) => to_strings(DEFAULT_NAMESPACE, v)?,
+        None => return Err("Missing field 'features'".to_string())
+    };
+
+    Ok(Some((node_id, features)))
+}
+
+fn parse_tsv_line(vocab: &Vocab, line: &str) -> Result<Option<RawFeatures>, String> {
+    let pieces: Vec<_> = line.split('\t').collect();
+    if pieces.len() != 3 {
+        return Err("Malformed feature line! Need node_type<TAB>name<TAB>f1 f2 ...".to_string())
+    }
+
+    Ok(vocab.get_node_id(pieces[0], pieces[1]).map(|node_id| {
+        let feats = pieces[2].split_whitespace()
+            .map(|f| (DEFAULT_NAMESPACE.to_string(), f.to_string()))
+            .collect();
+        (node_id, feats)
+    }))
+}
+
+#[cfg(test)]
+mod feature_store_tests {
+    use super::*;
+
+    fn build_store() -> FeatureStore {
+        let mut fs = FeatureStore::new(3);
+        fs.set_features(0, [("title_tokens", "logo"), ("category", "design")].into_iter());
+        fs.set_features(1, [("title_tokens", "logo"), ("seller_country", "us")].into_iter());
+        fs.set_features(2, [("category", "writing")].into_iter());
+        fs
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let fs = build_store();
+        let names: Vec<_> = fs.namespaces().iter().map(|ns| ns.as_str()).collect();
+        assert_eq!(names, vec!["title_tokens", "category", "seller_country"]);
+        assert_eq!(fs.namespace_id("category"), Some(1));
+        assert_eq!(fs.namespace_id("missing"), None);
+        assert_eq!(fs.feature_namespaces(), vec![0, 1, 2, 1]);
+
+        let cats: Vec<_> = fs.get_features_in_namespace(0, 1).collect();
+        assert_eq!(cats, vec![1]);
+
+        let groups = fs.vocab_by_namespace();
+        assert_eq!(groups[1].1, vec![(1, "design".to_string()), (3, "writing".to_string())]);
+    }
+
+    fn build_vocab() -> Vocab {
+        let mut vocab = Vocab::new();
+        vocab.get_or_insert("gig", "a");
+        vocab.get_or_insert("gig", "b");
+        vocab.get_or_insert("user", "c");
+        vocab
+    }
+
+    fn write_file(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_from_jsonl() {
+        let vocab = build_vocab();
+        let path = write_file("feature_store_test.jsonl", concat!(
+            "{\"node_type\": \"gig\", \"node\": \"a\", \"features\": [\"logo\", \"design\"]}\n",
+            "{\"node_type\": \"gig\", \"node\": \"b\", \"features\": {\"category\": [\"design\"], \"feat\": [\"logo\"]}}\n",
+            "{\"node_type\": \"gig\", \"node\": \"missing\", \"features\": [\"logo\"]}\n"
+        ));
+
+        let fs = FeatureStore::from_jsonl(&path, &vocab, None).unwrap();
+        assert_eq!(fs.num_nodes(), 3);
+        assert_eq!(fs.get_pretty_features(0), vec![
+            ("feat".to_string(), "logo".to_string()),
+            ("feat".to_string(), "design".to_string())
+        ]);
+        assert_eq!(fs.get_features(1).len(), 2);
+        assert_eq!(fs.get_features(2).len(), 0);
+
+        // Only feat/logo occurs twice
+        let fs = FeatureStore::from_jsonl(&path, &vocab, Some(2)).unwrap();
+        assert_eq!(fs.num_features(), 1);
+        assert_eq!(fs.get_features(0), fs.get_features(1));
+
+        let path = write_file("feature_store_bad.jsonl", "{\"node_type\": \"gig\"}\n");
+        assert!(FeatureStore::from_jsonl(&path, &vocab, None).is_err());
+    }
+
+    #[test]
+    fn test_from_tsv() {
+        let vocab = build_vocab();
+        let path = write_file("feature_store_test.tsv", "gig\ta\tlogo design\nuser\tc\tlogo\n");
+        let fs = FeatureStore::from_tsv(&path, &vocab, Some(2)).unwrap();
+        assert_eq!(fs.get_pretty_features(2), vec![("feat".to_string(), "logo".to_string())]);
+        assert_eq!(fs.get_features(0).len(), 1);
+
+        let path = write_file("feature_store_bad.tsv", "gig\ta\n");
+        assert!(FeatureStore::from_tsv(&path, &vocab, None).is_err());
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut fs = build_store();
+        fs.add_features(0, [("title_tokens", "logo")].into_iter());
+        assert_eq!(fs.count_features(), vec![3, 1, 1, 1]);
+        assert_eq!(fs.document_frequencies(), vec![2, 1, 1, 1]);
+
+        // Drop features in every node and features in more than half the nodes
+        let (pruned, remapping) = fs.prune(1, 0.5);
+        assert_eq!(remapping, vec![None, Some(0), Some(1), Some(2)]);
+        assert_eq!(pruned.num_features(), 3);
+        assert_eq!(pruned.get_features(0), &[0]);
+        assert_eq!(pruned.get_pretty_features(2), vec![("category".to_string(), "writing".to_string())]);
+
+        let (pruned, remapping) = fs.prune(2, 1.);
+        assert_eq!(remapping, vec![Some(0), None, None, None]);
+        assert_eq!(pruned.get_features(0), &[0, 0]);
+        assert!(pruned.get_features(2).is_empty());
+    }
+
+    #[test]
+    fn test_named_features() {
+        let fs = build_store();
+        let mut vocab = Vocab::new();
+        vocab.get_or_insert("gig", "a");
+        vocab.get_or_insert("gig", "b");
+        vocab.get_or_insert("gig", "c");
+        vocab.get_or_insert("gig", "d");
+
+        let expected = vec![("category".to_string(), "writing".to_string())];
+        assert_eq!(fs.get_named_features(&vocab, "gig:c").unwrap(), expected);
+        assert!(fs.get_named_features(&vocab, "gig:d").is_err());
+        assert!(fs.get_named_features(&vocab, "user:a").is_err());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut fs = build_store();
+        fs.set_features(2, [("category", "writing"), ("category", "writing")].into_iter());
+        fs.add_dense_columns(&["price"]);
+        fs.set_dense(1, &[12.5]).unwrap();
+        let mut buffer = Vec::new();
+        fs.write_to(&mut buffer).unwrap();
+
+        let loaded = FeatureStore::read_from(&mut buffer.as_slice()).unwrap();
+        assert_eq!(loaded.num_nodes(), fs.num_nodes());
+        assert_eq!(loaded.num_features(), fs.num_features());
+        assert_eq!(loaded.namespaces(), fs.namespaces());
+        assert_eq!(loaded.feature_namespaces(), fs.feature_namespaces());
+        for node_id in 0..fs.num_nodes() {
+            assert_eq!(loaded.get_features(node_id), fs.get_features(node_id));
+            assert_eq!(loaded.get_pretty_features(node_id), fs.get_pretty_features(node_id));
+            assert_eq!(loaded.get_dense(node_id), fs.get_dense(node_id));
+        }
+        assert_eq!(loaded.dense_names(), fs.dense_names());
+
+        // Truncated and foreign files fail cleanly
+        assert!(FeatureStore::read_from(&mut &buffer[..buffer.len() - 1]).is_err());
+        assert!(FeatureStore::read_from(&mut &[0u8; 64][..]).is_err());
+
+        let path = std::env::temp_dir().join("feature_store_test.bin");
+        let path = path.to_str().unwrap();
+        fs.save(path).unwrap();
+        assert_eq!(FeatureStore::load(path).unwrap().get_features(1), fs.get_features(1));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_dense() {
+        let mut fs = build_store();
+        assert_eq!(fs.dense_dims(), 0);
+        assert!(fs.get_dense(1).is_empty());
+
+        fs.add_dense_columns(&["price"]);
+        fs.set_dense(1, &[12.5]).unwrap();
+        fs.add_dense_columns(&["rating", "age_days"]);
+        assert_eq!(fs.dense_names(), &["price", "rating", "age_days"]);
+        assert_eq!(fs.get_dense(1), &[12.5, 0., 0.]);
+        assert!(fs.set_dense(0, &[1.]).is_err());
+
+        assert_eq!(fs.num_embeddings(), 7);
+        assert_eq!(fs.dense_feature_id(1), 5);
+        let (pruned, _) = fs.prune(1, 1.);
+        assert_eq!(pruned.get_dense(1), fs.get_dense(1));
+    }
+
+    #[test]
+    fn test_mask_namespaces() {
+        let fs = build_store();
+        let masked = fs.mask_namespaces(&["title_tokens", "missing"]);
+        assert_eq!(masked.get_features(0), &[1]);
+        assert_eq!(masked.get_features(1), &[2]);
+        assert_eq!(masked.get_features(2), &[3]);
+        assert_eq!(masked.num_features(), fs.num_features());
+        assert!(masked.get_vocab().is_identical(fs.get_vocab()));
+    }
+
+    #[test]
+    fn test_embeddings_round_trip() {
+        let mut fs = build_store();
+        fs.add_dense_columns(&["price"]);
+        let mut es = EmbeddingStore::new(fs.num_embeddings(), 2, Distance::Cosine);
+        for feat_id in 0..es.len() {
+            es.get_embedding_mut(feat_id).copy_from_slice(&[feat_id as f32, 1f32]);
+        }
+
+        let path = std::env::temp_dir().join("feature_store_test_embeddings.txt");
+        let path = path.to_str().unwrap();
+        fs.write_embeddings(path, &es, None).unwrap();
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(contents.starts_with("title_tokens\tlogo\t[0.0,1.0]\n"));
+        assert!(contents.contains("__dense__\tprice\t[4.0,1.0]\n"));
+
+        // New store with a different vocabulary order, a new feature, and no dense columns
+        let mut new_fs = FeatureStore::new(2);
+        new_fs.set_features(0, [("category", "writing"), ("category", "music")].into_iter());
+        new_fs.set_features(1, [("title_tokens", "logo")].into_iter());
+
+        let (new_es, loaded) = new_fs.read_embeddings(path, 2023).unwrap();
+        assert_eq!(new_es.len(), 3);
+        assert_eq!(new_es.dims(), 2);
+        assert_eq!(loaded, vec![true, false, true]);
+        assert_eq!(new_es.get_embedding(0), &[3f32, 1f32]);
+        assert_eq!(new_es.get_embedding(2), &[0f32, 1f32]);
+
+        let small = EmbeddingStore::new(2, 2, Distance::Cosine);
+        assert!(fs.write_embeddings(path, &small, None).is_err());
+    }
+
+    #[test]
+    fn test_namespace_mask() {
+        let mut fs = build_store();
+        fs.add_dense_columns(&["price"]);
+        assert_eq!(fs.namespace_mask(&["title_tokens", "missing"]), 
+                   vec![true, false, false, false, false]);
+        assert_eq!(fs.namespace_mask(&["category"]), vec![false, true, false, true, false]);
+        assert_eq!(fs.namespace_mask(&[]), vec![false; 5]);
+    }
+
+    #[test]
+    fn test_merge_nodes() {
+        let mut fs = build_store();
+        fs.add_dense_columns(&["price"]);
+        fs.set_dense(0, &[1.]).unwrap();
+        fs.set_dense(1, &[3.]).unwrap();
+        fs.set_dense(2, &[5.]).unwrap();
+
+        let merged = fs.merge_nodes(&[0, 0, 1], 2);
+        assert_eq!(merged.num_nodes(), 2);
+        assert_eq!(merged.num_features(), fs.num_features());
+        assert_eq!(merged.get_features(0), &[0, 1, 2]);
+        assert_eq!(merged.get_features(1), &[3]);
+        assert_eq!(merged.get_dense(0), &[2.]);
+        assert_eq!(merged.get_dense(1), &[5.]);
+    }
+
+    #[test]
+    fn test_subset() {
+        let mut fs = build_store();
+        fs.add_dense_columns(&["price"]);
+        fs.set_dense(2, &[5.]).unwrap();
+
+        let subset = fs.subset(&[2, 0]);
+        assert_eq!(subset.num_nodes(), 2);
+        assert_eq!(subset.num_embeddings(), fs.num_embeddings());
+        assert_eq!(subset.get_features(0), fs.get_features(2));
+        assert_eq!(subset.get_features(1), fs.get_features(0));
+        assert_eq!(subset.get_dense(0), &[5.]);
+        assert_eq!(subset.get_dense(1), &[0.]);
+    }
+
+    #[test]
+    fn test_stack_towers() {
+        let fs = build_store();
+        let stacked = fs.stack_towers("dst:").unwrap();
+        assert_eq!(stacked.num_nodes(), 6);
+        assert_eq!(stacked.num_embeddings(), 8);
+        assert_eq!(stacked.get_features(0), fs.get_features(0));
+        assert_eq!(stacked.get_features(4), &[4, 6]);
+        assert_eq!(stacked.get_pretty_features(5),
+                   vec![("dst:category".to_string(), "writing".to_string())]);
+
+        assert!(stacked.stack_towers("dst:").is_err());
+        let mut dense = build_store();
+        dense.add_dense_columns(&["price"]);
+        assert!(dense.stack_towers("dst:").is_err());
+    }
+}
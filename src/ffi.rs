@@ -0,0 +1,307 @@
+//! C ABI for the read-only serving path: load an EmbeddingStore and an Ann index from disk, run
+//! predictions, and free them.  This lets services written in other languages, such as Go or C++,
+//! query an index in process rather than through a sidecar.  The declarations are mirrored in
+//! `include/graph_library.h`.
+//!
+//! Handles are opaque pointers owned by the caller, who must free each exactly once with the
+//! matching `gl_*_free` function.  Strings must be NUL terminated UTF-8 and buffers must be valid
+//! for the lengths passed alongside them.  Loaded handles are immutable, so they can be shared
+//! between threads.
+//!
+//! Failing calls return NULL or a negative value and record a message which can be retrieved
+//! with `gl_last_error`.  Panics are caught at the boundary and reported the same way.
+#![allow(clippy::missing_safety_doc)]
+use std::cell::RefCell;
+use std::ffi::{CStr,CString};
+use std::os::raw::{c_char,c_int};
+use std::panic::{catch_unwind,AssertUnwindSafe};
+use std::ptr;
+
+use crate::algos::ann::Ann;
+use crate::distance::Distance;
+use crate::embeddings::EmbeddingStore;
+use crate::error::{GraphLibError,check_dims};
+use crate::io::EmbeddingReader;
+use crate::vocab::Vocab;
+
+/// Distance codes accepted by `gl_embeddings_load`
+pub const GL_DISTANCE_COSINE: c_int = 0;
+pub const GL_DISTANCE_DOT: c_int = 1;
+pub const GL_DISTANCE_EUCLIDEAN: c_int = 2;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+
+/// Embeddings along with the vocab needed to look up nodes by name
+pub struct GlEmbeddings {
+    vocab: Vocab,
+    es: EmbeddingStore
+}
+
+fn set_last_error(msg: String) {
+    // Interior NULs would truncate the message, so replace them
+    let msg = CString::new(msg.replace('\0', " ")).expect("NULs were removed");
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// Runs the closure, converting errors and panics into the failure value and recording the
+/// error message for the calling thread.
+fn guard<T>(failure: T, f: impl FnOnce() -> Result<T, GraphLibError>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(v)) => v,
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            failure
+        },
+        Err(_) => {
+            set_last_error("Panicked while handling the call!".to_string());
+            failure
+        }
+    }
+}
+
+unsafe fn to_str<'a>(s: *const c_char, name: &str) -> Result<&'a str, GraphLibError> {
+    if s.is_null() {
+        return Err(GraphLibError::InvalidInput(format!("{} is NULL!", name)))
+    }
+    CStr::from_ptr(s).to_str()
+        .map_err(|_| GraphLibError::InvalidInput(format!("{} isn't valid UTF-8!", name)))
+}
+
+unsafe fn to_ref<'a, T>(p: *const T, name: &str) -> Result<&'a T, GraphLibError> {
+    p.as_ref().ok_or_else(|| GraphLibError::InvalidInput(format!("{} is NULL!", name)))
+}
+
+/// Returns the last error raised on the calling thread, or NULL if there was none.  The string
+/// is owned by the library and remains valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn gl_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()))
+}
+
+/// Loads embeddings written by `NodeEmbeddings.save`, optionally gzipped.  Returns NULL on
+/// failure.
+#[no_mangle]
+pub unsafe extern "C" fn gl_embeddings_load(
+    path: *const c_char,
+    distance: c_int
+) -> *mut GlEmbeddings {
+    guard(ptr::null_mut(), || {
+        let path = to_str(path, "path")?;
+        let distance = match distance {
+            GL_DISTANCE_COSINE => Distance::Cosine,
+            GL_DISTANCE_DOT => Distance::Dot,
+            GL_DISTANCE_EUCLIDEAN => Distance::Euclidean,
+            _ => return Err(GraphLibError::InvalidInput(format!("Unknown distance {}!", distance)))
+        };
+        let (vocab, es) = EmbeddingReader::load(path, distance, &None, None, None)?;
+        Ok(Box::into_raw(Box::new(GlEmbeddings { vocab, es })))
+    })
+}
+
+/// Frees embeddings returned by `gl_embeddings_load`.  NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn gl_embeddings_free(embeddings: *mut GlEmbeddings) {
+    if !embeddings.is_null() {
+        drop(Box::from_raw(embeddings));
+    }
+}
+
+/// Number of nodes in the embeddings, or 0 if the handle is NULL
+#[no_mangle]
+pub unsafe extern "C" fn gl_embeddings_len(embeddings: *const GlEmbeddings) -> usize {
+    embeddings.as_ref().map(|e| e.es.len()).unwrap_or(0)
+}
+
+/// Dimensions of each embedding, or 0 if the handle is NULL
+#[no_mangle]
+pub unsafe extern "C" fn gl_embeddings_dims(embeddings: *const GlEmbeddings) -> usize {
+    embeddings.as_ref().map(|e| e.es.dims()).unwrap_or(0)
+}
+
+/// Looks up the node id for the given node type and name.  Returns -1 if the node doesn't exist
+/// or on error.
+#[no_mangle]
+pub unsafe extern "C" fn gl_embeddings_node_id(
+    embeddings: *const GlEmbeddings,
+    node_type: *const c_char,
+    name: *const c_char
+) -> i64 {
+    guard(-1, || {
+        let embeddings = to_ref(embeddings, "embeddings")?;
+        let node_type = to_str(node_type, "node_type")?;
+        let name = to_str(name, "name")?;
+        Ok(embeddings.vocab.get_node_id(node_type, name).map(|id| id as i64).unwrap_or(-1))
+    })
+}
+
+/// Copies the embedding for `node_id` into `out`, which must hold `out_len` floats.  Returns 0
+/// on success and -1 on error.
+#[no_mangle]
+pub unsafe extern "C" fn gl_embeddings_get(
+    embeddings: *const GlEmbeddings,
+    node_id: u64,
+    out: *mut f32,
+    out_len: usize
+) -> c_int {
+    guard(-1, || {
+        let embeddings = to_ref(embeddings, "embeddings")?;
+        if out.is_null() {
+            return Err("out is NULL!".into())
+        }
+        if node_id as usize >= embeddings.es.len() {
+            return Err(GraphLibError::InvalidInput(format!("Node id {} out of range!", node_id)))
+        }
+        check_dims(embeddings.es.dims(), out_len)?;
+        let out = std::slice::from_raw_parts_mut(out, out_len);
+        out.copy_from_slice(embeddings.es.get_embedding(node_id as usize));
+        Ok(0)
+    })
+}
+
+/// Loads an index written by `Ann::save`.  Returns NULL on failure.
+#[no_mangle]
+pub unsafe extern "C" fn gl_ann_load(path: *const c_char) -> *mut Ann {
+    guard(ptr::null_mut(), || {
+        let ann = Ann::load(to_str(path, "path")?)?;
+        Ok(Box::into_raw(Box::new(ann)))
+    })
+}
+
+/// Frees an index returned by `gl_ann_load`.  NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn gl_ann_free(ann: *mut Ann) {
+    if !ann.is_null() {
+        drop(Box::from_raw(ann));
+    }
+}
+
+/// Finds the approximate `k` nearest neighbors of `query`, which must have the embeddings'
+/// dimensions.  `out_ids` and `out_distances` must each hold `k` values and are filled closest
+/// first.  A `min_search_nodes` of 0 uses the default.  Returns the number of neighbors written,
+/// which can be less than `k`, or -1 on error.
+#[no_mangle]
+pub unsafe extern "C" fn gl_ann_predict(
+    ann: *const Ann,
+    embeddings: *const GlEmbeddings,
+    query: *const f32,
+    query_len: usize,
+    k: usize,
+    min_search_nodes: usize,
+    out_ids: *mut u64,
+    out_distances: *mut f32
+) -> i64 {
+    guard(-1, || {
+        let ann = to_ref(ann, "ann")?;
+        let embeddings = to_ref(embeddings, "embeddings")?;
+        if query.is_null() || out_ids.is_null() || out_distances.is_null() {
+            return Err("query and output buffers must not be NULL!".into())
+        }
+
+        let query = std::slice::from_raw_parts(query, query_len);
+        let min_search_nodes = if min_search_nodes > 0 { Some(min_search_nodes) } else { None };
+        let results = ann.predict(&embeddings.es, query, k, min_search_nodes)?;
+
+        let out_ids = std::slice::from_raw_parts_mut(out_ids, k);
+        let out_distances = std::slice::from_raw_parts_mut(out_distances, k);
+        for (i, nd) in results.iter().take(k).enumerate() {
+            out_ids[i] = nd.1 as u64;
+            out_distances[i] = nd.0;
+        }
+        Ok(results.len().min(k) as i64)
+    })
+}
+
+#[cfg(test)]
+mod ffi_tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_xorshift::XorShiftRng;
+    use crate::io::EmbeddingWriter;
+
+    // Unique per process, so concurrent test runs don't clobber each other's files
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ffi_test_{}_{}", std::process::id(), name))
+    }
+
+    fn write_files() -> (CString, CString) {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut vocab = Vocab::new();
+        let mut es = EmbeddingStore::new(100, 4, Distance::Cosine);
+        for node_id in 0..es.len() {
+            vocab.get_or_insert("gig", node_id.to_string());
+            let emb: Vec<f32> = (0..es.dims()).map(|_| rng.gen::<f32>() - 0.5).collect();
+            es.set_embedding(node_id, &emb);
+        }
+
+        let emb_path = temp_path("embeddings.txt");
+        let emb_path = emb_path.to_str().unwrap();
+        let mut writer = EmbeddingWriter::new(emb_path, &vocab, None).unwrap();
+        writer.stream((0..es.len()).map(|node_id| (node_id, es.get_embedding(node_id)))).unwrap();
+        drop(writer);
+
+        let mut ann = Ann::new();
+        ann.fit(&es, 3, 10, None, None, None, 2023).unwrap();
+        let ann_path = temp_path("ann.bin");
+        let ann_path = ann_path.to_str().unwrap();
+        ann.save(ann_path).unwrap();
+
+        (CString::new(emb_path).unwrap(), CString::new(ann_path).unwrap())
+    }
+
+    #[test]
+    fn test_predict() {
+        let (emb_path, ann_path) = write_files();
+        unsafe {
+            let embeddings = gl_embeddings_load(emb_path.as_ptr(), GL_DISTANCE_COSINE);
+            assert!(!embeddings.is_null());
+            assert_eq!(gl_embeddings_len(embeddings), 100);
+            assert_eq!(gl_embeddings_dims(embeddings), 4);
+
+            let node_type = CString::new("gig").unwrap();
+            let name = CString::new("42").unwrap();
+            let node_id = gl_embeddings_node_id(embeddings, node_type.as_ptr(), name.as_ptr());
+            assert!(node_id >= 0);
+
+            let mut query = [0f32; 4];
+            assert_eq!(gl_embeddings_get(embeddings, node_id as u64, query.as_mut_ptr(), 4), 0);
+
+            let ann = gl_ann_load(ann_path.as_ptr());
+            assert!(!ann.is_null());
+            let mut ids = [0u64; 5];
+            let mut dists = [0f32; 5];
+            let n = gl_ann_predict(ann, embeddings, query.as_ptr(), 4, 5, 0,
+                                   ids.as_mut_ptr(), dists.as_mut_ptr());
+            assert_eq!(n, 5);
+            assert_eq!(ids[0], node_id as u64);
+            assert!(dists[0].abs() < 1e-5);
+
+            // Wrong query size is reported rather than panicking
+            let n = gl_ann_predict(ann, embeddings, query.as_ptr(), 3, 5, 0,
+                                   ids.as_mut_ptr(), dists.as_mut_ptr());
+            assert_eq!(n, -1);
+            let msg = CStr::from_ptr(gl_last_error()).to_str().unwrap();
+            assert!(msg.contains("Dimension mismatch"));
+
+            gl_ann_free(ann);
+            gl_embeddings_free(embeddings);
+        }
+
+        std::fs::remove_file(emb_path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(ann_path.to_str().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let missing = CString::new("/does/not/exist").unwrap();
+        unsafe {
+            assert!(gl_embeddings_load(missing.as_ptr(), GL_DISTANCE_COSINE).is_null());
+            assert!(CStr::from_ptr(gl_last_error()).to_str().unwrap().contains("IO error"));
+            assert!(gl_embeddings_load(missing.as_ptr(), 42).is_null());
+            assert!(gl_ann_load(ptr::null()).is_null());
+            assert_eq!(gl_embeddings_node_id(ptr::null(), ptr::null(), ptr::null()), -1);
+            gl_ann_free(ptr::null_mut());
+        }
+    }
+}
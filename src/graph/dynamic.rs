@@ -0,0 +1,413 @@
+//! Mutable graphs for near real time updates.  A `DynamicGraph` wraps a static CumCSR and layers
+//! edge insertions and deletions on top of it, so new interactions are visible to walks and
+//! samplers immediately rather than after the next full rebuild.  Only rows which have changed
+//! are copied into the overlay; everything else is read straight from the CSR.  Once the overlay
+//! grows large, `compact` folds it back into a fresh CSR.
+//!
+//! Base edges are read as their transition probabilities, so each node's existing edges weigh 1
+//! in total and inserted weights are relative to that.  An inserted edge with weight 1 gets as
+//! much mass as all of a node's existing edges combined.
+use hashbrown::HashMap;
+
+use super::{Graph,CDFGraph,CDFtoP,CumCSR,GraphBuilder,NodeID,convert_edges_to_cdf};
+use crate::error::GraphLibError;
+
+/// A single mutation, as recorded in the log
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum EdgeUpdate {
+    /// Inserted (from, to, weight).  Weights add onto an existing edge.
+    Insert(NodeID, NodeID, f32),
+
+    /// Deleted every (from, to) edge
+    Delete(NodeID, NodeID)
+}
+
+// Merged edges of a node that has changed since the last compaction
+#[derive(Clone,Debug,Default)]
+struct OverlayRow {
+    edges: Vec<NodeID>,
+
+    /// Raw weights, in the units described above
+    weights: Vec<f32>,
+
+    /// CDF of the weights, as served through the Graph trait
+    cdf: Vec<f32>
+}
+
+impl OverlayRow {
+    fn update_cdf(&mut self) {
+        self.cdf.clear();
+        self.cdf.extend_from_slice(&self.weights);
+        if !self.cdf.is_empty() {
+            convert_edges_to_cdf(&mut self.cdf);
+        }
+    }
+}
+
+// Fenwick tree over each node's change in degree since the last compaction.  Edge ranges are
+// the base ranges shifted by the changes of every earlier node, which this sums in O(log |V|).
+#[derive(Clone,Debug,Default)]
+struct DegreeDeltas {
+    tree: Vec<i64>
+}
+
+impl DegreeDeltas {
+    fn new(len: usize) -> Self {
+        DegreeDeltas { tree: vec![0; len] }
+    }
+
+    // Adds to the change in degree of the node, growing the tree if needed
+    fn add(&mut self, node_id: NodeID, delta: i64) {
+        if node_id >= self.tree.len() {
+            self.grow((node_id + 1).max(2 * self.tree.len()));
+        }
+        let mut i = node_id + 1;
+        while i <= self.tree.len() {
+            self.tree[i - 1] += delta;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // Total change in degree of the nodes before node_id
+    fn prefix(&self, node_id: NodeID) -> i64 {
+        let mut i = node_id.min(self.tree.len());
+        let mut total = 0;
+        while i > 0 {
+            total += self.tree[i - 1];
+            i &= i - 1;
+        }
+        total
+    }
+
+    // Rebuilds the tree over more nodes, in O(|V| log |V|)
+    fn grow(&mut self, len: usize) {
+        let mut tree: Vec<_> = (0..self.tree.len())
+            .map(|node_id| self.prefix(node_id + 1) - self.prefix(node_id))
+            .collect();
+        tree.resize(len, 0);
+        for i in 1..=len {
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= len {
+                tree[parent - 1] += tree[i - 1];
+            }
+        }
+        self.tree = tree;
+    }
+}
+
+/// CumCSR with an overlay of inserted and deleted edges.  Implements CDFGraph, so it can be used
+/// anywhere the underlying CSR can.  Edge ranges are renumbered densely over the merged edges and
+/// computed on demand, so a mutation costs O(log |V|) plus the size of the node's row.  Mutations
+/// still move the ranges of every later node, so per edge arrays, such as alias tables, must be
+/// rebuilt after mutating.
+pub struct DynamicGraph {
+    base: CumCSR,
+
+    /// Merged rows for nodes whose edges changed since the last compaction
+    overlay: HashMap<NodeID, OverlayRow>,
+
+    /// Mutations since the last compaction, in the order they were applied
+    log: Vec<EdgeUpdate>,
+
+    /// Change in each node's degree since the last compaction
+    deltas: DegreeDeltas,
+
+    /// Number of nodes in the merged graph
+    num_nodes: usize,
+
+    /// Number of edges in the merged graph
+    num_edges: usize
+}
+
+impl DynamicGraph {
+    pub fn new(base: CumCSR) -> Self {
+        let (num_nodes, num_edges) = (base.len(), base.edges());
+        DynamicGraph {
+            base,
+            overlay: HashMap::new(),
+            log: Vec::new(),
+            deltas: DegreeDeltas::new(num_nodes),
+            num_nodes,
+            num_edges
+        }
+    }
+
+    /// Graph as of the last compaction
+    pub fn base(&self) -> &CumCSR {
+        &self.base
+    }
+
+    /// Mutations applied since the last compaction
+    pub fn log(&self) -> &[EdgeUpdate] {
+        &self.log
+    }
+
+    /// Number of nodes whose edges are served from the overlay
+    pub fn overlay_len(&self) -> usize {
+        self.overlay.len()
+    }
+
+    /// Inserts an edge from u to v, adding nodes as needed.  If the edge already exists, the
+    /// weight is added onto it.  Fails, leaving the graph unchanged, if the weight isn't finite
+    /// and positive, as it would corrupt the node's CDF.
+    pub fn insert_edge(&mut self, u: NodeID, v: NodeID, weight: f32) -> Result<(), GraphLibError> {
+        if !weight.is_finite() || weight <= 0f32 {
+            return Err(GraphLibError::InvalidInput(
+                format!("Inserted edge weights must be finite and positive, got {}!", weight)))
+        }
+        self.ensure_nodes(u.max(v) + 1);
+        self.log.push(EdgeUpdate::Insert(u, v, weight));
+
+        let row = self.row_mut(u);
+        let added = match row.edges.iter().position(|t_n| *t_n == v) {
+            Some(idx) => {
+                row.weights[idx] += weight;
+                0
+            },
+            None => {
+                row.edges.push(v);
+                row.weights.push(weight);
+                1
+            }
+        };
+        row.update_cdf();
+        self.deltas.add(u, added);
+        self.num_edges += added as usize;
+        Ok(())
+    }
+
+    /// Deletes every edge from u to v.  Returns whether any edge was removed.
+    pub fn delete_edge(&mut self, u: NodeID, v: NodeID) -> bool {
+        self.log.push(EdgeUpdate::Delete(u, v));
+        if u >= self.len() || !self.get_edges(u).0.contains(&v) {
+            return false
+        }
+
+        let row = self.row_mut(u);
+        let old_degree = row.edges.len();
+        let mut idx = 0;
+        while idx < row.edges.len() {
+            if row.edges[idx] == v {
+                row.edges.swap_remove(idx);
+                row.weights.swap_remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+        let removed = old_degree - row.edges.len();
+        row.update_cdf();
+        self.deltas.add(u, -(removed as i64));
+        self.num_edges -= removed;
+        true
+    }
+
+    /// Applies a batch of mutations in order.  Stops at the first invalid insertion, keeping the
+    /// mutations before it.
+    pub fn apply(&mut self, updates: impl IntoIterator<Item=EdgeUpdate>) -> Result<(), GraphLibError> {
+        for update in updates {
+            match update {
+                EdgeUpdate::Insert(u, v, w) => self.insert_edge(u, v, w)?,
+                EdgeUpdate::Delete(u, v) => { self.delete_edge(u, v); }
+            }
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the CSR from the merged edges, emptying the overlay and the log.  Weights are
+    /// renormalized, so every node's edges weigh 1 in total again.
+    pub fn compact(&mut self) {
+        let mut builder = GraphBuilder::new(false);
+        builder.ensure_nodes(self.len());
+        for node_id in 0..self.len() {
+            match self.overlay.get(&node_id) {
+                Some(row) => {
+                    row.edges.iter().zip(row.weights.iter())
+                        .for_each(|(t_n, w)| builder.add_edge(node_id, *t_n, *w));
+                },
+                None => {
+                    let (edges, weights) = self.base.get_edges(node_id);
+                    edges.iter().zip(CDFtoP::new(weights))
+                        .for_each(|(t_n, p)| builder.add_edge(node_id, *t_n, p));
+                }
+            }
+        }
+
+        self.base = builder.build_cum_csr();
+        self.overlay.clear();
+        self.log.clear();
+        self.num_nodes = self.base.len();
+        self.num_edges = self.base.edges();
+        self.deltas = DegreeDeltas::new(self.num_nodes);
+    }
+
+    fn ensure_nodes(&mut self, num_nodes: usize) {
+        self.num_nodes = self.num_nodes.max(num_nodes);
+    }
+
+    // Copies the node's edges into the overlay, if they aren't there already
+    fn row_mut(&mut self, node_id: NodeID) -> &mut OverlayRow {
+        let base = &self.base;
+        self.overlay.entry(node_id).or_insert_with(|| {
+            let mut row = OverlayRow::default();
+            if node_id < base.len() {
+                let (edges, weights) = base.get_edges(node_id);
+                row.edges.extend_from_slice(edges);
+                row.weights.extend(CDFtoP::new(weights));
+            }
+            row
+        })
+    }
+}
+
+impl Graph for DynamicGraph {
+    // Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.num_nodes
+    }
+
+    // Get number of edges in graph
+    fn edges(&self) -> usize {
+        self.num_edges
+    }
+
+    // Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize {
+        match self.overlay.get(&idx) {
+            Some(row) => row.edges.len(),
+            None if idx < self.base.len() => self.base.degree(idx),
+            None => 0
+        }
+    }
+
+    // Get edges and corresponding weights, as CDFs
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        match self.overlay.get(&idx) {
+            Some(row) => (row.edges.as_slice(), row.cdf.as_slice()),
+            None if idx < self.base.len() => self.base.get_edges(idx),
+            None => (&[], &[])
+        }
+    }
+
+    // Edge range within the merged graph's dense edge numbering
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        let base_start = if idx < self.base.len() {
+            self.base.get_edge_range(idx).0
+        } else {
+            self.base.edges()
+        };
+        let start = (base_start as i64 + self.deltas.prefix(idx)) as usize;
+        (start, start + self.degree(idx))
+    }
+}
+
+impl CDFGraph for DynamicGraph {}
+
+#[cfg(test)]
+mod dynamic_tests {
+    use super::*;
+    use crate::graph::CSR;
+
+    fn build_graph() -> DynamicGraph {
+        let edges = vec![(0, 1, 1.), (0, 2, 3.), (1, 2, 1.), (2, 0, 1.)];
+        DynamicGraph::new(CumCSR::convert(CSR::construct_from_edges(edges, false)))
+    }
+
+    fn check_offsets(graph: &DynamicGraph) {
+        let mut offset = 0;
+        for node_id in 0..graph.len() {
+            assert_eq!(graph.get_edge_range(node_id), (offset, offset + graph.degree(node_id)));
+            assert_eq!(graph.get_edges(node_id).0.len(), graph.degree(node_id));
+            offset += graph.degree(node_id);
+        }
+        assert_eq!(offset, graph.edges());
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut graph = build_graph();
+        graph.insert_edge(1, 0, 1.).unwrap();
+        graph.insert_edge(4, 0, 2.).unwrap();
+        assert_eq!(graph.len(), 5);
+        assert_eq!(graph.edges(), 6);
+        assert_eq!(graph.overlay_len(), 2);
+        check_offsets(&graph);
+
+        // Node 1's existing edge weighs 1, same as the new one
+        assert_eq!(graph.get_edges(1), (&[2, 0][..], &[0.5, 1.][..]));
+        assert_eq!(graph.get_edges(3), (&[][..], &[][..]));
+        assert_eq!(graph.get_edges(4), (&[0][..], &[1.][..]));
+
+        // Inserting an existing edge adds to its weight
+        graph.insert_edge(1, 0, 2.).unwrap();
+        assert_eq!(graph.degree(1), 2);
+        assert_eq!(graph.get_edges(1).1, &[0.25, 1.]);
+    }
+
+    #[test]
+    fn test_delete() {
+        let mut graph = build_graph();
+        assert!(graph.delete_edge(0, 2));
+        assert!(!graph.delete_edge(0, 2));
+        assert!(!graph.delete_edge(10, 2));
+        assert_eq!(graph.edges(), 3);
+        assert_eq!(graph.get_edges(0), (&[1][..], &[1.][..]));
+        check_offsets(&graph);
+        assert_eq!(graph.log().len(), 3);
+    }
+
+    #[test]
+    fn test_invalid_weights() {
+        let mut graph = build_graph();
+        for weight in [0., -1., f32::NAN, f32::INFINITY] {
+            assert!(graph.insert_edge(1, 0, weight).is_err());
+        }
+        assert!(graph.insert_edge(7, 0, -1.).is_err());
+        assert_eq!((graph.len(), graph.edges(), graph.overlay_len()), (3, 4, 0));
+        assert!(graph.log().is_empty());
+
+        // Batches keep the mutations before the invalid one
+        let updates = vec![EdgeUpdate::Insert(1, 0, 1.), EdgeUpdate::Insert(2, 1, 0.)];
+        assert!(graph.apply(updates).is_err());
+        assert_eq!(graph.log(), &[EdgeUpdate::Insert(1, 0, 1.)]);
+        check_offsets(&graph);
+    }
+
+    #[test]
+    fn test_many_mutations() {
+        let mut graph = build_graph();
+        for node_id in 0..50 {
+            graph.insert_edge(node_id, (node_id * 7) % 50, 1.).unwrap();
+            graph.insert_edge((node_id * 3) % 50, node_id, 0.5).unwrap();
+            graph.delete_edge(node_id / 2, node_id);
+        }
+        check_offsets(&graph);
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut graph = build_graph();
+        graph.apply(vec![
+            EdgeUpdate::Insert(3, 1, 1.),
+            EdgeUpdate::Delete(0, 1),
+            EdgeUpdate::Insert(0, 3, 0.75)
+        ]).unwrap();
+
+        let merged: Vec<_> = (0..graph.len())
+            .map(|node_id| {
+                let (edges, weights) = graph.get_edges(node_id);
+                (edges.to_vec(), weights.to_vec())
+            })
+            .collect();
+
+        graph.compact();
+        assert_eq!(graph.overlay_len(), 0);
+        assert!(graph.log().is_empty());
+        assert_eq!(graph.base().len(), 4);
+        check_offsets(&graph);
+        for (node_id, (edges, weights)) in merged.iter().enumerate() {
+            let (c_edges, c_weights) = graph.get_edges(node_id);
+            assert_eq!(c_edges, edges.as_slice());
+            c_weights.iter().zip(weights.iter()).for_each(|(c, w)| assert!((c - w).abs() < 1e-6));
+        }
+    }
+}
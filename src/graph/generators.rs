@@ -0,0 +1,229 @@
+//! Random graph generators for tests and benchmarks.  All generators are deterministic for a given
+//! seed and produce unit weight edges.  Undirected graphs emit each edge in both directions.
+//!
+//! Sparse generators use geometric skipping: rather than flipping a coin for every candidate edge,
+//! we sample the gap to the next accepted candidate, so generation is linear in the number of
+//! edges rather than quadratic in the number of nodes.
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+
+use super::{CSR,NodeID};
+
+/// Calls `f` with the index of each accepted candidate in [0, count), where each candidate is
+/// accepted independently with probability `p`.
+fn geometric_skips(count: usize, p: f32, rng: &mut impl Rng, mut f: impl FnMut(usize)) {
+    if p <= 0. || count == 0 {
+        return
+    } else if p >= 1. {
+        (0..count).for_each(f);
+        return
+    }
+
+    let log_q = (1. - p as f64).ln();
+    let mut idx = 0usize;
+    loop {
+        let r: f64 = rng.gen();
+        let skip = ((1. - r).ln() / log_q).floor();
+        if skip >= (count - idx) as f64 {
+            break
+        }
+        idx += skip as usize;
+        f(idx);
+        idx += 1;
+        if idx >= count { break }
+    }
+}
+
+fn build(num_nodes: usize, mut edges: Vec<(NodeID, NodeID, f32)>) -> CSR {
+    edges.par_sort_by_key(|(f_n, t_n, _)| (*f_n, *t_n));
+    CSR::from_sorted_edges(num_nodes, edges)
+}
+
+fn symmetrize(edges: Vec<(NodeID, NodeID)>) -> Vec<(NodeID, NodeID, f32)> {
+    edges.into_iter()
+        .flat_map(|(u, v)| [(u, v, 1f32), (v, u, 1f32)])
+        .collect()
+}
+
+/// Erdős–Rényi G(n, p) graph: every possible edge exists independently with probability `p`.  No
+/// self loops.
+pub fn erdos_renyi(num_nodes: usize, p: f32, directed: bool, seed: u64) -> CSR {
+    let edges: Vec<_> = (0..num_nodes).into_par_iter().flat_map_iter(|u| {
+        let mut rng = XorShiftRng::seed_from_u64(seed + u as u64);
+        let mut row = Vec::new();
+        if directed {
+            // Candidates are every other node
+            geometric_skips(num_nodes.saturating_sub(1), p, &mut rng, |i| {
+                let v = if i < u { i } else { i + 1 };
+                row.push((u, v, 1f32));
+            });
+        } else {
+            // Only generate each pair once from the lower node
+            geometric_skips(num_nodes - u - 1, p, &mut rng, |i| {
+                let v = u + 1 + i;
+                row.push((u, v, 1f32));
+                row.push((v, u, 1f32));
+            });
+        }
+        row.into_iter()
+    }).collect();
+
+    build(num_nodes, edges)
+}
+
+/// Barabási–Albert preferential attachment graph.  Starts with a clique of `m + 1` nodes, then each
+/// new node connects to `m` distinct existing nodes chosen proportionally to their degree.
+/// Undirected.
+pub fn barabasi_albert(num_nodes: usize, m: usize, seed: u64) -> Result<CSR, &'static str> {
+    if m == 0 || m >= num_nodes {
+        return Err("m must be between 1 and the number of nodes!")
+    }
+
+    let mut rng = XorShiftRng::seed_from_u64(seed);
+    let mut edges = Vec::new();
+
+    // Each node shows up once per degree, so uniform sampling is proportional to degree
+    let mut targets = Vec::new();
+    for u in 0..(m + 1) {
+        for v in (u + 1)..(m + 1) {
+            edges.push((u, v));
+            targets.push(u);
+            targets.push(v);
+        }
+    }
+
+    let mut chosen = Vec::with_capacity(m);
+    for new_node in (m + 1)..num_nodes {
+        chosen.clear();
+        while chosen.len() < m {
+            let t = targets[rng.gen_range(0, targets.len())];
+            if !chosen.contains(&t) {
+                chosen.push(t);
+            }
+        }
+        for t in chosen.iter() {
+            edges.push((new_node, *t));
+            targets.push(new_node);
+            targets.push(*t);
+        }
+    }
+
+    Ok(build(num_nodes, symmetrize(edges)))
+}
+
+/// Stochastic block model.  Nodes are assigned to contiguous blocks of the provided sizes, and
+/// nodes in blocks `a` and `b` are connected with probability `probs[a][b]`, which must be
+/// symmetric.  Undirected.  Returns the graph along with the block of each node.
+pub fn stochastic_block_model(
+    sizes: &[usize],
+    probs: &[Vec<f32>],
+    seed: u64
+) -> Result<(CSR, Vec<usize>), &'static str> {
+    if probs.len() != sizes.len() || probs.iter().any(|row| row.len() != sizes.len()) {
+        return Err("Probabilities must be a square matrix matching the number of blocks!")
+    }
+    for a in 0..sizes.len() {
+        for b in 0..sizes.len() {
+            if probs[a][b] != probs[b][a] {
+                return Err("Probabilities must be symmetric!")
+            }
+        }
+    }
+
+    let membership: Vec<_> = sizes.iter().enumerate()
+        .flat_map(|(block, size)| std::iter::repeat(block).take(*size))
+        .collect();
+
+    // Start offset of each block
+    let mut offsets = vec![0; sizes.len() + 1];
+    sizes.iter().enumerate().for_each(|(b, size)| offsets[b + 1] = offsets[b] + size);
+
+    let num_nodes = membership.len();
+    let edges: Vec<_> = (0..num_nodes).into_par_iter().flat_map_iter(|u| {
+        let mut rng = XorShiftRng::seed_from_u64(seed + u as u64);
+        let a = membership[u];
+        let mut row = Vec::new();
+        for b in a..sizes.len() {
+            // Only generate each pair once from the lower node
+            let start = offsets[b].max(u + 1);
+            let stop = offsets[b + 1];
+            geometric_skips(stop.saturating_sub(start), probs[a][b], &mut rng, |i| {
+                row.push((u, start + i, 1f32));
+                row.push((start + i, u, 1f32));
+            });
+        }
+        row.into_iter()
+    }).collect();
+
+    Ok((build(num_nodes, edges), membership))
+}
+
+#[cfg(test)]
+mod generators_tests {
+    use super::*;
+    use crate::graph::Graph;
+
+    #[test]
+    fn test_geometric_skips() {
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        let mut accepted = Vec::new();
+        geometric_skips(100_000, 0.1, &mut rng, |i| accepted.push(i));
+        assert!(accepted.windows(2).all(|w| w[0] < w[1]));
+        assert!(*accepted.last().unwrap() < 100_000);
+        assert!(accepted.len() > 9_000 && accepted.len() < 11_000);
+
+        let mut all = Vec::new();
+        geometric_skips(5, 1., &mut rng, |i| all.push(i));
+        assert_eq!(all, vec![0, 1, 2, 3, 4]);
+        geometric_skips(5, 0., &mut rng, |_| panic!("Should never accept"));
+    }
+
+    #[test]
+    fn test_erdos_renyi() {
+        let graph = erdos_renyi(200, 0.05, false, 2023);
+        assert_eq!(graph.len(), 200);
+        let expected = 200. * 199. * 0.05;
+        assert!((graph.edges() as f32 - expected).abs() < expected * 0.2);
+        for u in 0..graph.len() {
+            for v in graph.get_edges(u).0.iter() {
+                assert_ne!(u, *v);
+                assert!(graph.get_edges(*v).0.contains(&u));
+            }
+        }
+
+        let directed = erdos_renyi(200, 0.05, true, 2023);
+        assert!((directed.edges() as f32 - expected).abs() < expected * 0.2);
+        assert_eq!(erdos_renyi(50, 0.1, true, 1).edges(), erdos_renyi(50, 0.1, true, 1).edges());
+    }
+
+    #[test]
+    fn test_barabasi_albert() {
+        let graph = barabasi_albert(500, 3, 2023).unwrap();
+        assert_eq!(graph.len(), 500);
+        // Clique of 4 plus 3 edges per new node, in both directions
+        assert_eq!(graph.edges(), 2 * (6 + 3 * 496));
+        assert!((0..graph.len()).all(|u| graph.degree(u) >= 3));
+        assert!(barabasi_albert(5, 5, 2023).is_err());
+    }
+
+    #[test]
+    fn test_sbm() {
+        let probs = vec![vec![0.5, 0.01], vec![0.01, 0.5]];
+        let (graph, membership) = stochastic_block_model(&[50, 30], &probs, 2023).unwrap();
+        assert_eq!(graph.len(), 80);
+        assert_eq!(membership[49], 0);
+        assert_eq!(membership[50], 1);
+
+        let (mut intra, mut inter) = (0, 0);
+        for u in 0..graph.len() {
+            for v in graph.get_edges(u).0.iter() {
+                if membership[u] == membership[*v] { intra += 1 } else { inter += 1 }
+            }
+        }
+        assert!(intra > 10 * inter);
+
+        assert!(stochastic_block_model(&[2], &[vec![0.5, 0.5]], 0).is_err());
+        assert!(stochastic_block_model(&[2, 2], &[vec![0.5, 0.1], vec![0.2, 0.5]], 0).is_err());
+    }
+}
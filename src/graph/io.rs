@@ -0,0 +1,621 @@
+//! Readers and writers for common graph file formats.  Unlike the python facing readers in the
+//! top level io module, these are pure rust and return standard IO errors.  Besides plain edge
+//! lists, GraphML is supported for interchange with networkx and Gephi, and the METIS adjacency
+//! format for interchange with graph partitioners.
+use std::fs::File;
+use std::io::{BufRead,BufReader,Error,ErrorKind,Read,Result as IOResult,Write};
+
+use flate2::read::GzDecoder;
+use hashbrown::{HashMap,HashSet};
+
+use crate::vocab::{Vocab,split_qualified};
+use super::{Graph,CSR,GraphBuilder};
+
+/// Node type used for edge lists, which don't have a notion of node types.  Matches the node type
+/// used when filling missing nodes in the FeatureStore.
+pub const EDGE_LIST_NODE_TYPE: &str = "node";
+
+/// Compression applied to the file
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Compression {
+    /// Infers the compression from the file extension: .gz or .zst
+    Infer,
+
+    /// Plain text
+    None,
+
+    /// Gzip compressed
+    Gzip,
+
+    /// Zstandard compressed.  Requires the `zstd` feature.
+    Zstd
+}
+
+impl Compression {
+    fn resolve(&self, path: &str) -> Compression {
+        match self {
+            Compression::Infer if path.ends_with(".gz") => Compression::Gzip,
+            Compression::Infer if path.ends_with(".zst") => Compression::Zstd,
+            Compression::Infer => Compression::None,
+            c => *c
+        }
+    }
+}
+
+/// Opens a file for streaming reads, decompressing it as needed
+pub fn open_reader(path: &str, compression: Compression) -> IOResult<Box<dyn BufRead>> {
+    let f = BufReader::new(File::open(path)?);
+    let reader: Box<dyn BufRead> = match compression.resolve(path) {
+        Compression::Gzip => Box::new(BufReader::new(GzDecoder::new(f))),
+        Compression::Zstd => open_zstd(f)?,
+        _ => Box::new(f)
+    };
+    Ok(reader)
+}
+
+#[cfg(feature = "zstd")]
+fn open_zstd(f: BufReader<File>) -> IOResult<Box<dyn BufRead>> {
+    Ok(Box::new(BufReader::new(zstd::stream::read::Decoder::with_buffer(f)?)))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn open_zstd(_f: BufReader<File>) -> IOResult<Box<dyn BufRead>> {
+    Err(Error::new(ErrorKind::Unsupported, "Zstd support requires the `zstd` feature"))
+}
+
+/// Streams a (src, dst, [weight]) edge list into a CSR.  Node names are mapped to NodeIDs via the
+/// returned Vocab, using the EDGE_LIST_NODE_TYPE node type.  Blank lines and lines starting with
+/// `#` are skipped.  If `weighted` is false, the third column is ignored and all edges have a
+/// weight of 1.
+pub fn load_edge_list(
+    path: &str,
+    delimiter: char,
+    weighted: bool,
+    compression: Compression
+) -> IOResult<(Vocab, CSR)> {
+    let reader = open_reader(path, compression)?;
+    read_edge_list(reader, delimiter, weighted)
+}
+
+/// Reads an edge list from any reader.
+pub fn read_edge_list<R: BufRead>(
+    reader: R,
+    delimiter: char,
+    weighted: bool
+) -> IOResult<(Vocab, CSR)> {
+    read_edges(reader, delimiter, weighted, |_, name| Ok((EDGE_LIST_NODE_TYPE, name)))
+}
+
+/// Same as `load_edge_list`, but the endpoints are qualified "node_type:name" strings, such as
+/// "user:123", so the Vocab keeps the node types of heterogeneous graphs.
+pub fn load_qualified_edge_list(
+    path: &str,
+    delimiter: char,
+    weighted: bool,
+    compression: Compression
+) -> IOResult<(Vocab, CSR)> {
+    let reader = open_reader(path, compression)?;
+    read_qualified_edge_list(reader, delimiter, weighted)
+}
+
+/// Reads a qualified edge list from any reader.  Endpoints without a node type are malformed.
+pub fn read_qualified_edge_list<R: BufRead>(
+    reader: R,
+    delimiter: char,
+    weighted: bool
+) -> IOResult<(Vocab, CSR)> {
+    read_edges(reader, delimiter, weighted, |i, node| {
+        split_qualified(node).ok_or_else(|| malformed(i, &format!("Unqualified node {:?}", node)))
+    })
+}
+
+// Parses the edges, splitting each endpoint into its node type and name with `split`
+fn read_edges<R: BufRead>(
+    reader: R,
+    delimiter: char,
+    weighted: bool,
+    split: impl Fn(usize, &str) -> IOResult<(&str, &str)>
+) -> IOResult<(Vocab, CSR)> {
+    let mut vocab = Vocab::new();
+    let mut builder = GraphBuilder::new(false);
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim_end();
+        if line.len() == 0 || line.starts_with('#') {
+            continue
+        }
+
+        let mut pieces = line.split(delimiter);
+        let (from_node, to_node) = match (pieces.next(), pieces.next()) {
+            (Some(f), Some(t)) => (f, t),
+            _ => return Err(malformed(i, "Expected at least 2 fields!"))
+        };
+
+        let weight = if weighted {
+            let w = pieces.next()
+                .ok_or_else(|| malformed(i, "Missing weight field!"))?;
+            w.trim().parse::<f32>()
+                .map_err(|e| malformed(i, &format!("{} - {:?}", e, w)))?
+        } else {
+            1f32
+        };
+
+        let (f_type, f_name) = split(i, from_node.trim())?;
+        let (t_type, t_name) = split(i, to_node.trim())?;
+        let f_id = vocab.get_or_insert(f_type, f_name);
+        let t_id = vocab.get_or_insert(t_type, t_name);
+        builder.add_edge(f_id, t_id, weight);
+    }
+
+    Ok((vocab, builder.build_csr()))
+}
+
+/// Loads a GraphML file into a CSR.  See `read_graphml`.
+pub fn load_graphml(path: &str, compression: Compression) -> IOResult<(Vocab, CSR)> {
+    let reader = open_reader(path, compression)?;
+    read_graphml(reader)
+}
+
+/// Reads a GraphML document.  Nodes are added to the Vocab in the order they're declared, followed
+/// by any nodes only referenced by edges.  A node's type comes from its `node_type` data
+/// attribute, defaulting to EDGE_LIST_NODE_TYPE; ids of the form "node_type:name", as written by
+/// `write_graphml`, are stripped back to the name.  Edge weights come from the edge `weight`
+/// attribute, defaulting to 1.  Undirected edges are added in both directions.  Only the first
+/// graph in the document is read and nested graphs, hyperedges, and ports aren't supported.
+pub fn read_graphml<R: Read>(mut reader: R) -> IOResult<(Vocab, CSR)> {
+    let mut doc = String::new();
+    reader.read_to_string(&mut doc)?;
+
+    let mut node_type_key = None;
+    let mut weight_key = None;
+    let mut directed = true;
+    let mut graphs = 0;
+
+    // (id, node type)
+    let mut nodes: Vec<(String, Option<String>)> = Vec::new();
+    // (source, target, weight, directed)
+    let mut edges: Vec<(String, String, f32, bool)> = Vec::new();
+    let mut data_key: Option<String> = None;
+    let mut text = String::new();
+
+    let mut rest = doc.as_str();
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        // Comments, processing instructions, and doctypes are skipped
+        let skip_to = if rest.starts_with("<!--") {
+            Some("-->")
+        } else if rest.starts_with("<?") {
+            Some("?>")
+        } else if rest.starts_with("<!") {
+            Some(">")
+        } else {
+            None
+        };
+        if let Some(end) = skip_to {
+            let idx = rest.find(end).ok_or_else(|| malformed_graphml("Unterminated tag"))?;
+            rest = &rest[idx + end.len()..];
+            continue
+        }
+
+        let end = rest.find('>').ok_or_else(|| malformed_graphml("Unterminated tag"))?;
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+
+        if let Some(name) = tag.strip_prefix('/') {
+            if name.trim() == "data" {
+                if let Some(key) = data_key.take() {
+                    let value = unescape_xml(text.trim());
+                    if Some(&key) == node_type_key.as_ref() {
+                        if let Some(node) = nodes.last_mut() {
+                            node.1 = Some(value);
+                        }
+                    } else if Some(&key) == weight_key.as_ref() {
+                        if let Some(edge) = edges.last_mut() {
+                            edge.2 = value.parse::<f32>()
+                                .map_err(|e| malformed_graphml(&format!("{} - {:?}", e, value)))?;
+                        }
+                    }
+                }
+            } else if name.trim() == "graph" {
+                // Stop at the end of the first graph
+                break
+            }
+            text.clear();
+            continue
+        }
+
+        text.clear();
+        let self_closing = tag.ends_with('/');
+        let tag = tag.strip_suffix('/').unwrap_or(tag);
+        let (name, attrs) = parse_tag(tag)?;
+        let attr = |attr_name: &str| {
+            attrs.iter().find(|(k, _)| *k == attr_name).map(|(_, v)| v.as_str())
+        };
+        match name {
+            "key" => {
+                let id = attr("id").ok_or_else(|| malformed_graphml("Key without an id"))?;
+                match (attr("for"), attr("attr.name")) {
+                    (Some("node"), Some("node_type")) => node_type_key = Some(id.to_string()),
+                    (Some("edge"), Some("weight")) => weight_key = Some(id.to_string()),
+                    _ => ()
+                }
+            },
+            "graph" => {
+                graphs += 1;
+                if graphs > 1 {
+                    return Err(malformed_graphml("Nested graphs aren't supported"))
+                }
+                directed = attr("edgedefault") != Some("undirected");
+            },
+            "node" => {
+                let id = attr("id").ok_or_else(|| malformed_graphml("Node without an id"))?;
+                nodes.push((id.to_string(), None));
+            },
+            "edge" => {
+                let (source, target) = match (attr("source"), attr("target")) {
+                    (Some(s), Some(t)) => (s, t),
+                    _ => return Err(malformed_graphml("Edge without a source or target"))
+                };
+                let edge_directed = attr("directed").map(|d| d == "true").unwrap_or(directed);
+                edges.push((source.to_string(), target.to_string(), 1f32, edge_directed));
+            },
+            "data" if !self_closing => data_key = attr("key").map(|k| k.to_string()),
+            "hyperedge" | "port" => {
+                return Err(malformed_graphml(&format!("<{}> isn't supported", name)))
+            },
+            _ => ()
+        }
+    }
+
+    let mut vocab = Vocab::new();
+    let mut ids = HashMap::new();
+    for (id, node_type) in nodes.iter() {
+        let node_type = node_type.as_deref().unwrap_or(EDGE_LIST_NODE_TYPE);
+        let name = id.strip_prefix(node_type)
+            .and_then(|n| n.strip_prefix(':'))
+            .unwrap_or(id.as_str());
+        ids.insert(id.as_str(), vocab.get_or_insert(node_type, name));
+    }
+
+    let mut builder = GraphBuilder::new(false);
+    builder.ensure_nodes(vocab.len());
+    for (source, target, weight, edge_directed) in edges.iter() {
+        let mut node_id = |id: &str| match ids.get(id) {
+            Some(node_id) => *node_id,
+            None => vocab.get_or_insert(EDGE_LIST_NODE_TYPE, id)
+        };
+        let (s_id, t_id) = (node_id(source), node_id(target));
+        builder.add_edge(s_id, t_id, *weight);
+        if !edge_directed && s_id != t_id {
+            builder.add_edge(t_id, s_id, *weight);
+        }
+    }
+
+    Ok((vocab, builder.build_csr()))
+}
+
+/// Writes the graph as a directed GraphML document with edge weights.  If a Vocab is provided,
+/// node ids are their "node_type:name" names and each node carries a `node_type` attribute;
+/// otherwise node ids are their NodeIDs.
+pub fn write_graphml(w: &mut impl Write, graph: &CSR, vocab: Option<&Vocab>) -> IOResult<()> {
+    let node_name = |node_id: usize| -> IOResult<String> {
+        match vocab {
+            Some(vocab) => vocab.qualified_name(node_id).map(|n| escape_xml(&n)).ok_or_else(|| {
+                Error::new(ErrorKind::InvalidInput, format!("Node {} isn't in the vocab", node_id))
+            }),
+            None => Ok(node_id.to_string())
+        }
+    };
+
+    writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(w, r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#)?;
+    if vocab.is_some() {
+        writeln!(w, r#"  <key id="d0" for="node" attr.name="node_type" attr.type="string"/>"#)?;
+    }
+    writeln!(w, r#"  <key id="d1" for="edge" attr.name="weight" attr.type="double"/>"#)?;
+    writeln!(w, r#"  <graph id="G" edgedefault="directed">"#)?;
+    for node_id in 0..graph.len() {
+        let name = node_name(node_id)?;
+        match vocab.and_then(|v| v.get_node_type(node_id)) {
+            Some(node_type) => writeln!(w, r#"    <node id="{}"><data key="d0">{}</data></node>"#,
+                                        name, escape_xml(node_type))?,
+            None => writeln!(w, r#"    <node id="{}"/>"#, name)?
+        }
+    }
+    for node_id in 0..graph.len() {
+        let source = node_name(node_id)?;
+        let (edges, weights) = graph.get_edges(node_id);
+        for (t_n, weight) in edges.iter().zip(weights.iter()) {
+            writeln!(w, r#"    <edge source="{}" target="{}"><data key="d1">{}</data></edge>"#,
+                     source, node_name(*t_n)?, weight)?;
+        }
+    }
+    writeln!(w, "  </graph>")?;
+    writeln!(w, "</graphml>")
+}
+
+// Splits a start tag into its name and attributes
+fn parse_tag(tag: &str) -> IOResult<(&str, Vec<(&str, String)>)> {
+    let tag = tag.trim();
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let (name, mut rest) = tag.split_at(name_end);
+
+    let mut attrs = Vec::new();
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break
+        }
+        let eq = rest.find('=').ok_or_else(|| malformed_graphml("Attribute without a value"))?;
+        let key = rest[..eq].trim();
+        rest = rest[eq + 1..].trim_start();
+        let quote = rest.chars().next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| malformed_graphml("Unquoted attribute value"))?;
+        let end = rest[1..].find(quote)
+            .ok_or_else(|| malformed_graphml("Unterminated attribute value"))?;
+        attrs.push((key, unescape_xml(&rest[1..end + 1])));
+        rest = &rest[end + 2..];
+    }
+    Ok((name, attrs))
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn malformed_graphml(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Malformed GraphML: {}", msg))
+}
+
+/// Loads a METIS graph file into a CSR.  See `read_metis`.
+pub fn load_metis(path: &str, compression: Compression) -> IOResult<CSR> {
+    let reader = open_reader(path, compression)?;
+    read_metis(reader)
+}
+
+/// Reads a graph in the METIS adjacency format: a header of "n m [fmt [ncon]]" followed by one
+/// line per vertex listing its 1-indexed neighbors, each followed by the edge weight when fmt
+/// enables them.  Vertex sizes and weights are skipped.  Vertex i becomes NodeID i - 1, and since
+/// METIS lists every undirected edge from both ends, each listed neighbor becomes one directed
+/// edge.  Lines starting with `%` are comments.
+pub fn read_metis<R: BufRead>(reader: R) -> IOResult<CSR> {
+    let mut lines = reader.lines().enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(l) if l.starts_with('%')));
+
+    let (h_i, header) = lines.next().ok_or_else(|| malformed_metis(0, "Missing header!"))?;
+    let header = header?;
+    let fields = header.split_whitespace()
+        .map(|f| f.parse::<usize>().map_err(|e| malformed_metis(h_i, &format!("{} - {:?}", e, f))))
+        .collect::<IOResult<Vec<_>>>()?;
+    if fields.len() < 2 {
+        return Err(malformed_metis(h_i, "Expected at least 2 header fields!"))
+    }
+    let (num_nodes, num_edges) = (fields[0], fields[1]);
+
+    // fmt is up to three binary flags: vertex sizes, vertex weights, and edge weights
+    let fmt = fields.get(2).cloned().unwrap_or(0);
+    let has_sizes = (fmt / 100) % 10 == 1;
+    let has_vertex_weights = (fmt / 10) % 10 == 1;
+    let has_edge_weights = fmt % 10 == 1;
+    let ncon = if has_vertex_weights { fields.get(3).cloned().unwrap_or(1) } else { 0 };
+    let skip = has_sizes as usize + ncon;
+
+    let mut builder = GraphBuilder::new(false);
+    builder.ensure_nodes(num_nodes);
+    let mut adjacent = 0;
+    for node_id in 0..num_nodes {
+        let (i, line) = lines.next()
+            .ok_or_else(|| malformed_metis(h_i, &format!("Expected {} vertex lines!", num_nodes)))?;
+        let line = line?;
+        let mut values = line.split_whitespace().skip(skip);
+        while let Some(v) = values.next() {
+            let t_n = v.parse::<usize>()
+                .map_err(|e| malformed_metis(i, &format!("{} - {:?}", e, v)))?;
+            if t_n == 0 || t_n > num_nodes {
+                return Err(malformed_metis(i, &format!("Vertex {} out of range!", t_n)))
+            }
+
+            let weight = if has_edge_weights {
+                let w = values.next().ok_or_else(|| malformed_metis(i, "Missing edge weight!"))?;
+                w.parse::<f32>().map_err(|e| malformed_metis(i, &format!("{} - {:?}", e, w)))?
+            } else {
+                1f32
+            };
+            builder.add_edge(node_id, t_n - 1, weight);
+            adjacent += 1;
+        }
+    }
+
+    if adjacent != 2 * num_edges {
+        let msg = format!("Header lists {} edges but found {} adjacencies!", num_edges, adjacent);
+        return Err(malformed_metis(h_i, &msg))
+    }
+    Ok(builder.build_csr())
+}
+
+/// Writes the graph in the METIS adjacency format.  METIS requires an undirected graph, so every
+/// edge must have a reverse edge and self loops aren't allowed.  The graph should also be
+/// deduplicated, as the header's edge count assumes each edge is listed once from each end.  When
+/// `weighted` is true, edge weights are written too, rounded to integers of at least 1 since METIS
+/// only accepts positive integer weights.
+pub fn write_metis(w: &mut impl Write, graph: &CSR, weighted: bool) -> IOResult<()> {
+    let invalid = |msg: String| Error::new(ErrorKind::InvalidInput, msg);
+    let mut seen = HashSet::with_capacity(graph.edges());
+    for node_id in 0..graph.len() {
+        for t_n in graph.get_edges(node_id).0.iter() {
+            if *t_n == node_id {
+                return Err(invalid(format!("METIS doesn't allow self loops: node {}", node_id)))
+            }
+            seen.insert((node_id, *t_n));
+        }
+    }
+    if let Some((u, v)) = seen.iter().find(|(u, v)| !seen.contains(&(*v, *u))) {
+        let msg = format!("METIS requires an undirected graph: {} -> {} has no reverse", u, v);
+        return Err(invalid(msg))
+    }
+
+    let fmt = if weighted { " 001" } else { "" };
+    writeln!(w, "{} {}{}", graph.len(), graph.edges() / 2, fmt)?;
+    for node_id in 0..graph.len() {
+        let (edges, weights) = graph.get_edges(node_id);
+        let mut first = true;
+        for (t_n, weight) in edges.iter().zip(weights.iter()) {
+            if !first {
+                write!(w, " ")?;
+            }
+            first = false;
+            write!(w, "{}", t_n + 1)?;
+            if weighted {
+                write!(w, " {}", weight.round().max(1.) as u64)?;
+            }
+        }
+        writeln!(w)?;
+    }
+    Ok(())
+}
+
+fn malformed_metis(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}: Malformed METIS graph: {}", line + 1, msg))
+}
+
+fn malformed(line: usize, msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("{}: Malformed edge list: {}", line + 1, msg))
+}
+
+#[cfg(test)]
+mod graph_io_tests {
+    use super::*;
+    use crate::graph::Graph;
+
+    #[test]
+    fn test_read_edge_list() {
+        let data = "# comment\na,b,2\nb,c,0.5\n\nc,a,1\n";
+        let (vocab, csr) = read_edge_list(data.as_bytes(), ',', true).unwrap();
+        assert_eq!(vocab.len(), 3);
+        assert_eq!(csr.len(), 3);
+        assert_eq!(csr.edges(), 3);
+
+        let a = vocab.get_node_id(EDGE_LIST_NODE_TYPE, "a").unwrap();
+        let b = vocab.get_node_id(EDGE_LIST_NODE_TYPE, "b").unwrap();
+        assert_eq!(csr.get_edges(a), (&[b][..], &[2f32][..]));
+    }
+
+    #[test]
+    fn test_read_unweighted() {
+        let data = "a\tb\nb\tc\n";
+        let (_vocab, csr) = read_edge_list(data.as_bytes(), '\t', false).unwrap();
+        assert_eq!(csr.get_edges(0).1, &[1f32]);
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(read_edge_list("a,b\n".as_bytes(), ',', true).is_err());
+        assert!(read_edge_list("a\n".as_bytes(), ',', false).is_err());
+        assert!(read_edge_list("a,b,x\n".as_bytes(), ',', true).is_err());
+    }
+
+    #[test]
+    fn test_read_qualified_edge_list() {
+        let data = "user:1,gig:a:b,2\nuser:2,gig:a:b,1\n";
+        let (vocab, csr) = read_qualified_edge_list(data.as_bytes(), ',', true).unwrap();
+        assert_eq!((vocab.len(), csr.edges()), (3, 2));
+
+        let gig = vocab.get_node_id("gig", "a:b").unwrap();
+        let user = vocab.get_qualified("user:1").unwrap();
+        assert_eq!(csr.get_edges(user), (&[gig][..], &[2f32][..]));
+        assert_eq!(vocab.qualified_name(gig).unwrap(), "gig:a:b");
+
+        assert!(read_qualified_edge_list("user:1,gig\n".as_bytes(), ',', false).is_err());
+    }
+
+    #[test]
+    fn test_compression() {
+        assert_eq!(Compression::Infer.resolve("foo.tsv.gz"), Compression::Gzip);
+        assert_eq!(Compression::Infer.resolve("foo.tsv.zst"), Compression::Zstd);
+        assert_eq!(Compression::Infer.resolve("foo.tsv"), Compression::None);
+        assert_eq!(Compression::Gzip.resolve("foo.tsv"), Compression::Gzip);
+    }
+
+    #[test]
+    fn test_graphml() {
+        let data = r#"<?xml version="1.0" encoding="UTF-8"?>
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <!-- Exported from networkx -->
+  <key id="w" for="edge" attr.name="weight" attr.type="double"/>
+  <key id="t" for="node" attr.name="node_type" attr.type="string"/>
+  <graph edgedefault="undirected">
+    <node id="gig:1"><data key="t">gig</data></node>
+    <node id="a &amp; b"/>
+    <edge source="gig:1" target="a &amp; b"><data key="w">2.5</data></edge>
+    <edge source="a &amp; b" target="c" directed="true"/>
+  </graph>
+</graphml>"#;
+        let (vocab, csr) = read_graphml(data.as_bytes()).unwrap();
+        assert_eq!(vocab.len(), 3);
+        assert_eq!(csr.edges(), 3);
+
+        let gig = vocab.get_node_id("gig", "1").unwrap();
+        let ab = vocab.get_node_id(EDGE_LIST_NODE_TYPE, "a & b").unwrap();
+        let c = vocab.get_node_id(EDGE_LIST_NODE_TYPE, "c").unwrap();
+        assert_eq!(csr.get_edges(gig), (&[ab][..], &[2.5f32][..]));
+        assert_eq!(csr.get_edges(ab), (&[gig, c][..], &[2.5f32, 1.][..]));
+        assert_eq!(csr.degree(c), 0);
+
+        // Round trips through the writer
+        let mut buffer = Vec::new();
+        write_graphml(&mut buffer, &csr, Some(&vocab)).unwrap();
+        let (vocab2, csr2) = read_graphml(buffer.as_slice()).unwrap();
+        assert_eq!(vocab2.get_node_id("gig", "1"), Some(gig));
+        assert_eq!(vocab2.get_node_id(EDGE_LIST_NODE_TYPE, "a & b"), Some(ab));
+        (0..csr.len()).for_each(|n| assert_eq!(csr.get_edges(n), csr2.get_edges(n)));
+
+        assert!(read_graphml(r#"<graphml><graph><node id="a"#.as_bytes()).is_err());
+        assert!(read_graphml(r#"<graphml><graph><edge source="a"/></graph>"#.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_metis() {
+        // Triangle 1-2-3 with a pendant vertex 4 on 3, and a comment
+        let data = "% comment\n4 4 001\n2 1 3 2\n1 1 3 1\n1 2 2 1 4 5\n3 5\n";
+        let csr = read_metis(data.as_bytes()).unwrap();
+        assert_eq!(csr.len(), 4);
+        assert_eq!(csr.edges(), 8);
+        assert_eq!(csr.get_edges(2), (&[0, 1, 3][..], &[2f32, 1., 5.][..]));
+
+        let mut buffer = Vec::new();
+        write_metis(&mut buffer, &csr, true).unwrap();
+        assert_eq!(String::from_utf8(buffer.clone()).unwrap(), &data[10..]);
+        let csr2 = read_metis(buffer.as_slice()).unwrap();
+        (0..csr.len()).for_each(|n| assert_eq!(csr.get_edges(n), csr2.get_edges(n)));
+
+        // Vertex weights are skipped
+        let csr = read_metis("3 1 011\n7 2 4\n1 1 4\n2\n".as_bytes()).unwrap();
+        assert_eq!(csr.get_edges(0), (&[1][..], &[4f32][..]));
+        assert_eq!(csr.degree(2), 0);
+
+        // Isolated vertices have blank lines
+        let csr = read_metis("3 1\n2\n1\n\n".as_bytes()).unwrap();
+        assert_eq!(csr.len(), 3);
+        assert_eq!(csr.degree(2), 0);
+
+        assert!(read_metis("2 2\n2\n1\n".as_bytes()).is_err());
+        assert!(read_metis("2 1\n3\n1\n".as_bytes()).is_err());
+        let directed = CSR::construct_from_edges(vec![(0, 1, 1.)], false);
+        assert!(write_metis(&mut Vec::new(), &directed, false).is_err());
+    }
+}
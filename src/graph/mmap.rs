@@ -0,0 +1,269 @@
+//! Memory mapped CSR graph.  The edge arrays live in a file and are paged in by the OS on demand,
+//! allowing us to run random walk algorithms, which are mostly sequential per walk, over graphs
+//! which are larger than RAM.
+//!
+//! The file layout is a fixed header followed by the rows, columns, and weights arrays, all in
+//! native endianness:
+//!
+//! ```text
+//! [magic: u64][num_nodes: u64][num_edges: u64][reserved: u64]
+//! [rows: u64 * (num_nodes + 1)][columns: u64 * num_edges][weights: f32 * num_edges]
+//! ```
+use std::fs::File;
+use std::io::{BufWriter,Error,ErrorKind,Result as IOResult,Write};
+use std::mem::size_of;
+
+use memmap2::Mmap;
+
+use super::{Graph,CDFGraph,CumCSR,NodeID};
+
+/// Identifies the file format and catches endianness mismatches
+const MAGIC: u64 = 0x4353_524d_4d41_5031;
+
+const HEADER_SIZE: usize = 4 * size_of::<u64>();
+
+/// CDF graph whose rows, columns, and weights are memory mapped from a file.
+pub struct MmapCSR {
+    mmap: Mmap,
+    num_nodes: usize,
+    num_edges: usize,
+    columns_offset: usize,
+    weights_offset: usize
+}
+
+impl MmapCSR {
+
+    /// Writes a CumCSR to disk in the memory mapped format.
+    pub fn write(graph: &CumCSR, path: &str) -> IOResult<()> {
+        let csr = &graph.0;
+        let mut bw = BufWriter::new(File::create(path)?);
+        for v in [MAGIC, graph.len() as u64, graph.edges() as u64, 0u64].iter() {
+            bw.write_all(&v.to_ne_bytes())?;
+        }
+        for r in csr.rows.iter() {
+            bw.write_all(&(*r as u64).to_ne_bytes())?;
+        }
+        for c in csr.columns.iter() {
+            bw.write_all(&(*c as u64).to_ne_bytes())?;
+        }
+        for w in csr.weights.iter() {
+            bw.write_all(&w.to_ne_bytes())?;
+        }
+        bw.flush()
+    }
+
+    /// Converts a CumCSR to the memory mapped format, returning the opened graph.
+    pub fn convert(graph: &CumCSR, path: &str) -> IOResult<Self> {
+        MmapCSR::write(graph, path)?;
+        MmapCSR::open(path)
+    }
+
+    /// Opens a previously written graph.  Only the header, file size, and the first and last row
+    /// offsets are checked, so opening is constant time regardless of graph size; corrupt offsets
+    /// in the middle of the file will panic when the affected node is accessed.  Use
+    /// `open_validated` for untrusted files.
+    pub fn open(path: &str) -> IOResult<Self> {
+        let f = File::open(path)?;
+        // Safety: we assume the file isn't modified while mapped.
+        let mmap = unsafe { Mmap::map(&f)? };
+        if mmap.len() < HEADER_SIZE {
+            return Err(Error::new(ErrorKind::InvalidData, "File too small for header!"))
+        }
+
+        let header: Vec<u64> = mmap[..HEADER_SIZE].chunks(size_of::<u64>()).map(|b| {
+            let mut buff = [0u8; 8];
+            buff.copy_from_slice(b);
+            u64::from_ne_bytes(buff)
+        }).collect();
+
+        if header[0] != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a mmap CSR file, or wrong endianness!"))
+        }
+
+        let num_nodes = header[1] as usize;
+        let num_edges = header[2] as usize;
+
+        // Corrupt headers can overflow the offsets, which would otherwise wrap and pass the size
+        // check
+        let size_mismatch = || Error::new(ErrorKind::InvalidData, "File size doesn't match header!");
+        let columns_offset = num_nodes.checked_add(1)
+            .and_then(|rows| rows.checked_mul(size_of::<u64>()))
+            .and_then(|rows| rows.checked_add(HEADER_SIZE))
+            .ok_or_else(size_mismatch)?;
+        let weights_offset = num_edges.checked_mul(size_of::<u64>())
+            .and_then(|columns| columns.checked_add(columns_offset))
+            .ok_or_else(size_mismatch)?;
+        let expected = num_edges.checked_mul(size_of::<f32>())
+            .and_then(|weights| weights.checked_add(weights_offset))
+            .ok_or_else(size_mismatch)?;
+        if mmap.len() != expected {
+            return Err(size_mismatch())
+        }
+
+        let graph = MmapCSR { mmap, num_nodes, num_edges, columns_offset, weights_offset };
+        let rows = graph.rows();
+        if rows[0] != 0 || rows[num_nodes] != num_edges {
+            return Err(Error::new(ErrorKind::InvalidData, "Row offsets don't span the edges!"))
+        }
+        Ok(graph)
+    }
+
+    /// Opens a previously written graph, scanning the full file to validate it.  This pages in
+    /// the rows and columns arrays, which is expensive for large graphs.
+    pub fn open_validated(path: &str) -> IOResult<Self> {
+        let graph = MmapCSR::open(path)?;
+        graph.validate()?;
+        Ok(graph)
+    }
+
+    /// Checks the row offsets and columns, which are otherwise trusted when slicing edges.  Row
+    /// offsets must never decrease and columns must point to nodes within the graph.
+    fn validate(&self) -> IOResult<()> {
+        let rows = self.rows();
+        if rows.windows(2).any(|w| w[0] > w[1]) {
+            return Err(Error::new(ErrorKind::InvalidData, "Row offsets must be non-decreasing!"))
+        }
+        if self.columns().iter().any(|c| *c >= self.num_nodes) {
+            return Err(Error::new(ErrorKind::InvalidData, "Column references a missing node!"))
+        }
+        Ok(())
+    }
+
+    fn rows(&self) -> &[usize] {
+        self.slice(HEADER_SIZE, self.num_nodes + 1)
+    }
+
+    fn columns(&self) -> &[NodeID] {
+        self.slice(self.columns_offset, self.num_edges)
+    }
+
+    fn weights(&self) -> &[f32] {
+        self.slice(self.weights_offset, self.num_edges)
+    }
+
+    fn slice<T>(&self, offset: usize, len: usize) -> &[T] {
+        // Safety: offsets were validated against the file size on open, mmaps are page aligned,
+        // and all offsets are multiples of the element size.  usize is asserted to be 64 bits.
+        unsafe {
+            let ptr = self.mmap.as_ptr().add(offset) as *const T;
+            std::slice::from_raw_parts(ptr, len)
+        }
+    }
+}
+
+// The rows and columns are stored as u64 and read back as usize
+const _: () = assert!(size_of::<usize>() == size_of::<u64>());
+
+impl Graph for MmapCSR {
+    /// Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.num_nodes
+    }
+
+    /// Get number of edges in graph
+    fn edges(&self) -> usize {
+        self.num_edges
+    }
+
+    /// Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize {
+        let rows = self.rows();
+        rows[idx+1] - rows[idx]
+    }
+
+    /// Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        let (start, stop) = self.get_edge_range(idx);
+        (&self.columns()[start..stop], &self.weights()[start..stop])
+    }
+
+    /// Get edge Range
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        let rows = self.rows();
+        (rows[idx], rows[idx+1])
+    }
+}
+
+impl CDFGraph for MmapCSR {}
+
+#[cfg(test)]
+mod mmap_tests {
+    use super::*;
+    use crate::graph::CSR;
+
+    #[test]
+    fn test_round_trip() {
+        let edges = vec![
+            (0, 1, 1.),
+            (1, 1, 3.),
+            (1, 2, 2.),
+            (2, 0, 2.5),
+            (1, 0, 10.),
+        ];
+        let ccsr = CumCSR::convert(CSR::construct_from_edges(edges, false));
+
+        let path = temp_path("round_trip");
+        let path = path.to_str().unwrap();
+        let mcsr = MmapCSR::convert(&ccsr, path).unwrap();
+
+        assert_eq!(mcsr.len(), ccsr.len());
+        assert_eq!(mcsr.edges(), ccsr.edges());
+        for node_id in 0..ccsr.len() {
+            assert_eq!(mcsr.degree(node_id), ccsr.degree(node_id));
+            assert_eq!(mcsr.get_edges(node_id), ccsr.get_edges(node_id));
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    // Unique per test and process, so concurrent test runs don't clobber each other's files
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir()
+            .join(format!("graph_library_mmap_{}_{}.csr", name, std::process::id()))
+    }
+
+    // Writes a raw file in the mmap format
+    fn write_raw(path: &str, num_nodes: u64, rows: &[u64], columns: &[u64]) {
+        let mut bytes = Vec::new();
+        for v in [MAGIC, num_nodes, columns.len() as u64, 0u64].iter().chain(rows).chain(columns) {
+            bytes.extend_from_slice(&v.to_ne_bytes());
+        }
+        for _ in columns {
+            bytes.extend_from_slice(&1f32.to_ne_bytes());
+        }
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_offsets() {
+        let path = temp_path("corrupt");
+        let path = path.to_str().unwrap();
+
+        write_raw(path, 2, &[0, 1, 2], &[1, 0]);
+        assert!(MmapCSR::open(path).is_ok());
+        assert!(MmapCSR::open_validated(path).is_ok());
+
+        // Decreasing row offsets are only caught by the full scan
+        write_raw(path, 2, &[0, 2, 1], &[1, 0]);
+        assert!(MmapCSR::open(path).is_ok());
+        assert!(MmapCSR::open_validated(path).is_err());
+
+        // Offsets past the end of the edges
+        write_raw(path, 2, &[0, 1, 5], &[1, 0]);
+        assert!(MmapCSR::open(path).is_err());
+
+        // Offsets which don't start at zero
+        write_raw(path, 2, &[1, 1, 2], &[1, 0]);
+        assert!(MmapCSR::open(path).is_err());
+
+        // Column pointing past the last node is only caught by the full scan
+        write_raw(path, 2, &[0, 1, 2], &[1, 7]);
+        assert!(MmapCSR::open(path).is_ok());
+        assert!(MmapCSR::open_validated(path).is_err());
+
+        // Node count which overflows the offsets
+        write_raw(path, u64::MAX, &[0, 1, 2], &[1, 0]);
+        assert!(MmapCSR::open(path).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
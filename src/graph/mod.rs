@@ -0,0 +1,1018 @@
+//! This is the core graph library.
+//! While we define a number of different representations, including mutability, in practice we're
+//! only using CSR variants which also assumes static construction.  We have a couplte of tricks
+//! defined in here to allow for swapping of edges while minimizing the amount of memory we have to
+//! copy.
+
+pub mod io;
+pub mod generators;
+pub mod temporal;
+pub mod typed;
+pub mod dynamic;
+mod projection;
+mod sparsify;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+
+use rayon::prelude::*;
+
+use crate::error::GraphLibError;
+
+pub use projection::{project_bipartite,ProjectionWeighting};
+pub use sparsify::{sparsify,sparsify_typed,DegreeCap,EdgeSelection};
+
+pub type NodeID = usize;
+
+pub trait Graph {
+    /// Get number of nodes in graph
+    fn len(&self) -> usize;
+    
+    /// Get number of edges in graph
+    fn edges(&self) -> usize;
+
+    /// Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize;
+
+    /// Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]);
+    
+    /// Get edge offset in graph
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize);
+    
+}
+
+/// trait which allows graphs to be updated
+pub trait ModifiableGraph {
+    /// Get edges and corresponding weights
+    fn modify_edges(&mut self, idx: NodeID) -> (&mut [NodeID], &mut [f32]);
+}
+
+/// Used for trait bounds.  Confirms the underlying weights for each node
+/// sum to 1, as in a transition matrix.
+pub trait NormalizedGraph: Graph {}
+
+/// Used for trait bounds.  Confirms the weights are normalized transition
+/// matrix, optimized in cumulative distribution function.
+pub trait CDFGraph: Graph {}
+
+/// Graphs which can construct induced subgraphs.
+pub trait Subgraph: Graph + Sized {
+    /// Creates the subgraph induced by `nodes`, keeping only edges where both nodes are in the
+    /// set.  NodeIDs are remapped to their position in `nodes`.
+    fn subgraph(&self, nodes: &[NodeID]) -> Self;
+}
+
+/// Builds the remapped edges for an induced subgraph, where weights are computed by `weight_fn`
+/// given the original weights of the node.
+fn induced_edges<G: Graph>(
+    graph: &G,
+    nodes: &[NodeID],
+    weight_fn: impl Fn(&[f32], usize) -> f32
+) -> Vec<(NodeID, NodeID, f32)> {
+    let mut mapping = vec![None; graph.len()];
+    nodes.iter().enumerate().for_each(|(new_id, node_id)| mapping[*node_id] = Some(new_id));
+
+    let mut edges = Vec::new();
+    for (new_id, node_id) in nodes.iter().enumerate() {
+        let (out_edges, weights) = graph.get_edges(*node_id);
+        for (idx, out_node) in out_edges.iter().enumerate() {
+            if let Some(new_out) = mapping[*out_node] {
+                edges.push((new_id, new_out, weight_fn(weights, idx)));
+            }
+        }
+    }
+    edges
+}
+
+/// Compressed Sparse Row Format.  We use this for graphs since adjancency
+/// lists tend to use more memory.
+#[derive(Clone)]
+pub struct CSR {
+    rows: Vec<NodeID>,
+    columns: Vec<NodeID>,
+    weights: Vec<f32>
+}
+
+impl CSR {
+    /// Same as construct_from_edges, but validates the edges first.  Fails if there are no edges
+    /// or any weight is negative or not finite, either of which would silently poison the CDFs
+    /// used for sampling.
+    pub fn try_construct_from_edges(
+        edges: Vec<(NodeID, NodeID, f32)>, 
+        deduplicate: bool
+    ) -> Result<Self, GraphLibError> {
+        if edges.is_empty() {
+            return Err(GraphLibError::EmptyGraph)
+        }
+        if edges.par_iter().any(|(_, _, w)| !w.is_finite() || *w < 0f32) {
+            return Err("Edge weights must be finite and non-negative!".into())
+        }
+        Ok(CSR::construct_from_edges(edges, deduplicate))
+    }
+
+    pub fn construct_from_edges(mut edges: Vec<(NodeID, NodeID, f32)>, deduplicate: bool) -> Self {
+
+        if deduplicate {
+            CSR::deduplicate_edges(&mut edges);
+        }
+
+        // Determine the number of rows in the adjacency graph
+        let max_node = edges.iter().map(|(from_node, to_node, _)| {
+            *from_node.max(to_node)
+        }).max().unwrap_or(0);
+
+        // Figure out how many out edges per node
+        let mut rows = vec![0; max_node+2];
+        edges.iter().for_each(|(from_node, _to_node, _w)| {
+            rows[*from_node + 1] += 1;
+        });
+
+        // Convert to row offset format
+        let mut offset = 0;
+        rows.iter_mut().skip(1).for_each(|count| {
+            offset += *count;
+            *count = offset;
+        });
+
+        // Insert columns and weights
+        let mut counts  = vec![0; max_node+1];
+        let mut columns = vec![0; edges.len()];
+        let mut data    = vec![0f32; edges.len()];
+        edges.into_iter().for_each(|(from_node, to_node, weight)| {
+            let idx = rows[from_node] + counts[from_node];
+            columns[idx] = to_node;
+            data[idx] = weight;
+            counts[from_node] += 1;
+        });
+
+        CSR { rows, columns, weights: data }
+    }
+
+    fn deduplicate_edges(
+        edges: &mut Vec<(NodeID, NodeID, f32)>
+    ) -> () {
+        edges.par_sort_by_key(|(f_n, t_n, _)| (*f_n, *t_n));
+        let mut cur_record = 0;
+        let mut idx = 1;
+        while idx < edges.len() {
+            let (f_n, t_n, w) = edges[idx];
+            let c_r = edges[cur_record];
+            // Same edge, add the weights.
+            if f_n == c_r.0 && t_n == c_r.1 {
+                (&mut edges[cur_record]).2 += w;
+            } else {
+                // Different record, move it
+                cur_record += 1;
+                edges[cur_record] = edges[idx];
+            }
+            idx += 1;
+        }
+        edges.truncate(cur_record + 1);
+
+    }
+
+    /// Builds a CSR with exactly `num_nodes` rows from edges sorted by source node.
+    fn from_sorted_edges(num_nodes: usize, edges: Vec<(NodeID, NodeID, f32)>) -> Self {
+        let mut rows = vec![0; num_nodes + 1];
+        edges.iter().for_each(|(from_node, _to_node, _w)| {
+            rows[*from_node + 1] += 1;
+        });
+
+        let mut offset = 0;
+        rows.iter_mut().skip(1).for_each(|count| {
+            offset += *count;
+            *count = offset;
+        });
+
+        let (columns, weights) = edges.into_iter()
+            .map(|(_f_n, t_n, w)| (t_n, w))
+            .unzip();
+        CSR { rows, columns, weights }
+    }
+
+    /// Estimated bytes allocated for the graph
+    pub fn memory_bytes(&self) -> usize {
+        (self.rows.len() + self.columns.len()) * std::mem::size_of::<NodeID>() 
+            + self.weights.len() * std::mem::size_of::<f32>()
+    }
+
+    /// Iterates over all edges in the graph as (from, to, weight).
+    pub fn iter_edges(&self) -> impl Iterator<Item=(NodeID, NodeID, f32)> + '_ {
+        (0..self.len()).flat_map(move |from_node| {
+            let (edges, weights) = self.get_edges(from_node);
+            edges.iter().zip(weights.iter()).map(move |(to_node, w)| (from_node, *to_node, *w))
+        })
+    }
+
+    /// Reverses the direction of all edges in the graph.  Useful for "who reaches me" queries
+    /// on directed graphs.
+    pub fn transpose(&self) -> CSR {
+        let mut edges: Vec<_> = self.iter_edges()
+            .map(|(f_n, t_n, w)| (t_n, f_n, w))
+            .collect();
+        edges.par_sort_by_key(|(f_n, t_n, _)| (*f_n, *t_n));
+        CSR::from_sorted_edges(self.len(), edges)
+    }
+
+    /// Symmetrizes the graph, ensuring every edge has a reciprocal edge.  When both directions
+    /// exist, or an edge is repeated, weights are combined according to the policy.
+    pub fn to_undirected(&self, policy: SymmetrizePolicy) -> CSR {
+        let mut edges = Vec::with_capacity(self.edges() * 2);
+        self.iter_edges().for_each(|(f_n, t_n, w)| {
+            edges.push((f_n, t_n, w));
+            if f_n != t_n {
+                edges.push((t_n, f_n, w));
+            }
+        });
+        edges.par_sort_by_key(|(f_n, t_n, _)| (*f_n, *t_n));
+
+        // Merge repeated edges
+        let mut merged: Vec<(NodeID, NodeID, f32)> = Vec::with_capacity(edges.len());
+        for (f_n, t_n, w) in edges.into_iter() {
+            match merged.last_mut() {
+                Some(last) if last.0 == f_n && last.1 == t_n => {
+                    last.2 = match policy {
+                        SymmetrizePolicy::Max => last.2.max(w),
+                        SymmetrizePolicy::Sum => last.2 + w
+                    };
+                },
+                _ => merged.push((f_n, t_n, w))
+            }
+        }
+
+        CSR::from_sorted_edges(self.len(), merged)
+    }
+
+}
+
+impl CSR {
+    /// Transforms the edge weights in place
+    pub fn transform_weights(&mut self, transform: EdgeTransform) {
+        match transform {
+            EdgeTransform::RowNormalize => {
+                let rows = &self.rows;
+                let weights = &mut self.weights;
+                for start_stop in rows.windows(2) {
+                    let slice = &mut weights[start_stop[0]..start_stop[1]];
+                    let denom = slice.iter().sum::<f32>();
+                    if denom > 0f32 {
+                        slice.iter_mut().for_each(|w| *w /= denom);
+                    } else {
+                        let n = slice.len() as f32;
+                        slice.iter_mut().for_each(|w| *w = 1f32 / n);
+                    }
+                }
+            },
+            EdgeTransform::Log1p => {
+                self.weights.par_iter_mut().for_each(|w| *w = w.ln_1p());
+            },
+            EdgeTransform::Binarize { threshold } => {
+                self.weights.par_iter_mut().for_each(|w| {
+                    *w = if *w > threshold { 1f32 } else { 0f32 };
+                });
+            },
+            EdgeTransform::Symmetric => {
+                let out_degrees: Vec<f32> = (0..self.len()).into_par_iter()
+                    .map(|node_id| self.get_edges(node_id).1.iter().sum())
+                    .collect();
+                let mut in_degrees = vec![0f32; self.len()];
+                self.columns.iter().zip(self.weights.iter())
+                    .for_each(|(t_n, w)| in_degrees[*t_n] += w);
+
+                for node_id in 0..self.len() {
+                    let (start, stop) = self.get_edge_range(node_id);
+                    let out_d = out_degrees[node_id];
+                    for idx in start..stop {
+                        let denom = (out_d * in_degrees[self.columns[idx]]).sqrt();
+                        self.weights[idx] = if denom > 0f32 { self.weights[idx] / denom } else { 0f32 };
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Subgraph for CSR {
+    fn subgraph(&self, nodes: &[NodeID]) -> Self {
+        let edges = induced_edges(self, nodes, |weights, idx| weights[idx]);
+        CSR::from_sorted_edges(nodes.len(), edges)
+    }
+}
+
+/// Transforms of raw edge weights, applied in place before converting to a CumCSR.  Walks and
+/// positive sampling follow the transformed weights, so heavy tailed counts can be tamed without
+/// preprocessing the edge list.
+#[derive(Clone,Copy,Debug)]
+pub enum EdgeTransform {
+    /// Scales each node's weights to sum to 1.  CumCSR already normalizes each row, so this only
+    /// matters when combined with other transforms or when the CSR is used directly.  Rows summing
+    /// to zero become uniform.
+    RowNormalize,
+
+    /// ln(1 + w), which dampens large counts
+    Log1p,
+
+    /// 1 if the weight is above the threshold, 0 otherwise
+    Binarize { threshold: f32 },
+
+    /// D^-1/2 A D^-1/2, where degrees are weighted degrees.  Edges are scaled by the out degree of
+    /// their source and the in degree of their target, which are the same for undirected graphs.
+    /// Edges into popular nodes are discounted relative to their raw weights.
+    Symmetric
+}
+
+/// How to combine edge weights when symmetrizing a graph
+#[derive(Clone,Copy,Debug)]
+pub enum SymmetrizePolicy {
+    /// Keep the larger of the weights
+    Max,
+
+    /// Add the weights together
+    Sum
+}
+
+impl Graph for CSR {
+    // Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.rows.len() - 1
+    }
+    
+    // Get number of nodes in graph
+    fn edges(&self) -> usize {
+        self.weights.len()
+    }
+
+    // Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize {
+        self.rows[idx+1] - self.rows[idx]
+    }
+
+    // Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        let (start, stop) = self.get_edge_range(idx);
+        (&self.columns[start..stop], &self.weights[start..stop])
+    }
+
+    // get edge range
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        let start = self.rows[idx];
+        let stop  = self.rows[idx+1];
+        (start, stop)
+    }
+    
+}
+
+impl ModifiableGraph for CSR {
+    // Get edges and corresponding weights
+    fn modify_edges(&mut self, idx: NodeID) -> (&mut [NodeID], &mut [f32]) {
+        let (start, stop) = self.get_edge_range(idx);
+        (&mut self.columns[start..stop], &mut self.weights[start..stop])
+    }
+
+}
+
+/// Incrementally constructs a CSR from a stream of edges.  Rather than materializing the full edge
+/// list up front, edges are stored in per-node adjacency lists which are consumed during
+/// finalization.  When deduplicating, repeated edges are periodically compacted, summing their
+/// weights, to keep memory bounded for graphs with many duplicate edges.
+pub struct GraphBuilder {
+    /// Outbound edges for each node
+    adjacency: Vec<Vec<(NodeID, f32)>>,
+
+    /// Size of each adjacency list after its last compaction
+    compacted: Vec<usize>,
+
+    /// Whether to merge repeated edges, summing their weights
+    deduplicate: bool,
+
+    /// Number of nodes seen, either as a source or destination
+    num_nodes: usize
+}
+
+impl GraphBuilder {
+    pub fn new(deduplicate: bool) -> Self {
+        GraphBuilder {
+            adjacency: Vec::new(),
+            compacted: Vec::new(),
+            deduplicate,
+            num_nodes: 0
+        }
+    }
+
+    /// Adds a directed edge from u to v.
+    pub fn add_edge(&mut self, u: NodeID, v: NodeID, weight: f32) {
+        if u >= self.adjacency.len() {
+            self.adjacency.resize_with(u + 1, Vec::new);
+            self.compacted.resize(u + 1, 0);
+        }
+        self.num_nodes = self.num_nodes.max(u.max(v) + 1);
+
+        let row = &mut self.adjacency[u];
+        row.push((v, weight));
+
+        // Compact the row once it's doubled since the last compaction
+        if self.deduplicate && row.len() >= 2 * self.compacted[u].max(16) {
+            GraphBuilder::compact_row(row);
+            self.compacted[u] = row.len();
+        }
+    }
+
+    /// Ensures the graph has at least `num_nodes` nodes, even if some never have edges.
+    pub fn ensure_nodes(&mut self, num_nodes: usize) {
+        self.num_nodes = self.num_nodes.max(num_nodes);
+    }
+
+    /// Adds all edges from an iterator.
+    pub fn extend<I: Iterator<Item=(NodeID, NodeID, f32)>>(&mut self, edges: I) {
+        edges.for_each(|(u, v, w)| self.add_edge(u, v, w));
+    }
+
+    /// Number of nodes currently in the graph
+    pub fn len(&self) -> usize {
+        self.num_nodes
+    }
+
+    /// Number of edges currently stored.  If deduplicating, this is an upper bound.
+    pub fn edges(&self) -> usize {
+        self.adjacency.iter().map(|row| row.len()).sum()
+    }
+
+    fn compact_row(row: &mut Vec<(NodeID, f32)>) {
+        row.sort_by_key(|(t_n, _)| *t_n);
+        let mut cur_record = 0;
+        for idx in 1..row.len() {
+            let (t_n, w) = row[idx];
+            if t_n == row[cur_record].0 {
+                row[cur_record].1 += w;
+            } else {
+                cur_record += 1;
+                row[cur_record] = row[idx];
+            }
+        }
+        row.truncate(cur_record + 1);
+    }
+
+    /// Finalizes the builder into a CSR.
+    pub fn build_csr(mut self) -> CSR {
+        if self.deduplicate {
+            self.adjacency.par_iter_mut()
+                .filter(|row| row.len() > 0)
+                .for_each(|row| GraphBuilder::compact_row(row));
+        }
+
+        let n_edges = self.edges();
+        let mut rows = Vec::with_capacity(self.num_nodes + 1);
+        let mut columns = Vec::with_capacity(n_edges);
+        let mut weights = Vec::with_capacity(n_edges);
+        rows.push(0);
+        for node_id in 0..self.num_nodes {
+            if node_id < self.adjacency.len() {
+                // Release the adjacency list as we go
+                let row = std::mem::take(&mut self.adjacency[node_id]);
+                for (t_n, w) in row.into_iter() {
+                    columns.push(t_n);
+                    weights.push(w);
+                }
+            }
+            rows.push(columns.len());
+        }
+
+        // Match construct_from_edges, which always has at least one row
+        if self.num_nodes == 0 {
+            rows.push(0);
+        }
+
+        CSR { rows, columns, weights }
+    }
+
+    /// Finalizes the builder into a CumCSR.
+    pub fn build_cum_csr(self) -> CumCSR {
+        CumCSR::convert(self.build_csr())
+    }
+
+    /// Finalizes the builder into a CumCSR, transforming the raw weights first.
+    pub fn build_cum_csr_with(self, transforms: &[EdgeTransform]) -> CumCSR {
+        CumCSR::convert_with(self.build_csr(), transforms)
+    }
+}
+
+/// Normalizes sum of weights for a node to 1
+pub struct NormalizedCSR(CSR);
+
+impl NormalizedCSR {
+    pub fn convert(mut csr: CSR) -> Self {
+        for start_stop in csr.rows.windows(2) {
+            let (start, stop) = (start_stop[0], start_stop[1]);
+            let slice = &mut csr.weights[start..stop];
+            let denom = slice.iter().sum::<f32>();
+            if denom > 0f32 {
+                slice.iter_mut().for_each(|w| *w /= denom);
+            } else {
+                let n = slice.len() as f32;
+                slice.iter_mut().for_each(|w| *w = 1f32 / n );
+            }
+        }
+        NormalizedCSR(csr)
+    }
+}
+
+impl Graph for NormalizedCSR {
+    /// Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+    
+    /// Get number of nodes in graph
+    fn edges(&self) -> usize {
+        self.0.edges()
+    }
+
+    /// Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize {
+        self.0.degree(idx)
+    }
+
+    /// Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        self.0.get_edges(idx)
+    }
+    
+    // get edge range
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        self.0.get_edge_range(idx)
+    }
+
+     
+}
+
+impl ModifiableGraph for NormalizedCSR {
+    /// Get edges and corresponding weights
+    fn modify_edges(&mut self, idx: NodeID) -> (&mut [NodeID], &mut [f32]) {
+        self.0.modify_edges(idx)
+    }
+}
+
+impl NormalizedGraph for NormalizedCSR {}
+
+/// This is the main one used in Cloverleaf - converts CSR formatted graphs into CDF format to make sampling from
+/// edges efficient (log(N)).  
+#[derive(Clone)]
+pub struct CumCSR(CSR);
+
+impl CumCSR {
+    pub fn convert(mut csr: CSR) -> Self {
+        for start_stop in csr.rows.windows(2) {
+            let (start, stop) = (start_stop[0], start_stop[1]);
+            if start < stop {
+                convert_edges_to_cdf(&mut csr.weights[start..stop]);
+            }
+        }
+        CumCSR(csr)
+    }
+
+    /// Applies the transforms to the raw weights, in order, then converts.
+    pub fn convert_with(mut csr: CSR, transforms: &[EdgeTransform]) -> Self {
+        transforms.iter().for_each(|t| csr.transform_weights(*t));
+        CumCSR::convert(csr)
+    }
+
+    /// Estimated bytes allocated for the graph
+    pub fn memory_bytes(&self) -> usize {
+        self.0.memory_bytes()
+    }
+
+    pub fn clone_with_edges(&self, weights: Vec<f32>) -> Result<CumCSR,&'static str> {
+        if weights.len() != self.0.weights.len() {
+            Err("weights lengths not equal!")?
+        }
+
+        let graph = CSR {
+            rows: self.0.rows.clone(),
+            columns: self.0.columns.clone(),
+            weights: weights
+        };
+
+        // Test that the weights are properly CDF
+        for node_id in 0..graph.len() {
+            let weights = self.get_edges(node_id).1;
+            for pair in weights.windows(2) {
+                match pair {
+                    &[p, n] => { 
+                        if p > 1.0 {
+                            Err("Edge weight exceeds 1.0, illegal in CDF")?
+                        } else if n < p {
+                            Err("Edge weight for node in decreasing order")?
+                        }
+                    },
+                    _ => panic!("Something busted with built in")
+                }
+            }
+            if weights[weights.len() - 1] > 1.0 {
+                Err("Edge weight exceeds 1.0, illegal in CDF")?
+            }
+        }
+
+        Ok(CumCSR(graph))
+    }
+}
+
+impl Graph for CumCSR {
+    /// Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Get number of nodes in graph
+    fn edges(&self) -> usize {
+        self.0.edges()
+    }
+
+    /// Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize {
+        self.0.degree(idx)
+    }
+
+    /// Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        self.0.get_edges(idx)
+    }
+    
+    /// Get edge Range
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        self.0.get_edge_range(idx)
+    }
+
+}
+
+impl ModifiableGraph for CumCSR {
+    
+    /// Get edges and corresponding weights
+    fn modify_edges(&mut self, idx: NodeID) -> (&mut [NodeID], &mut [f32]) {
+        self.0.modify_edges(idx)
+    }
+
+}
+
+impl CDFGraph for CumCSR {}
+
+impl Subgraph for CumCSR {
+    /// Transition probabilities are renormalized over the remaining edges.
+    fn subgraph(&self, nodes: &[NodeID]) -> Self {
+        let edges = induced_edges(self, nodes, |weights, idx| CDFtoP::new(weights).prob(idx));
+        CumCSR::convert(CSR::from_sorted_edges(nodes.len(), edges))
+    }
+}
+
+/// This is a graph which allows us to swap in a new set of edge weights without having to copy the
+/// entire graph.  We use it in cases where policies update edge transition probabilities.
+pub struct OptCDFGraph<'a,G> {
+    graph: &'a G,
+    weights: Vec<f32>
+}
+
+impl <'a,G:Graph> OptCDFGraph<'a,G> {
+    pub fn new(graph: &'a G, weights: Vec<f32>) -> Self {
+        let mut s = OptCDFGraph { graph, weights };
+        s.convert_edges();
+        s
+    }
+
+    pub fn into_weights(self) -> Vec<f32> {
+        self.weights
+    }
+
+    pub fn convert_edges(&mut self) {
+        for idx in 0..self.len() {
+            let (start, stop) = self.get_edge_range(idx);
+            if start < stop {
+                convert_edges_to_cdf(&mut self.weights[start..stop]);
+            }
+        }
+    }
+
+}
+
+impl <'a,G:CDFGraph> OptCDFGraph<'a,G> {
+    pub fn clone_from_cdf(graph: &'a G) -> Self {
+        let mut weights = vec![0f32; graph.edges()];
+        for node_id in 0..graph.len() {
+            let w = graph.get_edges(node_id).1;
+            let (start, stop) = graph.get_edge_range(node_id);
+            weights[start..stop].clone_from_slice(w);
+        }
+
+        OptCDFGraph { graph, weights }
+    }
+
+}
+
+impl <'a,G:Graph> Graph for OptCDFGraph<'a,G> {
+    /// Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.graph.len()
+    }
+
+    /// Get number of nodes in graph
+    fn edges(&self) -> usize {
+        self.graph.edges()
+    }
+
+    /// Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize {
+        self.graph.degree(idx)
+    }
+
+    /// Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        let edges = self.graph.get_edges(idx).0;
+        let (start, stop) = self.get_edge_range(idx);
+        let weights = &self.weights[start..stop];
+        (edges, weights)
+    }
+    
+    /// Get edge Range
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        self.graph.get_edge_range(idx)
+    }
+
+}
+
+impl <'a,G:Graph> CDFGraph for OptCDFGraph<'a,G> {}
+
+/// Struct which converts CDF format to transition probabilities.
+#[derive(Clone,Copy)]
+pub struct CDFtoP<'a> {
+    cdf: &'a [f32],
+    idx: usize
+}
+
+impl <'a> CDFtoP<'a> {
+    pub fn new(weights: &'a [f32]) -> Self {
+        CDFtoP { cdf: weights, idx: 0 }
+    }
+
+    pub fn prob(&self, idx: usize) -> f32 {
+        if idx == 0 {
+            self.cdf[idx]
+        } else {
+            self.cdf[idx] - self.cdf[idx - 1]
+        }
+    }
+}
+
+impl <'a> Iterator for CDFtoP<'a> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx < self.cdf.len() {
+            let p = if self.idx == 0 {
+                Some(self.cdf[self.idx])
+            } else {
+                Some(self.cdf[self.idx] - self.cdf[self.idx-1])
+            };
+            self.idx += 1;
+            p
+        } else {
+            None
+        }
+    }
+}
+
+/// Converts a set of weights to CDF
+pub fn convert_edges_to_cdf(weights: &mut [f32]) {
+    let mut denom = weights.iter().sum::<f32>();
+    if denom == 0f32 {
+        // If we have no weights, set all weights to uniform.
+        weights.iter_mut().for_each(|w| {
+            *w = 1.
+        });
+        denom = weights.len() as f32;
+    }
+
+    let mut acc = 0.;
+    weights.iter_mut().for_each(|w| {
+        acc += *w;
+        *w = acc / denom;
+    });
+    weights[weights.len() - 1] = 1.;
+}
+
+/// Converts an iterator of weights to CDF
+pub fn collect_weights_into(
+    weights: impl Iterator<Item=f32>,
+    cdf_weights: &mut Vec<f32>
+) {
+    cdf_weights.clear();
+    if let (_, Some(ul)) = weights.size_hint() {
+        cdf_weights.reserve(ul);
+    }
+
+    let mut acc = 0f32;
+    cdf_weights.push(0f32);
+    for wi in weights {
+        acc += wi;
+        cdf_weights.push(acc);
+    }
+    for wi in cdf_weights.iter_mut() {
+        *wi /= acc;
+    }
+    let l = cdf_weights.len();
+    cdf_weights[l - 1] = 1.;
+}
+
+#[cfg(test)]
+mod csr_tests {
+    use super::*;
+
+    fn build_edges() -> Vec<(usize, usize, f32)> {
+        vec![
+            (0, 1, 1.),
+            (1, 1, 3.),
+            (1, 2, 2.),
+            (2, 0, 2.5),
+            (1, 0, 10.),
+        ]
+    }
+
+    #[test]
+    fn construct_csr() {
+        let edges = build_edges();
+
+        let csr = CSR::construct_from_edges(edges);
+        assert_eq!(csr.rows, vec![0, 1, 4, 5]);
+        assert_eq!(csr.columns, vec![1, 1, 2, 0, 0]);
+        assert_eq!(csr.weights, vec![1., 3., 2., 10., 2.5]);
+    }
+
+    #[test]
+    fn test_try_construct() {
+        assert!(CSR::try_construct_from_edges(build_edges(), false).is_ok());
+        assert!(matches!(CSR::try_construct_from_edges(vec![], false), 
+                         Err(GraphLibError::EmptyGraph)));
+        assert!(CSR::try_construct_from_edges(vec![(0, 1, f32::NAN)], false).is_err());
+        assert!(CSR::try_construct_from_edges(vec![(0, 1, -1.)], false).is_err());
+    }
+
+    #[test]
+    fn test_graph_builder() {
+        let mut builder = GraphBuilder::new(false);
+        builder.extend(build_edges().into_iter());
+        assert_eq!(builder.len(), 3);
+
+        let csr = builder.build_csr();
+        assert_eq!(csr.rows, vec![0, 1, 4, 5]);
+        assert_eq!(csr.columns, vec![1, 1, 2, 0, 0]);
+        assert_eq!(csr.weights, vec![1., 3., 2., 10., 2.5]);
+    }
+
+    #[test]
+    fn test_graph_builder_dedup() {
+        let mut builder = GraphBuilder::new(true);
+        for _ in 0..100 {
+            builder.add_edge(0, 2, 1.);
+            builder.add_edge(0, 1, 0.5);
+        }
+        builder.add_edge(3, 0, 1.);
+
+        // Compaction should keep the adjacency lists small
+        assert!(builder.edges() < 50);
+
+        let csr = builder.build_csr();
+        assert_eq!(csr.rows, vec![0, 2, 2, 2, 3]);
+        assert_eq!(csr.columns, vec![1, 2, 0]);
+        assert_eq!(csr.weights, vec![50., 100., 1.]);
+    }
+
+    #[test]
+    fn test_transpose() {
+        let csr = CSR::construct_from_edges(build_edges(), false);
+        let t_csr = csr.transpose();
+        assert_eq!(t_csr.rows, vec![0, 2, 4, 5]);
+        assert_eq!(t_csr.columns, vec![1, 2, 0, 1, 1]);
+        assert_eq!(t_csr.weights, vec![10., 2.5, 1., 3., 2.]);
+
+        // Double transpose should be the original, modulo sorting
+        let tt_csr = t_csr.transpose();
+        assert_eq!(tt_csr.rows, csr.rows);
+        assert_eq!(tt_csr.get_edges(1), (&[0, 1, 2][..], &[10., 3., 2.][..]));
+    }
+
+    #[test]
+    fn test_to_undirected() {
+        let csr = CSR::construct_from_edges(build_edges(), false);
+        let u_csr = csr.to_undirected(SymmetrizePolicy::Max);
+        assert_eq!(u_csr.get_edges(0), (&[1, 2][..], &[10., 2.5][..]));
+        assert_eq!(u_csr.get_edges(1), (&[0, 1, 2][..], &[10., 3., 2.][..]));
+        assert_eq!(u_csr.get_edges(2), (&[0, 1][..], &[2.5, 2.][..]));
+
+        let u_csr = csr.to_undirected(SymmetrizePolicy::Sum);
+        assert_eq!(u_csr.get_edges(0), (&[1, 2][..], &[11., 2.5][..]));
+        assert_eq!(u_csr.get_edges(1), (&[0, 1, 2][..], &[11., 3., 2.][..]));
+    }
+
+    #[test]
+    fn test_subgraph() {
+        let csr = CSR::construct_from_edges(build_edges(), false);
+        let sub = csr.subgraph(&[1, 2]);
+        assert_eq!(sub.rows, vec![0, 2, 2]);
+        assert_eq!(sub.columns, vec![0, 1]);
+        assert_eq!(sub.weights, vec![3., 2.]);
+
+        let ccsr = CumCSR::convert(csr);
+        let sub = ccsr.subgraph(&[1, 2]);
+        let (edges, weights) = sub.get_edges(0);
+        assert_eq!(edges, &[0, 1]);
+        assert!((weights[0] - 0.6).abs() < 1e-5);
+        assert_eq!(weights[1], 1.);
+    }
+
+    #[test]
+    fn test_graph() {
+        let edges = build_edges();
+
+        let mut csr = CSR::construct_from_edges(edges);
+        assert_eq!(csr.len(), 3);
+        assert_eq!(csr.degree(0), 1);
+        assert_eq!(csr.degree(1), 3);
+        assert_eq!(csr.degree(2), 1);
+        assert_eq!(csr.get_edges(2), (vec![0].as_slice(), vec![2.5].as_slice()));
+        assert_eq!(csr.get_edges(1), (vec![1,2,0].as_slice(), vec![3., 2., 10.].as_slice()));
+
+        {
+            let (_edges, weights) = csr.modify_edges(1);
+            weights[2] = 20.;
+        }
+
+        assert_eq!(csr.get_edges(1), (vec![1,2,0].as_slice(), vec![3., 2., 20.].as_slice()));
+    }
+
+    #[test]
+    fn construct_mk() {
+        let edges = build_edges();
+
+        let csr = CSR::construct_from_edges(edges);
+        let mk = NormalizedCSR::convert(csr);
+
+        assert_eq!(mk.get_edges(0), (vec![1].as_slice(), vec![1.].as_slice()));
+        assert_eq!(mk.get_edges(1), (vec![1,2,0].as_slice(), vec![3./15., 2./15., 10./15.].as_slice()));
+        assert_eq!(mk.get_edges(2), (vec![0].as_slice(), vec![1.].as_slice()));
+
+    }
+
+    #[test]
+    fn construct_cdf() {
+        let edges = build_edges();
+
+        let csr = CSR::construct_from_edges(edges);
+        let ccsr = CumCSR::convert(csr);
+
+        assert_eq!(ccsr.0.rows, vec![0, 1, 4, 5]);
+        assert_eq!(ccsr.0.columns, vec![1, 1, 2, 0, 0]);
+        assert_eq!(ccsr.0.weights, vec![1., 3./15., 5./15., 15./15., 1.]);
+
+    }
+
+    #[test]
+    fn construct_cdf_to_p() {
+        let edges = build_edges();
+
+        let csr = CSR::construct_from_edges(edges);
+        let ccsr = CumCSR::convert(csr);
+
+        let weights = ccsr.get_edges(1).1;
+        let ps = CDFtoP::new(weights);
+        let exp = vec![3./15., 2./15., 10./15.];
+        ps.zip(exp.iter()).for_each(|(p, exp_p)| {
+            assert!((p - exp_p).abs() < 1e-7);
+        });
+    }
+
+    #[test]
+    fn transform_weights() {
+        let edges = vec![(0, 1, 3.), (0, 2, 1.), (1, 0, 1.), (2, 0, 0.5)];
+        let graph = CSR::construct_from_edges(edges, false);
+
+        let mut csr = graph.clone();
+        csr.transform_weights(EdgeTransform::RowNormalize);
+        assert_eq!(csr.weights, vec![0.75, 0.25, 1., 1.]);
+
+        let mut csr = graph.clone();
+        csr.transform_weights(EdgeTransform::Binarize { threshold: 0.75 });
+        assert_eq!(csr.weights, vec![1., 1., 1., 0.]);
+
+        let mut csr = graph.clone();
+        csr.transform_weights(EdgeTransform::Log1p);
+        assert!((csr.weights[0] - 4f32.ln()).abs() < 1e-6);
+
+        // Out degrees are [4, 1, 0.5] and in degrees [1.5, 3, 1]
+        let mut csr = graph.clone();
+        csr.transform_weights(EdgeTransform::Symmetric);
+        let expected = [3. / (4f32 * 3.).sqrt(), 1. / 4f32.sqrt(), 1. / 1.5f32.sqrt(), 0.5 / 0.75f32.sqrt()];
+        csr.weights.iter().zip(expected.iter()).for_each(|(w, e)| assert!((w - e).abs() < 1e-6));
+
+        // Transforms apply in order before the CDF conversion
+        let ccsr = CumCSR::convert_with(graph, &[EdgeTransform::Binarize { threshold: 0.75 }]);
+        assert_eq!(ccsr.get_edges(0).1, &[0.5, 1.]);
+    }
+
+}
@@ -0,0 +1,140 @@
+//! Bipartite projections, collapsing a user-item graph into an item-item graph where two items are
+//! connected if they share users.  The graph must contain edges in both directions, ie. from items
+//! to users and from users to items, with raw rather than CDF weights.
+//!
+//! Projections are quadratic in the degree of the dropped side, so rather than materializing every
+//! pair we compute each kept node's neighborhood independently and immediately truncate it to the
+//! `max_neighbors` highest weighted neighbors.  Memory stays linear in the number of kept nodes.
+use hashbrown::HashMap;
+use float_ord::FloatOrd;
+use rayon::prelude::*;
+
+use super::{CSR,Graph,NodeID};
+
+/// How to weight edges in a bipartite projection
+#[derive(Clone,Copy,Debug)]
+pub enum ProjectionWeighting {
+    /// Number of shared neighbors
+    CoOccurrence,
+
+    /// Shared neighbors divided by the size of the union of neighbors
+    Jaccard,
+
+    /// Cosine similarity of the weighted neighbor vectors
+    Cosine
+}
+
+/// Projects the bipartite graph onto the nodes where `keep_side` is true, connecting nodes which
+/// share a neighbor on the other side.  NodeIDs are preserved; dropped nodes have no edges.  Each
+/// node keeps at most `max_neighbors` edges, preferring higher weights.
+pub fn project_bipartite<G: Graph + Sync>(
+    graph: &G,
+    keep_side: impl Fn(NodeID) -> bool + Sync,
+    weighting: ProjectionWeighting,
+    max_neighbors: usize
+) -> CSR {
+    // Degree and norm of each kept node over the other side
+    let stats: Vec<(usize, f32)> = (0..graph.len()).into_par_iter().map(|node_id| {
+        if !keep_side(node_id) { return (0, 0.) }
+        let (edges, weights) = graph.get_edges(node_id);
+        edges.iter().zip(weights.iter())
+            .filter(|(out_node, _)| !keep_side(**out_node))
+            .fold((0, 0.), |(d, n), (_, w)| (d + 1, n + w * w))
+    }).collect();
+
+    let rows: Vec<Vec<(NodeID, f32)>> = (0..graph.len()).into_par_iter().map(|u| {
+        if !keep_side(u) { return Vec::new() }
+
+        // Shared neighbor counts and weighted dot products to each two hop neighbor
+        let mut shared: HashMap<NodeID, (usize, f32)> = HashMap::new();
+        let (edges, weights) = graph.get_edges(u);
+        for (mid, w_u) in edges.iter().zip(weights.iter()) {
+            if keep_side(*mid) { continue }
+            let (mid_edges, mid_weights) = graph.get_edges(*mid);
+            for (v, w_v) in mid_edges.iter().zip(mid_weights.iter()) {
+                if *v != u && keep_side(*v) {
+                    let e = shared.entry(*v).or_insert((0, 0.));
+                    e.0 += 1;
+                    e.1 += w_u * w_v;
+                }
+            }
+        }
+
+        let (d_u, n_u) = stats[u];
+        let mut row: Vec<_> = shared.into_iter().map(|(v, (count, dot))| {
+            let (d_v, n_v) = stats[v];
+            let w = match weighting {
+                ProjectionWeighting::CoOccurrence => count as f32,
+                ProjectionWeighting::Jaccard => count as f32 / (d_u + d_v - count).max(1) as f32,
+                ProjectionWeighting::Cosine => {
+                    let denom = (n_u * n_v).sqrt();
+                    if denom > 0. { dot / denom } else { 0. }
+                }
+            };
+            (v, w)
+        }).collect();
+
+        if row.len() > max_neighbors {
+            row.sort_by_key(|(v, w)| (FloatOrd(-*w), *v));
+            row.truncate(max_neighbors);
+        }
+        row.sort_by_key(|(v, _)| *v);
+        row
+    }).collect();
+
+    let edges = rows.into_iter().enumerate()
+        .flat_map(|(u, row)| row.into_iter().map(move |(v, w)| (u, v, w)))
+        .collect();
+    CSR::from_sorted_edges(graph.len(), edges)
+}
+
+#[cfg(test)]
+mod projection_tests {
+    use super::*;
+
+    fn build_graph() -> CSR {
+        // Users 0, 1, 2 and items 3, 4, 5.  User 0 bought items 3 and 4, user 1 bought 3, 4, and 5,
+        // user 2 bought item 5.
+        let mut edges = Vec::new();
+        for (user, item) in [(0, 3), (0, 4), (1, 3), (1, 4), (1, 5), (2, 5)] {
+            edges.push((user, item, 1.));
+            edges.push((item, user, 1.));
+        }
+        CSR::construct_from_edges(edges, false)
+    }
+
+    #[test]
+    fn test_cooccurrence() {
+        let graph = build_graph();
+        let items = project_bipartite(&graph, |n| n >= 3, ProjectionWeighting::CoOccurrence, 10);
+        assert_eq!(items.len(), 6);
+        assert_eq!(items.degree(0), 0);
+        assert_eq!(items.get_edges(3), (&[4, 5][..], &[2., 1.][..]));
+        assert_eq!(items.get_edges(5), (&[3, 4][..], &[1., 1.][..]));
+
+        let users = project_bipartite(&graph, |n| n < 3, ProjectionWeighting::CoOccurrence, 10);
+        assert_eq!(users.get_edges(0), (&[1][..], &[2.][..]));
+        assert_eq!(users.get_edges(2), (&[1][..], &[1.][..]));
+    }
+
+    #[test]
+    fn test_jaccard_cosine() {
+        let graph = build_graph();
+        let items = project_bipartite(&graph, |n| n >= 3, ProjectionWeighting::Jaccard, 10);
+        // Items 3 and 4 have identical users, item 5 shares one of three
+        assert_eq!(items.get_edges(3).1, &[1., 1. / 3.]);
+
+        let items = project_bipartite(&graph, |n| n >= 3, ProjectionWeighting::Cosine, 10);
+        assert!((items.get_edges(3).1[0] - 1.).abs() < 1e-6);
+        assert!((items.get_edges(3).1[1] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_max_neighbors() {
+        let graph = build_graph();
+        let items = project_bipartite(&graph, |n| n >= 3, ProjectionWeighting::CoOccurrence, 1);
+        assert_eq!(items.get_edges(3), (&[4][..], &[2.][..]));
+        // Ties are broken by NodeID
+        assert_eq!(items.get_edges(5), (&[3][..], &[1.][..]));
+    }
+}
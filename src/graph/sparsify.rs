@@ -0,0 +1,187 @@
+//! Caps the out degree of hub nodes.  A handful of nodes with hundreds of thousands of edges
+//! dominate positive sampling and random walks, and make every per node scan slow.  Sparsifying
+//! keeps at most a fixed number of edges per node, either the heaviest ones or a weight
+//! proportional sample, and leaves every other node untouched.
+use hashbrown::HashMap;
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+use rayon::prelude::*;
+use float_ord::FloatOrd;
+
+use super::{Graph,CSR,NodeID};
+use super::typed::{TypedGraph,NodeType};
+use crate::algos::utils::weighted_reservoir_sample_keyed;
+
+/// How edges are chosen when a node exceeds its cap
+#[derive(Clone,Copy,Debug)]
+pub enum EdgeSelection {
+    /// Keeps the highest weight edges, breaking ties by position
+    TopWeight,
+
+    /// Samples edges without replacement, proportionally to their weights.  Edges with zero
+    /// weight are never kept.
+    WeightedSample
+}
+
+/// Degree caps for sparsification
+#[derive(Clone,Debug)]
+pub struct DegreeCap {
+    /// Max out degree of nodes without a type specific cap
+    pub max_degree: usize,
+
+    /// Caps for specific node types, overriding max_degree.  Only used with typed graphs.
+    pub type_caps: HashMap<NodeType, usize>,
+
+    /// How to choose the edges to keep
+    pub selection: EdgeSelection,
+
+    /// Random seed for weighted sampling.  Each node uses its own stream, so results don't
+    /// depend on the number of threads.
+    pub seed: u64
+}
+
+impl DegreeCap {
+    pub fn new(max_degree: usize, selection: EdgeSelection, seed: u64) -> Self {
+        DegreeCap { max_degree, type_caps: HashMap::new(), selection, seed }
+    }
+
+    // Positions, within the node's edges, of the edges to keep, in their original order
+    fn select(&self, node_id: NodeID, weights: &[f32], cap: usize) -> Vec<usize> {
+        let mut kept: Vec<usize> = match self.selection {
+            EdgeSelection::TopWeight => {
+                let mut idxs: Vec<_> = (0..weights.len()).collect();
+                idxs.sort_by_key(|idx| (FloatOrd(-weights[*idx]), *idx));
+                idxs.truncate(cap);
+                idxs
+            },
+            EdgeSelection::WeightedSample => {
+                let mut rng = XorShiftRng::seed_from_u64(self.seed + node_id as u64);
+                let it = weights.iter().enumerate().map(|(idx, w)| (idx, *w));
+                weighted_reservoir_sample_keyed(it, cap, &mut rng).into_iter()
+                    .map(|(idx, _, _)| idx)
+                    .collect()
+            }
+        };
+        kept.sort_unstable();
+        kept
+    }
+}
+
+/// Caps the out degree of every node at `cap.max_degree`, returning the new graph.  Weights are
+/// copied over as is, so this should be done on raw weights, before converting to a CumCSR.
+pub fn sparsify(graph: &CSR, cap: &DegreeCap) -> CSR {
+    let (csr, _) = sparsify_with(graph, |_| cap.max_degree, cap);
+    csr
+}
+
+/// Same as `sparsify`, but each node's cap comes from its type, falling back to max_degree.  Edge
+/// types of the kept edges are carried over.
+pub fn sparsify_typed(graph: &TypedGraph<CSR>, cap: &DegreeCap) -> TypedGraph<CSR> {
+    let node_cap = |node_id| {
+        cap.type_caps.get(&graph.node_type(node_id)).cloned().unwrap_or(cap.max_degree)
+    };
+    let (csr, kept) = sparsify_with(graph.inner(), node_cap, cap);
+
+    let mut edge_types = Vec::with_capacity(csr.edges());
+    for node_id in 0..graph.len() {
+        let types = graph.get_edge_types(node_id);
+        match &kept[node_id] {
+            Some(idxs) => edge_types.extend(idxs.iter().map(|idx| types[*idx])),
+            None => edge_types.extend_from_slice(types)
+        }
+    }
+
+    TypedGraph::new(csr, graph.node_types().to_vec(), edge_types)
+        .expect("Types are aligned with the sparsified graph")
+}
+
+// Returns the sparsified graph along with the kept edge positions of each capped node
+fn sparsify_with(
+    graph: &CSR,
+    node_cap: impl Fn(NodeID) -> usize + Sync,
+    cap: &DegreeCap
+) -> (CSR, Vec<Option<Vec<usize>>>) {
+    let kept: Vec<_> = (0..graph.len()).into_par_iter().map(|node_id| {
+        let node_cap = node_cap(node_id);
+        if graph.degree(node_id) <= node_cap {
+            None
+        } else {
+            Some(cap.select(node_id, graph.get_edges(node_id).1, node_cap))
+        }
+    }).collect();
+
+    let mut rows = Vec::with_capacity(graph.len() + 1);
+    let mut columns = Vec::new();
+    let mut weights = Vec::new();
+    rows.push(0);
+    for node_id in 0..graph.len() {
+        let (edges, ws) = graph.get_edges(node_id);
+        match &kept[node_id] {
+            Some(idxs) => idxs.iter().for_each(|idx| {
+                columns.push(edges[*idx]);
+                weights.push(ws[*idx]);
+            }),
+            None => {
+                columns.extend_from_slice(edges);
+                weights.extend_from_slice(ws);
+            }
+        }
+        rows.push(columns.len());
+    }
+
+    (CSR { rows, columns, weights }, kept)
+}
+
+#[cfg(test)]
+mod sparsify_tests {
+    use super::*;
+    use crate::graph::typed::EdgeType;
+
+    // Node 0 is a hub with edges to 1 through 5, weighted by the target's id
+    fn build_hub() -> CSR {
+        let mut edges: Vec<_> = (1..6).map(|t_n| (0, t_n, t_n as f32)).collect();
+        edges.push((1, 0, 1.));
+        edges.push((2, 0, 1.));
+        CSR::construct_from_edges(edges, false)
+    }
+
+    #[test]
+    fn test_top_weight() {
+        let graph = build_hub();
+        let sparse = sparsify(&graph, &DegreeCap::new(2, EdgeSelection::TopWeight, 2023));
+        assert_eq!(sparse.len(), graph.len());
+        assert_eq!(sparse.get_edges(0), (&[4, 5][..], &[4., 5.][..]));
+        assert_eq!(sparse.get_edges(1), graph.get_edges(1));
+        assert_eq!(sparse.edges(), 4);
+    }
+
+    #[test]
+    fn test_weighted_sample() {
+        let graph = build_hub();
+        let cap = DegreeCap::new(3, EdgeSelection::WeightedSample, 2023);
+        let sparse = sparsify(&graph, &cap);
+        let (edges, weights) = sparse.get_edges(0);
+        assert_eq!(edges.len(), 3);
+        assert!(edges.windows(2).all(|w| w[0] < w[1]));
+        edges.iter().zip(weights.iter()).for_each(|(t_n, w)| assert_eq!(*t_n as f32, *w));
+
+        // Reproducible given the seed
+        assert_eq!(sparsify(&graph, &cap).get_edges(0), (edges, weights));
+    }
+
+    #[test]
+    fn test_typed() {
+        let graph = build_hub();
+        let node_types = vec![1, 0, 0, 0, 0, 0];
+        let edge_types = (0..graph.edges()).map(|idx| idx as EdgeType).collect();
+        let typed = TypedGraph::new(graph, node_types, edge_types).unwrap();
+
+        let mut cap = DegreeCap::new(10, EdgeSelection::TopWeight, 2023);
+        cap.type_caps.insert(1, 1);
+        let sparse = sparsify_typed(&typed, &cap);
+        assert_eq!(sparse.get_edges(0).0, &[5]);
+        assert_eq!(sparse.get_edge_types(0), &[4]);
+        assert_eq!(sparse.get_edge_types(1), typed.get_edge_types(1));
+        assert_eq!(sparse.edges(), 3);
+    }
+}
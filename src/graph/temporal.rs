@@ -0,0 +1,265 @@
+//! Temporal graphs, where every edge carries the time at which it was observed.  Within each node,
+//! edges are sorted by timestamp rather than by destination, so the edges falling within any time
+//! range are a contiguous slice.  This lets us build windowed views, ie. "the last 90 days", which
+//! implement `Graph` by slicing into the original arrays rather than rebuilding the CSR.
+//!
+//! Windows carry raw weights; use `TemporalWindow::cdf` to renormalize them into the CDF format
+//! expected by RWR, PPREmbed, and the weighted samplers.
+use rayon::prelude::*;
+
+use super::{Graph,NodeID,OptCDFGraph};
+
+/// Edge timestamps, typically seconds since the epoch
+pub type Timestamp = u64;
+
+/// CSR with a timestamp for every edge.  Edges for each node are ordered by time.
+#[derive(Clone)]
+pub struct TemporalCSR {
+    rows: Vec<NodeID>,
+    columns: Vec<NodeID>,
+    weights: Vec<f32>,
+    timestamps: Vec<Timestamp>
+}
+
+impl TemporalCSR {
+    /// Constructs the graph from (from, to, weight, timestamp) edges.  Repeated edges are kept as
+    /// separate interactions.
+    pub fn construct_from_edges(mut edges: Vec<(NodeID, NodeID, f32, Timestamp)>) -> Self {
+        edges.par_sort_by_key(|(f_n, t_n, _, ts)| (*f_n, *ts, *t_n));
+
+        let max_node = edges.iter().map(|(from_node, to_node, _, _)| {
+            *from_node.max(to_node)
+        }).max().unwrap_or(0);
+
+        let mut rows = vec![0; max_node + 2];
+        edges.iter().for_each(|(from_node, _to_node, _w, _ts)| {
+            rows[*from_node + 1] += 1;
+        });
+
+        let mut offset = 0;
+        rows.iter_mut().skip(1).for_each(|count| {
+            offset += *count;
+            *count = offset;
+        });
+
+        let mut columns = Vec::with_capacity(edges.len());
+        let mut weights = Vec::with_capacity(edges.len());
+        let mut timestamps = Vec::with_capacity(edges.len());
+        edges.into_iter().for_each(|(_f_n, t_n, w, ts)| {
+            columns.push(t_n);
+            weights.push(w);
+            timestamps.push(ts);
+        });
+
+        TemporalCSR { rows, columns, weights, timestamps }
+    }
+
+    /// Estimated bytes allocated for the graph
+    pub fn memory_bytes(&self) -> usize {
+        (self.rows.len() + self.columns.len()) * std::mem::size_of::<NodeID>()
+            + self.weights.len() * std::mem::size_of::<f32>()
+            + self.timestamps.len() * std::mem::size_of::<Timestamp>()
+    }
+
+    /// Get edges, weights, and timestamps for a node, ordered by time
+    pub fn get_temporal_edges(&self, idx: NodeID) -> (&[NodeID], &[f32], &[Timestamp]) {
+        let (start, stop) = self.get_edge_range(idx);
+        (&self.columns[start..stop], &self.weights[start..stop], &self.timestamps[start..stop])
+    }
+
+    /// Earliest and latest timestamps in the graph, or None if there are no edges
+    pub fn time_range(&self) -> Option<(Timestamp, Timestamp)> {
+        let min = self.timestamps.par_iter().min()?;
+        let max = self.timestamps.par_iter().max()?;
+        Some((*min, *max))
+    }
+
+    /// Creates a view of the graph containing only edges with timestamps in [start, end).
+    pub fn window(&self, start: Timestamp, end: Timestamp) -> TemporalWindow<'_> {
+        let starts: Vec<_> = (0..self.len()).into_par_iter().map(|node_id| {
+            let (e_start, e_stop) = self.get_edge_range(node_id);
+            let ts = &self.timestamps[e_start..e_stop];
+            let lo = ts.partition_point(|t| *t < start);
+            let hi = ts.partition_point(|t| *t < end).max(lo);
+            (e_start + lo, hi - lo)
+        }).collect();
+
+        // Offsets give the window its own dense edge numbering, which is what get_edge_range
+        // reports so that per edge arrays sized by `edges()` line up.
+        let mut offsets = Vec::with_capacity(starts.len() + 1);
+        offsets.push(0);
+        starts.iter().for_each(|(_, degree)| offsets.push(offsets[offsets.len() - 1] + degree));
+
+        TemporalWindow {
+            graph: self,
+            starts: starts.into_iter().map(|(s, _)| s).collect(),
+            offsets,
+            start,
+            end
+        }
+    }
+}
+
+impl Graph for TemporalCSR {
+    // Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.rows.len() - 1
+    }
+
+    // Get number of edges in graph
+    fn edges(&self) -> usize {
+        self.weights.len()
+    }
+
+    // Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize {
+        self.rows[idx+1] - self.rows[idx]
+    }
+
+    // Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        let (start, stop) = self.get_edge_range(idx);
+        (&self.columns[start..stop], &self.weights[start..stop])
+    }
+
+    // get edge range
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        (self.rows[idx], self.rows[idx+1])
+    }
+}
+
+/// View over the edges of a TemporalCSR within [start, end).  Every node in the original graph is
+/// present, though it may have no edges within the window.
+pub struct TemporalWindow<'a> {
+    graph: &'a TemporalCSR,
+
+    /// Offset of each node's first edge in the window within the original graph
+    starts: Vec<usize>,
+
+    /// Dense edge offsets within the window
+    offsets: Vec<usize>,
+
+    start: Timestamp,
+    end: Timestamp
+}
+
+impl <'a> TemporalWindow<'a> {
+    /// Bounds of the window
+    pub fn bounds(&self) -> (Timestamp, Timestamp) {
+        (self.start, self.end)
+    }
+
+    /// Get edges, weights, and timestamps within the window for a node, ordered by time
+    pub fn get_temporal_edges(&self, idx: NodeID) -> (&'a [NodeID], &'a [f32], &'a [Timestamp]) {
+        let start = self.starts[idx];
+        let stop = start + self.degree(idx);
+        let g = self.graph;
+        (&g.columns[start..stop], &g.weights[start..stop], &g.timestamps[start..stop])
+    }
+
+    /// Renormalizes the weights within the window into CDF format, allowing the window to be used
+    /// with the CDF based samplers.  Only the weights are copied.
+    pub fn cdf(&self) -> OptCDFGraph<'_, TemporalWindow<'a>> {
+        let mut weights = Vec::with_capacity(self.edges());
+        (0..self.len()).for_each(|node_id| weights.extend_from_slice(self.get_edges(node_id).1));
+        OptCDFGraph::new(self, weights)
+    }
+}
+
+impl <'a> Graph for TemporalWindow<'a> {
+    // Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.graph.len()
+    }
+
+    // Get number of edges within the window
+    fn edges(&self) -> usize {
+        self.offsets[self.offsets.len() - 1]
+    }
+
+    // Get degree of node within the window
+    fn degree(&self, idx: NodeID) -> usize {
+        self.offsets[idx+1] - self.offsets[idx]
+    }
+
+    // Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        let (edges, weights, _) = self.get_temporal_edges(idx);
+        (edges, weights)
+    }
+
+    // Edge range within the window's dense edge numbering
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        (self.offsets[idx], self.offsets[idx+1])
+    }
+}
+
+#[cfg(test)]
+mod temporal_tests {
+    use super::*;
+    use rand::prelude::*;
+    use rand_xorshift::XorShiftRng;
+    use crate::graph::CDFGraph;
+    use crate::sampler::{Sampler,Weighted};
+
+    fn build_graph() -> TemporalCSR {
+        let edges = vec![
+            (0, 1, 1., 30),
+            (0, 2, 1., 10),
+            (0, 3, 2., 20),
+            (1, 2, 1., 40),
+            (2, 0, 1., 50),
+            (2, 3, 1., 15),
+            (3, 0, 4., 5),
+        ];
+        TemporalCSR::construct_from_edges(edges)
+    }
+
+    #[test]
+    fn test_construct() {
+        let graph = build_graph();
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.edges(), 7);
+        assert_eq!(graph.get_temporal_edges(0), (&[2, 3, 1][..], &[1., 2., 1.][..], &[10, 20, 30][..]));
+        assert_eq!(graph.time_range(), Some((5, 50)));
+    }
+
+    #[test]
+    fn test_window() {
+        let graph = build_graph();
+        let window = graph.window(15, 40);
+        assert_eq!(window.len(), 4);
+        assert_eq!(window.edges(), 3);
+        assert_eq!(window.get_edges(0), (&[3, 1][..], &[2., 1.][..]));
+        assert_eq!(window.degree(1), 0);
+        assert_eq!(window.get_edges(2).0, &[3]);
+        assert_eq!(window.degree(3), 0);
+        assert_eq!(window.get_edge_range(2), (2, 3));
+
+        // Empty and inverted windows have no edges
+        assert_eq!(graph.window(100, 200).edges(), 0);
+        assert_eq!(graph.window(40, 15).edges(), 0);
+        assert_eq!(graph.window(0, Timestamp::MAX).edges(), graph.edges());
+    }
+
+    #[test]
+    fn test_window_cdf() {
+        let graph = build_graph();
+        let window = graph.window(15, 40);
+        let cdf = window.cdf();
+        fn is_cdf<G: CDFGraph>(_g: &G) {}
+        is_cdf(&cdf);
+
+        let (edges, weights) = cdf.get_edges(0);
+        assert_eq!(edges, &[3, 1]);
+        assert!((weights[0] - 2. / 3.).abs() < 1e-6);
+        assert_eq!(weights[1], 1.);
+
+        // Samples never leave the window
+        let mut rng = XorShiftRng::seed_from_u64(2023);
+        for _ in 0..100 {
+            assert_ne!(Weighted.sample(&cdf, 0, &mut rng), Some(2));
+            assert_eq!(Weighted.sample(&cdf, 3, &mut rng), None);
+        }
+    }
+}
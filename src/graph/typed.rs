@@ -0,0 +1,118 @@
+//! Heterogeneous graphs, where every node and edge carries a type id, ie. users, gigs, sellers,
+//! and search queries.  Types are stored alongside an existing graph rather than in a new
+//! representation, so a typed CumCSR can still be used anywhere a CDFGraph is expected.
+use super::{Graph,CDFGraph,NodeID};
+
+/// Node type id
+pub type NodeType = u16;
+
+/// Edge type id
+pub type EdgeType = u16;
+
+/// Graph with a type for each node and edge.  Edge types are aligned with the underlying graph's
+/// edge ranges, so they must be assigned after any reordering of the edges.
+pub struct TypedGraph<G> {
+    graph: G,
+    node_types: Vec<NodeType>,
+    edge_types: Vec<EdgeType>
+}
+
+impl <G: Graph> TypedGraph<G> {
+    pub fn new(
+        graph: G,
+        node_types: Vec<NodeType>,
+        edge_types: Vec<EdgeType>
+    ) -> Result<Self, &'static str> {
+        if node_types.len() != graph.len() {
+            return Err("Number of node types doesn't match the number of nodes!")
+        }
+        if edge_types.len() != graph.edges() {
+            return Err("Number of edge types doesn't match the number of edges!")
+        }
+        Ok(TypedGraph { graph, node_types, edge_types })
+    }
+
+    /// Creates a typed graph with only node types; all edges are given type 0.
+    pub fn from_node_types(graph: G, node_types: Vec<NodeType>) -> Result<Self, &'static str> {
+        let edge_types = vec![0; graph.edges()];
+        TypedGraph::new(graph, node_types, edge_types)
+    }
+
+    /// Underlying untyped graph
+    pub fn inner(&self) -> &G {
+        &self.graph
+    }
+
+    /// Type of the given node
+    pub fn node_type(&self, idx: NodeID) -> NodeType {
+        self.node_types[idx]
+    }
+
+    /// Types of all nodes
+    pub fn node_types(&self) -> &[NodeType] {
+        &self.node_types
+    }
+
+    /// Types of the outbound edges for a node, aligned with `get_edges`
+    pub fn get_edge_types(&self, idx: NodeID) -> &[EdgeType] {
+        let (start, stop) = self.graph.get_edge_range(idx);
+        &self.edge_types[start..stop]
+    }
+
+    /// All nodes of the given type
+    pub fn nodes_of_type(&self, node_type: NodeType) -> Vec<NodeID> {
+        self.node_types.iter().enumerate()
+            .filter(|(_, nt)| **nt == node_type)
+            .map(|(node_id, _)| node_id)
+            .collect()
+    }
+}
+
+impl <G: Graph> Graph for TypedGraph<G> {
+    // Get number of nodes in graph
+    fn len(&self) -> usize {
+        self.graph.len()
+    }
+
+    // Get number of edges in graph
+    fn edges(&self) -> usize {
+        self.graph.edges()
+    }
+
+    // Get degree of node in graph
+    fn degree(&self, idx: NodeID) -> usize {
+        self.graph.degree(idx)
+    }
+
+    // Get edges and corresponding weights
+    fn get_edges(&self, idx: NodeID) -> (&[NodeID], &[f32]) {
+        self.graph.get_edges(idx)
+    }
+
+    // get edge range
+    fn get_edge_range(&self, idx: NodeID) -> (usize, usize) {
+        self.graph.get_edge_range(idx)
+    }
+}
+
+impl <G: CDFGraph> CDFGraph for TypedGraph<G> {}
+
+#[cfg(test)]
+mod typed_tests {
+    use super::*;
+    use crate::graph::CSR;
+
+    #[test]
+    fn test_typed_graph() {
+        let edges = vec![(0, 2, 1.), (1, 2, 1.), (2, 0, 1.), (2, 1, 1.)];
+        let graph = CSR::construct_from_edges(edges, false);
+        assert!(TypedGraph::new(graph.clone(), vec![0, 0], vec![0; 4]).is_err());
+        assert!(TypedGraph::new(graph.clone(), vec![0, 0, 1], vec![0; 3]).is_err());
+
+        let typed = TypedGraph::new(graph, vec![0, 0, 1], vec![0, 0, 1, 2]).unwrap();
+        assert_eq!(typed.node_type(2), 1);
+        assert_eq!(typed.get_edge_types(2), &[1, 2]);
+        assert_eq!(typed.nodes_of_type(0), vec![0, 1]);
+        assert_eq!(typed.get_edges(2).0, &[0, 1]);
+    }
+}
@@ -10,7 +10,9 @@ use flate2::Compression;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use itertools::Itertools;
-use pyo3::exceptions::{PyValueError,PyKeyError,PyIOError};
+#[cfg(feature = "python")]
+use pyo3::exceptions::{PyValueError,PyIOError};
+#[cfg(feature = "python")]
 use pyo3::prelude::{PyResult,PyErr};
 use rayon::prelude::*;
 use ryu::Buffer;
@@ -18,8 +20,11 @@ use ryu::Buffer;
 use crate::vocab::Vocab;
 use crate::embeddings::EmbeddingStore;
 use crate::distance::Distance;
-use crate::graph::CumCSR;
-use crate::{CSR,EdgeType};
+use crate::error::GraphLibError;
+#[cfg(feature = "python")]
+use crate::graph::{CSR,CumCSR};
+#[cfg(feature = "python")]
+use crate::python::EdgeType;
 
 /// Streaming writer for NodeEmbeddings.  Since Embeddings are often gigantic, creating them adhoc
 /// then streaming them to disk is beneficial.
@@ -76,7 +81,7 @@ impl <'a> EmbeddingWriter<'a> {
     }
 }
 
-struct RecordReader {
+pub struct RecordReader {
     chunk_size: usize,
     skip: usize
 }
@@ -141,18 +146,15 @@ impl EmbeddingReader {
         filter_type: &Option<&HashSet<String>>, 
         chunk_size: Option<usize>,
         skip_rows: Option<usize>
-    ) -> PyResult<(Vocab, EmbeddingStore)> {
+    ) -> Result<(Vocab, EmbeddingStore), GraphLibError> {
 
         // Convert filter types to something more efficient
         let ft_strs = filter_type
             .map(|hs| hs.iter().map(|s| s.as_str()).collect());
         let ft_refs = ft_strs.as_ref();
 
-        let num_embeddings = count_lines(path, ft_refs)
-            .map_err(|e| PyIOError::new_err(format!("{:?}", e)))?;
-
-        let reader = open_file_for_reading(path)
-            .map_err(|e| PyIOError::new_err(format!("{:?}", e)))?;
+        let num_embeddings = count_lines(path, ft_refs)?;
+        let reader = open_file_for_reading(path)?;
 
         let mut vocab = Vocab::new();
         
@@ -171,7 +173,7 @@ impl EmbeddingReader {
                }
 
                Some(line_to_embedding(&line)
-                    .ok_or_else(|| PyValueError::new_err(format!("Error parsing line: {}", line))))
+                    .ok_or_else(|| GraphLibError::InvalidInput(format!("Error parsing line: {}", line))))
             },
             |_, record| {
                 let (node_type, node_name, emb) = record?;
@@ -182,11 +184,11 @@ impl EmbeddingReader {
 
                 let node_id = vocab.get_or_insert(&node_type, &node_name);
                 if node_id < i {
-                    return Err(PyKeyError::new_err(format!("found duplicate node at index {}! node_id: {}, node_type: '{}', node_name: '{}'", i, node_id, node_type, node_name)));
+                    return Err(GraphLibError::InvalidInput(format!("found duplicate node at index {}! node_id: {}, node_type: '{}', node_name: '{}'", i, node_id, node_type, node_name)));
                 }
                 let m = es.get_embedding_mut(node_id);
                 if m.len() != emb.len() {
-                    return Err(PyValueError::new_err("Embeddings have different sizes!"));
+                    return Err(GraphLibError::DimensionMismatch { expected: m.len(), found: emb.len() });
                 }
                 m.copy_from_slice(&emb);
                 i += 1;
@@ -267,8 +269,10 @@ fn line_to_embedding(line: &String) -> Option<(String,String,Vec<f32>)> {
     emb.ok().map(|e| (node_type.to_string(), name.to_string(), e))
 }
 
+#[cfg(feature = "python")]
 pub struct GraphReader; 
 
+#[cfg(feature = "python")]
 impl GraphReader {
     
     pub fn load(
@@ -316,7 +320,7 @@ impl GraphReader {
                 Ok::<(), PyErr>(())
             })?;
 
-        let csr = CSR::construct_from_edges(edges, deduplicate);
+        let csr = CSR::try_construct_from_edges(edges, deduplicate)?;
 
         Ok((vocab, CumCSR::convert(csr)))
     }
@@ -45,6 +45,19 @@ impl CLProgressBar {
         }
     }
 
+    pub fn set_length(&self, len: u64) {
+        if let Some(pb) = &self.pb {
+            pb.set_length(len);
+        }
+    }
+
+    /// Prints a line above the bar, if it's enabled
+    pub fn println(&self, msg: impl AsRef<str>) {
+        if let Some(pb) = &self.pb {
+            pb.println(msg);
+        }
+    }
+
     pub fn inc(&self, amt: u64) {
         if let Some(pb) = &self.pb {
             pb.inc(amt);
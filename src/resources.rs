@@ -0,0 +1,137 @@
+//! Lightweight resource accounting.  Tracks wall clock time per phase, the peak resident set size
+//! of the process, and the size of key data structures, so capacity planning doesn't require
+//! watching htop during runs.
+use std::fs::read_to_string;
+use std::sync::Mutex;
+use std::time::{Duration,Instant};
+
+/// Snapshot of the resources used by a job.
+#[derive(Clone,Debug,Default)]
+pub struct ResourceReport {
+    /// Wall clock time spent in each phase, in the order phases were first entered
+    pub phases: Vec<(String, Duration)>,
+
+    /// Total wall clock time since the tracker was created
+    pub total: Duration,
+
+    /// Peak resident set size of the process, if the platform exposes it
+    pub peak_rss_bytes: Option<u64>,
+
+    /// Estimated bytes allocated for key data structures
+    pub structures: Vec<(String, usize)>
+}
+
+impl ResourceReport {
+    /// Time spent within a phase, if it was recorded
+    pub fn phase(&self, name: &str) -> Option<Duration> {
+        self.phases.iter().find(|(n, _)| n == name).map(|(_, d)| *d)
+    }
+
+    /// Total bytes across all recorded structures
+    pub fn structure_bytes(&self) -> usize {
+        self.structures.iter().map(|(_, b)| b).sum()
+    }
+}
+
+/// Collects timings and structure sizes.  Threadsafe, so it can be shared across parallel jobs.
+pub struct ResourceTracker {
+    start: Instant,
+    phases: Mutex<Vec<(String, Duration)>>,
+    structures: Mutex<Vec<(String, usize)>>
+}
+
+impl Default for ResourceTracker {
+    fn default() -> Self {
+        ResourceTracker::new()
+    }
+}
+
+impl ResourceTracker {
+    pub fn new() -> Self {
+        ResourceTracker {
+            start: Instant::now(),
+            phases: Mutex::new(Vec::new()),
+            structures: Mutex::new(Vec::new())
+        }
+    }
+
+    /// Runs `f`, adding the time taken to the named phase.  Phases entered multiple times, such
+    /// as once per pass, accumulate.  With the `tracing` feature, `f` runs within a span named
+    /// after the phase.
+    pub fn phase<R>(&self, name: &str, f: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::info_span!("phase", name).entered();
+
+        let start = Instant::now();
+        let ret = f();
+        self.add_time(name, start.elapsed());
+        ret
+    }
+
+    /// Adds time to the named phase.
+    pub fn add_time(&self, name: &str, elapsed: Duration) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(phase = name, elapsed_ms = elapsed.as_secs_f64() * 1e3, "phase timing");
+
+        let mut phases = self.phases.lock().expect("Mutex poisoned!");
+        if let Some(entry) = phases.iter_mut().find(|(n, _)| n == name) {
+            entry.1 += elapsed;
+        } else {
+            phases.push((name.to_string(), elapsed));
+        }
+    }
+
+    /// Records the size of a data structure, replacing any previous record of the same name.
+    pub fn record_bytes(&self, name: &str, bytes: usize) {
+        let mut structures = self.structures.lock().expect("Mutex poisoned!");
+        if let Some(entry) = structures.iter_mut().find(|(n, _)| n == name) {
+            entry.1 = bytes;
+        } else {
+            structures.push((name.to_string(), bytes));
+        }
+    }
+
+    pub fn report(&self) -> ResourceReport {
+        ResourceReport {
+            phases: self.phases.lock().expect("Mutex poisoned!").clone(),
+            total: self.start.elapsed(),
+            peak_rss_bytes: peak_rss_bytes(),
+            structures: self.structures.lock().expect("Mutex poisoned!").clone()
+        }
+    }
+}
+
+/// Reads the peak resident set size of the process.  Only supported on Linux; returns None
+/// elsewhere.
+pub fn peak_rss_bytes() -> Option<u64> {
+    let status = read_to_string("/proc/self/status").ok()?;
+    status.lines()
+        .find(|line| line.starts_with("VmHWM:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+}
+
+#[cfg(test)]
+mod resources_tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker() {
+        let tracker = ResourceTracker::new();
+        let x = tracker.phase("a", || 1 + 1);
+        assert_eq!(x, 2);
+        tracker.phase("b", || ());
+        tracker.add_time("a", Duration::from_secs(1));
+        tracker.record_bytes("embeddings", 100);
+        tracker.record_bytes("graph", 50);
+        tracker.record_bytes("embeddings", 200);
+
+        let report = tracker.report();
+        assert_eq!(report.phases.len(), 2);
+        assert!(report.phase("a").unwrap() >= Duration::from_secs(1));
+        assert!(report.phase("c").is_none());
+        assert_eq!(report.structure_bytes(), 250);
+        assert!(report.total >= report.phase("b").unwrap());
+    }
+}
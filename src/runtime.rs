@@ -0,0 +1,103 @@
+//! Controls where the heavy algorithms run.  By default everything uses the global rayon pool,
+//! which is a poor neighbor when the crate is embedded in a server that is also handling
+//! requests.  A Runtime lets callers pin training and index builds to a dedicated pool, and
+//! optionally provide a memory budget which caps batch sizes.
+use std::sync::Arc;
+
+use rayon::{ThreadPool,ThreadPoolBuilder};
+
+/// Thread pool and memory budget for a job
+#[derive(Clone,Default)]
+pub struct Runtime {
+    /// Pool to run on.  If None, uses the global rayon pool.
+    pool: Option<Arc<ThreadPool>>,
+
+    /// Hint for the maximum bytes a job should keep resident for transient, per batch state.
+    /// Structures which scale with the graph, such as embeddings, are not included.
+    pub max_memory_bytes: Option<usize>
+}
+
+impl Runtime {
+    /// Runs on the global rayon pool without a memory budget
+    pub fn global() -> Self {
+        Runtime::default()
+    }
+
+    /// Creates a dedicated pool with the given number of threads
+    pub fn with_threads(num_threads: usize) -> Result<Self, &'static str> {
+        if num_threads == 0 {
+            return Err("Runtime needs at least one thread!")
+        }
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .build()
+            .map_err(|_| "Unable to build thread pool!")?;
+
+        Ok(Runtime::from_pool(Arc::new(pool)))
+    }
+
+    /// Runs on an existing pool, allowing it to be shared with the rest of the application
+    pub fn from_pool(pool: Arc<ThreadPool>) -> Self {
+        Runtime { pool: Some(pool), max_memory_bytes: None }
+    }
+
+    /// Sets the memory budget
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory_bytes = Some(bytes);
+        self
+    }
+
+    /// Number of threads parallel work will be spread across
+    pub fn num_threads(&self) -> usize {
+        match &self.pool {
+            Some(pool) => pool.current_num_threads(),
+            None => rayon::current_num_threads()
+        }
+    }
+
+    /// Runs the closure within the pool.  All rayon parallelism started from within the closure,
+    /// including nested parallel iterators, uses the same pool.
+    pub fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(f),
+            None => f()
+        }
+    }
+
+    /// Caps the batch size so that `item_bytes` per batch item fits within the memory budget.
+    /// Always allows at least one item per batch.
+    pub fn cap_batch_size(&self, batch_size: usize, item_bytes: usize) -> usize {
+        match self.max_memory_bytes {
+            Some(budget) if item_bytes > 0 => batch_size.min((budget / item_bytes).max(1)),
+            _ => batch_size
+        }
+    }
+}
+
+#[cfg(test)]
+mod runtime_tests {
+    use super::*;
+
+    #[test]
+    fn test_install() {
+        assert!(Runtime::with_threads(0).is_err());
+
+        let runtime = Runtime::with_threads(2).unwrap();
+        assert_eq!(runtime.num_threads(), 2);
+        assert_eq!(runtime.install(|| rayon::current_num_threads()), 2);
+        assert_eq!(Runtime::global().install(|| 1 + 1), 2);
+    }
+
+    #[test]
+    fn test_cap_batch_size() {
+        let runtime = Runtime::global();
+        assert_eq!(runtime.cap_batch_size(128, 1024), 128);
+
+        let runtime = runtime.with_max_memory(10 * 1024);
+        assert_eq!(runtime.cap_batch_size(128, 1024), 10);
+        assert_eq!(runtime.cap_batch_size(8, 1024), 8);
+        assert_eq!(runtime.cap_batch_size(128, 1 << 20), 1);
+        assert_eq!(runtime.cap_batch_size(128, 0), 128);
+    }
+}
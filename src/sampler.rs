@@ -4,6 +4,7 @@ use rand_distr::{Distribution,Uniform};
 use float_ord::FloatOrd;
 
 use crate::graph::{CDFGraph,Graph,NodeID,CSR,NormalizedCSR};
+use crate::algos::utils::EdgeAliasTable;
 
 pub trait Sampler<G>: Send + Sync {
     fn sample<R: Rng>(&self, g: &G, node_id: NodeID, rng: &mut R) -> Option<NodeID>;
@@ -23,6 +24,17 @@ impl <S: CDFGraph> Sampler<S> for Weighted {
     }
 }
 
+/// Weighted sampling through precomputed alias tables, which is O(1) per step rather than a
+/// binary search over the CDF.  Worth it for long or many walks over the same graph.  The tables
+/// must be built from the graph being sampled.
+pub struct Alias<'a>(pub &'a EdgeAliasTable);
+
+impl <'a, S: CDFGraph> Sampler<S> for Alias<'a> {
+    fn sample<R: Rng>(&self, g: &S, node_id: NodeID, rng: &mut R) -> Option<NodeID> {
+        self.0.sample(g, node_id, rng)
+    }
+}
+
 #[inline]
 pub fn weighted_sample_cdf<R: Rng>(weights: &[f32], rng: &mut R) -> usize {
     let p: f32 = rng.gen();
@@ -1,6 +1,7 @@
 use lasso::{Rodeo,Spur};
 use hashbrown::HashMap;
 use crate::graph::NodeID;
+use crate::error::GraphLibError;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize,Ordering};
 
@@ -18,6 +19,12 @@ pub struct Vocab {
     id_to_node_type: Vec<Arc<String>>,
 }
 
+impl Default for Vocab {
+    fn default() -> Self {
+        Vocab::new()
+    }
+}
+
 impl Vocab {
     pub fn new() -> Self {
         let vocab_id = VOCAB_ID.fetch_add(1, Ordering::SeqCst);
@@ -53,6 +60,21 @@ impl Vocab {
         })
     }
 
+    /// Id of the node type for a node, dense in order of first insertion
+    pub fn get_node_type_id(&self, node: NodeID) -> Option<usize> {
+        self.node_id_to_node.get(node).map(|(nt_id, _name)| *nt_id)
+    }
+
+    /// Looks up the id for a node type
+    pub fn get_node_type_id_by_name(&self, node_type: &str) -> Option<usize> {
+        self.id_to_node_type.iter().position(|nt| nt.as_str() == node_type)
+    }
+
+    /// All node types, indexed by node type id
+    pub fn node_types(&self) -> &[Arc<String>] {
+        &self.id_to_node_type
+    }
+
     fn get_or_insert_node_type(&mut self, node_type: Arc<String>) -> usize {
         if let Some(nt_id) = self.node_type_to_id.get(&node_type) {
             *nt_id
@@ -90,10 +112,34 @@ impl Vocab {
         })
     }
 
+    /// Looks up a qualified "node_type:name" string, such as "gig:12345".  The node type ends at
+    /// the first ':', so names may contain colons.
+    pub fn get_qualified(&self, qualified: &str) -> Option<NodeID> {
+        let (node_type, name) = split_qualified(qualified)?;
+        self.get_node_id(node_type, name)
+    }
+
+    /// Same as get_qualified, but fails if the node is unknown or not below `limit`, such as the
+    /// number of embeddings the vocab keys
+    pub fn lookup_qualified(&self, qualified: &str, limit: usize) -> Result<NodeID, GraphLibError> {
+        self.get_qualified(qualified)
+            .filter(|node_id| *node_id < limit)
+            .ok_or_else(|| GraphLibError::InvalidInput(format!("Unknown node: {}", qualified)))
+    }
+
+    /// Qualified "node_type:name" string for a node
+    pub fn qualified_name(&self, node: NodeID) -> Option<String> {
+        self.get_name(node).map(|(node_type, name)| format!("{}:{}", node_type, name))
+    }
+
     pub fn len(&self) -> usize {
         self.node_id_to_node.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.node_id_to_node.is_empty()
+    }
+
     pub fn translate_node(&self, other: &Vocab, other_node_id: NodeID) -> Option<NodeID> {
         if self.is_identical(other) {
             Some(other_node_id)
@@ -118,6 +164,11 @@ impl Vocab {
 
 }
 
+/// Splits a qualified "node_type:name" string at the first ':'
+pub(crate) fn split_qualified(qualified: &str) -> Option<(&str, &str)> {
+    qualified.split_once(':')
+}
+
 #[cfg(test)]
 mod vocab_tests {
     use super::*;
@@ -149,4 +200,22 @@ mod vocab_tests {
         });
     }
 
+    #[test]
+    fn test_qualified() {
+        let mut vocab = Vocab::new();
+        let gig = vocab.get_or_insert("gig", "12345");
+        let url = vocab.get_or_insert("url", "https://fiverr.com");
+
+        assert_eq!(vocab.get_qualified("gig:12345"), Some(gig));
+        assert_eq!(vocab.get_qualified("url:https://fiverr.com"), Some(url));
+        assert_eq!(vocab.qualified_name(url), Some("url:https://fiverr.com".to_string()));
+        assert_eq!(vocab.get_qualified("gig12345"), None);
+        assert_eq!(vocab.get_qualified("user:12345"), None);
+        assert_eq!(vocab.qualified_name(2), None);
+
+        assert_eq!(vocab.lookup_qualified("gig:12345", 2).unwrap(), gig);
+        assert!(vocab.lookup_qualified("url:https://fiverr.com", 1).is_err());
+        assert!(vocab.lookup_qualified("user:12345", 2).is_err());
+    }
+
 }
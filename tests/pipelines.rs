@@ -0,0 +1,380 @@
+//! End-to-end pipelines which act as executable contracts for the training loop and the indexes.
+//! These are slower than the unit tests, so they only run with the `integration-tests` feature:
+//!
+//! ```text
+//! cargo test --release --features integration-tests --test pipelines
+//! ```
+use rand::prelude::*;
+use rand_xorshift::XorShiftRng;
+
+use graph_library::graph::{Graph,CumCSR,NodeID};
+use graph_library::graph::generators::stochastic_block_model;
+use graph_library::algos::ann::Ann;
+use graph_library::algos::ep::EmbeddingPropagation;
+use graph_library::algos::ep::loss::Loss;
+use graph_library::algos::ep::model::{AveragedFeatureModel,Model,NodeCounts,embed_features};
+use graph_library::algos::ep::model::{collect_embeddings_from_node,collect_embeddings_from_features};
+use graph_library::algos::ep::model::sample_neighbors;
+use graph_library::algos::ep::model::simple_grad::*;
+use graph_library::algos::pprembed::{PPREmbed,WeightTransform};
+use graph_library::algos::utils::{Sample,RngKind};
+use graph_library::distance::Distance;
+use graph_library::embeddings::{EmbeddingStore,Entity};
+use graph_library::feature_store::FeatureStore;
+use graph_library::runtime::Runtime;
+
+const SEED: u64 = 20222022;
+
+/// Builds an undirected stochastic block model graph, returning the graph and block of each node.
+fn build_sbm(blocks: usize, block_size: usize, p_in: f32, p_out: f32) -> (CumCSR, Vec<usize>) {
+    let sizes = vec![block_size; blocks];
+    let probs: Vec<_> = (0..blocks).map(|a| {
+        (0..blocks).map(|b| if a == b { p_in } else { p_out }).collect()
+    }).collect();
+    let (graph, membership) = stochastic_block_model(&sizes, &probs, SEED)
+        .expect("Valid block model");
+    (CumCSR::convert(graph), membership)
+}
+
+fn build_features(n: usize) -> FeatureStore {
+    let mut features = FeatureStore::new(n);
+    features.fill_missing_nodes();
+    features
+}
+
+fn mean_distances(es: &EmbeddingStore, ids: &[NodeID], membership: &[usize]) -> (f32, f32) {
+    let (mut intra, mut inter) = ((0f32, 0usize), (0f32, 0usize));
+    for u in 0..ids.len() {
+        for v in (u+1)..ids.len() {
+            let d = es.compute_distance(&Entity::Node(ids[u]), &Entity::Node(ids[v]));
+            let acc = if membership[u] == membership[v] { &mut intra } else { &mut inter };
+            acc.0 += d;
+            acc.1 += 1;
+        }
+    }
+    (intra.0 / intra.1 as f32, inter.0 / inter.1 as f32)
+}
+
+#[test]
+fn test_ep_sbm_cluster_separation() {
+    let (graph, membership) = build_sbm(2, 40, 0.3, 0.005);
+    let features = build_features(graph.len());
+    let model = AveragedFeatureModel::new(Sample::All, None, false, false);
+    let ep = EmbeddingPropagation {
+        loss: Loss::StarSpace(0.5, 5),
+        batch_size: 16,
+        d_model: 16,
+        valid_pct: 0.0,
+        passes: 50,
+        seed: SEED,
+        ..EmbeddingPropagation::default()
+    };
+
+    let feature_embeddings = ep.learn(&graph, &features, None, &model).unwrap();
+
+    // Each node has exactly one feature, its own id, so the node embedding is the feature embedding
+    let ids: Vec<_> = (0..graph.len()).map(|node_id| features.get_features(node_id)[0]).collect();
+    let (intra, inter) = mean_distances(&feature_embeddings, &ids, &membership);
+    assert!(intra < inter, "intra: {}, inter: {}", intra, inter);
+}
+
+#[test]
+fn test_ann_recall() {
+    let mut rng = XorShiftRng::seed_from_u64(SEED);
+    let mut es = EmbeddingStore::new(5000, 16, Distance::Cosine);
+    for node_id in 0..es.len() {
+        let emb: Vec<f32> = (0..es.dims()).map(|_| rng.gen::<f32>() - 0.5).collect();
+        es.set_embedding(node_id, &emb);
+    }
+
+    let mut ann = Ann::new();
+    ann.fit(&es, 20, 50, None, None, None, SEED).unwrap();
+
+    let k = 10;
+    let queries = 100;
+    let mut found = 0;
+    for _ in 0..queries {
+        let query: Vec<f32> = (0..es.dims()).map(|_| rng.gen::<f32>() - 0.5).collect();
+        let expected = es.nearest_neighbor(&Entity::Embedding(&query), k, |_| true);
+        let predicted = ann.predict(&es, &query, k, None).unwrap();
+        found += expected.iter()
+            .filter(|e| predicted.iter().any(|p| p.1 == e.1))
+            .count();
+    }
+
+    let recall = found as f32 / (k * queries) as f32;
+    assert!(recall > 0.9, "recall: {}", recall);
+}
+
+#[test]
+fn test_pprembed_leaf_codes() {
+    let (graph, _membership) = build_sbm(4, 25, 0.3, 0.01);
+    let features = build_features(graph.len());
+    let embedder = PPREmbed {
+        num_walks: 1000,
+        steps: Sample::Probability(0.15),
+        beta: 0.8,
+        dims: 64,
+        eps: 1e-5,
+        seed: SEED,
+        rng_kind: RngKind::XorShift,
+        push_eps: Some(1e-5),
+        hash_count: 3,
+        weight_transform: WeightTransform::Log,
+        l2_normalize: false,
+        deterministic: true
+    };
+
+    // Bit for bit reproducible
+    let embs = embedder.learn(&graph, &features).unwrap();
+    let embs_2 = embedder.learn(&graph, &features).unwrap();
+    for node_id in 0..graph.len() {
+        assert_eq!(embs.get_embedding(node_id), embs_2.get_embedding(node_id));
+    }
+
+    let mut ann = Ann::new();
+    ann.fit(&embs, 5, 10, None, None, None, SEED).unwrap();
+    let mut ann_2 = Ann::new();
+    ann_2.fit(&embs_2, 5, 10, None, None, None, SEED).unwrap();
+
+    for node_id in 0..graph.len() {
+        let emb = embs.get_embedding(node_id);
+
+        // Leaf codes are reproducible from the same embeddings and seed
+        let codes = ann.predict_leaf_indices(emb);
+        assert_eq!(codes.len(), 5);
+        assert_eq!(codes, ann_2.predict_leaf_indices(emb));
+        assert_eq!(ann.predict_leaf_paths(emb).len(), 5);
+
+        // And a node's own embedding leads back to it
+        let nearest = ann.predict(&embs, emb, 1, None).unwrap();
+        assert!(nearest[0].0.abs() < 1e-5);
+    }
+}
+
+#[test]
+fn test_pprembed_transforms() {
+    let (graph, _membership) = build_sbm(2, 20, 0.3, 0.01);
+    let features = build_features(graph.len());
+    let embedder = PPREmbed {
+        num_walks: 1000,
+        steps: Sample::Probability(0.15),
+        beta: 0.8,
+        dims: 32,
+        eps: 1e-5,
+        seed: SEED,
+        rng_kind: RngKind::XorShift,
+        push_eps: Some(1e-5),
+        hash_count: 5,
+        weight_transform: WeightTransform::Sqrt,
+        l2_normalize: true,
+        deterministic: false
+    };
+
+    let embs = embedder.learn(&graph, &features).unwrap();
+    for node_id in 0..graph.len() {
+        let norm: f32 = embs.get_embedding(node_id).iter().map(|v| v * v).sum::<f32>().sqrt();
+        assert!((norm - 1.).abs() < 1e-4);
+    }
+
+    // Without hashes, nothing is written
+    let empty = PPREmbed { hash_count: 0, ..embedder };
+    let embs = empty.learn(&graph, &features).unwrap();
+    assert!((0..graph.len()).all(|node_id| embs.get_embedding(node_id).iter().all(|v| *v == 0.)));
+}
+
+#[test]
+fn test_pprembed_sparse() {
+    let (graph, _membership) = build_sbm(2, 20, 0.3, 0.01);
+    let features = build_features(graph.len());
+    let embedder = PPREmbed {
+        num_walks: 1000,
+        steps: Sample::Probability(0.15),
+        beta: 0.8,
+        dims: 32,
+        eps: 1e-5,
+        seed: SEED,
+        rng_kind: RngKind::XorShift,
+        push_eps: Some(1e-5),
+        hash_count: 3,
+        weight_transform: WeightTransform::Log,
+        l2_normalize: false,
+        deterministic: true
+    };
+
+    let sparse = embedder.learn_sparse(&graph, &features).unwrap();
+    assert_eq!(sparse.num_nodes(), graph.len());
+    for node_id in 0..graph.len() {
+        let row = sparse.get_features(node_id);
+        assert!(row.windows(2).all(|w| w[0].0 < w[1].0));
+        assert!(row.iter().all(|(_, w)| *w > embedder.eps));
+
+        // A node is always part of its own neighborhood
+        let own = features.get_features(node_id)[0];
+        assert!(row.iter().any(|(feat_id, _)| *feat_id == own));
+    }
+
+    let (embs, sparse_2) = embedder.learn_dense_and_sparse(&graph, &features).unwrap();
+    let expected = embedder.learn(&graph, &features).unwrap();
+    assert_eq!(sparse_2.nnz(), sparse.nnz());
+    for node_id in 0..graph.len() {
+        assert_eq!(embs.get_embedding(node_id), expected.get_embedding(node_id));
+    }
+
+    let mut out = Vec::new();
+    sparse.write_to(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap().lines().count(), graph.len());
+}
+
+#[test]
+fn test_pprembed_deterministic() {
+    let (graph, _membership) = build_sbm(2, 20, 0.3, 0.01);
+    let features = build_features(graph.len());
+    let embedder = PPREmbed {
+        num_walks: 500,
+        steps: Sample::Probability(0.15),
+        beta: 0.8,
+        dims: 32,
+        eps: 1e-5,
+        seed: SEED,
+        rng_kind: RngKind::XorShift,
+        push_eps: None,
+        hash_count: 3,
+        weight_transform: WeightTransform::Log,
+        l2_normalize: false,
+        deterministic: true
+    };
+
+    // Bit for bit identical, regardless of the number of threads
+    let embs = embedder.learn(&graph, &features).unwrap();
+    let runtime = Runtime::with_threads(1).unwrap();
+    let embs_2 = embedder.learn_with_runtime(&runtime, &graph, &features).unwrap();
+    for node_id in 0..graph.len() {
+        assert_eq!(embs.get_embedding(node_id), embs_2.get_embedding(node_id));
+    }
+
+    let (embs_3, _sparse) = embedder.learn_dense_and_sparse(&graph, &features).unwrap();
+    for node_id in 0..graph.len() {
+        assert_eq!(embs.get_embedding(node_id), embs_3.get_embedding(node_id));
+    }
+}
+
+#[test]
+fn test_pprembed_invalid() {
+    let (graph, _membership) = build_sbm(2, 10, 0.3, 0.01);
+    let features = build_features(graph.len());
+    let embedder = PPREmbed {
+        num_walks: 10,
+        steps: Sample::Probability(0.15),
+        beta: 0.8,
+        dims: 8,
+        eps: 1e-5,
+        seed: SEED,
+        rng_kind: RngKind::XorShift,
+        push_eps: Some(1e-5),
+        hash_count: 3,
+        weight_transform: WeightTransform::Log,
+        l2_normalize: false,
+        deterministic: false
+    };
+    assert!(embedder.learn(&graph, &features).is_ok());
+
+    for push_eps in [Some(0.), Some(-1.), Some(f32::NAN)] {
+        let invalid = PPREmbed { push_eps, ..embedder.clone() };
+        assert!(invalid.learn(&graph, &features).is_err());
+    }
+    for push_eps in [Some(1e-5), None] {
+        for steps in [Sample::All, Sample::Probability(0.), Sample::Probability(1.5)] {
+            let invalid = PPREmbed { steps, push_eps, ..embedder.clone() };
+            assert!(invalid.learn(&graph, &features).is_err());
+            assert!(invalid.learn_sparse(&graph, &features).is_err());
+        }
+    }
+}
+
+/// Sums feature embeddings rather than averaging them, using only the public model API as a
+/// downstream crate would.
+struct SummedFeatureModel;
+
+fn sum_embeddings(feature_map: &NodeCounts) -> ANode {
+    feature_map.values()
+        .map(|(emb, count)| emb * *count)
+        .collect::<Vec<_>>()
+        .sum_all()
+}
+
+impl Model for SummedFeatureModel {
+    fn construct_node_embedding<R: Rng>(
+        &self,
+        node: NodeID,
+        weight: f32,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode) {
+        self.construct_from_multiple_nodes(
+            std::iter::once((node, weight)), feature_store, feature_embeddings, rng)
+    }
+
+    fn reconstruct_node_embedding<G: Graph, R: Rng>(
+        &self,
+        graph: &G,
+        node: NodeID,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode) {
+        let neighbors = sample_neighbors(graph, node, Some(5), false, false, rng);
+        self.construct_from_multiple_nodes(neighbors, feature_store, feature_embeddings, rng)
+    }
+
+    fn construct_from_multiple_nodes<I: Iterator<Item=(NodeID, f32)>, R: Rng>(
+        &self,
+        nodes: I,
+        feature_store: &FeatureStore,
+        feature_embeddings: &EmbeddingStore,
+        rng: &mut R
+    ) -> (NodeCounts, ANode) {
+        let mut feature_map = NodeCounts::new();
+        for (node, weight) in nodes {
+            collect_embeddings_from_node(node, weight, feature_store, feature_embeddings,
+                                         &mut feature_map, Sample::All, rng);
+        }
+        let emb = sum_embeddings(&feature_map);
+        (feature_map, emb)
+    }
+
+    fn construct_from_features<R: Rng>(
+        &self,
+        features: &[usize],
+        feature_embeddings: &EmbeddingStore,
+        _rng: &mut R
+    ) -> ANode {
+        sum_embeddings(&collect_embeddings_from_features(features, feature_embeddings))
+    }
+}
+
+#[test]
+fn test_custom_model() {
+    let (graph, _) = build_sbm(2, 20, 0.3, 0.01);
+    let features = build_features(graph.len());
+    let ep = EmbeddingPropagation {
+        batch_size: 16,
+        d_model: 8,
+        valid_pct: 0.0,
+        passes: 5,
+        seed: SEED,
+        ..EmbeddingPropagation::default()
+    };
+
+    let model = SummedFeatureModel;
+    let feature_embeddings = ep.learn(&graph, &features, None, &model).unwrap();
+    assert_eq!(feature_embeddings.dims(), 8);
+
+    let emb = embed_features(&[0, 1], &feature_embeddings, &model);
+    let expected: Vec<_> = feature_embeddings.get_embedding(0).iter()
+        .zip(feature_embeddings.get_embedding(1).iter())
+        .map(|(a, b)| a + b)
+        .collect();
+    emb.iter().zip(expected.iter()).for_each(|(e, x)| assert!((e - x).abs() < 1e-5));
+}
@@ -0,0 +1,61 @@
+"""
+Python binding tests.  Build the extension first, then run with pytest:
+
+    maturin develop && pytest tests/python
+"""
+import random
+
+import graph_library as gl
+
+
+def build_graph():
+    builder = gl.GraphBuilder()
+    for clique in (0, 4):
+        for u in range(clique, clique + 4):
+            for v in range(u + 1, clique + 4):
+                builder.add_edge(("node", str(u)), ("node", str(v)), 1.0, gl.EdgeType.Undirected)
+    builder.add_edge(("node", "3"), ("node", "4"), 1.0, gl.EdgeType.Undirected)
+    return builder.build_graph()
+
+
+def build_features(graph):
+    features = gl.FeatureSet.new_from_graph(graph)
+    for node in graph.vocab():
+        features.set_features(node, [("node", node[1]), ("clique", str(int(node[1]) // 4))])
+    return features
+
+
+def snapshot(embeddings):
+    return {node: embeddings.get_embedding(node) for node in embeddings.vocab()}
+
+
+def build_ep():
+    return gl.EmbeddingPropagator(dims=4, passes=2, batch_size=4, valid_pct=0.0, indicator=False)
+
+
+def test_learn_features_leaves_input_unchanged():
+    graph = build_graph()
+    features = build_features(graph)
+    ep = build_ep()
+
+    start = ep.learn_features(graph, features)
+    before = snapshot(start)
+    learned = ep.learn_features(graph, features, feature_embeddings=start)
+
+    assert snapshot(start) == before
+    assert snapshot(learned) != before
+
+
+def test_learn_node_embeddings_leaves_input_unchanged():
+    graph = build_graph()
+    ep = build_ep()
+
+    rng = random.Random(2023)
+    start = gl.NodeEmbeddings(graph, 4, gl.Distance.Cosine)
+    for node in start.vocab():
+        start.set_embedding(node, [rng.uniform(-1, 1) for _ in range(4)])
+    before = snapshot(start)
+    learned = ep.learn_node_embeddings(graph, node_embeddings=start)
+
+    assert snapshot(start) == before
+    assert snapshot(learned) != before